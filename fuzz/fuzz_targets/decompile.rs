@@ -9,20 +9,20 @@ use wasm_decompile::Module as DecompileModule;
 fuzz_target!(|bytes: Vec<u8>| {
     let mut u = Unstructured::new(&bytes);
     let config = wasm_smith::Config {
-        reference_types_enabled: false,
-        gc_enabled: false,
-        exceptions_enabled: false,
+        reference_types_enabled: true,
+        gc_enabled: true,
+        exceptions_enabled: true,
+        relaxed_simd_enabled: true,
+        simd_enabled: true,
 
-        multi_value_enabled: false,
         bulk_memory_enabled: false,
         memory64_enabled: false,
-        relaxed_simd_enabled: false,
-        simd_enabled: false,
         tail_call_enabled: false,
         threads_enabled: false,
         wide_arithmetic_enabled: false,
         custom_page_sizes_enabled: false,
 
+        multi_value_enabled: true,
         saturating_float_to_int_enabled: true,
         sign_extension_ops_enabled: true,
         min_funcs: 1,
@@ -33,6 +33,20 @@ fuzz_target!(|bytes: Vec<u8>| {
     let wasm_bytes = module.to_bytes();
     // println!("{}", wasmprinter::print_bytes(&wasm_bytes).unwrap());
     let module = DecompileModule::from_buffer(&wasm_bytes).unwrap();
+    // Operators the decompiler doesn't support yet are expected, but any
+    // other internal error is a bug.
+    for error in module.errors() {
+        let error = error.to_string();
+        if !error.contains("unsupported operator") {
+            panic!("{}", error);
+        }
+    }
     let mut output = Vec::new();
     module.write(&mut output).unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    let text = wasm_decompile::text::parse(&output).unwrap();
+    if let Err(err) = text.check() {
+        panic!("{}\n{}", err, output);
+    }
 });
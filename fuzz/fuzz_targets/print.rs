@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use wasm_decompile::ArbitraryFunc;
+
+fuzz_target!(|func: ArbitraryFunc| {
+    func.write(std::io::sink()).unwrap();
+    func.write_graphviz(std::io::sink()).unwrap();
+});
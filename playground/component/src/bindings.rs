@@ -220,22 +220,70 @@ pub unsafe fn _export_print_decompiled_cabi<T: Guest>(
     match result1 {
         Ok(e) => {
             *ptr2.add(0).cast::<u8>() = (0i32) as u8;
-            let vec3 = (e.into_bytes()).into_boxed_slice();
-            let ptr3 = vec3.as_ptr().cast::<u8>();
-            let len3 = vec3.len();
-            ::core::mem::forget(vec3);
-            *ptr2.add(2 * ::core::mem::size_of::<*const u8>()).cast::<usize>() = len3;
-            *ptr2.add(::core::mem::size_of::<*const u8>()).cast::<*mut u8>() = ptr3
-                .cast_mut();
+            let vec4 = e;
+            let len4 = vec4.len();
+            let layout4 = _rt::alloc::Layout::from_size_align_unchecked(
+                vec4.len() * (3 * ::core::mem::size_of::<*const u8>()),
+                ::core::mem::size_of::<*const u8>(),
+            );
+            let result4 = if layout4.size() != 0 {
+                let ptr = _rt::alloc::alloc(layout4).cast::<u8>();
+                if ptr.is_null() {
+                    _rt::alloc::handle_alloc_error(layout4);
+                }
+                ptr
+            } else {
+                ::core::ptr::null_mut()
+            };
+            for (i, e) in vec4.into_iter().enumerate() {
+                let base = result4.add(i * (3 * ::core::mem::size_of::<*const u8>()));
+                {
+                    match e {
+                        PrintPart::Str(e) => {
+                            *base.add(0).cast::<u8>() = (0i32) as u8;
+                            let vec3 = (e.into_bytes()).into_boxed_slice();
+                            let ptr3 = vec3.as_ptr().cast::<u8>();
+                            let len3 = vec3.len();
+                            ::core::mem::forget(vec3);
+                            *base
+                                .add(2 * ::core::mem::size_of::<*const u8>())
+                                .cast::<usize>() = len3;
+                            *base
+                                .add(::core::mem::size_of::<*const u8>())
+                                .cast::<*mut u8>() = ptr3.cast_mut();
+                        }
+                        PrintPart::Name => {
+                            *base.add(0).cast::<u8>() = (1i32) as u8;
+                        }
+                        PrintPart::Literal => {
+                            *base.add(0).cast::<u8>() = (2i32) as u8;
+                        }
+                        PrintPart::Keyword => {
+                            *base.add(0).cast::<u8>() = (3i32) as u8;
+                        }
+                        PrintPart::Type => {
+                            *base.add(0).cast::<u8>() = (4i32) as u8;
+                        }
+                        PrintPart::Comment => {
+                            *base.add(0).cast::<u8>() = (5i32) as u8;
+                        }
+                        PrintPart::Reset => {
+                            *base.add(0).cast::<u8>() = (6i32) as u8;
+                        }
+                    }
+                }
+            }
+            *ptr2.add(2 * ::core::mem::size_of::<*const u8>()).cast::<usize>() = len4;
+            *ptr2.add(::core::mem::size_of::<*const u8>()).cast::<*mut u8>() = result4;
         }
         Err(e) => {
             *ptr2.add(0).cast::<u8>() = (1i32) as u8;
-            let vec4 = (e.into_bytes()).into_boxed_slice();
-            let ptr4 = vec4.as_ptr().cast::<u8>();
-            let len4 = vec4.len();
-            ::core::mem::forget(vec4);
-            *ptr2.add(2 * ::core::mem::size_of::<*const u8>()).cast::<usize>() = len4;
-            *ptr2.add(::core::mem::size_of::<*const u8>()).cast::<*mut u8>() = ptr4
+            let vec5 = (e.into_bytes()).into_boxed_slice();
+            let ptr5 = vec5.as_ptr().cast::<u8>();
+            let len5 = vec5.len();
+            ::core::mem::forget(vec5);
+            *ptr2.add(2 * ::core::mem::size_of::<*const u8>()).cast::<usize>() = len5;
+            *ptr2.add(::core::mem::size_of::<*const u8>()).cast::<*mut u8>() = ptr5
                 .cast_mut();
         }
     };
@@ -249,12 +297,41 @@ pub unsafe fn __post_return_print_decompiled<T: Guest>(arg0: *mut u8) {
         0 => {
             let l1 = *arg0.add(::core::mem::size_of::<*const u8>()).cast::<*mut u8>();
             let l2 = *arg0.add(2 * ::core::mem::size_of::<*const u8>()).cast::<usize>();
-            _rt::cabi_dealloc(l1, l2, 1);
+            let base6 = l1;
+            let len6 = l2;
+            for i in 0..len6 {
+                let base = base6.add(i * (3 * ::core::mem::size_of::<*const u8>()));
+                {
+                    let l3 = i32::from(*base.add(0).cast::<u8>());
+                    match l3 {
+                        0 => {
+                            let l4 = *base
+                                .add(::core::mem::size_of::<*const u8>())
+                                .cast::<*mut u8>();
+                            let l5 = *base
+                                .add(2 * ::core::mem::size_of::<*const u8>())
+                                .cast::<usize>();
+                            _rt::cabi_dealloc(l4, l5, 1);
+                        }
+                        1 => {}
+                        2 => {}
+                        3 => {}
+                        4 => {}
+                        5 => {}
+                        _ => {}
+                    }
+                }
+            }
+            _rt::cabi_dealloc(
+                base6,
+                len6 * (3 * ::core::mem::size_of::<*const u8>()),
+                ::core::mem::size_of::<*const u8>(),
+            );
         }
         _ => {
-            let l3 = *arg0.add(::core::mem::size_of::<*const u8>()).cast::<*mut u8>();
-            let l4 = *arg0.add(2 * ::core::mem::size_of::<*const u8>()).cast::<usize>();
-            _rt::cabi_dealloc(l3, l4, 1);
+            let l7 = *arg0.add(::core::mem::size_of::<*const u8>()).cast::<*mut u8>();
+            let l8 = *arg0.add(2 * ::core::mem::size_of::<*const u8>()).cast::<usize>();
+            _rt::cabi_dealloc(l7, l8, 1);
         }
     }
 }
@@ -264,7 +341,9 @@ pub trait Guest {
         contents: _rt::Vec<u8>,
         skeleton: bool,
     ) -> Result<_rt::Vec<PrintPart>, _rt::String>;
-    fn print_decompiled(contents: _rt::Vec<u8>) -> Result<_rt::String, _rt::String>;
+    fn print_decompiled(
+        contents: _rt::Vec<u8>,
+    ) -> Result<_rt::Vec<PrintPart>, _rt::String>;
 }
 #[doc(hidden)]
 macro_rules! __export_world_wasm_tools_cabi {
@@ -367,16 +446,15 @@ pub(crate) use __export_wasm_tools_impl as export;
 )]
 #[doc(hidden)]
 #[allow(clippy::octal_escapes)]
-pub static __WIT_BINDGEN_COMPONENT_TYPE: [u8; 368] = *b"\
-\0asm\x0d\0\x01\0\0\x19\x16wit-component-encoding\x04\0\x07\xef\x01\x01A\x02\x01\
-A\x0d\x01q\x07\x03str\x01s\0\x04name\0\0\x07literal\0\0\x07keyword\0\0\x04type\0\
+pub static __WIT_BINDGEN_COMPONENT_TYPE: [u8; 362] = *b"\
+\0asm\x0d\0\x01\0\0\x19\x16wit-component-encoding\x04\0\x07\xe9\x01\x01A\x02\x01\
+A\x0c\x01q\x07\x03str\x01s\0\x04name\0\0\x07literal\0\0\x07keyword\0\0\x04type\0\
 \0\x07comment\0\0\x05reset\0\0\x03\0\x0aprint-part\x03\0\0\x01p}\x01j\x01\x02\x01\
 s\x01@\x01\x08contentss\0\x03\x04\0\x05parse\x01\x04\x01p\x01\x01j\x01\x05\x01s\x01\
-@\x02\x08contents\x02\x08skeleton\x7f\0\x06\x04\0\x05print\x01\x07\x01j\x01s\x01\
-s\x01@\x01\x08contents\x02\0\x08\x04\0\x10print-decompiled\x01\x09\x04\0\x1ecomp\
-onent:component/wasm-tools\x04\0\x0b\x10\x01\0\x0awasm-tools\x03\0\0\0G\x09produ\
-cers\x01\x0cprocessed-by\x02\x0dwit-component\x070.227.1\x10wit-bindgen-rust\x06\
-0.41.0";
+@\x02\x08contents\x02\x08skeleton\x7f\0\x06\x04\0\x05print\x01\x07\x01@\x01\x08c\
+ontents\x02\0\x06\x04\0\x10print-decompiled\x01\x08\x04\0\x1ecomponent:component\
+/wasm-tools\x04\0\x0b\x10\x01\0\x0awasm-tools\x03\0\0\0G\x09producers\x01\x0cpro\
+cessed-by\x02\x0dwit-component\x070.227.1\x10wit-bindgen-rust\x060.41.0";
 #[inline(never)]
 #[doc(hidden)]
 pub fn __link_custom_section_describing_imports() {
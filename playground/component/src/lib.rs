@@ -2,9 +2,20 @@
 mod bindings;
 
 use bindings::{Guest, PrintPart};
+use std::time::Duration;
 use wasm_decompile::*;
 use wat;
 
+// Generous for real programs, but they keep a pasted giant module from
+// hanging the tab.
+fn limits() -> Limits {
+    Limits {
+        max_module_bytes: Some(32 * 1024 * 1024),
+        max_funcs: Some(10_000),
+        func_time_budget: Some(Duration::from_millis(250)),
+    }
+}
+
 struct Component;
 
 struct StringWriter(pub Vec<PrintPart>);
@@ -46,6 +57,43 @@ impl wasmprinter::Print for StringWriter {
     }
 }
 
+impl DecompilePrint for StringWriter {
+    fn write_str(&mut self, s: &str) -> std::io::Result<()> {
+        self.0.push(PrintPart::Str(s.to_string()));
+        Ok(())
+    }
+
+    fn start_name(&mut self) -> std::io::Result<()> {
+        self.0.push(PrintPart::Name);
+        Ok(())
+    }
+
+    fn start_literal(&mut self) -> std::io::Result<()> {
+        self.0.push(PrintPart::Literal);
+        Ok(())
+    }
+
+    fn start_keyword(&mut self) -> std::io::Result<()> {
+        self.0.push(PrintPart::Keyword);
+        Ok(())
+    }
+
+    fn start_type(&mut self) -> std::io::Result<()> {
+        self.0.push(PrintPart::Type);
+        Ok(())
+    }
+
+    fn start_comment(&mut self) -> std::io::Result<()> {
+        self.0.push(PrintPart::Comment);
+        Ok(())
+    }
+
+    fn reset_color(&mut self) -> std::io::Result<()> {
+        self.0.push(PrintPart::Reset);
+        Ok(())
+    }
+}
+
 impl Guest for Component {
     fn parse(contents: String) -> Result<Vec<u8>, String> {
         wat::parse_str(contents).map_err(|e| e.to_string())
@@ -61,12 +109,17 @@ impl Guest for Component {
         result.map(|_| writer.0).map_err(|e| e.to_string())
     }
 
-    fn print_decompiled(contents: Vec<u8>) -> Result<String, String> {
+    fn print_decompiled(contents: Vec<u8>) -> Result<Vec<PrintPart>, String> {
         let input_binary = wat::parse_bytes(&contents).map_err(|e| e.to_string())?;
-        let module = Module::from_buffer(&input_binary).map_err(|e| e.to_string())?;
-        let mut out = Vec::new();
-        module.write(&mut out).map_err(|x| x.to_string())?;
-        String::from_utf8(out).map_err(|x| x.to_string())
+        let options = DecodeOptions {
+            limits: limits(),
+            ..Default::default()
+        };
+        let module =
+            Module::from_buffer_with_options(&input_binary, &options).map_err(|e| e.to_string())?;
+        let mut writer = StringWriter(Vec::new());
+        module.write_with(&mut writer).map_err(|x| x.to_string())?;
+        Ok(writer.0)
     }
 }
 
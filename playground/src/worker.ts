@@ -29,8 +29,7 @@ addEventListener('message', ({ data }: { data: MessageToWorker }) => {
     case 'print-decompiled': {
       try {
         let source = printDecompiled(data.bytes);
-        let printParts = [{tag: 'str', val: source}, {tag: 'reset'}];
-        postMessage({ success: true, messageId: data.messageId, source: printParts });
+        postMessage({ success: true, messageId: data.messageId, source });
       } catch (e) {
         postMessage({ success: false, messageId: data.messageId, error: (e as Error).message });
       }
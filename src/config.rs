@@ -0,0 +1,109 @@
+use anyhow::{bail, Context};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgMatches, Command, ValueHint};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use wasm_decompile::Theme;
+
+// The config file used when `--config` doesn't name one, looked for in the
+// current directory and then in each of its parents.
+pub(crate) const FILE_NAME: &str = "wasm-decompile.toml";
+
+// Finds the config file to use when `--config` isn't given, if there is
+// one.
+pub(crate) fn find() -> Option<PathBuf> {
+    let dir = std::env::current_dir().ok()?;
+    (dir.ancestors())
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+// Reads the config file at `path` into the arguments it stands for, to go
+// before the ones on the command line. Each key is the long name of an
+// option, e.g. `width = 100` or `skip-passes = ["dead-code"]`, with
+// `true` for flags. Options already given in `matches`, and those that
+// conflict with one given there, are left out so that the command line
+// wins. Relative paths are relative to the config file.
+pub(crate) fn args(
+    command: &Command,
+    matches: &ArgMatches,
+    path: &Path,
+) -> anyhow::Result<Vec<OsString>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("cannot read `{}`", path.display()))?;
+    let table: toml::Table = text
+        .parse()
+        .with_context(|| format!("invalid config file `{}`", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    let mut args = vec![];
+    for (key, value) in &table {
+        let arg = (command.get_arguments())
+            .find(|arg| arg.get_long() == Some(key.as_str()))
+            .filter(|arg| !matches!(arg.get_id().as_str(), "config" | "no_config"));
+        let Some(arg) = arg else {
+            bail!("`{}` in `{}` is not an option", key, path.display());
+        };
+        // Conflicts are only recorded on one of the two options.
+        let conflicts = |other: &Arg| {
+            let conflicting = |a: &Arg, b: &Arg| {
+                (command.get_arg_conflicts_with(a).iter()).any(|arg| arg.get_id() == b.get_id())
+            };
+            conflicting(arg, other) || conflicting(other, arg)
+        };
+        if given(arg.get_id().as_str())
+            || (command.get_arguments())
+                .any(|other| given(other.get_id().as_str()) && conflicts(other))
+        {
+            continue;
+        }
+
+        let values = match value {
+            toml::Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                toml::Value::Boolean(true) if !arg.get_action().takes_values() => {
+                    args.push(format!("--{}", key).into());
+                    continue;
+                }
+                toml::Value::Boolean(false) if !arg.get_action().takes_values() => continue,
+                toml::Value::String(value) => resolve(key, arg.get_value_hint(), value, dir),
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                toml::Value::Boolean(value) => value.to_string(),
+                _ => bail!(
+                    "`{}` in `{}` must be a string, number, boolean or array of them",
+                    key,
+                    path.display()
+                ),
+            };
+            args.push(format!("--{}={}", key, value).into());
+        }
+    }
+    Ok(args)
+}
+
+// Makes a relative path given to `key` relative to `dir`, the config file's
+// directory, leaving other values as they are.
+fn resolve(key: &str, hint: ValueHint, value: &str, dir: &Path) -> String {
+    let join = |path: &str| {
+        if Path::new(path).is_relative() {
+            dir.join(path).to_string_lossy().into_owned()
+        } else {
+            path.to_string()
+        }
+    };
+    match (key, hint) {
+        (_, ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath) => join(value),
+        ("link", _) => match value.split_once('=') {
+            Some((name, path)) => format!("{}={}", name, join(path)),
+            None => join(value),
+        },
+        ("theme", _) if Theme::builtin(value).is_none() => join(value),
+        _ => value.to_string(),
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::ir::print::val_type_name;
+use crate::ir::*;
+
+/// What a parameter of an exported function appears to be for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamRole {
+    /// Used as a memory address.
+    Pointer,
+    /// An integer right after a pointer, taken to be the length of what it
+    /// points to.
+    Length,
+    Value,
+}
+
+impl fmt::Display for ParamRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParamRole::Pointer => "pointer",
+            ParamRole::Length => "length",
+            ParamRole::Value => "value",
+        })
+    }
+}
+
+/// The interface of an exported function, as seen by its callers.
+#[derive(Debug, Clone)]
+pub struct ExportAbi {
+    pub name: String,
+    pub func_index: FuncIdx,
+    pub param_roles: Vec<ParamRole>,
+    /// The globals read and written by the function or anything it calls,
+    /// by printed name.
+    pub reads: Vec<String>,
+    pub writes: Vec<String>,
+    /// The imported functions it may call, directly or not, as
+    /// `module.field`.
+    pub imports: Vec<String>,
+    // The printed types of the params and results.
+    params: Vec<String>,
+    results: Vec<String>,
+}
+
+impl ExportAbi {
+    // e.g. `(arg0: i32, arg1: i32) -> (i32)`
+    fn signature(&self) -> String {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| format!("arg{}: {}", i, ty))
+            .collect();
+        format!("({}) -> ({})", params.join(", "), self.results.join(", "))
+    }
+}
+
+impl fmt::Display for ExportAbi {
+    /// e.g.
+    ///
+    /// ```text
+    /// "write" (func3): (arg0: i32, arg1: i32) -> (i32)
+    ///   arg0: pointer
+    ///   arg1: length
+    ///   reads __stack_pointer
+    ///   may call env.log
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "\"{}\" (func{}): {}",
+            self.name,
+            self.func_index,
+            self.signature()
+        )?;
+        for (i, role) in self.param_roles.iter().enumerate() {
+            if *role != ParamRole::Value {
+                write!(f, "\n  arg{}: {}", i, role)?;
+            }
+        }
+        if !self.reads.is_empty() {
+            write!(f, "\n  reads {}", self.reads.join(", "))?;
+        }
+        if !self.writes.is_empty() {
+            write!(f, "\n  writes {}", self.writes.join(", "))?;
+        }
+        if !self.imports.is_empty() {
+            write!(f, "\n  may call {}", self.imports.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// How to write the export interface document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbiFormat {
+    Text,
+    Markdown,
+    Json,
+}
+
+impl Module {
+    /// The interface of every exported function, in export order.
+    pub fn export_abis(&self) -> Vec<ExportAbi> {
+        let call_graph = self.call_graph();
+        self.func_exports
+            .iter()
+            .filter_map(|(name, func_index)| {
+                let ty = self.func_signature(*func_index)?.clone();
+                Some(self.export_abi(name, *func_index, ty, &call_graph))
+            })
+            .collect()
+    }
+
+    fn export_abi(
+        &self,
+        name: &str,
+        func_index: FuncIdx,
+        ty: wasm::FuncType,
+        call_graph: &CallGraph,
+    ) -> ExportAbi {
+        let param_roles = match self.func(func_index) {
+            Some(func) => func.param_roles(),
+            None => vec![ParamRole::Value; ty.params().len()],
+        };
+
+        let reachable = call_graph.reachable_from([func_index]);
+        // Sorted by index.
+        let mut counts: BTreeMap<GlobalIdx, (usize, usize)> = BTreeMap::new();
+        let mut imports = BTreeMap::new();
+        for callee in reachable {
+            match self.func(callee) {
+                Some(func) => {
+                    for (global_index, (reads, writes)) in func.global_counts() {
+                        let count = counts.entry(global_index).or_default();
+                        count.0 += reads;
+                        count.1 += writes;
+                    }
+                }
+                None => {
+                    if let Some(import) = self.func_imports.get(callee.as_usize()) {
+                        imports.insert(callee, import.qualified_name());
+                    }
+                }
+            }
+        }
+        let global_name = |global_index: &GlobalIdx| match self.names.globals.get(global_index) {
+            Some(name) => name.clone(),
+            None => format!("globals[{}]", global_index),
+        };
+
+        ExportAbi {
+            name: name.to_string(),
+            func_index,
+            param_roles,
+            reads: counts
+                .iter()
+                .filter(|(_, (reads, _))| *reads > 0)
+                .map(|(global_index, _)| global_name(global_index))
+                .collect(),
+            writes: counts
+                .iter()
+                .filter(|(_, (_, writes))| *writes > 0)
+                .map(|(global_index, _)| global_name(global_index))
+                .collect(),
+            imports: imports.into_values().collect(),
+            params: ty
+                .params()
+                .iter()
+                .map(|ty| val_type_name(*ty, &self.names))
+                .collect(),
+            results: ty
+                .results()
+                .iter()
+                .map(|ty| val_type_name(*ty, &self.names))
+                .collect(),
+        }
+    }
+
+    /// Writes a document describing every exported function: its signature,
+    /// what its params seem to be for, the globals it touches and the
+    /// imports it may call.
+    pub fn write_abi(
+        &self,
+        mut output: impl std::io::Write,
+        format: AbiFormat,
+    ) -> anyhow::Result<()> {
+        let abis = self.export_abis();
+        match format {
+            AbiFormat::Text => {
+                for abi in &abis {
+                    writeln!(output, "{}", abi)?;
+                }
+            }
+            AbiFormat::Markdown => {
+                for (i, abi) in abis.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(output)?;
+                    }
+                    write_abi_markdown(abi, &mut output)?;
+                }
+            }
+            AbiFormat::Json => {
+                let abis: Vec<serde_json::Value> = abis.iter().map(abi_json).collect();
+                writeln!(output, "{}", serde_json::to_string_pretty(&abis)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_abi_markdown(abi: &ExportAbi, output: &mut impl std::io::Write) -> anyhow::Result<()> {
+    writeln!(output, "## `{}`", abi.name)?;
+    writeln!(output)?;
+    writeln!(output, "`func{}{}`", abi.func_index, abi.signature())?;
+    if !abi.param_roles.is_empty() {
+        writeln!(output)?;
+        writeln!(output, "| Param | Type | Role |")?;
+        writeln!(output, "| --- | --- | --- |")?;
+        for (i, (ty, role)) in abi.params.iter().zip(&abi.param_roles).enumerate() {
+            writeln!(output, "| `arg{}` | `{}` | {} |", i, ty, role)?;
+        }
+    }
+    let lists = [
+        ("Reads", &abi.reads),
+        ("Writes", &abi.writes),
+        ("May call", &abi.imports),
+    ];
+    if lists.iter().any(|(_, names)| !names.is_empty()) {
+        writeln!(output)?;
+    }
+    for (label, names) in lists {
+        if !names.is_empty() {
+            let names: Vec<String> = names.iter().map(|name| format!("`{}`", name)).collect();
+            writeln!(output, "- {}: {}", label, names.join(", "))?;
+        }
+    }
+    Ok(())
+}
+
+fn abi_json(abi: &ExportAbi) -> serde_json::Value {
+    let params: Vec<serde_json::Value> = abi
+        .params
+        .iter()
+        .zip(&abi.param_roles)
+        .map(|(ty, role)| {
+            serde_json::json!({
+                "type": ty,
+                "role": role.to_string(),
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "name": abi.name,
+        "index": abi.func_index.as_u32(),
+        "params": params,
+        "results": abi.results,
+        "reads": abi.reads,
+        "writes": abi.writes,
+        "imports": abi.imports,
+    })
+}
+
+impl Func {
+    // The locals used as a memory address, on their own or plus an offset.
+    pub(crate) fn pointer_locals(&self) -> HashSet<LocalIdx> {
+        fn address(pointers: &mut HashSet<LocalIdx>, expr: &Expression) {
+            match expr {
+                Expression::GetLocal(get) => {
+                    pointers.insert(get.local_index);
+                }
+                Expression::Binary(BinaryExpression::I32Add | BinaryExpression::I64Add, a, b) => {
+                    address(pointers, a);
+                    address(pointers, b);
+                }
+                _ => {}
+            }
+        }
+
+        let mut pointers = HashSet::new();
+        self.visit_statements(|stmt| {
+            if let Statement::MemoryStore(store) = stmt {
+                address(&mut pointers, &store.index);
+            }
+        });
+        self.visit_expressions(|expr| {
+            if let Expression::MemoryLoad(load) = expr {
+                address(&mut pointers, &load.index);
+            }
+        });
+        pointers
+    }
+
+    // The pairs of locals passed as consecutive arguments of a call, e.g.
+    // `(p, n)` in `f(p, n, 0)`.
+    fn adjacent_args(&self) -> HashSet<(LocalIdx, LocalIdx)> {
+        fn pairs(args: &[Expression], found: &mut HashSet<(LocalIdx, LocalIdx)>) {
+            for pair in args.windows(2) {
+                if let [Expression::GetLocal(a), Expression::GetLocal(b)] = pair {
+                    found.insert((a.local_index, b.local_index));
+                }
+            }
+        }
+
+        let mut found = HashSet::new();
+        self.visit_statements(|stmt| match stmt {
+            Statement::Call(call) => pairs(call.params(), &mut found),
+            Statement::CallIndirect(call) => pairs(call.params(), &mut found),
+            Statement::CallRef(call) => pairs(call.params(), &mut found),
+            _ => {}
+        });
+        self.visit_expressions(|expr| match expr {
+            Expression::Call(call) => pairs(call.params(), &mut found),
+            Expression::CallIndirect(call) => pairs(call.params(), &mut found),
+            Expression::CallRef(call) => pairs(call.params(), &mut found),
+            _ => {}
+        });
+        found
+    }
+
+    // A param is a pointer if it's used as a memory address. An integer
+    // param right after a pointer is its length if it's used as one: it
+    // bounds a loop, either counting down itself or as `n` in `i <_u n`, or
+    // it's passed along right after the pointer, as in `f(p, n)`.
+    pub(crate) fn param_roles(&self) -> Vec<ParamRole> {
+        let pointers = self.pointer_locals();
+        let counters = self.counter_locals();
+        let bounds = self.bound_locals(&counters);
+        let adjacent_args = self.adjacent_args();
+        let params = self.ty.params();
+        let mut roles: Vec<ParamRole> = Vec::with_capacity(params.len());
+        for (i, ty) in params.iter().enumerate() {
+            let local_index = LocalIdx::from(i as u32);
+            let used_as_length = |pointer: LocalIdx| {
+                counters.contains(&local_index)
+                    || bounds.contains(&local_index)
+                    || adjacent_args.contains(&(pointer, local_index))
+            };
+            let role = if pointers.contains(&local_index) {
+                ParamRole::Pointer
+            } else if roles.last() == Some(&ParamRole::Pointer)
+                && matches!(ty, wasm::ValType::I32 | wasm::ValType::I64)
+                && used_as_length(LocalIdx::from(i as u32 - 1))
+            {
+                ParamRole::Length
+            } else {
+                ParamRole::Value
+            };
+            roles.push(role);
+        }
+        roles
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashSet;
+
+use crate::ir::*;
+
+/// How a function can reach another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallKind {
+    /// A `call` of the function.
+    Direct,
+    /// A `call_indirect` or `call_ref` that may reach the function, because
+    /// it's in an element segment and has the type the call expects.
+    Indirect,
+    /// A `ref.func` of the function, which whoever gets the reference may
+    /// call.
+    Reference,
+}
+
+/// An edge of the call graph, from the function making the call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Call {
+    pub func_index: FuncIdx,
+    pub kind: CallKind,
+}
+
+/// Which functions each defined function may call. Imported functions
+/// call nothing, as far as the module knows.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    callees: HashMap<FuncIdx, Vec<Call>>,
+    callers: HashMap<FuncIdx, Vec<Call>>,
+}
+
+impl CallGraph {
+    /// The functions `func_index` may call, each once, in the order they
+    /// were found. A function called both directly and indirectly is only
+    /// listed as called directly.
+    pub fn callees(&self, func_index: FuncIdx) -> &[Call] {
+        self.callees
+            .get(&func_index)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The functions that may call `func_index`, with `Call::func_index`
+    /// the caller.
+    pub fn callers(&self, func_index: FuncIdx) -> &[Call] {
+        self.callers
+            .get(&func_index)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The functions, imported or defined, that can be called starting from
+    /// `roots`, including the roots.
+    pub fn reachable_from(&self, roots: impl IntoIterator<Item = FuncIdx>) -> HashSet<FuncIdx> {
+        let mut reachable = HashSet::new();
+        let mut worklist: Vec<FuncIdx> = roots.into_iter().collect();
+        while let Some(func_index) = worklist.pop() {
+            if reachable.insert(func_index) {
+                worklist.extend(self.callees(func_index).iter().map(|call| call.func_index));
+            }
+        }
+        reachable
+    }
+
+    /// The functions reachable from `roots`, as by `reachable_from`, in an
+    /// order where each comes before the functions it calls, unless they
+    /// call each other back. The first root comes first, and the functions
+    /// a function calls follow it in the order it calls them, where they
+    /// can.
+    pub fn topological_order(&self, roots: impl IntoIterator<Item = FuncIdx>) -> Vec<FuncIdx> {
+        let roots: Vec<FuncIdx> = roots.into_iter().collect();
+        let mut visited = HashSet::new();
+        let mut postorder = vec![];
+        // Walked last to first, so that reversing the postorder puts them
+        // first to last.
+        for root in roots.into_iter().rev() {
+            if !visited.insert(root) {
+                continue;
+            }
+            // The functions on the path from the root, each with how many
+            // of its callees have been walked.
+            let mut path = vec![(root, 0)];
+            while let Some(&(func_index, walked)) = path.last() {
+                match self.callees(func_index).iter().rev().nth(walked) {
+                    Some(call) => {
+                        path.last_mut().unwrap().1 += 1;
+                        if visited.insert(call.func_index) {
+                            path.push((call.func_index, 0));
+                        }
+                    }
+                    None => {
+                        postorder.push(func_index);
+                        path.pop();
+                    }
+                }
+            }
+        }
+        postorder.reverse();
+        postorder
+    }
+
+    /// Whether calling `from` may lead to a call of `to`, through any
+    /// number of calls.
+    pub fn reaches(&self, from: FuncIdx, to: FuncIdx) -> bool {
+        self.reachable_from([from]).iter().any(|func_index| {
+            self.callees(*func_index)
+                .iter()
+                .any(|call| call.func_index == to)
+        })
+    }
+
+    /// Whether `func_index` can end up calling itself.
+    pub fn is_recursive(&self, func_index: FuncIdx) -> bool {
+        self.reaches(func_index, func_index)
+    }
+
+    fn add(&mut self, caller: FuncIdx, callee: FuncIdx, kind: CallKind) {
+        let callees = self.callees.entry(caller).or_default();
+        match callees.iter_mut().find(|call| call.func_index == callee) {
+            Some(call) => {
+                if kind == CallKind::Direct {
+                    call.kind = kind;
+                    let callers = self.callers.get_mut(&callee).unwrap();
+                    let call = callers
+                        .iter_mut()
+                        .find(|call| call.func_index == caller)
+                        .unwrap();
+                    call.kind = kind;
+                }
+            }
+            None => {
+                callees.push(Call {
+                    func_index: callee,
+                    kind,
+                });
+                self.callers.entry(callee).or_default().push(Call {
+                    func_index: caller,
+                    kind,
+                });
+            }
+        }
+    }
+}
+
+impl Module {
+    /// The module's call graph. An indirect call is assumed to reach every
+    /// function in an element segment whose type it expects, or any of them
+    /// at all if the type it expects can have subtypes.
+    pub fn call_graph(&self) -> CallGraph {
+        let mut targets_by_type: HashMap<TypeIdx, Vec<FuncIdx>> = HashMap::new();
+        let mut indirect_targets = |type_index: TypeIdx| -> Vec<FuncIdx> {
+            targets_by_type
+                .entry(type_index)
+                .or_insert_with(|| self.indirect_targets(type_index))
+                .clone()
+        };
+
+        let mut graph = CallGraph::default();
+        for func in &self.funcs {
+            for callee in func.callees() {
+                match callee {
+                    Callee::Func(func_index, kind) => graph.add(func.index, func_index, kind),
+                    Callee::Indirect(type_index) => {
+                        for func_index in indirect_targets(type_index) {
+                            graph.add(func.index, func_index, CallKind::Indirect);
+                        }
+                    }
+                }
+            }
+        }
+        graph
+    }
+
+    // The functions an indirect call expecting `type_index` may reach, in
+    // order: those in an element segment with that type, or all of them if
+    // the type can have subtypes.
+    pub(crate) fn indirect_targets(&self, type_index: TypeIdx) -> Vec<FuncIdx> {
+        let sub_type = (self.rec_groups.iter())
+            .flat_map(|rec_group| rec_group.types())
+            .nth(type_index.as_usize());
+        let mut targets: Vec<FuncIdx> = self
+            .elem_funcs
+            .iter()
+            .copied()
+            .filter(|func_index| match sub_type {
+                Some(sub_type) if sub_type.is_final => {
+                    let wasm::CompositeInnerType::Func(ty) = &sub_type.composite_type.inner else {
+                        return false;
+                    };
+                    self.func_signature(*func_index) == Some(ty)
+                }
+                _ => true,
+            })
+            .collect();
+        targets.sort();
+        targets.dedup();
+        targets
+    }
+}
+
+enum Callee {
+    Func(FuncIdx, CallKind),
+    // An indirect call expecting a function of this type.
+    Indirect(TypeIdx),
+}
+
+impl Func {
+    // What this function calls or takes a reference to.
+    fn callees(&self) -> Vec<Callee> {
+        struct Callees(Vec<Callee>);
+        impl Visitor for Callees {
+            fn pre_statement(&mut self, stmt: &Statement) {
+                match stmt {
+                    Statement::Call(call) => {
+                        self.0.push(Callee::Func(call.func_index, CallKind::Direct))
+                    }
+                    Statement::CallIndirect(call) => {
+                        self.0.push(Callee::Indirect(call.func_type_index))
+                    }
+                    Statement::CallRef(call) => self.0.push(Callee::Indirect(call.type_index)),
+                    _ => {}
+                }
+            }
+
+            fn pre_expression(&mut self, expr: &Expression) {
+                match expr {
+                    Expression::Call(call) => {
+                        self.0.push(Callee::Func(call.func_index, CallKind::Direct))
+                    }
+                    Expression::RefFunc { func_index } => {
+                        self.0.push(Callee::Func(*func_index, CallKind::Reference))
+                    }
+                    Expression::CallIndirect(call) => {
+                        self.0.push(Callee::Indirect(call.func_type_index))
+                    }
+                    Expression::CallRef(call) => self.0.push(Callee::Indirect(call.type_index)),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut callees = Callees(vec![]);
+        self.walk(&mut callees);
+        callees.0
+    }
+}
@@ -0,0 +1,38 @@
+use crate::ir::*;
+
+/// Whether a binary is a component rather than a core module.
+pub fn is_component(buffer: &[u8]) -> bool {
+    wasm::Parser::is_component(buffer)
+}
+
+/// The core modules embedded in a component, in the order they appear,
+/// including those in nested components.
+pub fn core_modules(buffer: &[u8]) -> anyhow::Result<Vec<&[u8]>> {
+    if !is_component(buffer) {
+        bail!("not a component");
+    }
+    let mut modules = vec![];
+    for payload in wasm::Parser::new(0).parse_all(buffer) {
+        if let wasm::Payload::ModuleSection {
+            unchecked_range, ..
+        } = payload?
+        {
+            let Some(module) = buffer.get(unchecked_range) else {
+                bail!("core module extends past the end of the component");
+            };
+            modules.push(module);
+        }
+    }
+    Ok(modules)
+}
+
+impl Module {
+    /// Decodes every core module embedded in a component, in the order
+    /// they appear.
+    pub fn from_component(buffer: &[u8], options: &DecodeOptions) -> anyhow::Result<Vec<Module>> {
+        core_modules(buffer)?
+            .into_iter()
+            .map(|module| Module::from_buffer_with_options(module, options))
+            .collect()
+    }
+}
@@ -0,0 +1,114 @@
+use crate::ir::passes::negate;
+use crate::ir::*;
+
+impl Func {
+    // Puts conditions in the form that reads best:
+    //
+    // - `eqz(a < b)` becomes `a >= b`, and `eqz(eqz(x))` tested as a
+    //   condition becomes `x`
+    // - `!a && !b` becomes `!(a || b)`, and `!(a && b)` with one side that
+    //   can be flipped becomes `!a || b >= c`, whichever has fewer `!`s
+    // - `if (c) {} else { B }` becomes `if (!c) { B }`
+    pub(crate) fn normalize_conditions(&mut self) {
+        for block in self.blocks.values_mut() {
+            normalize_statements(&mut block.statements);
+            match &mut block.terminator {
+                Terminator::BrIf(condition, _, _, values) => {
+                    normalize_in_place(condition, true);
+                    for value in values {
+                        normalize_in_place(value, false);
+                    }
+                }
+                terminator => {
+                    for expr in terminator.expressions_mut() {
+                        normalize_in_place(expr, false);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn normalize_statements(statements: &mut [Statement]) {
+    for stmt in statements {
+        match &mut *stmt {
+            Statement::If(stmt) => {
+                normalize_in_place(&mut stmt.condition, true);
+                if stmt.true_statements.iter().all(Statement::is_location)
+                    && !stmt.false_statements.iter().all(Statement::is_location)
+                {
+                    let condition = std::mem::replace(&mut *stmt.condition, placeholder());
+                    *stmt.condition = negate(condition);
+                    std::mem::swap(&mut stmt.true_statements, &mut stmt.false_statements);
+                }
+            }
+            Statement::Loop(stmt) => normalize_in_place(&mut stmt.condition, true),
+            stmt => {
+                for expr in stmt.expressions_mut() {
+                    normalize_in_place(expr, false);
+                }
+            }
+        }
+        for body in stmt.bodies_mut() {
+            normalize_statements(body);
+        }
+    }
+}
+
+fn placeholder() -> Expression {
+    Expression::I32Const { value: 0 }
+}
+
+fn normalize_in_place(expr: &mut Expression, is_condition: bool) {
+    let normalized = normalize(std::mem::replace(expr, placeholder()), is_condition);
+    *expr = normalized;
+}
+
+// `expr` normalized. Only whether a condition is zero matters, so more can
+// be done to one: as a value `eqz(eqz(x))` is 0 or 1, but as a condition
+// it's the same as `x`.
+fn normalize(expr: Expression, is_condition: bool) -> Expression {
+    match expr {
+        Expression::Unary(UnaryExpression::I32Eqz, value) => {
+            let value = normalize(*value, true);
+            if !is_condition && matches!(value, Expression::Unary(UnaryExpression::I32Eqz, _)) {
+                return Expression::Unary(UnaryExpression::I32Eqz, Box::new(value));
+            }
+            negate(value)
+        }
+        // Either side of `&&` or `||` is a condition, and the whole is 0 or
+        // 1 either way.
+        Expression::Logical(op, lhs, rhs) => {
+            let lhs = normalize(*lhs, true);
+            let rhs = normalize(*rhs, true);
+            match (lhs, rhs) {
+                (
+                    Expression::Unary(UnaryExpression::I32Eqz, lhs),
+                    Expression::Unary(UnaryExpression::I32Eqz, rhs),
+                ) => {
+                    let flipped = match op {
+                        LogicalExpression::And => LogicalExpression::Or,
+                        LogicalExpression::Or => LogicalExpression::And,
+                    };
+                    Expression::Unary(
+                        UnaryExpression::I32Eqz,
+                        Box::new(Expression::Logical(flipped, lhs, rhs)),
+                    )
+                }
+                (lhs, rhs) => Expression::Logical(op, Box::new(lhs), Box::new(rhs)),
+            }
+        }
+        Expression::Select(mut select) => {
+            normalize_in_place(&mut select.condition, true);
+            normalize_in_place(&mut select.on_true, is_condition);
+            normalize_in_place(&mut select.on_false, is_condition);
+            Expression::Select(select)
+        }
+        mut expr => {
+            for operand in expr.operands_mut() {
+                normalize_in_place(operand, false);
+            }
+            expr
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::ir::*;
+
+/// Where an active data segment is placed in the first memory, and what
+/// it holds.
+#[derive(Debug, Clone)]
+pub(crate) struct DataRange {
+    index: u32,
+    start: u64,
+    bytes: Arc<[u8]>,
+}
+
+impl DataRange {
+    // The range of an active segment of the first memory whose offset is a
+    // constant. Segments placed relative to an imported base, as in
+    // position-independent code, have no known address.
+    pub(crate) fn new(index: u32, data: &wasm::Data) -> anyhow::Result<Option<DataRange>> {
+        let wasm::DataKind::Active {
+            memory_index: 0,
+            offset_expr,
+        } = &data.kind
+        else {
+            return Ok(None);
+        };
+        let Some(start) = const_offset(offset_expr)? else {
+            return Ok(None);
+        };
+        Ok(Some(DataRange {
+            index,
+            start,
+            bytes: Arc::from(data.data),
+        }))
+    }
+
+    // Segments placed at address zero are ignored, see `data_segment_at`.
+    fn contains(&self, address: u64) -> bool {
+        self.start != 0 && address >= self.start && address - self.start < self.bytes.len() as u64
+    }
+}
+
+// The offset of an active data or element segment, if it's a constant.
+pub(crate) fn const_offset(offset_expr: &wasm::ConstExpr) -> anyhow::Result<Option<u64>> {
+    Ok(match offset_expr.get_operators_reader().read()? {
+        wasm::Operator::I32Const { value } => Some(value as u32 as u64),
+        wasm::Operator::I64Const { value } => Some(value as u64),
+        _ => None,
+    })
+}
+
+impl Func {
+    /// The data segment an address falls in and the offset into it.
+    /// Segments placed at address zero are ignored, since every small
+    /// constant would fall inside them.
+    pub(crate) fn data_segment_at(&self, address: u64) -> Option<(u32, u64)> {
+        self.data_ranges
+            .iter()
+            .find(|range| range.contains(address))
+            .map(|range| (range.index, address - range.start))
+    }
+
+    /// The NUL-terminated string at an address in a data segment, quoted
+    /// and escaped, e.g. `"error: %s\n"`. Only text counts as a string: it
+    /// has to be valid UTF-8 with no control characters other than tabs
+    /// and line breaks. Long strings are cut short with `...`.
+    pub(crate) fn string_at(&self, address: u64) -> Option<String> {
+        const MAX_CHARS: usize = 64;
+
+        let range = self
+            .data_ranges
+            .iter()
+            .find(|range| range.contains(address))?;
+        let bytes = &range.bytes[(address - range.start) as usize..];
+        let len = bytes.iter().position(|byte| *byte == 0)?;
+        let string = std::str::from_utf8(&bytes[..len]).ok()?;
+        if string.is_empty()
+            || string
+                .chars()
+                .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+        {
+            return None;
+        }
+        let quoted = if string.chars().count() > MAX_CHARS {
+            let prefix: String = string.chars().take(MAX_CHARS).collect();
+            format!("{:?}...", prefix)
+        } else {
+            format!("{:?}", string)
+        };
+        // It's printed in a comment, which `*/` would end.
+        Some(quoted.replace("*/", "*\\/"))
+    }
+}
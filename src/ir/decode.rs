@@ -2,6 +2,28 @@ use std::collections::HashMap;
 
 use crate::ir::*;
 
+type BuildResult<T> = std::result::Result<T, DecompileError>;
+
+// Abort building the current function with an internal error. The builder
+// expression must have `func_index` and `offset` fields.
+macro_rules! internal_bail {
+    ($builder:expr, $($arg:tt)*) => {
+        return Err(DecompileError::Internal {
+            func: $builder.func_index,
+            offset: $builder.offset,
+            detail: format!($($arg)*),
+        })
+    };
+}
+
+macro_rules! internal_ensure {
+    ($builder:expr, $cond:expr, $($arg:tt)*) => {
+        if !$cond {
+            internal_bail!($builder, $($arg)*);
+        }
+    };
+}
+
 #[derive(Debug)]
 struct Frame {
     kind: FrameKind,
@@ -16,6 +38,9 @@ enum FrameKind {
     Block {
         join_block: BlockIndex,
     },
+    TryTable {
+        join_block: BlockIndex,
+    },
     Loop {
         header_block: BlockIndex,
         join_block: BlockIndex,
@@ -33,56 +58,61 @@ enum FrameKind {
 }
 
 impl FrameKind {
-    fn is_func(&self) -> bool {
-        matches!(self, FrameKind::Func)
-    }
-
-    fn branch_target_block(&self) -> BlockIndex {
+    // Returns `None` for the function frame. Callers must handle this to
+    // manually emit a return.
+    fn branch_target_block(&self) -> Option<BlockIndex> {
         match self {
-            FrameKind::Block { join_block } => *join_block,
-            FrameKind::Loop { header_block, .. } => *header_block,
-            FrameKind::If { join_block, .. } => *join_block,
-            FrameKind::Else { join_block, .. } => *join_block,
-            // Callers must handle this to manually emit a return
-            FrameKind::Func => unreachable!(),
+            FrameKind::Block { join_block } => Some(*join_block),
+            FrameKind::TryTable { join_block } => Some(*join_block),
+            FrameKind::Loop { header_block, .. } => Some(*header_block),
+            FrameKind::If { join_block, .. } => Some(*join_block),
+            FrameKind::Else { join_block, .. } => Some(*join_block),
+            FrameKind::Func => None,
         }
     }
 }
 
-struct Builder {
-    func_index: u32,
+struct Builder<'a> {
+    func_index: FuncIdx,
     func_type: wasm::FuncType,
     locals: Vec<Local>,
     temp_count: u32,
     frames: Vec<Frame>,
     stack: Vec<Expression>,
-    validator: wasm::FuncValidator<wasm::ValidatorResources>,
+    validator: wasm::FuncValidator<Arc<wasm::ValidatorResources>>,
     blocks: HashMap<BlockIndex, Block>,
     start_block: BlockIndex,
     current_block: BlockIndex,
     return_block: BlockIndex,
     next_block_index: BlockIndex,
+    // Offset of the operator currently being decoded, for error reporting.
+    offset: usize,
+    // Offset just past the operator currently being decoded.
+    op_end: usize,
+    source_map: Option<&'a SourceMap>,
+    // Where the instructions each value on the stack was computed from
+    // start, as of the start of the operator being decoded.
+    stack_starts: Vec<usize>,
+    // The lowest the stack has been while decoding the current operator.
+    // The values in `stack_starts` from here on are the ones it took.
+    low_water: usize,
+    // The block and operator offset of the last offset emitted, and the
+    // index of its statement in the block.
+    last_offset: Option<(BlockIndex, usize, usize)>,
+    // The block and location of the last `Statement::Location` emitted.
+    last_location: Option<(BlockIndex, SourceLocation)>,
 }
 
-impl Builder {
+impl<'a> Builder<'a> {
     fn new(
-        func_index: u32,
+        func_index: FuncIdx,
+        func_type_index: TypeIdx,
+        func_type: wasm::FuncType,
         mut locals: Vec<Local>,
-        validator: wasm::FuncValidator<wasm::ValidatorResources>,
+        validator: wasm::FuncValidator<Arc<wasm::ValidatorResources>>,
+        offset: usize,
+        source_map: Option<&'a SourceMap>,
     ) -> Self {
-        let func_type = validator
-            .resources()
-            .sub_type_at(
-                validator
-                    .resources()
-                    .type_index_of_function(func_index)
-                    .unwrap(),
-            )
-            .unwrap()
-            .composite_type
-            .unwrap_func()
-            .clone();
-
         let mut blocks = HashMap::new();
 
         let start_block_index = BlockIndex(0);
@@ -90,6 +120,7 @@ impl Builder {
             params: Vec::new(),
             statements: Vec::new(),
             terminator: Terminator::Unknown,
+            terminator_offsets: None,
         };
         blocks.insert(start_block_index, start_block);
 
@@ -104,6 +135,7 @@ impl Builder {
             params: func_type.results().to_vec(),
             statements: Vec::new(),
             terminator: Terminator::Return(return_block_results),
+            terminator_offsets: None,
         };
         blocks.insert(return_block_index, return_block);
 
@@ -125,12 +157,7 @@ impl Builder {
                 kind: FrameKind::Func,
                 unreachable: false,
                 stack_height: 0,
-                blockty: wasm::BlockType::FuncType(
-                    validator
-                        .resources()
-                        .type_index_of_function(func_index)
-                        .unwrap(),
-                ),
+                blockty: wasm::BlockType::FuncType(func_type_index.as_u32()),
             }],
             stack: Vec::new(),
             validator,
@@ -139,108 +166,298 @@ impl Builder {
             current_block: start_block_index,
             return_block: return_block_index,
             next_block_index: BlockIndex(2),
+            offset,
+            op_end: offset,
+            source_map,
+            stack_starts: Vec::new(),
+            low_water: 0,
+            last_offset: None,
+            last_location: None,
+        }
+    }
+
+    fn func_type(&self, type_index: TypeIdx) -> BuildResult<&wasm::FuncType> {
+        match lookup_func_type(self.validator.resources(), type_index) {
+            Some(func_type) => Ok(func_type),
+            None => internal_bail!(self, "type {} is not a function type", type_index),
         }
     }
 
-    fn func_type(&self, type_index: u32) -> &wasm::FuncType {
-        self.validator
+    fn type_of_func(&self, func_index: FuncIdx) -> BuildResult<&wasm::FuncType> {
+        match self
+            .validator
             .resources()
-            .sub_type_at(type_index)
-            .unwrap()
-            .composite_type
-            .unwrap_func()
+            .type_index_of_function(func_index.as_u32())
+        {
+            Some(type_index) => self.func_type(type_index.into()),
+            None => internal_bail!(self, "no type for function {}", func_index),
+        }
+    }
+
+    fn memory_type(&self, memory: u32) -> BuildResult<wasm::MemoryType> {
+        match self.validator.resources().memory_at(memory) {
+            Some(memory_type) => Ok(memory_type),
+            None => internal_bail!(self, "unknown memory {}", memory),
+        }
+    }
+
+    fn table_type(&self, table: u32) -> BuildResult<wasm::TableType> {
+        match self.validator.resources().table_at(table) {
+            Some(table_type) => Ok(table_type),
+            None => internal_bail!(self, "unknown table {}", table),
+        }
     }
 
-    fn type_of_func(&self, func_index: u32) -> &wasm::FuncType {
-        self.func_type(
-            self.validator
+    // A reference type, with concrete heap types canonicalized the way the
+    // validator types its operands.
+    fn ref_type(
+        &self,
+        nullable: bool,
+        mut heap_type: wasm::HeapType,
+    ) -> BuildResult<wasm::ValType> {
+        let is_module_index = matches!(
+            heap_type,
+            wasm::HeapType::Concrete(wasm::UnpackedIndex::Module(_))
+        );
+        if is_module_index
+            && self
+                .validator
                 .resources()
-                .type_index_of_function(func_index)
-                .unwrap(),
-        )
+                .check_heap_type(&mut heap_type, self.offset)
+                .is_err()
+        {
+            internal_bail!(self, "invalid heap type {:?}", heap_type);
+        }
+        match wasm::RefType::new(nullable, heap_type) {
+            Some(ref_type) => Ok(wasm::ValType::Ref(ref_type)),
+            None => internal_bail!(self, "unrepresentable reference type {:?}", heap_type),
+        }
+    }
+
+    // A block result of a concrete reference type, as the validator has it.
+    fn canonical_blockty(&self, blockty: wasm::BlockType) -> BuildResult<wasm::BlockType> {
+        Ok(match blockty {
+            wasm::BlockType::Type(wasm::ValType::Ref(ref_type)) => {
+                wasm::BlockType::Type(self.ref_type(ref_type.is_nullable(), ref_type.heap_type())?)
+            }
+            blockty => blockty,
+        })
+    }
+
+    fn concrete_ref_type(&self, nullable: bool, type_index: TypeIdx) -> BuildResult<wasm::ValType> {
+        let heap_type = wasm::HeapType::Concrete(wasm::UnpackedIndex::Module(type_index.as_u32()));
+        self.ref_type(nullable, heap_type)
+    }
+
+    fn struct_type(&self, type_index: TypeIdx) -> BuildResult<&wasm::StructType> {
+        match self
+            .validator
+            .resources()
+            .sub_type_at(type_index.as_u32())
+            .map(|sub_type| &sub_type.composite_type.inner)
+        {
+            Some(wasm::CompositeInnerType::Struct(struct_type)) => Ok(struct_type),
+            _ => internal_bail!(self, "type {} is not a struct type", type_index),
+        }
+    }
+
+    fn struct_field(&self, type_index: TypeIdx, field: u32) -> BuildResult<wasm::FieldType> {
+        match self.struct_type(type_index)?.fields.get(field as usize) {
+            Some(field_type) => Ok(*field_type),
+            None => internal_bail!(self, "type {} has no field {}", type_index, field),
+        }
+    }
+
+    fn array_element(&self, type_index: TypeIdx) -> BuildResult<wasm::FieldType> {
+        match self
+            .validator
+            .resources()
+            .sub_type_at(type_index.as_u32())
+            .map(|sub_type| &sub_type.composite_type.inner)
+        {
+            Some(wasm::CompositeInnerType::Array(array_type)) => Ok(array_type.0),
+            _ => internal_bail!(self, "type {} is not an array type", type_index),
+        }
+    }
+
+    fn local_type(&self, local_index: LocalIdx) -> BuildResult<wasm::ValType> {
+        match self.locals.get(local_index.as_usize()) {
+            Some(local) => Ok(local.ty),
+            None => internal_bail!(self, "unknown local {}", local_index),
+        }
+    }
+
+    fn current_block_mut(&mut self) -> BuildResult<&mut Block> {
+        if !self.blocks.contains_key(&self.current_block) {
+            internal_bail!(self, "missing current block @{}", self.current_block.0);
+        }
+        Ok(self.blocks.get_mut(&self.current_block).unwrap())
     }
 
-    fn expr_type(&self, expression: &Expression, in_block: &Block) -> Vec<wasm::ValType> {
-        match expression {
+    fn expr_type(
+        &self,
+        expression: &Expression,
+        in_block: &Block,
+    ) -> BuildResult<Vec<wasm::ValType>> {
+        Ok(match expression {
             Expression::I32Const { .. } => vec![wasm::ValType::I32],
             Expression::I64Const { .. } => vec![wasm::ValType::I64],
             Expression::F32Const { .. } => vec![wasm::ValType::F32],
             Expression::F64Const { .. } => vec![wasm::ValType::F64],
+            Expression::V128Const { .. } => vec![wasm::ValType::V128],
+            Expression::RefNull { ty } => vec![self.ref_type(true, *ty)?],
+            // A non-null reference to the function's exact type, as the
+            // validator types it.
+            Expression::RefFunc { func_index } => {
+                let Some(type_id) = self
+                    .validator
+                    .resources()
+                    .type_id_of_function(func_index.as_u32())
+                else {
+                    internal_bail!(self, "unknown function {}", func_index);
+                };
+                let heap_type = wasm::HeapType::Concrete(wasm::UnpackedIndex::Id(type_id));
+                match wasm::RefType::new(false, heap_type) {
+                    Some(ref_type) => vec![wasm::ValType::Ref(ref_type)],
+                    None => internal_bail!(self, "unrepresentable reference type"),
+                }
+            }
             Expression::GetLocal(GetLocalExpression { local_index }) => {
-                vec![self.locals[*local_index as usize].ty]
+                vec![self.local_type(*local_index)?]
             }
             Expression::GetLocalN(GetLocalNExpression { local_indices }) => local_indices
                 .iter()
-                .map(|x| self.locals[*x as usize].ty)
-                .collect(),
+                .map(|x| self.local_type(*x))
+                .collect::<BuildResult<_>>()?,
             Expression::GetGlobal(GetGlobalExpression { global_index }) => {
-                vec![
-                    self.validator
-                        .resources()
-                        .global_at(*global_index)
-                        .unwrap()
-                        .content_type,
-                ]
+                match self.validator.resources().global_at(global_index.as_u32()) {
+                    Some(global) => vec![global.content_type],
+                    None => internal_bail!(self, "unknown global {}", global_index),
+                }
             }
             Expression::Call(CallExpression { func_index, .. }) => {
-                self.type_of_func(*func_index).results().to_vec()
+                self.type_of_func(*func_index)?.results().to_vec()
             }
             Expression::CallIndirect(CallIndirectExpression {
                 func_type_index, ..
-            }) => self.func_type(*func_type_index).results().to_vec(),
-            Expression::MemorySize => {
-                // TODO
-                vec![wasm::ValType::I32]
+            }) => self.func_type(*func_type_index)?.results().to_vec(),
+            Expression::CallRef(CallRefExpression { type_index, .. }) => {
+                self.func_type(*type_index)?.results().to_vec()
+            }
+            Expression::MemorySize { memory }
+            | Expression::MemoryGrow(MemoryGrowExpression { memory, .. }) => {
+                if self.memory_type(*memory)?.memory64 {
+                    vec![wasm::ValType::I64]
+                } else {
+                    vec![wasm::ValType::I32]
+                }
+            }
+            Expression::TableGet(TableGetExpression { table, .. }) => {
+                vec![wasm::ValType::Ref(self.table_type(*table)?.element_type)]
+            }
+            Expression::TableSize { table }
+            | Expression::TableGrow(TableGrowExpression { table, .. }) => {
+                if self.table_type(*table)?.table64 {
+                    vec![wasm::ValType::I64]
+                } else {
+                    vec![wasm::ValType::I32]
+                }
             }
-            Expression::MemoryGrow(_) => vec![wasm::ValType::I32],
             Expression::MemoryLoad(MemoryLoadExpression { kind, .. }) => {
                 vec![kind.result_type()]
             }
+            Expression::StructNew(StructNewExpression { type_index, .. })
+            | Expression::ArrayNew(ArrayNewExpression { type_index, .. }) => {
+                vec![self.concrete_ref_type(false, *type_index)?]
+            }
+            Expression::StructGet(StructGetExpression {
+                type_index, field, ..
+            }) => vec![self
+                .struct_field(*type_index, *field)?
+                .element_type
+                .unpack()],
+            Expression::ArrayGet(ArrayGetExpression { type_index, .. }) => {
+                vec![self.array_element(*type_index)?.element_type.unpack()]
+            }
+            Expression::ArrayLen(_) | Expression::RefTest(_) => vec![wasm::ValType::I32],
+            Expression::RefAsNonNull(value) => match self.expr_type(value, in_block)?[..] {
+                [wasm::ValType::Ref(ref_type)] => {
+                    vec![self.ref_type(false, ref_type.heap_type())?]
+                }
+                _ => internal_bail!(self, "ref.as_non_null of a non-reference"),
+            },
+            Expression::RefCast(RefCastExpression {
+                nullable,
+                heap_type,
+                ..
+            }) => vec![self.ref_type(*nullable, *heap_type)?],
             Expression::Unary(op, _) => vec![op.result_type()],
             Expression::Binary(op, _, _) => vec![op.result_type()],
+            Expression::Logical(..) => vec![wasm::ValType::I32],
+            Expression::Ternary(op, _, _, _) => vec![op.result_type()],
+            Expression::ExtractLane(ExtractLaneExpression { shape, .. }) => {
+                vec![shape.lane_type()]
+            }
+            Expression::ReplaceLane(_) | Expression::Shuffle(_) => vec![wasm::ValType::V128],
+            Expression::Select(SelectExpression { ty: Some(ty), .. }) => vec![*ty],
             Expression::Select(op) => {
-                let on_true = self.expr_type(&op.on_true, in_block);
-                let on_false = self.expr_type(&op.on_false, in_block);
-                assert_eq!(on_true, on_false);
+                let on_true = self.expr_type(&op.on_true, in_block)?;
+                let on_false = self.expr_type(&op.on_false, in_block)?;
+                internal_ensure!(
+                    self,
+                    on_true == on_false,
+                    "select operand types differ: {:?} vs {:?}",
+                    on_true,
+                    on_false
+                );
                 on_true
             }
-            Expression::BlockParam(i) => {
-                vec![in_block.params[*i as usize]]
-            }
+            Expression::BlockParam(i) => match in_block.params.get(*i as usize) {
+                Some(ty) => vec![*ty],
+                None => internal_bail!(self, "unknown block param b{}", i),
+            },
             Expression::Bottom => vec![],
-        }
+        })
     }
 
-    fn blockty_params(&self, blockty: wasm::BlockType) -> Vec<wasm::ValType> {
-        match blockty {
+    fn blockty_params(&self, blockty: wasm::BlockType) -> BuildResult<Vec<wasm::ValType>> {
+        Ok(match blockty {
             wasm::BlockType::Empty => vec![],
-            wasm::BlockType::FuncType(type_index) => self.func_type(type_index).params().to_vec(),
+            wasm::BlockType::FuncType(type_index) => {
+                self.func_type(type_index.into())?.params().to_vec()
+            }
             wasm::BlockType::Type(_) => vec![],
-        }
+        })
     }
 
-    fn blockty_results(&self, blockty: wasm::BlockType) -> Vec<wasm::ValType> {
-        match blockty {
+    fn blockty_results(&self, blockty: wasm::BlockType) -> BuildResult<Vec<wasm::ValType>> {
+        Ok(match blockty {
             wasm::BlockType::Empty => vec![],
-            wasm::BlockType::FuncType(type_index) => self.func_type(type_index).results().to_vec(),
+            wasm::BlockType::FuncType(type_index) => {
+                self.func_type(type_index.into())?.results().to_vec()
+            }
             wasm::BlockType::Type(ty) => vec![ty],
-        }
+        })
     }
 
-    fn blockty_params_count(&self, blockty: wasm::BlockType) -> usize {
-        match blockty {
+    fn blockty_params_count(&self, blockty: wasm::BlockType) -> BuildResult<usize> {
+        Ok(match blockty {
             wasm::BlockType::Empty => 0,
-            wasm::BlockType::FuncType(type_index) => self.func_type(type_index).params().len(),
+            wasm::BlockType::FuncType(type_index) => {
+                self.func_type(type_index.into())?.params().len()
+            }
             wasm::BlockType::Type(_) => 0,
-        }
+        })
     }
 
-    fn blockty_results_count(&self, blockty: wasm::BlockType) -> usize {
-        match blockty {
+    fn blockty_results_count(&self, blockty: wasm::BlockType) -> BuildResult<usize> {
+        Ok(match blockty {
             wasm::BlockType::Empty => 0,
-            wasm::BlockType::FuncType(type_index) => self.func_type(type_index).results().len(),
+            wasm::BlockType::FuncType(type_index) => {
+                self.func_type(type_index.into())?.results().len()
+            }
             wasm::BlockType::Type(_) => 1,
-        }
+        })
     }
 
     fn add_block(&mut self, node: Block) -> BlockIndex {
@@ -250,31 +467,126 @@ impl Builder {
         block_index
     }
 
+    // Appends a statement to the current block, preceded by its source
+    // location if that differs from the previous statement's in the block,
+    // and by the range of the instructions it was decoded from.
+    fn push_statement(&mut self, statement: Statement) -> BuildResult<()> {
+        self.push_statement_from(statement, self.consumed_start())
+    }
+
+    // Like `push_statement`, for a statement whose instructions start at
+    // `start`.
+    fn push_statement_from(&mut self, statement: Statement, start: usize) -> BuildResult<()> {
+        if let Some(location) = self.source_map.and_then(|map| map.lookup(self.offset)) {
+            let location = (self.current_block, location);
+            if self.last_location.as_ref() != Some(&location) {
+                self.current_block_mut()?
+                    .statements
+                    .push(Statement::Location(location.1.clone()));
+                self.last_location = Some(location);
+            }
+        }
+        // Values spilled before a statement share its offsets, which take
+        // in the instructions of each.
+        let (current_block, offset, op_end) = (self.current_block, self.offset, self.op_end);
+        let last_offset = (self.last_offset)
+            .filter(|(block_index, last, _)| (*block_index, *last) == (current_block, offset));
+        let block = self.current_block_mut()?;
+        match last_offset {
+            Some((_, _, i)) => {
+                if let Some(Statement::Location(SourceLocation::Offset(range))) =
+                    block.statements.get_mut(i)
+                {
+                    range.start = range.start.min(start);
+                }
+            }
+            None => {
+                let i = block.statements.len();
+                (block.statements).push(Statement::Location(SourceLocation::Offset(start..op_end)));
+                self.last_offset = Some((current_block, offset, i));
+            }
+        }
+        self.current_block_mut()?.statements.push(statement);
+        Ok(())
+    }
+
+    // Where the first instruction the operator being decoded has taken a
+    // value from so far starts, or the operator itself if it's taken none.
+    fn consumed_start(&self) -> usize {
+        (self.stack_starts.get(self.low_water..).unwrap_or_default())
+            .iter()
+            .fold(self.offset, |start, value_start| start.min(*value_start))
+    }
+
+    // The values already on the stack were computed before the operator at
+    // `op_offset`, by the instructions `stack_starts` says.
+    fn begin_op(&mut self, op_offset: usize) {
+        self.offset = op_offset;
+        self.low_water = self.stack.len();
+        self.stack_starts.resize(self.stack.len(), op_offset);
+    }
+
+    // The values the operator just decoded left on the stack are computed
+    // from the instructions of those it took, and its own.
+    fn end_op(&mut self) {
+        let start = self.consumed_start();
+        self.stack_starts.truncate(self.low_water);
+        self.stack_starts.resize(self.stack.len(), start);
+    }
+
+    // Called after the stack shrinks, to keep track of the values the
+    // operator took.
+    fn note_stack_height(&mut self) {
+        self.low_water = self.low_water.min(self.stack.len());
+    }
+
+    // Gives the terminator the operator just decoded ended `block_index`
+    // with, if it did, the offsets of that operator and of the instructions
+    // its operands came from. Terminators no operator stands for, such as
+    // that of an `if` without an `else`, have none.
+    fn note_terminator(&mut self, block_index: BlockIndex) {
+        let offsets = self.consumed_start()..self.op_end;
+        if let Some(block) = self.blocks.get_mut(&block_index) {
+            if block.terminator_offsets.is_none()
+                && !matches!(block.terminator, Terminator::Unknown)
+            {
+                block.terminator_offsets = Some(offsets);
+            }
+        }
+    }
+
     fn push_frame(&mut self, frame: Frame) {
         self.frames.push(frame);
     }
 
-    fn pop_frame(&mut self) -> Frame {
-        self.frames.pop().unwrap()
+    fn pop_frame(&mut self) -> BuildResult<Frame> {
+        match self.frames.pop() {
+            Some(frame) => Ok(frame),
+            None => internal_bail!(self, "control stack underflow"),
+        }
     }
 
-    fn frame_at(&self, relative_depth: u32) -> &Frame {
-        assert!((relative_depth as usize) < self.frames.len());
+    fn frame_at(&self, relative_depth: u32) -> BuildResult<&Frame> {
+        internal_ensure!(
+            self,
+            (relative_depth as usize) < self.frames.len(),
+            "branch depth {} out of range",
+            relative_depth
+        );
         let index = self.frames.len() - relative_depth as usize - 1;
-        &self.frames[index]
+        Ok(&self.frames[index])
     }
 
-    fn frame_unreachable(&self, relative_depth: u32) -> bool {
-        self.frame_at(relative_depth).unreachable
+    fn frame_unreachable(&self, relative_depth: u32) -> BuildResult<bool> {
+        Ok(self.frame_at(relative_depth)?.unreachable)
     }
 
-    fn branch_target_block(&self, relative_depth: u32) -> BlockIndex {
-        let frame = self.frame_at(relative_depth);
-        if frame.kind.is_func() {
-            self.return_block
-        } else {
-            frame.kind.branch_target_block()
-        }
+    fn branch_target_block(&self, relative_depth: u32) -> BuildResult<BlockIndex> {
+        let frame = self.frame_at(relative_depth)?;
+        Ok(frame
+            .kind
+            .branch_target_block()
+            .unwrap_or(self.return_block))
     }
 
     fn push_block_params(&mut self, n: usize) {
@@ -283,105 +595,114 @@ impl Builder {
         }
     }
 
-    fn pop_branch_params(&mut self, relative_depth: u32) -> Vec<Expression> {
-        let frame = self.frame_at(relative_depth);
+    fn pop_branch_params(&mut self, relative_depth: u32) -> BuildResult<Vec<Expression>> {
+        let frame = self.frame_at(relative_depth)?;
         let count = match frame.kind {
-            FrameKind::Block { join_block: _ } => self.blockty_results_count(frame.blockty),
+            FrameKind::Block { join_block: _ } => self.blockty_results_count(frame.blockty)?,
+            FrameKind::TryTable { join_block: _ } => self.blockty_results_count(frame.blockty)?,
             FrameKind::Loop {
                 header_block: _,
                 join_block: _,
-            } => self.blockty_params_count(frame.blockty),
+            } => self.blockty_params_count(frame.blockty)?,
             FrameKind::If {
                 true_block: _,
                 false_block: _,
                 join_block: _,
-            } => self.blockty_results_count(frame.blockty),
+            } => self.blockty_results_count(frame.blockty)?,
             FrameKind::Else {
                 _true_block,
                 _false_block,
                 join_block: _,
-            } => self.blockty_results_count(frame.blockty),
+            } => self.blockty_results_count(frame.blockty)?,
             FrameKind::Func => self.func_type.results().len(),
         };
         self.popn(count)
     }
 
-    fn after_unconditional_branch(&mut self) {
-        let frame = self.frames.last_mut().unwrap();
-        assert!(!frame.unreachable);
-        let block = self.blocks.get_mut(&self.current_block).unwrap();
+    fn after_unconditional_branch(&mut self) -> BuildResult<()> {
+        let Some(frame) = self.frames.last() else {
+            internal_bail!(self, "control stack underflow");
+        };
+        internal_ensure!(
+            self,
+            !frame.unreachable,
+            "unconditional branch in unreachable code"
+        );
+        internal_ensure!(
+            self,
+            self.stack.len() >= frame.stack_height,
+            "operand stack below frame height"
+        );
+        let stack_height = frame.stack_height;
 
         // TODO
         // assert!(block.terminator != Terminator::Unknown);
 
         // Emit drop statements for all the expressions on the stack
         // that would get clobbered by the unconditional branch
-        let dropped_values = self.stack.drain(frame.stack_height..);
+        let dropped_values: Vec<Expression> = self.stack.drain(stack_height..).collect();
+        self.note_stack_height();
         for value in dropped_values {
-            block.statements.push(Statement::Drop(value));
+            self.push_statement(Statement::Drop(value))?;
         }
 
         // We don't need to truncate after manually dropping all those expressions
-        assert!(self.stack.len() == frame.stack_height);
-        frame.unreachable = true;
+        self.frames.last_mut().unwrap().unreachable = true;
+        Ok(())
     }
 
-    fn pop(&mut self) -> Expression {
-        let frame = self.frame_at(0);
-        assert!(self.stack.len() >= frame.stack_height);
+    fn pop(&mut self) -> BuildResult<Expression> {
+        let frame = self.frame_at(0)?;
+        internal_ensure!(
+            self,
+            self.stack.len() >= frame.stack_height,
+            "operand stack below frame height"
+        );
         if self.stack.len() == frame.stack_height {
-            assert!(self.frame_unreachable(0));
-            Expression::Bottom
+            internal_ensure!(
+                self,
+                frame.unreachable,
+                "operand stack underflow in reachable code"
+            );
+            Ok(Expression::Bottom)
         } else {
-            self.stack.pop().unwrap()
+            let value = self.stack.pop().unwrap();
+            self.note_stack_height();
+            Ok(value)
         }
     }
 
-    fn popn(&mut self, n: usize) -> Vec<Expression> {
+    fn popn(&mut self, n: usize) -> BuildResult<Vec<Expression>> {
         if n == 0 {
-            return Vec::new();
+            return Ok(Vec::new());
         }
         let mut result = Vec::with_capacity(n);
         for _ in 0..n {
-            let frame = self.frame_at(0);
-            assert!(self.stack.len() >= frame.stack_height);
-            if self.stack.len() == frame.stack_height {
-                assert!(frame.unreachable);
-                result.push(Expression::Bottom);
-            } else {
-                result.push(self.stack.pop().unwrap());
-            }
+            result.push(self.pop()?);
         }
         result.reverse();
-        result
+        Ok(result)
     }
 
-    fn sync_stack_before_statement(&mut self) {
-        let frame = self.frames.last_mut().unwrap();
+    fn sync_stack_before_statement(&mut self) -> BuildResult<()> {
+        let Some(frame) = self.frames.last() else {
+            internal_bail!(self, "control stack underflow");
+        };
         for i in frame.stack_height..self.stack.len() {
-            let expr_type = self.expr_type(
-                &self.stack[i],
-                self.blocks.get(&self.current_block).unwrap(),
-            );
+            let Some(current_block) = self.blocks.get(&self.current_block) else {
+                internal_bail!(self, "missing current block @{}", self.current_block.0);
+            };
+            let expr_type = self.expr_type(&self.stack[i], current_block)?;
             if expr_type.is_empty() {
-                assert!(matches!(self.stack[i], Expression::Bottom));
+                internal_ensure!(
+                    self,
+                    matches!(self.stack[i], Expression::Bottom),
+                    "expression without a type on the operand stack"
+                );
                 continue;
             }
 
-            let temps_needed = expr_type.len() as u32;
-            let local_start_index = self.locals.len() as u32;
-            let temp_start_index = self.temp_count;
-            self.temp_count += temps_needed;
-
-            // Add temp locals for all the expressions on the stack
-            let mut local_indices = Vec::new();
-            for l in 0..temps_needed {
-                self.locals.push(Local {
-                    ty: expr_type[l as usize],
-                    name: format!("temp{}", temp_start_index + l),
-                });
-                local_indices.push(local_start_index + l);
-            }
+            let local_indices = self.new_temps(&expr_type);
 
             // Replace the expression on the stack with a GetLocalN expression
             let replacement_expr = Expression::GetLocalN(GetLocalNExpression {
@@ -389,27 +710,68 @@ impl Builder {
             });
             // Swap it in on the expression stack, grabbing the original value
             let init_temp_value = std::mem::replace(&mut self.stack[i], replacement_expr);
+            // Values above `low_water` were pushed by this operator.
+            let start = match self.stack_starts.get_mut(i).filter(|_| i < self.low_water) {
+                Some(start) => std::mem::replace(start, self.offset),
+                None => self.consumed_start(),
+            };
 
             // Add a LocalSetN statement to initialize the temp local
-            let block = self.blocks.get_mut(&self.current_block).unwrap();
-            block
-                .statements
-                .push(Statement::LocalSetN(LocalSetNStatement {
+            self.push_statement_from(
+                Statement::LocalSetN(LocalSetNStatement {
                     index: local_indices,
                     value: Box::new(init_temp_value),
-                }));
+                }),
+                start,
+            )?;
+        }
+        Ok(())
+    }
+
+    // Adds a temp local for each type.
+    fn new_temps(&mut self, types: &[wasm::ValType]) -> Vec<LocalIdx> {
+        let mut local_indices = Vec::new();
+        for ty in types {
+            local_indices.push(LocalIdx::from(self.locals.len() as u32));
+            self.locals.push(Local {
+                ty: *ty,
+                name: format!("temp{}", self.temp_count),
+            });
+            self.temp_count += 1;
+        }
+        local_indices
+    }
+
+    // Assigns the results of a call that returns more than one value to new
+    // temps, and pushes the temps in their place, e.g. `temp0, temp1 =
+    // func3(x)`.
+    fn push_multi_value(&mut self, value: Expression, types: &[wasm::ValType]) -> BuildResult<()> {
+        self.sync_stack_before_statement()?;
+        let local_indices = self.new_temps(types);
+        self.push_statement(Statement::LocalSetN(LocalSetNStatement {
+            index: local_indices.clone(),
+            value: Box::new(value),
+        }))?;
+        for local_index in local_indices {
+            self.stack
+                .push(Expression::GetLocal(GetLocalExpression { local_index }));
         }
+        Ok(())
     }
 
-    fn check_stack_for_block(&mut self, block_params: usize) -> Vec<Expression> {
-        let results = self.popn(block_params);
-        self.sync_stack_before_statement();
+    fn check_stack_for_block(&mut self, block_params: usize) -> BuildResult<Vec<Expression>> {
+        let results = self.popn(block_params)?;
+        self.sync_stack_before_statement()?;
         self.push_block_params(block_params);
-        results
+        Ok(results)
     }
 
-    #[allow(unused)]
-    fn dump_state(&self, op: wasm::Operator) {
+    // Logs the decoder's stacks next to the validator's after `op`, to
+    // find where they part ways.
+    fn trace_state(&self, op: &wasm::Operator) {
+        if !tracing::enabled!(tracing::Level::TRACE) {
+            return;
+        }
         let mut operands = Vec::new();
         for i in 0..self.validator.operand_stack_height() {
             operands.push(self.validator.get_operand_type(i as usize).unwrap());
@@ -422,51 +784,54 @@ impl Builder {
         }
         frames.reverse();
 
-        println!("{:?}", op);
-        println!("\tour-stack = {:?}", self.stack);
-        println!("\tvalidator-stack={:?}", operands);
-        println!();
-        println!("\tour-frames = {:?}", self.frames);
-        println!("\tvalidator-frames = {:?}", frames);
-        println!();
+        tracing::trace!(
+            ?op,
+            stack = ?self.stack,
+            validator_stack = ?operands,
+            frames = ?self.frames,
+            validator_frames = ?frames,
+        );
     }
 
-    fn check_invariants(&self) {
+    fn check_invariants(&self) -> BuildResult<()> {
         // Our internal state is allowed to diverge from wasmparser when we're
         // in unreachable code.
         for frame in &self.frames {
             if frame.unreachable {
-                return;
+                return Ok(());
             }
         }
 
-        assert_eq!(
-            self.frames.len(),
-            self.validator.control_stack_height() as usize,
+        internal_ensure!(
+            self,
+            self.frames.len() == self.validator.control_stack_height() as usize,
             "decoder and validator control stack height mismatch"
         );
         for i in 0..self.validator.control_stack_height() {
             if let Some(validator_frame) = self.validator.get_control_frame(i as usize) {
-                let frame = self.frame_at(i);
-                assert_eq!(
-                    frame.unreachable, validator_frame.unreachable,
+                let frame = self.frame_at(i)?;
+                internal_ensure!(
+                    self,
+                    frame.unreachable == validator_frame.unreachable,
                     "decoder and validator control frame unreachable mismatch"
                 );
-                assert_eq!(
-                    frame.stack_height, validator_frame.height,
+                internal_ensure!(
+                    self,
+                    frame.stack_height == validator_frame.height,
                     "decoder and validator control frame stack height mismatch"
                 );
-                assert_eq!(
-                    frame.blockty, validator_frame.block_type,
+                internal_ensure!(
+                    self,
+                    frame.blockty == validator_frame.block_type,
                     "decoder and validator block type mismatch"
                 );
             }
         }
 
         if !self.frames.is_empty() {
-            assert_eq!(
-                self.stack.len(),
-                self.validator.operand_stack_height() as usize,
+            internal_ensure!(
+                self,
+                self.stack.len() == self.validator.operand_stack_height() as usize,
                 "decoder and validator operand stack height mismatch"
             );
             for i in 0..self.validator.operand_stack_height() {
@@ -474,25 +839,25 @@ impl Builder {
 
                 match self.validator.get_operand_type(i as usize) {
                     None => continue,
-                    Some(None) => assert!(
+                    Some(None) => internal_ensure!(
+                        self,
                         matches!(our_expression, Expression::Bottom),
                         "decoder and validator type mismatch at depth {i}"
                     ),
                     Some(Some(validator_ty)) => {
                         let our_ty =
-                            self.expr_type(our_expression, &self.blocks[&self.current_block]);
-                        assert!(
-                            our_ty.len() == 1,
-                            "decoder and validator type mismatch at depth {i}"
-                        );
-                        assert_eq!(
-                            our_ty[0], validator_ty,
+                            self.expr_type(our_expression, &self.blocks[&self.current_block])?;
+                        internal_ensure!(
+                            self,
+                            our_ty.len() == 1 && our_ty[0] == validator_ty,
                             "decoder and validator type mismatch at depth {i}"
                         );
                     }
                 }
             }
         }
+
+        Ok(())
     }
 
     fn visit_op(
@@ -500,81 +865,118 @@ impl Builder {
         op_offset: usize,
         current_offset: usize,
         op: wasm::Operator,
-    ) -> anyhow::Result<()> {
-        self.validator.op(op_offset, &op)?;
+    ) -> BuildResult<()> {
+        self.begin_op(op_offset);
+        self.op_end = current_offset;
 
         match op {
             wasm::Operator::Block { blockty } => {
-                self.visit_block_op(blockty);
+                self.visit_block_op(self.canonical_blockty(blockty)?)?;
             }
             wasm::Operator::Loop { blockty } => {
-                self.visit_loop_op(blockty);
+                self.visit_loop_op(self.canonical_blockty(blockty)?)?;
+            }
+            wasm::Operator::TryTable { mut try_table } => {
+                try_table.ty = self.canonical_blockty(try_table.ty)?;
+                self.visit_try_table_op(try_table)?;
             }
             wasm::Operator::If { blockty } => {
-                self.visit_if_op(blockty);
+                self.visit_if_op(self.canonical_blockty(blockty)?)?;
             }
             wasm::Operator::Else => {
-                self.visit_else_op();
+                self.visit_else_op()?;
             }
             wasm::Operator::End => {
                 self.visit_end_op(current_offset)?;
             }
             wasm::Operator::Unreachable => {
                 // If our current frame is in unreachable code, don't codegen anything
-                if self.frame_unreachable(0) {
+                if self.frame_unreachable(0)? {
                     return Ok(());
                 }
 
-                self.visit_unreachable_op();
+                self.visit_unreachable_op()?;
             }
             wasm::Operator::Return => {
                 // If our current frame is in unreachable code, don't codegen anything
-                if self.frame_unreachable(0) {
+                if self.frame_unreachable(0)? {
                     return Ok(());
                 }
 
-                self.visit_return_op();
+                self.visit_return_op()?;
             }
             wasm::Operator::Br { relative_depth } => {
                 // If our current frame is in unreachable code, don't codegen anything
-                if self.frame_unreachable(0) {
+                if self.frame_unreachable(0)? {
                     return Ok(());
                 }
 
-                self.visit_br_op(relative_depth);
+                self.visit_br_op(relative_depth)?;
             }
             wasm::Operator::BrIf { relative_depth } => {
                 // If our current frame is in unreachable code, don't codegen anything
-                if self.frame_unreachable(0) {
+                if self.frame_unreachable(0)? {
+                    return Ok(());
+                }
+
+                self.visit_br_if_op(relative_depth)?;
+            }
+            wasm::Operator::BrOnNull { relative_depth } => {
+                // If our current frame is in unreachable code, don't codegen anything
+                if self.frame_unreachable(0)? {
+                    return Ok(());
+                }
+
+                self.visit_br_on_null_op(relative_depth, false)?;
+            }
+            wasm::Operator::BrOnNonNull { relative_depth } => {
+                // If our current frame is in unreachable code, don't codegen anything
+                if self.frame_unreachable(0)? {
                     return Ok(());
                 }
 
-                self.visit_br_if_op(relative_depth);
+                self.visit_br_on_null_op(relative_depth, true)?;
             }
             wasm::Operator::BrTable { targets } => {
                 // If our current frame is in unreachable code, don't codegen anything
-                if self.frame_unreachable(0) {
+                if self.frame_unreachable(0)? {
                     return Ok(());
                 }
 
                 self.visit_br_table_op(targets)?;
             }
+            wasm::Operator::Throw { tag_index } => {
+                // If our current frame is in unreachable code, don't codegen anything
+                if self.frame_unreachable(0)? {
+                    return Ok(());
+                }
+
+                self.visit_throw_op(tag_index)?;
+            }
+            wasm::Operator::ThrowRef => {
+                // If our current frame is in unreachable code, don't codegen anything
+                if self.frame_unreachable(0)? {
+                    return Ok(());
+                }
+
+                self.visit_throw_ref_op()?;
+            }
             _ => {
                 // If our current frame is in unreachable code, don't codegen anything
-                if self.frame_unreachable(0) {
+                if self.frame_unreachable(0)? {
                     return Ok(());
                 }
 
-                self.visit_statement_op(op);
+                self.visit_statement_op(op)?;
             }
         }
 
         Ok(())
     }
 
-    fn visit_block_op(&mut self, blockty: wasm::BlockType) {
-        let block_params = self.blockty_params(blockty);
-        let block_results = self.blockty_results(blockty);
+    fn visit_block_op(&mut self, blockty: wasm::BlockType) -> BuildResult<()> {
+        let block_params = self.blockty_params(blockty)?;
+        let block_results = self.blockty_results(blockty)?;
         let block_params_count = block_params.len();
 
         // Create the inner block that will contain the block's body
@@ -582,6 +984,7 @@ impl Builder {
             params: block_params,
             statements: Vec::new(),
             terminator: Terminator::Unknown,
+            terminator_offsets: None,
         });
 
         // Create a join block
@@ -589,14 +992,15 @@ impl Builder {
             params: block_results,
             statements: Vec::new(),
             terminator: Terminator::Unknown,
+            terminator_offsets: None,
         });
 
         // Get the block params and the value stack height
-        let results = self.check_stack_for_block(block_params_count);
+        let results = self.check_stack_for_block(block_params_count)?;
         let stack_height = self.stack.len() - block_params_count;
 
         // Jump to the inner block
-        let current_block_ref = self.blocks.get_mut(&self.current_block).unwrap();
+        let current_block_ref = self.current_block_mut()?;
         current_block_ref.terminator = Terminator::Br(inner_block, results);
         self.current_block = inner_block;
 
@@ -607,11 +1011,75 @@ impl Builder {
             stack_height,
             blockty,
         });
+        Ok(())
+    }
+
+    fn visit_try_table_op(&mut self, try_table: wasm::TryTable) -> BuildResult<()> {
+        let blockty = try_table.ty;
+        let block_params = self.blockty_params(blockty)?;
+        let block_results = self.blockty_results(blockty)?;
+        let block_params_count = block_params.len();
+
+        // Catch labels are relative to the frames enclosing the try_table,
+        // so resolve them before pushing its frame.
+        let mut catches = Vec::new();
+        for catch in try_table.catches {
+            catches.push(match catch {
+                wasm::Catch::One { tag, label } => Catch::One {
+                    tag,
+                    target: self.branch_target_block(label)?,
+                },
+                wasm::Catch::OneRef { tag, label } => Catch::OneRef {
+                    tag,
+                    target: self.branch_target_block(label)?,
+                },
+                wasm::Catch::All { label } => Catch::All {
+                    target: self.branch_target_block(label)?,
+                },
+                wasm::Catch::AllRef { label } => Catch::AllRef {
+                    target: self.branch_target_block(label)?,
+                },
+            });
+        }
+
+        // Create the inner block that will contain the try_table's body
+        let inner_block = self.add_block(Block {
+            params: block_params,
+            statements: Vec::new(),
+            terminator: Terminator::Unknown,
+            terminator_offsets: None,
+        });
+
+        // Create a join block
+        let join_block = self.add_block(Block {
+            params: block_results,
+            statements: Vec::new(),
+            terminator: Terminator::Unknown,
+            terminator_offsets: None,
+        });
+
+        // Get the block params and the value stack height
+        let results = self.check_stack_for_block(block_params_count)?;
+        let stack_height = self.stack.len() - block_params_count;
+
+        // Enter the inner block, with edges to the handlers
+        let current_block_ref = self.current_block_mut()?;
+        current_block_ref.terminator = Terminator::Try(inner_block, catches, results);
+        self.current_block = inner_block;
+
+        // Push the try_table frame
+        self.push_frame(Frame {
+            kind: FrameKind::TryTable { join_block },
+            unreachable: false,
+            stack_height,
+            blockty,
+        });
+        Ok(())
     }
 
-    fn visit_loop_op(&mut self, blockty: wasm::BlockType) {
-        let block_params = self.blockty_params(blockty);
-        let block_results = self.blockty_results(blockty);
+    fn visit_loop_op(&mut self, blockty: wasm::BlockType) -> BuildResult<()> {
+        let block_params = self.blockty_params(blockty)?;
+        let block_results = self.blockty_results(blockty)?;
         let block_params_count = block_params.len();
 
         // Create the 'loop_header' block
@@ -619,6 +1087,7 @@ impl Builder {
             params: block_params,
             statements: Vec::new(),
             terminator: Terminator::Unknown,
+            terminator_offsets: None,
         });
 
         // Create a join block
@@ -626,14 +1095,15 @@ impl Builder {
             params: block_results,
             statements: Vec::new(),
             terminator: Terminator::Unknown,
+            terminator_offsets: None,
         });
 
         // Get the block params and the value stack height
-        let results = self.check_stack_for_block(block_params_count);
+        let results = self.check_stack_for_block(block_params_count)?;
         let stack_height = self.stack.len() - block_params_count;
 
         // Move to the loop header block
-        let current_block_ref = self.blocks.get_mut(&self.current_block).unwrap();
+        let current_block_ref = self.current_block_mut()?;
         current_block_ref.terminator = Terminator::Br(header_block, results);
         self.current_block = header_block;
 
@@ -647,11 +1117,12 @@ impl Builder {
             stack_height,
             blockty,
         });
+        Ok(())
     }
 
-    fn visit_if_op(&mut self, blockty: wasm::BlockType) {
-        let block_params = self.blockty_params(blockty);
-        let block_results = self.blockty_results(blockty);
+    fn visit_if_op(&mut self, blockty: wasm::BlockType) -> BuildResult<()> {
+        let block_params = self.blockty_params(blockty)?;
+        let block_results = self.blockty_results(blockty)?;
         let block_params_count = block_params.len();
 
         // Create the true, false, and join blocks
@@ -659,26 +1130,29 @@ impl Builder {
             params: block_params.clone(),
             statements: Vec::new(),
             terminator: Terminator::Unknown,
+            terminator_offsets: None,
         });
         let false_block = self.add_block(Block {
             params: block_params,
             statements: Vec::new(),
             terminator: Terminator::Unknown,
+            terminator_offsets: None,
         });
         let join_block = self.add_block(Block {
             params: block_results,
             statements: Vec::new(),
             terminator: Terminator::Unknown,
+            terminator_offsets: None,
         });
 
-        let condition = self.pop();
+        let condition = self.pop()?;
 
         // Get the block params and the value stack height
-        let results = self.check_stack_for_block(block_params_count);
+        let results = self.check_stack_for_block(block_params_count)?;
         let stack_height = self.stack.len() - block_params_count;
 
         // Terminate the if predecessor block with br_if(true, false) and then move to the 'true_block'
-        let current_block_ref = self.blocks.get_mut(&self.current_block).unwrap();
+        let current_block_ref = self.current_block_mut()?;
         current_block_ref.terminator =
             Terminator::BrIf(condition, true_block, false_block, results);
         self.current_block = true_block;
@@ -694,13 +1168,14 @@ impl Builder {
             stack_height,
             blockty,
         });
+        Ok(())
     }
 
-    fn visit_else_op(&mut self) {
+    fn visit_else_op(&mut self) -> BuildResult<()> {
         // Read state from the `if` frame before it is popped
-        let frame = self.frame_at(0);
-        let block_params_count = self.blockty_params(frame.blockty).len();
-        let block_results_count = self.blockty_results(frame.blockty).len();
+        let frame = self.frame_at(0)?;
+        let block_params_count = self.blockty_params_count(frame.blockty)?;
+        let block_results_count = self.blockty_results_count(frame.blockty)?;
         let (true_block, false_block, join_block) = match frame.kind {
             FrameKind::If {
                 true_block,
@@ -708,18 +1183,24 @@ impl Builder {
                 join_block,
                 ..
             } => (true_block, false_block, join_block),
-            _ => unreachable!(),
+            _ => internal_bail!(self, "`else` without a matching `if` frame"),
         };
 
         // Pop this block's results and pass them to the join block.
-        let results = self.popn(block_results_count);
-        let frame = self.pop_frame();
+        let results = self.popn(block_results_count)?;
+        let frame = self.pop_frame()?;
 
         // Reset the value stack to the height it was at the start of the if block.
         if frame.unreachable {
             self.stack.truncate(frame.stack_height);
+            self.note_stack_height();
         } else {
-            assert!(self.stack.len() == frame.stack_height);
+            internal_ensure!(
+                self,
+                self.stack.len() == frame.stack_height,
+                "mismatched stack at `else`: {:?}",
+                frame
+            );
         }
         // Re-push this block's params.
         self.push_block_params(block_params_count);
@@ -737,24 +1218,26 @@ impl Builder {
         });
 
         // Terminate the true block with br(join) and then move to the 'false_block'
-        let current_block_ref = self.blocks.get_mut(&self.current_block).unwrap();
+        let current_block_ref = self.current_block_mut()?;
         current_block_ref.terminator = Terminator::Br(join_block, results);
         self.current_block = false_block;
+        Ok(())
     }
 
-    fn visit_end_op(&mut self, current_offset: usize) -> anyhow::Result<()> {
-        let block_results_count = self.blockty_results(self.frame_at(0).blockty).len();
-        let results = self.popn(block_results_count);
+    fn visit_end_op(&mut self, current_offset: usize) -> BuildResult<()> {
+        let block_results_count = self.blockty_results_count(self.frame_at(0)?.blockty)?;
+        let results = self.popn(block_results_count)?;
         // Pop the frame after popping the results, in case the frame was unreachable
-        let frame = self.pop_frame();
+        let frame = self.pop_frame()?;
 
         // Reset the value stack to the height it was at the start of the block.
         if frame.unreachable {
             self.stack.truncate(frame.stack_height);
+            self.note_stack_height();
         } else {
-            assert_eq!(
-                self.stack.len(),
-                frame.stack_height,
+            internal_ensure!(
+                self,
+                self.stack.len() == frame.stack_height,
                 "mismatched stack at 0x{:x}: {:?}",
                 current_offset,
                 frame
@@ -764,19 +1247,17 @@ impl Builder {
         match frame.kind {
             FrameKind::Func => {
                 // Terminate the function with a return
-                let current_block_ref = self.blocks.get_mut(&self.current_block).unwrap();
+                let current_block_ref = self.current_block_mut()?;
                 if !frame.unreachable {
                     current_block_ref.terminator = Terminator::Return(results.clone());
                 } else {
                     // TODO
                     // assert!(current_block_ref.terminator != Terminator::Unknown);
                 }
-
-                self.validator.finish(current_offset)?;
             }
-            FrameKind::Block { join_block } => {
+            FrameKind::Block { join_block } | FrameKind::TryTable { join_block } => {
                 // Terminate with a br to the join block
-                let current_block_ref = self.blocks.get_mut(&self.current_block).unwrap();
+                let current_block_ref = self.current_block_mut()?;
                 if !frame.unreachable {
                     current_block_ref.terminator = Terminator::Br(join_block, results);
                 } else {
@@ -791,7 +1272,7 @@ impl Builder {
                 join_block,
             } => {
                 // Terminate with a br to the join block
-                let current_block_ref = self.blocks.get_mut(&self.current_block).unwrap();
+                let current_block_ref = self.current_block_mut()?;
                 if !frame.unreachable {
                     current_block_ref.terminator = Terminator::Br(join_block, results);
                 } else {
@@ -807,7 +1288,7 @@ impl Builder {
                 join_block,
             } => {
                 // Terminate the true block with a br(join_block)
-                let current_block_ref = self.blocks.get_mut(&self.current_block).unwrap();
+                let current_block_ref = self.current_block_mut()?;
                 if !frame.unreachable {
                     current_block_ref.terminator = Terminator::Br(join_block, results);
                 } else {
@@ -822,8 +1303,10 @@ impl Builder {
                 // the block.
                 let block_params_count = block_results_count;
                 self.push_block_params(block_params_count);
-                let results = self.popn(block_results_count);
-                let false_block_ref = self.blocks.get_mut(&false_block).unwrap();
+                let results = self.popn(block_results_count)?;
+                let Some(false_block_ref) = self.blocks.get_mut(&false_block) else {
+                    internal_bail!(self, "missing false block @{}", false_block.0);
+                };
                 false_block_ref.terminator = Terminator::Br(join_block, results);
 
                 // Move to the join block
@@ -836,7 +1319,7 @@ impl Builder {
                 join_block,
             } => {
                 // Terminate with a br(join_block) and move to the join block
-                let current_block_ref = self.blocks.get_mut(&self.current_block).unwrap();
+                let current_block_ref = self.current_block_mut()?;
                 if !frame.unreachable {
                     current_block_ref.terminator = Terminator::Br(join_block, results);
                 } else {
@@ -851,115 +1334,187 @@ impl Builder {
         Ok(())
     }
 
-    fn visit_unreachable_op(&mut self) {
-        let block = self.blocks.get_mut(&self.current_block).unwrap();
+    fn visit_unreachable_op(&mut self) -> BuildResult<()> {
+        let block = self.current_block_mut()?;
         block.terminator = Terminator::Unreachable;
 
-        self.after_unconditional_branch();
+        self.after_unconditional_branch()
     }
 
-    fn visit_return_op(&mut self) {
+    fn visit_return_op(&mut self) -> BuildResult<()> {
         let func_frame_depth = self.frames.len() - 1;
-        self.visit_br_op(func_frame_depth as u32);
+        self.visit_br_op(func_frame_depth as u32)
     }
 
-    fn visit_br_op(&mut self, relative_depth: u32) {
-        let branch_params = self.pop_branch_params(relative_depth);
-        let target_frame = self.frame_at(relative_depth);
-        if target_frame.kind.is_func() {
-            let block = self.blocks.get_mut(&self.current_block).unwrap();
-            block.terminator = Terminator::Return(branch_params);
-        } else {
-            let target_block = target_frame.kind.branch_target_block();
-            let block = self.blocks.get_mut(&self.current_block).unwrap();
-            block.terminator = Terminator::Br(target_block, branch_params);
-        }
+    fn visit_br_op(&mut self, relative_depth: u32) -> BuildResult<()> {
+        let branch_params = self.pop_branch_params(relative_depth)?;
+        let target_frame = self.frame_at(relative_depth)?;
+        let terminator = match target_frame.kind.branch_target_block() {
+            Some(target_block) => Terminator::Br(target_block, branch_params),
+            None => Terminator::Return(branch_params),
+        };
+        self.current_block_mut()?.terminator = terminator;
 
-        self.after_unconditional_branch();
+        self.after_unconditional_branch()
     }
 
-    fn visit_br_if_op(&mut self, relative_depth: u32) {
-        let condition = self.pop();
-        let branch_params = self.pop_branch_params(relative_depth);
+    fn visit_br_if_op(&mut self, relative_depth: u32) -> BuildResult<()> {
+        let condition = self.pop()?;
+        let branch_params = self.pop_branch_params(relative_depth)?;
         let branch_params_len = branch_params.len();
-        self.sync_stack_before_statement();
+        self.sync_stack_before_statement()?;
 
-        let target_frame = self.frame_at(relative_depth);
-        let target_block = if target_frame.kind.is_func() {
-            self.return_block
-        } else {
-            target_frame.kind.branch_target_block()
-        };
+        let target_block = self.branch_target_block(relative_depth)?;
 
-        let branch_param_types = branch_params
-            .iter()
-            .flat_map(|x| self.expr_type(x, self.blocks.get(&self.current_block).unwrap()))
-            .collect();
+        let Some(current_block) = self.blocks.get(&self.current_block) else {
+            internal_bail!(self, "missing current block @{}", self.current_block.0);
+        };
+        let mut branch_param_types = Vec::new();
+        for param in &branch_params {
+            branch_param_types.extend(self.expr_type(param, current_block)?);
+        }
         let fallthrough_block = self.add_block(Block {
             params: branch_param_types,
             statements: Vec::new(),
             terminator: Terminator::Unknown,
+            terminator_offsets: None,
         });
 
-        let block = self.blocks.get_mut(&self.current_block).unwrap();
+        let block = self.current_block_mut()?;
         block.terminator =
             Terminator::BrIf(condition, target_block, fallthrough_block, branch_params);
 
         self.current_block = fallthrough_block;
         self.push_block_params(branch_params_len);
+        Ok(())
+    }
+
+    // `br_on_null` and `br_on_non_null` become a `br_if` on `is_null(x)`,
+    // with the reference kept in a temp so that it's evaluated once. Either
+    // way, the reference is passed on as non-null: to the fallthrough for
+    // `br_on_null`, and to the target for `br_on_non_null`.
+    fn visit_br_on_null_op(&mut self, relative_depth: u32, on_non_null: bool) -> BuildResult<()> {
+        let value = self.pop()?;
+        self.sync_stack_before_statement()?;
+        let Some(current_block) = self.blocks.get(&self.current_block) else {
+            internal_bail!(self, "missing current block @{}", self.current_block.0);
+        };
+        let value_type = self.expr_type(&value, current_block)?;
+        let [local_index] = self.new_temps(&value_type)[..] else {
+            internal_bail!(self, "br_on_null operand is not a single value");
+        };
+        self.push_statement(Statement::LocalSet(LocalSetStatement {
+            index: local_index,
+            value: Box::new(value),
+        }))?;
+
+        let non_null =
+            Expression::RefAsNonNull(Box::new(Expression::GetLocal(GetLocalExpression {
+                local_index,
+            })));
+        let is_null = Expression::Unary(
+            UnaryExpression::RefIsNull,
+            Box::new(Expression::GetLocal(GetLocalExpression { local_index })),
+        );
+        if on_non_null {
+            self.stack.push(non_null);
+            self.stack.push(Expression::Unary(
+                UnaryExpression::I32Eqz,
+                Box::new(is_null),
+            ));
+            self.visit_br_if_op(relative_depth)?;
+            // The reference only goes to the target.
+            self.pop()?;
+        } else {
+            self.stack.push(is_null);
+            self.visit_br_if_op(relative_depth)?;
+            self.stack.push(non_null);
+        }
+        Ok(())
+    }
+
+    fn visit_throw_op(&mut self, tag_index: u32) -> BuildResult<()> {
+        let params_count = match self.validator.resources().tag_at(tag_index) {
+            Some(tag_type) => tag_type.params().len(),
+            None => internal_bail!(self, "unknown tag {}", tag_index),
+        };
+        let params = self.popn(params_count)?;
+        self.current_block_mut()?.terminator = Terminator::Throw(tag_index, params);
+
+        self.after_unconditional_branch()
+    }
+
+    fn visit_throw_ref_op(&mut self) -> BuildResult<()> {
+        let exception = self.pop()?;
+        self.current_block_mut()?.terminator = Terminator::ThrowRef(exception);
+
+        self.after_unconditional_branch()
     }
 
-    fn visit_br_table_op(&mut self, br_table: wasm::BrTable) -> anyhow::Result<()> {
+    fn visit_br_table_op(&mut self, br_table: wasm::BrTable) -> BuildResult<()> {
         let default_target_depth = br_table.default();
-        let default_target = self.branch_target_block(default_target_depth);
-        let branch_params = self.pop_branch_params(default_target_depth);
+        let default_target = self.branch_target_block(default_target_depth)?;
+        // The index is on top of the values passed to the target.
+        let index = self.pop()?;
+        let branch_params = self.pop_branch_params(default_target_depth)?;
 
         let mut targets = Vec::new();
         for relative_depth in br_table.targets() {
-            targets.push(self.branch_target_block(relative_depth?));
+            let relative_depth = match relative_depth {
+                Ok(relative_depth) => relative_depth,
+                Err(err) => internal_bail!(self, "malformed br_table: {}", err),
+            };
+            targets.push(self.branch_target_block(relative_depth)?);
         }
 
-        let block = self.blocks.get_mut(&self.current_block).unwrap();
-        block.terminator = Terminator::BrTable(targets, default_target, branch_params);
+        let block = self.current_block_mut()?;
+        block.terminator = Terminator::BrTable(index, targets, default_target, branch_params);
 
-        self.after_unconditional_branch();
-        Ok(())
+        self.after_unconditional_branch()
     }
 
-    fn visit_statement_op(&mut self, op: wasm::Operator) {
+    fn visit_statement_op(&mut self, op: wasm::Operator) -> BuildResult<()> {
         // We only parse statements if we're not in dead code
-        assert!(!self.frame_unreachable(0));
+        internal_ensure!(
+            self,
+            !self.frame_unreachable(0)?,
+            "statement in unreachable code"
+        );
 
         let statement = match op {
             wasm::Operator::Nop => Statement::Nop,
             wasm::Operator::Drop => {
-                let value = self.pop();
+                let value = self.pop()?;
                 Statement::Drop(value)
             }
             wasm::Operator::LocalSet { local_index } => {
-                let value = self.pop();
+                let value = self.pop()?;
 
                 Statement::LocalSet(LocalSetStatement {
-                    index: local_index,
+                    index: local_index.into(),
                     value: Box::new(value),
                 })
             }
             wasm::Operator::LocalTee { local_index } => {
-                let value = self.pop();
-
-                self.stack
-                    .push(Expression::GetLocal(GetLocalExpression { local_index }));
-
-                Statement::LocalSet(LocalSetStatement {
-                    index: local_index,
+                let value = self.pop()?;
+                self.sync_stack_before_statement()?;
+                self.push_statement(Statement::LocalSet(LocalSetStatement {
+                    index: local_index.into(),
                     value: Box::new(value),
-                })
+                }))?;
+
+                // Pushed after the set, so that it reads the new value
+                // rather than being saved to a temp beforehand.
+                self.stack.push(Expression::GetLocal(GetLocalExpression {
+                    local_index: local_index.into(),
+                }));
+                return Ok(());
             }
             wasm::Operator::GlobalSet { global_index } => {
-                let value = self.pop();
+                let value = self.pop()?;
 
                 Statement::GlobalSet(GlobalSetStatement {
-                    index: global_index,
+                    index: global_index.into(),
                     value: Box::new(value),
                 })
             }
@@ -971,76 +1526,218 @@ impl Builder {
             | wasm::Operator::I64Store16 { memarg }
             | wasm::Operator::I64Store8 { memarg }
             | wasm::Operator::F32Store { memarg }
-            | wasm::Operator::F64Store { memarg } => {
-                let value = self.pop();
-                let index = self.pop();
+            | wasm::Operator::F64Store { memarg }
+            | wasm::Operator::V128Store { memarg } => {
+                let value = self.pop()?;
+                let index = self.pop()?;
+                Statement::MemoryStore(MemoryStoreStatement {
+                    arg: memarg,
+                    index: Box::new(index),
+                    value: Box::new(value),
+                })
+            }
+            // Storing a lane is a store of the extracted lane.
+            wasm::Operator::V128Store8Lane { memarg, lane }
+            | wasm::Operator::V128Store16Lane { memarg, lane }
+            | wasm::Operator::V128Store32Lane { memarg, lane }
+            | wasm::Operator::V128Store64Lane { memarg, lane } => {
+                let shape = match op {
+                    wasm::Operator::V128Store8Lane { .. } => LaneShape::I8x16,
+                    wasm::Operator::V128Store16Lane { .. } => LaneShape::I16x8,
+                    wasm::Operator::V128Store32Lane { .. } => LaneShape::I32x4,
+                    _ => LaneShape::I64x2,
+                };
+                let vector = self.pop()?;
+                let index = self.pop()?;
                 Statement::MemoryStore(MemoryStoreStatement {
-                    _arg: memarg,
+                    arg: memarg,
+                    index: Box::new(index),
+                    value: Box::new(Expression::ExtractLane(ExtractLaneExpression {
+                        shape,
+                        signed: false,
+                        lane,
+                        vector: Box::new(vector),
+                    })),
+                })
+            }
+            wasm::Operator::TableSet { table } => {
+                let value = self.pop()?;
+                let index = self.pop()?;
+                Statement::TableSet(TableSetStatement {
+                    table,
+                    index: Box::new(index),
+                    value: Box::new(value),
+                })
+            }
+            wasm::Operator::TableFill { table } => {
+                let len = self.pop()?;
+                let value = self.pop()?;
+                let index = self.pop()?;
+                Statement::TableFill(TableFillStatement {
+                    table,
+                    index: Box::new(index),
+                    value: Box::new(value),
+                    len: Box::new(len),
+                })
+            }
+            wasm::Operator::StructSet {
+                struct_type_index,
+                field_index,
+            } => {
+                let value = self.pop()?;
+                let object = self.pop()?;
+                Statement::StructSet(StructSetStatement {
+                    type_index: struct_type_index.into(),
+                    field: field_index,
+                    object: Box::new(object),
+                    value: Box::new(value),
+                })
+            }
+            wasm::Operator::ArraySet { array_type_index } => {
+                let value = self.pop()?;
+                let index = self.pop()?;
+                let array = self.pop()?;
+                Statement::ArraySet(ArraySetStatement {
+                    type_index: array_type_index.into(),
+                    array: Box::new(array),
                     index: Box::new(index),
                     value: Box::new(value),
                 })
             }
+            wasm::Operator::ArrayFill { array_type_index } => {
+                let len = self.pop()?;
+                let value = self.pop()?;
+                let offset = self.pop()?;
+                let array = self.pop()?;
+                Statement::ArrayFill(ArrayFillStatement {
+                    type_index: array_type_index.into(),
+                    array: Box::new(array),
+                    offset: Box::new(offset),
+                    value: Box::new(value),
+                    len: Box::new(len),
+                })
+            }
+            wasm::Operator::ArrayCopy {
+                array_type_index_dst,
+                array_type_index_src,
+            } => {
+                let len = self.pop()?;
+                let src_offset = self.pop()?;
+                let src = self.pop()?;
+                let dst_offset = self.pop()?;
+                let dst = self.pop()?;
+                Statement::ArrayCopy(ArrayCopyStatement {
+                    dst_type_index: array_type_index_dst.into(),
+                    src_type_index: array_type_index_src.into(),
+                    dst: Box::new(dst),
+                    dst_offset: Box::new(dst_offset),
+                    src: Box::new(src),
+                    src_offset: Box::new(src_offset),
+                    len: Box::new(len),
+                })
+            }
+            wasm::Operator::ArrayInitData {
+                array_type_index,
+                array_data_index: index,
+            }
+            | wasm::Operator::ArrayInitElem {
+                array_type_index,
+                array_elem_index: index,
+            } => {
+                let segment = if matches!(op, wasm::Operator::ArrayInitData { .. }) {
+                    Segment::Data(index)
+                } else {
+                    Segment::Elem(index)
+                };
+                let len = self.pop()?;
+                let source_offset = self.pop()?;
+                let offset = self.pop()?;
+                let array = self.pop()?;
+                Statement::ArrayInit(ArrayInitStatement {
+                    type_index: array_type_index.into(),
+                    segment,
+                    array: Box::new(array),
+                    offset: Box::new(offset),
+                    source_offset: Box::new(source_offset),
+                    len: Box::new(len),
+                })
+            }
             wasm::Operator::Call { function_index } => {
-                let func_type = self.type_of_func(function_index);
-                let result_count = func_type.results().len();
-                let params = self.popn(func_type.params().len());
+                let func_type = self.type_of_func(function_index.into())?;
+                let results = func_type.results().to_vec();
+                let params = self.popn(func_type.params().len())?;
 
                 let call = CallExpression {
-                    func_index: function_index,
+                    func_index: function_index.into(),
                     params,
                 };
 
-                if result_count == 0 {
-                    Statement::Call(call)
-                } else {
-                    if result_count == 1 {
+                match results.len() {
+                    0 => Statement::Call(call),
+                    1 => {
                         self.stack.push(Expression::Call(call));
-                    } else {
-                        unimplemented!()
+                        return Ok(());
+                    }
+                    _ => return self.push_multi_value(Expression::Call(call), &results),
+                }
+            }
+            wasm::Operator::CallRef { type_index } => {
+                let callee = Box::new(self.pop()?);
+                let func_type = self.func_type(type_index.into())?;
+                let results = func_type.results().to_vec();
+                let params = self.popn(func_type.params().len())?;
+
+                let call = CallRefExpression {
+                    type_index: type_index.into(),
+                    callee,
+                    params,
+                };
+
+                match results.len() {
+                    0 => Statement::CallRef(call),
+                    1 => {
+                        self.stack.push(Expression::CallRef(call));
+                        return Ok(());
                     }
-                    return;
+                    _ => return self.push_multi_value(Expression::CallRef(call), &results),
                 }
             }
             wasm::Operator::CallIndirect {
                 type_index,
                 table_index,
             } => {
-                let callee_index = Box::new(self.pop());
-                let func_type = self.func_type(type_index);
-                let result_count = func_type.results().len();
-                let params = self.popn(func_type.params().len());
+                let callee_index = Box::new(self.pop()?);
+                let func_type = self.func_type(type_index.into())?;
+                let results = func_type.results().to_vec();
+                let params = self.popn(func_type.params().len())?;
 
                 let call = CallIndirectExpression {
-                    func_type_index: type_index,
-                    _table_index: table_index,
+                    func_type_index: type_index.into(),
+                    table_index,
                     callee_index,
                     params,
+                    targets: IndirectTargets::Unknown,
                 };
 
-                if result_count == 0 {
-                    Statement::CallIndirect(call)
-                } else {
-                    if result_count == 1 {
+                match results.len() {
+                    0 => Statement::CallIndirect(call),
+                    1 => {
                         self.stack.push(Expression::CallIndirect(call));
-                    } else {
-                        unimplemented!()
+                        return Ok(());
                     }
-                    return;
+                    _ => return self.push_multi_value(Expression::CallIndirect(call), &results),
                 }
             }
             _ => {
-                self.expr_op(op);
-                return;
+                return self.expr_op(op);
             }
         };
 
-        self.sync_stack_before_statement();
-
-        let current_block_ref = self.blocks.get_mut(&self.current_block).unwrap();
-        current_block_ref.statements.push(statement);
+        self.sync_stack_before_statement()?;
+        self.push_statement(statement)
     }
 
-    fn expr_op(&mut self, op: wasm::Operator) {
+    fn expr_op(&mut self, op: wasm::Operator) -> BuildResult<()> {
         match op {
             wasm::Operator::I32Const { value } => {
                 self.stack.push(Expression::I32Const { value });
@@ -1054,26 +1751,47 @@ impl Builder {
             wasm::Operator::F64Const { value } => {
                 self.stack.push(Expression::F64Const { value });
             }
-            wasm::Operator::Call { .. } | wasm::Operator::CallIndirect { .. } => {
-                // Handled in visit_statement_op
-                unreachable!()
+            wasm::Operator::V128Const { value } => {
+                self.stack.push(Expression::V128Const {
+                    value: *value.bytes(),
+                });
+            }
+            wasm::Operator::Call { .. }
+            | wasm::Operator::CallIndirect { .. }
+            | wasm::Operator::CallRef { .. } => {
+                internal_bail!(self, "calls must be handled in visit_statement_op")
             }
             wasm::Operator::LocalGet { local_index } => {
-                self.stack
-                    .push(Expression::GetLocal(GetLocalExpression { local_index }));
+                self.stack.push(Expression::GetLocal(GetLocalExpression {
+                    local_index: local_index.into(),
+                }));
             }
             wasm::Operator::GlobalGet { global_index } => {
-                self.stack
-                    .push(Expression::GetGlobal(GetGlobalExpression { global_index }));
+                self.stack.push(Expression::GetGlobal(GetGlobalExpression {
+                    global_index: global_index.into(),
+                }));
+            }
+            wasm::Operator::RefNull { hty } => {
+                self.stack.push(Expression::RefNull { ty: hty });
+            }
+            wasm::Operator::RefFunc { function_index } => {
+                self.stack.push(Expression::RefFunc {
+                    func_index: function_index.into(),
+                });
             }
-            wasm::Operator::Select => {
-                let cond = self.pop();
-                let false_expr = self.pop();
-                let true_expr = self.pop();
+            wasm::Operator::Select | wasm::Operator::TypedSelect { .. } => {
+                let ty = match op {
+                    wasm::Operator::TypedSelect { ty } => Some(ty),
+                    _ => None,
+                };
+                let cond = self.pop()?;
+                let false_expr = self.pop()?;
+                let true_expr = self.pop()?;
                 self.stack.push(Expression::Select(SelectExpression {
                     condition: Box::new(cond),
                     on_false: Box::new(false_expr),
                     on_true: Box::new(true_expr),
+                    ty,
                 }));
             }
             wasm::Operator::I32Load { memarg }
@@ -1089,22 +1807,204 @@ impl Builder {
             | wasm::Operator::I64Load32S { memarg }
             | wasm::Operator::I64Load32U { memarg }
             | wasm::Operator::F32Load { memarg }
-            | wasm::Operator::F64Load { memarg } => {
-                let index = self.pop();
+            | wasm::Operator::F64Load { memarg }
+            | wasm::Operator::V128Load { memarg }
+            | wasm::Operator::V128Load8x8S { memarg }
+            | wasm::Operator::V128Load8x8U { memarg }
+            | wasm::Operator::V128Load16x4S { memarg }
+            | wasm::Operator::V128Load16x4U { memarg }
+            | wasm::Operator::V128Load32x2S { memarg }
+            | wasm::Operator::V128Load32x2U { memarg }
+            | wasm::Operator::V128Load8Splat { memarg }
+            | wasm::Operator::V128Load16Splat { memarg }
+            | wasm::Operator::V128Load32Splat { memarg }
+            | wasm::Operator::V128Load64Splat { memarg }
+            | wasm::Operator::V128Load32Zero { memarg }
+            | wasm::Operator::V128Load64Zero { memarg } => {
+                let index = self.pop()?;
                 self.stack
                     .push(Expression::MemoryLoad(MemoryLoadExpression {
-                        _arg: memarg,
+                        arg: memarg,
                         kind: op.into(),
                         index: Box::new(index),
                     }));
             }
-            wasm::Operator::MemorySize { mem: _ } => {
-                self.stack.push(Expression::MemorySize);
+            // Loading a lane is a replacement of the lane with the loaded value.
+            wasm::Operator::V128Load8Lane { memarg, lane }
+            | wasm::Operator::V128Load16Lane { memarg, lane }
+            | wasm::Operator::V128Load32Lane { memarg, lane }
+            | wasm::Operator::V128Load64Lane { memarg, lane } => {
+                let (shape, kind) = match op {
+                    wasm::Operator::V128Load8Lane { .. } => {
+                        (LaneShape::I8x16, MemoryLoadKind::I32Load8U)
+                    }
+                    wasm::Operator::V128Load16Lane { .. } => {
+                        (LaneShape::I16x8, MemoryLoadKind::I32Load16U)
+                    }
+                    wasm::Operator::V128Load32Lane { .. } => {
+                        (LaneShape::I32x4, MemoryLoadKind::I32Load)
+                    }
+                    _ => (LaneShape::I64x2, MemoryLoadKind::I64Load),
+                };
+                let vector = self.pop()?;
+                let index = self.pop()?;
+                self.stack
+                    .push(Expression::ReplaceLane(ReplaceLaneExpression {
+                        shape,
+                        lane,
+                        vector: Box::new(vector),
+                        value: Box::new(Expression::MemoryLoad(MemoryLoadExpression {
+                            arg: memarg,
+                            kind,
+                            index: Box::new(index),
+                        })),
+                    }));
+            }
+            wasm::Operator::TableGet { table } => {
+                let index = self.pop()?;
+                self.stack.push(Expression::TableGet(TableGetExpression {
+                    table,
+                    index: Box::new(index),
+                }));
+            }
+            wasm::Operator::TableSize { table } => {
+                self.stack.push(Expression::TableSize { table });
+            }
+            wasm::Operator::TableGrow { table } => {
+                let delta = self.pop()?;
+                let value = self.pop()?;
+                self.stack.push(Expression::TableGrow(TableGrowExpression {
+                    table,
+                    value: Box::new(value),
+                    delta: Box::new(delta),
+                }));
+            }
+            wasm::Operator::MemorySize { mem } => {
+                self.stack.push(Expression::MemorySize { memory: mem });
+            }
+            wasm::Operator::StructNew { struct_type_index }
+            | wasm::Operator::StructNewDefault { struct_type_index } => {
+                let type_index = struct_type_index.into();
+                let default = matches!(op, wasm::Operator::StructNewDefault { .. });
+                let fields = if default {
+                    Vec::new()
+                } else {
+                    let num_fields = self.struct_type(type_index)?.fields.len();
+                    self.popn(num_fields)?
+                };
+                self.stack.push(Expression::StructNew(StructNewExpression {
+                    type_index,
+                    default,
+                    fields,
+                }));
+            }
+            wasm::Operator::StructGet {
+                struct_type_index,
+                field_index,
+            }
+            | wasm::Operator::StructGetS {
+                struct_type_index,
+                field_index,
+            }
+            | wasm::Operator::StructGetU {
+                struct_type_index,
+                field_index,
+            } => {
+                let extension = match op {
+                    wasm::Operator::StructGetS { .. } => Extension::Signed,
+                    wasm::Operator::StructGetU { .. } => Extension::Unsigned,
+                    _ => Extension::None,
+                };
+                let object = self.pop()?;
+                self.stack.push(Expression::StructGet(StructGetExpression {
+                    type_index: struct_type_index.into(),
+                    field: field_index,
+                    extension,
+                    object: Box::new(object),
+                }));
+            }
+            wasm::Operator::ArrayNew { array_type_index }
+            | wasm::Operator::ArrayNewDefault { array_type_index }
+            | wasm::Operator::ArrayNewFixed {
+                array_type_index, ..
             }
-            wasm::Operator::MemoryGrow { mem: _ } => {
-                let value = self.pop();
+            | wasm::Operator::ArrayNewData {
+                array_type_index, ..
+            }
+            | wasm::Operator::ArrayNewElem {
+                array_type_index, ..
+            } => {
+                let (kind, num_args) = match op {
+                    wasm::Operator::ArrayNew { .. } => (ArrayNewKind::Value, 2),
+                    wasm::Operator::ArrayNewDefault { .. } => (ArrayNewKind::Default, 1),
+                    wasm::Operator::ArrayNewFixed { array_size, .. } => {
+                        (ArrayNewKind::Fixed, array_size as usize)
+                    }
+                    wasm::Operator::ArrayNewData {
+                        array_data_index, ..
+                    } => (ArrayNewKind::Segment(Segment::Data(array_data_index)), 2),
+                    wasm::Operator::ArrayNewElem {
+                        array_elem_index, ..
+                    } => (ArrayNewKind::Segment(Segment::Elem(array_elem_index)), 2),
+                    _ => unreachable!(),
+                };
+                let args = self.popn(num_args)?;
+                self.stack.push(Expression::ArrayNew(ArrayNewExpression {
+                    type_index: array_type_index.into(),
+                    kind,
+                    args,
+                }));
+            }
+            wasm::Operator::ArrayGet { array_type_index }
+            | wasm::Operator::ArrayGetS { array_type_index }
+            | wasm::Operator::ArrayGetU { array_type_index } => {
+                let extension = match op {
+                    wasm::Operator::ArrayGetS { .. } => Extension::Signed,
+                    wasm::Operator::ArrayGetU { .. } => Extension::Unsigned,
+                    _ => Extension::None,
+                };
+                let index = self.pop()?;
+                let array = self.pop()?;
+                self.stack.push(Expression::ArrayGet(ArrayGetExpression {
+                    type_index: array_type_index.into(),
+                    extension,
+                    array: Box::new(array),
+                    index: Box::new(index),
+                }));
+            }
+            wasm::Operator::ArrayLen => {
+                let array = self.pop()?;
+                self.stack.push(Expression::ArrayLen(Box::new(array)));
+            }
+            wasm::Operator::RefAsNonNull => {
+                let value = self.pop()?;
+                self.stack.push(Expression::RefAsNonNull(Box::new(value)));
+            }
+            wasm::Operator::RefTestNonNull { hty }
+            | wasm::Operator::RefTestNullable { hty }
+            | wasm::Operator::RefCastNonNull { hty }
+            | wasm::Operator::RefCastNullable { hty } => {
+                let value = self.pop()?;
+                let cast = RefCastExpression {
+                    nullable: matches!(
+                        op,
+                        wasm::Operator::RefTestNullable { .. }
+                            | wasm::Operator::RefCastNullable { .. }
+                    ),
+                    heap_type: hty,
+                    value: Box::new(value),
+                };
+                self.stack.push(match op {
+                    wasm::Operator::RefTestNonNull { .. }
+                    | wasm::Operator::RefTestNullable { .. } => Expression::RefTest(cast),
+                    _ => Expression::RefCast(cast),
+                });
+            }
+            wasm::Operator::MemoryGrow { mem } => {
+                let value = self.pop()?;
                 self.stack
                     .push(Expression::MemoryGrow(MemoryGrowExpression {
+                        memory: mem,
                         value: Box::new(value),
                     }));
             }
@@ -1169,8 +2069,81 @@ impl Builder {
             | wasm::Operator::I64TruncSatF32S
             | wasm::Operator::I64TruncSatF32U
             | wasm::Operator::I64TruncSatF64S
-            | wasm::Operator::I64TruncSatF64U => {
-                let value = Box::new(self.pop());
+            | wasm::Operator::I64TruncSatF64U
+            | wasm::Operator::RefIsNull
+            | wasm::Operator::RefI31
+            | wasm::Operator::I31GetS
+            | wasm::Operator::I31GetU
+            | wasm::Operator::I8x16Splat
+            | wasm::Operator::I16x8Splat
+            | wasm::Operator::I32x4Splat
+            | wasm::Operator::I64x2Splat
+            | wasm::Operator::F32x4Splat
+            | wasm::Operator::F64x2Splat
+            | wasm::Operator::V128Not
+            | wasm::Operator::V128AnyTrue
+            | wasm::Operator::I8x16Abs
+            | wasm::Operator::I8x16Neg
+            | wasm::Operator::I8x16Popcnt
+            | wasm::Operator::I8x16AllTrue
+            | wasm::Operator::I8x16Bitmask
+            | wasm::Operator::I16x8ExtAddPairwiseI8x16S
+            | wasm::Operator::I16x8ExtAddPairwiseI8x16U
+            | wasm::Operator::I16x8Abs
+            | wasm::Operator::I16x8Neg
+            | wasm::Operator::I16x8AllTrue
+            | wasm::Operator::I16x8Bitmask
+            | wasm::Operator::I16x8ExtendLowI8x16S
+            | wasm::Operator::I16x8ExtendHighI8x16S
+            | wasm::Operator::I16x8ExtendLowI8x16U
+            | wasm::Operator::I16x8ExtendHighI8x16U
+            | wasm::Operator::I32x4ExtAddPairwiseI16x8S
+            | wasm::Operator::I32x4ExtAddPairwiseI16x8U
+            | wasm::Operator::I32x4Abs
+            | wasm::Operator::I32x4Neg
+            | wasm::Operator::I32x4AllTrue
+            | wasm::Operator::I32x4Bitmask
+            | wasm::Operator::I32x4ExtendLowI16x8S
+            | wasm::Operator::I32x4ExtendHighI16x8S
+            | wasm::Operator::I32x4ExtendLowI16x8U
+            | wasm::Operator::I32x4ExtendHighI16x8U
+            | wasm::Operator::I64x2Abs
+            | wasm::Operator::I64x2Neg
+            | wasm::Operator::I64x2AllTrue
+            | wasm::Operator::I64x2Bitmask
+            | wasm::Operator::I64x2ExtendLowI32x4S
+            | wasm::Operator::I64x2ExtendHighI32x4S
+            | wasm::Operator::I64x2ExtendLowI32x4U
+            | wasm::Operator::I64x2ExtendHighI32x4U
+            | wasm::Operator::F32x4Ceil
+            | wasm::Operator::F32x4Floor
+            | wasm::Operator::F32x4Trunc
+            | wasm::Operator::F32x4Nearest
+            | wasm::Operator::F32x4Abs
+            | wasm::Operator::F32x4Neg
+            | wasm::Operator::F32x4Sqrt
+            | wasm::Operator::F64x2Ceil
+            | wasm::Operator::F64x2Floor
+            | wasm::Operator::F64x2Trunc
+            | wasm::Operator::F64x2Nearest
+            | wasm::Operator::F64x2Abs
+            | wasm::Operator::F64x2Neg
+            | wasm::Operator::F64x2Sqrt
+            | wasm::Operator::I32x4TruncSatF32x4S
+            | wasm::Operator::I32x4TruncSatF32x4U
+            | wasm::Operator::F32x4ConvertI32x4S
+            | wasm::Operator::F32x4ConvertI32x4U
+            | wasm::Operator::I32x4TruncSatF64x2SZero
+            | wasm::Operator::I32x4TruncSatF64x2UZero
+            | wasm::Operator::F64x2ConvertLowI32x4S
+            | wasm::Operator::F64x2ConvertLowI32x4U
+            | wasm::Operator::F32x4DemoteF64x2Zero
+            | wasm::Operator::I32x4RelaxedTruncF32x4S
+            | wasm::Operator::I32x4RelaxedTruncF32x4U
+            | wasm::Operator::I32x4RelaxedTruncF64x2SZero
+            | wasm::Operator::I32x4RelaxedTruncF64x2UZero
+            | wasm::Operator::F64x2PromoteLowF32x4 => {
+                let value = Box::new(self.pop()?);
                 self.stack.push(Expression::Unary(op.into(), value));
             }
 
@@ -1250,33 +2223,297 @@ impl Builder {
             | wasm::Operator::F64Mul
             | wasm::Operator::F64Div
             | wasm::Operator::F64Min
-            | wasm::Operator::F64Max => {
-                let rhs = Box::new(self.pop());
-                let lhs = Box::new(self.pop());
+            | wasm::Operator::F64Max
+            | wasm::Operator::RefEq
+            | wasm::Operator::I8x16Swizzle
+            | wasm::Operator::I8x16Eq
+            | wasm::Operator::I8x16Ne
+            | wasm::Operator::I8x16LtS
+            | wasm::Operator::I8x16LtU
+            | wasm::Operator::I8x16GtS
+            | wasm::Operator::I8x16GtU
+            | wasm::Operator::I8x16LeS
+            | wasm::Operator::I8x16LeU
+            | wasm::Operator::I8x16GeS
+            | wasm::Operator::I8x16GeU
+            | wasm::Operator::I16x8Eq
+            | wasm::Operator::I16x8Ne
+            | wasm::Operator::I16x8LtS
+            | wasm::Operator::I16x8LtU
+            | wasm::Operator::I16x8GtS
+            | wasm::Operator::I16x8GtU
+            | wasm::Operator::I16x8LeS
+            | wasm::Operator::I16x8LeU
+            | wasm::Operator::I16x8GeS
+            | wasm::Operator::I16x8GeU
+            | wasm::Operator::I32x4Eq
+            | wasm::Operator::I32x4Ne
+            | wasm::Operator::I32x4LtS
+            | wasm::Operator::I32x4LtU
+            | wasm::Operator::I32x4GtS
+            | wasm::Operator::I32x4GtU
+            | wasm::Operator::I32x4LeS
+            | wasm::Operator::I32x4LeU
+            | wasm::Operator::I32x4GeS
+            | wasm::Operator::I32x4GeU
+            | wasm::Operator::I64x2Eq
+            | wasm::Operator::I64x2Ne
+            | wasm::Operator::I64x2LtS
+            | wasm::Operator::I64x2GtS
+            | wasm::Operator::I64x2LeS
+            | wasm::Operator::I64x2GeS
+            | wasm::Operator::F32x4Eq
+            | wasm::Operator::F32x4Ne
+            | wasm::Operator::F32x4Lt
+            | wasm::Operator::F32x4Gt
+            | wasm::Operator::F32x4Le
+            | wasm::Operator::F32x4Ge
+            | wasm::Operator::F64x2Eq
+            | wasm::Operator::F64x2Ne
+            | wasm::Operator::F64x2Lt
+            | wasm::Operator::F64x2Gt
+            | wasm::Operator::F64x2Le
+            | wasm::Operator::F64x2Ge
+            | wasm::Operator::V128And
+            | wasm::Operator::V128AndNot
+            | wasm::Operator::V128Or
+            | wasm::Operator::V128Xor
+            | wasm::Operator::I8x16NarrowI16x8S
+            | wasm::Operator::I8x16NarrowI16x8U
+            | wasm::Operator::I8x16Shl
+            | wasm::Operator::I8x16ShrS
+            | wasm::Operator::I8x16ShrU
+            | wasm::Operator::I8x16Add
+            | wasm::Operator::I8x16AddSatS
+            | wasm::Operator::I8x16AddSatU
+            | wasm::Operator::I8x16Sub
+            | wasm::Operator::I8x16SubSatS
+            | wasm::Operator::I8x16SubSatU
+            | wasm::Operator::I8x16MinS
+            | wasm::Operator::I8x16MinU
+            | wasm::Operator::I8x16MaxS
+            | wasm::Operator::I8x16MaxU
+            | wasm::Operator::I8x16AvgrU
+            | wasm::Operator::I16x8Q15MulrSatS
+            | wasm::Operator::I16x8NarrowI32x4S
+            | wasm::Operator::I16x8NarrowI32x4U
+            | wasm::Operator::I16x8Shl
+            | wasm::Operator::I16x8ShrS
+            | wasm::Operator::I16x8ShrU
+            | wasm::Operator::I16x8Add
+            | wasm::Operator::I16x8AddSatS
+            | wasm::Operator::I16x8AddSatU
+            | wasm::Operator::I16x8Sub
+            | wasm::Operator::I16x8SubSatS
+            | wasm::Operator::I16x8SubSatU
+            | wasm::Operator::I16x8Mul
+            | wasm::Operator::I16x8MinS
+            | wasm::Operator::I16x8MinU
+            | wasm::Operator::I16x8MaxS
+            | wasm::Operator::I16x8MaxU
+            | wasm::Operator::I16x8AvgrU
+            | wasm::Operator::I16x8ExtMulLowI8x16S
+            | wasm::Operator::I16x8ExtMulHighI8x16S
+            | wasm::Operator::I16x8ExtMulLowI8x16U
+            | wasm::Operator::I16x8ExtMulHighI8x16U
+            | wasm::Operator::I32x4Shl
+            | wasm::Operator::I32x4ShrS
+            | wasm::Operator::I32x4ShrU
+            | wasm::Operator::I32x4Add
+            | wasm::Operator::I32x4Sub
+            | wasm::Operator::I32x4Mul
+            | wasm::Operator::I32x4MinS
+            | wasm::Operator::I32x4MinU
+            | wasm::Operator::I32x4MaxS
+            | wasm::Operator::I32x4MaxU
+            | wasm::Operator::I32x4DotI16x8S
+            | wasm::Operator::I32x4ExtMulLowI16x8S
+            | wasm::Operator::I32x4ExtMulHighI16x8S
+            | wasm::Operator::I32x4ExtMulLowI16x8U
+            | wasm::Operator::I32x4ExtMulHighI16x8U
+            | wasm::Operator::I64x2Shl
+            | wasm::Operator::I64x2ShrS
+            | wasm::Operator::I64x2ShrU
+            | wasm::Operator::I64x2Add
+            | wasm::Operator::I64x2Sub
+            | wasm::Operator::I64x2Mul
+            | wasm::Operator::I64x2ExtMulLowI32x4S
+            | wasm::Operator::I64x2ExtMulHighI32x4S
+            | wasm::Operator::I64x2ExtMulLowI32x4U
+            | wasm::Operator::I64x2ExtMulHighI32x4U
+            | wasm::Operator::I8x16RelaxedSwizzle
+            | wasm::Operator::F32x4RelaxedMin
+            | wasm::Operator::F32x4RelaxedMax
+            | wasm::Operator::F64x2RelaxedMin
+            | wasm::Operator::F64x2RelaxedMax
+            | wasm::Operator::I16x8RelaxedQ15mulrS
+            | wasm::Operator::I16x8RelaxedDotI8x16I7x16S
+            | wasm::Operator::F32x4Add
+            | wasm::Operator::F32x4Sub
+            | wasm::Operator::F32x4Mul
+            | wasm::Operator::F32x4Div
+            | wasm::Operator::F32x4Min
+            | wasm::Operator::F32x4Max
+            | wasm::Operator::F32x4PMin
+            | wasm::Operator::F32x4PMax
+            | wasm::Operator::F64x2Add
+            | wasm::Operator::F64x2Sub
+            | wasm::Operator::F64x2Mul
+            | wasm::Operator::F64x2Div
+            | wasm::Operator::F64x2Min
+            | wasm::Operator::F64x2Max
+            | wasm::Operator::F64x2PMin
+            | wasm::Operator::F64x2PMax => {
+                let rhs = Box::new(self.pop()?);
+                let lhs = Box::new(self.pop()?);
                 self.stack.push(Expression::Binary(op.into(), lhs, rhs));
             }
 
-            _ => todo!("unimplemented op: {:?}", op),
+            // SIMD lane operators
+            wasm::Operator::I8x16ExtractLaneS { lane }
+            | wasm::Operator::I8x16ExtractLaneU { lane }
+            | wasm::Operator::I16x8ExtractLaneS { lane }
+            | wasm::Operator::I16x8ExtractLaneU { lane }
+            | wasm::Operator::I32x4ExtractLane { lane }
+            | wasm::Operator::I64x2ExtractLane { lane }
+            | wasm::Operator::F32x4ExtractLane { lane }
+            | wasm::Operator::F64x2ExtractLane { lane } => {
+                let (shape, signed) = match op {
+                    wasm::Operator::I8x16ExtractLaneS { .. } => (LaneShape::I8x16, true),
+                    wasm::Operator::I8x16ExtractLaneU { .. } => (LaneShape::I8x16, false),
+                    wasm::Operator::I16x8ExtractLaneS { .. } => (LaneShape::I16x8, true),
+                    wasm::Operator::I16x8ExtractLaneU { .. } => (LaneShape::I16x8, false),
+                    wasm::Operator::I32x4ExtractLane { .. } => (LaneShape::I32x4, false),
+                    wasm::Operator::I64x2ExtractLane { .. } => (LaneShape::I64x2, false),
+                    wasm::Operator::F32x4ExtractLane { .. } => (LaneShape::F32x4, false),
+                    _ => (LaneShape::F64x2, false),
+                };
+                let vector = self.pop()?;
+                self.stack
+                    .push(Expression::ExtractLane(ExtractLaneExpression {
+                        shape,
+                        signed,
+                        lane,
+                        vector: Box::new(vector),
+                    }));
+            }
+            wasm::Operator::I8x16ReplaceLane { lane }
+            | wasm::Operator::I16x8ReplaceLane { lane }
+            | wasm::Operator::I32x4ReplaceLane { lane }
+            | wasm::Operator::I64x2ReplaceLane { lane }
+            | wasm::Operator::F32x4ReplaceLane { lane }
+            | wasm::Operator::F64x2ReplaceLane { lane } => {
+                let shape = match op {
+                    wasm::Operator::I8x16ReplaceLane { .. } => LaneShape::I8x16,
+                    wasm::Operator::I16x8ReplaceLane { .. } => LaneShape::I16x8,
+                    wasm::Operator::I32x4ReplaceLane { .. } => LaneShape::I32x4,
+                    wasm::Operator::I64x2ReplaceLane { .. } => LaneShape::I64x2,
+                    wasm::Operator::F32x4ReplaceLane { .. } => LaneShape::F32x4,
+                    _ => LaneShape::F64x2,
+                };
+                let value = self.pop()?;
+                let vector = self.pop()?;
+                self.stack
+                    .push(Expression::ReplaceLane(ReplaceLaneExpression {
+                        shape,
+                        lane,
+                        vector: Box::new(vector),
+                        value: Box::new(value),
+                    }));
+            }
+            wasm::Operator::I8x16Shuffle { lanes } => {
+                let rhs = Box::new(self.pop()?);
+                let lhs = Box::new(self.pop()?);
+                self.stack
+                    .push(Expression::Shuffle(ShuffleExpression { lanes, lhs, rhs }));
+            }
+
+            // Ternary operators
+            wasm::Operator::V128Bitselect
+            | wasm::Operator::F32x4RelaxedMadd
+            | wasm::Operator::F32x4RelaxedNmadd
+            | wasm::Operator::F64x2RelaxedMadd
+            | wasm::Operator::F64x2RelaxedNmadd
+            | wasm::Operator::I8x16RelaxedLaneselect
+            | wasm::Operator::I16x8RelaxedLaneselect
+            | wasm::Operator::I32x4RelaxedLaneselect
+            | wasm::Operator::I64x2RelaxedLaneselect
+            | wasm::Operator::I32x4RelaxedDotI8x16I7x16AddS => {
+                let c = Box::new(self.pop()?);
+                let b = Box::new(self.pop()?);
+                let a = Box::new(self.pop()?);
+                self.stack.push(Expression::Ternary(op.into(), a, b, c));
+            }
+
+            _ => internal_bail!(self, "unsupported operator: {:?}", op),
         }
+
+        Ok(())
     }
 
-    fn finish(self) -> anyhow::Result<Func> {
-        Ok(Func {
+    fn finish(self) -> Func {
+        Func {
             index: self.func_index,
-            ty: self.type_of_func(self.func_index).clone(),
+            ty: self.func_type,
             locals: self.locals,
             blocks: self.blocks,
             entry_block: self.start_block,
-        })
+            error: None,
+            names: Arc::default(),
+            multi_memory: false,
+            data_ranges: Vec::new(),
+            callee_names: HashMap::new(),
+            deadline: None,
+            over_budget: None,
+            structure: None,
+            frame: None,
+            signedness: HashMap::new(),
+            body_range: 0..0,
+            exports: Vec::new(),
+            is_start: false,
+            show_offsets: false,
+            trailing_instructions: Vec::new(),
+            pass_dumps: Vec::new(),
+        }
+    }
+}
+
+fn lookup_func_type(
+    resources: &wasm::ValidatorResources,
+    type_index: TypeIdx,
+) -> Option<&wasm::FuncType> {
+    match &resources
+        .sub_type_at(type_index.as_u32())?
+        .composite_type
+        .inner
+    {
+        wasm::CompositeInnerType::Func(func_type) => Some(func_type),
+        _ => None,
+    }
+}
+
+// Keep validating the rest of a function body after decoding it failed, so
+// that invalid code is still reported as an error for the whole module.
+fn validate_remaining(
+    validator: &mut wasm::FuncValidator<Arc<wasm::ValidatorResources>>,
+    operator_reader: &mut wasm::OperatorsReader,
+) -> anyhow::Result<()> {
+    while !operator_reader.eof() {
+        let (op, offset) = operator_reader.read_with_offset()?;
+        validator.op(offset, &op)?;
     }
+    operator_reader.ensure_end()?;
+    validator.finish(operator_reader.original_position())?;
+    Ok(())
 }
 
 impl Func {
-    pub fn decode(
+    pub(crate) fn decode(
         body: wasm::FunctionBody,
-        func_to_validate: wasm::FuncToValidate<wasm::ValidatorResources>,
+        func_to_validate: wasm::FuncToValidate<Arc<wasm::ValidatorResources>>,
+        source_map: Option<&SourceMap>,
     ) -> anyhow::Result<Self> {
-        let index = func_to_validate.index;
+        let index = FuncIdx::from(func_to_validate.index);
         let mut body_validator =
             func_to_validate.into_validator(FuncValidatorAllocations::default());
 
@@ -1296,18 +2533,175 @@ impl Func {
             }
             body_validator.define_locals(body.get_binary_reader().current_position(), count, ty)?;
         }
-
-        let mut builder = Builder::new(index, locals, body_validator);
+        // The validator canonicalizes concrete reference types to type ids,
+        // and the builder compares its types with the validator's.
+        let num_params = body_validator.len_locals() - locals.len() as u32;
+        for (i, local) in locals.iter_mut().enumerate() {
+            if let Some(ty) = body_validator.get_local_type(num_params + i as u32) {
+                local.ty = ty;
+            }
+        }
 
         let mut operator_reader = body.get_operators_reader()?;
+
+        let type_index = body_validator
+            .resources()
+            .type_index_of_function(index.as_u32())
+            .map(TypeIdx::from);
+        let func_type = type_index
+            .and_then(|type_index| lookup_func_type(body_validator.resources(), type_index))
+            .cloned();
+        let (Some(type_index), Some(func_type)) = (type_index, func_type) else {
+            validate_remaining(&mut body_validator, &mut operator_reader)?;
+            let error = DecompileError::Internal {
+                func: index,
+                offset: operator_reader.original_position(),
+                detail: "function has no function type".to_string(),
+            };
+            return Ok(Func::with_error(
+                index,
+                wasm::FuncType::new([], []),
+                locals,
+                error,
+            ));
+        };
+
+        let num_locals = func_type.params().len() + locals.len();
+        let mut builder = Builder::new(
+            index,
+            type_index,
+            func_type,
+            locals,
+            body_validator,
+            operator_reader.original_position(),
+            source_map,
+        );
+
         while !operator_reader.eof() {
             let (op, offset) = operator_reader.read_with_offset()?;
-            builder.visit_op(offset, operator_reader.original_position(), op.clone())?;
-            // builder.dump_state(op);
-            builder.check_invariants();
+            builder.validator.op(offset, &op)?;
+
+            let block_index = builder.current_block;
+            let result = builder
+                .visit_op(offset, operator_reader.original_position(), op.clone())
+                .map(|()| builder.note_terminator(block_index))
+                .map(|()| builder.end_op())
+                .and_then(|()| builder.check_invariants());
+            builder.trace_state(&op);
+            if let Err(error) = result {
+                tracing::debug!(%error, "cannot decompile the function");
+                validate_remaining(&mut builder.validator, &mut operator_reader)?;
+                let mut locals = builder.locals;
+                locals.truncate(num_locals);
+                return Ok(Func::with_error(index, builder.func_type, locals, error));
+            }
         }
         operator_reader.ensure_end()?;
+        builder
+            .validator
+            .finish(operator_reader.original_position())?;
+
+        Ok(builder.finish())
+    }
+
+    // Types are canonicalized to type ids while decoding. Map them back to
+    // the module's type indices so that they can be printed.
+    pub(crate) fn remap_type_ids(
+        &mut self,
+        type_indices: &HashMap<wasm::types::CoreTypeId, TypeIdx>,
+    ) {
+        let remap = |ty: &wasm::ValType| remap_type_id(*ty, type_indices);
+        self.ty = wasm::FuncType::new(
+            self.ty.params().iter().map(remap),
+            self.ty.results().iter().map(remap),
+        );
+        for local in &mut self.locals {
+            local.ty = remap(&local.ty);
+        }
+        for block in self.blocks.values_mut() {
+            for param in &mut block.params {
+                *param = remap(param);
+            }
+        }
+    }
+
+    // Validates the body of a function that `DecodeOptions::only` leaves
+    // out, without decoding it. It's kept with its signature and no blocks,
+    // like a function that couldn't be decompiled.
+    pub(crate) fn skip(
+        body: wasm::FunctionBody,
+        func_to_validate: wasm::FuncToValidate<Arc<wasm::ValidatorResources>>,
+    ) -> anyhow::Result<Self> {
+        let index = FuncIdx::from(func_to_validate.index);
+        let mut validator = func_to_validate.into_validator(FuncValidatorAllocations::default());
+        validator.validate(&body)?;
+        let func_type = (validator.resources())
+            .type_index_of_function(index.as_u32())
+            .and_then(|type_index| {
+                lookup_func_type(validator.resources(), TypeIdx::from(type_index))
+            })
+            .cloned()
+            .unwrap_or_else(|| wasm::FuncType::new([], []));
+        let params = (func_type.params().iter().enumerate())
+            .map(|(i, ty)| Local {
+                ty: *ty,
+                name: format!("arg{}", i),
+            })
+            .collect();
+        let error = DecompileError::Skipped { func: index };
+        Ok(Func::with_error(index, func_type, params, error))
+    }
+
+    // A function that couldn't be decompiled. It keeps its signature so it can
+    // still be printed, but has no blocks.
+    fn with_error(
+        index: FuncIdx,
+        ty: wasm::FuncType,
+        locals: Vec<Local>,
+        error: DecompileError,
+    ) -> Self {
+        Func {
+            index,
+            ty,
+            locals,
+            blocks: HashMap::new(),
+            entry_block: BlockIndex(0),
+            error: Some(error),
+            names: Arc::default(),
+            multi_memory: false,
+            data_ranges: Vec::new(),
+            callee_names: HashMap::new(),
+            deadline: None,
+            over_budget: None,
+            structure: None,
+            frame: None,
+            signedness: HashMap::new(),
+            body_range: 0..0,
+            exports: Vec::new(),
+            is_start: false,
+            show_offsets: false,
+            trailing_instructions: Vec::new(),
+            pass_dumps: Vec::new(),
+        }
+    }
+}
 
-        builder.finish()
+fn remap_type_id(
+    ty: wasm::ValType,
+    type_indices: &HashMap<wasm::types::CoreTypeId, TypeIdx>,
+) -> wasm::ValType {
+    let wasm::ValType::Ref(ref_type) = ty else {
+        return ty;
+    };
+    let wasm::HeapType::Concrete(wasm::UnpackedIndex::Id(id)) = ref_type.heap_type() else {
+        return ty;
+    };
+    let Some(type_index) = type_indices.get(&id) else {
+        return ty;
+    };
+    let heap_type = wasm::HeapType::Concrete(wasm::UnpackedIndex::Module(type_index.as_u32()));
+    match wasm::RefType::new(ref_type.is_nullable(), heap_type) {
+        Some(ref_type) => wasm::ValType::Ref(ref_type),
+        None => ty,
     }
 }
@@ -0,0 +1,176 @@
+use crate::ir::effects::Effects;
+use crate::ir::*;
+
+impl Func {
+    // Hoists subexpressions nested more than `max_depth` deep into temps
+    // assigned just before the statement that uses them, so that with a
+    // depth of 3 `x = a + b * (c - d)` reads as `temp0 = c - d` followed by
+    // `x = a + b * temp0`. Likewise, an expression with more than
+    // `max_width` operands that aren't just a local or a constant has the
+    // leftmost of them hoisted, so that with a width of 1 `f(a + 1, g(b))`
+    // reads as `temp0 = a + 1` followed by `f(temp0, g(b))`. Only operators
+    // and calls whose result type is known, from the operator or from
+    // `call_results`, are hoisted, and only when their effects commute with
+    // those of whatever comes before them in the statement. Loop conditions
+    // are left alone, since they're evaluated on every iteration.
+    pub(crate) fn limit_expressions(
+        &mut self,
+        max_depth: Option<usize>,
+        max_width: Option<usize>,
+        call_results: &HashMap<FuncIdx, wasm::ValType>,
+    ) {
+        let next_temp = (self.locals.iter())
+            .filter_map(|local| local.name.strip_prefix("temp")?.parse::<u32>().ok())
+            .max()
+            .map_or(0, |temp| temp + 1);
+        let mut hoister = Hoister {
+            locals: &mut self.locals,
+            next_temp,
+            // A whole expression is at depth 1, so anything shallower than 2
+            // would hoist it forever.
+            max_depth: max_depth.map_or(usize::MAX, |max_depth| max_depth.max(2)),
+            max_width: max_width.unwrap_or(usize::MAX),
+            call_results,
+        };
+        for block in self.blocks.values_mut() {
+            let statements = std::mem::take(&mut block.statements);
+            block.statements = hoister.statements(statements);
+            // A `br_if`'s values are evaluated before its condition.
+            let is_br_if = matches!(block.terminator, Terminator::BrIf(..));
+            let mut exprs = block.terminator.expressions_mut();
+            if is_br_if {
+                exprs.rotate_left(1);
+            }
+            let mut before = Effects::default();
+            for expr in exprs {
+                hoister.expression(expr, 1, false, &mut before, &mut block.statements);
+            }
+        }
+    }
+}
+
+struct Hoister<'a> {
+    locals: &'a mut Vec<Local>,
+    next_temp: u32,
+    max_depth: usize,
+    max_width: usize,
+    call_results: &'a HashMap<FuncIdx, wasm::ValType>,
+}
+
+impl Hoister<'_> {
+    fn statements(&mut self, statements: Vec<Statement>) -> Vec<Statement> {
+        let mut result = Vec::with_capacity(statements.len());
+        for mut stmt in statements {
+            for body in stmt.bodies_mut() {
+                *body = self.statements(std::mem::take(body));
+            }
+            if !matches!(stmt, Statement::Loop(_)) {
+                let mut before = Effects::default();
+                for expr in stmt.expressions_mut() {
+                    self.expression(expr, 1, false, &mut before, &mut result);
+                }
+            }
+            result.push(stmt);
+        }
+        result
+    }
+
+    // Limits the depth of `expr`, which is `depth` deep in its statement,
+    // adding the temps it needs to `hoisted`. `conditional` is whether it's
+    // only evaluated some of the time, as the right of `&&` is, and
+    // `before` the effects of what's evaluated before it.
+    fn expression(
+        &mut self,
+        expr: &mut Expression,
+        depth: usize,
+        conditional: bool,
+        before: &mut Effects,
+        hoisted: &mut Vec<Statement>,
+    ) {
+        if depth >= self.max_depth
+            && !expr.operands().is_empty()
+            && self.try_hoist(expr, conditional, *before, hoisted)
+        {
+            return;
+        }
+        let mut excess = (expr.operands().into_iter())
+            .filter(|operand| is_compound(operand))
+            .count()
+            .saturating_sub(self.max_width);
+        // The right of `&&` and `||` is only evaluated some of the time.
+        let is_logical = matches!(expr, Expression::Logical(..));
+        for (i, operand) in expr.operands_mut().into_iter().enumerate() {
+            let conditional = conditional || is_logical && i == 1;
+            if excess > 0
+                && is_compound(operand)
+                && self.try_hoist(operand, conditional, *before, hoisted)
+            {
+                excess -= 1;
+                continue;
+            }
+            self.expression(operand, depth + 1, conditional, before, hoisted);
+        }
+        *before = before.union(expr.own_effects());
+    }
+
+    // Hoists `expr` if it can be evaluated before the statement: it
+    // commutes with what's evaluated `before` it, is evaluated every time
+    // unless it's pure, and its type is known.
+    fn try_hoist(
+        &mut self,
+        expr: &mut Expression,
+        conditional: bool,
+        before: Effects,
+        hoisted: &mut Vec<Statement>,
+    ) -> bool {
+        if !expr.effects().commutes_with(before) || conditional && !expr.is_pure() {
+            return false;
+        }
+        match self.result_type(expr) {
+            Some(ty) => {
+                self.hoist(expr, ty, hoisted);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Replaces `expr` with a new temp, assigned it just before.
+    fn hoist(&mut self, expr: &mut Expression, ty: wasm::ValType, hoisted: &mut Vec<Statement>) {
+        let local_index = LocalIdx::from(self.locals.len() as u32);
+        self.locals.push(Local {
+            ty,
+            name: format!("temp{}", self.next_temp),
+        });
+        self.next_temp += 1;
+        let get = Expression::GetLocal(GetLocalExpression { local_index });
+        let mut value = Box::new(std::mem::replace(expr, get));
+        self.expression(&mut value, 1, false, &mut Effects::default(), hoisted);
+        hoisted.push(Statement::LocalSet(LocalSetStatement {
+            index: local_index,
+            value,
+        }));
+    }
+}
+
+impl Hoister<'_> {
+    // The type of `expr`, if it can be told from the operator, or from the
+    // signature of the function it calls.
+    fn result_type(&self, expr: &Expression) -> Option<wasm::ValType> {
+        Some(match expr {
+            Expression::Unary(op, _) => op.result_type(),
+            Expression::Binary(op, _, _) => op.result_type(),
+            Expression::Ternary(op, _, _, _) => op.result_type(),
+            Expression::Logical(..) => wasm::ValType::I32,
+            Expression::MemoryLoad(load) => load.kind.result_type(),
+            Expression::Call(call) => *self.call_results.get(&call.func_index)?,
+            _ => return None,
+        })
+    }
+}
+
+// Whether `expr` computes something, rather than just being a local or a
+// constant. Calls count even without arguments.
+fn is_compound(expr: &Expression) -> bool {
+    !expr.operands().is_empty() || matches!(expr, Expression::Call(_))
+}
@@ -0,0 +1,126 @@
+use similar::TextDiff;
+
+use crate::ir::*;
+
+// A defined function as printed, for comparing with another module's.
+struct Printed<'m> {
+    func: &'m Func,
+    // The name it has in the name section, an export or a symbol map.
+    name: Option<&'m str>,
+    text: String,
+}
+
+impl Printed<'_> {
+    // The function's text without its header, which has its name in it.
+    fn body(&self) -> &str {
+        self.text.split_once('\n').map_or("", |(_, body)| body)
+    }
+}
+
+// Which functions of two modules have been paired up.
+struct Matching {
+    old_matched: Vec<bool>,
+    new_matched: Vec<bool>,
+    // Indices into the old and new functions.
+    pairs: Vec<(usize, usize)>,
+}
+
+impl Matching {
+    // Pairs each function not yet matched with the first of the other
+    // module's left that `same` says it is.
+    fn match_up(
+        &mut self,
+        old_funcs: &[Printed],
+        new_funcs: &[Printed],
+        same: impl Fn(&Printed, &Printed) -> bool,
+    ) {
+        for (i, old_func) in old_funcs.iter().enumerate() {
+            if self.old_matched[i] {
+                continue;
+            }
+            let found = (0..new_funcs.len())
+                .find(|j| !self.new_matched[*j] && same(old_func, &new_funcs[*j]));
+            if let Some(j) = found {
+                self.old_matched[i] = true;
+                self.new_matched[j] = true;
+                self.pairs.push((i, j));
+            }
+        }
+    }
+}
+
+impl Module {
+    fn printed_defined_funcs(&self) -> anyhow::Result<Vec<Printed<'_>>> {
+        (self.funcs.iter())
+            .map(|func| {
+                Ok(Printed {
+                    func,
+                    name: self.names.func(func.index),
+                    text: func.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Writes a unified diff of each function that differs between this
+    /// module and `new`. Functions are matched up by name first, which
+    /// covers exports, then those left by identical bodies, and then those
+    /// left without a name by index. Functions without a match are diffed
+    /// against nothing, as removed or added. Only functions are compared.
+    pub fn write_diff(&self, new: &Module, mut output: impl std::io::Write) -> anyhow::Result<()> {
+        let old_funcs = self.printed_defined_funcs()?;
+        let new_funcs = new.printed_defined_funcs()?;
+        let mut matching = Matching {
+            old_matched: vec![false; old_funcs.len()],
+            new_matched: vec![false; new_funcs.len()],
+            pairs: vec![],
+        };
+        matching.match_up(&old_funcs, &new_funcs, |old, new| {
+            old.name.is_some() && old.name == new.name
+        });
+        matching.match_up(&old_funcs, &new_funcs, |old, new| old.body() == new.body());
+        matching.match_up(&old_funcs, &new_funcs, |old, new| {
+            old.name.is_none() && new.name.is_none() && old.func.index == new.func.index
+        });
+
+        // Matched and removed functions in their old order, then added ones.
+        let mut pairs: Vec<(Option<usize>, Option<usize>)> = (matching.pairs.iter())
+            .map(|(i, j)| (Some(*i), Some(*j)))
+            .chain(
+                (0..old_funcs.len())
+                    .filter(|i| !matching.old_matched[*i])
+                    .map(|i| (Some(i), None)),
+            )
+            .collect();
+        pairs.sort_by_key(|(i, _)| *i);
+        pairs.extend(
+            (0..new_funcs.len())
+                .filter(|j| !matching.new_matched[*j])
+                .map(|j| (None, Some(j))),
+        );
+
+        for (old_func, new_func) in pairs {
+            let old_func = old_func.map(|i| &old_funcs[i]);
+            let new_func = new_func.map(|j| &new_funcs[j]);
+            let old_text = old_func.map_or("", |func| func.text.as_str());
+            let new_text = new_func.map_or("", |func| func.text.as_str());
+            if old_text == new_text {
+                continue;
+            }
+            let old_label = old_func.map_or("/dev/null".to_string(), |func| {
+                format!("a/{}", func.func.name())
+            });
+            let new_label = new_func.map_or("/dev/null".to_string(), |func| {
+                format!("b/{}", func.func.name())
+            });
+            write!(
+                output,
+                "{}",
+                TextDiff::from_lines(old_text, new_text)
+                    .unified_diff()
+                    .header(&old_label, &new_label)
+            )?;
+        }
+        Ok(())
+    }
+}
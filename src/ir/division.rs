@@ -0,0 +1,297 @@
+use crate::ir::passes::{count_local_accesses, count_local_reads};
+use crate::ir::*;
+
+impl Func {
+    // Recovers division and remainder by a constant from the multiply and
+    // shift sequences compilers replace them with, so that
+    // `wrap_i64((extend_i32_u(x) * 0xcccccccd) >>_u 35)` prints as `x / 10`
+    // and `x - (x / 10) * 10` as `x % 10`. Signed division needs the product
+    // twice, so it's assigned to a local in the statement before, which is
+    // dropped once both reads are replaced. A sequence is only recovered
+    // when it's exact for every 32-bit value. Returns whether any of those
+    // assignments were dropped.
+    pub(crate) fn recover_divisions(&mut self) -> bool {
+        let mut reads = HashMap::new();
+        let mut writes = HashMap::new();
+        for block in self.blocks.values() {
+            count_local_accesses(&block.statements, &mut reads, &mut writes);
+            for expr in block.terminator.expressions() {
+                count_local_reads(expr, &mut reads);
+            }
+        }
+        let products = Products { reads, writes };
+        let mut dropped = false;
+        for block in self.blocks.values_mut() {
+            dropped |= recover_in(
+                &mut block.statements,
+                Some(&mut block.terminator),
+                &products,
+            );
+        }
+        dropped
+    }
+}
+
+// How often each local is read and written, to tell which hold nothing but
+// the product a signed division reads twice.
+struct Products {
+    reads: HashMap<LocalIdx, usize>,
+    writes: HashMap<LocalIdx, usize>,
+}
+
+impl Products {
+    // The product `stmt` assigns, if it's `t = extend_i32_s(x) * M` and `t`
+    // is read twice, by a signed division, and written nowhere else.
+    fn assigned(&self, stmt: &Statement) -> Option<Product> {
+        let Statement::LocalSet(stmt) = stmt else {
+            return None;
+        };
+        if self.writes.get(&stmt.index) != Some(&1) || self.reads.get(&stmt.index) != Some(&2) {
+            return None;
+        }
+        let (x, magic) = extended_product(&stmt.value, true)?;
+        let Expression::GetLocal(x) = x else {
+            return None;
+        };
+        Some(Product {
+            local_index: stmt.index,
+            x: x.local_index,
+            magic,
+        })
+    }
+}
+
+// `local_index` was just assigned `extend_i32_s(x) * magic`.
+struct Product {
+    local_index: LocalIdx,
+    x: LocalIdx,
+    magic: u64,
+}
+
+fn recover_in(
+    statements: &mut Vec<Statement>,
+    terminator: Option<&mut Terminator>,
+    products: &Products,
+) -> bool {
+    // The product assigned by the last statement, and where.
+    let mut product: Option<(usize, Product)> = None;
+    let mut used = vec![];
+    let mut dropped = false;
+    for (i, stmt) in statements.iter_mut().enumerate() {
+        for body in stmt.bodies_mut() {
+            dropped |= recover_in(body, None, products);
+        }
+        let mut found = false;
+        for expr in stmt.expressions_mut() {
+            found |= recover(expr, product.as_ref().map(|(_, product)| product));
+        }
+        if found {
+            used.push(product.as_ref().unwrap().0);
+        }
+        // Source locations don't count as statements in between.
+        if !matches!(stmt, Statement::Location(_)) {
+            product = products.assigned(stmt).map(|product| (i, product));
+        }
+    }
+    if let Some(terminator) = terminator {
+        let mut found = false;
+        for expr in terminator.expressions_mut() {
+            found |= recover(expr, product.as_ref().map(|(_, product)| product));
+        }
+        if found {
+            used.push(product.unwrap().0);
+        }
+    }
+    dropped |= !used.is_empty();
+    for i in used.into_iter().rev() {
+        statements.remove(i);
+    }
+    dropped
+}
+
+// Recovers the divisions and remainders in `expr`, innermost first.
+// Returns whether a signed division read `product`.
+fn recover(expr: &mut Expression, product: Option<&Product>) -> bool {
+    let mut found = false;
+    for operand in expr.operands_mut() {
+        found |= recover(operand, product);
+    }
+    if let Some((x, divisor)) = unsigned_division(expr).map(|(x, divisor)| (x.clone(), divisor)) {
+        *expr = Expression::Binary(
+            BinaryExpression::I32DivU,
+            Box::new(x),
+            Box::new(Expression::I32Const {
+                value: divisor as i32,
+            }),
+        );
+    } else if let Some(divisor) = product.and_then(|product| signed_division(expr, product)) {
+        let local_index = product.unwrap().x;
+        *expr = Expression::Binary(
+            BinaryExpression::I32DivS,
+            Box::new(Expression::GetLocal(GetLocalExpression { local_index })),
+            Box::new(Expression::I32Const {
+                value: divisor as i32,
+            }),
+        );
+        found = true;
+    }
+    if let Some(remainder) = remainder(expr) {
+        *expr = remainder;
+    }
+    found
+}
+
+// `wrap_i64((extend_i32_u(x) * magic) >>_u shift)`, which is `x / d` for
+// the `d` the magic number is for. A divisor with trailing zeros may have
+// them shifted out of `x` first, leaving fewer bits to divide.
+fn unsigned_division(expr: &Expression) -> Option<(&Expression, u64)> {
+    let (product, shift) = wrapped_shift(expr, |op| matches!(op, BinaryExpression::I64ShrU))?;
+    let (x, magic) = extended_product(product, false)?;
+    let (x, bits, pre_shift) = match x {
+        Expression::Binary(BinaryExpression::I32ShrU, y, k) => match **k {
+            Expression::I32Const { value: k } if (1..32).contains(&k) => (&**y, 32 - k as u32, k),
+            _ => (x, 32, 0),
+        },
+        _ => (x, 32, 0),
+    };
+    let divisor = magic_divisor(magic, shift, bits, false)? << pre_shift;
+    (divisor <= u32::MAX as u64).then_some((x, divisor))
+}
+
+// `wrap_i64(t >>_u 63) + wrap_i64(t >>_s shift)`, either way round, with
+// `t` the product of `x` and a magic number. Adding the sign bit rounds a
+// negative quotient towards zero.
+fn signed_division(expr: &Expression, product: &Product) -> Option<u64> {
+    let Expression::Binary(BinaryExpression::I32Add, lhs, rhs) = expr else {
+        return None;
+    };
+    let is_product = |expr: &Expression| matches!(expr, Expression::GetLocal(get) if get.local_index == product.local_index);
+    let quotient = |sign: &Expression, quotient: &Expression| {
+        let (sign, 63) = wrapped_shift(sign, |op| matches!(op, BinaryExpression::I64ShrU))? else {
+            return None;
+        };
+        let (quotient, shift) =
+            wrapped_shift(quotient, |op| matches!(op, BinaryExpression::I64ShrS))?;
+        (is_product(sign) && is_product(quotient)).then_some(shift)
+    };
+    let shift = quotient(lhs, rhs).or_else(|| quotient(rhs, lhs))?;
+    if product.magic as i64 <= 0 {
+        return None;
+    }
+    magic_divisor(product.magic, shift, 32, true)
+}
+
+// `x - q * d` or `x + q * -d`, with `q` the quotient `x / d`, which is
+// `x % d`.
+fn remainder(expr: &Expression) -> Option<Expression> {
+    let (x, multiple, negated) = match expr {
+        Expression::Binary(BinaryExpression::I32Sub, x, multiple) => (&**x, &**multiple, false),
+        Expression::Binary(BinaryExpression::I32Add, lhs, rhs) => match (&**lhs, &**rhs) {
+            (x @ Expression::GetLocal(_), multiple) | (multiple, x @ Expression::GetLocal(_)) => {
+                (x, multiple, true)
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let Expression::GetLocal(x) = x else {
+        return None;
+    };
+    let Expression::Binary(BinaryExpression::I32Mul, lhs, rhs) = multiple else {
+        return None;
+    };
+    let (quotient, factor) = match (&**lhs, &**rhs) {
+        (quotient, Expression::I32Const { value }) | (Expression::I32Const { value }, quotient) => {
+            (quotient, *value)
+        }
+        _ => return None,
+    };
+    let Expression::Binary(op, dividend, divisor) = quotient else {
+        return None;
+    };
+    let op = match op {
+        BinaryExpression::I32DivU => BinaryExpression::I32RemU,
+        BinaryExpression::I32DivS => BinaryExpression::I32RemS,
+        _ => return None,
+    };
+    let (Expression::GetLocal(dividend), Expression::I32Const { value: divisor }) =
+        (&**dividend, &**divisor)
+    else {
+        return None;
+    };
+    let factor = if negated {
+        factor.wrapping_neg()
+    } else {
+        factor
+    };
+    if dividend.local_index != x.local_index || factor != *divisor {
+        return None;
+    }
+    Some(Expression::Binary(
+        op,
+        Box::new(Expression::GetLocal(x.clone())),
+        Box::new(Expression::I32Const { value: *divisor }),
+    ))
+}
+
+// `wrap_i64(value >> shift)`, with a constant shift and a shift operator
+// `is_shift` accepts.
+fn wrapped_shift(
+    expr: &Expression,
+    is_shift: impl Fn(&BinaryExpression) -> bool,
+) -> Option<(&Expression, u32)> {
+    let Expression::Unary(UnaryExpression::I32WrapI64, value) = expr else {
+        return None;
+    };
+    let Expression::Binary(op, value, shift) = &**value else {
+        return None;
+    };
+    let Expression::I64Const { value: shift } = **shift else {
+        return None;
+    };
+    is_shift(op).then_some((value, (shift & 63) as u32))
+}
+
+// `extend_i32_s(x) * magic` or `extend_i32_u(x) * magic`, either way round.
+fn extended_product(expr: &Expression, signed: bool) -> Option<(&Expression, u64)> {
+    let Expression::Binary(BinaryExpression::I64Mul, lhs, rhs) = expr else {
+        return None;
+    };
+    let (value, magic) = match (&**lhs, &**rhs) {
+        (value, Expression::I64Const { value: magic })
+        | (Expression::I64Const { value: magic }, value) => (value, *magic as u64),
+        _ => return None,
+    };
+    match value {
+        Expression::Unary(UnaryExpression::I64ExtendI32S, x) if signed => Some((x, magic)),
+        Expression::Unary(UnaryExpression::I64ExtendI32U, x) if !signed => Some((x, magic)),
+        _ => None,
+    }
+}
+
+// The divisor `d` that multiplying a `bits`-bit value by `magic` and
+// shifting right by `shift` divides by. By Granlund and Montgomery's
+// "Division by Invariant Integers using Multiplication", the result is
+// exact for every value when `magic * d` exceeds `2^shift` by no more than
+// `2^(shift - bits)`, or for a signed value by more than nothing and no
+// more than `2^(shift - bits + 1)`.
+fn magic_divisor(magic: u64, shift: u32, bits: u32, signed: bool) -> Option<u64> {
+    if shift < 32 || magic == 0 {
+        return None;
+    }
+    let power = 1u128 << shift;
+    let magic = magic as u128;
+    let divisor = power.div_ceil(magic);
+    let error = magic * divisor - power;
+    let exact = if signed {
+        error > 0 && error <= 1 << (shift + 1 - bits)
+    } else {
+        error <= 1 << (shift - bits)
+    };
+    let max = if signed {
+        i32::MAX as u128
+    } else {
+        u32::MAX as u128
+    };
+    (exact && divisor >= 2 && divisor <= max).then_some(divisor as u64)
+}
@@ -0,0 +1,171 @@
+use crate::ir::*;
+
+/// Which of a function's blocks dominate which: a block dominates another
+/// if every path from the entry block to the other goes through it. Blocks
+/// that can't be reached from the entry block aren't in the tree.
+#[derive(Debug, Clone)]
+pub struct DominatorTree {
+    root: BlockIndex,
+    idom: HashMap<BlockIndex, BlockIndex>,
+    // The blocks each block immediately dominates, in reverse post order.
+    children: HashMap<BlockIndex, Vec<BlockIndex>>,
+}
+
+impl DominatorTree {
+    /// The entry block, which dominates every block in the tree.
+    pub fn root(&self) -> BlockIndex {
+        self.root
+    }
+
+    /// Whether `block_index` can be reached from the entry block.
+    pub fn contains(&self, block_index: BlockIndex) -> bool {
+        self.idom.contains_key(&block_index)
+    }
+
+    /// The closest block other than `block_index` that dominates it. The
+    /// entry block has none.
+    pub fn immediate_dominator(&self, block_index: BlockIndex) -> Option<BlockIndex> {
+        self.idom
+            .get(&block_index)
+            .copied()
+            .filter(|idom| *idom != block_index)
+    }
+
+    /// The blocks `block_index` immediately dominates, in reverse post
+    /// order.
+    pub fn children(&self, block_index: BlockIndex) -> &[BlockIndex] {
+        self.children
+            .get(&block_index)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Whether every path from the entry block to `b` goes through `a`. A
+    /// block dominates itself.
+    pub fn dominates(&self, a: BlockIndex, mut b: BlockIndex) -> bool {
+        if !self.contains(b) {
+            return false;
+        }
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.immediate_dominator(b) {
+                Some(idom) => b = idom,
+                None => return false,
+            }
+        }
+    }
+
+    // Writes the tree indented by depth, each block under its immediate
+    // dominator, e.g.
+    //
+    //   @0
+    //     @1
+    //     @3
+    //       @2
+    fn write(
+        &self,
+        block_index: BlockIndex,
+        depth: usize,
+        output: &mut impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        writeln!(output, "{:indent$}{}", "", block_index, indent = depth * 2)?;
+        for child in self.children(block_index) {
+            self.write(*child, depth + 1, output)?;
+        }
+        Ok(())
+    }
+}
+
+impl Func {
+    /// The function's dominator tree, found with Cooper, Harvey and
+    /// Kennedy's "A Simple, Fast Dominance Algorithm".
+    pub fn dominator_tree(&self) -> DominatorTree {
+        let rpo = self.reverse_post_order();
+        let rpo_number: HashMap<BlockIndex, usize> = rpo
+            .iter()
+            .enumerate()
+            .map(|(number, block_index)| (*block_index, number))
+            .collect();
+        let predecessors = self.predecessors();
+
+        let mut idom: HashMap<BlockIndex, BlockIndex> = HashMap::new();
+        if !rpo.is_empty() {
+            idom.insert(self.entry_block, self.entry_block);
+        }
+        let intersect = |idom: &HashMap<BlockIndex, BlockIndex>, mut a, mut b| {
+            while a != b {
+                while rpo_number[&a] > rpo_number[&b] {
+                    a = idom[&a];
+                }
+                while rpo_number[&b] > rpo_number[&a] {
+                    b = idom[&b];
+                }
+            }
+            a
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block_index in rpo.iter().skip(1) {
+                let mut new_idom = None;
+                for pred in predecessors.get(block_index).into_iter().flatten() {
+                    if !idom.contains_key(pred) {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        Some(other) => intersect(&idom, *pred, other),
+                        None => *pred,
+                    });
+                }
+                let new_idom = new_idom.unwrap();
+                if idom.insert(*block_index, new_idom) != Some(new_idom) {
+                    changed = true;
+                }
+            }
+        }
+
+        let mut children: HashMap<BlockIndex, Vec<BlockIndex>> = HashMap::new();
+        for block_index in rpo.iter().skip(1) {
+            children
+                .entry(idom[block_index])
+                .or_default()
+                .push(*block_index);
+        }
+        DominatorTree {
+            root: self.entry_block,
+            idom,
+            children,
+        }
+    }
+
+    fn write_dominators(&self, output: &mut impl std::io::Write) -> anyhow::Result<()> {
+        writeln!(output, "{}:", self.name())?;
+        let dominators = self.dominator_tree();
+        if dominators.contains(dominators.root()) {
+            dominators.write(dominators.root(), 1, output)?;
+        }
+        Ok(())
+    }
+}
+
+impl Module {
+    /// Writes the dominator tree of each defined function, as its blocks
+    /// indented under their immediate dominators. Blocks are numbered as
+    /// in the graphviz output.
+    pub fn write_dominators(&self, mut output: impl std::io::Write) -> anyhow::Result<()> {
+        for func in &self.funcs {
+            func.write_dominators(&mut output)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_func_dominators(
+        &self,
+        func_index: FuncIdx,
+        mut output: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        self.defined_func(func_index)?.write_dominators(&mut output)
+    }
+}
@@ -0,0 +1,117 @@
+use crate::ir::*;
+
+/// What evaluating an expression can do besides produce its value, as far as
+/// reordering it with others is concerned. Memory, tables, globals and the
+/// GC heap are all lumped together as state.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Effects {
+    pub(crate) reads_state: bool,
+    pub(crate) writes_state: bool,
+    pub(crate) may_trap: bool,
+}
+
+impl Effects {
+    const NONE: Effects = Effects {
+        reads_state: false,
+        writes_state: false,
+        may_trap: false,
+    };
+    const READS: Effects = Effects {
+        reads_state: true,
+        writes_state: false,
+        may_trap: false,
+    };
+    const TRAPS: Effects = Effects {
+        reads_state: false,
+        writes_state: false,
+        may_trap: true,
+    };
+    const READS_OR_TRAPS: Effects = Effects {
+        reads_state: true,
+        writes_state: false,
+        may_trap: true,
+    };
+    const ANY: Effects = Effects {
+        reads_state: true,
+        writes_state: true,
+        may_trap: true,
+    };
+
+    pub(crate) fn union(self, other: Effects) -> Effects {
+        Effects {
+            reads_state: self.reads_state || other.reads_state,
+            writes_state: self.writes_state || other.writes_state,
+            may_trap: self.may_trap || other.may_trap,
+        }
+    }
+
+    /// Whether something with these effects can be evaluated in either
+    /// order with something with `other`'s and give the same result. A write
+    /// can't pass anything that reads or writes state or could trap before
+    /// it, and of two things that could trap, which one does has to stay
+    /// the same.
+    pub(crate) fn commutes_with(self, other: Effects) -> bool {
+        let conflicts = |a: Effects, b: Effects| {
+            a.writes_state && (b.reads_state || b.writes_state || b.may_trap)
+        };
+        let both_trap = self.may_trap && other.may_trap;
+        !(conflicts(self, other) || conflicts(other, self) || both_trap)
+    }
+}
+
+impl Expression {
+    /// The effects of evaluating the expression, operands included.
+    pub(crate) fn effects(&self) -> Effects {
+        (self.operands().into_iter())
+            .map(Expression::effects)
+            .fold(self.own_effects(), Effects::union)
+    }
+
+    /// The effects of the operator alone, once its operands are evaluated.
+    pub(crate) fn own_effects(&self) -> Effects {
+        match self {
+            Expression::I32Const { .. }
+            | Expression::I64Const { .. }
+            | Expression::F32Const { .. }
+            | Expression::F64Const { .. }
+            | Expression::V128Const { .. }
+            | Expression::RefNull { .. }
+            | Expression::RefFunc { .. }
+            | Expression::BlockParam(_)
+            | Expression::Ternary(..)
+            | Expression::Logical(..)
+            | Expression::ExtractLane(_)
+            | Expression::ReplaceLane(_)
+            | Expression::Shuffle(_)
+            | Expression::GetLocal(_)
+            | Expression::GetLocalN(_)
+            | Expression::Select(_)
+            | Expression::StructNew(_)
+            | Expression::RefTest(_) => Effects::NONE,
+            Expression::Unary(..) | Expression::Binary(..) => {
+                if self.is_pure_op() {
+                    Effects::NONE
+                } else {
+                    Effects::TRAPS
+                }
+            }
+            Expression::GetGlobal(_)
+            | Expression::MemorySize { .. }
+            | Expression::TableSize { .. } => Effects::READS,
+            Expression::MemoryLoad(_)
+            | Expression::TableGet(_)
+            | Expression::StructGet(_)
+            | Expression::ArrayGet(_) => Effects::READS_OR_TRAPS,
+            Expression::ArrayNew(_)
+            | Expression::ArrayLen(_)
+            | Expression::RefAsNonNull(_)
+            | Expression::RefCast(_)
+            | Expression::Bottom => Effects::TRAPS,
+            Expression::Call(_)
+            | Expression::CallIndirect(_)
+            | Expression::CallRef(_)
+            | Expression::MemoryGrow(_)
+            | Expression::TableGrow(_) => Effects::ANY,
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::ir::*;
+
+/// Names of functions that run first, in the order they run: constructors,
+/// then the command entry point, then the `main` it calls.
+const ENTRY_NAMES: [&str; 6] = [
+    "__wasm_call_ctors",
+    "_initialize",
+    "_start",
+    "main",
+    "__main_argc_argv",
+    "__original_main",
+];
+
+/// Why a function is considered an entry point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryKind {
+    /// The module's start function.
+    Start,
+    /// Exported under a conventional entry point name.
+    Export(String),
+    /// Given a conventional entry point name by the name section, e.g. an
+    /// unexported `__wasm_call_ctors`.
+    Named(String),
+}
+
+/// A function that's likely called first, by the host or on instantiation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryPoint {
+    pub func_index: FuncIdx,
+    pub kind: EntryKind,
+}
+
+impl fmt::Display for EntryPoint {
+    /// e.g. `func3 (export "_start")`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "func{} ", self.func_index)?;
+        match &self.kind {
+            EntryKind::Start => write!(f, "(start)"),
+            EntryKind::Export(name) => write!(f, "(export \"{}\")", name),
+            EntryKind::Named(name) => write!(f, "(named \"{}\")", name),
+        }
+    }
+}
+
+impl Module {
+    /// The module's entry points, in the order they're likely to run: the
+    /// start function, then functions with conventional entry point names.
+    pub fn entry_points(&self) -> Vec<EntryPoint> {
+        let mut entry_points = vec![];
+        if let Some(func_index) = self.start_func {
+            entry_points.push(EntryPoint {
+                func_index,
+                kind: EntryKind::Start,
+            });
+        }
+        for entry_name in ENTRY_NAMES {
+            let exports = self
+                .entry_names
+                .iter()
+                .filter(|(_, kind)| matches!(kind, EntryKind::Export(name) if name == entry_name));
+            let named = self
+                .entry_names
+                .iter()
+                .filter(|(_, kind)| matches!(kind, EntryKind::Named(name) if name == entry_name));
+            // An exported function's debug name usually repeats the export
+            // name, so it's only listed once.
+            for (func_index, kind) in exports.chain(named) {
+                if !entry_points
+                    .iter()
+                    .any(|entry_point| entry_point.func_index == *func_index)
+                {
+                    entry_points.push(EntryPoint {
+                        func_index: *func_index,
+                        kind: kind.clone(),
+                    });
+                }
+            }
+        }
+        entry_points
+    }
+
+    // Records a function's export or debug name if it's an entry point name.
+    pub(crate) fn name_entry(&mut self, func_index: FuncIdx, kind: EntryKind) {
+        let (EntryKind::Export(name) | EntryKind::Named(name)) = &kind else {
+            return;
+        };
+        if ENTRY_NAMES.contains(&name.as_str()) {
+            self.entry_names.push((func_index, kind));
+        }
+    }
+
+    /// The defined functions that can be called, directly or through a
+    /// table, starting from the entry points, as by `CallGraph`.
+    pub fn reachable_from_entry_points(&self) -> HashSet<FuncIdx> {
+        let roots = self
+            .entry_points()
+            .into_iter()
+            .map(|entry_point| entry_point.func_index);
+        let mut reachable = self.call_graph().reachable_from(roots);
+        reachable.retain(|func_index| self.func(*func_index).is_some());
+        reachable
+    }
+
+    // The functions in the order they're printed: entry points first with
+    // `entry_first`, and only those reachable from them with `entry_only`,
+    // or from the exports in `reachable_from` with callers first.
+    pub(crate) fn printed_funcs(&self) -> Vec<&Func> {
+        let mut funcs: Vec<&Func> = if !self.reachable_from.is_empty() {
+            let roots = self.reachable_from.iter().copied();
+            (self.call_graph().topological_order(roots).into_iter())
+                .filter_map(|func_index| self.func(func_index))
+                .collect()
+        } else if self.entry_only {
+            let reachable = self.reachable_from_entry_points();
+            self.funcs
+                .iter()
+                .filter(|func| reachable.contains(&func.index))
+                .collect()
+        } else {
+            self.funcs.iter().collect()
+        };
+        if self.entry_first {
+            let entry_points = self.entry_points();
+            // A stable sort keeps the other functions in index order.
+            funcs.sort_by_key(|func| {
+                entry_points
+                    .iter()
+                    .position(|entry_point| entry_point.func_index == func.index)
+                    .unwrap_or(entry_points.len())
+            });
+        }
+        funcs
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt;
+
+use crate::ir::FuncIdx;
+
+/// An error that prevented a single function from being decompiled.
+///
+/// These are reported per function instead of aborting the whole module, so
+/// that one function the decompiler can't handle doesn't take down the rest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecompileError {
+    /// An internal invariant of the decoder didn't hold, or the function uses
+    /// something the decompiler doesn't support yet.
+    Internal {
+        func: FuncIdx,
+        offset: usize,
+        detail: String,
+    },
+    /// The function was only validated, because
+    /// [`DecodeOptions::only`](crate::DecodeOptions::only) left it out.
+    Skipped { func: FuncIdx },
+}
+
+impl fmt::Display for DecompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecompileError::Internal {
+                func,
+                offset,
+                detail,
+            } => write!(
+                f,
+                "internal error in func {} at 0x{:x}: {}",
+                func, offset, detail
+            ),
+            DecompileError::Skipped { func } => write!(f, "func {} was not decompiled", func),
+        }
+    }
+}
+
+impl std::error::Error for DecompileError {}
@@ -0,0 +1,405 @@
+use std::collections::HashSet;
+
+use crate::ir::passes::{count_local_accesses, count_local_reads};
+use crate::ir::*;
+
+impl Func {
+    // Folds arithmetic on integer constants, e.g. `(1 + 2) * x` becomes
+    // `3 * x`, puts the constant in place of each read of a local that's
+    // only ever assigned that constant, and turns a `br_if` on a constant
+    // into a `br`. Unoptimized builds are full of all three.
+    pub(crate) fn fold_constants(&mut self) {
+        loop {
+            for block in self.blocks.values_mut() {
+                for stmt in &mut block.statements {
+                    fold_statement(stmt);
+                }
+                for expr in block.terminator.expressions_mut() {
+                    fold(expr);
+                }
+            }
+            // A propagated constant can make more to fold.
+            if !self.propagate_constants() {
+                break;
+            }
+        }
+
+        let mut changed = false;
+        for block in self.blocks.values_mut() {
+            let terminator = std::mem::replace(&mut block.terminator, Terminator::Unknown);
+            block.terminator = match terminator {
+                Terminator::BrIf(condition, true_block, false_block, params) => {
+                    match constant_i32(&condition) {
+                        Some(value) => {
+                            changed = true;
+                            let target = if value != 0 { true_block } else { false_block };
+                            Terminator::Br(target, params)
+                        }
+                        None => Terminator::BrIf(condition, true_block, false_block, params),
+                    }
+                }
+                terminator => terminator,
+            };
+        }
+        // Blocks only reached through the branch not taken are gone.
+        if changed {
+            self.eliminate_dead_code();
+        }
+    }
+
+    // Replaces the reads of each local other than a param that's assigned
+    // a constant once, by a statement that isn't nested in another, with
+    // the constant, and removes the assignment. Anywhere the assignment
+    // doesn't dominate, a read might see the local's zero instead, so it's
+    // only done if it dominates every read. Returns whether any were.
+    fn propagate_constants(&mut self) -> bool {
+        let mut reads = HashMap::new();
+        let mut writes = HashMap::new();
+        for block in self.blocks.values() {
+            count_local_accesses(&block.statements, &mut reads, &mut writes);
+            for expr in block.terminator.expressions() {
+                count_local_reads(expr, &mut reads);
+            }
+        }
+        let num_params = self.ty.params().len();
+        let mut constants: HashMap<LocalIdx, (BlockIndex, usize, Expression)> = HashMap::new();
+        for (block_index, block) in &self.blocks {
+            for (i, stmt) in block.statements.iter().enumerate() {
+                let (local_index, value) = match stmt {
+                    Statement::LocalSet(stmt) => (stmt.index, &stmt.value),
+                    Statement::LocalSetN(stmt) if stmt.index.len() == 1 => {
+                        (stmt.index[0], &stmt.value)
+                    }
+                    _ => continue,
+                };
+                if local_index.as_usize() >= num_params
+                    && writes[&local_index] == 1
+                    && is_constant(value)
+                {
+                    constants.insert(local_index, (*block_index, i, (**value).clone()));
+                }
+            }
+        }
+        // A read of several locals at once stays as it is.
+        let mut grouped = HashSet::new();
+        for block in self.blocks.values() {
+            grouped_reads_in(&block.statements, &mut grouped);
+            for expr in block.terminator.expressions() {
+                grouped_reads(expr, &mut grouped);
+            }
+        }
+        constants.retain(|local_index, _| !grouped.contains(local_index));
+        if constants.is_empty() {
+            return false;
+        }
+
+        // Every statement, and the terminator after them, has to come after
+        // the assignment of each constant it reads.
+        let dominators = self.dominator_tree();
+        for (block_index, block) in &self.blocks {
+            if !dominators.contains(*block_index) {
+                continue;
+            }
+            let mut uses: Vec<(usize, HashMap<LocalIdx, usize>)> = vec![];
+            for (i, stmt) in block.statements.iter().enumerate() {
+                let mut reads = HashMap::new();
+                count_local_accesses(std::slice::from_ref(stmt), &mut reads, &mut HashMap::new());
+                uses.push((i, reads));
+            }
+            let mut reads = HashMap::new();
+            for expr in block.terminator.expressions() {
+                count_local_reads(expr, &mut reads);
+            }
+            uses.push((block.statements.len(), reads));
+            for (i, reads) in uses {
+                constants.retain(|local_index, (write_block, write_index, _)| {
+                    !reads.contains_key(local_index)
+                        || if write_block == block_index {
+                            *write_index < i
+                        } else {
+                            dominators.dominates(*write_block, *block_index)
+                        }
+                });
+            }
+        }
+        if constants.is_empty() {
+            return false;
+        }
+
+        let assignments: HashSet<(BlockIndex, usize)> = constants
+            .values()
+            .map(|(block_index, i, _)| (*block_index, *i))
+            .collect();
+        let constants: HashMap<LocalIdx, Expression> = constants
+            .into_iter()
+            .map(|(local_index, (_, _, value))| (local_index, value))
+            .collect();
+        for (block_index, block) in self.blocks.iter_mut() {
+            let mut i = 0;
+            block.statements.retain(|_| {
+                i += 1;
+                !assignments.contains(&(*block_index, i - 1))
+            });
+            replace_reads_in(&mut block.statements, &constants);
+            for expr in block.terminator.expressions_mut() {
+                replace_reads(expr, &constants);
+            }
+        }
+        true
+    }
+}
+
+fn is_constant(expr: &Expression) -> bool {
+    matches!(
+        expr,
+        Expression::I32Const { .. }
+            | Expression::I64Const { .. }
+            | Expression::F32Const { .. }
+            | Expression::F64Const { .. }
+    )
+}
+
+fn constant_i32(expr: &Expression) -> Option<i32> {
+    match expr {
+        Expression::I32Const { value } => Some(*value),
+        _ => None,
+    }
+}
+
+fn grouped_reads_in(statements: &[Statement], grouped: &mut HashSet<LocalIdx>) {
+    for stmt in statements {
+        for expr in stmt.expressions() {
+            grouped_reads(expr, grouped);
+        }
+        for body in stmt.bodies() {
+            grouped_reads_in(body, grouped);
+        }
+    }
+}
+
+fn grouped_reads(expr: &Expression, grouped: &mut HashSet<LocalIdx>) {
+    if let Expression::GetLocalN(get) = expr {
+        if get.local_indices.len() > 1 {
+            grouped.extend(&get.local_indices);
+        }
+    }
+    for operand in expr.operands() {
+        grouped_reads(operand, grouped);
+    }
+}
+
+fn replace_reads_in(statements: &mut [Statement], constants: &HashMap<LocalIdx, Expression>) {
+    for stmt in statements {
+        for expr in stmt.expressions_mut() {
+            replace_reads(expr, constants);
+        }
+        for body in stmt.bodies_mut() {
+            replace_reads_in(body, constants);
+        }
+    }
+}
+
+fn replace_reads(expr: &mut Expression, constants: &HashMap<LocalIdx, Expression>) {
+    let local_index = match expr {
+        Expression::GetLocal(get) => Some(get.local_index),
+        Expression::GetLocalN(get) if get.local_indices.len() == 1 => Some(get.local_indices[0]),
+        _ => None,
+    };
+    if let Some(value) = local_index.and_then(|local_index| constants.get(&local_index)) {
+        *expr = value.clone();
+    }
+    for operand in expr.operands_mut() {
+        replace_reads(operand, constants);
+    }
+}
+
+fn fold_statement(stmt: &mut Statement) {
+    for expr in stmt.expressions_mut() {
+        fold(expr);
+    }
+    for body in stmt.bodies_mut() {
+        for stmt in body {
+            fold_statement(stmt);
+        }
+    }
+}
+
+// Folds the operands first, so that constants fold all the way up.
+fn fold(expr: &mut Expression) {
+    for operand in expr.operands_mut() {
+        fold(operand);
+    }
+    let folded = match expr {
+        Expression::Unary(op, value) => fold_unary(op, value),
+        Expression::Binary(op, lhs, rhs) => fold_binary(op, lhs, rhs),
+        // A select evaluates both of its values, so the one not chosen can
+        // only be dropped if it's pure.
+        Expression::Select(select) => match constant_i32(&select.condition) {
+            Some(0) if select.on_true.is_pure() => Some((*select.on_false).clone()),
+            Some(_) if select.on_false.is_pure() => Some((*select.on_true).clone()),
+            _ => None,
+        },
+        // The right of `&&` and `||` isn't evaluated if the left decides.
+        Expression::Logical(LogicalExpression::And, lhs, _) if constant_i32(lhs) == Some(0) => {
+            Some(Expression::I32Const { value: 0 })
+        }
+        Expression::Logical(LogicalExpression::Or, lhs, _)
+            if constant_i32(lhs).is_some_and(|value| value != 0) =>
+        {
+            Some(Expression::I32Const { value: 1 })
+        }
+        _ => None,
+    };
+    if let Some(folded) = folded {
+        *expr = folded;
+    }
+}
+
+fn fold_unary(op: &UnaryExpression, value: &Expression) -> Option<Expression> {
+    use Expression::{I32Const, I64Const};
+    use UnaryExpression::*;
+
+    Some(match (op, value) {
+        (I32Eqz, I32Const { value }) => I32Const {
+            value: (*value == 0) as i32,
+        },
+        (I32Clz, I32Const { value }) => I32Const {
+            value: value.leading_zeros() as i32,
+        },
+        (I32Ctz, I32Const { value }) => I32Const {
+            value: value.trailing_zeros() as i32,
+        },
+        (I32Popcnt, I32Const { value }) => I32Const {
+            value: value.count_ones() as i32,
+        },
+        (I32Extend8S, I32Const { value }) => I32Const {
+            value: *value as i8 as i32,
+        },
+        (I32Extend16S, I32Const { value }) => I32Const {
+            value: *value as i16 as i32,
+        },
+        (I64ExtendI32S, I32Const { value }) => I64Const {
+            value: *value as i64,
+        },
+        (I64ExtendI32U, I32Const { value }) => I64Const {
+            value: *value as u32 as i64,
+        },
+        (I64Eqz, I64Const { value }) => I32Const {
+            value: (*value == 0) as i32,
+        },
+        (I64Clz, I64Const { value }) => I64Const {
+            value: value.leading_zeros() as i64,
+        },
+        (I64Ctz, I64Const { value }) => I64Const {
+            value: value.trailing_zeros() as i64,
+        },
+        (I64Popcnt, I64Const { value }) => I64Const {
+            value: value.count_ones() as i64,
+        },
+        (I64Extend8S, I64Const { value }) => I64Const {
+            value: *value as i8 as i64,
+        },
+        (I64Extend16S, I64Const { value }) => I64Const {
+            value: *value as i16 as i64,
+        },
+        (I64Extend32S, I64Const { value }) => I64Const {
+            value: *value as i32 as i64,
+        },
+        (I32WrapI64, I64Const { value }) => I32Const {
+            value: *value as i32,
+        },
+        _ => return None,
+    })
+}
+
+// `op` applied to two integer constants, or `None` if either isn't one or
+// it would trap, as dividing by zero does.
+pub(crate) fn fold_binary(
+    op: &BinaryExpression,
+    lhs: &Expression,
+    rhs: &Expression,
+) -> Option<Expression> {
+    use BinaryExpression::*;
+    use Expression::{I32Const, I64Const};
+
+    match (lhs, rhs) {
+        (I32Const { value: a }, I32Const { value: b }) => {
+            let (a, b) = (*a, *b);
+            let (ua, ub) = (a as u32, b as u32);
+            let value = match op {
+                I32Eq => (a == b) as i32,
+                I32Ne => (a != b) as i32,
+                I32LtS => (a < b) as i32,
+                I32LtU => (ua < ub) as i32,
+                I32GtS => (a > b) as i32,
+                I32GtU => (ua > ub) as i32,
+                I32LeS => (a <= b) as i32,
+                I32LeU => (ua <= ub) as i32,
+                I32GeS => (a >= b) as i32,
+                I32GeU => (ua >= ub) as i32,
+                I32Add => a.wrapping_add(b),
+                I32Sub => a.wrapping_sub(b),
+                I32Mul => a.wrapping_mul(b),
+                // Also traps on overflow, for `i32::MIN / -1`.
+                I32DivS => a.checked_div(b)?,
+                I32DivU => ua.checked_div(ub)? as i32,
+                I32RemS if b == 0 => return None,
+                I32RemS => a.wrapping_rem(b),
+                I32RemU => ua.checked_rem(ub)? as i32,
+                I32And => a & b,
+                I32Or => a | b,
+                I32Xor => a ^ b,
+                I32Shl => a.wrapping_shl(ub),
+                I32ShrS => a.wrapping_shr(ub),
+                I32ShrU => ua.wrapping_shr(ub) as i32,
+                I32Rotl => ua.rotate_left(ub % 32) as i32,
+                I32Rotr => ua.rotate_right(ub % 32) as i32,
+                _ => return None,
+            };
+            Some(I32Const { value })
+        }
+        (I64Const { value: a }, I64Const { value: b }) => {
+            let (a, b) = (*a, *b);
+            let (ua, ub) = (a as u64, b as u64);
+            let comparison = match op {
+                I64Eq => Some(a == b),
+                I64Ne => Some(a != b),
+                I64LtS => Some(a < b),
+                I64LtU => Some(ua < ub),
+                I64GtS => Some(a > b),
+                I64GtU => Some(ua > ub),
+                I64LeS => Some(a <= b),
+                I64LeU => Some(ua <= ub),
+                I64GeS => Some(a >= b),
+                I64GeU => Some(ua >= ub),
+                _ => None,
+            };
+            if let Some(comparison) = comparison {
+                return Some(I32Const {
+                    value: comparison as i32,
+                });
+            }
+            let value = match op {
+                I64Add => a.wrapping_add(b),
+                I64Sub => a.wrapping_sub(b),
+                I64Mul => a.wrapping_mul(b),
+                I64DivS => a.checked_div(b)?,
+                I64DivU => ua.checked_div(ub)? as i64,
+                I64RemS if b == 0 => return None,
+                I64RemS => a.wrapping_rem(b),
+                I64RemU => ua.checked_rem(ub)? as i64,
+                I64And => a & b,
+                I64Or => a | b,
+                I64Xor => a ^ b,
+                I64Shl => a.wrapping_shl(ub as u32),
+                I64ShrS => a.wrapping_shr(ub as u32),
+                I64ShrU => ua.wrapping_shr(ub as u32) as i64,
+                I64Rotl => ua.rotate_left((ub % 64) as u32) as i64,
+                I64Rotr => ua.rotate_right((ub % 64) as u32) as i64,
+                _ => return None,
+            };
+            Some(I64Const { value })
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,98 @@
+use crate::ir::passes::count_local_accesses;
+use crate::ir::*;
+
+/// The part of the shadow stack a function compiled by LLVM sets aside for
+/// itself, through a local assigned `__stack_pointer - size` on entry.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StackFrame {
+    local_index: LocalIdx,
+    size: u32,
+}
+
+impl Func {
+    // Finds the local the function's stack frame is addressed through: one
+    // assigned only `__stack_pointer - size`, where `__stack_pointer` is
+    // `stack_pointer`. The local is named `frame`, unless the name is
+    // `taken` or the local has one from a symbol map, so that the frame's
+    // slots print as `frame.var_8`.
+    pub(crate) fn find_stack_frame(&mut self, stack_pointer: GlobalIdx, taken: bool) {
+        if self.error.is_some() {
+            return;
+        }
+        let mut reads = HashMap::new();
+        let mut writes = HashMap::new();
+        for block in self.blocks.values() {
+            count_local_accesses(&block.statements, &mut reads, &mut writes);
+        }
+        let frame = self.blocks.values().find_map(|block| {
+            block.statements.iter().find_map(|stmt| {
+                let Statement::LocalSet(stmt) = stmt else {
+                    return None;
+                };
+                let Expression::Binary(BinaryExpression::I32Sub, base, size) = &*stmt.value else {
+                    return None;
+                };
+                match (&**base, &**size) {
+                    (Expression::GetGlobal(get), Expression::I32Const { value: size })
+                        if get.global_index == stack_pointer
+                            && *size > 0
+                            && writes.get(&stmt.index) == Some(&1) =>
+                    {
+                        Some(StackFrame {
+                            local_index: stmt.index,
+                            size: *size as u32,
+                        })
+                    }
+                    _ => None,
+                }
+            })
+        });
+        let Some(frame) = frame else {
+            return;
+        };
+        let local = &mut self.locals[frame.local_index.as_usize()];
+        if !taken && !local.name.starts_with('$') {
+            local.name = "frame".to_string();
+        }
+        self.frame = Some(frame);
+    }
+
+    // The slot of the stack frame a load or store from `index` accesses,
+    // e.g. `frame.var_8` for `memory[frame + 8]`, if it's in the frame.
+    pub(crate) fn frame_slot(&self, index: &Expression, arg: &wasm::MemArg) -> Option<String> {
+        let frame = self.frame?;
+        if arg.memory != 0 {
+            return None;
+        }
+        let is_frame = |expr: &Expression| matches!(expr, Expression::GetLocal(get) if get.local_index == frame.local_index);
+        let offset = match index {
+            index if is_frame(index) => 0,
+            Expression::Binary(BinaryExpression::I32Add, a, b) => match (&**a, &**b) {
+                (base, Expression::I32Const { value }) | (Expression::I32Const { value }, base)
+                    if is_frame(base) =>
+                {
+                    *value as i64
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let offset = offset + arg.offset as i64;
+        if !(0..frame.size as i64).contains(&offset) {
+            return None;
+        }
+        Some(format!(
+            "{}.var_{}",
+            self.local(frame.local_index).name,
+            offset
+        ))
+    }
+
+    // Whether `local_index` is the local the stack frame is addressed
+    // through, and if so the frame's size.
+    pub(crate) fn frame_size(&self, local_index: LocalIdx) -> Option<u32> {
+        self.frame
+            .filter(|frame| frame.local_index == local_index)
+            .map(|frame| frame.size)
+    }
+}
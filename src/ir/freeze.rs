@@ -0,0 +1,355 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::ir::data::const_offset;
+use crate::ir::*;
+
+/// An active element segment, as far as freezing its table is concerned.
+#[derive(Debug, Clone)]
+pub(crate) struct ActiveElem {
+    table: u32,
+    // `None` if the offset isn't a constant.
+    offset: Option<u64>,
+    // The function in each slot, or `None` for anything else.
+    funcs: Vec<Option<FuncIdx>>,
+}
+
+impl ActiveElem {
+    pub(crate) fn new(element: &wasm::Element) -> anyhow::Result<Option<ActiveElem>> {
+        let wasm::ElementKind::Active {
+            table_index,
+            offset_expr,
+        } = &element.kind
+        else {
+            return Ok(None);
+        };
+        let mut funcs = vec![];
+        match element.items.clone() {
+            wasm::ElementItems::Functions(items) => {
+                for func in items {
+                    funcs.push(Some(func?.into()));
+                }
+            }
+            wasm::ElementItems::Expressions(_, items) => {
+                for expr in items {
+                    funcs.push(match expr?.get_operators_reader().read()? {
+                        wasm::Operator::RefFunc { function_index } => Some(function_index.into()),
+                        _ => None,
+                    });
+                }
+            }
+        }
+        Ok(Some(ActiveElem {
+            table: table_index.unwrap_or(0),
+            offset: const_offset(offset_expr)?,
+            funcs,
+        }))
+    }
+}
+
+/// What keeps its initial value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrozenKind {
+    Global(GlobalIdx),
+    TableSlot { table: u32, slot: u32 },
+}
+
+/// A mutable global or table slot that's never written after
+/// instantiation, and so is treated as a constant with
+/// [`DecodeOptions::freeze`].
+#[derive(Debug, Clone)]
+pub struct Frozen {
+    pub kind: FrozenKind,
+    /// The functions that do write it, none of which can be called from an
+    /// export or the start function.
+    pub unreachable_writers: Vec<FuncIdx>,
+    // A constant, or `RefFunc` for a table slot.
+    value: Expression,
+}
+
+impl fmt::Display for Frozen {
+    /// e.g. `table0[1] = func3 (only written by unreachable func7)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            FrozenKind::Global(global_index) => write!(f, "globals[{}]", global_index)?,
+            FrozenKind::TableSlot { table, slot } => write!(f, "table{}[{}]", table, slot)?,
+        }
+        match &self.value {
+            Expression::I32Const { value } => write!(f, " = {}", value)?,
+            Expression::I64Const { value } => write!(f, " = {}", value)?,
+            Expression::F32Const { value } => write!(f, " = {}", f32::from_bits(value.bits()))?,
+            Expression::F64Const { value } => write!(f, " = {}", f64::from_bits(value.bits()))?,
+            Expression::RefFunc { func_index } => write!(f, " = func{}", func_index)?,
+            _ => {}
+        }
+        if self.unreachable_writers.is_empty() {
+            write!(f, " (never written)")
+        } else {
+            let writers: Vec<String> = self
+                .unreachable_writers
+                .iter()
+                .map(|func_index| format!("func{}", func_index))
+                .collect();
+            write!(f, " (only written by unreachable {})", writers.join(", "))
+        }
+    }
+}
+
+// What `freeze` substitutes, looked up while the functions are borrowed
+// mutably.
+struct FrozenValues {
+    globals: HashMap<GlobalIdx, Expression>,
+    slots: HashMap<(u32, u32), FuncIdx>,
+    // The signature of each function in a frozen slot.
+    slot_types: HashMap<FuncIdx, wasm::FuncType>,
+    types: Vec<Option<wasm::FuncType>>,
+}
+
+impl FrozenValues {
+    // The function an indirect call always reaches, if its table slot is
+    // frozen and the function has the type the call expects.
+    fn direct_callee(&self, call: &CallIndirectExpression) -> Option<FuncIdx> {
+        let slot = match *call.callee_index {
+            Expression::I32Const { value } => value as u32,
+            Expression::I64Const { value } => u32::try_from(value).ok()?,
+            _ => return None,
+        };
+        let func_index = *self.slots.get(&(call.table_index, slot))?;
+        let expected = self.types.get(call.func_type_index.as_usize())?.as_ref()?;
+        (self.slot_types.get(&func_index)? == expected).then_some(func_index)
+    }
+}
+
+impl Module {
+    /// The mutable globals and table slots treated as constants with
+    /// [`DecodeOptions::freeze`]. Empty without it.
+    pub fn frozen(&self) -> &[Frozen] {
+        &self.frozen
+    }
+
+    // A global is frozen if it's defined here with a constant initializer,
+    // isn't exported, and no function that can run writes it. The same goes
+    // for the slots of a table that element segments fill in at constant
+    // offsets.
+    fn find_frozen(&self) -> Vec<Frozen> {
+        // A function that couldn't be decoded might write anything.
+        if self.funcs.iter().any(|func| func.error.is_some()) {
+            return vec![];
+        }
+        // The host can call exports and anything it can reach in a table.
+        let mut roots: Vec<FuncIdx> = self.func_exports.iter().map(|(_, func)| *func).collect();
+        roots.extend(self.start_func);
+        if self
+            .elem_segments
+            .iter()
+            .any(|elem| self.escaped_table(elem.table))
+        {
+            roots.extend(self.elem_funcs.iter().copied());
+        }
+        let reachable = self.call_graph().reachable_from(roots);
+
+        let mut global_writers: HashMap<GlobalIdx, Vec<FuncIdx>> = HashMap::new();
+        let mut table_writers: HashMap<u32, Vec<FuncIdx>> = HashMap::new();
+        for func in &self.funcs {
+            for (global_index, (_, writes)) in func.global_counts() {
+                if writes > 0 {
+                    global_writers
+                        .entry(global_index)
+                        .or_default()
+                        .push(func.index);
+                }
+            }
+            for table in func.written_tables() {
+                table_writers.entry(table).or_default().push(func.index);
+            }
+        }
+        // The writers, if none of them can run. A function that writes a
+        // table more than once is listed once.
+        let unreachable_writers = |writers: Option<&Vec<FuncIdx>>| -> Option<Vec<FuncIdx>> {
+            let mut writers = writers.cloned().unwrap_or_default();
+            if writers
+                .iter()
+                .any(|func_index| reachable.contains(func_index))
+            {
+                return None;
+            }
+            writers.dedup();
+            Some(writers)
+        };
+
+        let mut frozen = vec![];
+        let mut global_inits: Vec<(&GlobalIdx, &Expression)> = self.global_inits.iter().collect();
+        global_inits.sort_by_key(|(global_index, _)| **global_index);
+        for (global_index, value) in global_inits {
+            let is_number = matches!(
+                value,
+                Expression::I32Const { .. }
+                    | Expression::I64Const { .. }
+                    | Expression::F32Const { .. }
+                    | Expression::F64Const { .. }
+            );
+            if !is_number
+                || !self.globals[global_index.as_usize()].mutable
+                || self.exported_globals.contains(global_index)
+            {
+                continue;
+            }
+            if let Some(writers) = unreachable_writers(global_writers.get(global_index)) {
+                frozen.push(Frozen {
+                    kind: FrozenKind::Global(*global_index),
+                    unreachable_writers: writers,
+                    value: value.clone(),
+                });
+            }
+        }
+
+        for ((table, slot), func_index) in self.elem_slots() {
+            if self.escaped_table(table) {
+                continue;
+            }
+            if let Some(writers) = unreachable_writers(table_writers.get(&table)) {
+                frozen.push(Frozen {
+                    kind: FrozenKind::TableSlot { table, slot },
+                    unreachable_writers: writers,
+                    value: Expression::RefFunc { func_index },
+                });
+            }
+        }
+        frozen
+    }
+
+    // Whether the host can see the table, and so change it.
+    pub(crate) fn escaped_table(&self, table: u32) -> bool {
+        table < self.num_table_imports || self.exported_tables.contains(&table)
+    }
+
+    // The function the element segments put in each table slot. Tables a
+    // segment is put in at an offset that isn't constant are left out.
+    pub(crate) fn elem_slots(&self) -> BTreeMap<(u32, u32), FuncIdx> {
+        // Later segments overwrite earlier ones.
+        let mut slots: BTreeMap<(u32, u32), Option<FuncIdx>> = BTreeMap::new();
+        let mut unknown_offsets = vec![];
+        for elem in &self.elem_segments {
+            let Some(offset) = elem.offset else {
+                unknown_offsets.push(elem.table);
+                continue;
+            };
+            for (i, func) in elem.funcs.iter().enumerate() {
+                if let Ok(slot) = u32::try_from(offset + i as u64) {
+                    slots.insert((elem.table, slot), *func);
+                }
+            }
+        }
+        slots
+            .into_iter()
+            .filter(|((table, _), _)| !unknown_offsets.contains(table))
+            .filter_map(|(slot, func)| Some((slot, func?)))
+            .collect()
+    }
+
+    // Replaces reads of frozen globals with their value, and indirect calls
+    // through frozen table slots with direct calls.
+    pub(crate) fn freeze(&mut self) {
+        self.frozen = self.find_frozen();
+        let mut values = FrozenValues {
+            globals: HashMap::new(),
+            slots: HashMap::new(),
+            slot_types: HashMap::new(),
+            types: self
+                .rec_groups
+                .iter()
+                .flat_map(|rec_group| rec_group.types())
+                .map(|sub_type| match &sub_type.composite_type.inner {
+                    wasm::CompositeInnerType::Func(func_type) => Some(func_type.clone()),
+                    _ => None,
+                })
+                .collect(),
+        };
+        for frozen in &self.frozen {
+            match (frozen.kind, &frozen.value) {
+                (FrozenKind::Global(global_index), value) => {
+                    values.globals.insert(global_index, value.clone());
+                }
+                (FrozenKind::TableSlot { table, slot }, Expression::RefFunc { func_index }) => {
+                    values.slots.insert((table, slot), *func_index);
+                    if let Some(ty) = self.func_signature(*func_index) {
+                        values.slot_types.insert(*func_index, ty.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+        for func in &mut self.funcs {
+            func.freeze(&values);
+        }
+    }
+}
+
+impl Func {
+    // The tables this function writes to. Growing a table doesn't change
+    // the slots it already has.
+    pub(crate) fn written_tables(&self) -> Vec<u32> {
+        let mut tables = vec![];
+        self.visit_statements(|stmt| match stmt {
+            Statement::TableSet(stmt) => tables.push(stmt.table),
+            Statement::TableFill(stmt) => tables.push(stmt.table),
+            _ => {}
+        });
+        tables
+    }
+
+    fn freeze(&mut self, values: &FrozenValues) {
+        fn expression(expr: &mut Expression, values: &FrozenValues) {
+            for operand in expr.operands_mut() {
+                expression(operand, values);
+            }
+            match expr {
+                Expression::GetGlobal(get) => {
+                    if let Some(value) = values.globals.get(&get.global_index) {
+                        *expr = value.clone();
+                    }
+                }
+                Expression::CallIndirect(call) => {
+                    if let Some(func_index) = values.direct_callee(call) {
+                        *expr = Expression::Call(CallExpression {
+                            func_index,
+                            params: std::mem::take(&mut call.params),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        fn statement(stmt: &mut Statement, values: &FrozenValues) {
+            for expr in stmt.expressions_mut() {
+                expression(expr, values);
+            }
+            match stmt {
+                Statement::If(_) | Statement::Loop(_) => {
+                    for stmt in stmt.bodies_mut().into_iter().flatten() {
+                        statement(stmt, values);
+                    }
+                }
+                Statement::CallIndirect(call) => {
+                    if let Some(func_index) = values.direct_callee(call) {
+                        *stmt = Statement::Call(CallExpression {
+                            func_index,
+                            params: std::mem::take(&mut call.params),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for block in self.blocks.values_mut() {
+            for stmt in &mut block.statements {
+                statement(stmt, values);
+            }
+            for expr in block.terminator.expressions_mut() {
+                expression(expr, values);
+            }
+        }
+    }
+}
@@ -0,0 +1,549 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use arbitrary::{Arbitrary, Unstructured};
+
+use crate::ir::render::WIDTH;
+use crate::ir::*;
+
+// Bounds the size of generated functions. Expression depth is capped so that
+// generation itself can't overflow the stack; the printers still see trees
+// much deeper than anything the decoder produces in practice.
+const MAX_PARAMS: usize = 4;
+const MAX_RESULTS: usize = 2;
+const MAX_LOCALS: usize = 8;
+const MAX_BLOCKS: usize = 16;
+const MAX_BLOCK_PARAMS: usize = 3;
+const MAX_STATEMENTS: usize = 8;
+const MAX_OPERANDS: usize = 4;
+const MAX_STATEMENT_DEPTH: u32 = 4;
+const MAX_EXPRESSION_DEPTH: u32 = 32;
+
+/// A randomly generated function, for fuzzing the printers independently of
+/// the decoder.
+///
+/// The IR is structurally valid: every branch targets an existing block,
+/// every local and block param reference is in range, and the entry block
+/// has no params. It is not necessarily well-typed.
+pub struct ArbitraryFunc(Func);
+
+impl ArbitraryFunc {
+    pub fn write(&self, mut output: impl std::io::Write) -> anyhow::Result<()> {
+        self.0
+            .pretty(INDENT, &pretty::BoxAllocator)
+            .render(WIDTH, &mut output)?;
+        writeln!(output)?;
+        Ok(())
+    }
+
+    pub fn write_graphviz(&self, mut output: impl std::io::Write) -> anyhow::Result<()> {
+        self.0.to_graphviz(&mut output)?;
+        writeln!(output)?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ArbitraryFunc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let locals: Vec<_> = self
+            .0
+            .locals
+            .iter()
+            .map(|local| (&local.name, local.ty))
+            .collect();
+        let mut blocks: Vec<_> = self.0.blocks.iter().collect();
+        blocks.sort_by_key(|(index, _)| **index);
+        f.debug_struct("ArbitraryFunc")
+            .field("ty", &self.0.ty)
+            .field("locals", &locals)
+            .field("blocks", &blocks)
+            .finish()
+    }
+}
+
+impl<'a> Arbitrary<'a> for ArbitraryFunc {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let params = arbitrary_val_types(u, MAX_PARAMS)?;
+        let results = arbitrary_val_types(u, MAX_RESULTS)?;
+
+        let mut locals = Vec::new();
+        for (i, param) in params.iter().enumerate() {
+            locals.push(Local {
+                ty: *param,
+                name: format!("arg{}", i),
+            });
+        }
+        for ty in arbitrary_val_types(u, MAX_LOCALS)? {
+            let prefix = match ty {
+                wasm::ValType::I32 | wasm::ValType::I64 => "i",
+                _ => "f",
+            };
+            let name = format!("{}{}", prefix, locals.len());
+            locals.push(Local { ty, name });
+        }
+
+        // The entry block must not have params, the rest are arbitrary.
+        let num_blocks = u.int_in_range(1..=MAX_BLOCKS)?;
+        let mut block_params = vec![Vec::new()];
+        for _ in 1..num_blocks {
+            block_params.push(arbitrary_val_types(u, MAX_BLOCK_PARAMS)?);
+        }
+
+        let mut blocks = HashMap::new();
+        for (i, params) in block_params.into_iter().enumerate() {
+            let mut generator = Generator {
+                u: &mut *u,
+                num_locals: locals.len() as u32,
+                num_blocks: num_blocks as u32,
+                num_block_params: params.len() as u32,
+            };
+            let statements = generator.statements(0)?;
+            let terminator = generator.terminator()?;
+            blocks.insert(
+                BlockIndex(i as u32),
+                Block {
+                    params,
+                    statements,
+                    terminator,
+                    terminator_offsets: None,
+                },
+            );
+        }
+
+        Ok(ArbitraryFunc(Func {
+            index: u.arbitrary()?,
+            ty: wasm::FuncType::new(params, results),
+            locals,
+            blocks,
+            entry_block: BlockIndex(0),
+            error: None,
+            names: Arc::default(),
+            multi_memory: u.arbitrary()?,
+            data_ranges: Vec::new(),
+            callee_names: HashMap::new(),
+            deadline: None,
+            over_budget: None,
+            structure: None,
+            frame: None,
+            signedness: HashMap::new(),
+            body_range: 0..0,
+            exports: Vec::new(),
+            is_start: false,
+            show_offsets: false,
+            trailing_instructions: Vec::new(),
+            pass_dumps: Vec::new(),
+        }))
+    }
+}
+
+fn arbitrary_val_types(u: &mut Unstructured, max: usize) -> arbitrary::Result<Vec<wasm::ValType>> {
+    let len = u.int_in_range(0..=max)?;
+    let mut types = Vec::with_capacity(len);
+    for _ in 0..len {
+        types.push(*u.choose(&[
+            wasm::ValType::I32,
+            wasm::ValType::I64,
+            wasm::ValType::F32,
+            wasm::ValType::F64,
+        ])?);
+    }
+    Ok(types)
+}
+
+// Generates the contents of a single block.
+struct Generator<'u, 'a> {
+    u: &'u mut Unstructured<'a>,
+    num_locals: u32,
+    num_blocks: u32,
+    num_block_params: u32,
+}
+
+impl Generator<'_, '_> {
+    fn block_index(&mut self) -> arbitrary::Result<BlockIndex> {
+        Ok(BlockIndex(self.u.int_in_range(0..=self.num_blocks - 1)?))
+    }
+
+    fn local_index(&mut self) -> arbitrary::Result<Option<LocalIdx>> {
+        if self.num_locals == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.u.int_in_range(0..=self.num_locals - 1)?.into()))
+    }
+
+    fn mem_arg(&mut self) -> arbitrary::Result<wasm::MemArg> {
+        Ok(wasm::MemArg {
+            align: self.u.int_in_range(0..=3)?,
+            max_align: 3,
+            offset: self.u.arbitrary()?,
+            memory: self.u.int_in_range(0..=1)?,
+        })
+    }
+
+    fn terminator(&mut self) -> arbitrary::Result<Terminator> {
+        Ok(match self.u.int_in_range(0..=8)? {
+            0 => Terminator::Unknown,
+            1 => Terminator::Unreachable,
+            2 => Terminator::Return(self.expressions(0)?),
+            3 => Terminator::Br(self.block_index()?, self.expressions(0)?),
+            4 => Terminator::BrIf(
+                self.expression(0)?,
+                self.block_index()?,
+                self.block_index()?,
+                self.expressions(0)?,
+            ),
+            5 => {
+                let len = self.u.int_in_range(0..=MAX_OPERANDS)?;
+                let mut targets = Vec::with_capacity(len);
+                for _ in 0..len {
+                    targets.push(self.block_index()?);
+                }
+                Terminator::BrTable(
+                    self.expression(0)?,
+                    targets,
+                    self.block_index()?,
+                    self.expressions(0)?,
+                )
+            }
+            6 => {
+                let len = self.u.int_in_range(0..=MAX_OPERANDS)?;
+                let mut catches = Vec::with_capacity(len);
+                for _ in 0..len {
+                    catches.push(self.catch()?);
+                }
+                Terminator::Try(self.block_index()?, catches, self.expressions(0)?)
+            }
+            7 => Terminator::Throw(self.u.arbitrary()?, self.expressions(0)?),
+            _ => Terminator::ThrowRef(self.expression(0)?),
+        })
+    }
+
+    fn catch(&mut self) -> arbitrary::Result<Catch> {
+        let target = self.block_index()?;
+        Ok(match self.u.int_in_range(0..=3)? {
+            0 => Catch::One {
+                tag: self.u.arbitrary()?,
+                target,
+            },
+            1 => Catch::OneRef {
+                tag: self.u.arbitrary()?,
+                target,
+            },
+            2 => Catch::All { target },
+            _ => Catch::AllRef { target },
+        })
+    }
+
+    fn statements(&mut self, depth: u32) -> arbitrary::Result<Vec<Statement>> {
+        let len = self.u.int_in_range(0..=MAX_STATEMENTS)?;
+        let mut statements = Vec::with_capacity(len);
+        for _ in 0..len {
+            statements.push(self.statement(depth)?);
+        }
+        Ok(statements)
+    }
+
+    fn statement(&mut self, depth: u32) -> arbitrary::Result<Statement> {
+        let max_kind = if depth < MAX_STATEMENT_DEPTH { 18 } else { 16 };
+        Ok(match self.u.int_in_range(0..=max_kind)? {
+            0 => Statement::Nop,
+            1 => Statement::Drop(self.expression(0)?),
+            2 => match self.local_index()? {
+                Some(index) => Statement::LocalSet(LocalSetStatement {
+                    index,
+                    value: Box::new(self.expression(0)?),
+                }),
+                None => Statement::Nop,
+            },
+            3 => {
+                let len = self.u.int_in_range(0..=MAX_OPERANDS)?;
+                let mut index = Vec::with_capacity(len);
+                for _ in 0..len {
+                    if let Some(local_index) = self.local_index()? {
+                        index.push(local_index);
+                    }
+                }
+                Statement::LocalSetN(LocalSetNStatement {
+                    index,
+                    value: Box::new(self.expression(0)?),
+                })
+            }
+            4 => Statement::GlobalSet(GlobalSetStatement {
+                index: self.u.arbitrary()?,
+                value: Box::new(self.expression(0)?),
+            }),
+            5 => Statement::MemoryStore(MemoryStoreStatement {
+                arg: self.mem_arg()?,
+                index: Box::new(self.expression(0)?),
+                value: Box::new(self.expression(0)?),
+            }),
+            6 => Statement::Call(self.call(0)?),
+            7 => Statement::CallIndirect(self.call_indirect(0)?),
+            8 => Statement::TableSet(TableSetStatement {
+                table: self.u.arbitrary()?,
+                index: Box::new(self.expression(0)?),
+                value: Box::new(self.expression(0)?),
+            }),
+            9 => Statement::TableFill(TableFillStatement {
+                table: self.u.arbitrary()?,
+                index: Box::new(self.expression(0)?),
+                value: Box::new(self.expression(0)?),
+                len: Box::new(self.expression(0)?),
+            }),
+            10 => Statement::StructSet(StructSetStatement {
+                type_index: self.u.arbitrary()?,
+                field: self.u.arbitrary()?,
+                object: Box::new(self.expression(0)?),
+                value: Box::new(self.expression(0)?),
+            }),
+            11 => Statement::ArraySet(ArraySetStatement {
+                type_index: self.u.arbitrary()?,
+                array: Box::new(self.expression(0)?),
+                index: Box::new(self.expression(0)?),
+                value: Box::new(self.expression(0)?),
+            }),
+            12 => Statement::ArrayFill(ArrayFillStatement {
+                type_index: self.u.arbitrary()?,
+                array: Box::new(self.expression(0)?),
+                offset: Box::new(self.expression(0)?),
+                value: Box::new(self.expression(0)?),
+                len: Box::new(self.expression(0)?),
+            }),
+            13 => Statement::ArrayCopy(ArrayCopyStatement {
+                dst_type_index: self.u.arbitrary()?,
+                src_type_index: self.u.arbitrary()?,
+                dst: Box::new(self.expression(0)?),
+                dst_offset: Box::new(self.expression(0)?),
+                src: Box::new(self.expression(0)?),
+                src_offset: Box::new(self.expression(0)?),
+                len: Box::new(self.expression(0)?),
+            }),
+            14 => Statement::ArrayInit(ArrayInitStatement {
+                type_index: self.u.arbitrary()?,
+                segment: self.u.arbitrary()?,
+                array: Box::new(self.expression(0)?),
+                offset: Box::new(self.expression(0)?),
+                source_offset: Box::new(self.expression(0)?),
+                len: Box::new(self.expression(0)?),
+            }),
+            15 => Statement::Location(self.u.arbitrary()?),
+            16 => Statement::CallRef(self.call_ref(0)?),
+            17 => Statement::If(IfStatement {
+                condition: Box::new(self.expression(0)?),
+                true_statements: self.statements(depth + 1)?,
+                false_statements: self.statements(depth + 1)?,
+            }),
+            _ => Statement::Loop(LoopStatement {
+                kind: self.u.arbitrary()?,
+                condition: Box::new(self.expression(0)?),
+                body: self.statements(depth + 1)?,
+            }),
+        })
+    }
+
+    fn call(&mut self, depth: u32) -> arbitrary::Result<CallExpression> {
+        Ok(CallExpression {
+            func_index: self.u.arbitrary()?,
+            params: self.expressions(depth + 1)?,
+        })
+    }
+
+    fn call_indirect(&mut self, depth: u32) -> arbitrary::Result<CallIndirectExpression> {
+        Ok(CallIndirectExpression {
+            func_type_index: self.u.arbitrary()?,
+            table_index: self.u.arbitrary()?,
+            callee_index: Box::new(self.expression(depth + 1)?),
+            params: self.expressions(depth + 1)?,
+            targets: IndirectTargets::Unknown,
+        })
+    }
+
+    fn call_ref(&mut self, depth: u32) -> arbitrary::Result<CallRefExpression> {
+        Ok(CallRefExpression {
+            type_index: self.u.arbitrary()?,
+            callee: Box::new(self.expression(depth + 1)?),
+            params: self.expressions(depth + 1)?,
+        })
+    }
+
+    fn expressions(&mut self, depth: u32) -> arbitrary::Result<Vec<Expression>> {
+        let len = self.u.int_in_range(0..=MAX_OPERANDS)?;
+        let mut expressions = Vec::with_capacity(len);
+        for _ in 0..len {
+            expressions.push(self.expression(depth)?);
+        }
+        Ok(expressions)
+    }
+
+    fn expression(&mut self, depth: u32) -> arbitrary::Result<Expression> {
+        // Past the depth limit only leaves are generated.
+        let max_kind = if depth < MAX_EXPRESSION_DEPTH { 36 } else { 12 };
+        Ok(match self.u.int_in_range(0..=max_kind)? {
+            // Leaves
+            0 => Expression::I32Const {
+                value: self.u.arbitrary()?,
+            },
+            1 => Expression::I64Const {
+                value: self.u.arbitrary()?,
+            },
+            2 => Expression::F32Const {
+                value: f32::from_bits(self.u.arbitrary()?).into(),
+            },
+            3 => Expression::F64Const {
+                value: f64::from_bits(self.u.arbitrary()?).into(),
+            },
+            4 if self.num_block_params > 0 => {
+                Expression::BlockParam(self.u.int_in_range(0..=self.num_block_params - 1)?)
+            }
+            5 => match self.local_index()? {
+                Some(local_index) => Expression::GetLocal(GetLocalExpression { local_index }),
+                None => Expression::Bottom,
+            },
+            6 => Expression::GetGlobal(GetGlobalExpression {
+                global_index: self.u.arbitrary()?,
+            }),
+            7 => Expression::MemorySize {
+                memory: self.u.int_in_range(0..=1)?,
+            },
+            8 => Expression::Bottom,
+            9 => Expression::V128Const {
+                value: self.u.arbitrary()?,
+            },
+            10 => Expression::RefNull {
+                ty: self.heap_type()?,
+            },
+            11 => Expression::RefFunc {
+                func_index: self.u.arbitrary()?,
+            },
+            12 => Expression::TableSize {
+                table: self.u.arbitrary()?,
+            },
+
+            // Interior nodes
+            13 => Expression::Unary(self.u.arbitrary()?, Box::new(self.expression(depth + 1)?)),
+            14 => Expression::Binary(
+                self.u.arbitrary()?,
+                Box::new(self.expression(depth + 1)?),
+                Box::new(self.expression(depth + 1)?),
+            ),
+            15 => Expression::Call(self.call(depth)?),
+            16 => Expression::CallIndirect(self.call_indirect(depth)?),
+            17 => {
+                let len = self.u.int_in_range(0..=MAX_OPERANDS)?;
+                let mut local_indices = Vec::with_capacity(len);
+                for _ in 0..len {
+                    if let Some(local_index) = self.local_index()? {
+                        local_indices.push(local_index);
+                    }
+                }
+                Expression::GetLocalN(GetLocalNExpression { local_indices })
+            }
+            18 => Expression::Select(SelectExpression {
+                condition: Box::new(self.expression(depth + 1)?),
+                on_true: Box::new(self.expression(depth + 1)?),
+                on_false: Box::new(self.expression(depth + 1)?),
+                ty: None,
+            }),
+            19 => Expression::MemoryLoad(MemoryLoadExpression {
+                kind: self.u.arbitrary()?,
+                arg: self.mem_arg()?,
+                index: Box::new(self.expression(depth + 1)?),
+            }),
+            20 => Expression::MemoryGrow(MemoryGrowExpression {
+                memory: self.u.int_in_range(0..=1)?,
+                value: Box::new(self.expression(depth + 1)?),
+            }),
+            21 => Expression::Ternary(
+                self.u.arbitrary()?,
+                Box::new(self.expression(depth + 1)?),
+                Box::new(self.expression(depth + 1)?),
+                Box::new(self.expression(depth + 1)?),
+            ),
+            22 => Expression::ExtractLane(ExtractLaneExpression {
+                shape: self.u.arbitrary()?,
+                signed: self.u.arbitrary()?,
+                lane: self.u.arbitrary()?,
+                vector: Box::new(self.expression(depth + 1)?),
+            }),
+            23 => Expression::ReplaceLane(ReplaceLaneExpression {
+                shape: self.u.arbitrary()?,
+                lane: self.u.arbitrary()?,
+                vector: Box::new(self.expression(depth + 1)?),
+                value: Box::new(self.expression(depth + 1)?),
+            }),
+            24 => Expression::Shuffle(ShuffleExpression {
+                lanes: self.u.arbitrary()?,
+                lhs: Box::new(self.expression(depth + 1)?),
+                rhs: Box::new(self.expression(depth + 1)?),
+            }),
+            25 => Expression::TableGet(TableGetExpression {
+                table: self.u.arbitrary()?,
+                index: Box::new(self.expression(depth + 1)?),
+            }),
+            26 => Expression::TableGrow(TableGrowExpression {
+                table: self.u.arbitrary()?,
+                value: Box::new(self.expression(depth + 1)?),
+                delta: Box::new(self.expression(depth + 1)?),
+            }),
+            27 => Expression::StructNew(StructNewExpression {
+                type_index: self.u.arbitrary()?,
+                default: false,
+                fields: self.expressions(depth + 1)?,
+            }),
+            28 => Expression::StructGet(StructGetExpression {
+                type_index: self.u.arbitrary()?,
+                field: self.u.arbitrary()?,
+                extension: self.u.arbitrary()?,
+                object: Box::new(self.expression(depth + 1)?),
+            }),
+            29 => Expression::ArrayNew(ArrayNewExpression {
+                type_index: self.u.arbitrary()?,
+                kind: self.u.arbitrary()?,
+                args: self.expressions(depth + 1)?,
+            }),
+            30 => Expression::ArrayGet(ArrayGetExpression {
+                type_index: self.u.arbitrary()?,
+                extension: self.u.arbitrary()?,
+                array: Box::new(self.expression(depth + 1)?),
+                index: Box::new(self.expression(depth + 1)?),
+            }),
+            31 => Expression::ArrayLen(Box::new(self.expression(depth + 1)?)),
+            32 => Expression::RefTest(self.ref_cast(depth)?),
+            33 => Expression::RefCast(self.ref_cast(depth)?),
+            34 => Expression::RefAsNonNull(Box::new(self.expression(depth + 1)?)),
+            35 => Expression::CallRef(self.call_ref(depth)?),
+            _ => Expression::Bottom,
+        })
+    }
+
+    fn ref_cast(&mut self, depth: u32) -> arbitrary::Result<RefCastExpression> {
+        Ok(RefCastExpression {
+            nullable: self.u.arbitrary()?,
+            heap_type: self.heap_type()?,
+            value: Box::new(self.expression(depth + 1)?),
+        })
+    }
+
+    fn heap_type(&mut self) -> arbitrary::Result<wasm::HeapType> {
+        const ABSTRACT: [wasm::AbstractHeapType; 11] = [
+            wasm::AbstractHeapType::Func,
+            wasm::AbstractHeapType::Extern,
+            wasm::AbstractHeapType::Any,
+            wasm::AbstractHeapType::None,
+            wasm::AbstractHeapType::NoExtern,
+            wasm::AbstractHeapType::NoFunc,
+            wasm::AbstractHeapType::Eq,
+            wasm::AbstractHeapType::Struct,
+            wasm::AbstractHeapType::Array,
+            wasm::AbstractHeapType::I31,
+            wasm::AbstractHeapType::Exn,
+        ];
+        Ok(if self.u.arbitrary()? {
+            wasm::HeapType::Abstract {
+                shared: false,
+                ty: *self.u.choose(&ABSTRACT)?,
+            }
+        } else {
+            wasm::HeapType::Concrete(wasm::UnpackedIndex::Module(self.u.int_in_range(0..=99)?))
+        })
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::hash_map::Entry;
+use std::fmt;
+
+use crate::ir::fold::fold_binary;
+use crate::ir::print::val_type_name;
+use crate::ir::*;
+
+/// Globals with a conventional meaning in modules built by LLVM and
+/// Emscripten, printed by name instead of as `globals[N]`.
+const WELL_KNOWN_GLOBALS: [&str; 3] = ["__stack_pointer", "__memory_base", "__table_base"];
+
+/// How one function uses a global.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalUse {
+    pub func_index: FuncIdx,
+    /// The name the function is printed under, e.g. `malloc` or `func3`.
+    pub func_name: String,
+    pub reads: usize,
+    pub writes: usize,
+    /// Whether every return leaves the global with the value it had on
+    /// entry, either by writing back a saved copy or by undoing constant
+    /// adjustments, as a shadow stack pointer does. Always true for a
+    /// function that doesn't write the global.
+    pub balanced: bool,
+}
+
+/// Where a mutable global is read and written.
+#[derive(Debug, Clone)]
+pub struct GlobalReport {
+    pub global_index: GlobalIdx,
+    /// The well-known name of the global, if it has one.
+    pub name: Option<String>,
+    pub imported: bool,
+    /// The functions that read or write the global, in index order.
+    pub uses: Vec<GlobalUse>,
+    ty: wasm::ValType,
+}
+
+impl GlobalReport {
+    /// Whether every function that writes the global restores it before
+    /// returning.
+    pub fn is_balanced(&self) -> bool {
+        self.uses.iter().all(|global_use| global_use.balanced)
+    }
+}
+
+impl fmt::Display for GlobalReport {
+    /// e.g. `globals[0]: mut i32, read by func1 (2), written by func1 (2, balanced)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} (globals[{}]): ", name, self.global_index)?,
+            None => write!(f, "globals[{}]: ", self.global_index)?,
+        }
+        if self.imported {
+            write!(f, "imported ")?;
+        }
+        write!(f, "mut {}", self.ty)?;
+
+        let readers: Vec<String> = self
+            .uses
+            .iter()
+            .filter(|global_use| global_use.reads > 0)
+            .map(|global_use| format!("{} ({})", global_use.func_name, global_use.reads))
+            .collect();
+        let writers: Vec<String> = self
+            .uses
+            .iter()
+            .filter(|global_use| global_use.writes > 0)
+            .map(|global_use| {
+                format!(
+                    "{} ({}, {})",
+                    global_use.func_name,
+                    global_use.writes,
+                    if global_use.balanced {
+                        "balanced"
+                    } else {
+                        "unbalanced"
+                    }
+                )
+            })
+            .collect();
+
+        if readers.is_empty() && writers.is_empty() {
+            write!(f, ", unused")?;
+        }
+        if !readers.is_empty() {
+            write!(f, ", read by {}", readers.join(", "))?;
+        }
+        if !writers.is_empty() {
+            write!(f, ", written by {}", writers.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A global as it's declared at the top of the printed module, e.g.
+/// `global __stack_pointer: mut i32 = 66560`.
+#[derive(Debug, Clone)]
+pub(crate) struct GlobalDef {
+    name: String,
+    ty: String,
+    mutable: bool,
+    // The initializer of a defined global, if it's simple enough to print.
+    init: Option<String>,
+    // `module.field` of an imported global.
+    import: Option<String>,
+}
+
+impl fmt::Display for GlobalDef {
+    /// e.g. `global globals[1]: i32 = 1024` or
+    /// `global $g: mut i32 // imported from env.g`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "global {}: ", self.name)?;
+        if self.mutable {
+            write!(f, "mut ")?;
+        }
+        write!(f, "{}", self.ty)?;
+        if let Some(init) = &self.init {
+            write!(f, " = {}", init)?;
+        }
+        if let Some(import) = &self.import {
+            write!(f, " // imported from {}", import)?;
+        }
+        Ok(())
+    }
+}
+
+// The value of a constant expression, with arithmetic on constants folded,
+// or `None` if it does something not handled here, such as allocating a GC
+// object.
+pub(crate) fn const_expr_value(init_expr: &wasm::ConstExpr) -> anyhow::Result<Option<Expression>> {
+    let mut stack = vec![];
+    let mut ops = init_expr.get_operators_reader();
+    loop {
+        let value = match ops.read()? {
+            wasm::Operator::End => break,
+            wasm::Operator::I32Const { value } => Expression::I32Const { value },
+            wasm::Operator::I64Const { value } => Expression::I64Const { value },
+            wasm::Operator::F32Const { value } => Expression::F32Const { value },
+            wasm::Operator::F64Const { value } => Expression::F64Const { value },
+            wasm::Operator::V128Const { value } => Expression::V128Const {
+                value: *value.bytes(),
+            },
+            wasm::Operator::RefNull { hty } => Expression::RefNull { ty: hty },
+            wasm::Operator::RefFunc { function_index } => Expression::RefFunc {
+                func_index: function_index.into(),
+            },
+            wasm::Operator::GlobalGet { global_index } => {
+                Expression::GetGlobal(GetGlobalExpression {
+                    global_index: global_index.into(),
+                })
+            }
+            wasm::Operator::I32Add => const_binary(&mut stack, BinaryExpression::I32Add)?,
+            wasm::Operator::I32Sub => const_binary(&mut stack, BinaryExpression::I32Sub)?,
+            wasm::Operator::I32Mul => const_binary(&mut stack, BinaryExpression::I32Mul)?,
+            wasm::Operator::I64Add => const_binary(&mut stack, BinaryExpression::I64Add)?,
+            wasm::Operator::I64Sub => const_binary(&mut stack, BinaryExpression::I64Sub)?,
+            wasm::Operator::I64Mul => const_binary(&mut stack, BinaryExpression::I64Mul)?,
+            _ => return Ok(None),
+        };
+        stack.push(value);
+    }
+    Ok(stack.pop())
+}
+
+// Pops the operands of `op` and pushes the result, computed if both are
+// constants.
+fn const_binary(stack: &mut Vec<Expression>, op: BinaryExpression) -> anyhow::Result<Expression> {
+    let (Some(rhs), Some(lhs)) = (stack.pop(), stack.pop()) else {
+        bail!("constant expression pops from an empty stack");
+    };
+    Ok(fold_binary(&op, &lhs, &rhs)
+        .unwrap_or_else(|| Expression::Binary(op, Box::new(lhs), Box::new(rhs))))
+}
+
+// How a value from `const_expr_value` is printed, or `None` for one it
+// doesn't produce.
+fn const_expr_text(expr: &Expression, names: &Names) -> Option<String> {
+    Some(match expr {
+        Expression::I32Const { value } => value.to_string(),
+        Expression::I64Const { value } => value.to_string(),
+        Expression::F32Const { value } => f32::from_bits(value.bits()).to_string(),
+        Expression::F64Const { value } => f64::from_bits(value.bits()).to_string(),
+        Expression::V128Const { value } => {
+            let lanes: Vec<String> = value
+                .chunks(4)
+                .map(|lane| format!("0x{:08x}", u32::from_le_bytes(lane.try_into().unwrap())))
+                .collect();
+            format!("i32x4({})", lanes.join(", "))
+        }
+        Expression::RefNull { .. } => "null".to_string(),
+        Expression::RefFunc { func_index } => names.func_name(*func_index),
+        Expression::GetGlobal(get) => match names.global(get.global_index) {
+            Some(name) => name.to_string(),
+            None => format!("globals[{}]", get.global_index),
+        },
+        Expression::Binary(op, lhs, rhs) => {
+            let operand = |expr: &Expression| {
+                let text = const_expr_text(expr, names)?;
+                Some(match expr {
+                    Expression::Binary(..) => format!("({})", text),
+                    _ => text,
+                })
+            };
+            format!(
+                "{} {} {}",
+                operand(lhs)?,
+                op.to_string_and_infix().0,
+                operand(rhs)?
+            )
+        }
+        _ => return None,
+    })
+}
+
+impl Module {
+    // Every global, imported ones first, as declared at the top of the
+    // printed module.
+    pub(crate) fn global_defs(&self) -> Vec<GlobalDef> {
+        let mut defs = vec![];
+        for (i, global) in self.globals.iter().enumerate() {
+            let global_index = GlobalIdx::from(i as u32);
+            defs.push(GlobalDef {
+                name: match self.names.global(global_index) {
+                    Some(name) => name.to_string(),
+                    None => format!("globals[{}]", global_index),
+                },
+                ty: val_type_name(global.content_type, &self.names),
+                mutable: global.mutable,
+                init: self
+                    .global_inits
+                    .get(&global_index)
+                    .and_then(|value| const_expr_text(value, &self.names)),
+                import: self.global_imports.get(i).cloned(),
+            });
+        }
+        defs
+    }
+
+    /// Where each mutable global is read and written, in global index order.
+    pub fn global_report(&self) -> Vec<GlobalReport> {
+        let counts: Vec<HashMap<GlobalIdx, (usize, usize)>> =
+            self.funcs.iter().map(Func::global_counts).collect();
+
+        let mut reports = vec![];
+        for (global_index, global) in self.globals.iter().enumerate() {
+            if !global.mutable {
+                continue;
+            }
+            let global_index = GlobalIdx::from(global_index as u32);
+            let mut uses = vec![];
+            for (func, counts) in self.funcs.iter().zip(&counts) {
+                let Some(&(reads, writes)) = counts.get(&global_index) else {
+                    continue;
+                };
+                uses.push(GlobalUse {
+                    func_index: func.index,
+                    func_name: self.names.func_name(func.index),
+                    reads,
+                    writes,
+                    balanced: writes == 0 || func.restores_global(global_index),
+                });
+            }
+            reports.push(GlobalReport {
+                global_index,
+                name: self.names.globals.get(&global_index).cloned(),
+                imported: global_index.as_u32() < self.num_global_imports,
+                uses,
+                ty: global.content_type,
+            });
+        }
+        reports
+    }
+
+    // Names a global if `name` is well-known and neither the global nor the
+    // name is taken yet.
+    pub(crate) fn name_global(&mut self, global_index: GlobalIdx, name: &str) {
+        if !WELL_KNOWN_GLOBALS.contains(&name)
+            || self.names.globals.contains_key(&global_index)
+            || self.names.globals.values().any(|other| other == name)
+        {
+            return;
+        }
+        self.names.globals.insert(global_index, name.to_string());
+    }
+
+    // Without an import, export or debug name to go by, guess that the stack
+    // pointer is the only unnamed mutable integer global that's written and
+    // that every writer restores before returning.
+    pub(crate) fn name_stack_pointer(&mut self) {
+        if self
+            .names
+            .globals
+            .values()
+            .any(|name| name == "__stack_pointer")
+        {
+            return;
+        }
+        let candidates: Vec<GlobalIdx> = self
+            .global_report()
+            .into_iter()
+            .filter(|report| {
+                report.name.is_none()
+                    && matches!(report.ty, wasm::ValType::I32 | wasm::ValType::I64)
+                    && report.uses.iter().any(|global_use| global_use.writes > 0)
+                    && report.is_balanced()
+            })
+            .map(|report| report.global_index)
+            .collect();
+        if let [global_index] = candidates[..] {
+            self.name_global(global_index, "__stack_pointer");
+        }
+    }
+}
+
+impl Func {
+    // The number of reads and writes of every global the function uses.
+    pub(crate) fn global_counts(&self) -> HashMap<GlobalIdx, (usize, usize)> {
+        let mut counts: HashMap<GlobalIdx, (usize, usize)> = HashMap::new();
+        self.visit_statements(|stmt| {
+            if let Statement::GlobalSet(set) = stmt {
+                counts.entry(set.index).or_default().1 += 1;
+            }
+        });
+        self.visit_expressions(|expr| {
+            if let Expression::GetGlobal(get) = expr {
+                counts.entry(get.global_index).or_default().0 += 1;
+            }
+        });
+        counts
+    }
+
+    // Forward dataflow over the blocks in reverse post-order, tracking the
+    // global and the locals that hold its entry value plus a constant. Loop
+    // back edges are ignored, so this is a heuristic rather than a proof.
+    fn restores_global(&self, global: GlobalIdx) -> bool {
+        let mut entry_states = HashMap::new();
+        entry_states.insert(self.entry_block, Offsets::entry());
+        for block_index in self.reverse_post_order() {
+            let block = &self.blocks[&block_index];
+            let mut state = entry_states
+                .remove(&block_index)
+                .unwrap_or_else(Offsets::unknown);
+            for stmt in &block.statements {
+                state.statement(global, stmt);
+            }
+            if matches!(block.terminator, Terminator::Return(_)) && state.global != Some(0) {
+                return false;
+            }
+            for successor in block.successors() {
+                match entry_states.entry(successor) {
+                    Entry::Occupied(mut entry) => entry.get_mut().meet(&state),
+                    Entry::Vacant(entry) => {
+                        entry.insert(state.clone());
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+// Values known to be the global's value on function entry plus a constant.
+#[derive(Debug, Clone, PartialEq)]
+struct Offsets {
+    global: Option<i64>,
+    locals: HashMap<LocalIdx, i64>,
+}
+
+impl Offsets {
+    fn entry() -> Offsets {
+        Offsets {
+            global: Some(0),
+            locals: HashMap::new(),
+        }
+    }
+
+    fn unknown() -> Offsets {
+        Offsets {
+            global: None,
+            locals: HashMap::new(),
+        }
+    }
+
+    fn meet(&mut self, other: &Offsets) {
+        if self.global != other.global {
+            self.global = None;
+        }
+        self.locals
+            .retain(|local, offset| other.locals.get(local) == Some(offset));
+    }
+
+    fn eval(&self, global: GlobalIdx, expr: &Expression) -> Option<i64> {
+        match expr {
+            Expression::GetGlobal(get) if get.global_index == global => self.global,
+            Expression::GetLocal(get) => self.locals.get(&get.local_index).copied(),
+            Expression::Binary(op, lhs, rhs) => {
+                let constant = match **rhs {
+                    Expression::I32Const { value } => value as i64,
+                    Expression::I64Const { value } => value,
+                    _ => return None,
+                };
+                let base = self.eval(global, lhs)?;
+                match op {
+                    BinaryExpression::I32Add | BinaryExpression::I64Add => {
+                        Some(base.wrapping_add(constant))
+                    }
+                    BinaryExpression::I32Sub | BinaryExpression::I64Sub => {
+                        Some(base.wrapping_sub(constant))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn statement(&mut self, global: GlobalIdx, stmt: &Statement) {
+        match stmt {
+            Statement::LocalSet(set) => match self.eval(global, &set.value) {
+                Some(offset) => {
+                    self.locals.insert(set.index, offset);
+                }
+                None => {
+                    self.locals.remove(&set.index);
+                }
+            },
+            Statement::LocalSetN(set) => {
+                for index in &set.index {
+                    self.locals.remove(index);
+                }
+            }
+            Statement::GlobalSet(set) if set.index == global => {
+                self.global = self.eval(global, &set.value);
+            }
+            Statement::If(stmt) => {
+                let mut false_state = self.clone();
+                for stmt in &stmt.true_statements {
+                    self.statement(global, stmt);
+                }
+                for stmt in &stmt.false_statements {
+                    false_state.statement(global, stmt);
+                }
+                self.meet(&false_state);
+            }
+            Statement::Loop(stmt) => {
+                // The state at the top of the loop is what's known both on
+                // entry and after any number of iterations. Meeting only
+                // forgets offsets, so this stops.
+                loop {
+                    let before = self.clone();
+                    for stmt in &stmt.body {
+                        self.statement(global, stmt);
+                    }
+                    self.meet(&before);
+                    if *self == before {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
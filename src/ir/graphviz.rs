@@ -1,49 +1,245 @@
 use crate::ir::print::Ctx;
 use crate::ir::*;
 
+/// Options for the graphviz output, to keep the nodes of large functions
+/// a readable size.
+#[derive(Debug, Default, Clone)]
+pub struct GraphvizOptions {
+    /// How many statements to show in a block before eliding the rest.
+    pub max_statements: Option<usize>,
+    /// How many characters of a line to show, such as those of a long
+    /// expression, before cutting it short with an ellipsis.
+    pub max_line_length: Option<usize>,
+}
+
 impl Func {
+    /// Writes the function's control flow graph in DOT. Blocks are listed
+    /// in reverse post order, so that graphviz ranks them in the order
+    /// control reaches them, with each loop's blocks in a cluster of their
+    /// own. Branches back to a loop's header are drawn in red and don't
+    /// affect the ranking.
     pub fn to_graphviz(&self, output: &mut dyn std::io::Write) -> anyhow::Result<()> {
-        writeln!(output, "digraph func_{} {{", self.index)?;
-        writeln!(output, "  rankdir=TB;")?;
-        writeln!(
-            output,
-            "  node [shape=box, style=filled, fillcolor=lightblue, labeljust=l];"
-        )?;
-        writeln!(output, "")?;
+        self.to_graphviz_with_options(output, &GraphvizOptions::default())
+    }
 
-        let ctx = Ctx { func: self };
+    pub fn to_graphviz_with_options(
+        &self,
+        output: &mut dyn std::io::Write,
+        options: &GraphvizOptions,
+    ) -> anyhow::Result<()> {
+        self.write_graphviz_header(output)?;
 
-        // Write all blocks
-        let block_order = self.visual_block_order();
-        for block_index in &block_order {
-            if let Some(block) = self.blocks.get(block_index) {
-                write!(output, "  block_{} [label=\"", block_index.0)?;
-                let mut body = Vec::new();
-                block
-                    .pretty::<_, ()>(self, *block_index, false, ctx, &pretty::BoxAllocator)
-                    .render(80, &mut body)?;
-                let body_text = String::from_utf8(body)?.replace("\n", "\\l");
-                write!(output, "{}\\l", body_text)?;
-                writeln!(output, "\"];")?;
-            }
-        }
+        // Reachable blocks in reverse post order, then the rest.
+        let mut block_order = self.reverse_post_order();
+        let reachable: HashSet<BlockIndex> = block_order.iter().copied().collect();
+        block_order.extend(
+            (self.visual_block_order().into_iter())
+                .filter(|block_index| !reachable.contains(block_index)),
+        );
+
+        let dominators = self.dominator_tree();
+        let loops = Loops::new(self, &dominators);
+        loops.write(self, None, &block_order, 1, options, output)?;
 
-        writeln!(output, "")?;
+        writeln!(output)?;
 
         // Write edges between blocks
         for block_index in &block_order {
             if let Some(block) = self.blocks.get(block_index) {
-                let successors = block.successors();
-                for successor in successors {
+                for (successor, label) in self.graphviz_edges(&block.terminator)? {
+                    let mut attributes = vec![];
+                    if !label.is_empty() {
+                        attributes.push(format!("label=\"{}\"", dot_string(&label)));
+                    }
+                    if dominators.dominates(successor, *block_index) {
+                        attributes.push("color=red, constraint=false".to_string());
+                    }
+                    let attributes = if attributes.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" [{}]", attributes.join(", "))
+                    };
                     writeln!(
                         output,
-                        "  block_{} -> block_{};",
-                        block_index.0, successor.0
+                        "  block_{} -> block_{}{};",
+                        block_index.0, successor.0, attributes
                     )?;
                 }
             }
         }
 
+        self.write_graphviz_footer(output)
+    }
+
+    /// Writes the function's dominator tree in DOT, with the blocks
+    /// labelled as in [`Func::to_graphviz`] and an edge from each block to
+    /// those it immediately dominates. Blocks that can't be reached aren't
+    /// included.
+    pub fn dominators_to_graphviz(&self, output: &mut dyn std::io::Write) -> anyhow::Result<()> {
+        self.dominators_to_graphviz_with_options(output, &GraphvizOptions::default())
+    }
+
+    pub fn dominators_to_graphviz_with_options(
+        &self,
+        output: &mut dyn std::io::Write,
+        options: &GraphvizOptions,
+    ) -> anyhow::Result<()> {
+        self.write_graphviz_header(output)?;
+
+        let dominators = self.dominator_tree();
+        let block_order: Vec<BlockIndex> = (self.visual_block_order().into_iter())
+            .filter(|block_index| dominators.contains(*block_index))
+            .collect();
+        for block_index in &block_order {
+            self.write_graphviz_block(*block_index, 1, options, output)?;
+        }
+
+        writeln!(output)?;
+
+        for block_index in &block_order {
+            for child in dominators.children(*block_index) {
+                writeln!(output, "  block_{} -> block_{};", block_index.0, child.0)?;
+            }
+        }
+
+        self.write_graphviz_footer(output)
+    }
+
+    // The successors of a block ending in `terminator`, each with a label
+    // for its edge: which way a `br_if` or `br_table` goes to get there and
+    // the values it passes, one per line.
+    fn graphviz_edges(&self, terminator: &Terminator) -> anyhow::Result<Vec<(BlockIndex, String)>> {
+        let (edges, values): (Vec<(BlockIndex, String)>, &[Expression]) = match terminator {
+            Terminator::Br(target, values) => (vec![(*target, String::new())], values),
+            Terminator::BrIf(_, true_target, false_target, values) => (
+                vec![
+                    (*true_target, "true".to_string()),
+                    (*false_target, "false".to_string()),
+                ],
+                values,
+            ),
+            // Cases that go to the same block share an edge.
+            Terminator::BrTable(_, targets, default_target, values) => {
+                let mut edges: Vec<(BlockIndex, String)> = vec![];
+                let cases = (targets.iter().enumerate())
+                    .map(|(i, target)| (*target, i.to_string()))
+                    .chain([(*default_target, "default".to_string())]);
+                for (target, case) in cases {
+                    match edges.iter_mut().find(|(other, _)| *other == target) {
+                        Some((_, label)) => *label = format!("{}, {}", label, case),
+                        None => edges.push((target, case)),
+                    }
+                }
+                (edges, values)
+            }
+            // The values are the body's params, not the handlers'.
+            Terminator::Try(body, catches, values) => {
+                let mut edges = vec![(*body, self.graphviz_values(values)?)];
+                edges.extend(catches.iter().map(|catch| {
+                    let label = match catch {
+                        Catch::One { tag, .. } => format!("catch tag{}", tag),
+                        Catch::OneRef { tag, .. } => format!("catch_ref tag{}", tag),
+                        Catch::All { .. } => "catch_all".to_string(),
+                        Catch::AllRef { .. } => "catch_all_ref".to_string(),
+                    };
+                    (catch.target(), label)
+                }));
+                return Ok(edges);
+            }
+            _ => return Ok(vec![]),
+        };
+        let values = self.graphviz_values(values)?;
+        Ok(edges
+            .into_iter()
+            .map(|(target, label)| {
+                let label = match (label.is_empty(), values.is_empty()) {
+                    (_, true) => label,
+                    (true, false) => values.clone(),
+                    (false, false) => format!("{}\n{}", label, values),
+                };
+                (target, label)
+            })
+            .collect())
+    }
+
+    // The values a branch passes, as `b0 := value` lines.
+    fn graphviz_values(&self, values: &[Expression]) -> anyhow::Result<String> {
+        let ctx = Ctx {
+            func: self,
+            names: &self.names,
+            indent: INDENT,
+        };
+        let mut lines = vec![];
+        for (i, value) in values.iter().enumerate() {
+            let mut text = Vec::new();
+            value
+                .pretty(ctx, &pretty::BoxAllocator)
+                .render(80, &mut text)?;
+            lines.push(format!("b{} := {}", i, String::from_utf8(text)?));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn write_graphviz_header(&self, output: &mut dyn std::io::Write) -> anyhow::Result<()> {
+        writeln!(output, "digraph func_{} {{", self.index)?;
+        writeln!(output, "  rankdir=TB;")?;
+        writeln!(
+            output,
+            "  node [shape=box, style=filled, fillcolor=lightblue, labeljust=l];"
+        )?;
+        writeln!(output)?;
+        Ok(())
+    }
+
+    fn write_graphviz_block(
+        &self,
+        block_index: BlockIndex,
+        depth: usize,
+        options: &GraphvizOptions,
+        output: &mut dyn std::io::Write,
+    ) -> anyhow::Result<()> {
+        let ctx = Ctx {
+            func: self,
+            names: &self.names,
+            indent: INDENT,
+        };
+        if let Some(block) = self.blocks.get(&block_index) {
+            let mut body = Vec::new();
+            block
+                .pretty_elided(
+                    self,
+                    block_index,
+                    false,
+                    options.max_statements,
+                    ctx,
+                    &pretty::BoxAllocator,
+                )
+                .render(80, &mut body)?;
+            // Each line is left-justified.
+            let mut label = String::new();
+            for line in String::from_utf8(body)?.lines() {
+                let line = match options.max_line_length {
+                    Some(max) if line.chars().count() > max => {
+                        line.chars().take(max).collect::<String>() + "..."
+                    }
+                    _ => line.to_string(),
+                };
+                label.push_str(&dot_string(&line));
+                label.push_str("\\l");
+            }
+            writeln!(
+                output,
+                "{:indent$}block_{} [label=\"{}\"];",
+                "",
+                block_index.0,
+                label,
+                indent = depth * 2
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_graphviz_footer(&self, output: &mut dyn std::io::Write) -> anyhow::Result<()> {
         // Mark entry block differently
         writeln!(
             output,
@@ -55,3 +251,168 @@ impl Func {
         Ok(())
     }
 }
+
+// The natural loops of a function: for each block with a branch back to it
+// from a block it dominates, that block and every block that reaches the
+// branch without going through it.
+struct Loops {
+    bodies: HashMap<BlockIndex, HashSet<BlockIndex>>,
+    // The header of the innermost loop each block is in, other than the one
+    // it's the header of.
+    parents: HashMap<BlockIndex, BlockIndex>,
+}
+
+impl Loops {
+    fn new(func: &Func, dominators: &DominatorTree) -> Loops {
+        let predecessors = func.predecessors();
+        let mut bodies: HashMap<BlockIndex, HashSet<BlockIndex>> = HashMap::new();
+        for (block_index, block) in &func.blocks {
+            for successor in block.successors() {
+                if !dominators.dominates(successor, *block_index) {
+                    continue;
+                }
+                let body = bodies.entry(successor).or_default();
+                body.insert(successor);
+                let mut stack = vec![*block_index];
+                while let Some(member) = stack.pop() {
+                    // Blocks that can't be reached aren't in any loop.
+                    if dominators.contains(member) && body.insert(member) {
+                        stack.extend(predecessors.get(&member).into_iter().flatten());
+                    }
+                }
+            }
+        }
+
+        // Loops are either nested or apart, so the innermost one a block is
+        // in is the smallest.
+        let mut parents: HashMap<BlockIndex, BlockIndex> = HashMap::new();
+        for (header, body) in &bodies {
+            for member in body {
+                if member == header {
+                    continue;
+                }
+                match parents.get(member) {
+                    Some(parent) if bodies[parent].len() <= body.len() => {}
+                    _ => {
+                        parents.insert(*member, *header);
+                    }
+                }
+            }
+        }
+        Loops { bodies, parents }
+    }
+
+    fn parent(&self, block_index: BlockIndex) -> Option<BlockIndex> {
+        self.parents.get(&block_index).copied()
+    }
+
+    // Writes the blocks directly in the loop headed by `header`, or in no
+    // loop at all, with the loops nested in it as clusters.
+    fn write(
+        &self,
+        func: &Func,
+        header: Option<BlockIndex>,
+        block_order: &[BlockIndex],
+        depth: usize,
+        options: &GraphvizOptions,
+        output: &mut dyn std::io::Write,
+    ) -> anyhow::Result<()> {
+        for block_index in block_order {
+            if self.parent(*block_index) != header {
+                continue;
+            }
+            if !self.bodies.contains_key(block_index) {
+                func.write_graphviz_block(*block_index, depth, options, output)?;
+                continue;
+            }
+            let indent = depth * 2;
+            writeln!(
+                output,
+                "{:indent$}subgraph cluster_loop_{} {{",
+                "", block_index.0
+            )?;
+            writeln!(
+                output,
+                "{:indent$}  label=\"loop {}\"; labeljust=l; style=dashed; color=red;",
+                "", block_index
+            )?;
+            func.write_graphviz_block(*block_index, depth + 1, options, output)?;
+            self.write(
+                func,
+                Some(*block_index),
+                block_order,
+                depth + 1,
+                options,
+                output,
+            )?;
+            writeln!(output, "{:indent$}}}", "")?;
+        }
+        Ok(())
+    }
+}
+
+impl Module {
+    /// Writes the module's call graph in DOT: a node per function, labelled
+    /// with its name and the size of its body, and an edge per call. Edges
+    /// for indirect calls that may reach a function are dashed, and those
+    /// for references taken with `ref.func` dotted.
+    pub fn write_call_graph_graphviz(&self, mut output: impl std::io::Write) -> anyhow::Result<()> {
+        writeln!(output, "digraph call_graph {{")?;
+        writeln!(output, "  rankdir=LR;")?;
+        writeln!(
+            output,
+            "  node [shape=box, style=filled, fillcolor=lightblue];"
+        )?;
+        writeln!(output)?;
+
+        for i in 0..self.num_func_imports {
+            let func_index = FuncIdx::from(i);
+            let name = match self.linked_names.get(&func_index) {
+                Some(name) => name.clone(),
+                None => self.names.func_name(func_index),
+            };
+            writeln!(
+                output,
+                "  func_{} [label=\"{}\\nimport\", fillcolor=lightgrey];",
+                func_index,
+                dot_string(&name)
+            )?;
+        }
+        for func in &self.funcs {
+            writeln!(
+                output,
+                "  func_{} [label=\"{}\\n{} bytes\"];",
+                func.index,
+                dot_string(&func.name()),
+                func.body_range.len()
+            )?;
+        }
+
+        writeln!(output)?;
+
+        let call_graph = self.call_graph();
+        for func in &self.funcs {
+            for call in call_graph.callees(func.index) {
+                let style = match call.kind {
+                    CallKind::Direct => "",
+                    CallKind::Indirect => " [style=dashed]",
+                    CallKind::Reference => " [style=dotted]",
+                };
+                writeln!(
+                    output,
+                    "  func_{} -> func_{}{};",
+                    func.index, call.func_index, style
+                )?;
+            }
+        }
+
+        writeln!(output, "}}")?;
+        Ok(())
+    }
+}
+
+// Escapes `text` for a double-quoted DOT string, with line breaks centering
+// the line before them.
+fn dot_string(text: &str) -> String {
+    (text.replace('\\', "\\\\").replace('"', "\\\"")).replace('\n', "\\n")
+}
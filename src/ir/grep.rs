@@ -0,0 +1,52 @@
+use crate::ir::*;
+
+impl Module {
+    /// Writes each line of each printed function that `matches` accepts,
+    /// as `name:line: text` with lines numbered from one within the
+    /// function, like `grep -n`. Functions are rendered one at a time, so
+    /// the whole output is never held in memory.
+    pub fn write_grep(
+        &self,
+        matches: impl Fn(&str) -> bool,
+        output: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        self.grep(self.printed_funcs(), matches, output)
+    }
+
+    /// Like [`write_grep`](Module::write_grep), only searching the functions
+    /// at `func_indices`.
+    pub fn write_grep_in(
+        &self,
+        func_indices: &[FuncIdx],
+        matches: impl Fn(&str) -> bool,
+        output: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        let funcs = (func_indices.iter())
+            .map(|func_index| self.defined_func(*func_index))
+            .collect::<anyhow::Result<Vec<&Func>>>()?;
+        self.grep(funcs, matches, output)
+    }
+
+    fn grep(
+        &self,
+        funcs: Vec<&Func>,
+        matches: impl Fn(&str) -> bool,
+        mut output: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        let mut text = Vec::new();
+        for func in funcs {
+            text.clear();
+            RenderOptions::default().render_part(
+                self.pretty_func(func, INDENT, &pretty::BoxAllocator),
+                &mut text,
+            )?;
+            let text = std::str::from_utf8(&text)?;
+            for (i, line) in text.lines().enumerate() {
+                if matches(line) {
+                    writeln!(output, "{}:{}: {}", func.name(), i + 1, line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
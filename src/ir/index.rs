@@ -0,0 +1,76 @@
+use std::fmt;
+
+// Typed wrappers around the different wasm index spaces, so that an index
+// from one space can't be used in another by accident.
+macro_rules! index_type {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
+        #[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+        pub struct $name(u32);
+
+        impl $name {
+            pub fn as_u32(self) -> u32 {
+                self.0
+            }
+
+            pub fn as_usize(self) -> usize {
+                self.0 as usize
+            }
+        }
+
+        impl From<u32> for $name {
+            fn from(index: u32) -> Self {
+                $name(index)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    };
+}
+
+index_type!(
+    /// An index into the module's function index space, which starts with the
+    /// imported functions.
+    FuncIdx
+);
+
+index_type!(
+    /// An index into the functions defined in the module, excluding imports.
+    DefFuncIdx
+);
+
+index_type!(
+    /// An index into the module's type index space.
+    TypeIdx
+);
+
+index_type!(
+    /// An index into the module's global index space.
+    GlobalIdx
+);
+
+index_type!(
+    /// An index into a function's locals, which start with its params.
+    LocalIdx
+);
+
+impl FuncIdx {
+    /// The defined function this refers to, or `None` if it's one of the
+    /// first `num_imports` functions.
+    pub fn to_defined(self, num_imports: u32) -> Option<DefFuncIdx> {
+        self.0.checked_sub(num_imports).map(DefFuncIdx)
+    }
+}
+
+impl DefFuncIdx {
+    /// The index of this function in the module's function index space, or
+    /// `None` if that would overflow.
+    pub fn to_func(self, num_imports: u32) -> Option<FuncIdx> {
+        self.0.checked_add(num_imports).map(FuncIdx)
+    }
+}
@@ -0,0 +1,154 @@
+use crate::ir::*;
+
+/// What an indirect call is known to reach, printed as a comment after its
+/// callee.
+#[derive(Debug, Clone)]
+pub(crate) enum IndirectTargets {
+    Unknown,
+    /// The function in the slot a constant index picks, in a table that
+    /// keeps what its element segments put in it.
+    Slot(FuncIdx),
+    /// The functions the call may reach given the type it expects, with
+    /// [`DecodeOptions::indirect_candidates`].
+    Candidates(Vec<FuncIdx>),
+}
+
+// What `resolve_indirect_calls` looks up while the functions are borrowed
+// mutably.
+struct Targets {
+    slots: HashMap<(u32, u32), FuncIdx>,
+    // The signature of each function in a slot.
+    slot_types: HashMap<FuncIdx, wasm::FuncType>,
+    types: Vec<Option<wasm::FuncType>>,
+    // The candidates for each type a call expects, if they're wanted.
+    candidates: Option<HashMap<TypeIdx, Vec<FuncIdx>>>,
+}
+
+impl Targets {
+    fn of(&self, call: &CallIndirectExpression) -> IndirectTargets {
+        if let Some(func_index) = self.slot(call) {
+            return IndirectTargets::Slot(func_index);
+        }
+        match self
+            .candidates
+            .as_ref()
+            .and_then(|candidates| candidates.get(&call.func_type_index))
+        {
+            Some(candidates) => IndirectTargets::Candidates(candidates.clone()),
+            None => IndirectTargets::Unknown,
+        }
+    }
+
+    // The function in the slot the call's constant index picks, if it has
+    // the type the call expects.
+    fn slot(&self, call: &CallIndirectExpression) -> Option<FuncIdx> {
+        let slot = match *call.callee_index {
+            Expression::I32Const { value } => value as u32,
+            Expression::I64Const { value } => u32::try_from(value).ok()?,
+            _ => return None,
+        };
+        let func_index = *self.slots.get(&(call.table_index, slot))?;
+        let expected = self.types.get(call.func_type_index.as_usize())?.as_ref()?;
+        (self.slot_types.get(&func_index)? == expected).then_some(func_index)
+    }
+}
+
+impl Module {
+    // Works out what each `call_indirect` reaches: the function its
+    // constant index picks out of a table the host can't see and no
+    // function writes, or, with `candidates`, every function of the type it
+    // expects.
+    pub(crate) fn resolve_indirect_calls(&mut self, candidates: bool) {
+        // A function that couldn't be decoded might write any table.
+        let any_errors = self.funcs.iter().any(|func| func.error.is_some());
+        let written: HashSet<u32> = (self.funcs.iter())
+            .flat_map(|func| func.written_tables())
+            .collect();
+        let mut targets = Targets {
+            slots: HashMap::new(),
+            slot_types: HashMap::new(),
+            types: self
+                .rec_groups
+                .iter()
+                .flat_map(|rec_group| rec_group.types())
+                .map(|sub_type| match &sub_type.composite_type.inner {
+                    wasm::CompositeInnerType::Func(func_type) => Some(func_type.clone()),
+                    _ => None,
+                })
+                .collect(),
+            candidates: None,
+        };
+        if !any_errors {
+            for ((table, slot), func_index) in self.elem_slots() {
+                if self.escaped_table(table) || written.contains(&table) {
+                    continue;
+                }
+                targets.slots.insert((table, slot), func_index);
+                if let Some(ty) = self.func_signature(func_index) {
+                    targets.slot_types.insert(func_index, ty.clone());
+                }
+            }
+        }
+        if candidates {
+            let mut type_indices = HashSet::new();
+            for func in &mut self.funcs {
+                func.for_each_indirect_call_mut(&mut |call| {
+                    type_indices.insert(call.func_type_index);
+                });
+            }
+            targets.candidates = Some(
+                type_indices
+                    .into_iter()
+                    .map(|type_index| (type_index, self.indirect_targets(type_index)))
+                    .collect(),
+            );
+        }
+        if targets.slots.is_empty() && targets.candidates.is_none() {
+            return;
+        }
+        for func in &mut self.funcs {
+            func.resolve_indirect_calls(&targets);
+        }
+    }
+}
+
+impl Func {
+    fn resolve_indirect_calls(&mut self, targets: &Targets) {
+        self.for_each_indirect_call_mut(&mut |call| call.targets = targets.of(call));
+    }
+
+    fn for_each_indirect_call_mut(&mut self, f: &mut impl FnMut(&mut CallIndirectExpression)) {
+        fn expression(expr: &mut Expression, f: &mut impl FnMut(&mut CallIndirectExpression)) {
+            if let Expression::CallIndirect(call) = expr {
+                f(call);
+            }
+            for operand in expr.operands_mut() {
+                expression(operand, f);
+            }
+        }
+
+        fn statement(stmt: &mut Statement, f: &mut impl FnMut(&mut CallIndirectExpression)) {
+            match stmt {
+                Statement::CallIndirect(call) => f(call),
+                Statement::If(_) | Statement::Loop(_) => {
+                    for stmt in stmt.bodies_mut().into_iter().flatten() {
+                        statement(stmt, f);
+                    }
+                }
+                _ => {}
+            }
+            for expr in stmt.expressions_mut() {
+                expression(expr, f);
+            }
+        }
+
+        for block in self.blocks.values_mut() {
+            for stmt in &mut block.statements {
+                statement(stmt, f);
+            }
+            for expr in block.terminator.expressions_mut() {
+                expression(expr, f);
+            }
+        }
+    }
+}
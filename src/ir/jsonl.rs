@@ -0,0 +1,222 @@
+use std::fmt::Write;
+use std::io;
+
+use pretty::{Render, RenderAnnotated};
+
+use crate::ir::print::ModulePart;
+use crate::ir::*;
+
+impl Func {
+    /// The name the function is referred to by in printed output: its
+    /// name from the name section or its export name, or `funcN`.
+    pub fn name(&self) -> String {
+        self.names.func_name(self.index)
+    }
+
+    fn to_jsonl(&self) -> anyhow::Result<String> {
+        let text = self.to_string();
+
+        let num_statements: usize = self
+            .blocks
+            .values()
+            .flat_map(|block| &block.statements)
+            .filter(|stmt| !matches!(stmt, Statement::Location(_)))
+            .count();
+        let error = match &self.error {
+            Some(error) => json_string(&error.to_string()),
+            None => "null".to_string(),
+        };
+
+        Ok(format!(
+            "{{\"index\":{},\"name\":{},\"text\":{},\"metrics\":{{\"params\":{},\"locals\":{},\"blocks\":{},\"statements\":{}}},\"error\":{}}}",
+            self.index,
+            json_string(&self.name()),
+            json_string(&text),
+            self.ty.params().len(),
+            self.locals.len() - self.ty.params().len(),
+            self.blocks.len(),
+            num_statements,
+            error,
+        ))
+    }
+}
+
+impl Module {
+    /// Writes one JSON object per defined function, one per line, with the
+    /// function's index, name, printed text, some size metrics and its
+    /// decompile error if it has one.
+    pub fn write_jsonl(&self, mut output: impl std::io::Write) -> anyhow::Result<()> {
+        for func in self.printed_funcs() {
+            writeln!(output, "{}", func.to_jsonl()?)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_func_jsonl(
+        &self,
+        func_index: FuncIdx,
+        mut output: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        writeln!(output, "{}", self.defined_func(func_index)?.to_jsonl()?)?;
+        Ok(())
+    }
+
+    /// Writes one JSON object per function, imports included, one per line,
+    /// with the function's index, the name it's printed with, and for a
+    /// defined function the byte range of its body in the module, e.g.
+    /// `{"index":3,"name":"malloc","import":false,"range":[1204,1873]}`.
+    /// Meant for lining up other tools with the names printed here.
+    pub fn write_name_map(&self, mut output: impl std::io::Write) -> anyhow::Result<()> {
+        for i in 0..self.num_func_imports {
+            let func_index = FuncIdx::from(i);
+            let name = match self.linked_names.get(&func_index) {
+                Some(name) => name.clone(),
+                None => self.names.func_name(func_index),
+            };
+            writeln!(
+                output,
+                "{{\"index\":{},\"name\":{},\"import\":true,\"range\":null}}",
+                func_index,
+                json_string(&name),
+            )?;
+        }
+        for func in &self.funcs {
+            writeln!(
+                output,
+                "{{\"index\":{},\"name\":{},\"import\":false,\"range\":[{},{}]}}",
+                func.index,
+                json_string(&func.name()),
+                func.body_range.start,
+                func.body_range.end,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes one JSON object per printed function, one per line, with
+    /// where it is in the module's printed output and where each of its
+    /// statements is, for jumping between the output and the module, e.g.
+    /// `{"index":3,"lines":[4,9],"name":"f","range":[52,80],"statements":[{"column":2,"line":5,"offset":54}]}`.
+    /// `lines` is the range of lines the function is printed on, end
+    /// excluded, and `range` the byte range of its body in the module.
+    /// Lines and columns are numbered from zero. Terminators, such as a
+    /// `return` or the condition of an `if`, are listed as statements too,
+    /// and each starts at the position given and runs up to the next one.
+    pub fn write_line_map(&self, output: impl std::io::Write) -> anyhow::Result<()> {
+        self.write_line_map_with_options(output, &RenderOptions::default())
+    }
+
+    /// Like [`write_line_map`](Module::write_line_map), for output written
+    /// with `options`.
+    pub fn write_line_map_with_options(
+        &self,
+        mut output: impl std::io::Write,
+        options: &RenderOptions,
+    ) -> anyhow::Result<()> {
+        let mut writer = LineMapWriter::default();
+        for (i, part) in self.parts().iter().enumerate() {
+            if i > 0 {
+                writer.write_str_all("\n\n")?;
+            }
+            let start = writer.line;
+            writer.statements.clear();
+            self.pretty_part(part, options.indent, &pretty::BoxAllocator)
+                .render_raw(options.width, &mut writer)?;
+            let ModulePart::Func(func) = part else {
+                continue;
+            };
+            let statements: Vec<serde_json::Value> = (writer.statements.iter())
+                .map(|(line, column, offset)| {
+                    serde_json::json!({
+                        "line": line,
+                        "column": column,
+                        "offset": offset,
+                    })
+                })
+                .collect();
+            let line = serde_json::json!({
+                "index": func.index.as_u32(),
+                "name": func.name(),
+                "lines": [start, writer.line + 1],
+                "range": [func.body_range.start, func.body_range.end],
+                "statements": statements,
+            });
+            writeln!(output, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+// Follows where rendered output is up to, without keeping it, and notes
+// where each statement annotated with its offset starts.
+#[derive(Default)]
+struct LineMapWriter {
+    line: usize,
+    column: usize,
+    // The offset of the statement being rendered, until its first
+    // character.
+    offset: Option<usize>,
+    // The line, column and offset of each statement in the current part.
+    statements: Vec<(usize, usize, usize)>,
+}
+
+impl Render for LineMapWriter {
+    type Error = io::Error;
+
+    fn write_str(&mut self, s: &str) -> io::Result<usize> {
+        self.write_str_all(s)?;
+        Ok(s.len())
+    }
+
+    fn write_str_all(&mut self, s: &str) -> io::Result<()> {
+        for c in s.chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 0;
+                continue;
+            }
+            if !c.is_whitespace() {
+                if let Some(offset) = self.offset.take() {
+                    self.statements.push((self.line, self.column, offset));
+                }
+            }
+            self.column += 1;
+        }
+        Ok(())
+    }
+
+    fn fail_doc(&self) -> io::Error {
+        io::Error::other("Document failed to render")
+    }
+}
+
+impl RenderAnnotated<'_, Token> for LineMapWriter {
+    fn push_annotation(&mut self, token: &Token) -> io::Result<()> {
+        if let Token::Offset(offset) = token {
+            self.offset = Some(*offset);
+        }
+        Ok(())
+    }
+
+    fn pop_annotation(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => write!(result, "\\u{:04x}", c as u32).unwrap(),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
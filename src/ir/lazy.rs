@@ -0,0 +1,150 @@
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use anyhow::bail;
+
+use crate::ir::*;
+
+/// A module whose functions are decompiled one at a time, the first time
+/// each is asked for, for embedders that show a function at a time and
+/// shouldn't wait on the whole module. The module is read and validated up
+/// front, and each function is kept once it's decompiled.
+///
+/// Each function is decompiled as if it were the only one
+/// [`DecodeOptions::only`] picked, so analyses that look at every function,
+/// such as `freeze`, only see that one.
+pub struct LazyModule {
+    buffer: Vec<u8>,
+    options: DecodeOptions,
+    // The module as read, for each function to be decompiled in.
+    read: Module,
+    // Where each defined function's body is, to decode it on its own.
+    bodies: LazyBodies,
+    // The module with none of its functions decompiled.
+    module: Module,
+    // The defined functions decompiled so far.
+    funcs: Vec<OnceLock<Func>>,
+}
+
+// What's kept of reading the module to decode a function's body later
+// without reading the module again.
+#[derive(Default)]
+pub(crate) struct LazyBodies {
+    // Each defined function's body, and what validating it needs.
+    pub(crate) funcs: Vec<(
+        Range<usize>,
+        wasm::FuncToValidate<Arc<wasm::ValidatorResources>>,
+    )>,
+    // The first type index of each type id, as `Func::remap_type_ids`
+    // wants.
+    pub(crate) type_indices: HashMap<wasm::types::CoreTypeId, TypeIdx>,
+}
+
+impl LazyModule {
+    pub fn from_buffer(buffer: Vec<u8>) -> anyhow::Result<Self> {
+        LazyModule::from_buffer_with_options(buffer, &DecodeOptions::default())
+    }
+
+    pub fn from_buffer_with_options(
+        buffer: Vec<u8>,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Self> {
+        let mut options = options.clone();
+        // Read the embedded source map once, not again for each function.
+        if options.source_map.is_none() {
+            options.source_map = SourceMap::from_module(&buffer, options.module_dir.as_deref());
+        }
+        let mut bodies = LazyBodies::default();
+        let read = Module::read(
+            &buffer,
+            &DecodeOptions {
+                only: Some(FuncPicks::default()),
+                ..options.clone()
+            },
+            Some(&mut bodies),
+        )?;
+        let mut module = read.clone();
+        module.finish(&buffer, &options)?;
+        let funcs = module.funcs.iter().map(|_| OnceLock::new()).collect();
+        Ok(LazyModule {
+            buffer,
+            options,
+            read,
+            bodies,
+            module,
+            funcs,
+        })
+    }
+
+    /// The module with none of its functions decompiled, for what doesn't
+    /// need them, such as its exports, [`Module::functions`] or
+    /// [`Module::write_list`].
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// The function at `func_index`, decompiled now if it hasn't been yet.
+    pub fn func(&self, func_index: FuncIdx) -> anyhow::Result<&Func> {
+        let Some(def_func_index) = func_index.to_defined(self.module.num_func_imports) else {
+            bail!("cannot decompile an imported function");
+        };
+        let Some(cell) = self.funcs.get(def_func_index.as_usize()) else {
+            bail!("too large of a function index");
+        };
+        if let Some(func) = cell.get() {
+            return Ok(func);
+        }
+        let (range, func_to_validate) = &self.bodies.funcs[def_func_index.as_usize()];
+        let reader = wasm::BinaryReader::new(&self.buffer[range.clone()], range.start);
+        let func_to_validate = wasm::FuncToValidate {
+            resources: func_to_validate.resources.clone(),
+            index: func_to_validate.index,
+            ty: func_to_validate.ty,
+            features: func_to_validate.features,
+        };
+        let mut func = Func::decode(
+            wasm::FunctionBody::new(reader),
+            func_to_validate,
+            self.options.source_map.as_ref(),
+        )?;
+        func.body_range = range.clone();
+        func.show_offsets = self.options.offsets;
+        func.remap_type_ids(&self.bodies.type_indices);
+        // The rest is done as for a whole module, with this the only
+        // function that's been decoded.
+        let mut module = self.read.clone();
+        module.funcs[def_func_index.as_usize()] = func;
+        module.finish(&self.buffer, &self.options)?;
+        let func = module.funcs.swap_remove(def_func_index.as_usize());
+        // Another thread may have decompiled it in the meantime.
+        Ok(cell.get_or_init(|| func))
+    }
+
+    /// Whether the function at `func_index` has been decompiled yet.
+    pub fn is_decompiled(&self, func_index: FuncIdx) -> bool {
+        (func_index.to_defined(self.module.num_func_imports))
+            .and_then(|def_func_index| self.funcs.get(def_func_index.as_usize()))
+            .is_some_and(|cell| cell.get().is_some())
+    }
+
+    pub fn write_func(
+        &self,
+        func_index: FuncIdx,
+        output: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        self.write_func_with_options(func_index, output, &RenderOptions::default())
+    }
+
+    pub fn write_func_with_options(
+        &self,
+        func_index: FuncIdx,
+        mut output: impl std::io::Write,
+        options: &RenderOptions,
+    ) -> anyhow::Result<()> {
+        let func = self.func(func_index)?;
+        options.render(
+            func.pretty(options.indent, &pretty::BoxAllocator),
+            &mut output,
+        )
+    }
+}
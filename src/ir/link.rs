@@ -0,0 +1,101 @@
+use anyhow::bail;
+
+use crate::ir::*;
+
+/// An imported function.
+#[derive(Debug, Clone)]
+pub(crate) struct FuncImport {
+    module: String,
+    field: String,
+    ty: wasm::FuncType,
+}
+
+impl FuncImport {
+    pub(crate) fn new(module: &str, field: &str, ty: wasm::FuncType) -> FuncImport {
+        FuncImport {
+            module: module.to_string(),
+            field: field.to_string(),
+            ty,
+        }
+    }
+
+    // e.g. `env.log`
+    pub(crate) fn qualified_name(&self) -> String {
+        format!("{}.{}", self.module, self.field)
+    }
+}
+
+impl Module {
+    /// Resolves this module's imports from `module_name` against the
+    /// exports of `other`, so that calls to them print as
+    /// `module_name.name(...)`, where `name` is the debug name of the
+    /// function in `other`, or its export name if it has none. Returns the
+    /// number of imports resolved.
+    ///
+    /// Fails if an import has a different signature than the function it
+    /// resolves to. Imports `other` doesn't export are left alone.
+    pub fn link(&mut self, module_name: &str, other: &Module) -> anyhow::Result<usize> {
+        let mut resolved = 0;
+        for (i, import) in self.func_imports.iter().enumerate() {
+            if import.module != module_name {
+                continue;
+            }
+            let Some((_, other_index)) = other
+                .func_exports
+                .iter()
+                .find(|(name, _)| *name == import.field)
+            else {
+                continue;
+            };
+            let Some(other_ty) = other.func_signature(*other_index) else {
+                bail!("{} exports unknown function {}", module_name, other_index);
+            };
+            if !signatures_match(&import.ty, other_ty) {
+                bail!(
+                    "import {}.{} has a different signature than the function it resolves to",
+                    import.module,
+                    import.field
+                );
+            }
+            let name = other
+                .debug_func_names
+                .get(other_index)
+                .unwrap_or(&import.field);
+            self.linked_names
+                .insert(FuncIdx::from(i as u32), format!("{}.{}", module_name, name));
+            resolved += 1;
+        }
+        for func in &mut self.funcs {
+            func.callee_names = self.linked_names.clone();
+        }
+        Ok(resolved)
+    }
+
+    // The signature of a function, imported or defined.
+    pub(crate) fn func_signature(&self, func_index: FuncIdx) -> Option<&wasm::FuncType> {
+        match func_index.to_defined(self.num_func_imports) {
+            Some(_) => self.func(func_index).map(|func| &func.ty),
+            None => self
+                .func_imports
+                .get(func_index.as_usize())
+                .map(|import| &import.ty),
+        }
+    }
+}
+
+// Concrete reference types are numbered differently in each module, so two
+// of them are taken to match if their nullability does.
+fn signatures_match(a: &wasm::FuncType, b: &wasm::FuncType) -> bool {
+    fn val_types_match(a: &[wasm::ValType], b: &[wasm::ValType]) -> bool {
+        a.len() == b.len()
+            && a.iter().zip(b).all(|(a, b)| match (a, b) {
+                (wasm::ValType::Ref(a), wasm::ValType::Ref(b))
+                    if a.is_concrete_type_ref() && b.is_concrete_type_ref() =>
+                {
+                    a.is_nullable() == b.is_nullable()
+                }
+                _ => a == b,
+            })
+    }
+    val_types_match(a.params(), b.params()) && val_types_match(a.results(), b.results())
+}
@@ -0,0 +1,222 @@
+use crate::ir::print::val_type_name;
+use crate::ir::*;
+
+/// What [`Module::write_top`] ranks functions by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ranking {
+    /// The size of the body in bytes.
+    Size,
+    /// The number of lines the function decompiles to, which needs the
+    /// functions decompiled.
+    Lines,
+}
+
+/// A function in a module, imported or defined, as [`Module::functions`]
+/// yields them.
+#[derive(Clone, Copy)]
+pub struct FuncInfo<'a> {
+    module: &'a Module,
+    index: FuncIdx,
+    ty: &'a wasm::FuncType,
+    func: Option<&'a Func>,
+}
+
+impl<'a> FuncInfo<'a> {
+    pub fn index(&self) -> FuncIdx {
+        self.index
+    }
+
+    /// The name the function is printed with.
+    pub fn name(&self) -> String {
+        match self.module.linked_names.get(&self.index) {
+            Some(name) => name.clone(),
+            None => self.module.names.func_name(self.index),
+        }
+    }
+
+    /// The function's params and results.
+    pub fn ty(&self) -> &'a wasm::FuncType {
+        self.ty
+    }
+
+    /// The size of the function's body in bytes, or `None` if it's
+    /// imported.
+    pub fn size(&self) -> Option<usize> {
+        self.func.map(|func| func.body_range.len())
+    }
+
+    pub fn is_import(&self) -> bool {
+        self.func.is_none()
+    }
+
+    /// The names the function is exported as.
+    pub fn exports(&self) -> impl Iterator<Item = &'a str> {
+        let index = self.index;
+        (self.module.func_exports.iter())
+            .filter(move |(_, exported)| *exported == index)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The decompiled function, or `None` if it's imported. Its
+    /// [`Func::error`] says if it wasn't decompiled.
+    pub fn func(&self) -> Option<&'a Func> {
+        self.func
+    }
+}
+
+// One function's row in a listing.
+struct Row {
+    index: String,
+    // What the function is listed or ranked by, such as its size.
+    counts: Vec<String>,
+    name: String,
+    signature: String,
+    exports: Vec<String>,
+}
+
+impl Module {
+    fn list_row(
+        &self,
+        func_index: FuncIdx,
+        ty: &wasm::FuncType,
+        counts: Vec<String>,
+        name: String,
+    ) -> Row {
+        let params: Vec<String> = (ty.params().iter().enumerate())
+            .map(|(i, ty)| format!("arg{}: {}", i, val_type_name(*ty, &self.names)))
+            .collect();
+        let results: Vec<String> = (ty.results().iter())
+            .map(|ty| val_type_name(*ty, &self.names))
+            .collect();
+        Row {
+            index: func_index.to_string(),
+            counts,
+            name,
+            signature: format!("({}) -> ({})", params.join(", "), results.join(", ")),
+            exports: (self.func_exports.iter())
+                .filter(|(_, exported)| *exported == func_index)
+                .map(|(name, _)| format!("\"{}\"", name))
+                .collect(),
+        }
+    }
+
+    /// Each function, imports first, with what's known about it without
+    /// reading its decompiled body: for building pickers and reports.
+    pub fn functions(&self) -> impl Iterator<Item = FuncInfo<'_>> {
+        let imports = (0..self.num_func_imports).filter_map(|i| {
+            let index = FuncIdx::from(i);
+            Some(FuncInfo {
+                module: self,
+                index,
+                ty: self.func_signature(index)?,
+                func: None,
+            })
+        });
+        let defined = self.funcs.iter().map(|func| FuncInfo {
+            module: self,
+            index: func.index,
+            ty: &func.ty,
+            func: Some(func),
+        });
+        imports.chain(defined)
+    }
+
+    /// Writes a line for each function, imports included, with its index,
+    /// the size of its body in bytes or `import`, the name it's printed
+    /// with, its signature and the names it's exported as, e.g.
+    /// `3  412  malloc  (arg0: i32) -> (i32)  exported as "malloc"`. Meant
+    /// for finding the functions to pick with `-f`, so it works without
+    /// decompiling anything: decode with [`DecodeOptions::only`] picking
+    /// none of them to make it fast.
+    pub fn write_list(&self, output: impl std::io::Write) -> anyhow::Result<()> {
+        let rows: Vec<Row> = (self.functions())
+            .map(|info| {
+                let size = match info.size() {
+                    Some(size) => size.to_string(),
+                    None => "import".to_string(),
+                };
+                self.list_row(info.index(), info.ty(), vec![size], info.name())
+            })
+            .collect();
+        write_rows(&rows, output)
+    }
+
+    /// Writes a line for each of the `n` largest defined functions by
+    /// `ranking`, largest first, like [`Module::write_list`] does with the
+    /// size and number of decompiled lines of each, e.g.
+    /// `12  4021 bytes  318 lines  parse  (arg0: i32) -> (i32)`. For
+    /// finding the functions worth reading first, or the bloat worth
+    /// cutting. Ranking by size works without decompiling anything, as
+    /// with `write_list`.
+    pub fn write_top(
+        &self,
+        n: usize,
+        ranking: Ranking,
+        output: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        let mut funcs: Vec<(&Func, usize)> = (self.funcs.iter())
+            .map(|func| match ranking {
+                Ranking::Size => (func, func.body_range.len()),
+                Ranking::Lines => (func, func.num_lines()),
+            })
+            .collect();
+        // Largest first, and in index order among equals.
+        funcs.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let rows: Vec<Row> = (funcs.iter().take(n))
+            .map(|(func, count)| {
+                let size = format!("{} bytes", func.body_range.len());
+                let counts = match ranking {
+                    Ranking::Size => vec![size],
+                    Ranking::Lines => vec![size, format!("{} lines", count)],
+                };
+                self.list_row(func.index, &func.ty, counts, func.name())
+            })
+            .collect();
+        write_rows(&rows, output)
+    }
+}
+
+impl Func {
+    // The number of lines the function prints as, its header and closing
+    // brace included.
+    fn num_lines(&self) -> usize {
+        self.to_string()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .count()
+    }
+}
+
+// Writes `rows` with their columns lined up.
+fn write_rows(rows: &[Row], mut output: impl std::io::Write) -> anyhow::Result<()> {
+    let width =
+        |column: fn(&Row) -> &String| rows.iter().map(|row| column(row).len()).max().unwrap_or(0);
+    let num_counts = rows.iter().map(|row| row.counts.len()).max().unwrap_or(0);
+    let count_widths: Vec<usize> = (0..num_counts)
+        .map(|i| {
+            (rows.iter())
+                .filter_map(|row| row.counts.get(i))
+                .map(String::len)
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let index_width = width(|row| &row.index);
+    let name_width = width(|row| &row.name);
+    let signature_width = width(|row| &row.signature);
+    for row in rows {
+        let mut line = format!("{:>index_width$}", row.index);
+        for (count, count_width) in row.counts.iter().zip(&count_widths) {
+            line.push_str(&format!("  {:>count_width$}", count));
+        }
+        line.push_str(&format!(
+            "  {:<name_width$}  {:<signature_width$}",
+            row.name, row.signature
+        ));
+        if !row.exports.is_empty() {
+            line.push_str(&format!("  exported as {}", row.exports.join(", ")));
+        }
+        writeln!(output, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use crate::ir::*;
+
+/// The module's instructions as `wasmprinter` prints them, with their byte
+/// offsets, to print next to the statements they were decoded to.
+pub(crate) struct Listing {
+    // Sorted by offset.
+    instructions: Vec<(usize, String)>,
+}
+
+impl Listing {
+    pub(crate) fn new(buffer: &[u8]) -> anyhow::Result<Listing> {
+        let mut storage = String::new();
+        let lines = wasmprinter::Config::new().offsets_and_lines(buffer, &mut storage)?;
+        let mut instructions: Vec<(usize, String)> = lines
+            .filter_map(|(offset, line)| {
+                // Without the `;; label = @1` comments on blocks.
+                let line = line.split(" ;;").next().unwrap_or(line).trim();
+                // Everything in parens is a declaration, such as a local.
+                let instruction = !line.is_empty() && !line.starts_with(['(', ')']);
+                Some((offset?, line.to_string())).filter(|_| instruction)
+            })
+            .collect();
+        instructions.sort_by_key(|(offset, _)| *offset);
+        Ok(Listing { instructions })
+    }
+
+    // The instructions after `start` up to and including `end`.
+    fn between(&self, start: usize, end: usize) -> &[(usize, String)] {
+        let from = self
+            .instructions
+            .partition_point(|(offset, _)| *offset <= start);
+        let to = self
+            .instructions
+            .partition_point(|(offset, _)| *offset <= end);
+        &self.instructions[from..to.max(from)]
+    }
+}
+
+impl Func {
+    // Replaces each offset annotation with the instructions decoded since
+    // the one before it, in the order they're in the module. That's the
+    // instructions of the statement itself and of any that were folded
+    // into it or dropped, so that every instruction is printed once.
+    // Whatever comes after the last statement is printed at the end of the
+    // function.
+    pub(crate) fn interleave_instructions(&mut self, listing: &Listing) {
+        fn offsets(stmts: &[Statement], result: &mut Vec<usize>) {
+            for stmt in stmts {
+                if let Statement::Location(SourceLocation::Offset(offsets)) = stmt {
+                    result.push(offsets.end - 1);
+                }
+                for body in stmt.bodies() {
+                    offsets(body, result);
+                }
+            }
+        }
+
+        fn replace(stmts: &mut [Statement], starts: &HashMap<usize, usize>, listing: &Listing) {
+            for stmt in stmts {
+                if let Statement::Location(SourceLocation::Offset(offsets)) = stmt {
+                    let offset = offsets.end - 1;
+                    let instructions = listing.between(starts[&offset], offset);
+                    *stmt =
+                        Statement::Location(SourceLocation::Instructions(instructions.to_vec()));
+                }
+                for body in stmt.bodies_mut() {
+                    replace(body, starts, listing);
+                }
+            }
+        }
+
+        let mut ends = vec![];
+        for block in self.blocks.values() {
+            offsets(&block.statements, &mut ends);
+        }
+        ends.sort_unstable();
+        ends.dedup();
+        // The offset each annotation's instructions start after.
+        let mut start = self.body_range.start;
+        let mut starts = HashMap::new();
+        for end in ends {
+            starts.insert(end, start);
+            start = end;
+        }
+        for block in self.blocks.values_mut() {
+            replace(&mut block.statements, &starts, listing);
+        }
+        self.trailing_instructions = listing.between(start, self.body_range.end).to_vec();
+    }
+}
@@ -0,0 +1,472 @@
+use std::collections::HashSet;
+
+use crate::ir::*;
+
+/// What a local seems to be for, which decides its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    /// Counts up or down by one.
+    Counter,
+    /// Used as a memory address.
+    Pointer,
+    /// What a counter is compared against, the number of times round a
+    /// loop.
+    Length,
+    Value,
+}
+
+impl Role {
+    /// Names tried in order. Once they run out they're reused with a
+    /// number, e.g. `i1`.
+    fn names(self) -> &'static [&'static str] {
+        match self {
+            Role::Counter => &["i", "j", "k"],
+            Role::Pointer => &["ptr"],
+            Role::Length => &["len"],
+            Role::Value => &["a", "b", "c", "d", "e", "f", "g", "h"],
+        }
+    }
+}
+
+// Two locals whose values are live at the same time, smaller index first.
+type Interference = HashSet<(LocalIdx, LocalIdx)>;
+
+fn interfere(interference: &mut Interference, a: LocalIdx, b: LocalIdx) {
+    if a != b {
+        interference.insert((a.min(b), a.max(b)));
+    }
+}
+
+fn expression_uses(live: &mut HashSet<LocalIdx>, expr: &Expression) {
+    match expr {
+        Expression::GetLocal(get) => {
+            live.insert(get.local_index);
+        }
+        Expression::GetLocalN(get) => live.extend(get.local_indices.iter().copied()),
+        _ => {}
+    }
+    for operand in expr.operands() {
+        expression_uses(live, operand);
+    }
+}
+
+// Turns the locals live after `statements` into those live before them. A
+// local that's written interferes with every other local live after the
+// write.
+fn statements_liveness(
+    statements: &[Statement],
+    live: &mut HashSet<LocalIdx>,
+    interference: &mut Interference,
+) {
+    for stmt in statements.iter().rev() {
+        match stmt {
+            Statement::LocalSet(stmt) => {
+                for other in live.iter() {
+                    interfere(interference, stmt.index, *other);
+                }
+                live.remove(&stmt.index);
+            }
+            Statement::LocalSetN(stmt) => {
+                for (i, index) in stmt.index.iter().enumerate() {
+                    for other in live.iter().chain(&stmt.index[..i]) {
+                        interfere(interference, *index, *other);
+                    }
+                }
+                for index in &stmt.index {
+                    live.remove(index);
+                }
+            }
+            Statement::If(stmt) => {
+                let mut live_false = live.clone();
+                statements_liveness(&stmt.true_statements, live, interference);
+                statements_liveness(&stmt.false_statements, &mut live_false, interference);
+                live.extend(live_false);
+            }
+            Statement::Loop(stmt) => {
+                // What's live at the top of the loop is what's live after
+                // it plus what the next iteration uses, found by going
+                // round until nothing changes.
+                let live_after = std::mem::take(live);
+                loop {
+                    let mut top = match stmt.kind {
+                        LoopKind::While => {
+                            let mut top = live.clone();
+                            statements_liveness(&stmt.body, &mut top, interference);
+                            top.extend(live_after.iter().copied());
+                            expression_uses(&mut top, &stmt.condition);
+                            top
+                        }
+                        LoopKind::DoWhile => {
+                            let mut top = live.clone();
+                            top.extend(live_after.iter().copied());
+                            expression_uses(&mut top, &stmt.condition);
+                            statements_liveness(&stmt.body, &mut top, interference);
+                            top
+                        }
+                    };
+                    if top == *live {
+                        break;
+                    }
+                    std::mem::swap(live, &mut top);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        for expr in stmt.expressions() {
+            expression_uses(live, expr);
+        }
+    }
+}
+
+// Whether `local` was named by a symbol map, e.g. `$size`.
+fn is_named(local: &Local) -> bool {
+    local.name.starts_with('$')
+}
+
+// Whether `stmt` is `x = x + 1` or `x = x - 1` for the local `x`.
+fn is_step(stmt: &LocalSetStatement) -> bool {
+    let Expression::Binary(op, a, b) = &*stmt.value else {
+        return false;
+    };
+    matches!(
+        op,
+        BinaryExpression::I32Add
+            | BinaryExpression::I32Sub
+            | BinaryExpression::I64Add
+            | BinaryExpression::I64Sub
+    ) && matches!(&**a, Expression::GetLocal(get) if get.local_index == stmt.index)
+        && matches!(
+            **b,
+            Expression::I32Const { value: 1 | -1 } | Expression::I64Const { value: 1 | -1 }
+        )
+}
+
+impl Func {
+    // Calls `f` with every reference to a local, reads and writes.
+    fn for_each_local_mut(&mut self, f: &mut impl FnMut(&mut LocalIdx)) {
+        fn expression(expr: &mut Expression, f: &mut impl FnMut(&mut LocalIdx)) {
+            match expr {
+                Expression::GetLocal(get) => f(&mut get.local_index),
+                Expression::GetLocalN(get) => get.local_indices.iter_mut().for_each(&mut *f),
+                _ => {}
+            }
+            for operand in expr.operands_mut() {
+                expression(operand, f);
+            }
+        }
+
+        fn statement(stmt: &mut Statement, f: &mut impl FnMut(&mut LocalIdx)) {
+            match stmt {
+                Statement::LocalSet(stmt) => f(&mut stmt.index),
+                Statement::LocalSetN(stmt) => stmt.index.iter_mut().for_each(&mut *f),
+                Statement::If(_) | Statement::Loop(_) => {
+                    for stmt in stmt.bodies_mut().into_iter().flatten() {
+                        statement(stmt, f);
+                    }
+                }
+                _ => {}
+            }
+            for expr in stmt.expressions_mut() {
+                expression(expr, f);
+            }
+        }
+
+        for block in self.blocks.values_mut() {
+            for stmt in &mut block.statements {
+                statement(stmt, f);
+            }
+            for expr in block.terminator.expressions_mut() {
+                expression(expr, f);
+            }
+        }
+    }
+
+    // Which pairs of locals are ever live at the same time. The locals live
+    // on entry hold their params or their zero initial value.
+    fn local_interference(&self) -> Interference {
+        let mut interference = HashSet::new();
+        let mut live_in: HashMap<BlockIndex, HashSet<LocalIdx>> = HashMap::new();
+        let order = self.reverse_post_order();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            interference.clear();
+            for block_index in order.iter().rev() {
+                let block = &self.blocks[block_index];
+                let mut live = HashSet::new();
+                for successor in block.successors() {
+                    live.extend(live_in.get(&successor).into_iter().flatten().copied());
+                }
+                for expr in block.terminator.expressions() {
+                    expression_uses(&mut live, expr);
+                }
+                statements_liveness(&block.statements, &mut live, &mut interference);
+                if live_in.get(block_index) != Some(&live) {
+                    live_in.insert(*block_index, live);
+                    changed = true;
+                }
+            }
+        }
+
+        let live_on_entry: Vec<LocalIdx> = live_in
+            .remove(&self.entry_block)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        for (i, a) in live_on_entry.iter().enumerate() {
+            for b in &live_on_entry[..i] {
+                interfere(&mut interference, *a, *b);
+            }
+        }
+        interference
+    }
+
+    // The locals that count up or down by one.
+    pub(crate) fn counter_locals(&self) -> HashSet<LocalIdx> {
+        let mut counters = HashSet::new();
+        self.visit_statements(|stmt| {
+            if let Statement::LocalSet(stmt) = stmt {
+                if is_step(stmt) {
+                    counters.insert(stmt.index);
+                }
+            }
+        });
+        counters
+    }
+
+    // The locals a counter is compared against, e.g. `n` in `i <_u n`.
+    pub(crate) fn bound_locals(&self, counters: &HashSet<LocalIdx>) -> HashSet<LocalIdx> {
+        use BinaryExpression::*;
+        let mut bounds = HashSet::new();
+        self.visit_expressions(|expr| {
+            if let Expression::Binary(
+                I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS
+                | I32GeU | I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU
+                | I64GeS | I64GeU,
+                a,
+                b,
+            ) = expr
+            {
+                if let (Expression::GetLocal(a), Expression::GetLocal(b)) = (&**a, &**b) {
+                    if counters.contains(&a.local_index) {
+                        bounds.insert(b.local_index);
+                    }
+                    if counters.contains(&b.local_index) {
+                        bounds.insert(a.local_index);
+                    }
+                }
+            }
+        });
+        bounds
+    }
+
+    // What each local seems to be for. A counter that's also used as an
+    // address is still a counter, as in `p[i]` where `p + i` is the
+    // address.
+    fn local_roles(&self) -> HashMap<LocalIdx, Role> {
+        let counters = self.counter_locals();
+        let pointers = self.pointer_locals();
+        let bounds = self.bound_locals(&counters);
+        (0..self.locals.len())
+            .map(|i| {
+                let local_index = LocalIdx::from(i as u32);
+                let role = if counters.contains(&local_index) {
+                    Role::Counter
+                } else if pointers.contains(&local_index) {
+                    Role::Pointer
+                } else if bounds.contains(&local_index) {
+                    Role::Length
+                } else {
+                    Role::Value
+                };
+                (local_index, role)
+            })
+            .collect()
+    }
+
+    // Renames each i32 local that still has the name it was decoded with,
+    // e.g. `i3`, after what it's used for: `ptr3` for a memory address,
+    // `len3` for what a counter is compared against, and `i3` otherwise.
+    pub(crate) fn name_integer_locals(&mut self) {
+        if self.error.is_some() {
+            return;
+        }
+        let num_params = self.ty.params().len();
+        let roles = self.local_roles();
+        for i in num_params..self.locals.len() {
+            let local = &mut self.locals[i];
+            let number = i - num_params;
+            if local.ty != wasm::ValType::I32 || local.name != format!("i{}", number) {
+                continue;
+            }
+            match roles[&LocalIdx::from(i as u32)] {
+                Role::Pointer => local.name = format!("ptr{}", number),
+                Role::Length => local.name = format!("len{}", number),
+                Role::Counter | Role::Value => {}
+            }
+        }
+    }
+
+    // Drops the locals other than params that are never read or written,
+    // such as temps whose values were all folded into where they're used,
+    // and renumbers the rest in order.
+    pub(crate) fn remove_unused_locals(&mut self) {
+        if self.error.is_some() {
+            return;
+        }
+        let num_params = self.ty.params().len();
+        let mut used = HashSet::new();
+        self.for_each_local_mut(&mut |local_index| {
+            used.insert(*local_index);
+        });
+
+        let mut mapping = HashMap::new();
+        let mut locals = self.locals[..num_params].to_vec();
+        for (i, local) in self.locals.iter().enumerate().skip(num_params) {
+            let local_index = LocalIdx::from(i as u32);
+            if used.contains(&local_index) {
+                mapping.insert(local_index, LocalIdx::from(locals.len() as u32));
+                locals.push(local.clone());
+            }
+        }
+        if locals.len() == self.locals.len() {
+            return;
+        }
+
+        self.for_each_local_mut(&mut |local_index| {
+            if let Some(new_index) = mapping.get(local_index) {
+                *local_index = *new_index;
+            }
+        });
+        self.locals = locals;
+    }
+
+    // Drops the locals that are never used, merges locals of the same type
+    // and role that are never live at the same time, and names what's left
+    // by its role. Params and locals named by a symbol map keep their names,
+    // and the latter aren't merged. Generated names avoid `reserved`.
+    pub(crate) fn tidy_locals(&mut self, reserved: &HashSet<&str>) {
+        if self.error.is_some() {
+            return;
+        }
+        let num_params = self.ty.params().len();
+        let mut used = HashSet::new();
+        self.for_each_local_mut(&mut |local_index| {
+            used.insert(*local_index);
+        });
+
+        // An exception can leave a `try_table` body from the middle of a
+        // block, which the liveness analysis doesn't see, so locals aren't
+        // merged in functions that catch.
+        let catches = self
+            .blocks
+            .values()
+            .any(|block| matches!(block.terminator, Terminator::Try(..)));
+        let interference = if catches {
+            None
+        } else {
+            Some(self.local_interference())
+        };
+
+        let roles = self.local_roles();
+
+        // Each group of locals becomes one local.
+        let mut groups: Vec<(Role, Vec<LocalIdx>)> = vec![];
+        for i in num_params..self.locals.len() {
+            let local_index = LocalIdx::from(i as u32);
+            if !used.contains(&local_index) {
+                continue;
+            }
+            let ty = self.locals[i].ty;
+            let role = roles[&local_index];
+            if is_named(&self.locals[i]) {
+                groups.push((role, vec![local_index]));
+                continue;
+            }
+            let group = interference.as_ref().and_then(|interference| {
+                groups.iter_mut().find(|(group_role, group)| {
+                    *group_role == role
+                        && !is_named(self.local(group[0]))
+                        && self.local(group[0]).ty == ty
+                        && group.iter().all(|member| {
+                            !interference
+                                .contains(&(local_index.min(*member), local_index.max(*member)))
+                        })
+                })
+            });
+            match group {
+                Some((_, group)) => group.push(local_index),
+                None => groups.push((role, vec![local_index])),
+            }
+        }
+
+        let mut taken: HashSet<String> = self
+            .locals
+            .iter()
+            .enumerate()
+            .filter(|(i, local)| *i < num_params || is_named(local))
+            .map(|(_, local)| local)
+            .map(|local| local.name.clone())
+            .collect();
+        let mut next_name = |names: &[&str]| -> String {
+            for n in 0.. {
+                let name = match n / names.len() {
+                    0 => names[n % names.len()].to_string(),
+                    suffix => format!("{}{}", names[n % names.len()], suffix),
+                };
+                if !reserved.contains(name.as_str()) && taken.insert(name.clone()) {
+                    return name;
+                }
+            }
+            unreachable!()
+        };
+
+        let mut mapping = HashMap::new();
+        let mut locals = self.locals[..num_params].to_vec();
+        for (role, group) in &groups {
+            let new_index = LocalIdx::from(locals.len() as u32);
+            for member in group {
+                mapping.insert(*member, new_index);
+            }
+            let local = self.local(group[0]);
+            let name = if is_named(local) {
+                local.name.clone()
+            } else {
+                next_name(role.names())
+            };
+            locals.push(Local { ty: local.ty, name });
+        }
+
+        self.for_each_local_mut(&mut |local_index| {
+            if let Some(new_index) = mapping.get(local_index) {
+                *local_index = *new_index;
+            }
+        });
+        self.locals = locals;
+        self.remove_self_assignments();
+    }
+
+    // Merging a local with the one it's copied from leaves `x = x`, which
+    // does nothing.
+    fn remove_self_assignments(&mut self) {
+        fn remove(stmts: &mut Vec<Statement>) {
+            stmts.retain(|stmt| {
+                !matches!(
+                    stmt,
+                    Statement::LocalSet(set)
+                        if matches!(&*set.value, Expression::GetLocal(get) if get.local_index == set.index)
+                )
+            });
+            for stmt in stmts {
+                for body in stmt.bodies_mut() {
+                    remove(body);
+                }
+            }
+        }
+
+        for block in self.blocks.values_mut() {
+            remove(&mut block.statements);
+        }
+    }
+}
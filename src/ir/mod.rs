@@ -1,27 +1,147 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::bail;
 use pretty::{DocAllocator, DocBuilder};
+use rayon::prelude::*;
 use wasmparser::{self as wasm, FuncValidatorAllocations, WasmModuleResources};
 
+mod abi;
+mod call_graph;
+mod component;
+mod conditions;
+mod data;
 mod decode;
+mod depth;
+mod diff;
+mod division;
+mod dominators;
+mod effects;
+mod entry;
+mod error;
+mod fold;
+mod frame;
+mod freeze;
+#[cfg(feature = "arbitrary")]
+mod fuzzing;
+mod globals;
 mod graphviz;
+mod grep;
+mod index;
+mod indirect;
+mod jsonl;
+mod lazy;
+mod link;
+mod list;
+mod listing;
+mod locals;
+mod names;
 mod passes;
 mod print;
+mod render;
+mod signedness;
+mod source_map;
+mod ssa;
+mod structure;
+mod symbol_map;
+mod theme;
+mod toolchain;
+mod visit;
+mod window;
 
+pub use abi::{AbiFormat, ExportAbi, ParamRole};
+pub use call_graph::{Call, CallGraph, CallKind};
+pub use component::{core_modules, is_component};
+use data::DataRange;
+pub use dominators::DominatorTree;
+pub use entry::{EntryKind, EntryPoint};
+pub use error::DecompileError;
+use frame::StackFrame;
+use freeze::ActiveElem;
+pub use freeze::{Frozen, FrozenKind};
+#[cfg(feature = "arbitrary")]
+pub use fuzzing::ArbitraryFunc;
+use globals::GlobalDef;
+pub use globals::{GlobalReport, GlobalUse};
+pub use graphviz::GraphvizOptions;
+pub use index::*;
+use indirect::IndirectTargets;
+use lazy::LazyBodies;
+pub use lazy::LazyModule;
+use link::FuncImport;
+pub use list::{FuncInfo, Ranking};
+use listing::Listing;
+use names::Names;
+pub use passes::Pass;
+pub use render::{DecompilePrint, OutputFormat, RenderOptions};
+use render::{PrintWriter, INDENT};
+use signedness::Signedness;
+pub use source_map::{SourceLocation, SourceMap};
+use structure::Node;
+pub use symbol_map::SymbolMap;
+pub use theme::{Color, Style, Theme, Token};
+pub use toolchain::Toolchain;
+pub use visit::Visitor;
+pub use wasmparser::WasmFeatures;
+pub use window::LineIndex;
+
+/// Identifies a block within a function. Printed as `@N`.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
-pub(crate) struct BlockIndex(u32);
+pub struct BlockIndex(u32);
+
+impl BlockIndex {
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
 
+impl std::fmt::Display for BlockIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "@{}", self.0)
+    }
+}
+
+/// A basic block: straight-line statements followed by a terminator.
 #[derive(Debug, Clone)]
-pub(crate) struct Block {
+pub struct Block {
     params: Vec<wasm::ValType>,
     statements: Vec<Statement>,
     terminator: Terminator,
+    // Where the instruction the terminator was decoded from is in the
+    // module.
+    terminator_offsets: Option<Range<usize>>,
 }
 
 impl Block {
-    fn successors(&self) -> Vec<BlockIndex> {
+    /// The types of the values the block is entered with, read as
+    /// [`Expression::BlockParam`].
+    pub fn params(&self) -> &[wasm::ValType] {
+        &self.params
+    }
+
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    pub fn terminator(&self) -> &Terminator {
+        &self.terminator
+    }
+
+    /// The byte range in the module of the instruction the terminator was
+    /// decoded from, such as a `br_if` or the `end` of a block. Terminators
+    /// made up while decoding or optimizing, such as the branch that joins
+    /// an `if` without an `else`, have none.
+    pub fn terminator_offsets(&self) -> Option<Range<usize>> {
+        self.terminator_offsets.clone()
+    }
+
+    /// The targets of this block's terminator, in order. A target appears once
+    /// per edge to it.
+    pub fn successors(&self) -> Vec<BlockIndex> {
         self.terminator.successors()
     }
 
@@ -30,7 +150,7 @@ impl Block {
     }
 
     fn is_trivial_block(&self) -> Option<BlockIndex> {
-        if self.params.is_empty() && self.statements.is_empty() {
+        if self.params.is_empty() && self.statements.iter().all(Statement::is_location) {
             if let Terminator::Br(target, values) = &self.terminator {
                 if !values.is_empty() {
                     return None;
@@ -43,17 +163,94 @@ impl Block {
     }
 }
 
+/// How a block ends: by branching to other blocks of the function, or by
+/// leaving it.
 #[derive(Debug, Clone)]
-pub(crate) enum Terminator {
+#[non_exhaustive]
+pub enum Terminator {
     Unknown,
     Unreachable,
     Return(Vec<Expression>),
     Br(BlockIndex, Vec<Expression>),
     BrIf(Expression, BlockIndex, BlockIndex, Vec<Expression>),
-    BrTable(Vec<BlockIndex>, BlockIndex, Vec<Expression>),
+    /// Branch to the target the index picks, or the default one if it's
+    /// out of range.
+    BrTable(Expression, Vec<BlockIndex>, BlockIndex, Vec<Expression>),
+    /// Enter the body of a `try_table`. Exceptions thrown while it runs
+    /// branch to the first matching handler.
+    Try(BlockIndex, Vec<Catch>, Vec<Expression>),
+    Throw(u32, Vec<Expression>),
+    ThrowRef(Expression),
+}
+
+/// A `try_table` catch clause. The caught values, and the exception itself
+/// for the `_ref` forms, are passed to the handler as block params.
+#[derive(Debug, Clone)]
+pub enum Catch {
+    One { tag: u32, target: BlockIndex },
+    OneRef { tag: u32, target: BlockIndex },
+    All { target: BlockIndex },
+    AllRef { target: BlockIndex },
+}
+
+impl Catch {
+    pub fn target(&self) -> BlockIndex {
+        match self {
+            Catch::One { target, .. }
+            | Catch::OneRef { target, .. }
+            | Catch::All { target }
+            | Catch::AllRef { target } => *target,
+        }
+    }
+
+    fn target_mut(&mut self) -> &mut BlockIndex {
+        match self {
+            Catch::One { target, .. }
+            | Catch::OneRef { target, .. }
+            | Catch::All { target }
+            | Catch::AllRef { target } => target,
+        }
+    }
 }
 
 impl Terminator {
+    /// The expressions the terminator uses: its condition or exception and
+    /// the values it passes on.
+    pub fn expressions(&self) -> Vec<&Expression> {
+        match self {
+            Terminator::Unknown | Terminator::Unreachable => vec![],
+            Terminator::Return(values)
+            | Terminator::Br(_, values)
+            | Terminator::Try(_, _, values)
+            | Terminator::Throw(_, values) => values.iter().collect(),
+            Terminator::BrIf(condition, _, _, values) => {
+                std::iter::once(condition).chain(values).collect()
+            }
+            // The index is evaluated after the values.
+            Terminator::BrTable(index, _, _, values) => {
+                values.iter().chain(std::iter::once(index)).collect()
+            }
+            Terminator::ThrowRef(exception) => vec![exception],
+        }
+    }
+
+    fn expressions_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Terminator::Unknown | Terminator::Unreachable => vec![],
+            Terminator::Return(values)
+            | Terminator::Br(_, values)
+            | Terminator::Try(_, _, values)
+            | Terminator::Throw(_, values) => values.iter_mut().collect(),
+            Terminator::BrIf(condition, _, _, values) => {
+                std::iter::once(condition).chain(values).collect()
+            }
+            Terminator::BrTable(index, _, _, values) => {
+                values.iter_mut().chain(std::iter::once(index)).collect()
+            }
+            Terminator::ThrowRef(exception) => vec![exception],
+        }
+    }
+
     fn is_empty_return(&self) -> bool {
         match self {
             Terminator::Return(exprs) => exprs.is_empty(),
@@ -61,15 +258,19 @@ impl Terminator {
         }
     }
 
-    fn successors(&self) -> Vec<BlockIndex> {
+    /// The blocks the terminator can branch to, as by [`Block::successors`].
+    pub fn successors(&self) -> Vec<BlockIndex> {
         match self {
             Terminator::Br(target, ..) => vec![*target],
             Terminator::BrIf(_, true_block, false_block, _) => vec![*true_block, *false_block],
-            Terminator::BrTable(targets, unknown_target, _) => {
+            Terminator::BrTable(_, targets, unknown_target, _) => {
                 let mut result = targets.clone();
                 result.push(*unknown_target);
                 result
             }
+            Terminator::Try(body, catches, _) => std::iter::once(*body)
+                .chain(catches.iter().map(Catch::target))
+                .collect(),
             _ => vec![],
         }
     }
@@ -83,89 +284,717 @@ impl Terminator {
                 *true_block = *mapping.get(true_block).unwrap();
                 *false_block = *mapping.get(false_block).unwrap();
             }
-            Terminator::BrTable(targets, unknown_target, _) => {
+            Terminator::BrTable(_, targets, unknown_target, _) => {
                 for target in targets {
                     *target = *mapping.get(target).unwrap();
                 }
                 *unknown_target = *mapping.get(unknown_target).unwrap();
             }
+            Terminator::Try(body, catches, _) => {
+                *body = *mapping.get(body).unwrap();
+                for catch in catches {
+                    let target = catch.target_mut();
+                    *target = *mapping.get(target).unwrap();
+                }
+            }
             _ => {}
         }
     }
 }
 
+/// A statement of a block, run for its effect. A call is a statement when
+/// its results aren't used.
 #[derive(Debug, Clone)]
-enum Statement {
+#[non_exhaustive]
+pub enum Statement {
     Nop,
     Drop(Expression),
     LocalSet(LocalSetStatement),
     LocalSetN(LocalSetNStatement),
     GlobalSet(GlobalSetStatement),
     MemoryStore(MemoryStoreStatement),
+    TableSet(TableSetStatement),
+    TableFill(TableFillStatement),
+    StructSet(StructSetStatement),
+    ArraySet(ArraySetStatement),
+    ArrayFill(ArrayFillStatement),
+    ArrayCopy(ArrayCopyStatement),
+    ArrayInit(ArrayInitStatement),
     If(IfStatement),
+    Loop(LoopStatement),
     Call(CallExpression),
     CallIndirect(CallIndirectExpression),
+    CallRef(CallRefExpression),
+    /// Where the statements that follow came from, when decoding with a
+    /// source map, or the range of the instructions they were decoded from.
+    Location(SourceLocation),
+}
+
+impl Statement {
+    // Whether this only says where the statements that follow came from,
+    // which leaves a block with nothing else empty.
+    pub(crate) fn is_location(&self) -> bool {
+        matches!(self, Statement::Location(_))
+    }
+
+    /// The expressions directly used by this statement. The bodies of an `if`
+    /// or a loop are not included.
+    pub fn expressions(&self) -> Vec<&Expression> {
+        match self {
+            Statement::Nop | Statement::Location(_) => vec![],
+            Statement::Drop(value) => vec![value],
+            Statement::LocalSet(stmt) => vec![&stmt.value],
+            Statement::LocalSetN(stmt) => vec![&stmt.value],
+            Statement::GlobalSet(stmt) => vec![&stmt.value],
+            Statement::MemoryStore(stmt) => vec![&stmt.index, &stmt.value],
+            Statement::TableSet(stmt) => vec![&stmt.index, &stmt.value],
+            Statement::TableFill(stmt) => vec![&stmt.index, &stmt.value, &stmt.len],
+            Statement::StructSet(stmt) => vec![&stmt.object, &stmt.value],
+            Statement::ArraySet(stmt) => vec![&stmt.array, &stmt.index, &stmt.value],
+            Statement::ArrayFill(stmt) => vec![&stmt.array, &stmt.offset, &stmt.value, &stmt.len],
+            Statement::ArrayCopy(stmt) => vec![
+                &stmt.dst,
+                &stmt.dst_offset,
+                &stmt.src,
+                &stmt.src_offset,
+                &stmt.len,
+            ],
+            Statement::ArrayInit(stmt) => {
+                vec![&stmt.array, &stmt.offset, &stmt.source_offset, &stmt.len]
+            }
+            Statement::If(stmt) => vec![&stmt.condition],
+            Statement::Loop(stmt) => vec![&stmt.condition],
+            Statement::Call(call) => call.params.iter().collect(),
+            Statement::CallIndirect(call) => std::iter::once(&*call.callee_index)
+                .chain(&call.params)
+                .collect(),
+            Statement::CallRef(call) => {
+                std::iter::once(&*call.callee).chain(&call.params).collect()
+            }
+        }
+    }
+
+    fn expressions_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Statement::Nop | Statement::Location(_) => vec![],
+            Statement::Drop(value) => vec![value],
+            Statement::LocalSet(stmt) => vec![&mut stmt.value],
+            Statement::LocalSetN(stmt) => vec![&mut stmt.value],
+            Statement::GlobalSet(stmt) => vec![&mut stmt.value],
+            Statement::MemoryStore(stmt) => vec![&mut stmt.index, &mut stmt.value],
+            Statement::TableSet(stmt) => vec![&mut stmt.index, &mut stmt.value],
+            Statement::TableFill(stmt) => vec![&mut stmt.index, &mut stmt.value, &mut stmt.len],
+            Statement::StructSet(stmt) => vec![&mut stmt.object, &mut stmt.value],
+            Statement::ArraySet(stmt) => vec![&mut stmt.array, &mut stmt.index, &mut stmt.value],
+            Statement::ArrayFill(stmt) => vec![
+                &mut stmt.array,
+                &mut stmt.offset,
+                &mut stmt.value,
+                &mut stmt.len,
+            ],
+            Statement::ArrayCopy(stmt) => vec![
+                &mut stmt.dst,
+                &mut stmt.dst_offset,
+                &mut stmt.src,
+                &mut stmt.src_offset,
+                &mut stmt.len,
+            ],
+            Statement::ArrayInit(stmt) => {
+                vec![
+                    &mut stmt.array,
+                    &mut stmt.offset,
+                    &mut stmt.source_offset,
+                    &mut stmt.len,
+                ]
+            }
+            Statement::If(stmt) => vec![&mut stmt.condition],
+            Statement::Loop(stmt) => vec![&mut stmt.condition],
+            Statement::Call(call) => call.params.iter_mut().collect(),
+            Statement::CallIndirect(call) => std::iter::once(&mut *call.callee_index)
+                .chain(&mut call.params)
+                .collect(),
+            Statement::CallRef(call) => std::iter::once(&mut *call.callee)
+                .chain(&mut call.params)
+                .collect(),
+        }
+    }
+
+    /// The statement lists nested in this statement: the branches of an
+    /// `if` or the body of a loop.
+    pub fn bodies(&self) -> Vec<&Vec<Statement>> {
+        match self {
+            Statement::If(stmt) => vec![&stmt.true_statements, &stmt.false_statements],
+            Statement::Loop(stmt) => vec![&stmt.body],
+            _ => vec![],
+        }
+    }
+
+    fn bodies_mut(&mut self) -> Vec<&mut Vec<Statement>> {
+        match self {
+            Statement::If(stmt) => vec![&mut stmt.true_statements, &mut stmt.false_statements],
+            Statement::Loop(stmt) => vec![&mut stmt.body],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalSetStatement {
+    index: LocalIdx,
+    value: Box<Expression>,
+}
+
+impl LocalSetStatement {
+    pub fn index(&self) -> LocalIdx {
+        self.index
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalSetNStatement {
+    index: Vec<LocalIdx>,
+    value: Box<Expression>,
+}
+
+impl LocalSetNStatement {
+    pub fn index(&self) -> &[LocalIdx] {
+        &self.index
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GlobalSetStatement {
+    index: GlobalIdx,
+    value: Box<Expression>,
+}
+
+impl GlobalSetStatement {
+    pub fn index(&self) -> GlobalIdx {
+        self.index
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryStoreStatement {
+    arg: wasm::MemArg,
+    index: Box<Expression>,
+    value: Box<Expression>,
+}
+
+impl MemoryStoreStatement {
+    pub fn arg(&self) -> wasm::MemArg {
+        self.arg
+    }
+
+    pub fn index(&self) -> &Expression {
+        &self.index
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct LocalSetStatement {
-    index: u32,
+pub struct TableSetStatement {
+    table: u32,
+    index: Box<Expression>,
     value: Box<Expression>,
 }
 
+impl TableSetStatement {
+    pub fn table(&self) -> u32 {
+        self.table
+    }
+
+    pub fn index(&self) -> &Expression {
+        &self.index
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+}
+
 #[derive(Debug, Clone)]
-pub(crate) struct LocalSetNStatement {
-    index: Vec<u32>,
+pub struct TableFillStatement {
+    table: u32,
+    index: Box<Expression>,
     value: Box<Expression>,
+    len: Box<Expression>,
+}
+
+impl TableFillStatement {
+    pub fn table(&self) -> u32 {
+        self.table
+    }
+
+    pub fn index(&self) -> &Expression {
+        &self.index
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+
+    pub fn len(&self) -> &Expression {
+        &self.len
+    }
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct GlobalSetStatement {
-    index: u32,
+pub struct StructSetStatement {
+    type_index: TypeIdx,
+    field: u32,
+    object: Box<Expression>,
     value: Box<Expression>,
 }
 
+impl StructSetStatement {
+    pub fn type_index(&self) -> TypeIdx {
+        self.type_index
+    }
+
+    pub fn field(&self) -> u32 {
+        self.field
+    }
+
+    pub fn object(&self) -> &Expression {
+        &self.object
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+}
+
 #[derive(Debug, Clone)]
-pub(crate) struct MemoryStoreStatement {
-    _arg: wasm::MemArg,
+pub struct ArraySetStatement {
+    type_index: TypeIdx,
+    array: Box<Expression>,
     index: Box<Expression>,
     value: Box<Expression>,
 }
 
+impl ArraySetStatement {
+    pub fn type_index(&self) -> TypeIdx {
+        self.type_index
+    }
+
+    pub fn array(&self) -> &Expression {
+        &self.array
+    }
+
+    pub fn index(&self) -> &Expression {
+        &self.index
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArrayFillStatement {
+    type_index: TypeIdx,
+    array: Box<Expression>,
+    offset: Box<Expression>,
+    value: Box<Expression>,
+    len: Box<Expression>,
+}
+
+impl ArrayFillStatement {
+    pub fn type_index(&self) -> TypeIdx {
+        self.type_index
+    }
+
+    pub fn array(&self) -> &Expression {
+        &self.array
+    }
+
+    pub fn offset(&self) -> &Expression {
+        &self.offset
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+
+    pub fn len(&self) -> &Expression {
+        &self.len
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArrayCopyStatement {
+    dst_type_index: TypeIdx,
+    src_type_index: TypeIdx,
+    dst: Box<Expression>,
+    dst_offset: Box<Expression>,
+    src: Box<Expression>,
+    src_offset: Box<Expression>,
+    len: Box<Expression>,
+}
+
+impl ArrayCopyStatement {
+    pub fn dst_type_index(&self) -> TypeIdx {
+        self.dst_type_index
+    }
+
+    pub fn src_type_index(&self) -> TypeIdx {
+        self.src_type_index
+    }
+
+    pub fn dst(&self) -> &Expression {
+        &self.dst
+    }
+
+    pub fn dst_offset(&self) -> &Expression {
+        &self.dst_offset
+    }
+
+    pub fn src(&self) -> &Expression {
+        &self.src
+    }
+
+    pub fn src_offset(&self) -> &Expression {
+        &self.src_offset
+    }
+
+    pub fn len(&self) -> &Expression {
+        &self.len
+    }
+}
+
+/// `array.init_data` and `array.init_elem`: copies `len` items starting at
+/// `source_offset` in the segment into the array at `offset`.
+#[derive(Debug, Clone)]
+pub struct ArrayInitStatement {
+    type_index: TypeIdx,
+    segment: Segment,
+    array: Box<Expression>,
+    offset: Box<Expression>,
+    source_offset: Box<Expression>,
+    len: Box<Expression>,
+}
+
+impl ArrayInitStatement {
+    pub fn type_index(&self) -> TypeIdx {
+        self.type_index
+    }
+
+    pub fn segment(&self) -> Segment {
+        self.segment
+    }
+
+    pub fn array(&self) -> &Expression {
+        &self.array
+    }
+
+    pub fn offset(&self) -> &Expression {
+        &self.offset
+    }
+
+    pub fn source_offset(&self) -> &Expression {
+        &self.source_offset
+    }
+
+    pub fn len(&self) -> &Expression {
+        &self.len
+    }
+}
+
+/// A passive data or element segment that arrays can be initialized from.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum Segment {
+    Data(u32),
+    Elem(u32),
+}
+
 #[derive(Debug, Clone)]
-pub(crate) struct IfStatement {
+pub struct IfStatement {
     condition: Box<Expression>,
     true_statements: Vec<Statement>,
     false_statements: Vec<Statement>,
 }
 
+impl IfStatement {
+    pub fn condition(&self) -> &Expression {
+        &self.condition
+    }
+
+    pub fn true_statements(&self) -> &[Statement] {
+        &self.true_statements
+    }
+
+    pub fn false_statements(&self) -> &[Statement] {
+        &self.false_statements
+    }
+}
+
+/// When a loop tests its condition, which decides whether it runs again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum LoopKind {
+    /// `while (c) { ... }`, before each iteration.
+    While,
+    /// `do { ... } while (c)`, after each iteration.
+    DoWhile,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoopStatement {
+    kind: LoopKind,
+    condition: Box<Expression>,
+    body: Vec<Statement>,
+}
+
+impl LoopStatement {
+    pub fn kind(&self) -> LoopKind {
+        self.kind
+    }
+
+    pub fn condition(&self) -> &Expression {
+        &self.condition
+    }
+
+    pub fn body(&self) -> &[Statement] {
+        &self.body
+    }
+}
+
+/// An expression, evaluated for its value.
 #[derive(Debug, Clone)]
-pub(crate) enum Expression {
-    I32Const { value: i32 },
-    I64Const { value: i64 },
-    F32Const { value: wasm::Ieee32 },
-    F64Const { value: wasm::Ieee64 },
+#[non_exhaustive]
+pub enum Expression {
+    I32Const {
+        value: i32,
+    },
+    I64Const {
+        value: i64,
+    },
+    F32Const {
+        value: wasm::Ieee32,
+    },
+    F64Const {
+        value: wasm::Ieee64,
+    },
+    V128Const {
+        value: [u8; 16],
+    },
+    RefNull {
+        ty: wasm::HeapType,
+    },
+    RefFunc {
+        func_index: FuncIdx,
+    },
 
     BlockParam(u32),
 
     Unary(UnaryExpression, Box<Expression>),
     Binary(BinaryExpression, Box<Expression>, Box<Expression>),
+    Ternary(
+        TernaryExpression,
+        Box<Expression>,
+        Box<Expression>,
+        Box<Expression>,
+    ),
+    /// `a && b` or `a || b`, which only evaluates `b` if it has to.
+    /// Recovered from branches, never decoded directly.
+    Logical(LogicalExpression, Box<Expression>, Box<Expression>),
+    ExtractLane(ExtractLaneExpression),
+    ReplaceLane(ReplaceLaneExpression),
+    Shuffle(ShuffleExpression),
     Call(CallExpression),
     CallIndirect(CallIndirectExpression),
+    CallRef(CallRefExpression),
     GetLocal(GetLocalExpression),
     GetLocalN(GetLocalNExpression),
     GetGlobal(GetGlobalExpression),
     Select(SelectExpression),
     MemoryLoad(MemoryLoadExpression),
-    MemorySize,
+    MemorySize {
+        memory: u32,
+    },
     MemoryGrow(MemoryGrowExpression),
+    TableGet(TableGetExpression),
+    TableSize {
+        table: u32,
+    },
+    TableGrow(TableGrowExpression),
+    StructNew(StructNewExpression),
+    StructGet(StructGetExpression),
+    ArrayNew(ArrayNewExpression),
+    ArrayGet(ArrayGetExpression),
+    ArrayLen(Box<Expression>),
+    RefAsNonNull(Box<Expression>),
+    RefTest(RefCastExpression),
+    RefCast(RefCastExpression),
 
     // Synthesized when popping from an unreachable stack. Should be eliminated by DCE.
     Bottom,
 }
 
+impl Expression {
+    /// The direct subexpressions, in evaluation order.
+    pub fn operands(&self) -> Vec<&Expression> {
+        match self {
+            Expression::I32Const { .. }
+            | Expression::I64Const { .. }
+            | Expression::F32Const { .. }
+            | Expression::F64Const { .. }
+            | Expression::V128Const { .. }
+            | Expression::RefNull { .. }
+            | Expression::RefFunc { .. }
+            | Expression::BlockParam(_)
+            | Expression::GetLocal(_)
+            | Expression::GetLocalN(_)
+            | Expression::GetGlobal(_)
+            | Expression::MemorySize { .. }
+            | Expression::TableSize { .. }
+            | Expression::Bottom => vec![],
+            Expression::StructNew(expr) => expr.fields.iter().collect(),
+            Expression::StructGet(expr) => vec![&expr.object],
+            Expression::ArrayNew(expr) => expr.args.iter().collect(),
+            Expression::ArrayGet(expr) => vec![&expr.array, &expr.index],
+            Expression::ArrayLen(array) => vec![array],
+            Expression::RefAsNonNull(value) => vec![value],
+            Expression::RefTest(expr) | Expression::RefCast(expr) => vec![&expr.value],
+            Expression::Unary(_, value) => vec![value],
+            Expression::Binary(_, lhs, rhs) | Expression::Logical(_, lhs, rhs) => vec![lhs, rhs],
+            Expression::Ternary(_, a, b, c) => vec![a, b, c],
+            Expression::ExtractLane(expr) => vec![&expr.vector],
+            Expression::ReplaceLane(expr) => vec![&expr.vector, &expr.value],
+            Expression::Shuffle(expr) => vec![&expr.lhs, &expr.rhs],
+            Expression::Call(call) => call.params.iter().collect(),
+            Expression::CallIndirect(call) => std::iter::once(&*call.callee_index)
+                .chain(&call.params)
+                .collect(),
+            Expression::CallRef(call) => {
+                std::iter::once(&*call.callee).chain(&call.params).collect()
+            }
+            Expression::Select(expr) => vec![&expr.on_true, &expr.on_false, &expr.condition],
+            Expression::MemoryLoad(expr) => vec![&expr.index],
+            Expression::MemoryGrow(expr) => vec![&expr.value],
+            Expression::TableGet(expr) => vec![&expr.index],
+            Expression::TableGrow(expr) => vec![&expr.value, &expr.delta],
+        }
+    }
+
+    /// Whether evaluating the expression has no effect and can't trap, so
+    /// that it can be dropped, duplicated or reordered.
+    fn is_pure(&self) -> bool {
+        self.is_pure_op() && self.operands().iter().all(|operand| operand.is_pure())
+    }
+
+    // Whether the operator alone, not counting its operands, is pure.
+    fn is_pure_op(&self) -> bool {
+        match self {
+            Expression::I32Const { .. }
+            | Expression::I64Const { .. }
+            | Expression::F32Const { .. }
+            | Expression::F64Const { .. }
+            | Expression::V128Const { .. }
+            | Expression::RefNull { .. }
+            | Expression::RefFunc { .. }
+            | Expression::BlockParam(_)
+            | Expression::GetLocal(_)
+            | Expression::GetLocalN(_)
+            | Expression::GetGlobal(_)
+            | Expression::Select(_)
+            | Expression::Logical(..) => true,
+            Expression::Unary(op, _) => !matches!(
+                op,
+                UnaryExpression::I32TruncF32S
+                    | UnaryExpression::I32TruncF32U
+                    | UnaryExpression::I32TruncF64S
+                    | UnaryExpression::I32TruncF64U
+                    | UnaryExpression::I64TruncF32S
+                    | UnaryExpression::I64TruncF32U
+                    | UnaryExpression::I64TruncF64S
+                    | UnaryExpression::I64TruncF64U
+                    | UnaryExpression::I31GetS
+                    | UnaryExpression::I31GetU
+            ),
+            Expression::Binary(op, _, _) => !matches!(
+                op,
+                BinaryExpression::I32DivS
+                    | BinaryExpression::I32DivU
+                    | BinaryExpression::I32RemS
+                    | BinaryExpression::I32RemU
+                    | BinaryExpression::I64DivS
+                    | BinaryExpression::I64DivU
+                    | BinaryExpression::I64RemS
+                    | BinaryExpression::I64RemU
+            ),
+            _ => false,
+        }
+    }
+
+    fn operands_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Expression::I32Const { .. }
+            | Expression::I64Const { .. }
+            | Expression::F32Const { .. }
+            | Expression::F64Const { .. }
+            | Expression::V128Const { .. }
+            | Expression::RefNull { .. }
+            | Expression::RefFunc { .. }
+            | Expression::BlockParam(_)
+            | Expression::GetLocal(_)
+            | Expression::GetLocalN(_)
+            | Expression::GetGlobal(_)
+            | Expression::MemorySize { .. }
+            | Expression::TableSize { .. }
+            | Expression::Bottom => vec![],
+            Expression::StructNew(expr) => expr.fields.iter_mut().collect(),
+            Expression::StructGet(expr) => vec![&mut expr.object],
+            Expression::ArrayNew(expr) => expr.args.iter_mut().collect(),
+            Expression::ArrayGet(expr) => vec![&mut expr.array, &mut expr.index],
+            Expression::ArrayLen(array) => vec![array],
+            Expression::RefAsNonNull(value) => vec![value],
+            Expression::RefTest(expr) | Expression::RefCast(expr) => vec![&mut expr.value],
+            Expression::Unary(_, value) => vec![value],
+            Expression::Binary(_, lhs, rhs) | Expression::Logical(_, lhs, rhs) => vec![lhs, rhs],
+            Expression::Ternary(_, a, b, c) => vec![a, b, c],
+            Expression::ExtractLane(expr) => vec![&mut expr.vector],
+            Expression::ReplaceLane(expr) => vec![&mut expr.vector, &mut expr.value],
+            Expression::Shuffle(expr) => vec![&mut expr.lhs, &mut expr.rhs],
+            Expression::Call(call) => call.params.iter_mut().collect(),
+            Expression::CallIndirect(call) => std::iter::once(&mut *call.callee_index)
+                .chain(&mut call.params)
+                .collect(),
+            Expression::CallRef(call) => std::iter::once(&mut *call.callee)
+                .chain(&mut call.params)
+                .collect(),
+            Expression::Select(expr) => {
+                vec![&mut expr.on_true, &mut expr.on_false, &mut expr.condition]
+            }
+            Expression::MemoryLoad(expr) => vec![&mut expr.index],
+            Expression::MemoryGrow(expr) => vec![&mut expr.value],
+            Expression::TableGet(expr) => vec![&mut expr.index],
+            Expression::TableGrow(expr) => vec![&mut expr.value, &mut expr.delta],
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
-pub(crate) enum UnaryExpression {
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub enum UnaryExpression {
     I32Eqz,
     I64Eqz,
     I32Clz,
@@ -226,6 +1055,81 @@ pub(crate) enum UnaryExpression {
     I64TruncSatF32U,
     I64TruncSatF64S,
     I64TruncSatF64U,
+    RefIsNull,
+    RefI31,
+    I31GetS,
+    I31GetU,
+
+    // SIMD
+    I8x16Splat,
+    I16x8Splat,
+    I32x4Splat,
+    I64x2Splat,
+    F32x4Splat,
+    F64x2Splat,
+    V128Not,
+    V128AnyTrue,
+    I8x16Abs,
+    I8x16Neg,
+    I8x16Popcnt,
+    I8x16AllTrue,
+    I8x16Bitmask,
+    I16x8ExtAddPairwiseI8x16S,
+    I16x8ExtAddPairwiseI8x16U,
+    I16x8Abs,
+    I16x8Neg,
+    I16x8AllTrue,
+    I16x8Bitmask,
+    I16x8ExtendLowI8x16S,
+    I16x8ExtendHighI8x16S,
+    I16x8ExtendLowI8x16U,
+    I16x8ExtendHighI8x16U,
+    I32x4ExtAddPairwiseI16x8S,
+    I32x4ExtAddPairwiseI16x8U,
+    I32x4Abs,
+    I32x4Neg,
+    I32x4AllTrue,
+    I32x4Bitmask,
+    I32x4ExtendLowI16x8S,
+    I32x4ExtendHighI16x8S,
+    I32x4ExtendLowI16x8U,
+    I32x4ExtendHighI16x8U,
+    I64x2Abs,
+    I64x2Neg,
+    I64x2AllTrue,
+    I64x2Bitmask,
+    I64x2ExtendLowI32x4S,
+    I64x2ExtendHighI32x4S,
+    I64x2ExtendLowI32x4U,
+    I64x2ExtendHighI32x4U,
+    F32x4Ceil,
+    F32x4Floor,
+    F32x4Trunc,
+    F32x4Nearest,
+    F32x4Abs,
+    F32x4Neg,
+    F32x4Sqrt,
+    F64x2Ceil,
+    F64x2Floor,
+    F64x2Trunc,
+    F64x2Nearest,
+    F64x2Abs,
+    F64x2Neg,
+    F64x2Sqrt,
+    I32x4TruncSatF32x4S,
+    I32x4TruncSatF32x4U,
+    F32x4ConvertI32x4S,
+    F32x4ConvertI32x4U,
+    I32x4TruncSatF64x2SZero,
+    I32x4TruncSatF64x2UZero,
+    F64x2ConvertLowI32x4S,
+    F64x2ConvertLowI32x4U,
+    F32x4DemoteF64x2Zero,
+    F64x2PromoteLowF32x4,
+    I32x4RelaxedTruncF32x4S,
+    I32x4RelaxedTruncF32x4U,
+    I32x4RelaxedTruncF64x2SZero,
+    I32x4RelaxedTruncF64x2UZero,
 }
 
 impl UnaryExpression {
@@ -293,6 +1197,79 @@ impl UnaryExpression {
             I64TruncSatF32U => "trunc_sat_f32_u",
             I64TruncSatF64S => "trunc_sat_f64_s",
             I64TruncSatF64U => "trunc_sat_f64_u",
+            RefIsNull => "is_null",
+            RefI31 => "ref.i31",
+            I31GetS => "i31.get_s",
+            I31GetU => "i31.get_u",
+            I8x16Splat => "i8x16.splat",
+            I16x8Splat => "i16x8.splat",
+            I32x4Splat => "i32x4.splat",
+            I64x2Splat => "i64x2.splat",
+            F32x4Splat => "f32x4.splat",
+            F64x2Splat => "f64x2.splat",
+            V128Not => "v128.not",
+            V128AnyTrue => "v128.any_true",
+            I8x16Abs => "i8x16.abs",
+            I8x16Neg => "i8x16.neg",
+            I8x16Popcnt => "i8x16.popcnt",
+            I8x16AllTrue => "i8x16.all_true",
+            I8x16Bitmask => "i8x16.bitmask",
+            I16x8ExtAddPairwiseI8x16S => "i16x8.extadd_pairwise_i8x16_s",
+            I16x8ExtAddPairwiseI8x16U => "i16x8.extadd_pairwise_i8x16_u",
+            I16x8Abs => "i16x8.abs",
+            I16x8Neg => "i16x8.neg",
+            I16x8AllTrue => "i16x8.all_true",
+            I16x8Bitmask => "i16x8.bitmask",
+            I16x8ExtendLowI8x16S => "i16x8.extend_low_i8x16_s",
+            I16x8ExtendHighI8x16S => "i16x8.extend_high_i8x16_s",
+            I16x8ExtendLowI8x16U => "i16x8.extend_low_i8x16_u",
+            I16x8ExtendHighI8x16U => "i16x8.extend_high_i8x16_u",
+            I32x4ExtAddPairwiseI16x8S => "i32x4.extadd_pairwise_i16x8_s",
+            I32x4ExtAddPairwiseI16x8U => "i32x4.extadd_pairwise_i16x8_u",
+            I32x4Abs => "i32x4.abs",
+            I32x4Neg => "i32x4.neg",
+            I32x4AllTrue => "i32x4.all_true",
+            I32x4Bitmask => "i32x4.bitmask",
+            I32x4ExtendLowI16x8S => "i32x4.extend_low_i16x8_s",
+            I32x4ExtendHighI16x8S => "i32x4.extend_high_i16x8_s",
+            I32x4ExtendLowI16x8U => "i32x4.extend_low_i16x8_u",
+            I32x4ExtendHighI16x8U => "i32x4.extend_high_i16x8_u",
+            I64x2Abs => "i64x2.abs",
+            I64x2Neg => "i64x2.neg",
+            I64x2AllTrue => "i64x2.all_true",
+            I64x2Bitmask => "i64x2.bitmask",
+            I64x2ExtendLowI32x4S => "i64x2.extend_low_i32x4_s",
+            I64x2ExtendHighI32x4S => "i64x2.extend_high_i32x4_s",
+            I64x2ExtendLowI32x4U => "i64x2.extend_low_i32x4_u",
+            I64x2ExtendHighI32x4U => "i64x2.extend_high_i32x4_u",
+            F32x4Ceil => "f32x4.ceil",
+            F32x4Floor => "f32x4.floor",
+            F32x4Trunc => "f32x4.trunc",
+            F32x4Nearest => "f32x4.nearest",
+            F32x4Abs => "f32x4.abs",
+            F32x4Neg => "f32x4.neg",
+            F32x4Sqrt => "f32x4.sqrt",
+            F64x2Ceil => "f64x2.ceil",
+            F64x2Floor => "f64x2.floor",
+            F64x2Trunc => "f64x2.trunc",
+            F64x2Nearest => "f64x2.nearest",
+            F64x2Abs => "f64x2.abs",
+            F64x2Neg => "f64x2.neg",
+            F64x2Sqrt => "f64x2.sqrt",
+            I32x4TruncSatF32x4S => "i32x4.trunc_sat_f32x4_s",
+            I32x4TruncSatF32x4U => "i32x4.trunc_sat_f32x4_u",
+            F32x4ConvertI32x4S => "f32x4.convert_i32x4_s",
+            F32x4ConvertI32x4U => "f32x4.convert_i32x4_u",
+            I32x4TruncSatF64x2SZero => "i32x4.trunc_sat_f64x2_s_zero",
+            I32x4TruncSatF64x2UZero => "i32x4.trunc_sat_f64x2_u_zero",
+            F64x2ConvertLowI32x4S => "f64x2.convert_low_i32x4_s",
+            F64x2ConvertLowI32x4U => "f64x2.convert_low_i32x4_u",
+            F32x4DemoteF64x2Zero => "f32x4.demote_f64x2_zero",
+            F64x2PromoteLowF32x4 => "f64x2.promote_low_f32x4",
+            I32x4RelaxedTruncF32x4S => "i32x4.relaxed_trunc_f32x4_s",
+            I32x4RelaxedTruncF32x4U => "i32x4.relaxed_trunc_f32x4_u",
+            I32x4RelaxedTruncF64x2SZero => "i32x4.relaxed_trunc_f64x2_s_zero",
+            I32x4RelaxedTruncF64x2UZero => "i32x4.relaxed_trunc_f64x2_u_zero",
         }
     }
 
@@ -360,6 +1337,79 @@ impl UnaryExpression {
             I64TruncSatF32U => wasm::ValType::I64,
             I64TruncSatF64S => wasm::ValType::I64,
             I64TruncSatF64U => wasm::ValType::I64,
+            RefIsNull => wasm::ValType::I32,
+            RefI31 => wasm::ValType::Ref(wasm::RefType::I31),
+            I31GetS => wasm::ValType::I32,
+            I31GetU => wasm::ValType::I32,
+            I8x16Splat => wasm::ValType::V128,
+            I16x8Splat => wasm::ValType::V128,
+            I32x4Splat => wasm::ValType::V128,
+            I64x2Splat => wasm::ValType::V128,
+            F32x4Splat => wasm::ValType::V128,
+            F64x2Splat => wasm::ValType::V128,
+            V128Not => wasm::ValType::V128,
+            V128AnyTrue => wasm::ValType::I32,
+            I8x16Abs => wasm::ValType::V128,
+            I8x16Neg => wasm::ValType::V128,
+            I8x16Popcnt => wasm::ValType::V128,
+            I8x16AllTrue => wasm::ValType::I32,
+            I8x16Bitmask => wasm::ValType::I32,
+            I16x8ExtAddPairwiseI8x16S => wasm::ValType::V128,
+            I16x8ExtAddPairwiseI8x16U => wasm::ValType::V128,
+            I16x8Abs => wasm::ValType::V128,
+            I16x8Neg => wasm::ValType::V128,
+            I16x8AllTrue => wasm::ValType::I32,
+            I16x8Bitmask => wasm::ValType::I32,
+            I16x8ExtendLowI8x16S => wasm::ValType::V128,
+            I16x8ExtendHighI8x16S => wasm::ValType::V128,
+            I16x8ExtendLowI8x16U => wasm::ValType::V128,
+            I16x8ExtendHighI8x16U => wasm::ValType::V128,
+            I32x4ExtAddPairwiseI16x8S => wasm::ValType::V128,
+            I32x4ExtAddPairwiseI16x8U => wasm::ValType::V128,
+            I32x4Abs => wasm::ValType::V128,
+            I32x4Neg => wasm::ValType::V128,
+            I32x4AllTrue => wasm::ValType::I32,
+            I32x4Bitmask => wasm::ValType::I32,
+            I32x4ExtendLowI16x8S => wasm::ValType::V128,
+            I32x4ExtendHighI16x8S => wasm::ValType::V128,
+            I32x4ExtendLowI16x8U => wasm::ValType::V128,
+            I32x4ExtendHighI16x8U => wasm::ValType::V128,
+            I64x2Abs => wasm::ValType::V128,
+            I64x2Neg => wasm::ValType::V128,
+            I64x2AllTrue => wasm::ValType::I32,
+            I64x2Bitmask => wasm::ValType::I32,
+            I64x2ExtendLowI32x4S => wasm::ValType::V128,
+            I64x2ExtendHighI32x4S => wasm::ValType::V128,
+            I64x2ExtendLowI32x4U => wasm::ValType::V128,
+            I64x2ExtendHighI32x4U => wasm::ValType::V128,
+            F32x4Ceil => wasm::ValType::V128,
+            F32x4Floor => wasm::ValType::V128,
+            F32x4Trunc => wasm::ValType::V128,
+            F32x4Nearest => wasm::ValType::V128,
+            F32x4Abs => wasm::ValType::V128,
+            F32x4Neg => wasm::ValType::V128,
+            F32x4Sqrt => wasm::ValType::V128,
+            F64x2Ceil => wasm::ValType::V128,
+            F64x2Floor => wasm::ValType::V128,
+            F64x2Trunc => wasm::ValType::V128,
+            F64x2Nearest => wasm::ValType::V128,
+            F64x2Abs => wasm::ValType::V128,
+            F64x2Neg => wasm::ValType::V128,
+            F64x2Sqrt => wasm::ValType::V128,
+            I32x4TruncSatF32x4S => wasm::ValType::V128,
+            I32x4TruncSatF32x4U => wasm::ValType::V128,
+            F32x4ConvertI32x4S => wasm::ValType::V128,
+            F32x4ConvertI32x4U => wasm::ValType::V128,
+            I32x4TruncSatF64x2SZero => wasm::ValType::V128,
+            I32x4TruncSatF64x2UZero => wasm::ValType::V128,
+            F64x2ConvertLowI32x4S => wasm::ValType::V128,
+            F64x2ConvertLowI32x4U => wasm::ValType::V128,
+            F32x4DemoteF64x2Zero => wasm::ValType::V128,
+            F64x2PromoteLowF32x4 => wasm::ValType::V128,
+            I32x4RelaxedTruncF32x4S => wasm::ValType::V128,
+            I32x4RelaxedTruncF32x4U => wasm::ValType::V128,
+            I32x4RelaxedTruncF64x2SZero => wasm::ValType::V128,
+            I32x4RelaxedTruncF64x2UZero => wasm::ValType::V128,
         }
     }
 }
@@ -427,13 +1477,92 @@ impl From<wasm::Operator<'_>> for UnaryExpression {
             wasm::Operator::I64TruncSatF32U => UnaryExpression::I64TruncSatF32U,
             wasm::Operator::I64TruncSatF64S => UnaryExpression::I64TruncSatF64S,
             wasm::Operator::I64TruncSatF64U => UnaryExpression::I64TruncSatF64U,
+            wasm::Operator::RefIsNull => UnaryExpression::RefIsNull,
+            wasm::Operator::RefI31 => UnaryExpression::RefI31,
+            wasm::Operator::I31GetS => UnaryExpression::I31GetS,
+            wasm::Operator::I31GetU => UnaryExpression::I31GetU,
+            wasm::Operator::I8x16Splat => UnaryExpression::I8x16Splat,
+            wasm::Operator::I16x8Splat => UnaryExpression::I16x8Splat,
+            wasm::Operator::I32x4Splat => UnaryExpression::I32x4Splat,
+            wasm::Operator::I64x2Splat => UnaryExpression::I64x2Splat,
+            wasm::Operator::F32x4Splat => UnaryExpression::F32x4Splat,
+            wasm::Operator::F64x2Splat => UnaryExpression::F64x2Splat,
+            wasm::Operator::V128Not => UnaryExpression::V128Not,
+            wasm::Operator::V128AnyTrue => UnaryExpression::V128AnyTrue,
+            wasm::Operator::I8x16Abs => UnaryExpression::I8x16Abs,
+            wasm::Operator::I8x16Neg => UnaryExpression::I8x16Neg,
+            wasm::Operator::I8x16Popcnt => UnaryExpression::I8x16Popcnt,
+            wasm::Operator::I8x16AllTrue => UnaryExpression::I8x16AllTrue,
+            wasm::Operator::I8x16Bitmask => UnaryExpression::I8x16Bitmask,
+            wasm::Operator::I16x8ExtAddPairwiseI8x16S => UnaryExpression::I16x8ExtAddPairwiseI8x16S,
+            wasm::Operator::I16x8ExtAddPairwiseI8x16U => UnaryExpression::I16x8ExtAddPairwiseI8x16U,
+            wasm::Operator::I16x8Abs => UnaryExpression::I16x8Abs,
+            wasm::Operator::I16x8Neg => UnaryExpression::I16x8Neg,
+            wasm::Operator::I16x8AllTrue => UnaryExpression::I16x8AllTrue,
+            wasm::Operator::I16x8Bitmask => UnaryExpression::I16x8Bitmask,
+            wasm::Operator::I16x8ExtendLowI8x16S => UnaryExpression::I16x8ExtendLowI8x16S,
+            wasm::Operator::I16x8ExtendHighI8x16S => UnaryExpression::I16x8ExtendHighI8x16S,
+            wasm::Operator::I16x8ExtendLowI8x16U => UnaryExpression::I16x8ExtendLowI8x16U,
+            wasm::Operator::I16x8ExtendHighI8x16U => UnaryExpression::I16x8ExtendHighI8x16U,
+            wasm::Operator::I32x4ExtAddPairwiseI16x8S => UnaryExpression::I32x4ExtAddPairwiseI16x8S,
+            wasm::Operator::I32x4ExtAddPairwiseI16x8U => UnaryExpression::I32x4ExtAddPairwiseI16x8U,
+            wasm::Operator::I32x4Abs => UnaryExpression::I32x4Abs,
+            wasm::Operator::I32x4Neg => UnaryExpression::I32x4Neg,
+            wasm::Operator::I32x4AllTrue => UnaryExpression::I32x4AllTrue,
+            wasm::Operator::I32x4Bitmask => UnaryExpression::I32x4Bitmask,
+            wasm::Operator::I32x4ExtendLowI16x8S => UnaryExpression::I32x4ExtendLowI16x8S,
+            wasm::Operator::I32x4ExtendHighI16x8S => UnaryExpression::I32x4ExtendHighI16x8S,
+            wasm::Operator::I32x4ExtendLowI16x8U => UnaryExpression::I32x4ExtendLowI16x8U,
+            wasm::Operator::I32x4ExtendHighI16x8U => UnaryExpression::I32x4ExtendHighI16x8U,
+            wasm::Operator::I64x2Abs => UnaryExpression::I64x2Abs,
+            wasm::Operator::I64x2Neg => UnaryExpression::I64x2Neg,
+            wasm::Operator::I64x2AllTrue => UnaryExpression::I64x2AllTrue,
+            wasm::Operator::I64x2Bitmask => UnaryExpression::I64x2Bitmask,
+            wasm::Operator::I64x2ExtendLowI32x4S => UnaryExpression::I64x2ExtendLowI32x4S,
+            wasm::Operator::I64x2ExtendHighI32x4S => UnaryExpression::I64x2ExtendHighI32x4S,
+            wasm::Operator::I64x2ExtendLowI32x4U => UnaryExpression::I64x2ExtendLowI32x4U,
+            wasm::Operator::I64x2ExtendHighI32x4U => UnaryExpression::I64x2ExtendHighI32x4U,
+            wasm::Operator::F32x4Ceil => UnaryExpression::F32x4Ceil,
+            wasm::Operator::F32x4Floor => UnaryExpression::F32x4Floor,
+            wasm::Operator::F32x4Trunc => UnaryExpression::F32x4Trunc,
+            wasm::Operator::F32x4Nearest => UnaryExpression::F32x4Nearest,
+            wasm::Operator::F32x4Abs => UnaryExpression::F32x4Abs,
+            wasm::Operator::F32x4Neg => UnaryExpression::F32x4Neg,
+            wasm::Operator::F32x4Sqrt => UnaryExpression::F32x4Sqrt,
+            wasm::Operator::F64x2Ceil => UnaryExpression::F64x2Ceil,
+            wasm::Operator::F64x2Floor => UnaryExpression::F64x2Floor,
+            wasm::Operator::F64x2Trunc => UnaryExpression::F64x2Trunc,
+            wasm::Operator::F64x2Nearest => UnaryExpression::F64x2Nearest,
+            wasm::Operator::F64x2Abs => UnaryExpression::F64x2Abs,
+            wasm::Operator::F64x2Neg => UnaryExpression::F64x2Neg,
+            wasm::Operator::F64x2Sqrt => UnaryExpression::F64x2Sqrt,
+            wasm::Operator::I32x4TruncSatF32x4S => UnaryExpression::I32x4TruncSatF32x4S,
+            wasm::Operator::I32x4TruncSatF32x4U => UnaryExpression::I32x4TruncSatF32x4U,
+            wasm::Operator::F32x4ConvertI32x4S => UnaryExpression::F32x4ConvertI32x4S,
+            wasm::Operator::F32x4ConvertI32x4U => UnaryExpression::F32x4ConvertI32x4U,
+            wasm::Operator::I32x4TruncSatF64x2SZero => UnaryExpression::I32x4TruncSatF64x2SZero,
+            wasm::Operator::I32x4TruncSatF64x2UZero => UnaryExpression::I32x4TruncSatF64x2UZero,
+            wasm::Operator::F64x2ConvertLowI32x4S => UnaryExpression::F64x2ConvertLowI32x4S,
+            wasm::Operator::F64x2ConvertLowI32x4U => UnaryExpression::F64x2ConvertLowI32x4U,
+            wasm::Operator::F32x4DemoteF64x2Zero => UnaryExpression::F32x4DemoteF64x2Zero,
+            wasm::Operator::F64x2PromoteLowF32x4 => UnaryExpression::F64x2PromoteLowF32x4,
+            wasm::Operator::I32x4RelaxedTruncF32x4S => UnaryExpression::I32x4RelaxedTruncF32x4S,
+            wasm::Operator::I32x4RelaxedTruncF32x4U => UnaryExpression::I32x4RelaxedTruncF32x4U,
+            wasm::Operator::I32x4RelaxedTruncF64x2SZero => {
+                UnaryExpression::I32x4RelaxedTruncF64x2SZero
+            }
+            wasm::Operator::I32x4RelaxedTruncF64x2UZero => {
+                UnaryExpression::I32x4RelaxedTruncF64x2UZero
+            }
             _ => unreachable!(),
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub(crate) enum BinaryExpression {
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub enum BinaryExpression {
     I32Eq,
     I32Ne,
     I32LtS,
@@ -510,9 +1639,157 @@ pub(crate) enum BinaryExpression {
     F64Div,
     F64Min,
     F64Max,
+    RefEq,
+
+    // SIMD
+    I8x16Swizzle,
+    I8x16Eq,
+    I8x16Ne,
+    I8x16LtS,
+    I8x16LtU,
+    I8x16GtS,
+    I8x16GtU,
+    I8x16LeS,
+    I8x16LeU,
+    I8x16GeS,
+    I8x16GeU,
+    I16x8Eq,
+    I16x8Ne,
+    I16x8LtS,
+    I16x8LtU,
+    I16x8GtS,
+    I16x8GtU,
+    I16x8LeS,
+    I16x8LeU,
+    I16x8GeS,
+    I16x8GeU,
+    I32x4Eq,
+    I32x4Ne,
+    I32x4LtS,
+    I32x4LtU,
+    I32x4GtS,
+    I32x4GtU,
+    I32x4LeS,
+    I32x4LeU,
+    I32x4GeS,
+    I32x4GeU,
+    I64x2Eq,
+    I64x2Ne,
+    I64x2LtS,
+    I64x2GtS,
+    I64x2LeS,
+    I64x2GeS,
+    F32x4Eq,
+    F32x4Ne,
+    F32x4Lt,
+    F32x4Gt,
+    F32x4Le,
+    F32x4Ge,
+    F64x2Eq,
+    F64x2Ne,
+    F64x2Lt,
+    F64x2Gt,
+    F64x2Le,
+    F64x2Ge,
+    V128And,
+    V128AndNot,
+    V128Or,
+    V128Xor,
+    I8x16NarrowI16x8S,
+    I8x16NarrowI16x8U,
+    I8x16Shl,
+    I8x16ShrS,
+    I8x16ShrU,
+    I8x16Add,
+    I8x16AddSatS,
+    I8x16AddSatU,
+    I8x16Sub,
+    I8x16SubSatS,
+    I8x16SubSatU,
+    I8x16MinS,
+    I8x16MinU,
+    I8x16MaxS,
+    I8x16MaxU,
+    I8x16AvgrU,
+    I16x8Q15MulrSatS,
+    I16x8NarrowI32x4S,
+    I16x8NarrowI32x4U,
+    I16x8Shl,
+    I16x8ShrS,
+    I16x8ShrU,
+    I16x8Add,
+    I16x8AddSatS,
+    I16x8AddSatU,
+    I16x8Sub,
+    I16x8SubSatS,
+    I16x8SubSatU,
+    I16x8Mul,
+    I16x8MinS,
+    I16x8MinU,
+    I16x8MaxS,
+    I16x8MaxU,
+    I16x8AvgrU,
+    I16x8ExtMulLowI8x16S,
+    I16x8ExtMulHighI8x16S,
+    I16x8ExtMulLowI8x16U,
+    I16x8ExtMulHighI8x16U,
+    I32x4Shl,
+    I32x4ShrS,
+    I32x4ShrU,
+    I32x4Add,
+    I32x4Sub,
+    I32x4Mul,
+    I32x4MinS,
+    I32x4MinU,
+    I32x4MaxS,
+    I32x4MaxU,
+    I32x4DotI16x8S,
+    I32x4ExtMulLowI16x8S,
+    I32x4ExtMulHighI16x8S,
+    I32x4ExtMulLowI16x8U,
+    I32x4ExtMulHighI16x8U,
+    I64x2Shl,
+    I64x2ShrS,
+    I64x2ShrU,
+    I64x2Add,
+    I64x2Sub,
+    I64x2Mul,
+    I64x2ExtMulLowI32x4S,
+    I64x2ExtMulHighI32x4S,
+    I64x2ExtMulLowI32x4U,
+    I64x2ExtMulHighI32x4U,
+    I8x16RelaxedSwizzle,
+    F32x4RelaxedMin,
+    F32x4RelaxedMax,
+    F64x2RelaxedMin,
+    F64x2RelaxedMax,
+    I16x8RelaxedQ15mulrS,
+    I16x8RelaxedDotI8x16I7x16S,
+    F32x4Add,
+    F32x4Sub,
+    F32x4Mul,
+    F32x4Div,
+    F32x4Min,
+    F32x4Max,
+    F32x4PMin,
+    F32x4PMax,
+    F64x2Add,
+    F64x2Sub,
+    F64x2Mul,
+    F64x2Div,
+    F64x2Min,
+    F64x2Max,
+    F64x2PMin,
+    F64x2PMax,
 }
 
 impl BinaryExpression {
+    // `&`, `|` and xor, whose constant operands are likely masks.
+    fn is_bitwise(&self) -> bool {
+        use BinaryExpression::*;
+        matches!(self, I32And | I32Or | I32Xor | I64And | I64Or | I64Xor)
+    }
+
     fn to_string_and_infix(&self) -> (&'static str, bool) {
         use BinaryExpression::*;
         match self {
@@ -583,7 +1860,7 @@ impl BinaryExpression {
             F32Add => ("+", true),
             F32Sub => ("-", true),
             F32Mul => ("*", true),
-            F32Div => ("", true),
+            F32Div => ("/", true),
             F32Min => ("min", false),
             F32Max => ("max", false),
             F64Add => ("+", true),
@@ -592,6 +1869,146 @@ impl BinaryExpression {
             F64Div => ("/", true),
             F64Min => ("min", false),
             F64Max => ("max", false),
+            RefEq => ("==", true),
+            I8x16Swizzle => ("i8x16.swizzle", false),
+            I8x16Eq => ("i8x16.eq", false),
+            I8x16Ne => ("i8x16.ne", false),
+            I8x16LtS => ("i8x16.lt_s", false),
+            I8x16LtU => ("i8x16.lt_u", false),
+            I8x16GtS => ("i8x16.gt_s", false),
+            I8x16GtU => ("i8x16.gt_u", false),
+            I8x16LeS => ("i8x16.le_s", false),
+            I8x16LeU => ("i8x16.le_u", false),
+            I8x16GeS => ("i8x16.ge_s", false),
+            I8x16GeU => ("i8x16.ge_u", false),
+            I16x8Eq => ("i16x8.eq", false),
+            I16x8Ne => ("i16x8.ne", false),
+            I16x8LtS => ("i16x8.lt_s", false),
+            I16x8LtU => ("i16x8.lt_u", false),
+            I16x8GtS => ("i16x8.gt_s", false),
+            I16x8GtU => ("i16x8.gt_u", false),
+            I16x8LeS => ("i16x8.le_s", false),
+            I16x8LeU => ("i16x8.le_u", false),
+            I16x8GeS => ("i16x8.ge_s", false),
+            I16x8GeU => ("i16x8.ge_u", false),
+            I32x4Eq => ("i32x4.eq", false),
+            I32x4Ne => ("i32x4.ne", false),
+            I32x4LtS => ("i32x4.lt_s", false),
+            I32x4LtU => ("i32x4.lt_u", false),
+            I32x4GtS => ("i32x4.gt_s", false),
+            I32x4GtU => ("i32x4.gt_u", false),
+            I32x4LeS => ("i32x4.le_s", false),
+            I32x4LeU => ("i32x4.le_u", false),
+            I32x4GeS => ("i32x4.ge_s", false),
+            I32x4GeU => ("i32x4.ge_u", false),
+            I64x2Eq => ("i64x2.eq", false),
+            I64x2Ne => ("i64x2.ne", false),
+            I64x2LtS => ("i64x2.lt_s", false),
+            I64x2GtS => ("i64x2.gt_s", false),
+            I64x2LeS => ("i64x2.le_s", false),
+            I64x2GeS => ("i64x2.ge_s", false),
+            F32x4Eq => ("f32x4.eq", false),
+            F32x4Ne => ("f32x4.ne", false),
+            F32x4Lt => ("f32x4.lt", false),
+            F32x4Gt => ("f32x4.gt", false),
+            F32x4Le => ("f32x4.le", false),
+            F32x4Ge => ("f32x4.ge", false),
+            F64x2Eq => ("f64x2.eq", false),
+            F64x2Ne => ("f64x2.ne", false),
+            F64x2Lt => ("f64x2.lt", false),
+            F64x2Gt => ("f64x2.gt", false),
+            F64x2Le => ("f64x2.le", false),
+            F64x2Ge => ("f64x2.ge", false),
+            V128And => ("v128.and", false),
+            V128AndNot => ("v128.andnot", false),
+            V128Or => ("v128.or", false),
+            V128Xor => ("v128.xor", false),
+            I8x16NarrowI16x8S => ("i8x16.narrow_i16x8_s", false),
+            I8x16NarrowI16x8U => ("i8x16.narrow_i16x8_u", false),
+            I8x16Shl => ("i8x16.shl", false),
+            I8x16ShrS => ("i8x16.shr_s", false),
+            I8x16ShrU => ("i8x16.shr_u", false),
+            I8x16Add => ("i8x16.add", false),
+            I8x16AddSatS => ("i8x16.add_sat_s", false),
+            I8x16AddSatU => ("i8x16.add_sat_u", false),
+            I8x16Sub => ("i8x16.sub", false),
+            I8x16SubSatS => ("i8x16.sub_sat_s", false),
+            I8x16SubSatU => ("i8x16.sub_sat_u", false),
+            I8x16MinS => ("i8x16.min_s", false),
+            I8x16MinU => ("i8x16.min_u", false),
+            I8x16MaxS => ("i8x16.max_s", false),
+            I8x16MaxU => ("i8x16.max_u", false),
+            I8x16AvgrU => ("i8x16.avgr_u", false),
+            I16x8Q15MulrSatS => ("i16x8.q15mulr_sat_s", false),
+            I16x8NarrowI32x4S => ("i16x8.narrow_i32x4_s", false),
+            I16x8NarrowI32x4U => ("i16x8.narrow_i32x4_u", false),
+            I16x8Shl => ("i16x8.shl", false),
+            I16x8ShrS => ("i16x8.shr_s", false),
+            I16x8ShrU => ("i16x8.shr_u", false),
+            I16x8Add => ("i16x8.add", false),
+            I16x8AddSatS => ("i16x8.add_sat_s", false),
+            I16x8AddSatU => ("i16x8.add_sat_u", false),
+            I16x8Sub => ("i16x8.sub", false),
+            I16x8SubSatS => ("i16x8.sub_sat_s", false),
+            I16x8SubSatU => ("i16x8.sub_sat_u", false),
+            I16x8Mul => ("i16x8.mul", false),
+            I16x8MinS => ("i16x8.min_s", false),
+            I16x8MinU => ("i16x8.min_u", false),
+            I16x8MaxS => ("i16x8.max_s", false),
+            I16x8MaxU => ("i16x8.max_u", false),
+            I16x8AvgrU => ("i16x8.avgr_u", false),
+            I16x8ExtMulLowI8x16S => ("i16x8.extmul_low_i8x16_s", false),
+            I16x8ExtMulHighI8x16S => ("i16x8.extmul_high_i8x16_s", false),
+            I16x8ExtMulLowI8x16U => ("i16x8.extmul_low_i8x16_u", false),
+            I16x8ExtMulHighI8x16U => ("i16x8.extmul_high_i8x16_u", false),
+            I32x4Shl => ("i32x4.shl", false),
+            I32x4ShrS => ("i32x4.shr_s", false),
+            I32x4ShrU => ("i32x4.shr_u", false),
+            I32x4Add => ("i32x4.add", false),
+            I32x4Sub => ("i32x4.sub", false),
+            I32x4Mul => ("i32x4.mul", false),
+            I32x4MinS => ("i32x4.min_s", false),
+            I32x4MinU => ("i32x4.min_u", false),
+            I32x4MaxS => ("i32x4.max_s", false),
+            I32x4MaxU => ("i32x4.max_u", false),
+            I32x4DotI16x8S => ("i32x4.dot_i16x8_s", false),
+            I32x4ExtMulLowI16x8S => ("i32x4.extmul_low_i16x8_s", false),
+            I32x4ExtMulHighI16x8S => ("i32x4.extmul_high_i16x8_s", false),
+            I32x4ExtMulLowI16x8U => ("i32x4.extmul_low_i16x8_u", false),
+            I32x4ExtMulHighI16x8U => ("i32x4.extmul_high_i16x8_u", false),
+            I64x2Shl => ("i64x2.shl", false),
+            I64x2ShrS => ("i64x2.shr_s", false),
+            I64x2ShrU => ("i64x2.shr_u", false),
+            I64x2Add => ("i64x2.add", false),
+            I64x2Sub => ("i64x2.sub", false),
+            I64x2Mul => ("i64x2.mul", false),
+            I64x2ExtMulLowI32x4S => ("i64x2.extmul_low_i32x4_s", false),
+            I64x2ExtMulHighI32x4S => ("i64x2.extmul_high_i32x4_s", false),
+            I64x2ExtMulLowI32x4U => ("i64x2.extmul_low_i32x4_u", false),
+            I64x2ExtMulHighI32x4U => ("i64x2.extmul_high_i32x4_u", false),
+            I8x16RelaxedSwizzle => ("i8x16.relaxed_swizzle", false),
+            F32x4RelaxedMin => ("f32x4.relaxed_min", false),
+            F32x4RelaxedMax => ("f32x4.relaxed_max", false),
+            F64x2RelaxedMin => ("f64x2.relaxed_min", false),
+            F64x2RelaxedMax => ("f64x2.relaxed_max", false),
+            I16x8RelaxedQ15mulrS => ("i16x8.relaxed_q15mulr_s", false),
+            I16x8RelaxedDotI8x16I7x16S => ("i16x8.relaxed_dot_i8x16_i7x16_s", false),
+            F32x4Add => ("f32x4.add", false),
+            F32x4Sub => ("f32x4.sub", false),
+            F32x4Mul => ("f32x4.mul", false),
+            F32x4Div => ("f32x4.div", false),
+            F32x4Min => ("f32x4.min", false),
+            F32x4Max => ("f32x4.max", false),
+            F32x4PMin => ("f32x4.pmin", false),
+            F32x4PMax => ("f32x4.pmax", false),
+            F64x2Add => ("f64x2.add", false),
+            F64x2Sub => ("f64x2.sub", false),
+            F64x2Mul => ("f64x2.mul", false),
+            F64x2Div => ("f64x2.div", false),
+            F64x2Min => ("f64x2.min", false),
+            F64x2Max => ("f64x2.max", false),
+            F64x2PMin => ("f64x2.pmin", false),
+            F64x2PMax => ("f64x2.pmax", false),
         }
     }
 
@@ -674,6 +2091,146 @@ impl BinaryExpression {
             F64Div => wasm::ValType::F64,
             F64Min => wasm::ValType::F64,
             F64Max => wasm::ValType::F64,
+            RefEq => wasm::ValType::I32,
+            I8x16Swizzle => wasm::ValType::V128,
+            I8x16Eq => wasm::ValType::V128,
+            I8x16Ne => wasm::ValType::V128,
+            I8x16LtS => wasm::ValType::V128,
+            I8x16LtU => wasm::ValType::V128,
+            I8x16GtS => wasm::ValType::V128,
+            I8x16GtU => wasm::ValType::V128,
+            I8x16LeS => wasm::ValType::V128,
+            I8x16LeU => wasm::ValType::V128,
+            I8x16GeS => wasm::ValType::V128,
+            I8x16GeU => wasm::ValType::V128,
+            I16x8Eq => wasm::ValType::V128,
+            I16x8Ne => wasm::ValType::V128,
+            I16x8LtS => wasm::ValType::V128,
+            I16x8LtU => wasm::ValType::V128,
+            I16x8GtS => wasm::ValType::V128,
+            I16x8GtU => wasm::ValType::V128,
+            I16x8LeS => wasm::ValType::V128,
+            I16x8LeU => wasm::ValType::V128,
+            I16x8GeS => wasm::ValType::V128,
+            I16x8GeU => wasm::ValType::V128,
+            I32x4Eq => wasm::ValType::V128,
+            I32x4Ne => wasm::ValType::V128,
+            I32x4LtS => wasm::ValType::V128,
+            I32x4LtU => wasm::ValType::V128,
+            I32x4GtS => wasm::ValType::V128,
+            I32x4GtU => wasm::ValType::V128,
+            I32x4LeS => wasm::ValType::V128,
+            I32x4LeU => wasm::ValType::V128,
+            I32x4GeS => wasm::ValType::V128,
+            I32x4GeU => wasm::ValType::V128,
+            I64x2Eq => wasm::ValType::V128,
+            I64x2Ne => wasm::ValType::V128,
+            I64x2LtS => wasm::ValType::V128,
+            I64x2GtS => wasm::ValType::V128,
+            I64x2LeS => wasm::ValType::V128,
+            I64x2GeS => wasm::ValType::V128,
+            F32x4Eq => wasm::ValType::V128,
+            F32x4Ne => wasm::ValType::V128,
+            F32x4Lt => wasm::ValType::V128,
+            F32x4Gt => wasm::ValType::V128,
+            F32x4Le => wasm::ValType::V128,
+            F32x4Ge => wasm::ValType::V128,
+            F64x2Eq => wasm::ValType::V128,
+            F64x2Ne => wasm::ValType::V128,
+            F64x2Lt => wasm::ValType::V128,
+            F64x2Gt => wasm::ValType::V128,
+            F64x2Le => wasm::ValType::V128,
+            F64x2Ge => wasm::ValType::V128,
+            V128And => wasm::ValType::V128,
+            V128AndNot => wasm::ValType::V128,
+            V128Or => wasm::ValType::V128,
+            V128Xor => wasm::ValType::V128,
+            I8x16NarrowI16x8S => wasm::ValType::V128,
+            I8x16NarrowI16x8U => wasm::ValType::V128,
+            I8x16Shl => wasm::ValType::V128,
+            I8x16ShrS => wasm::ValType::V128,
+            I8x16ShrU => wasm::ValType::V128,
+            I8x16Add => wasm::ValType::V128,
+            I8x16AddSatS => wasm::ValType::V128,
+            I8x16AddSatU => wasm::ValType::V128,
+            I8x16Sub => wasm::ValType::V128,
+            I8x16SubSatS => wasm::ValType::V128,
+            I8x16SubSatU => wasm::ValType::V128,
+            I8x16MinS => wasm::ValType::V128,
+            I8x16MinU => wasm::ValType::V128,
+            I8x16MaxS => wasm::ValType::V128,
+            I8x16MaxU => wasm::ValType::V128,
+            I8x16AvgrU => wasm::ValType::V128,
+            I16x8Q15MulrSatS => wasm::ValType::V128,
+            I16x8NarrowI32x4S => wasm::ValType::V128,
+            I16x8NarrowI32x4U => wasm::ValType::V128,
+            I16x8Shl => wasm::ValType::V128,
+            I16x8ShrS => wasm::ValType::V128,
+            I16x8ShrU => wasm::ValType::V128,
+            I16x8Add => wasm::ValType::V128,
+            I16x8AddSatS => wasm::ValType::V128,
+            I16x8AddSatU => wasm::ValType::V128,
+            I16x8Sub => wasm::ValType::V128,
+            I16x8SubSatS => wasm::ValType::V128,
+            I16x8SubSatU => wasm::ValType::V128,
+            I16x8Mul => wasm::ValType::V128,
+            I16x8MinS => wasm::ValType::V128,
+            I16x8MinU => wasm::ValType::V128,
+            I16x8MaxS => wasm::ValType::V128,
+            I16x8MaxU => wasm::ValType::V128,
+            I16x8AvgrU => wasm::ValType::V128,
+            I16x8ExtMulLowI8x16S => wasm::ValType::V128,
+            I16x8ExtMulHighI8x16S => wasm::ValType::V128,
+            I16x8ExtMulLowI8x16U => wasm::ValType::V128,
+            I16x8ExtMulHighI8x16U => wasm::ValType::V128,
+            I32x4Shl => wasm::ValType::V128,
+            I32x4ShrS => wasm::ValType::V128,
+            I32x4ShrU => wasm::ValType::V128,
+            I32x4Add => wasm::ValType::V128,
+            I32x4Sub => wasm::ValType::V128,
+            I32x4Mul => wasm::ValType::V128,
+            I32x4MinS => wasm::ValType::V128,
+            I32x4MinU => wasm::ValType::V128,
+            I32x4MaxS => wasm::ValType::V128,
+            I32x4MaxU => wasm::ValType::V128,
+            I32x4DotI16x8S => wasm::ValType::V128,
+            I32x4ExtMulLowI16x8S => wasm::ValType::V128,
+            I32x4ExtMulHighI16x8S => wasm::ValType::V128,
+            I32x4ExtMulLowI16x8U => wasm::ValType::V128,
+            I32x4ExtMulHighI16x8U => wasm::ValType::V128,
+            I64x2Shl => wasm::ValType::V128,
+            I64x2ShrS => wasm::ValType::V128,
+            I64x2ShrU => wasm::ValType::V128,
+            I64x2Add => wasm::ValType::V128,
+            I64x2Sub => wasm::ValType::V128,
+            I64x2Mul => wasm::ValType::V128,
+            I64x2ExtMulLowI32x4S => wasm::ValType::V128,
+            I64x2ExtMulHighI32x4S => wasm::ValType::V128,
+            I64x2ExtMulLowI32x4U => wasm::ValType::V128,
+            I64x2ExtMulHighI32x4U => wasm::ValType::V128,
+            I8x16RelaxedSwizzle => wasm::ValType::V128,
+            F32x4RelaxedMin => wasm::ValType::V128,
+            F32x4RelaxedMax => wasm::ValType::V128,
+            F64x2RelaxedMin => wasm::ValType::V128,
+            F64x2RelaxedMax => wasm::ValType::V128,
+            I16x8RelaxedQ15mulrS => wasm::ValType::V128,
+            I16x8RelaxedDotI8x16I7x16S => wasm::ValType::V128,
+            F32x4Add => wasm::ValType::V128,
+            F32x4Sub => wasm::ValType::V128,
+            F32x4Mul => wasm::ValType::V128,
+            F32x4Div => wasm::ValType::V128,
+            F32x4Min => wasm::ValType::V128,
+            F32x4Max => wasm::ValType::V128,
+            F32x4PMin => wasm::ValType::V128,
+            F32x4PMax => wasm::ValType::V128,
+            F64x2Add => wasm::ValType::V128,
+            F64x2Sub => wasm::ValType::V128,
+            F64x2Mul => wasm::ValType::V128,
+            F64x2Div => wasm::ValType::V128,
+            F64x2Min => wasm::ValType::V128,
+            F64x2Max => wasm::ValType::V128,
+            F64x2PMin => wasm::ValType::V128,
+            F64x2PMax => wasm::ValType::V128,
         }
     }
 }
@@ -757,49 +2314,481 @@ impl From<wasm::Operator<'_>> for BinaryExpression {
             wasm::Operator::F64Div => BinaryExpression::F64Div,
             wasm::Operator::F64Min => BinaryExpression::F64Min,
             wasm::Operator::F64Max => BinaryExpression::F64Max,
+            wasm::Operator::RefEq => BinaryExpression::RefEq,
+            wasm::Operator::I8x16Swizzle => BinaryExpression::I8x16Swizzle,
+            wasm::Operator::I8x16Eq => BinaryExpression::I8x16Eq,
+            wasm::Operator::I8x16Ne => BinaryExpression::I8x16Ne,
+            wasm::Operator::I8x16LtS => BinaryExpression::I8x16LtS,
+            wasm::Operator::I8x16LtU => BinaryExpression::I8x16LtU,
+            wasm::Operator::I8x16GtS => BinaryExpression::I8x16GtS,
+            wasm::Operator::I8x16GtU => BinaryExpression::I8x16GtU,
+            wasm::Operator::I8x16LeS => BinaryExpression::I8x16LeS,
+            wasm::Operator::I8x16LeU => BinaryExpression::I8x16LeU,
+            wasm::Operator::I8x16GeS => BinaryExpression::I8x16GeS,
+            wasm::Operator::I8x16GeU => BinaryExpression::I8x16GeU,
+            wasm::Operator::I16x8Eq => BinaryExpression::I16x8Eq,
+            wasm::Operator::I16x8Ne => BinaryExpression::I16x8Ne,
+            wasm::Operator::I16x8LtS => BinaryExpression::I16x8LtS,
+            wasm::Operator::I16x8LtU => BinaryExpression::I16x8LtU,
+            wasm::Operator::I16x8GtS => BinaryExpression::I16x8GtS,
+            wasm::Operator::I16x8GtU => BinaryExpression::I16x8GtU,
+            wasm::Operator::I16x8LeS => BinaryExpression::I16x8LeS,
+            wasm::Operator::I16x8LeU => BinaryExpression::I16x8LeU,
+            wasm::Operator::I16x8GeS => BinaryExpression::I16x8GeS,
+            wasm::Operator::I16x8GeU => BinaryExpression::I16x8GeU,
+            wasm::Operator::I32x4Eq => BinaryExpression::I32x4Eq,
+            wasm::Operator::I32x4Ne => BinaryExpression::I32x4Ne,
+            wasm::Operator::I32x4LtS => BinaryExpression::I32x4LtS,
+            wasm::Operator::I32x4LtU => BinaryExpression::I32x4LtU,
+            wasm::Operator::I32x4GtS => BinaryExpression::I32x4GtS,
+            wasm::Operator::I32x4GtU => BinaryExpression::I32x4GtU,
+            wasm::Operator::I32x4LeS => BinaryExpression::I32x4LeS,
+            wasm::Operator::I32x4LeU => BinaryExpression::I32x4LeU,
+            wasm::Operator::I32x4GeS => BinaryExpression::I32x4GeS,
+            wasm::Operator::I32x4GeU => BinaryExpression::I32x4GeU,
+            wasm::Operator::I64x2Eq => BinaryExpression::I64x2Eq,
+            wasm::Operator::I64x2Ne => BinaryExpression::I64x2Ne,
+            wasm::Operator::I64x2LtS => BinaryExpression::I64x2LtS,
+            wasm::Operator::I64x2GtS => BinaryExpression::I64x2GtS,
+            wasm::Operator::I64x2LeS => BinaryExpression::I64x2LeS,
+            wasm::Operator::I64x2GeS => BinaryExpression::I64x2GeS,
+            wasm::Operator::F32x4Eq => BinaryExpression::F32x4Eq,
+            wasm::Operator::F32x4Ne => BinaryExpression::F32x4Ne,
+            wasm::Operator::F32x4Lt => BinaryExpression::F32x4Lt,
+            wasm::Operator::F32x4Gt => BinaryExpression::F32x4Gt,
+            wasm::Operator::F32x4Le => BinaryExpression::F32x4Le,
+            wasm::Operator::F32x4Ge => BinaryExpression::F32x4Ge,
+            wasm::Operator::F64x2Eq => BinaryExpression::F64x2Eq,
+            wasm::Operator::F64x2Ne => BinaryExpression::F64x2Ne,
+            wasm::Operator::F64x2Lt => BinaryExpression::F64x2Lt,
+            wasm::Operator::F64x2Gt => BinaryExpression::F64x2Gt,
+            wasm::Operator::F64x2Le => BinaryExpression::F64x2Le,
+            wasm::Operator::F64x2Ge => BinaryExpression::F64x2Ge,
+            wasm::Operator::V128And => BinaryExpression::V128And,
+            wasm::Operator::V128AndNot => BinaryExpression::V128AndNot,
+            wasm::Operator::V128Or => BinaryExpression::V128Or,
+            wasm::Operator::V128Xor => BinaryExpression::V128Xor,
+            wasm::Operator::I8x16NarrowI16x8S => BinaryExpression::I8x16NarrowI16x8S,
+            wasm::Operator::I8x16NarrowI16x8U => BinaryExpression::I8x16NarrowI16x8U,
+            wasm::Operator::I8x16Shl => BinaryExpression::I8x16Shl,
+            wasm::Operator::I8x16ShrS => BinaryExpression::I8x16ShrS,
+            wasm::Operator::I8x16ShrU => BinaryExpression::I8x16ShrU,
+            wasm::Operator::I8x16Add => BinaryExpression::I8x16Add,
+            wasm::Operator::I8x16AddSatS => BinaryExpression::I8x16AddSatS,
+            wasm::Operator::I8x16AddSatU => BinaryExpression::I8x16AddSatU,
+            wasm::Operator::I8x16Sub => BinaryExpression::I8x16Sub,
+            wasm::Operator::I8x16SubSatS => BinaryExpression::I8x16SubSatS,
+            wasm::Operator::I8x16SubSatU => BinaryExpression::I8x16SubSatU,
+            wasm::Operator::I8x16MinS => BinaryExpression::I8x16MinS,
+            wasm::Operator::I8x16MinU => BinaryExpression::I8x16MinU,
+            wasm::Operator::I8x16MaxS => BinaryExpression::I8x16MaxS,
+            wasm::Operator::I8x16MaxU => BinaryExpression::I8x16MaxU,
+            wasm::Operator::I8x16AvgrU => BinaryExpression::I8x16AvgrU,
+            wasm::Operator::I16x8Q15MulrSatS => BinaryExpression::I16x8Q15MulrSatS,
+            wasm::Operator::I16x8NarrowI32x4S => BinaryExpression::I16x8NarrowI32x4S,
+            wasm::Operator::I16x8NarrowI32x4U => BinaryExpression::I16x8NarrowI32x4U,
+            wasm::Operator::I16x8Shl => BinaryExpression::I16x8Shl,
+            wasm::Operator::I16x8ShrS => BinaryExpression::I16x8ShrS,
+            wasm::Operator::I16x8ShrU => BinaryExpression::I16x8ShrU,
+            wasm::Operator::I16x8Add => BinaryExpression::I16x8Add,
+            wasm::Operator::I16x8AddSatS => BinaryExpression::I16x8AddSatS,
+            wasm::Operator::I16x8AddSatU => BinaryExpression::I16x8AddSatU,
+            wasm::Operator::I16x8Sub => BinaryExpression::I16x8Sub,
+            wasm::Operator::I16x8SubSatS => BinaryExpression::I16x8SubSatS,
+            wasm::Operator::I16x8SubSatU => BinaryExpression::I16x8SubSatU,
+            wasm::Operator::I16x8Mul => BinaryExpression::I16x8Mul,
+            wasm::Operator::I16x8MinS => BinaryExpression::I16x8MinS,
+            wasm::Operator::I16x8MinU => BinaryExpression::I16x8MinU,
+            wasm::Operator::I16x8MaxS => BinaryExpression::I16x8MaxS,
+            wasm::Operator::I16x8MaxU => BinaryExpression::I16x8MaxU,
+            wasm::Operator::I16x8AvgrU => BinaryExpression::I16x8AvgrU,
+            wasm::Operator::I16x8ExtMulLowI8x16S => BinaryExpression::I16x8ExtMulLowI8x16S,
+            wasm::Operator::I16x8ExtMulHighI8x16S => BinaryExpression::I16x8ExtMulHighI8x16S,
+            wasm::Operator::I16x8ExtMulLowI8x16U => BinaryExpression::I16x8ExtMulLowI8x16U,
+            wasm::Operator::I16x8ExtMulHighI8x16U => BinaryExpression::I16x8ExtMulHighI8x16U,
+            wasm::Operator::I32x4Shl => BinaryExpression::I32x4Shl,
+            wasm::Operator::I32x4ShrS => BinaryExpression::I32x4ShrS,
+            wasm::Operator::I32x4ShrU => BinaryExpression::I32x4ShrU,
+            wasm::Operator::I32x4Add => BinaryExpression::I32x4Add,
+            wasm::Operator::I32x4Sub => BinaryExpression::I32x4Sub,
+            wasm::Operator::I32x4Mul => BinaryExpression::I32x4Mul,
+            wasm::Operator::I32x4MinS => BinaryExpression::I32x4MinS,
+            wasm::Operator::I32x4MinU => BinaryExpression::I32x4MinU,
+            wasm::Operator::I32x4MaxS => BinaryExpression::I32x4MaxS,
+            wasm::Operator::I32x4MaxU => BinaryExpression::I32x4MaxU,
+            wasm::Operator::I32x4DotI16x8S => BinaryExpression::I32x4DotI16x8S,
+            wasm::Operator::I32x4ExtMulLowI16x8S => BinaryExpression::I32x4ExtMulLowI16x8S,
+            wasm::Operator::I32x4ExtMulHighI16x8S => BinaryExpression::I32x4ExtMulHighI16x8S,
+            wasm::Operator::I32x4ExtMulLowI16x8U => BinaryExpression::I32x4ExtMulLowI16x8U,
+            wasm::Operator::I32x4ExtMulHighI16x8U => BinaryExpression::I32x4ExtMulHighI16x8U,
+            wasm::Operator::I64x2Shl => BinaryExpression::I64x2Shl,
+            wasm::Operator::I64x2ShrS => BinaryExpression::I64x2ShrS,
+            wasm::Operator::I64x2ShrU => BinaryExpression::I64x2ShrU,
+            wasm::Operator::I64x2Add => BinaryExpression::I64x2Add,
+            wasm::Operator::I64x2Sub => BinaryExpression::I64x2Sub,
+            wasm::Operator::I64x2Mul => BinaryExpression::I64x2Mul,
+            wasm::Operator::I64x2ExtMulLowI32x4S => BinaryExpression::I64x2ExtMulLowI32x4S,
+            wasm::Operator::I64x2ExtMulHighI32x4S => BinaryExpression::I64x2ExtMulHighI32x4S,
+            wasm::Operator::I64x2ExtMulLowI32x4U => BinaryExpression::I64x2ExtMulLowI32x4U,
+            wasm::Operator::I64x2ExtMulHighI32x4U => BinaryExpression::I64x2ExtMulHighI32x4U,
+            wasm::Operator::I8x16RelaxedSwizzle => BinaryExpression::I8x16RelaxedSwizzle,
+            wasm::Operator::F32x4RelaxedMin => BinaryExpression::F32x4RelaxedMin,
+            wasm::Operator::F32x4RelaxedMax => BinaryExpression::F32x4RelaxedMax,
+            wasm::Operator::F64x2RelaxedMin => BinaryExpression::F64x2RelaxedMin,
+            wasm::Operator::F64x2RelaxedMax => BinaryExpression::F64x2RelaxedMax,
+            wasm::Operator::I16x8RelaxedQ15mulrS => BinaryExpression::I16x8RelaxedQ15mulrS,
+            wasm::Operator::I16x8RelaxedDotI8x16I7x16S => {
+                BinaryExpression::I16x8RelaxedDotI8x16I7x16S
+            }
+            wasm::Operator::F32x4Add => BinaryExpression::F32x4Add,
+            wasm::Operator::F32x4Sub => BinaryExpression::F32x4Sub,
+            wasm::Operator::F32x4Mul => BinaryExpression::F32x4Mul,
+            wasm::Operator::F32x4Div => BinaryExpression::F32x4Div,
+            wasm::Operator::F32x4Min => BinaryExpression::F32x4Min,
+            wasm::Operator::F32x4Max => BinaryExpression::F32x4Max,
+            wasm::Operator::F32x4PMin => BinaryExpression::F32x4PMin,
+            wasm::Operator::F32x4PMax => BinaryExpression::F32x4PMax,
+            wasm::Operator::F64x2Add => BinaryExpression::F64x2Add,
+            wasm::Operator::F64x2Sub => BinaryExpression::F64x2Sub,
+            wasm::Operator::F64x2Mul => BinaryExpression::F64x2Mul,
+            wasm::Operator::F64x2Div => BinaryExpression::F64x2Div,
+            wasm::Operator::F64x2Min => BinaryExpression::F64x2Min,
+            wasm::Operator::F64x2Max => BinaryExpression::F64x2Max,
+            wasm::Operator::F64x2PMin => BinaryExpression::F64x2PMin,
+            wasm::Operator::F64x2PMax => BinaryExpression::F64x2PMax,
             _ => unreachable!(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct CallExpression {
-    func_index: u32,
-    params: Vec<Expression>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum LogicalExpression {
+    And,
+    Or,
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct CallIndirectExpression {
-    func_type_index: u32,
-    _table_index: u32,
-    callee_index: Box<Expression>,
-    params: Vec<Expression>,
+impl LogicalExpression {
+    fn to_string(self) -> &'static str {
+        match self {
+            LogicalExpression::And => "&&",
+            LogicalExpression::Or => "||",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct GetLocalExpression {
-    local_index: u32,
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub enum TernaryExpression {
+    V128Bitselect,
+    F32x4RelaxedMadd,
+    F32x4RelaxedNmadd,
+    F64x2RelaxedMadd,
+    F64x2RelaxedNmadd,
+    I8x16RelaxedLaneselect,
+    I16x8RelaxedLaneselect,
+    I32x4RelaxedLaneselect,
+    I64x2RelaxedLaneselect,
+    I32x4RelaxedDotI8x16I7x16AddS,
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct GetLocalNExpression {
-    local_indices: Vec<u32>,
-}
+impl TernaryExpression {
+    fn to_string(&self) -> &'static str {
+        use TernaryExpression::*;
+        match self {
+            V128Bitselect => "v128.bitselect",
+            F32x4RelaxedMadd => "f32x4.relaxed_madd",
+            F32x4RelaxedNmadd => "f32x4.relaxed_nmadd",
+            F64x2RelaxedMadd => "f64x2.relaxed_madd",
+            F64x2RelaxedNmadd => "f64x2.relaxed_nmadd",
+            I8x16RelaxedLaneselect => "i8x16.relaxed_laneselect",
+            I16x8RelaxedLaneselect => "i16x8.relaxed_laneselect",
+            I32x4RelaxedLaneselect => "i32x4.relaxed_laneselect",
+            I64x2RelaxedLaneselect => "i64x2.relaxed_laneselect",
+            I32x4RelaxedDotI8x16I7x16AddS => "i32x4.relaxed_dot_i8x16_i7x16_add_s",
+        }
+    }
 
-#[derive(Debug, Clone)]
-pub(crate) struct GetGlobalExpression {
-    global_index: u32,
+    fn result_type(&self) -> wasm::ValType {
+        // Every ternary operator is a SIMD operator.
+        wasm::ValType::V128
+    }
+}
+
+impl From<wasm::Operator<'_>> for TernaryExpression {
+    fn from(value: wasm::Operator) -> Self {
+        match value {
+            wasm::Operator::V128Bitselect => TernaryExpression::V128Bitselect,
+            wasm::Operator::F32x4RelaxedMadd => TernaryExpression::F32x4RelaxedMadd,
+            wasm::Operator::F32x4RelaxedNmadd => TernaryExpression::F32x4RelaxedNmadd,
+            wasm::Operator::F64x2RelaxedMadd => TernaryExpression::F64x2RelaxedMadd,
+            wasm::Operator::F64x2RelaxedNmadd => TernaryExpression::F64x2RelaxedNmadd,
+            wasm::Operator::I8x16RelaxedLaneselect => TernaryExpression::I8x16RelaxedLaneselect,
+            wasm::Operator::I16x8RelaxedLaneselect => TernaryExpression::I16x8RelaxedLaneselect,
+            wasm::Operator::I32x4RelaxedLaneselect => TernaryExpression::I32x4RelaxedLaneselect,
+            wasm::Operator::I64x2RelaxedLaneselect => TernaryExpression::I64x2RelaxedLaneselect,
+            wasm::Operator::I32x4RelaxedDotI8x16I7x16AddS => {
+                TernaryExpression::I32x4RelaxedDotI8x16I7x16AddS
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// The lane interpretation of a v128 value.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum LaneShape {
+    I8x16,
+    I16x8,
+    I32x4,
+    I64x2,
+    F32x4,
+    F64x2,
+}
+
+impl LaneShape {
+    fn to_string(self) -> &'static str {
+        match self {
+            LaneShape::I8x16 => "i8x16",
+            LaneShape::I16x8 => "i16x8",
+            LaneShape::I32x4 => "i32x4",
+            LaneShape::I64x2 => "i64x2",
+            LaneShape::F32x4 => "f32x4",
+            LaneShape::F64x2 => "f64x2",
+        }
+    }
+
+    // The type of a single lane once extracted. Narrow integer lanes are
+    // extended to i32.
+    fn lane_type(self) -> wasm::ValType {
+        match self {
+            LaneShape::I8x16 | LaneShape::I16x8 | LaneShape::I32x4 => wasm::ValType::I32,
+            LaneShape::I64x2 => wasm::ValType::I64,
+            LaneShape::F32x4 => wasm::ValType::F32,
+            LaneShape::F64x2 => wasm::ValType::F64,
+        }
+    }
+}
+
+/// Reads one lane of a vector. `signed` picks the extension of narrow integer
+/// lanes and is ignored for the others.
+#[derive(Debug, Clone)]
+pub struct ExtractLaneExpression {
+    shape: LaneShape,
+    signed: bool,
+    lane: u8,
+    vector: Box<Expression>,
+}
+
+impl ExtractLaneExpression {
+    pub fn shape(&self) -> LaneShape {
+        self.shape
+    }
+
+    pub fn signed(&self) -> bool {
+        self.signed
+    }
+
+    pub fn lane(&self) -> u8 {
+        self.lane
+    }
+
+    pub fn vector(&self) -> &Expression {
+        &self.vector
+    }
+}
+
+/// A copy of a vector with one lane replaced.
+#[derive(Debug, Clone)]
+pub struct ReplaceLaneExpression {
+    shape: LaneShape,
+    lane: u8,
+    vector: Box<Expression>,
+    value: Box<Expression>,
+}
+
+impl ReplaceLaneExpression {
+    pub fn shape(&self) -> LaneShape {
+        self.shape
+    }
+
+    pub fn lane(&self) -> u8 {
+        self.lane
+    }
+
+    pub fn vector(&self) -> &Expression {
+        &self.vector
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShuffleExpression {
+    lanes: [u8; 16],
+    lhs: Box<Expression>,
+    rhs: Box<Expression>,
+}
+
+impl ShuffleExpression {
+    pub fn lanes(&self) -> [u8; 16] {
+        self.lanes
+    }
+
+    pub fn lhs(&self) -> &Expression {
+        &self.lhs
+    }
+
+    pub fn rhs(&self) -> &Expression {
+        &self.rhs
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CallExpression {
+    func_index: FuncIdx,
+    params: Vec<Expression>,
+}
+
+impl CallExpression {
+    pub fn func_index(&self) -> FuncIdx {
+        self.func_index
+    }
+
+    pub fn params(&self) -> &[Expression] {
+        &self.params
+    }
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct SelectExpression {
+pub struct CallIndirectExpression {
+    func_type_index: TypeIdx,
+    table_index: u32,
+    callee_index: Box<Expression>,
+    params: Vec<Expression>,
+    targets: IndirectTargets,
+}
+
+impl CallIndirectExpression {
+    pub fn func_type_index(&self) -> TypeIdx {
+        self.func_type_index
+    }
+
+    pub fn table_index(&self) -> u32 {
+        self.table_index
+    }
+
+    pub fn callee_index(&self) -> &Expression {
+        &self.callee_index
+    }
+
+    pub fn params(&self) -> &[Expression] {
+        &self.params
+    }
+
+    /// The functions the call is known to reach: the one in the slot a
+    /// constant index picks, or with
+    /// [`DecodeOptions::indirect_candidates`] those of the type it
+    /// expects. Empty if nothing is known.
+    pub fn targets(&self) -> &[FuncIdx] {
+        match &self.targets {
+            IndirectTargets::Unknown => &[],
+            IndirectTargets::Slot(func_index) => std::slice::from_ref(func_index),
+            IndirectTargets::Candidates(func_indices) => func_indices,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CallRefExpression {
+    type_index: TypeIdx,
+    callee: Box<Expression>,
+    params: Vec<Expression>,
+}
+
+impl CallRefExpression {
+    pub fn type_index(&self) -> TypeIdx {
+        self.type_index
+    }
+
+    pub fn callee(&self) -> &Expression {
+        &self.callee
+    }
+
+    pub fn params(&self) -> &[Expression] {
+        &self.params
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GetLocalExpression {
+    local_index: LocalIdx,
+}
+
+impl GetLocalExpression {
+    pub fn local_index(&self) -> LocalIdx {
+        self.local_index
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GetLocalNExpression {
+    local_indices: Vec<LocalIdx>,
+}
+
+impl GetLocalNExpression {
+    pub fn local_indices(&self) -> &[LocalIdx] {
+        &self.local_indices
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GetGlobalExpression {
+    global_index: GlobalIdx,
+}
+
+impl GetGlobalExpression {
+    pub fn global_index(&self) -> GlobalIdx {
+        self.global_index
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectExpression {
     condition: Box<Expression>,
     on_true: Box<Expression>,
     on_false: Box<Expression>,
+    ty: Option<wasm::ValType>,
+}
+
+impl SelectExpression {
+    /// The result type given by a typed `select`, which reference operands
+    /// need since they may be subtypes of it.
+    pub fn ty(&self) -> Option<wasm::ValType> {
+        self.ty
+    }
+
+    pub fn condition(&self) -> &Expression {
+        &self.condition
+    }
+
+    pub fn on_true(&self) -> &Expression {
+        &self.on_true
+    }
+
+    pub fn on_false(&self) -> &Expression {
+        &self.on_false
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
-enum MemoryLoadKind {
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub enum MemoryLoadKind {
     I32Load,
     I32Load8S,
     I32Load8U,
@@ -814,6 +2803,19 @@ enum MemoryLoadKind {
     I64Load32U,
     F32Load,
     F64Load,
+    V128Load,
+    V128Load8x8S,
+    V128Load8x8U,
+    V128Load16x4S,
+    V128Load16x4U,
+    V128Load32x2S,
+    V128Load32x2U,
+    V128Load8Splat,
+    V128Load16Splat,
+    V128Load32Splat,
+    V128Load64Splat,
+    V128Load32Zero,
+    V128Load64Zero,
 }
 
 impl From<wasm::Operator<'_>> for MemoryLoadKind {
@@ -833,6 +2835,19 @@ impl From<wasm::Operator<'_>> for MemoryLoadKind {
             wasm::Operator::I64Load32U { .. } => MemoryLoadKind::I64Load32U,
             wasm::Operator::F32Load { .. } => MemoryLoadKind::F32Load,
             wasm::Operator::F64Load { .. } => MemoryLoadKind::F64Load,
+            wasm::Operator::V128Load { .. } => MemoryLoadKind::V128Load,
+            wasm::Operator::V128Load8x8S { .. } => MemoryLoadKind::V128Load8x8S,
+            wasm::Operator::V128Load8x8U { .. } => MemoryLoadKind::V128Load8x8U,
+            wasm::Operator::V128Load16x4S { .. } => MemoryLoadKind::V128Load16x4S,
+            wasm::Operator::V128Load16x4U { .. } => MemoryLoadKind::V128Load16x4U,
+            wasm::Operator::V128Load32x2S { .. } => MemoryLoadKind::V128Load32x2S,
+            wasm::Operator::V128Load32x2U { .. } => MemoryLoadKind::V128Load32x2U,
+            wasm::Operator::V128Load8Splat { .. } => MemoryLoadKind::V128Load8Splat,
+            wasm::Operator::V128Load16Splat { .. } => MemoryLoadKind::V128Load16Splat,
+            wasm::Operator::V128Load32Splat { .. } => MemoryLoadKind::V128Load32Splat,
+            wasm::Operator::V128Load64Splat { .. } => MemoryLoadKind::V128Load64Splat,
+            wasm::Operator::V128Load32Zero { .. } => MemoryLoadKind::V128Load32Zero,
+            wasm::Operator::V128Load64Zero { .. } => MemoryLoadKind::V128Load64Zero,
             _ => unreachable!(),
         }
     }
@@ -855,34 +2870,322 @@ impl MemoryLoadKind {
             | MemoryLoadKind::I64Load32U => wasmparser::ValType::I64,
             MemoryLoadKind::F32Load => wasmparser::ValType::F32,
             MemoryLoadKind::F64Load => wasmparser::ValType::F64,
+            MemoryLoadKind::V128Load
+            | MemoryLoadKind::V128Load8x8S
+            | MemoryLoadKind::V128Load8x8U
+            | MemoryLoadKind::V128Load16x4S
+            | MemoryLoadKind::V128Load16x4U
+            | MemoryLoadKind::V128Load32x2S
+            | MemoryLoadKind::V128Load32x2U
+            | MemoryLoadKind::V128Load8Splat
+            | MemoryLoadKind::V128Load16Splat
+            | MemoryLoadKind::V128Load32Splat
+            | MemoryLoadKind::V128Load64Splat
+            | MemoryLoadKind::V128Load32Zero
+            | MemoryLoadKind::V128Load64Zero => wasmparser::ValType::V128,
+        }
+    }
+
+    // Vector loads that don't load a whole v128 are printed as a call on the
+    // loaded memory, since `memory[x]` alone doesn't say how it's widened.
+    fn simd_name(&self) -> Option<&'static str> {
+        match *self {
+            MemoryLoadKind::V128Load8x8S => Some("v128.load8x8_s"),
+            MemoryLoadKind::V128Load8x8U => Some("v128.load8x8_u"),
+            MemoryLoadKind::V128Load16x4S => Some("v128.load16x4_s"),
+            MemoryLoadKind::V128Load16x4U => Some("v128.load16x4_u"),
+            MemoryLoadKind::V128Load32x2S => Some("v128.load32x2_s"),
+            MemoryLoadKind::V128Load32x2U => Some("v128.load32x2_u"),
+            MemoryLoadKind::V128Load8Splat => Some("v128.load8_splat"),
+            MemoryLoadKind::V128Load16Splat => Some("v128.load16_splat"),
+            MemoryLoadKind::V128Load32Splat => Some("v128.load32_splat"),
+            MemoryLoadKind::V128Load64Splat => Some("v128.load64_splat"),
+            MemoryLoadKind::V128Load32Zero => Some("v128.load32_zero"),
+            MemoryLoadKind::V128Load64Zero => Some("v128.load64_zero"),
+            _ => None,
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct MemoryLoadExpression {
+pub struct MemoryLoadExpression {
     kind: MemoryLoadKind,
-    _arg: wasm::MemArg,
+    arg: wasm::MemArg,
+    index: Box<Expression>,
+}
+
+impl MemoryLoadExpression {
+    pub fn kind(&self) -> MemoryLoadKind {
+        self.kind
+    }
+
+    pub fn arg(&self) -> wasm::MemArg {
+        self.arg
+    }
+
+    pub fn index(&self) -> &Expression {
+        &self.index
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryGrowExpression {
+    memory: u32,
+    value: Box<Expression>,
+}
+
+impl MemoryGrowExpression {
+    pub fn memory(&self) -> u32 {
+        self.memory
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableGetExpression {
+    table: u32,
+    index: Box<Expression>,
+}
+
+impl TableGetExpression {
+    pub fn table(&self) -> u32 {
+        self.table
+    }
+
+    pub fn index(&self) -> &Expression {
+        &self.index
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableGrowExpression {
+    table: u32,
+    value: Box<Expression>,
+    delta: Box<Expression>,
+}
+
+impl TableGrowExpression {
+    pub fn table(&self) -> u32 {
+        self.table
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+
+    pub fn delta(&self) -> &Expression {
+        &self.delta
+    }
+}
+
+/// `struct.new`, or `struct.new_default` if `default` is set, in which case
+/// there are no `fields`.
+#[derive(Debug, Clone)]
+pub struct StructNewExpression {
+    type_index: TypeIdx,
+    default: bool,
+    fields: Vec<Expression>,
+}
+
+impl StructNewExpression {
+    pub fn type_index(&self) -> TypeIdx {
+        self.type_index
+    }
+
+    pub fn default(&self) -> bool {
+        self.default
+    }
+
+    pub fn fields(&self) -> &[Expression] {
+        &self.fields
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StructGetExpression {
+    type_index: TypeIdx,
+    field: u32,
+    extension: Extension,
+    object: Box<Expression>,
+}
+
+impl StructGetExpression {
+    pub fn type_index(&self) -> TypeIdx {
+        self.type_index
+    }
+
+    pub fn field(&self) -> u32 {
+        self.field
+    }
+
+    pub fn extension(&self) -> Extension {
+        self.extension
+    }
+
+    pub fn object(&self) -> &Expression {
+        &self.object
+    }
+}
+
+/// How a packed `i8` or `i16` field or array element is extended to an
+/// `i32` when read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum Extension {
+    None,
+    Signed,
+    Unsigned,
+}
+
+impl Extension {
+    fn suffix(self) -> &'static str {
+        match self {
+            Extension::None => "",
+            Extension::Signed => "_s",
+            Extension::Unsigned => "_u",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArrayNewExpression {
+    type_index: TypeIdx,
+    kind: ArrayNewKind,
+    args: Vec<Expression>,
+}
+
+impl ArrayNewExpression {
+    pub fn type_index(&self) -> TypeIdx {
+        self.type_index
+    }
+
+    pub fn kind(&self) -> ArrayNewKind {
+        self.kind
+    }
+
+    pub fn args(&self) -> &[Expression] {
+        &self.args
+    }
+}
+
+/// The `array.new*` variants, which differ in their operands.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum ArrayNewKind {
+    /// `value, len`
+    Value,
+    /// `len`
+    Default,
+    /// One operand per element.
+    Fixed,
+    /// `offset, len` into the segment.
+    Segment(Segment),
+}
+
+#[derive(Debug, Clone)]
+pub struct ArrayGetExpression {
+    type_index: TypeIdx,
+    extension: Extension,
+    array: Box<Expression>,
     index: Box<Expression>,
 }
 
+impl ArrayGetExpression {
+    pub fn type_index(&self) -> TypeIdx {
+        self.type_index
+    }
+
+    pub fn extension(&self) -> Extension {
+        self.extension
+    }
+
+    pub fn array(&self) -> &Expression {
+        &self.array
+    }
+
+    pub fn index(&self) -> &Expression {
+        &self.index
+    }
+}
+
+/// The target type of a `ref.test` or `ref.cast`.
 #[derive(Debug, Clone)]
-pub(crate) struct MemoryGrowExpression {
+pub struct RefCastExpression {
+    nullable: bool,
+    heap_type: wasm::HeapType,
     value: Box<Expression>,
 }
 
+impl RefCastExpression {
+    pub fn nullable(&self) -> bool {
+        self.nullable
+    }
+
+    pub fn heap_type(&self) -> wasm::HeapType {
+        self.heap_type
+    }
+
+    pub fn value(&self) -> &Expression {
+        &self.value
+    }
+}
+
+#[derive(Clone)]
 struct Local {
     ty: wasm::ValType,
     name: String,
 }
 
-pub(crate) struct Func {
+/// A decompiled function.
+#[derive(Clone)]
+pub struct Func {
     // name: String,
-    index: u32,
+    index: FuncIdx,
     ty: wasm::FuncType,
     locals: Vec<Local>,
     blocks: HashMap<BlockIndex, Block>,
     entry_block: BlockIndex,
+    error: Option<DecompileError>,
+    // Names of globals, tables, memories and types, shared by every
+    // function in the module.
+    names: Arc<Names>,
+    // Whether the module has more than one memory, so that memory accesses
+    // print which one they use.
+    multi_memory: bool,
+    // Where the data segments are, to annotate constant addresses.
+    data_ranges: Vec<DataRange>,
+    // Names of imported functions resolved against another module.
+    callee_names: HashMap<FuncIdx, String>,
+    // When the function runs out of its time budget, counted from when
+    // optimizing it started, and how long the budget is.
+    deadline: Option<(Instant, Duration)>,
+    // The time budget, if the function ran out of it.
+    over_budget: Option<Duration>,
+    // The blocks nested into ifs and loops, printed in their place.
+    structure: Option<Vec<Node>>,
+    // The shadow stack frame, whose slots print as `frame.var_8`.
+    frame: Option<StackFrame>,
+    // Whether integer locals are treated as signed or unsigned, which
+    // decides the type they're declared with and whether the operators
+    // applied to them need a `_s` or `_u` suffix.
+    signedness: HashMap<LocalIdx, Signedness>,
+    // Where the body is in the module, from its locals to its final `end`.
+    body_range: Range<usize>,
+    // The names the function is exported under.
+    exports: Vec<String>,
+    // Whether this is the module's start function.
+    is_start: bool,
+    // Whether statements are printed with their offsets, with
+    // `DecodeOptions::offsets`.
+    show_offsets: bool,
+    // The instructions after the last statement's, with
+    // `DecodeOptions::interleave`.
+    trailing_instructions: Vec<(usize, String)>,
+    // The function's text after each pass in `DecodeOptions::dump_after`.
+    pass_dumps: Vec<(Pass, String)>,
 }
 
 impl Func {
@@ -898,40 +3201,465 @@ impl Func {
         self.entry_block = *mapping.get(&self.entry_block).unwrap();
     }
 
+    pub fn index(&self) -> FuncIdx {
+        self.index
+    }
+
+    /// The function's params and results.
+    pub fn ty(&self) -> &wasm::FuncType {
+        &self.ty
+    }
+
+    /// How many locals the function has, its params first.
+    pub fn num_locals(&self) -> usize {
+        self.locals.len()
+    }
+
+    pub fn local_type(&self, local_index: LocalIdx) -> Option<wasm::ValType> {
+        self.locals
+            .get(local_index.as_usize())
+            .map(|local| local.ty)
+    }
+
+    /// The name a local is printed with, such as `arg0`, `i3` or `temp1`.
+    pub fn local_name(&self, local_index: LocalIdx) -> Option<&str> {
+        (self.locals.get(local_index.as_usize())).map(|local| local.name.as_str())
+    }
+
+    /// Why the function couldn't be decompiled, or wasn't, if so. Its
+    /// blocks are then empty.
+    pub fn error(&self) -> Option<&DecompileError> {
+        self.error.as_ref()
+    }
+
+    /// The block execution starts in. It has no params.
+    pub fn entry_block(&self) -> BlockIndex {
+        self.entry_block
+    }
+
+    pub fn block(&self, block_index: BlockIndex) -> Option<&Block> {
+        self.blocks.get(&block_index)
+    }
+
+    /// The indices of all blocks, in ascending order.
+    pub fn block_indices(&self) -> Vec<BlockIndex> {
+        self.visual_block_order()
+    }
+
+    /// The successors of a block, or nothing if there is no such block.
+    pub fn successors(&self, block_index: BlockIndex) -> Vec<BlockIndex> {
+        self.blocks
+            .get(&block_index)
+            .map(Block::successors)
+            .unwrap_or_default()
+    }
+
+    fn local(&self, local_index: LocalIdx) -> &Local {
+        &self.locals[local_index.as_usize()]
+    }
+
     fn visual_block_order(&self) -> Vec<BlockIndex> {
         let mut keys: Vec<BlockIndex> = self.blocks.keys().copied().collect();
         keys.sort();
         keys
     }
 
-    fn optimize(&mut self) {
-        self.reconstruct_control_flow();
-        self.jump_threading();
-        self.eliminate_dead_code();
-        self.renumber();
+    // Runs the passes not in `skip`, keeping the function's text after
+    // each of those in `dump` for `DecodeOptions::dump_after`.
+    fn optimize(&mut self, budget: Option<Duration>, skip: &[Pass], dump: &[Pass]) {
+        if self.error.is_some() {
+            return;
+        }
+        let runs = |pass| !skip.contains(&pass);
+        self.deadline = budget.map(|budget| (Instant::now() + budget, budget));
+        let deadline = self.deadline.map(|(deadline, _)| deadline);
+        if runs(Pass::FoldConstants) {
+            self.run_pass(Pass::FoldConstants, dump, Func::fold_constants);
+        }
+        if runs(Pass::ForwardTemps) {
+            self.run_pass(Pass::ForwardTemps, dump, Func::forward_temps);
+        }
+        let mut done = true;
+        if runs(Pass::MergeBlocks) {
+            done = self.run_pass(Pass::MergeBlocks, dump, |func| {
+                func.reconstruct_control_flow(deadline)
+            });
+        }
+        if runs(Pass::JumpThreading) {
+            self.run_pass(Pass::JumpThreading, dump, Func::jump_threading);
+        }
+        // Simplified terminators can leave more blocks to merge.
+        if runs(Pass::SimplifyTerminators) {
+            let simplified = self.run_pass(Pass::SimplifyTerminators, dump, |func| {
+                let simplified = func.simplify_terminators();
+                while func.simplify_terminators() {}
+                simplified
+            });
+            if simplified && runs(Pass::MergeBlocks) {
+                done = self.run_pass(Pass::MergeBlocks, dump, |func| {
+                    func.reconstruct_control_flow(deadline)
+                }) && done;
+            }
+        }
+        if !done {
+            tracing::debug!(func = %self.index, "ran out of time merging blocks");
+            self.over_budget = budget;
+        }
+        // Merged blocks can put more temps next to where they're read.
+        if runs(Pass::ForwardTemps) {
+            self.run_pass(Pass::ForwardTemps, dump, Func::forward_temps);
+        }
+        // Dropping the product a signed division reads twice can put the
+        // temp holding its dividend next to a remainder that reads it.
+        if runs(Pass::RecoverDivisions) {
+            self.run_pass(Pass::RecoverDivisions, dump, |func| {
+                if func.recover_divisions() {
+                    if runs(Pass::ForwardTemps) {
+                        func.forward_temps();
+                    }
+                    func.recover_divisions();
+                }
+            });
+        }
+        if runs(Pass::NormalizeConditions) {
+            self.run_pass(Pass::NormalizeConditions, dump, Func::normalize_conditions);
+        }
+        self.drop_stale_offsets();
+        // Renumbering leaves out unreachable blocks, so without dead code
+        // elimination the blocks keep the numbers they decoded with.
+        if runs(Pass::DeadCode) {
+            self.run_pass(Pass::DeadCode, dump, |func| {
+                func.eliminate_dead_code();
+                func.renumber();
+            });
+        }
+    }
+
+    // Whether the function's time budget has run out.
+    pub(crate) fn past_deadline(&self) -> bool {
+        (self.deadline).is_some_and(|(deadline, _)| Instant::now() >= deadline)
+    }
+
+    // Whether the function's time budget has run out, noting that it did
+    // in the output if so. The passes after optimizing are skipped once it
+    // has, leaving the function unstructured.
+    fn out_of_time(&mut self) -> bool {
+        if !self.past_deadline() {
+            return false;
+        }
+        self.over_budget = self.deadline.map(|(_, budget)| budget);
+        true
+    }
+
+    // Runs `pass` in a span of its own, and keeps the function's text after
+    // it if it's in `dump`.
+    fn run_pass<T>(&mut self, pass: Pass, dump: &[Pass], run: impl FnOnce(&mut Func) -> T) -> T {
+        let result = {
+            let _span = tracing::debug_span!("pass", name = pass.name()).entered();
+            run(self)
+        };
+        if dump.contains(&pass) {
+            let mut text = Vec::new();
+            // Writing to a `Vec` can't fail.
+            let _ = RenderOptions::default()
+                .render(self.pretty(INDENT, &pretty::BoxAllocator), &mut text);
+            self.pass_dumps
+                .push((pass, String::from_utf8_lossy(&text).into_owned()));
+        }
+        result
+    }
+}
+
+/// Options for decoding a module.
+#[derive(Debug, Default, Clone)]
+pub struct DecodeOptions {
+    /// Skip control flow reconstruction and the module-wide global analyses,
+    /// and print one function at a time. Each function is listed as the raw
+    /// blocks it decoded to, including unreachable ones. Meant for getting
+    /// searchable text out of very large modules quickly.
+    pub quick: bool,
+    /// Annotates statements with the source lines they came from. Without
+    /// one, the source map the module's `sourceMappingURL` refers to or
+    /// its DWARF line table is used, if it has either.
+    pub source_map: Option<SourceMap>,
+    /// Annotate each statement with the byte offset in the module of the
+    /// first instruction it came from, as `// @0x1a3c`, to match it up with
+    /// a disassembly or a stack trace. The whole range is recorded whether
+    /// or not this is set, as a [`Statement::Location`] before it.
+    pub offsets: bool,
+    /// Print the instructions each statement was decoded from before it,
+    /// as `// @0x2f: local.get 0`, for reading the decompiled code side by
+    /// side with the original. Instructions that were folded into an
+    /// expression are printed with the statement it ended up in, and those
+    /// after the last statement at the end of the function.
+    pub interleave: bool,
+    /// The directory the module was read from, which a relative
+    /// `sourceMappingURL` is resolved against.
+    pub module_dir: Option<PathBuf>,
+    /// Print a function whose body is byte-identical to an earlier
+    /// function's as an alias of it.
+    pub dedup: bool,
+    /// Print the entry points before the other functions.
+    pub entry_first: bool,
+    /// Only print the functions reachable from the entry points.
+    pub entry_only: bool,
+    /// Only print the functions reachable from the functions exported
+    /// under these names, callers before the functions they call, as by
+    /// [`CallGraph::topological_order`]. For auditing one entry point of a
+    /// large module. Takes the place of `entry_only`.
+    pub reachable_from: Vec<String>,
+    /// Treat mutable globals and table slots that are never written after
+    /// instantiation as constants: print their value in place of reads, and
+    /// turn indirect calls through them into direct calls.
+    pub freeze: bool,
+    /// Turn an assignment of a chain of selects at least this many deep
+    /// into nested if/else statements, so that `x = a ? b : (c ? d : e)`
+    /// reads as `if (a) { x = b } else { if (c) { x = d } else { x = e } }`.
+    /// Only chains whose values have no side effects are turned.
+    pub flatten_selects: Option<usize>,
+    /// Annotate each `call_indirect` whose target isn't known with the
+    /// functions it may call: those in an element segment with the type it
+    /// expects.
+    pub indirect_candidates: bool,
+    /// Hoist subexpressions nested deeper than this into temps assigned
+    /// just before the statement using them, so that no expression is
+    /// printed more than this many operators deep.
+    pub max_expression_depth: Option<usize>,
+    /// Hoist the leftmost operands of an expression with more than this
+    /// many that aren't just a local or a constant, such as a call whose
+    /// arguments are each computed, into temps assigned just before the
+    /// statement using them.
+    pub max_expression_width: Option<usize>,
+    /// Drop unused locals, merge locals of the same type whose values are
+    /// never needed at the same time, and name what's left by how it's
+    /// used: `i`, `j`, `k` for counters, `ptr` for memory addresses, `len`
+    /// for what a counter is compared against, and `a`, `b`, `c` and so on
+    /// for anything else. Locals are split as by
+    /// `split_locals` first, so one reused for unrelated values can be
+    /// named for each.
+    pub tidy_locals: bool,
+    /// Give each unrelated value a local is reused for its own local, so
+    /// that `x` assigned a length and later a pointer prints as `x_1` and
+    /// `x_2`. Values that meet where control flow joins stay together.
+    pub split_locals: bool,
+    /// Demangle Rust and C++ function names from the name section and
+    /// exports, so that `_ZN4core3fmt5write17h0123456789abcdefE` prints as
+    /// `core::fmt::write`.
+    pub demangle: bool,
+    /// Names for what the module doesn't name itself.
+    pub symbol_map: Option<SymbolMap>,
+    /// How many threads to decode, optimize and structure functions on. By
+    /// default there's one per CPU.
+    pub jobs: Option<usize>,
+    /// Keep the text of the functions in `dump_funcs` as it is after each
+    /// of these passes, for [`Module::pass_dumps`]. Meant for tracking down
+    /// which pass a change in the output comes from. A pass that runs more
+    /// than once is dumped each time.
+    pub dump_after: Vec<Pass>,
+    /// The functions `dump_after` keeps the text of.
+    pub dump_funcs: Vec<Range<FuncIdx>>,
+    /// Only decompile these functions, and only validate the rest. Much
+    /// faster for a few functions out of a large module. The rest are
+    /// printed as not decompiled, and analyses that look at every function,
+    /// such as `freeze`, treat them like functions that couldn't be
+    /// decompiled.
+    pub only: Option<FuncPicks>,
+    /// Optimization passes not to run, to see how each changes the output.
+    /// With all of them skipped, each function is printed as the blocks it
+    /// decoded to, as with `quick` but with the module-wide analyses still
+    /// done. Those analyses only name things and add comments: the stack
+    /// pointer global is still named, and indirect calls are still
+    /// annotated with what they reach. Rewrites asked for with their own
+    /// options, such as `freeze` or `tidy_locals`, still run.
+    pub skip_passes: Vec<Pass>,
+    /// The proposals a module may use. One using any other is rejected
+    /// with an error naming it, such as `SIMD support is not enabled`. By
+    /// default these are the proposals wasmparser accepts by default.
+    pub features: WasmFeatures,
+    /// Bounds on the work done decoding, for modules from somewhere
+    /// untrusted. Nothing is limited by default.
+    pub limits: Limits,
+}
+
+impl DecodeOptions {
+    // The passes to keep the text of the function at `func_index` after.
+    fn dumps(&self, func_index: FuncIdx) -> &[Pass] {
+        if (self.dump_funcs.iter()).any(|funcs| funcs.contains(&func_index)) {
+            &self.dump_after
+        } else {
+            &[]
+        }
     }
 }
 
+/// Functions picked out of a module, by index or by a name they're
+/// exported as.
+#[derive(Debug, Default, Clone)]
+pub struct FuncPicks {
+    pub ranges: Vec<Range<FuncIdx>>,
+    pub exports: Vec<String>,
+}
+
+/// Bounds on the work done decoding a module, for when it comes from
+/// somewhere untrusted. Nothing is limited by default.
+#[derive(Debug, Default, Clone)]
+pub struct Limits {
+    /// Refuse modules larger than this.
+    pub max_module_bytes: Option<usize>,
+    /// Only decompile this many functions. A notice in the output says how
+    /// many more there are.
+    pub max_funcs: Option<usize>,
+    /// How long to spend optimizing and structuring each function. A
+    /// function that runs out is printed unstructured, as far as it got,
+    /// with a notice.
+    pub func_time_budget: Option<Duration>,
+}
+
+#[derive(Clone)]
 pub struct Module {
     rec_groups: Vec<wasm::RecGroup>,
-    types_of_funcs: Vec<u32>,
+    types_of_funcs: Vec<TypeIdx>,
     num_func_imports: u32,
+    func_imports: Vec<FuncImport>,
+    func_exports: Vec<(String, FuncIdx)>,
+    // Function names from the name section.
+    debug_func_names: HashMap<FuncIdx, String>,
+    // Names of imported functions resolved by `link`.
+    linked_names: HashMap<FuncIdx, String>,
+    // Imported globals first, then defined ones.
+    globals: Vec<wasm::GlobalType>,
+    num_global_imports: u32,
+    names: Names,
+    source_mapping_url: Option<String>,
+    toolchain: Toolchain,
+    start_func: Option<FuncIdx>,
+    // Functions exported or named under an entry point name.
+    entry_names: Vec<(FuncIdx, EntryKind)>,
+    // Functions in element segments, which indirect calls may reach.
+    elem_funcs: Vec<FuncIdx>,
+    elem_segments: Vec<ActiveElem>,
+    num_table_imports: u32,
+    exported_tables: Vec<u32>,
+    // The values defined globals are initialized with, where they could be
+    // worked out.
+    global_inits: HashMap<GlobalIdx, Expression>,
+    // `module.field` of each imported global.
+    global_imports: Vec<String>,
+    exported_globals: Vec<GlobalIdx>,
+    frozen: Vec<Frozen>,
+    data_ranges: Vec<DataRange>,
     funcs: Vec<Func>,
+    // Functions with the same type and body bytes as an earlier function,
+    // mapped to the first such function.
+    duplicates: HashMap<FuncIdx, FuncIdx>,
+    quick: bool,
+    dedup: bool,
+    entry_first: bool,
+    entry_only: bool,
+    // The exported functions `DecodeOptions::reachable_from` names.
+    reachable_from: Vec<FuncIdx>,
+    // Functions not decompiled because of `Limits::max_funcs`.
+    omitted_funcs: usize,
+    max_funcs: Option<usize>,
+    func_time_budget: Option<Duration>,
+    // Whether the module has more than one memory.
+    multi_memory: bool,
 }
 
 impl Module {
     pub fn from_buffer(buffer: &[u8]) -> anyhow::Result<Self> {
+        Module::from_buffer_with_options(buffer, &DecodeOptions::default())
+    }
+
+    pub fn from_buffer_with_options(
+        buffer: &[u8],
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Self> {
+        let mut result = Module::read(buffer, options, None)?;
+        result.finish(buffer, options)?;
+        Ok(result)
+    }
+
+    // Reads and validates the module in `buffer`, and decodes the
+    // functions `options.only` picks, which are left for `finish` to
+    // decompile. With `bodies`, where each function's body is and what
+    // validating it again needs are kept there, for `LazyModule`.
+    pub(crate) fn read(
+        buffer: &[u8],
+        options: &DecodeOptions,
+        bodies: Option<&mut LazyBodies>,
+    ) -> anyhow::Result<Self> {
+        if is_component(buffer) {
+            bail!("this is a component, not a core module; decode its core modules with `Module::from_component`");
+        }
+        if let Some(max_module_bytes) = options.limits.max_module_bytes {
+            if buffer.len() > max_module_bytes {
+                bail!(
+                    "the module is {} bytes, over the limit of {}",
+                    buffer.len(),
+                    max_module_bytes
+                );
+            }
+        }
+        let embedded;
+        let source_map = match &options.source_map {
+            Some(source_map) => Some(source_map),
+            None => {
+                embedded = SourceMap::from_module(buffer, options.module_dir.as_deref());
+                embedded.as_ref()
+            }
+        };
         let parser = wasm::Parser::new(0);
-        let mut validator = wasm::Validator::new();
+        let mut validator = wasm::Validator::new_with_features(options.features);
+        // Identical types share a type id, so map each id to its first index.
+        let mut type_indices = HashMap::new();
         let mut result = Self {
             rec_groups: Vec::new(),
             types_of_funcs: Vec::new(),
             num_func_imports: 0,
+            func_imports: Vec::new(),
+            func_exports: Vec::new(),
+            debug_func_names: HashMap::new(),
+            linked_names: HashMap::new(),
+            globals: Vec::new(),
+            num_global_imports: 0,
+            names: Names::default(),
+            source_mapping_url: None,
+            toolchain: Toolchain::default(),
+            start_func: None,
+            entry_names: Vec::new(),
+            elem_funcs: Vec::new(),
+            elem_segments: Vec::new(),
+            num_table_imports: 0,
+            exported_tables: Vec::new(),
+            global_inits: HashMap::new(),
+            global_imports: Vec::new(),
+            exported_globals: Vec::new(),
+            frozen: Vec::new(),
+            data_ranges: Vec::new(),
             funcs: Vec::new(),
+            duplicates: HashMap::new(),
+            quick: options.quick,
+            dedup: options.dedup,
+            entry_first: options.entry_first,
+            entry_only: options.entry_only,
+            reachable_from: Vec::new(),
+            omitted_funcs: 0,
+            max_funcs: options.limits.max_funcs,
+            func_time_budget: options.limits.func_time_budget,
+            multi_memory: false,
         };
+        let pool = thread_pool(options)?;
+        // Function bodies are decoded once the whole module's been read, in
+        // parallel since they don't depend on each other.
+        let mut func_bodies = Vec::new();
+        let mut num_memories = 0;
 
+        let _span = tracing::info_span!("module", bytes = buffer.len()).entered();
         for payload in parser.parse_all(buffer) {
-            match payload? {
+            let payload = payload?;
+            let _span = tracing::debug_span!("section", name = section_name(&payload)).entered();
+            match payload {
                 // Sections for WebAssembly modules
                 wasm::Payload::Version {
                     num,
@@ -949,11 +3677,39 @@ impl Module {
                 wasm::Payload::ImportSection(section) => {
                     validator.import_section(&section)?;
                     result.num_func_imports = validator.types(0).unwrap().function_count();
+                    for import in section {
+                        let import = import?;
+                        match import.ty {
+                            wasm::TypeRef::Global(ty) => {
+                                let global_index = GlobalIdx::from(result.globals.len() as u32);
+                                result.name_global(global_index, import.name);
+                                result.globals.push(ty);
+                                result
+                                    .global_imports
+                                    .push(format!("{}.{}", import.module, import.name));
+                            }
+                            wasm::TypeRef::Memory(_) => num_memories += 1,
+                            wasm::TypeRef::Table(_) => result.num_table_imports += 1,
+                            wasm::TypeRef::Func(type_index) => {
+                                let types = validator.types(0).unwrap();
+                                let ty = types[types.core_type_at_in_module(type_index)]
+                                    .unwrap_func()
+                                    .clone();
+                                result.func_imports.push(FuncImport::new(
+                                    import.module,
+                                    import.name,
+                                    ty,
+                                ));
+                            }
+                            _ => {}
+                        }
+                    }
+                    result.num_global_imports = result.globals.len() as u32;
                 }
                 wasm::Payload::FunctionSection(section) => {
                     validator.function_section(&section)?;
                     for func in section {
-                        result.types_of_funcs.push(func?);
+                        result.types_of_funcs.push(func?.into());
                     }
                 }
                 wasm::Payload::TableSection(section) => {
@@ -961,27 +3717,84 @@ impl Module {
                 }
                 wasm::Payload::MemorySection(section) => {
                     validator.memory_section(&section)?;
+                    num_memories += section.count();
                 }
                 wasm::Payload::TagSection(section) => {
                     validator.tag_section(&section)?;
                 }
                 wasm::Payload::GlobalSection(section) => {
                     validator.global_section(&section)?;
+                    for global in section {
+                        let global = global?;
+                        if let Some(value) = globals::const_expr_value(&global.init_expr)? {
+                            let global_index = GlobalIdx::from(result.globals.len() as u32);
+                            result.global_inits.insert(global_index, value);
+                        }
+                        result.globals.push(global.ty);
+                    }
                 }
                 wasm::Payload::ExportSection(section) => {
                     validator.export_section(&section)?;
+                    for export in section {
+                        let export = export?;
+                        match export.kind {
+                            wasm::ExternalKind::Global => {
+                                result.name_global(export.index.into(), export.name);
+                                result.exported_globals.push(export.index.into());
+                            }
+                            wasm::ExternalKind::Table => {
+                                result.exported_tables.push(export.index);
+                            }
+                            wasm::ExternalKind::Func => {
+                                result
+                                    .func_exports
+                                    .push((export.name.to_string(), export.index.into()));
+                                result.name_entry(
+                                    export.index.into(),
+                                    EntryKind::Export(export.name.to_string()),
+                                );
+                            }
+                            _ => {}
+                        }
+                    }
                 }
                 wasm::Payload::StartSection { func, range } => {
                     validator.start_section(func, &range)?;
+                    result.start_func = Some(func.into());
                 }
                 wasm::Payload::ElementSection(section) => {
                     validator.element_section(&section)?;
+                    for element in section {
+                        let element = element?;
+                        result.elem_segments.extend(ActiveElem::new(&element)?);
+                        match element.items {
+                            wasm::ElementItems::Functions(funcs) => {
+                                for func in funcs {
+                                    result.elem_funcs.push(func?.into());
+                                }
+                            }
+                            wasm::ElementItems::Expressions(_, exprs) => {
+                                for expr in exprs {
+                                    for op in expr?.get_operators_reader() {
+                                        if let wasm::Operator::RefFunc { function_index } = op? {
+                                            result.elem_funcs.push(function_index.into());
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
                 }
                 wasm::Payload::DataCountSection { count, range } => {
                     validator.data_count_section(count, &range)?;
                 }
                 wasm::Payload::DataSection(section) => {
                     validator.data_section(&section)?;
+                    for (index, data) in section.into_iter().enumerate() {
+                        if let Some(range) = DataRange::new(index as u32, &data?)? {
+                            result.data_ranges.push(range);
+                        }
+                    }
                 }
 
                 // Here we know how many functions we'll be receiving as
@@ -994,14 +3807,72 @@ impl Module {
                     size: _,
                 } => {
                     validator.code_section_start(count, &range)?;
+                    let types = validator.types(0).unwrap();
+                    for type_index in 0..types.core_type_count_in_module() {
+                        type_indices
+                            .entry(types.core_type_at_in_module(type_index))
+                            .or_insert(TypeIdx::from(type_index));
+                    }
                 }
                 wasm::Payload::CodeSectionEntry(body) => {
                     let func_to_validate = validator.code_section_entry(&body)?;
-                    let func = Func::decode(body, func_to_validate)?;
-                    result.funcs.push(func);
+                    // Shared, so that `LazyModule` can validate the body
+                    // again later.
+                    let func_to_validate = wasm::FuncToValidate {
+                        resources: Arc::new(func_to_validate.resources),
+                        index: func_to_validate.index,
+                        ty: func_to_validate.ty,
+                        features: func_to_validate.features,
+                    };
+                    if Some(func_bodies.len()) == result.max_funcs {
+                        result.omitted_funcs += 1;
+                        continue;
+                    }
+                    func_bodies.push((body, func_to_validate));
                 }
 
-                wasm::Payload::CustomSection(_) => { /* ... */ }
+                // Like the name section, these aren't worth failing over if
+                // they're malformed.
+                wasm::Payload::CustomSection(section) if section.name() == "sourceMappingURL" => {
+                    let mut reader = wasm::BinaryReader::new(section.data(), section.data_offset());
+                    if let Ok(url) = reader.read_string() {
+                        result.source_mapping_url = Some(url.to_string());
+                    }
+                }
+                wasm::Payload::CustomSection(section) if section.name() == "target_features" => {
+                    let _ = result.toolchain.read_target_features(&section);
+                }
+                wasm::Payload::CustomSection(section) => {
+                    if let wasm::KnownCustom::Producers(producers) = section.as_known() {
+                        let _ = result.toolchain.read_producers(producers);
+                    }
+                    if let wasm::KnownCustom::Name(names) = section.as_known() {
+                        for name in names {
+                            // A malformed name section isn't worth failing over.
+                            match name {
+                                Ok(wasm::Name::Global(names)) => {
+                                    for naming in names.clone().into_iter().flatten() {
+                                        result.name_global(naming.index.into(), naming.name);
+                                    }
+                                    result.names.read_debug_names(wasm::Name::Global(names));
+                                }
+                                Ok(wasm::Name::Function(names)) => {
+                                    for naming in names.into_iter().flatten() {
+                                        result
+                                            .debug_func_names
+                                            .insert(naming.index.into(), naming.name.to_string());
+                                        result.name_entry(
+                                            naming.index.into(),
+                                            EntryKind::Named(naming.name.to_string()),
+                                        );
+                                    }
+                                }
+                                Ok(name) => result.names.read_debug_names(name),
+                                Err(_) => {}
+                            }
+                        }
+                    }
+                }
 
                 // Once we've reached the end of a parser we either resume
                 // at the parent parser or the payload iterator is at its
@@ -1018,57 +3889,462 @@ impl Module {
             }
         }
 
-        result.optimize();
+        for name in &options.reachable_from {
+            let Some(func_index) = result.exported_func(name) else {
+                bail!("no function is exported as `{}`", name);
+            };
+            result.reachable_from.push(func_index);
+        }
 
+        let exports_picked: HashSet<FuncIdx> = (options.only.iter())
+            .flat_map(|only| &only.exports)
+            .filter_map(|name| result.exported_func(name))
+            .collect();
+        let picked = |func_index: FuncIdx| match &options.only {
+            Some(only) => {
+                only.ranges.iter().any(|funcs| funcs.contains(&func_index))
+                    || exports_picked.contains(&func_index)
+            }
+            None => true,
+        };
+        result.multi_memory = num_memories > 1;
+        if let Some(bodies) = bodies {
+            bodies.funcs = (func_bodies.iter())
+                .map(|(body, func_to_validate)| {
+                    let func_to_validate = wasm::FuncToValidate {
+                        resources: func_to_validate.resources.clone(),
+                        index: func_to_validate.index,
+                        ty: func_to_validate.ty,
+                        features: func_to_validate.features,
+                    };
+                    (body.range(), func_to_validate)
+                })
+                .collect();
+            bodies.type_indices = type_indices.clone();
+        }
+        let span = tracing::Span::current();
+        let funcs = install(pool.as_ref(), || {
+            (func_bodies.into_par_iter())
+                .map(|(body, func_to_validate)| {
+                    let _span =
+                        tracing::debug_span!(parent: &span, "func", index = func_to_validate.index)
+                            .entered();
+                    let body_range = body.range();
+                    let mut func = if picked(FuncIdx::from(func_to_validate.index)) {
+                        Func::decode(body, func_to_validate, source_map)?
+                    } else {
+                        Func::skip(body, func_to_validate)?
+                    };
+                    func.body_range = body_range;
+                    func.show_offsets = options.offsets;
+                    Ok(func)
+                })
+                .collect::<Vec<anyhow::Result<Func>>>()
+        });
+        let mut bodies = HashMap::new();
+        for func in funcs {
+            let mut func = func?;
+            func.remap_type_ids(&type_indices);
+            let original = *bodies
+                .entry((&buffer[func.body_range.clone()], func.ty.clone()))
+                .or_insert(func.index);
+            if original != func.index {
+                result.duplicates.insert(func.index, original);
+            }
+            result.funcs.push(func);
+        }
         Ok(result)
     }
 
-    fn optimize(&mut self) {
+    // Decompiles the functions `read` decoded, and works out what's
+    // printed with them.
+    pub(crate) fn finish(&mut self, buffer: &[u8], options: &DecodeOptions) -> anyhow::Result<()> {
+        let pool = thread_pool(options)?;
+        if let Some(symbol_map) = &options.symbol_map {
+            self.apply_symbol_map(symbol_map);
+        }
+        self.name_funcs(options.demangle);
+        if !self.quick {
+            self.optimize(options, pool.as_ref());
+            if options.freeze {
+                self.freeze();
+            }
+            self.resolve_indirect_calls(options.indirect_candidates);
+            let runs = |pass| !options.skip_passes.contains(&pass);
+            let num_funcs = self.num_func_imports + self.funcs.len() as u32;
+            let call_results: HashMap<FuncIdx, wasm::ValType> = (0..num_funcs)
+                .map(FuncIdx::from)
+                .filter_map(
+                    |func_index| match self.func_signature(func_index)?.results() {
+                        [ty] => Some((func_index, *ty)),
+                        _ => None,
+                    },
+                )
+                .collect();
+            let reserved: HashSet<&str> = (self.names.globals.values())
+                .chain(self.names.funcs.values())
+                .map(String::as_str)
+                .collect();
+            let span = tracing::Span::current();
+            install(pool.as_ref(), || {
+                self.funcs.par_iter_mut().for_each(|func| {
+                    let _span = tracing::debug_span!(parent: &span, "rewrite", func = %func.index)
+                        .entered();
+                    let dump = options.dumps(func.index);
+                    if let Some(min_depth) = options.flatten_selects {
+                        if !func.out_of_time() {
+                            func.flatten_selects(min_depth);
+                        }
+                    }
+                    let limited = options.max_expression_depth.is_some()
+                        || options.max_expression_width.is_some();
+                    if limited && !func.out_of_time() {
+                        func.limit_expressions(
+                            options.max_expression_depth,
+                            options.max_expression_width,
+                            &call_results,
+                        );
+                    }
+                    if runs(Pass::NameLocals) && !func.out_of_time() {
+                        func.run_pass(Pass::NameLocals, dump, Func::name_integer_locals);
+                    }
+                    if (options.split_locals || options.tidy_locals) && !func.out_of_time() {
+                        func.split_locals();
+                    }
+                    if options.tidy_locals && !func.out_of_time() {
+                        func.tidy_locals(&reserved);
+                    }
+                    if runs(Pass::RemoveUnusedLocals) && !func.out_of_time() {
+                        func.run_pass(Pass::RemoveUnusedLocals, dump, Func::remove_unused_locals);
+                    }
+                })
+            });
+            // Which global is the stack pointer is found from how the
+            // functions, rewritten above, use it.
+            self.name_stack_pointer();
+            let stack_pointer = self
+                .names
+                .globals
+                .iter()
+                .find(|(_, name)| *name == "__stack_pointer")
+                .map(|(global_index, _)| *global_index);
+            let frame_taken = (self.names.globals.values())
+                .chain(self.names.funcs.values())
+                .any(|name| name == "frame");
+            install(pool.as_ref(), || {
+                self.funcs.par_iter_mut().for_each(|func| {
+                    let _span =
+                        tracing::debug_span!(parent: &span, "structure", func = %func.index)
+                            .entered();
+                    let dump = options.dumps(func.index);
+                    let stack_pointer =
+                        stack_pointer.filter(|_| runs(Pass::StackFrame) && !func.out_of_time());
+                    if let Some(stack_pointer) = stack_pointer {
+                        func.run_pass(Pass::StackFrame, dump, |func| {
+                            func.find_stack_frame(stack_pointer, frame_taken)
+                        });
+                    }
+                    if runs(Pass::InferSignedness) && !func.out_of_time() {
+                        func.run_pass(Pass::InferSignedness, dump, Func::infer_signedness);
+                    }
+                    if runs(Pass::Structuring) && !func.out_of_time() {
+                        func.structure = func.run_pass(Pass::Structuring, dump, |func| {
+                            func.structure_control_flow()
+                        });
+                        if func.structure.is_none() {
+                            func.out_of_time();
+                        }
+                    }
+                })
+            });
+        }
+        if options.interleave {
+            let listing = Listing::new(buffer)?;
+            for func in &mut self.funcs {
+                func.interleave_instructions(&listing);
+            }
+        }
+        let names = Arc::new(self.names.clone());
+        let mut exports: HashMap<FuncIdx, Vec<String>> = HashMap::new();
+        for (name, func_index) in &self.func_exports {
+            exports.entry(*func_index).or_default().push(name.clone());
+        }
         for func in &mut self.funcs {
-            func.optimize();
+            func.exports = exports.remove(&func.index).unwrap_or_default();
+            func.is_start = self.start_func == Some(func.index);
+            func.names = names.clone();
+            func.multi_memory = self.multi_memory;
+            func.data_ranges = self.data_ranges.clone();
         }
+        Ok(())
     }
 
-    pub fn write(&self, mut output: impl std::io::Write) -> anyhow::Result<()> {
-        self.pretty::<_, ()>(&pretty::BoxAllocator)
-            .render(80, &mut output)?;
-        writeln!(output)?;
-        Ok(())
+    /// The function at `func_index`, or `None` if it's imported or out of
+    /// range.
+    pub fn func(&self, func_index: FuncIdx) -> Option<&Func> {
+        let def_func_index = func_index.to_defined(self.num_func_imports)?;
+        self.funcs.get(def_func_index.as_usize())
+    }
+
+    /// The function exported as `name`.
+    pub fn exported_func(&self, name: &str) -> Option<FuncIdx> {
+        (self.func_exports.iter())
+            .find(|(export, _)| export == name)
+            .map(|(_, func_index)| *func_index)
+    }
+
+    /// The function named `name`: exported as it, or else given it by the
+    /// name section, or else printed with it. Imported functions are found
+    /// too.
+    pub fn func_index_of(&self, name: &str) -> Option<FuncIdx> {
+        let named = |names: &HashMap<FuncIdx, String>| {
+            (names.iter())
+                .filter(|(_, other)| *other == name)
+                .map(|(func_index, _)| *func_index)
+                .min()
+        };
+        (self.exported_func(name))
+            .or_else(|| named(&self.debug_func_names))
+            .or_else(|| named(&self.names.funcs))
+    }
+
+    /// The defined functions with a name `matches` accepts, either the
+    /// name they're printed with or one they're exported as.
+    pub fn find_funcs(&self, matches: impl Fn(&str) -> bool) -> Vec<FuncIdx> {
+        (self.funcs.iter())
+            .filter(|func| matches(&func.name()) || func.exports.iter().any(|name| matches(name)))
+            .map(|func| func.index)
+            .collect()
+    }
+
+    fn defined_func(&self, func_index: FuncIdx) -> anyhow::Result<&Func> {
+        let Some(def_func_index) = func_index.to_defined(self.num_func_imports) else {
+            bail!("cannot decompile an imported function");
+        };
+        match self.funcs.get(def_func_index.as_usize()) {
+            Some(func) => Ok(func),
+            None => bail!("too large of a function index"),
+        }
+    }
+
+    fn optimize(&mut self, options: &DecodeOptions, pool: Option<&rayon::ThreadPool>) {
+        // The functions are printed with their names in the dumps.
+        let names = (!options.dump_after.is_empty()).then(|| Arc::new(self.names.clone()));
+        let budget = self.func_time_budget;
+        let span = tracing::Span::current();
+        install(pool, || {
+            self.funcs.par_iter_mut().for_each(|func| {
+                let dump = options.dumps(func.index);
+                if let Some(names) = names.as_ref().filter(|_| !dump.is_empty()) {
+                    func.names = names.clone();
+                }
+                let _span =
+                    tracing::debug_span!(parent: &span, "optimize", func = %func.index).entered();
+                func.optimize(budget, &options.skip_passes, dump);
+            })
+        });
+    }
+
+    /// The text of the function at `func_index` after each of the passes in
+    /// [`DecodeOptions::dump_after`], in the order they ran.
+    pub fn pass_dumps(&self, func_index: FuncIdx) -> &[(Pass, String)] {
+        self.func(func_index)
+            .map_or(&[], |func| func.pass_dumps.as_slice())
+    }
+
+    /// The earlier function with the same type and body bytes as the one at
+    /// `func_index`, if there is one. With [`DecodeOptions::dedup`], such
+    /// functions are printed as aliases of it.
+    pub fn duplicate_of(&self, func_index: FuncIdx) -> Option<FuncIdx> {
+        self.duplicates.get(&func_index).copied()
+    }
+
+    /// The URL of the module's source map, from its `sourceMappingURL`
+    /// custom section.
+    pub fn source_mapping_url(&self) -> Option<&str> {
+        self.source_mapping_url.as_deref()
+    }
+
+    /// Errors for the functions that couldn't be decompiled. Those functions
+    /// are still printed, with their body replaced by the error. Functions
+    /// left out by [`DecodeOptions::only`] aren't errors.
+    pub fn errors(&self) -> impl Iterator<Item = &DecompileError> {
+        (self.funcs.iter())
+            .filter_map(|func| func.error.as_ref())
+            .filter(|error| !matches!(error, DecompileError::Skipped { .. }))
+    }
+
+    pub fn write(&self, output: impl std::io::Write) -> anyhow::Result<()> {
+        self.write_with_options(output, &RenderOptions::default())
+    }
+
+    pub fn write_with_options(
+        &self,
+        mut output: impl std::io::Write,
+        options: &RenderOptions,
+    ) -> anyhow::Result<()> {
+        self.write_module(&mut output, options)
+    }
+
+    /// The module as [`Module::write`] prints it.
+    pub fn render_to_string(&self) -> anyhow::Result<String> {
+        self.render_to_string_with_options(&RenderOptions::default())
+    }
+
+    pub fn render_to_string_with_options(&self, options: &RenderOptions) -> anyhow::Result<String> {
+        let mut output = Vec::new();
+        self.write_with_options(&mut output, options)?;
+        Ok(String::from_utf8(output)?)
+    }
+
+    /// Prints the module to `printer`, telling it where each keyword, name,
+    /// literal and so on starts, so that it can highlight them.
+    pub fn write_with(&self, printer: &mut impl DecompilePrint) -> anyhow::Result<()> {
+        self.write_module_with(printer)
     }
 
     pub fn write_func(
         &self,
-        func_index: u32,
+        func_index: FuncIdx,
+        output: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        self.write_func_with_options(func_index, output, &RenderOptions::default())
+    }
+
+    /// Like [`write_func`](Module::write_func), with the function picked by
+    /// name as [`Module::func_index_of`] finds it.
+    pub fn write_func_by_name(
+        &self,
+        name: &str,
+        output: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        let Some(func_index) = self.func_index_of(name) else {
+            bail!("no function is named `{}`", name);
+        };
+        self.write_func(func_index, output)
+    }
+
+    pub fn write_func_with_options(
+        &self,
+        func_index: FuncIdx,
         mut output: impl std::io::Write,
+        options: &RenderOptions,
     ) -> anyhow::Result<()> {
-        if func_index < self.num_func_imports {
-            bail!("cannot decompile an imported function");
-        }
-        let def_func_index = (func_index - self.num_func_imports) as usize;
-        if def_func_index >= self.funcs.len() {
-            bail!("too large of a function index");
-        }
-        self.funcs[def_func_index]
-            .pretty::<_, ()>(&pretty::BoxAllocator)
-            .render(80, &mut output)?;
+        let func = self.defined_func(func_index)?;
+        options.render(
+            func.pretty(options.indent, &pretty::BoxAllocator),
+            &mut output,
+        )
+    }
+
+    /// The function at `func_index` as [`Module::write_func`] prints it.
+    pub fn write_func_to_string(&self, func_index: FuncIdx) -> anyhow::Result<String> {
+        Ok(self.defined_func(func_index)?.to_string())
+    }
+
+    pub fn write_func_to_string_with_options(
+        &self,
+        func_index: FuncIdx,
+        options: &RenderOptions,
+    ) -> anyhow::Result<String> {
+        let mut output = Vec::new();
+        self.write_func_with_options(func_index, &mut output, options)?;
+        Ok(String::from_utf8(output)?)
+    }
+
+    pub fn write_func_graphviz(
+        &self,
+        func_index: FuncIdx,
+        output: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        self.write_func_graphviz_with_options(func_index, output, &GraphvizOptions::default())
+    }
+
+    pub fn write_func_graphviz_with_options(
+        &self,
+        func_index: FuncIdx,
+        mut output: impl std::io::Write,
+        options: &GraphvizOptions,
+    ) -> anyhow::Result<()> {
+        (self.defined_func(func_index)?).to_graphviz_with_options(&mut output, options)?;
         writeln!(output)?;
         Ok(())
     }
 
-    pub fn write_func_graphviz(
+    /// Like [`Module::write_func_graphviz`], but writes the function's
+    /// dominator tree instead of its control flow graph.
+    pub fn write_func_dominators_graphviz(
         &self,
-        func_index: u32,
+        func_index: FuncIdx,
+        output: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        self.write_func_dominators_graphviz_with_options(
+            func_index,
+            output,
+            &GraphvizOptions::default(),
+        )
+    }
+
+    pub fn write_func_dominators_graphviz_with_options(
+        &self,
+        func_index: FuncIdx,
         mut output: impl std::io::Write,
+        options: &GraphvizOptions,
     ) -> anyhow::Result<()> {
-        if func_index < self.num_func_imports {
-            bail!("cannot decompile an imported function");
-        }
-        let def_func_index = (func_index - self.num_func_imports) as usize;
-        if def_func_index >= self.funcs.len() {
-            bail!("too large of a function index");
-        }
-        self.funcs[def_func_index].to_graphviz(&mut output)?;
+        (self.defined_func(func_index)?)
+            .dominators_to_graphviz_with_options(&mut output, options)?;
         writeln!(output)?;
         Ok(())
     }
 }
+
+/// Prints the function as [`Module::write_func`] does, with the default
+/// [`RenderOptions`].
+impl std::fmt::Display for Func {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut text = Vec::new();
+        (RenderOptions::default())
+            .render(self.pretty(INDENT, &pretty::BoxAllocator), &mut text)
+            .map_err(|_| std::fmt::Error)?;
+        f.write_str(std::str::from_utf8(&text).map_err(|_| std::fmt::Error)?)
+    }
+}
+
+// Runs `run` on `pool`, or on rayon's global pool without one.
+fn thread_pool(options: &DecodeOptions) -> anyhow::Result<Option<rayon::ThreadPool>> {
+    match options.jobs {
+        Some(jobs) => Ok(Some(
+            rayon::ThreadPoolBuilder::new().num_threads(jobs).build()?,
+        )),
+        None => Ok(None),
+    }
+}
+
+fn install<T: Send>(pool: Option<&rayon::ThreadPool>, run: impl FnOnce() -> T + Send) -> T {
+    match pool {
+        Some(pool) => pool.install(run),
+        None => run(),
+    }
+}
+
+// What to call the section `payload` is from in its span.
+fn section_name(payload: &wasm::Payload) -> &'static str {
+    match payload {
+        wasm::Payload::Version { .. } => "header",
+        wasm::Payload::TypeSection(_) => "type",
+        wasm::Payload::ImportSection(_) => "import",
+        wasm::Payload::FunctionSection(_) => "function",
+        wasm::Payload::TableSection(_) => "table",
+        wasm::Payload::MemorySection(_) => "memory",
+        wasm::Payload::TagSection(_) => "tag",
+        wasm::Payload::GlobalSection(_) => "global",
+        wasm::Payload::ExportSection(_) => "export",
+        wasm::Payload::StartSection { .. } => "start",
+        wasm::Payload::ElementSection(_) => "element",
+        wasm::Payload::DataCountSection { .. } => "datacount",
+        wasm::Payload::DataSection(_) => "data",
+        wasm::Payload::CodeSectionStart { .. } | wasm::Payload::CodeSectionEntry(_) => "code",
+        wasm::Payload::CustomSection(_) => "custom",
+        _ => "other",
+    }
+}
@@ -0,0 +1,260 @@
+use crate::ir::*;
+
+/// Words that mean something in the printed syntax, so a function can't be
+/// named after them.
+const KEYWORDS: [&str; 31] = [
+    "module",
+    "func",
+    "alias",
+    "of",
+    "nop",
+    "unreachable",
+    "unknown",
+    "return",
+    "br",
+    "br_table",
+    "default",
+    "with",
+    "try",
+    "catch",
+    "catch_ref",
+    "catch_all",
+    "catch_all_ref",
+    "throw",
+    "rethrow",
+    "if",
+    "else",
+    "while",
+    "do",
+    "block",
+    "loop",
+    "break",
+    "continue",
+    "as",
+    "is",
+    "memory",
+    "null",
+];
+
+/// How a module's functions, globals, tables, memories and types are
+/// printed, for the ones that have a name. The rest are printed by index,
+/// e.g. `table0`.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Names {
+    /// Imported functions, named after their module and field, e.g.
+    /// `wasi_snapshot_preview1.fd_write`, and defined functions, named by
+    /// the name section or their export.
+    pub(crate) funcs: HashMap<FuncIdx, String>,
+    /// Well-known globals are printed as themselves, e.g.
+    /// `__stack_pointer`, and others named by the name section as `$name`.
+    pub(crate) globals: HashMap<GlobalIdx, String>,
+    tables: HashMap<u32, String>,
+    memories: HashMap<u32, String>,
+    types: HashMap<TypeIdx, String>,
+    /// Data segments, which are only named in comments, e.g. `.rodata` as
+    /// LLVM names them.
+    data: HashMap<u32, String>,
+}
+
+// `name` with anything that can't be part of a name replaced by `_`. Paths
+// like `core::fmt::write` keep their `::`.
+fn sanitize(name: &str) -> String {
+    let parts: Vec<String> = name
+        .split("::")
+        .map(|part| {
+            if part.is_empty() {
+                return "_".to_string();
+            }
+            part.chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || "_$.".contains(c) {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    parts.join("::")
+}
+
+// A Rust or C++ symbol demangled without its hash or params, or `None` if
+// it isn't mangled.
+fn demangled(name: &str) -> Option<String> {
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        return Some(format!("{:#}", demangled));
+    }
+    if !name.starts_with("_Z") {
+        return None;
+    }
+    let symbol = cpp_demangle::Symbol::new(name).ok()?;
+    let options = cpp_demangle::DemangleOptions::new()
+        .no_params()
+        .no_return_type();
+    symbol.demangle(&options).ok()
+}
+
+// A name from the name section as it's printed, `$` followed by the
+// sanitized name.
+pub(crate) fn debug_name(name: &str) -> Option<String> {
+    if name.is_empty() {
+        return None;
+    }
+    Some(format!("${}", sanitize(name)))
+}
+
+// A function's name as it's printed, or `None` if it would read as a keyword
+// or as another function's index.
+fn printable_func_name(name: &str) -> Option<String> {
+    let mut name = sanitize(name);
+    if name.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        name.insert(0, '_');
+    }
+    let is_index = name
+        .strip_prefix("func")
+        .is_some_and(|index| index.parse::<u32>().is_ok());
+    if name.is_empty() || is_index || KEYWORDS.contains(&name.as_str()) {
+        return None;
+    }
+    Some(name)
+}
+
+// Names `index` unless it or the name is already taken. Two things with the
+// same name are told apart by printing the second by index.
+fn insert<K: Eq + Hash>(names: &mut HashMap<K, String>, index: K, name: &str) {
+    let Some(name) = debug_name(name) else {
+        return;
+    };
+    if names.contains_key(&index) || names.values().any(|other| *other == name) {
+        return;
+    }
+    names.insert(index, name);
+}
+
+impl Names {
+    // Names the globals, tables, memories, types and data segments named by one
+    // subsection of the name section. Globals with a well-known name are
+    // named by `Module::name_global` first.
+    pub(crate) fn read_debug_names(&mut self, name: wasm::Name) {
+        match name {
+            wasm::Name::Global(names) => {
+                for naming in names.into_iter().flatten() {
+                    insert(&mut self.globals, naming.index.into(), naming.name);
+                }
+            }
+            wasm::Name::Table(names) => {
+                for naming in names.into_iter().flatten() {
+                    insert(&mut self.tables, naming.index, naming.name);
+                }
+            }
+            wasm::Name::Memory(names) => {
+                for naming in names.into_iter().flatten() {
+                    insert(&mut self.memories, naming.index, naming.name);
+                }
+            }
+            wasm::Name::Type(names) => {
+                for naming in names.into_iter().flatten() {
+                    insert(&mut self.types, naming.index.into(), naming.name);
+                }
+            }
+            wasm::Name::Data(names) => {
+                for naming in names.into_iter().flatten() {
+                    if !naming.name.is_empty() {
+                        self.data.insert(naming.index, sanitize(naming.name));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn insert_global(&mut self, global_index: GlobalIdx, name: &str) {
+        insert(&mut self.globals, global_index, name);
+    }
+
+    pub(crate) fn func(&self, func_index: FuncIdx) -> Option<&str> {
+        self.funcs.get(&func_index).map(String::as_str)
+    }
+
+    /// e.g. `malloc` or `func3`
+    pub(crate) fn func_name(&self, func_index: FuncIdx) -> String {
+        match self.func(func_index) {
+            Some(name) => name.to_string(),
+            None => format!("func{}", func_index),
+        }
+    }
+
+    pub(crate) fn global(&self, global_index: GlobalIdx) -> Option<&str> {
+        self.globals.get(&global_index).map(String::as_str)
+    }
+
+    /// e.g. `$__indirect_function_table` or `table0`
+    pub(crate) fn table(&self, table: u32) -> String {
+        match self.tables.get(&table) {
+            Some(name) => name.clone(),
+            None => format!("table{}", table),
+        }
+    }
+
+    /// e.g. `$heap` or `memory1`
+    pub(crate) fn memory(&self, memory: u32) -> String {
+        match self.memories.get(&memory) {
+            Some(name) => name.clone(),
+            None => format!("memory{}", memory),
+        }
+    }
+
+    /// e.g. `.rodata` or `data[2]`
+    pub(crate) fn data_segment(&self, segment: u32) -> String {
+        match self.data.get(&segment) {
+            Some(name) => name.clone(),
+            None => format!("data[{}]", segment),
+        }
+    }
+
+    /// e.g. `$point` or `type0`
+    pub(crate) fn type_name(&self, type_index: TypeIdx) -> String {
+        match self.types.get(&type_index) {
+            Some(name) => name.clone(),
+            None => format!("type{}", type_index),
+        }
+    }
+}
+
+impl Module {
+    // Names each imported function after its module and field, and each
+    // defined function after its name in the name section, or without one,
+    // after its first export, demangled if `demangle` is set. A name that's
+    // taken by an earlier function is left out.
+    pub(crate) fn name_funcs(&mut self, demangle: bool) {
+        for (i, import) in self.func_imports.iter().enumerate() {
+            let Some(name) = printable_func_name(&import.qualified_name()) else {
+                continue;
+            };
+            if !self.names.funcs.values().any(|other| *other == name) {
+                self.names.funcs.insert(FuncIdx::from(i as u32), name);
+            }
+        }
+        let mut export_names = HashMap::new();
+        for (name, func_index) in &self.func_exports {
+            export_names.entry(*func_index).or_insert(name);
+        }
+        for func in &self.funcs {
+            let name = self
+                .debug_func_names
+                .get(&func.index)
+                .or_else(|| export_names.get(&func.index).copied())
+                .and_then(|name| match demangle.then(|| demangled(name)).flatten() {
+                    Some(name) => printable_func_name(&name),
+                    None => printable_func_name(name),
+                });
+            let Some(name) = name else {
+                continue;
+            };
+            if !self.names.funcs.values().any(|other| *other == name) {
+                self.names.funcs.insert(func.index, name);
+            }
+        }
+    }
+}
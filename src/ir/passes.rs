@@ -1,9 +1,90 @@
 use std::collections::HashSet;
 
+use crate::ir::effects::Effects;
 use crate::ir::*;
 
+/// One of the passes each function is optimized with after it's decoded,
+/// in the order they run. [`DecodeOptions::skip_passes`] turns them off,
+/// to see what the function looks like without them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pass {
+    /// Fold arithmetic on constants and branches on them.
+    FoldConstants,
+    /// Fold temps into the one statement that reads them.
+    ForwardTemps,
+    /// Merge blocks into ifs, loops and `&&` and `||` conditions.
+    MergeBlocks,
+    /// Branch straight to where a block that only branches goes.
+    JumpThreading,
+    /// Turn branches whose targets are all the same into a `br`, and
+    /// branches to a `return` into one.
+    SimplifyTerminators,
+    /// Print the multiplications and shifts compilers divide by a constant
+    /// with as the division.
+    RecoverDivisions,
+    /// Flip negated comparisons and empty then branches.
+    NormalizeConditions,
+    /// Drop blocks that nothing branches to.
+    DeadCode,
+    /// Name integer locals by what they're used for, e.g. `ptr3` for one
+    /// used as a memory address.
+    NameLocals,
+    /// Drop the locals that are never read or written.
+    RemoveUnusedLocals,
+    /// Print the slots of a function's shadow stack frame as `frame.var_8`.
+    StackFrame,
+    /// Declare integer locals `u32` or `i32` by the operators applied to
+    /// them, and drop the `_u` and `_s` those operators then don't need.
+    InferSignedness,
+    /// Nest blocks inside the ifs and loops they belong to, instead of
+    /// printing them one after another as labels.
+    Structuring,
+}
+
+impl Pass {
+    pub const ALL: [Pass; 13] = [
+        Pass::FoldConstants,
+        Pass::ForwardTemps,
+        Pass::MergeBlocks,
+        Pass::JumpThreading,
+        Pass::SimplifyTerminators,
+        Pass::RecoverDivisions,
+        Pass::NormalizeConditions,
+        Pass::DeadCode,
+        Pass::NameLocals,
+        Pass::RemoveUnusedLocals,
+        Pass::StackFrame,
+        Pass::InferSignedness,
+        Pass::Structuring,
+    ];
+
+    /// The name the pass goes by on the command line, such as
+    /// `jump-threading`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::FoldConstants => "fold-constants",
+            Pass::ForwardTemps => "forward-temps",
+            Pass::MergeBlocks => "merge-blocks",
+            Pass::JumpThreading => "jump-threading",
+            Pass::SimplifyTerminators => "simplify-terminators",
+            Pass::RecoverDivisions => "recover-divisions",
+            Pass::NormalizeConditions => "normalize-conditions",
+            Pass::DeadCode => "dead-code",
+            Pass::NameLocals => "name-locals",
+            Pass::RemoveUnusedLocals => "remove-unused-locals",
+            Pass::StackFrame => "stack-frame",
+            Pass::InferSignedness => "infer-signedness",
+            Pass::Structuring => "structuring",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Pass> {
+        Pass::ALL.into_iter().find(|pass| pass.name() == name)
+    }
+}
+
 impl Func {
-    pub fn jump_threading(&mut self) {
+    pub(crate) fn jump_threading(&mut self) {
         let mut trivial_blocks = HashMap::new();
 
         for (block_index, block) in self.blocks.iter() {
@@ -19,7 +100,146 @@ impl Func {
         }
     }
 
-    fn get_all_predecessors(&mut self) -> HashMap<BlockIndex, Vec<BlockIndex>> {
+    // Jump threading leaves some terminators in a roundabout shape: a
+    // `br_if` or `br_table` whose targets are all the same becomes a `br`,
+    // and a `br` to a block that does nothing but return becomes a `return`.
+    pub(crate) fn simplify_terminators(&mut self) -> bool {
+        let mut changed = false;
+        let return_blocks: HashMap<BlockIndex, Vec<Expression>> = self
+            .blocks
+            .iter()
+            .filter_map(|(block_index, block)| match &block.terminator {
+                Terminator::Return(values)
+                    if block.statements.iter().all(Statement::is_location) =>
+                {
+                    Some((*block_index, values.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        for block in self.blocks.values_mut() {
+            let terminator = std::mem::replace(&mut block.terminator, Terminator::Unknown);
+            block.terminator = match terminator {
+                Terminator::BrIf(condition, true_block, false_block, params)
+                    if true_block == false_block =>
+                {
+                    // The condition is evaluated after the params, so it
+                    // can only go first if it commutes with each of them.
+                    if condition.is_pure() {
+                        changed = true;
+                        Terminator::Br(true_block, params)
+                    } else if commutes_with_all(&condition, &params) {
+                        changed = true;
+                        block.statements.push(Statement::Drop(condition));
+                        Terminator::Br(true_block, params)
+                    } else {
+                        Terminator::BrIf(condition, true_block, false_block, params)
+                    }
+                }
+                Terminator::BrTable(index, targets, default_target, params)
+                    if targets.iter().all(|target| *target == default_target) =>
+                {
+                    // Likewise for the index.
+                    if index.is_pure() {
+                        changed = true;
+                        Terminator::Br(default_target, params)
+                    } else if commutes_with_all(&index, &params) {
+                        changed = true;
+                        block.statements.push(Statement::Drop(index));
+                        Terminator::Br(default_target, params)
+                    } else {
+                        Terminator::BrTable(index, targets, default_target, params)
+                    }
+                }
+                terminator => terminator,
+            };
+
+            if let Terminator::Br(target, params) = &mut block.terminator {
+                if let Some(values) = return_blocks.get(target) {
+                    if let Some(values) = forward_return(values, params) {
+                        block.terminator = Terminator::Return(values);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    // Folds `tempN = value` into the statement right after it, or the
+    // block's terminator, when that's the one place tempN is read, e.g.
+    // `temp0 = f(x); *(p) = temp0` becomes `*(p) = f(x)`. The value then
+    // runs after whatever the statement evaluates before the read, so their
+    // effects have to commute: `f(x)` can move past `p + 4` but not past a
+    // load, which could see what it stores or trap first.
+    pub(crate) fn forward_temps(&mut self) {
+        let mut reads = HashMap::new();
+        let mut writes = HashMap::new();
+        for block in self.blocks.values() {
+            count_local_accesses(&block.statements, &mut reads, &mut writes);
+            for expr in block.terminator.expressions() {
+                count_local_reads(expr, &mut reads);
+            }
+        }
+        let forwardable: HashSet<LocalIdx> = (0..self.locals.len())
+            .map(|i| LocalIdx::from(i as u32))
+            .filter(|local_index| {
+                self.local(*local_index).name.starts_with("temp")
+                    && reads.get(local_index) == Some(&1)
+                    && writes.get(local_index) == Some(&1)
+            })
+            .collect();
+        if forwardable.is_empty() {
+            return;
+        }
+        for block in self.blocks.values_mut() {
+            forward_temps_in(
+                &mut block.statements,
+                Some(&mut block.terminator),
+                &forwardable,
+            );
+        }
+    }
+
+    // Assignments of a chain of selects at least `min_depth` deep become
+    // if/else statements, one per select. A select evaluates both of its
+    // values and an if only one, so only chains of pure values are
+    // converted.
+    pub(crate) fn flatten_selects(&mut self, min_depth: usize) {
+        fn statement(stmt: &mut Statement, min_depth: usize) {
+            if matches!(stmt, Statement::If(_) | Statement::Loop(_)) {
+                for stmt in stmt.bodies_mut().into_iter().flatten() {
+                    statement(stmt, min_depth);
+                }
+                return;
+            }
+            let Some(value) = assigned_value(stmt) else {
+                return;
+            };
+            let Expression::Select(select) = &*value else {
+                return;
+            };
+            if select_depth(value) < min_depth
+                || !select.on_true.is_pure()
+                || !select.on_false.is_pure()
+            {
+                return;
+            }
+            *stmt = split_select(std::mem::replace(stmt, Statement::Nop));
+        }
+
+        for block in self.blocks.values_mut() {
+            for stmt in &mut block.statements {
+                statement(stmt, min_depth);
+            }
+        }
+    }
+
+    /// The predecessors of every block that has any, in no particular order.
+    /// Blocks are listed once per edge, so a block that branches twice to the
+    /// same target appears twice.
+    pub fn predecessors(&self) -> HashMap<BlockIndex, Vec<BlockIndex>> {
         let mut predecessors = HashMap::new();
         for (block_index, block) in self.blocks.iter() {
             for successor in block.successors() {
@@ -33,17 +253,18 @@ impl Func {
     // A -> B, A has only one successor and B has only one predecessor. No branch parameters
     fn merge_trivial_branch_blocks(&mut self) -> bool {
         let mut changed = false;
-        for (block_index, predecessors) in self.get_all_predecessors() {
+        for (block_index, predecessors) in self.predecessors() {
             if predecessors.len() != 1 {
                 continue;
             }
 
             let predecessor = self.blocks.get_mut(&predecessors[0]).unwrap();
-            let predecessor_successors = predecessor.successors();
-            if predecessor_successors.len() != 1 {
+            // A br_table with only a default target also has a single
+            // successor, but only a plain br can be folded away.
+            if !matches!(predecessor.terminator, Terminator::Br(..)) {
                 continue;
             }
-            assert_eq!(predecessor_successors[0], block_index);
+            debug_assert_eq!(predecessor.successors()[0], block_index);
 
             let block = self.blocks.get_mut(&block_index).unwrap();
             if !block.params.is_empty() {
@@ -54,15 +275,97 @@ impl Func {
             // Merge all of block into predecessor
             let block_statements = std::mem::take(&mut block.statements);
             let block_terminator = std::mem::replace(&mut block.terminator, Terminator::Unknown);
+            let block_terminator_offsets = block.terminator_offsets.take();
             let predecessor = self.blocks.get_mut(&predecessors[0]).unwrap();
             predecessor.statements.extend(block_statements);
-            assert!(matches!(predecessor.terminator, Terminator::Br(..)));
             predecessor.terminator = block_terminator;
+            // A terminator no instruction stands for, such as the function's
+            // return, takes the offsets of the branch it replaces.
+            if block_terminator_offsets.is_some() {
+                predecessor.terminator_offsets = block_terminator_offsets;
+            }
             changed = true;
         }
         changed
     }
 
+    // A block B that only tests a condition and that nothing but A
+    // branches to, where A's other successor X is one of B's:
+    //
+    //   A          A
+    //   |\         |\
+    //   | B        | B
+    //   |/ \       |/ \
+    //   X   T      X   F
+    //
+    // The first is `a && b` and the second `a || b`, with either side
+    // negated for the other ways round. A branches on that and B goes.
+    fn merge_short_circuits(&mut self) -> bool {
+        let mut changed = false;
+        let predecessor_map = self.predecessors();
+        // Outermost first, so `a || b || c` comes out as `(a || b) || c`
+        // whatever order the blocks are in.
+        for index_a in self.reverse_post_order() {
+            let Terminator::BrIf(a, index_t, index_f, params) = &self.blocks[&index_a].terminator
+            else {
+                continue;
+            };
+            if !params.is_empty() || index_t == index_f {
+                continue;
+            }
+            let is_test = |index_b: &BlockIndex| {
+                let block_b = &self.blocks[index_b];
+                *index_b != index_a
+                    && block_b.statements.iter().all(Statement::is_location)
+                    && block_b.params.is_empty()
+                    && predecessor_map[index_b] == [index_a]
+                    && matches!(&block_b.terminator, Terminator::BrIf(_, _, _, params) if params.is_empty())
+            };
+            // B, A's other successor, and whether A goes to B when `a` is
+            // true.
+            let (index_b, index_x, and) = if is_test(index_t) {
+                (*index_t, *index_f, true)
+            } else if is_test(index_f) {
+                (*index_f, *index_t, false)
+            } else {
+                continue;
+            };
+            let Terminator::BrIf(b, index_bt, index_bf, _) = &self.blocks[&index_b].terminator
+            else {
+                unreachable!()
+            };
+            // Whether B goes to X when `b` is true, and its other successor.
+            let (b_to_x, index_y) = if *index_bt == index_x {
+                (true, *index_bf)
+            } else if *index_bf == index_x {
+                (false, *index_bt)
+            } else {
+                continue;
+            };
+            if index_y == index_x || index_y == index_b {
+                continue;
+            }
+
+            changed = true;
+            let (a, b) = (Box::new(a.clone()), b.clone());
+            // `a && b` goes to Y and `a || b` to X; `b` is negated if B goes
+            // the other way.
+            let terminator = if and {
+                let b = if b_to_x { negate(b) } else { b };
+                let condition = Expression::Logical(LogicalExpression::And, a, Box::new(b));
+                Terminator::BrIf(condition, index_y, index_x, vec![])
+            } else {
+                let b = if b_to_x { b } else { negate(b) };
+                let condition = Expression::Logical(LogicalExpression::Or, a, Box::new(b));
+                Terminator::BrIf(condition, index_x, index_y, vec![])
+            };
+            self.blocks.get_mut(&index_a).unwrap().terminator = terminator;
+            // B is dead now, left for dead code elimination.
+            self.blocks.get_mut(&index_b).unwrap().terminator = Terminator::Unknown;
+        }
+        changed
+    }
+
     //   A
     //  / \
     // B   C
@@ -77,7 +380,7 @@ impl Func {
     // A jumps to D
     fn merge_if_blocks(&mut self) -> bool {
         let mut changed = false;
-        let predecessor_map = self.get_all_predecessors();
+        let predecessor_map = self.predecessors();
         let keys: Vec<BlockIndex> = self.blocks.keys().cloned().collect();
         for index_a in keys {
             let block_a = self.blocks.get(&index_a).unwrap();
@@ -94,8 +397,8 @@ impl Func {
                     if predecessor_map[index_b].len() != 1 || predecessor_map[index_c].len() != 1 {
                         continue;
                     }
-                    assert_eq!(predecessor_map[index_b][0], index_a);
-                    assert_eq!(predecessor_map[index_c][0], index_a);
+                    debug_assert_eq!(predecessor_map[index_b][0], index_a);
+                    debug_assert_eq!(predecessor_map[index_c][0], index_a);
 
                     let successors_b = block_b.successors();
                     let successors_c = block_c.successors();
@@ -107,38 +410,27 @@ impl Func {
                     let successor_b = successors_b.first();
                     let successor_c = successors_c.first();
 
+                    // An if statement can't return, so both sides have to
+                    // carry on to D, without passing it anything.
                     let index_d = match (successor_b, successor_c) {
-                        (Some(x), Some(y)) if *x == *y => Some(*x),
-                        (Some(x), Some(y)) if *x != *y => continue,
-                        (Some(x), None) => Some(*x),
-                        (None, Some(y)) => Some(*y),
-                        _ => None,
+                        (Some(x), Some(y)) if *x == *y => *x,
+                        _ => continue,
                     };
+                    if !matches!(block_b.terminator, Terminator::Br(_, ref values) if values.is_empty())
+                        || !matches!(block_c.terminator, Terminator::Br(_, ref values) if values.is_empty())
+                    {
+                        continue;
+                    }
 
-                    if let Some(index_d) = index_d {
-                        let block_d = &self.blocks[&index_d];
-                        if !block_d.params.is_empty() {
-                            continue;
-                        }
-                        let predecessors_d = &predecessor_map[&index_d];
-                        for predecessor in predecessors_d {
-                            if *predecessor != *index_b || *predecessor != *index_c {
-                                continue;
-                            }
-                        }
+                    if !self.blocks[&index_d].params.is_empty() {
+                        continue;
                     }
 
                     // Do it!
                     changed = true;
 
                     let statements_b = block_b.statements.clone();
-                    let _terminator_b = block_b.terminator.clone();
-                    // TODO: add some terminators as statements
-                    // match terminator_b {
-                    //     Terminator::Return()
-                    // }
                     let statements_c = block_c.statements.clone();
-                    let _terminator_c = block_c.terminator.clone();
 
                     let if_statement = IfStatement {
                         condition: Box::new(condition.clone()),
@@ -147,9 +439,8 @@ impl Func {
                     };
 
                     let block_a = self.blocks.get_mut(&index_a).unwrap();
-                    block_a.terminator = index_d
-                        .map(|x| Terminator::Br(x, vec![]))
-                        .unwrap_or(Terminator::Unreachable);
+                    block_a.terminator = Terminator::Br(index_d, vec![]);
+                    block_a.terminator_offsets = None;
                     block_a.statements.push(Statement::If(if_statement));
                 }
                 _ => continue,
@@ -158,15 +449,98 @@ impl Func {
         changed
     }
 
-    pub fn reconstruct_control_flow(&mut self) {
+    // A block H that branches back to itself is a loop, and so is one that
+    // only tests a condition, where one side is a block B that nothing else
+    // branches to and that branches straight back:
+    //
+    //   H <-+      H <-+
+    //   |\__|      |\  |
+    //   X          X  B
+    //
+    // The first becomes `do { H } while (c)` and the second
+    // `while (c) { B }`, after which H branches to X.
+    fn merge_loops(&mut self) -> bool {
+        let mut changed = false;
+        let predecessor_map = self.predecessors();
+        let keys: Vec<BlockIndex> = self.blocks.keys().cloned().collect();
+        for index_h in keys {
+            let block_h = &self.blocks[&index_h];
+            let Terminator::BrIf(condition, index_t, index_f, params) = &block_h.terminator else {
+                continue;
+            };
+            if !params.is_empty() || !block_h.params.is_empty() || index_t == index_f {
+                continue;
+            }
+
+            let is_body = |index_b: BlockIndex| {
+                let block_b = &self.blocks[&index_b];
+                index_b == index_h
+                    || (block_h.statements.iter().all(Statement::is_location)
+                        && block_b.params.is_empty()
+                        && predecessor_map[&index_b] == [index_h]
+                        && matches!(&block_b.terminator, Terminator::Br(target, values) if *target == index_h && values.is_empty()))
+            };
+            // The block that runs again when the condition is true, or
+            // false if `negated`, and the block the loop exits to.
+            let (index_body, index_x, negated) = if is_body(*index_t) {
+                (*index_t, *index_f, false)
+            } else if is_body(*index_f) {
+                (*index_f, *index_t, true)
+            } else {
+                continue;
+            };
+            let kind = if index_body == index_h {
+                LoopKind::DoWhile
+            } else {
+                LoopKind::While
+            };
+
+            changed = true;
+            let condition = match negated {
+                true => negate(condition.clone()),
+                false => condition.clone(),
+            };
+            let body = match kind {
+                LoopKind::DoWhile => {
+                    std::mem::take(&mut self.blocks.get_mut(&index_h).unwrap().statements)
+                }
+                LoopKind::While => {
+                    let block_body = self.blocks.get_mut(&index_body).unwrap();
+                    block_body.terminator = Terminator::Unknown;
+                    std::mem::take(&mut block_body.statements)
+                }
+            };
+            let block_h = self.blocks.get_mut(&index_h).unwrap();
+            block_h.statements.push(Statement::Loop(LoopStatement {
+                kind,
+                condition: Box::new(condition),
+                body,
+            }));
+            block_h.terminator = Terminator::Br(index_x, vec![]);
+            block_h.terminator_offsets = None;
+        }
+        changed
+    }
+
+    // Returns false if it stopped at the deadline before it was done. Each
+    // round of merging leaves the function valid, so it can stop anywhere.
+    pub(crate) fn reconstruct_control_flow(&mut self, deadline: Option<Instant>) -> bool {
         self.eliminate_dead_code();
 
-        while self.merge_trivial_branch_blocks() || self.merge_if_blocks() {
+        while self.merge_trivial_branch_blocks()
+            || self.merge_short_circuits()
+            || self.merge_if_blocks()
+            || self.merge_loops()
+        {
             self.eliminate_dead_code();
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return false;
+            }
         }
+        true
     }
 
-    pub fn eliminate_dead_code(&mut self) {
+    pub(crate) fn eliminate_dead_code(&mut self) {
         let mut stack: Vec<BlockIndex> = Vec::new();
         let mut alive: HashSet<BlockIndex> = HashSet::new();
 
@@ -186,8 +560,8 @@ impl Func {
         self.blocks.retain(|node, _block| alive.contains(node));
     }
 
-    pub fn renumber(&mut self) {
-        let rpo = self.rpo();
+    pub(crate) fn renumber(&mut self) {
+        let rpo = self.reverse_post_order();
 
         let mut mapping = HashMap::new();
         for (rpo_index, old_index) in rpo.iter().enumerate() {
@@ -197,31 +571,397 @@ impl Func {
         self.remap_block_indices(&mapping);
     }
 
-    fn rpo(&self) -> Vec<BlockIndex> {
-        let mut visited = HashSet::new();
+    /// The blocks reachable from the entry block, in reverse post-order.
+    pub fn reverse_post_order(&self) -> Vec<BlockIndex> {
         let mut po = Vec::new();
-        self.po_recursive(self.entry_block, &mut visited, &mut po);
+        if !self.blocks.contains_key(&self.entry_block) {
+            return po;
+        }
+
+        // Iterative depth-first search, visiting successors in order. Each
+        // stack entry is a block and the index of its next successor to visit.
+        let mut visited = HashSet::new();
+        let mut stack = vec![(self.entry_block, 0)];
+        visited.insert(self.entry_block);
+        while let Some((current, next_successor)) = stack.last_mut() {
+            let successors = self.successors(*current);
+            if let Some(successor) = successors.get(*next_successor) {
+                *next_successor += 1;
+                if visited.insert(*successor) {
+                    stack.push((*successor, 0));
+                }
+            } else {
+                po.push(*current);
+                stack.pop();
+            }
+        }
+
         po.reverse();
         po
     }
 
-    // Naive recursive implementation, replace with iterative algorithm eventually.
-    fn po_recursive(
-        &self,
-        current: BlockIndex,
-        visited: &mut HashSet<BlockIndex>,
-        po: &mut Vec<BlockIndex>,
-    ) {
-        if visited.contains(&current) {
-            return;
+    // Merges the offsets of a statement that's gone, folded into a later
+    // one or removed, into the later statement's, so that its range takes
+    // in the instructions of both.
+    pub(crate) fn drop_stale_offsets(&mut self) {
+        fn statements(stmts: &mut Vec<Statement>) {
+            for stmt in stmts.iter_mut() {
+                for body in stmt.bodies_mut() {
+                    statements(body);
+                }
+            }
+            // The index of a later offset that comes before the next
+            // statement.
+            let mut superseding = None;
+            let mut i = stmts.len();
+            while i > 0 {
+                i -= 1;
+                match (&stmts[i], superseding) {
+                    (Statement::Location(SourceLocation::Offset(_)), Some(j)) => {
+                        let Statement::Location(SourceLocation::Offset(stale)) = stmts.remove(i)
+                        else {
+                            unreachable!()
+                        };
+                        // Removing it moves the later one down by one.
+                        if let Statement::Location(SourceLocation::Offset(offsets)) =
+                            &mut stmts[j - 1]
+                        {
+                            offsets.start = offsets.start.min(stale.start);
+                            offsets.end = offsets.end.max(stale.end);
+                        }
+                        superseding = Some(j - 1);
+                    }
+                    (Statement::Location(SourceLocation::Offset(_)), None) => superseding = Some(i),
+                    (Statement::Location(_), _) => {}
+                    _ => superseding = None,
+                }
+            }
         }
-        visited.insert(current);
 
-        let successors = self.blocks.get(&current).unwrap().successors();
-        for successor in successors {
-            self.po_recursive(successor, visited, po);
+        for block in self.blocks.values_mut() {
+            statements(&mut block.statements);
         }
+    }
+}
 
-        po.push(current);
+pub(crate) fn count_local_reads(expr: &Expression, reads: &mut HashMap<LocalIdx, usize>) {
+    match expr {
+        Expression::GetLocal(get) => *reads.entry(get.local_index).or_default() += 1,
+        Expression::GetLocalN(get) => {
+            for local_index in &get.local_indices {
+                *reads.entry(*local_index).or_default() += 1;
+            }
+        }
+        _ => {}
+    }
+    for operand in expr.operands() {
+        count_local_reads(operand, reads);
+    }
+}
+
+fn reads_local(expr: &Expression, local_index: LocalIdx) -> bool {
+    let mut reads = HashMap::new();
+    count_local_reads(expr, &mut reads);
+    reads.contains_key(&local_index)
+}
+
+pub(crate) fn count_local_accesses(
+    statements: &[Statement],
+    reads: &mut HashMap<LocalIdx, usize>,
+    writes: &mut HashMap<LocalIdx, usize>,
+) {
+    for stmt in statements {
+        match stmt {
+            Statement::LocalSet(stmt) => *writes.entry(stmt.index).or_default() += 1,
+            Statement::LocalSetN(stmt) => {
+                for local_index in &stmt.index {
+                    *writes.entry(*local_index).or_default() += 1;
+                }
+            }
+            _ => {}
+        }
+        for expr in stmt.expressions() {
+            count_local_reads(expr, reads);
+        }
+        for body in stmt.bodies() {
+            count_local_accesses(body, reads, writes);
+        }
+    }
+}
+
+// The temp a statement assigns a single value to, if it's one of
+// `forwardable`.
+fn forwardable_temp(stmt: &Statement, forwardable: &HashSet<LocalIdx>) -> Option<LocalIdx> {
+    let local_index = match stmt {
+        Statement::LocalSet(stmt) => stmt.index,
+        Statement::LocalSetN(stmt) if stmt.index.len() == 1 => stmt.index[0],
+        _ => return None,
+    };
+    forwardable.contains(&local_index).then_some(local_index)
+}
+
+// Last first, so that a temp read by the assignment of another that's
+// folded into the statement after it is then next to that statement.
+fn forward_temps_in(
+    statements: &mut Vec<Statement>,
+    mut terminator: Option<&mut Terminator>,
+    forwardable: &HashSet<LocalIdx>,
+) {
+    for stmt in statements.iter_mut() {
+        for body in stmt.bodies_mut() {
+            forward_temps_in(body, None, forwardable);
+        }
+    }
+    for i in (0..statements.len()).rev() {
+        let Some(local_index) = forwardable_temp(&statements[i], forwardable) else {
+            continue;
+        };
+        // Source locations don't count as statements in between.
+        let next =
+            (i + 1..statements.len()).find(|j| !matches!(statements[*j], Statement::Location(_)));
+        let mut value = match &statements[i] {
+            Statement::LocalSet(stmt) => Some((*stmt.value).clone()),
+            Statement::LocalSetN(stmt) => Some((*stmt.value).clone()),
+            _ => unreachable!(),
+        };
+        let forwarded = match next {
+            Some(j) => {
+                let stmt = &mut statements[j];
+                match stmt {
+                    // A loop's condition is evaluated every time round.
+                    Statement::Loop(_) => Forward::Blocked,
+                    Statement::CallIndirect(_) | Statement::CallRef(_) => forward_into(
+                        callee_last(stmt.expressions_mut()),
+                        local_index,
+                        &mut value,
+                        Effects::default(),
+                    ),
+                    _ => forward_into(
+                        stmt.expressions_mut(),
+                        local_index,
+                        &mut value,
+                        Effects::default(),
+                    ),
+                }
+            }
+            None => match terminator.as_deref_mut() {
+                // The values of a `br_if` are evaluated before its
+                // condition.
+                Some(Terminator::BrIf(_, _, _, values)) if !values.is_empty() => Forward::Blocked,
+                Some(terminator) => forward_into(
+                    terminator.expressions_mut(),
+                    local_index,
+                    &mut value,
+                    Effects::default(),
+                ),
+                None => Forward::NotFound,
+            },
+        };
+        if forwarded == Forward::Done {
+            statements.remove(i);
+        }
     }
 }
+
+#[derive(Debug, PartialEq, Eq)]
+enum Forward {
+    Done,
+    Blocked,
+    NotFound,
+}
+
+// An indirect call or `call_ref` evaluates its params before its callee.
+fn callee_last(mut exprs: Vec<&mut Expression>) -> Vec<&mut Expression> {
+    exprs.rotate_left(1);
+    exprs
+}
+
+// Replaces the read of `local_index` in `exprs`, which are evaluated in
+// order, with `value`, if `value` can be evaluated after everything that
+// comes before the read. `before` is the effects of what's evaluated before
+// `exprs`.
+fn forward_into(
+    exprs: Vec<&mut Expression>,
+    local_index: LocalIdx,
+    value: &mut Option<Expression>,
+    mut before: Effects,
+) -> Forward {
+    for expr in exprs {
+        if !reads_local(expr, local_index) {
+            before = before.union(expr.effects());
+            continue;
+        }
+        if !value.as_ref().unwrap().effects().commutes_with(before) {
+            return Forward::Blocked;
+        }
+        return match expr {
+            Expression::GetLocal(get) if get.local_index == local_index => {
+                *expr = value.take().unwrap();
+                Forward::Done
+            }
+            Expression::GetLocalN(get) if get.local_indices == [local_index] => {
+                *expr = value.take().unwrap();
+                Forward::Done
+            }
+            // One of several values read at once.
+            Expression::GetLocalN(_) => Forward::Blocked,
+            // The right of `&&` and `||` isn't always evaluated.
+            Expression::Logical(_, lhs, _) if !reads_local(lhs, local_index) => Forward::Blocked,
+            Expression::CallIndirect(_) | Expression::CallRef(_) => {
+                forward_into(callee_last(expr.operands_mut()), local_index, value, before)
+            }
+            expr => forward_into(expr.operands_mut(), local_index, value, before),
+        };
+    }
+    Forward::NotFound
+}
+
+// Whether `first` can be evaluated before all of `rest` without changing
+// what any of them does.
+fn commutes_with_all(first: &Expression, rest: &[Expression]) -> bool {
+    let effects = first.effects();
+    rest.iter()
+        .all(|expr| effects.commutes_with(expr.effects()))
+}
+
+// The values a block returns, given the params a branch passes it. The
+// params are evaluated before the block's values, so they can be
+// substituted in anywhere only if they're pure. Otherwise the block has to
+// return its params unchanged.
+fn forward_return(values: &[Expression], params: &mut Vec<Expression>) -> Option<Vec<Expression>> {
+    if params.iter().all(Expression::is_pure) {
+        fn substitute(expr: &mut Expression, params: &[Expression]) {
+            if let Expression::BlockParam(i) = expr {
+                if let Some(param) = params.get(*i as usize) {
+                    *expr = param.clone();
+                }
+                return;
+            }
+            for operand in expr.operands_mut() {
+                substitute(operand, params);
+            }
+        }
+
+        let mut values = values.to_vec();
+        for value in &mut values {
+            substitute(value, params);
+        }
+        return Some(values);
+    }
+    let forwards_params = values.len() == params.len()
+        && values
+            .iter()
+            .enumerate()
+            .all(|(i, value)| matches!(value, Expression::BlockParam(n) if *n as usize == i));
+    forwards_params.then(|| std::mem::take(params))
+}
+
+// `condition` negated, as a condition. Integer comparisons are flipped,
+// `eqz(x)` becomes `x` and `a && b` becomes `!a || !b`, unless that leaves
+// more `!`s than `!(a && b)` does. Float comparisons can't be flipped, as
+// `a < b` and `a >= b` are both false for NaN.
+pub(crate) fn negate(condition: Expression) -> Expression {
+    use BinaryExpression::*;
+    match condition {
+        Expression::Unary(UnaryExpression::I32Eqz, value) => *value,
+        Expression::Logical(op, lhs, rhs) => {
+            let flipped = match op {
+                LogicalExpression::And => LogicalExpression::Or,
+                LogicalExpression::Or => LogicalExpression::And,
+            };
+            let negated = Expression::Logical(
+                flipped,
+                Box::new(negate((*lhs).clone())),
+                Box::new(negate((*rhs).clone())),
+            );
+            let condition = Expression::Logical(op, lhs, rhs);
+            if count_negations(&negated) <= count_negations(&condition) + 1 {
+                negated
+            } else {
+                Expression::Unary(UnaryExpression::I32Eqz, Box::new(condition))
+            }
+        }
+        Expression::Binary(op, lhs, rhs) => {
+            let flipped = match op {
+                I32Eq => I32Ne,
+                I32Ne => I32Eq,
+                I32LtS => I32GeS,
+                I32LtU => I32GeU,
+                I32GtS => I32LeS,
+                I32GtU => I32LeU,
+                I32LeS => I32GtS,
+                I32LeU => I32GtU,
+                I32GeS => I32LtS,
+                I32GeU => I32LtU,
+                I64Eq => I64Ne,
+                I64Ne => I64Eq,
+                I64LtS => I64GeS,
+                I64LtU => I64GeU,
+                I64GtS => I64LeS,
+                I64GtU => I64LeU,
+                I64LeS => I64GtS,
+                I64LeU => I64GtU,
+                I64GeS => I64LtS,
+                I64GeU => I64LtU,
+                op => {
+                    let condition = Expression::Binary(op, lhs, rhs);
+                    return Expression::Unary(UnaryExpression::I32Eqz, Box::new(condition));
+                }
+            };
+            Expression::Binary(flipped, lhs, rhs)
+        }
+        condition => Expression::Unary(UnaryExpression::I32Eqz, Box::new(condition)),
+    }
+}
+
+// The number of `eqz`s in `expr`, each printed as `!`.
+fn count_negations(expr: &Expression) -> usize {
+    let own = matches!(expr, Expression::Unary(UnaryExpression::I32Eqz, _)) as usize;
+    own + expr
+        .operands()
+        .into_iter()
+        .map(count_negations)
+        .sum::<usize>()
+}
+
+// The value assigned by a statement that assigns a single value.
+fn assigned_value(stmt: &mut Statement) -> Option<&mut Expression> {
+    match stmt {
+        Statement::LocalSet(stmt) => Some(&mut stmt.value),
+        Statement::GlobalSet(stmt) => Some(&mut stmt.value),
+        _ => None,
+    }
+}
+
+// How many selects deep `expr` is, counting only through their values.
+fn select_depth(expr: &Expression) -> usize {
+    match expr {
+        Expression::Select(select) => {
+            select_depth(&select.on_true).max(select_depth(&select.on_false)) + 1
+        }
+        _ => 0,
+    }
+}
+
+// Turns `x = c ? a : b` into `if (c) { x = a } else { x = b }`, and the
+// same for any selects in `a` and `b`.
+fn split_select(mut stmt: Statement) -> Statement {
+    let Some(Expression::Select(select)) = assigned_value(&mut stmt) else {
+        return stmt;
+    };
+    let SelectExpression {
+        on_true,
+        on_false,
+        condition,
+        ..
+    } = select.clone();
+    let mut true_stmt = stmt.clone();
+    *assigned_value(&mut true_stmt).unwrap() = *on_true;
+    let mut false_stmt = stmt;
+    *assigned_value(&mut false_stmt).unwrap() = *on_false;
+    Statement::If(IfStatement {
+        condition,
+        true_statements: vec![split_select(true_stmt)],
+        false_statements: vec![split_select(false_stmt)],
+    })
+}
@@ -3,41 +3,135 @@ use crate::ir::*;
 #[derive(Clone, Copy)]
 pub(crate) struct Ctx<'b> {
     pub(crate) func: &'b Func,
+    pub(crate) names: &'b Names,
+    // How many spaces each level of nesting is indented by.
+    pub(crate) indent: usize,
+}
+
+// The statements that are printed, which leaves out their offsets unless
+// they're asked for.
+fn printed<'b>(statements: &'b [Statement], ctx: Ctx<'b>) -> impl Iterator<Item = &'b Statement> {
+    statements.iter().filter(move |stmt| {
+        ctx.func.show_offsets || !matches!(stmt, Statement::Location(SourceLocation::Offset(_)))
+    })
+}
+
+// The printed statements, each annotated with the offset it was decoded
+// from, for writers that map the output back to the module.
+fn pretty_statements<'b, D>(
+    statements: &'b [Statement],
+    ctx: Ctx<'b>,
+    allocator: &'b D,
+) -> impl Iterator<Item = DocBuilder<'b, D, Token>>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    let mut offset = None;
+    statements.iter().filter_map(move |stmt| match stmt {
+        Statement::Location(SourceLocation::Offset(offsets)) => {
+            offset = Some(offsets.start);
+            (ctx.func.show_offsets).then(|| stmt.pretty(ctx, allocator))
+        }
+        Statement::Location(_) => Some(stmt.pretty(ctx, allocator)),
+        _ => Some(pretty_offset(stmt.pretty(ctx, allocator), offset.take())),
+    })
+}
+
+fn pretty_offset<'b, D>(
+    doc: DocBuilder<'b, D, Token>,
+    offset: Option<usize>,
+) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    match offset {
+        Some(offset) => doc.annotate(Token::Offset(offset)),
+        None => doc,
+    }
 }
 
 impl Block {
-    pub(crate) fn pretty<'b, D, A>(
+    fn pretty_terminator<'b, D>(
+        &'b self,
+        ctx: Ctx<'b>,
+        allocator: &'b D,
+    ) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        let offset = self
+            .terminator_offsets
+            .as_ref()
+            .map(|offsets| offsets.start);
+        pretty_offset(self.terminator.pretty(ctx, allocator), offset)
+    }
+
+    pub(crate) fn pretty<'b, D>(
         &'b self,
         func: &Func,
         index: BlockIndex,
         is_last_block: bool,
         ctx: Ctx<'b>,
         allocator: &'b D,
-    ) -> DocBuilder<'b, D, A>
+    ) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        self.pretty_elided(func, index, is_last_block, None, ctx, allocator)
+    }
+
+    // Like `pretty`, but only the first `max_statements` statements are
+    // printed, followed by a count of the rest.
+    pub(crate) fn pretty_elided<'b, D>(
+        &'b self,
+        func: &Func,
+        index: BlockIndex,
+        is_last_block: bool,
+        max_statements: Option<usize>,
+        ctx: Ctx<'b>,
+        allocator: &'b D,
+    ) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
         // The entry block is guaranteed to be printed first. See assertion in printing Func.
         let is_entry_block = index == func.entry_block;
         // The entry block cannot have params, so we can skip printing the block label.
-        assert!(!is_entry_block || self.params.is_empty());
+        debug_assert!(!is_entry_block || self.params.is_empty());
 
-        let mut instructions = vec![];
-        for statement in &self.statements {
-            instructions.push(statement.pretty(ctx, allocator));
+        let shown = max_statements.unwrap_or(usize::MAX);
+        let mut instructions: Vec<_> = pretty_statements(&self.statements, ctx, allocator)
+            .take(shown)
+            .collect();
+        let hidden = printed(&self.statements, ctx).count().saturating_sub(shown);
+        if hidden > 0 {
+            instructions.push(
+                allocator
+                    .text(format!("/* ... {} more statements */", hidden))
+                    .annotate(Token::Comment),
+            );
         }
         // Skip an empty return in the last block
         if !is_last_block || !self.terminator.is_empty_return() {
-            instructions.push(self.terminator.pretty(ctx, allocator));
+            instructions.push(self.pretty_terminator(ctx, allocator));
         }
 
         let params = self.params.iter().enumerate().map(|(i, param)| {
             allocator
-                .text(format!("b{}:", i))
+                .text(format!("b{}", i))
+                .annotate(Token::Name)
+                .append(allocator.text(":"))
                 .append(allocator.space())
-                .append(allocator.text(param.to_string()))
+                .append(
+                    allocator
+                        .text(val_type_name(*param, ctx.names))
+                        .annotate(Token::Type),
+                )
         });
 
         let label = if is_entry_block {
@@ -45,6 +139,7 @@ impl Block {
         } else {
             allocator
                 .text(format!("@{}", index.0))
+                .annotate(Token::Label)
                 .append(if self.params.is_empty() {
                     allocator.nil()
                 } else {
@@ -57,157 +152,198 @@ impl Block {
         label.append(
             allocator
                 .intersperse(instructions, allocator.hardline())
-                .indent(2),
+                .indent(ctx.indent),
         )
     }
 }
 
 impl Terminator {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
         match self {
-            Terminator::Unknown => allocator.text("unknown"),
-            Terminator::Unreachable => allocator.text("unreachable"),
+            Terminator::Unknown => allocator.text("unknown").annotate(Token::Keyword),
+            Terminator::Unreachable => allocator.text("unreachable").annotate(Token::Keyword),
             Terminator::Return(params) => allocator
                 .text("return")
+                .annotate(Token::Keyword)
                 .append(allocator.space())
                 .append(allocator.intersperse(
                     params.iter().map(|param| param.pretty(ctx, allocator)),
                     allocator.text(", "),
                 )),
             Terminator::Br(target, params) => {
-                let params = if params.is_empty() {
-                    allocator.nil()
-                } else {
-                    allocator
-                        .space()
-                        .append(allocator.text("with"))
-                        .append(allocator.space())
-                        .append(
-                            allocator
-                                .intersperse(
-                                    params.iter().map(|param| param.pretty(ctx, allocator)),
-                                    allocator.text(", "),
-                                )
-                                .parens(),
-                        )
-                };
+                let params = pretty_with(params, ctx, allocator);
 
-                allocator.text(format!("br @{}", target.0)).append(params)
+                pretty_br(*target, allocator).append(params)
             }
             Terminator::BrIf(condition, true_target, false_target, params) => {
-                let params = if params.is_empty() {
-                    allocator.nil()
-                } else {
-                    allocator
-                        .space()
-                        .append(allocator.text("with"))
-                        .append(allocator.space())
-                        .append(
-                            allocator
-                                .intersperse(
-                                    params.iter().map(|param| param.pretty(ctx, allocator)),
-                                    allocator.text(", "),
-                                )
-                                .parens(),
-                        )
-                };
+                let params = pretty_with(params, ctx, allocator);
 
                 allocator
                     .text("if")
+                    .annotate(Token::Keyword)
                     .append(allocator.space())
                     .append(condition.pretty(ctx, allocator))
                     .append(allocator.hardline())
                     .append(
                         allocator
-                            .text(format!(" br @{}", true_target.0))
+                            .text(" ")
+                            .append(pretty_br(*true_target, allocator))
                             .append(params.clone())
-                            .indent(2),
+                            .indent(ctx.indent),
                     )
                     .append(allocator.hardline())
-                    .append(
-                        allocator
-                            .text(format!("br @{}", false_target.0))
-                            .append(params),
-                    )
+                    .append(pretty_br(*false_target, allocator).append(params))
             }
-            Terminator::BrTable(targets, default_target, params) => {
-                let params = if params.is_empty() {
-                    allocator.nil()
-                } else {
-                    allocator
-                        .space()
-                        .append(allocator.text("with"))
-                        .append(allocator.space())
-                        .append(
-                            allocator
-                                .intersperse(
-                                    params.iter().map(|param| param.pretty(ctx, allocator)),
-                                    allocator.text(", "),
-                                )
-                                .parens(),
-                        )
-                };
+            Terminator::BrTable(index, targets, default_target, params) => {
+                let params = pretty_with(params, ctx, allocator);
 
                 let targets = allocator.intersperse(
-                    targets.iter().map(|x| allocator.text(format!("@{}", x.0))),
+                    targets.iter().map(|x| pretty_label(*x, allocator)),
                     allocator.text(", "),
                 );
 
                 allocator
                     .text("br_table")
+                    .annotate(Token::Keyword)
+                    .append(index.pretty(ctx, allocator).brackets())
                     .append(
                         targets
                             .append(
                                 allocator
-                                    .text(" default ")
-                                    .append(allocator.text(format!("@{}", default_target.0))),
+                                    .space()
+                                    .append(allocator.text("default").annotate(Token::Keyword))
+                                    .append(allocator.space())
+                                    .append(pretty_label(*default_target, allocator)),
                             )
                             .parens(),
                     )
+                    .append(params)
+            }
+            Terminator::Try(body, catches, params) => {
+                let params = pretty_with(params, ctx, allocator);
+
+                let catches = catches.iter().map(|catch| {
+                    let (keyword, tag) = match catch {
+                        Catch::One { tag, .. } => ("catch", Some(*tag)),
+                        Catch::OneRef { tag, .. } => ("catch_ref", Some(*tag)),
+                        Catch::All { .. } => ("catch_all", None),
+                        Catch::AllRef { .. } => ("catch_all_ref", None),
+                    };
+                    let tag = match tag {
+                        Some(tag) => allocator.space().append(pretty_tag(tag, allocator)),
+                        None => allocator.nil(),
+                    };
+                    allocator.hardline().append(
+                        allocator
+                            .text(" ")
+                            .append(allocator.text(keyword).annotate(Token::Keyword))
+                            .append(tag)
+                            .append(allocator.space())
+                            .append(pretty_br(catch.target(), allocator))
+                            .indent(ctx.indent),
+                    )
+                });
+
+                allocator
+                    .text("try")
+                    .annotate(Token::Keyword)
                     .append(allocator.space())
+                    .append(pretty_br(*body, allocator))
                     .append(params)
+                    .append(allocator.concat(catches))
             }
+            Terminator::Throw(tag, params) => allocator
+                .text("throw")
+                .annotate(Token::Keyword)
+                .append(allocator.space())
+                .append(pretty_tag(*tag, allocator))
+                .append(
+                    allocator
+                        .intersperse(
+                            params.iter().map(|param| param.pretty(ctx, allocator)),
+                            allocator.text(", "),
+                        )
+                        .parens(),
+                ),
+            Terminator::ThrowRef(exception) => allocator
+                .text("rethrow")
+                .annotate(Token::Keyword)
+                .append(allocator.space())
+                .append(exception.pretty(ctx, allocator)),
         }
     }
 }
 
 impl Statement {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
         match self {
-            Statement::Nop => allocator.text("nop"),
+            Statement::Nop => allocator.text("nop").annotate(Token::Keyword),
+            Statement::Location(SourceLocation::Instructions(instructions)) => {
+                pretty_instructions(instructions, allocator)
+            }
+            Statement::Location(location) => allocator
+                .text(format!("// {}", location))
+                .annotate(Token::Comment),
             Statement::Drop(expr) => allocator
                 .text("drop")
+                .annotate(Token::Keyword)
                 .append(expr.pretty(ctx, allocator).parens()),
             Statement::LocalSet(stmt) => stmt.pretty(ctx, allocator),
             Statement::LocalSetN(stmt) => stmt.pretty(ctx, allocator),
             Statement::GlobalSet(stmt) => stmt.pretty(ctx, allocator),
             Statement::MemoryStore(stmt) => stmt.pretty(ctx, allocator),
+            Statement::TableSet(stmt) => stmt.pretty(ctx, allocator),
+            Statement::TableFill(stmt) => stmt.pretty(ctx, allocator),
+            Statement::StructSet(stmt) => stmt.pretty(ctx, allocator),
+            Statement::ArraySet(stmt) => stmt.pretty(ctx, allocator),
+            Statement::ArrayFill(stmt) => pretty_operator_call(
+                "array.fill".to_string(),
+                vec![
+                    stmt.array.pretty(ctx, allocator),
+                    stmt.offset.pretty(ctx, allocator),
+                    stmt.value.pretty(ctx, allocator),
+                    stmt.len.pretty(ctx, allocator),
+                ],
+                allocator,
+            ),
+            Statement::ArrayCopy(stmt) => pretty_operator_call(
+                "array.copy".to_string(),
+                vec![
+                    stmt.dst.pretty(ctx, allocator),
+                    stmt.dst_offset.pretty(ctx, allocator),
+                    stmt.src.pretty(ctx, allocator),
+                    stmt.src_offset.pretty(ctx, allocator),
+                    stmt.len.pretty(ctx, allocator),
+                ],
+                allocator,
+            ),
+            Statement::ArrayInit(stmt) => stmt.pretty(ctx, allocator),
             Statement::If(stmt) => stmt.pretty(ctx, allocator),
+            Statement::Loop(stmt) => stmt.pretty(ctx, allocator),
             Statement::Call(expr) => expr.pretty(ctx, allocator),
             Statement::CallIndirect(expr) => expr.pretty(ctx, allocator),
+            Statement::CallRef(expr) => expr.pretty(ctx, allocator),
         }
     }
 }
 
 impl LocalSetStatement {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
         allocator
-            .text(&ctx.func.locals[self.index as usize].name)
+            .text(&ctx.func.local(self.index).name)
+            .annotate(Token::Name)
             .append(allocator.space())
             .append(allocator.text("="))
             .append(allocator.space())
@@ -216,17 +352,18 @@ impl LocalSetStatement {
 }
 
 impl LocalSetNStatement {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
         allocator
             .intersperse(
-                self.index
-                    .iter()
-                    .map(|x| allocator.text(&ctx.func.locals[*x as usize].name)),
+                self.index.iter().map(|x| {
+                    allocator
+                        .text(&ctx.func.local(*x).name)
+                        .annotate(Token::Name)
+                }),
                 allocator.text(", "),
             )
             .append(allocator.space())
@@ -237,162 +374,826 @@ impl LocalSetNStatement {
 }
 
 impl GlobalSetStatement {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
+        let name = match ctx.names.global(self.index) {
+            Some(name) => name.to_string(),
+            None => format!("globals[{}]", self.index),
+        };
         allocator
-            .text(format!("global[{}] = ", self.index))
+            .text(name)
+            .annotate(Token::Name)
+            .append(allocator.text(" = "))
             .append(self.value.pretty(ctx, allocator))
     }
 }
 
 impl MemoryStoreStatement {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
-        // TODO: offset
-        allocator
-            .text("*")
-            .append(self.index.pretty(ctx, allocator).parens())
+        let target = if let Some(slot) = ctx.func.frame_slot(&self.index, &self.arg) {
+            allocator.text(slot).annotate(Token::Name)
+        } else if ctx.func.multi_memory {
+            pretty_memory(self.arg.memory, ctx, allocator)
+                .append(pretty_address(&self.index, &self.arg, ctx, allocator).brackets())
+        } else {
+            allocator
+                .text("*")
+                .annotate(Token::Operator)
+                .append(pretty_address(&self.index, &self.arg, ctx, allocator).parens())
+        };
+        target
             .append(allocator.space())
             .append(allocator.text("="))
             .append(allocator.space())
+            .append(self.value.pretty_pointer(ctx, allocator))
+    }
+}
+
+impl TableSetStatement {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        pretty_table(self.table, ctx, allocator)
+            .append(self.index.pretty(ctx, allocator).brackets())
+            .append(allocator.space())
+            .append(allocator.text("="))
+            .append(allocator.space())
+            .append(self.value.pretty(ctx, allocator))
+    }
+}
+
+impl TableFillStatement {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        pretty_table_method(
+            self.table,
+            "fill",
+            vec![
+                self.index.pretty(ctx, allocator),
+                self.value.pretty(ctx, allocator),
+                self.len.pretty(ctx, allocator),
+            ],
+            ctx,
+            allocator,
+        )
+    }
+}
+
+impl StructSetStatement {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        pretty_field(&self.object, self.field, Extension::None, ctx, allocator)
+            .append(allocator.text(" = "))
             .append(self.value.pretty(ctx, allocator))
     }
 }
 
+impl ArraySetStatement {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        self.array
+            .pretty_operand(ctx, allocator)
+            .append(self.index.pretty(ctx, allocator).brackets())
+            .append(allocator.text(" = "))
+            .append(self.value.pretty(ctx, allocator))
+    }
+}
+
+impl ArrayInitStatement {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        let name = match self.segment {
+            Segment::Data(_) => "array.init_data",
+            Segment::Elem(_) => "array.init_elem",
+        };
+        pretty_operator_call(
+            name.to_string(),
+            vec![
+                self.array.pretty(ctx, allocator),
+                self.offset.pretty(ctx, allocator),
+                pretty_segment(self.segment, allocator),
+                self.source_offset.pretty(ctx, allocator),
+                self.len.pretty(ctx, allocator),
+            ],
+            allocator,
+        )
+    }
+}
+
 impl IfStatement {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
         allocator
             .text("if")
+            .annotate(Token::Keyword)
             .append(allocator.space())
             .append(self.condition.pretty(ctx, allocator).parens())
             .append(allocator.space())
             .append(
                 allocator
                     .intersperse(
-                        self.true_statements
-                            .iter()
-                            .map(|x| x.pretty(ctx, allocator)),
+                        pretty_statements(&self.true_statements, ctx, allocator),
                         allocator.hardline(),
                     )
-                    .indent(2)
+                    .indent(ctx.indent)
                     .enclose(allocator.hardline(), allocator.hardline())
                     .braces(),
             )
-            .append(allocator.space())
-            .append(allocator.text("else"))
-            .append(allocator.space())
-            .append(
+            .append(if printed(&self.false_statements, ctx).next().is_none() {
+                allocator.nil()
+            } else {
                 allocator
-                    .intersperse(
-                        self.false_statements
-                            .iter()
-                            .map(|x| x.pretty(ctx, allocator)),
-                        allocator.hardline(),
+                    .space()
+                    .append(allocator.text("else").annotate(Token::Keyword))
+                    .append(allocator.space())
+                    .append(
+                        allocator
+                            .intersperse(
+                                pretty_statements(&self.false_statements, ctx, allocator),
+                                allocator.hardline(),
+                            )
+                            .indent(ctx.indent)
+                            .enclose(allocator.hardline(), allocator.hardline())
+                            .braces(),
                     )
-                    .indent(2)
-                    .enclose(allocator.hardline(), allocator.hardline())
-                    .braces(),
+            })
+    }
+}
+
+impl LoopStatement {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        let condition = allocator
+            .text("while")
+            .annotate(Token::Keyword)
+            .append(allocator.space())
+            .append(self.condition.pretty(ctx, allocator).parens());
+        let body = allocator
+            .intersperse(
+                pretty_statements(&self.body, ctx, allocator),
+                allocator.hardline(),
+            )
+            .indent(ctx.indent)
+            .enclose(allocator.hardline(), allocator.hardline())
+            .braces();
+        match self.kind {
+            LoopKind::While => condition.append(allocator.space()).append(body),
+            LoopKind::DoWhile => allocator
+                .text("do")
+                .annotate(Token::Keyword)
+                .append(allocator.space())
+                .append(body)
+                .append(allocator.space())
+                .append(condition),
+        }
+    }
+}
+
+// A `{ ... }` body of statements, one per line.
+fn pretty_body<'b, D>(
+    statements: Vec<DocBuilder<'b, D, Token>>,
+    indent: usize,
+    allocator: &'b D,
+) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    allocator
+        .intersperse(statements, allocator.hardline())
+        .indent(indent)
+        .enclose(allocator.hardline(), allocator.hardline())
+        .braces()
+}
+
+impl Node {
+    // Whether the node prints anything, which statements that only say
+    // where the ones that were removed came from may not.
+    fn is_printed(&self, ctx: Ctx) -> bool {
+        match self {
+            Node::Statements(block_index) => printed(&ctx.func.blocks[block_index].statements, ctx)
+                .next()
+                .is_some(),
+            _ => true,
+        }
+    }
+
+    // `innermost_loop` is the header of the loop the node is in, which its
+    // `break`s and `continue`s leave out.
+    fn pretty<'b, D>(
+        &'b self,
+        ctx: Ctx<'b>,
+        innermost_loop: Option<BlockIndex>,
+        allocator: &'b D,
+    ) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        let body = |nodes: &'b [Node], innermost_loop| {
+            pretty_body(
+                nodes
+                    .iter()
+                    .filter(|node| node.is_printed(ctx))
+                    .map(|node| node.pretty(ctx, innermost_loop, allocator))
+                    .collect(),
+                ctx.indent,
+                allocator,
             )
+        };
+        let jump = |keyword: &'static str, target: BlockIndex| {
+            let keyword = allocator.text(keyword).annotate(Token::Keyword);
+            if innermost_loop == Some(target) {
+                keyword
+            } else {
+                keyword
+                    .append(allocator.space())
+                    .append(pretty_label(target, allocator))
+            }
+        };
+        match self {
+            Node::Statements(block_index) => allocator.intersperse(
+                pretty_statements(&ctx.func.blocks[block_index].statements, ctx, allocator),
+                allocator.hardline(),
+            ),
+            Node::Block(target, nodes) => allocator
+                .text("block")
+                .annotate(Token::Keyword)
+                .append(allocator.space())
+                .append(pretty_label(*target, allocator))
+                .append(allocator.space())
+                .append(body(nodes, innermost_loop)),
+            Node::Loop(header, nodes) => {
+                let needs_label = nodes
+                    .iter()
+                    .any(|node| node.needs_loop_label(ctx.func, *header, false));
+                let label = if needs_label {
+                    allocator.space().append(pretty_label(*header, allocator))
+                } else {
+                    allocator.nil()
+                };
+                allocator
+                    .text("loop")
+                    .annotate(Token::Keyword)
+                    .append(label)
+                    .append(allocator.space())
+                    .append(body(nodes, Some(*header)))
+            }
+            Node::If(block_index, condition, true_nodes, false_nodes) => {
+                let false_part = match &false_nodes[..] {
+                    [] => allocator.nil(),
+                    // `else if` chains stay flat.
+                    [node @ Node::If(..)] => allocator
+                        .space()
+                        .append(allocator.text("else").annotate(Token::Keyword))
+                        .append(allocator.space())
+                        .append(node.pretty(ctx, innermost_loop, allocator)),
+                    nodes => allocator
+                        .space()
+                        .append(allocator.text("else").annotate(Token::Keyword))
+                        .append(allocator.space())
+                        .append(body(nodes, innermost_loop)),
+                };
+                let offset = ctx.func.blocks[block_index].terminator_offsets.as_ref();
+                let head = allocator
+                    .text("if")
+                    .annotate(Token::Keyword)
+                    .append(allocator.space())
+                    .append(condition.pretty(ctx, allocator).parens());
+                pretty_offset(head, offset.map(|offsets| offsets.start))
+                    .append(allocator.space())
+                    .append(body(true_nodes, innermost_loop))
+                    .append(false_part)
+            }
+            Node::Break(target) => jump("break", *target),
+            Node::Continue(target) => jump("continue", *target),
+            Node::Exit(block_index) => {
+                ctx.func.blocks[block_index].pretty_terminator(ctx, allocator)
+            }
+        }
     }
 }
 
 impl Expression {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    pub(crate) fn pretty<'b, D>(
+        &'b self,
+        ctx: Ctx<'b>,
+        allocator: &'b D,
+    ) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
         match self {
-            Expression::I32Const { value } => allocator.text(value.to_string()),
-            Expression::I64Const { value } => allocator.text(value.to_string()),
+            Expression::I32Const { value } => pretty_int_const(*value as i64, 32, ctx, allocator),
+            Expression::I64Const { value } => pretty_int_const(*value, 64, ctx, allocator),
             Expression::F32Const { value } => {
                 // TODO: Not correct for NaNs
-                allocator.text(f32::from_bits(value.bits()).to_string())
+                allocator
+                    .text(f32::from_bits(value.bits()).to_string())
+                    .annotate(Token::Literal)
             }
             Expression::F64Const { value } => {
                 // TODO: Not correct for NaNs
-                allocator.text(f64::from_bits(value.bits()).to_string())
+                allocator
+                    .text(f64::from_bits(value.bits()).to_string())
+                    .annotate(Token::Literal)
+            }
+            Expression::V128Const { value } => {
+                let lanes = value.chunks(4).map(|lane| {
+                    let lane = u32::from_le_bytes(lane.try_into().unwrap());
+                    allocator
+                        .text(format!("0x{:08x}", lane))
+                        .annotate(Token::Literal)
+                });
+                allocator
+                    .text("i32x4")
+                    .annotate(Token::Type)
+                    .append(allocator.intersperse(lanes, allocator.text(", ")).parens())
+            }
+            Expression::RefNull { .. } => allocator.text("null").annotate(Token::Literal),
+            Expression::RefFunc { func_index } => allocator
+                .text(ctx.names.func_name(*func_index))
+                .annotate(Token::Function),
+            Expression::BlockParam(index) => {
+                allocator.text(format!("b{}", index)).annotate(Token::Name)
+            }
+            Expression::Unary(UnaryExpression::I32Eqz | UnaryExpression::I64Eqz, value) => {
+                allocator
+                    .text("!")
+                    .annotate(Token::Operator)
+                    .append(value.pretty_operand(ctx, allocator))
             }
-            Expression::BlockParam(index) => allocator.text(format!("b{}", index)),
             Expression::Unary(op, value) => allocator
                 .text(op.to_string())
+                .annotate(Token::Operator)
                 .append(value.pretty(ctx, allocator).parens()),
             Expression::Binary(op, lhs, rhs) => {
                 let (text, is_infix) = op.to_string_and_infix();
-                if is_infix {
-                    lhs.pretty(ctx, allocator)
+                if is_infix && op.is_bitwise() {
+                    lhs.pretty_mask(ctx, allocator)
                         .append(allocator.space())
-                        .append(allocator.text(text))
+                        .append(allocator.text(text).annotate(Token::Operator))
                         .append(allocator.space())
-                        .append(rhs.pretty(ctx, allocator))
-                } else {
-                    allocator
-                        .text(text)
+                        .append(rhs.pretty_mask(ctx, allocator))
+                } else if is_infix {
+                    // e.g. `i < n` rather than `i <_u n` with `i: u32`.
+                    let text = match ctx.func.implies_signedness(op, lhs, rhs) {
+                        true => text.trim_end_matches(['_', 's', 'u']),
+                        false => text,
+                    };
+                    lhs.pretty_operand(ctx, allocator)
                         .append(allocator.space())
-                        .append(lhs.pretty(ctx, allocator))
+                        .append(allocator.text(text).annotate(Token::Operator))
                         .append(allocator.space())
-                        .append(rhs.pretty(ctx, allocator))
+                        .append(rhs.pretty_operand(ctx, allocator))
+                } else {
+                    allocator.text(text).annotate(Token::Operator).append(
+                        lhs.pretty(ctx, allocator)
+                            .append(allocator.text(", "))
+                            .append(rhs.pretty(ctx, allocator))
+                            .parens(),
+                    )
                 }
             }
+            Expression::Logical(op, lhs, rhs) => lhs
+                .pretty_operand(ctx, allocator)
+                .append(allocator.space())
+                .append(allocator.text(op.to_string()).annotate(Token::Operator))
+                .append(allocator.space())
+                .append(rhs.pretty_operand(ctx, allocator)),
+            Expression::Ternary(op, a, b, c) => pretty_operator_call(
+                op.to_string().to_string(),
+                vec![
+                    a.pretty(ctx, allocator),
+                    b.pretty(ctx, allocator),
+                    c.pretty(ctx, allocator),
+                ],
+                allocator,
+            ),
+            Expression::ExtractLane(expr) => expr.pretty(ctx, allocator),
+            Expression::ReplaceLane(expr) => expr.pretty(ctx, allocator),
+            Expression::Shuffle(expr) => expr.pretty(ctx, allocator),
             Expression::Call(expr) => expr.pretty(ctx, allocator),
             Expression::CallIndirect(expr) => expr.pretty(ctx, allocator),
+            Expression::CallRef(expr) => expr.pretty(ctx, allocator),
             Expression::GetLocal(expr) => expr.pretty(ctx, allocator),
             Expression::GetLocalN(expr) => expr.pretty(ctx, allocator),
             Expression::GetGlobal(expr) => expr.pretty(ctx, allocator),
             Expression::Select(expr) => expr.pretty(ctx, allocator),
             Expression::MemoryLoad(expr) => expr.pretty(ctx, allocator),
-            Expression::MemorySize => allocator.text("memory.size"),
+            Expression::MemorySize { memory } => {
+                if ctx.func.multi_memory {
+                    pretty_method(
+                        pretty_memory(*memory, ctx, allocator),
+                        "size",
+                        vec![],
+                        allocator,
+                    )
+                } else {
+                    allocator.text("memory.size").annotate(Token::Keyword)
+                }
+            }
             Expression::MemoryGrow(expr) => expr.pretty(ctx, allocator),
+            Expression::TableGet(expr) => pretty_table(expr.table, ctx, allocator)
+                .append(expr.index.pretty(ctx, allocator).brackets()),
+            Expression::TableSize { table } => {
+                pretty_table_method(*table, "size", vec![], ctx, allocator)
+            }
+            Expression::TableGrow(expr) => pretty_table_method(
+                expr.table,
+                "grow",
+                vec![
+                    expr.value.pretty(ctx, allocator),
+                    expr.delta.pretty(ctx, allocator),
+                ],
+                ctx,
+                allocator,
+            ),
+            Expression::StructNew(expr) => expr.pretty(ctx, allocator),
+            Expression::StructGet(expr) => expr.pretty(ctx, allocator),
+            Expression::ArrayNew(expr) => expr.pretty(ctx, allocator),
+            Expression::ArrayGet(expr) => expr.pretty(ctx, allocator),
+            Expression::ArrayLen(array) => pretty_operator_call(
+                "array.len".to_string(),
+                vec![array.pretty(ctx, allocator)],
+                allocator,
+            ),
+            Expression::RefAsNonNull(value) => pretty_operator_call(
+                "as_non_null".to_string(),
+                vec![value.pretty(ctx, allocator)],
+                allocator,
+            ),
+            Expression::RefTest(expr) => expr.pretty("is", ctx, allocator),
+            Expression::RefCast(expr) => expr.pretty("as", ctx, allocator),
 
             // Should be eliminated by dead code removal
-            Expression::Bottom => allocator.text("bottom"),
+            Expression::Bottom => allocator.text("bottom").annotate(Token::Keyword),
         }
     }
+
+    // Infix operators and selects are printed without precedence rules, so
+    // wrap them in parens when they appear as an operand of another one.
+    fn pretty_operand<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        let needs_parens = match self {
+            Expression::Binary(op, _, _) => op.to_string_and_infix().1,
+            Expression::Select(_)
+            | Expression::Logical(..)
+            | Expression::RefTest(_)
+            | Expression::RefCast(_) => true,
+            _ => false,
+        };
+        if needs_parens {
+            self.pretty(ctx, allocator).parens()
+        } else {
+            self.pretty(ctx, allocator)
+        }
+    }
+
+    // Where a constant is likely to be a bit mask: an operand of `&`, `|`
+    // or xor. It's printed in hex unless it's small, e.g. `x & 0xf`.
+    fn pretty_mask<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        let (value, bits) = match self {
+            Expression::I32Const { value } => (*value as i64, 32),
+            Expression::I64Const { value } => (*value, 64),
+            _ => return self.pretty_operand(ctx, allocator),
+        };
+        let text = if (-16..10).contains(&value) {
+            value.to_string()
+        } else {
+            hex_literal(unsigned(value, bits))
+        };
+        allocator.text(text).annotate(Token::Literal)
+    }
+
+    // Where a constant is likely to be an address: a call's argument or a
+    // value stored to memory. The address of a string in a data segment is
+    // printed with the string, e.g. `0x400 /* "hello" */`.
+    fn pretty_pointer<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        let address = match self {
+            Expression::I32Const { value } => *value as u32 as u64,
+            Expression::I64Const { value } => *value as u64,
+            _ => return self.pretty(ctx, allocator),
+        };
+        let Some(string) = ctx.func.string_at(address) else {
+            return self.pretty(ctx, allocator);
+        };
+        allocator
+            .text(format!("{:#x}", address))
+            .annotate(Token::Literal)
+            .append(allocator.space())
+            .append(
+                allocator
+                    .text(format!("/* {} */", string))
+                    .annotate(Token::Comment),
+            )
+    }
+}
+
+impl StructNewExpression {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        let method = if self.default { "new_default" } else { "new" };
+        pretty_operator_call(
+            format!("{}.{}", ctx.names.type_name(self.type_index), method),
+            self.fields
+                .iter()
+                .map(|field| field.pretty(ctx, allocator))
+                .collect(),
+            allocator,
+        )
+    }
+}
+
+impl StructGetExpression {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        pretty_field(&self.object, self.field, self.extension, ctx, allocator)
+    }
+}
+
+impl ArrayNewExpression {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        let mut args = vec![];
+        let method = match self.kind {
+            ArrayNewKind::Value => "new",
+            ArrayNewKind::Default => "new_default",
+            ArrayNewKind::Fixed => "new_fixed",
+            ArrayNewKind::Segment(segment) => {
+                args.push(pretty_segment(segment, allocator));
+                match segment {
+                    Segment::Data(_) => "new_data",
+                    Segment::Elem(_) => "new_elem",
+                }
+            }
+        };
+        args.extend(self.args.iter().map(|arg| arg.pretty(ctx, allocator)));
+        pretty_operator_call(
+            format!("{}.{}", ctx.names.type_name(self.type_index), method),
+            args,
+            allocator,
+        )
+    }
+}
+
+impl ArrayGetExpression {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        // Packed elements need their extension spelled out.
+        if self.extension != Extension::None {
+            return pretty_operator_call(
+                format!("array.get{}", self.extension.suffix()),
+                vec![
+                    self.array.pretty(ctx, allocator),
+                    self.index.pretty(ctx, allocator),
+                ],
+                allocator,
+            );
+        }
+        self.array
+            .pretty_operand(ctx, allocator)
+            .append(self.index.pretty(ctx, allocator).brackets())
+    }
+}
+
+impl RefCastExpression {
+    // `keyword` is `is` for a test and `as` for a cast.
+    fn pretty<'b, D>(
+        &'b self,
+        keyword: &'static str,
+        ctx: Ctx<'b>,
+        allocator: &'b D,
+    ) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        self.value
+            .pretty_operand(ctx, allocator)
+            .append(allocator.space())
+            .append(allocator.text(keyword).annotate(Token::Keyword))
+            .append(allocator.space())
+            .append(
+                allocator
+                    .text(ref_type_name(self.nullable, self.heap_type, ctx.names))
+                    .annotate(Token::Type),
+            )
+    }
+}
+
+impl ExtractLaneExpression {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        let suffix = match (self.shape, self.signed) {
+            (LaneShape::I8x16 | LaneShape::I16x8, true) => "_s",
+            (LaneShape::I8x16 | LaneShape::I16x8, false) => "_u",
+            _ => "",
+        };
+        pretty_operator_call(
+            format!("{}.extract_lane{}", self.shape.to_string(), suffix),
+            vec![
+                self.vector.pretty(ctx, allocator),
+                allocator
+                    .text(self.lane.to_string())
+                    .annotate(Token::Literal),
+            ],
+            allocator,
+        )
+    }
+}
+
+impl ReplaceLaneExpression {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        pretty_operator_call(
+            format!("{}.replace_lane", self.shape.to_string()),
+            vec![
+                self.vector.pretty(ctx, allocator),
+                allocator
+                    .text(self.lane.to_string())
+                    .annotate(Token::Literal),
+                self.value.pretty(ctx, allocator),
+            ],
+            allocator,
+        )
+    }
+}
+
+impl ShuffleExpression {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        let mut args = vec![
+            self.lhs.pretty(ctx, allocator),
+            self.rhs.pretty(ctx, allocator),
+        ];
+        args.extend(
+            self.lanes
+                .iter()
+                .map(|lane| allocator.text(lane.to_string()).annotate(Token::Literal)),
+        );
+        pretty_operator_call("i8x16.shuffle".to_string(), args, allocator)
+    }
 }
 
 impl CallExpression {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        allocator
+            .text(callee_name(self.func_index, ctx))
+            .annotate(Token::Function)
+            .append(
+                allocator
+                    .intersperse(
+                        self.params
+                            .iter()
+                            .map(|param| param.pretty_pointer(ctx, allocator)),
+                        allocator.text(", "),
+                    )
+                    .parens(),
+            )
+    }
+}
+
+// The name a call of `func_index` prints.
+fn callee_name(func_index: FuncIdx, ctx: Ctx) -> String {
+    match ctx.func.callee_names.get(&func_index) {
+        Some(name) => name.clone(),
+        None => ctx.names.func_name(func_index),
+    }
+}
+
+// How many of an indirect call's candidates are listed before the rest are
+// counted.
+const MAX_LISTED_CANDIDATES: usize = 8;
+
+impl CallIndirectExpression {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
-        allocator.text(format!("func{}", self.func_index)).append(
+        let mut callee = self.callee_index.pretty_operand(ctx, allocator);
+        if let Some(comment) = self.targets_comment(ctx) {
+            callee = callee
+                .append(allocator.space())
+                .append(allocator.text(comment).annotate(Token::Comment));
+        }
+        callee.append(
             allocator
                 .intersperse(
-                    self.params.iter().map(|param| param.pretty(ctx, allocator)),
+                    self.params
+                        .iter()
+                        .map(|param| param.pretty_pointer(ctx, allocator)),
                     allocator.text(", "),
                 )
                 .parens(),
         )
     }
+
+    // e.g. `/* add */` for the function in a constant slot, or
+    // `/* may call add, sub */`.
+    fn targets_comment(&self, ctx: Ctx) -> Option<String> {
+        match &self.targets {
+            IndirectTargets::Unknown => None,
+            IndirectTargets::Slot(func_index) => {
+                Some(format!("/* {} */", callee_name(*func_index, ctx)))
+            }
+            IndirectTargets::Candidates(candidates) if candidates.is_empty() => {
+                Some("/* may call nothing */".to_string())
+            }
+            IndirectTargets::Candidates(candidates) => {
+                let mut listed: Vec<String> = (candidates.iter())
+                    .take(MAX_LISTED_CANDIDATES)
+                    .map(|func_index| callee_name(*func_index, ctx))
+                    .collect();
+                if candidates.len() > MAX_LISTED_CANDIDATES {
+                    listed.push(format!(
+                        "and {} more",
+                        candidates.len() - MAX_LISTED_CANDIDATES
+                    ));
+                }
+                Some(format!("/* may call {} */", listed.join(", ")))
+            }
+        }
+    }
 }
 
-impl CallIndirectExpression {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+impl CallRefExpression {
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
-        self.callee_index.pretty(ctx, allocator).append(
+        self.callee.pretty_operand(ctx, allocator).append(
             allocator
                 .intersperse(
-                    self.params.iter().map(|param| param.pretty(ctx, allocator)),
+                    self.params
+                        .iter()
+                        .map(|param| param.pretty_pointer(ctx, allocator)),
                     allocator.text(", "),
                 )
                 .parens(),
@@ -401,96 +1202,123 @@ impl CallIndirectExpression {
 }
 
 impl GetLocalExpression {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
-        allocator.text(&ctx.func.locals[self.local_index as usize].name)
+        allocator
+            .text(&ctx.func.local(self.local_index).name)
+            .annotate(Token::Name)
     }
 }
 
 impl GetLocalNExpression {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
         allocator.intersperse(
-            self.local_indices
-                .iter()
-                .map(|x| allocator.text(&ctx.func.locals[*x as usize].name)),
+            self.local_indices.iter().map(|x| {
+                allocator
+                    .text(&ctx.func.local(*x).name)
+                    .annotate(Token::Name)
+            }),
             allocator.text(", "),
         )
     }
 }
 
 impl GetGlobalExpression {
-    fn pretty<'b, D, A>(&'b self, _ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
-        // TODO: Assign pretty names to globals
+        if let Some(name) = ctx.names.global(self.global_index) {
+            return allocator.text(name).annotate(Token::Name);
+        }
         allocator
             .text("globals")
             .append(allocator.text(self.global_index.to_string()).brackets())
+            .annotate(Token::Name)
     }
 }
 
 impl SelectExpression {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
         self.condition
-            .pretty(ctx, allocator)
+            .pretty_operand(ctx, allocator)
+            .append(allocator.space())
+            .append(allocator.text("?").annotate(Token::Operator))
+            .append(allocator.space())
+            .append(self.on_true.pretty_operand(ctx, allocator))
+            .append(allocator.space())
+            .append(allocator.text(":").annotate(Token::Operator))
             .append(allocator.space())
-            .append(allocator.text("?"))
-            .append(self.on_true.pretty(ctx, allocator))
-            .append(allocator.text(":"))
-            .append(self.on_false.pretty(ctx, allocator))
+            .append(self.on_false.pretty_operand(ctx, allocator))
     }
 }
 
 impl MemoryLoadExpression {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
-        // TODO: offset
-        allocator
-            .text("memory")
-            .append(self.index.pretty(ctx, allocator).brackets())
+        let memory = if ctx.func.multi_memory {
+            pretty_memory(self.arg.memory, ctx, allocator)
+        } else {
+            allocator.text("memory").annotate(Token::Keyword)
+        };
+        let load = match ctx.func.frame_slot(&self.index, &self.arg) {
+            Some(slot) => allocator.text(slot).annotate(Token::Name),
+            None => {
+                memory.append(pretty_address(&self.index, &self.arg, ctx, allocator).brackets())
+            }
+        };
+        match self.kind.simd_name() {
+            Some(name) => pretty_operator_call(name.to_string(), vec![load], allocator),
+            None => load,
+        }
     }
 }
 
 impl MemoryGrowExpression {
-    fn pretty<'b, D, A>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, A>
+    fn pretty<'b, D>(&'b self, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
+        if ctx.func.multi_memory {
+            return pretty_method(
+                pretty_memory(self.memory, ctx, allocator),
+                "grow",
+                vec![self.value.pretty(ctx, allocator)],
+                allocator,
+            );
+        }
         allocator
             .text("memory_grow")
+            .annotate(Token::Keyword)
             .append(self.value.pretty(ctx, allocator).parens())
     }
 }
 
 impl Func {
-    pub(crate) fn pretty<'b, D, A>(&'b self, allocator: &'b D) -> DocBuilder<'b, D, A>
+    pub(crate) fn pretty<'b, D>(
+        &'b self,
+        indent: usize,
+        allocator: &'b D,
+    ) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
         let params = self.ty.params();
         let num_params = params.len();
@@ -498,43 +1326,96 @@ impl Func {
         let param_group = if params.is_empty() {
             allocator.nil()
         } else {
+            // What each param seems to be for, as in the ABI summary, e.g.
+            // `arg0: i32 /* pointer */`.
+            let roles = self.param_roles();
             let mut param_items = vec![];
-            for param in &self.locals[0..num_params] {
+            for (i, (param, role)) in self.locals[0..num_params].iter().zip(roles).enumerate() {
+                let role = match role {
+                    ParamRole::Value => allocator.nil(),
+                    role => allocator.space().append(
+                        allocator
+                            .text(format!("/* {} */", role))
+                            .annotate(Token::Comment),
+                    ),
+                };
                 param_items.push(
                     allocator
                         .text(&param.name)
+                        .annotate(Token::Name)
                         .append(allocator.text(": "))
-                        .append(allocator.text(param.ty.to_string())),
+                        .append(
+                            allocator
+                                .text(self.local_type_name(LocalIdx::from(i as u32), &self.names))
+                                .annotate(Token::Type),
+                        )
+                        .append(role),
                 );
             }
             allocator.intersperse(param_items, allocator.text(", "))
         };
 
-        let local_group = if self.locals.is_empty() {
+        let local_group = if self.locals.len() == num_params {
             allocator.nil()
         } else {
             let mut local_items = vec![];
-            for local in &self.locals[num_params..self.locals.len()] {
+            for (i, local) in self.locals.iter().enumerate().skip(num_params) {
+                // e.g. `frame: i32 // 32 bytes of the stack`
+                let frame = match self.frame_size(LocalIdx::from(i as u32)) {
+                    Some(size) => allocator.space().append(
+                        allocator
+                            .text(format!("// {} bytes of the stack", size))
+                            .annotate(Token::Comment),
+                    ),
+                    None => allocator.nil(),
+                };
                 local_items.push(
                     allocator
                         .text(&local.name)
+                        .annotate(Token::Name)
                         .append(allocator.text(": "))
-                        .append(allocator.text(local.ty.to_string())),
+                        .append(
+                            allocator
+                                .text(self.local_type_name(LocalIdx::from(i as u32), &self.names))
+                                .annotate(Token::Type),
+                        )
+                        .append(frame),
                 );
             }
             allocator
                 .intersperse(local_items, allocator.hardline())
-                .indent(2)
+                .indent(indent)
                 .enclose(allocator.hardline(), allocator.hardline())
         };
 
-        let block_group = if self.blocks.is_empty() {
+        let block_group = if let Some(error) = &self.error {
+            allocator
+                .text(format!("// {}", error))
+                .annotate(Token::Comment)
+                .indent(indent)
+                .enclose(allocator.hardline(), allocator.hardline())
+        } else if self.blocks.is_empty() {
             allocator.nil()
+        } else if let Some(nodes) = &self.structure {
+            let ctx = Ctx {
+                func: self,
+                names: &self.names,
+                indent,
+            };
+            allocator
+                .intersperse(
+                    (nodes.iter())
+                        .filter(|node| node.is_printed(ctx))
+                        .map(|node| node.pretty(ctx, None, allocator)),
+                    allocator.hardline(),
+                )
+                .indent(indent)
+                .enclose(allocator.hardline(), allocator.hardline())
         } else {
             let mut block_items = vec![];
 
             let visual_block_order = self.visual_block_order();
-            assert!(self.entry_block == visual_block_order[0]);
+            debug_assert!(self.entry_block == visual_block_order[0]);
             for index in &visual_block_order {
                 let block = self.blocks.get(index).unwrap();
                 let is_last_block = *index == visual_block_order[visual_block_order.len() - 1];
@@ -542,7 +1423,11 @@ impl Func {
                     self,
                     *index,
                     is_last_block,
-                    Ctx { func: self },
+                    Ctx {
+                        func: self,
+                        names: &self.names,
+                        indent,
+                    },
                     allocator,
                 ));
             }
@@ -555,38 +1440,590 @@ impl Func {
                 .enclose(allocator.hardline(), allocator.hardline())
         };
 
-        let func_body = local_group.append(block_group).braces();
+        let trailing_group = if self.trailing_instructions.is_empty() {
+            allocator.nil()
+        } else {
+            pretty_instructions(&self.trailing_instructions, allocator)
+                .indent(indent)
+                .append(allocator.hardline())
+        };
 
-        allocator
-            .text(format!("func {}", self.index))
+        let func_body = local_group
+            .append(block_group)
+            .append(trailing_group)
+            .braces();
+
+        let notice = match self.over_budget {
+            Some(budget) => allocator
+                .text(format!(
+                    "// ran out of its {}ms budget, so control flow is only partly reconstructed",
+                    budget.as_millis()
+                ))
+                .annotate(Token::Comment)
+                .append(allocator.hardline()),
+            None => allocator.nil(),
+        };
+
+        notice
+            .append(allocator.text("func").annotate(Token::Keyword))
+            .append(allocator.space())
+            .append(
+                allocator
+                    .text(pretty_func_header_name(self.index, &self.names))
+                    .annotate(Token::Function),
+            )
             .append(param_group.parens())
+            .append(if self.is_start {
+                allocator
+                    .space()
+                    .append(allocator.text("start").annotate(Token::Keyword).parens())
+            } else {
+                allocator.nil()
+            })
+            .append(allocator.concat(self.exports.iter().map(|name| {
+                allocator.space().append(
+                    allocator
+                        .text("export")
+                        .annotate(Token::Keyword)
+                        .append(allocator.space())
+                        .append(
+                            allocator
+                                .text(format!("{:?}", name))
+                                .annotate(Token::Literal),
+                        )
+                        .parens(),
+                )
+            })))
             .append(allocator.space())
             .append(func_body)
     }
 }
 
+/// A piece of a module's printed output. The parts are printed in order,
+/// each starting at column zero, with a blank line between them.
+pub(crate) enum ModulePart<'m> {
+    Header,
+    Toolchain(&'m Toolchain),
+    SourceMappingUrl(&'m str),
+    Globals(Vec<GlobalDef>),
+    EntryPoints(Vec<EntryPoint>),
+    GlobalReport(Vec<GlobalReport>),
+    Frozen(&'m [Frozen]),
+    Func(&'m Func),
+    // The number of functions left out by `Limits::max_funcs`.
+    OmittedFuncs(usize),
+    Footer,
+}
+
 impl Module {
-    pub(crate) fn pretty<'b, D, A>(&'b self, allocator: &'b D) -> DocBuilder<'b, D, A>
+    pub(crate) fn parts(&self) -> Vec<ModulePart<'_>> {
+        let mut parts = vec![ModulePart::Header];
+        if !self.toolchain.is_empty() {
+            parts.push(ModulePart::Toolchain(&self.toolchain));
+        }
+        if let Some(url) = &self.source_mapping_url {
+            parts.push(ModulePart::SourceMappingUrl(url));
+        }
+        let globals = self.global_defs();
+        if !globals.is_empty() {
+            parts.push(ModulePart::Globals(globals));
+        }
+        let entry_points = self.entry_points();
+        if !entry_points.is_empty() {
+            parts.push(ModulePart::EntryPoints(entry_points));
+        }
+        // The report needs a dataflow pass over every function, which quick
+        // mode skips, and is wrong if some weren't decompiled.
+        if !self.quick && self.omitted_funcs == 0 {
+            let global_reports = self.global_report();
+            if !global_reports.is_empty() {
+                parts.push(ModulePart::GlobalReport(global_reports));
+            }
+        }
+        if !self.frozen.is_empty() {
+            parts.push(ModulePart::Frozen(&self.frozen));
+        }
+        parts.extend(self.printed_funcs().into_iter().map(ModulePart::Func));
+        if self.omitted_funcs > 0 {
+            parts.push(ModulePart::OmittedFuncs(self.omitted_funcs));
+        }
+        parts.push(ModulePart::Footer);
+        parts
+    }
+
+    pub(crate) fn pretty_part<'b, D>(
+        &'b self,
+        part: &'b ModulePart<'b>,
+        indent: usize,
+        allocator: &'b D,
+    ) -> DocBuilder<'b, D, Token>
+    where
+        D: DocAllocator<'b, Token>,
+        D::Doc: Clone,
+    {
+        match part {
+            ModulePart::Header => allocator
+                .text("module")
+                .annotate(Token::Keyword)
+                .append(allocator.text(" {")),
+            ModulePart::Toolchain(toolchain) => allocator
+                .text(format!("// {}", toolchain))
+                .annotate(Token::Comment),
+            ModulePart::SourceMappingUrl(url) => {
+                // An inline map is only worth its media type.
+                let url = match url.split_once(',') {
+                    Some((media_type, data)) if url.starts_with("data:") => {
+                        format!("{},... ({} bytes)", media_type, data.len())
+                    }
+                    _ => url.to_string(),
+                };
+                allocator
+                    .text(format!("// sourceMappingURL: {}", url))
+                    .annotate(Token::Comment)
+            }
+            ModulePart::EntryPoints(entry_points) => {
+                let entry_points: Vec<String> =
+                    entry_points.iter().map(ToString::to_string).collect();
+                allocator
+                    .text(format!("// entry points: {}", entry_points.join(", ")))
+                    .annotate(Token::Comment)
+            }
+            ModulePart::Globals(globals) => allocator.intersperse(
+                globals
+                    .iter()
+                    .map(|global| allocator.text(global.to_string())),
+                allocator.hardline(),
+            ),
+            ModulePart::GlobalReport(reports) => allocator.intersperse(
+                reports.iter().map(|report| {
+                    allocator
+                        .text(format!("// {}", report))
+                        .annotate(Token::Comment)
+                }),
+                allocator.hardline(),
+            ),
+            ModulePart::Frozen(frozen) => allocator.intersperse(
+                frozen.iter().map(|frozen| {
+                    allocator
+                        .text(format!("// frozen: {}", frozen))
+                        .annotate(Token::Comment)
+                }),
+                allocator.hardline(),
+            ),
+            ModulePart::Func(func) => self.pretty_func(func, indent, allocator),
+            ModulePart::OmittedFuncs(count) => allocator
+                .text(format!(
+                    "// {} more functions not decompiled, over the limit of {}",
+                    count,
+                    self.funcs.len()
+                ))
+                .annotate(Token::Comment),
+            ModulePart::Footer => allocator.text("}"),
+        }
+    }
+
+    // A function, or with `dedup`, `func 42 = alias of func 17` if it's a
+    // duplicate. Named functions are referred to by name.
+    pub(crate) fn pretty_func<'b, D>(
+        &'b self,
+        func: &'b Func,
+        indent: usize,
+        allocator: &'b D,
+    ) -> DocBuilder<'b, D, Token>
     where
-        D: DocAllocator<'b, A>,
+        D: DocAllocator<'b, Token>,
         D::Doc: Clone,
-        A: Clone,
     {
+        let original = match self.duplicate_of(func.index) {
+            Some(original) if self.dedup => original,
+            _ => return func.pretty(indent, allocator),
+        };
         allocator
-            .text("module")
+            .text("func")
+            .annotate(Token::Keyword)
             .append(allocator.space())
             .append(
                 allocator
-                    .intersperse(
-                        self.funcs.iter().map(|func| func.pretty(allocator)),
-                        allocator.hardline().append(allocator.hardline()),
-                    )
-                    .enclose(
-                        allocator.hardline().append(allocator.hardline()),
-                        allocator.hardline().append(allocator.hardline()),
-                    )
-                    .braces(),
+                    .text(pretty_func_header_name(func.index, &self.names))
+                    .annotate(Token::Function),
+            )
+            .append(allocator.text(" = "))
+            .append(allocator.text("alias of func").annotate(Token::Keyword))
+            .append(allocator.space())
+            .append(
+                allocator
+                    .text(pretty_func_header_name(original, &self.names))
+                    .annotate(Token::Function),
             )
-            .append(allocator.hardline())
+    }
+
+    // Renders one part at a time, so that only its document is held in
+    // memory.
+    pub(crate) fn write_module(
+        &self,
+        output: &mut dyn std::io::Write,
+        options: &RenderOptions,
+    ) -> anyhow::Result<()> {
+        options.begin(output)?;
+        for (i, part) in self.parts().iter().enumerate() {
+            if i > 0 {
+                write!(output, "\n\n")?;
+            }
+            options.render_part(
+                self.pretty_part(part, options.indent, &pretty::BoxAllocator),
+                output,
+            )?;
+        }
+        // The output ends with a blank line.
+        write!(output, "\n\n")?;
+        options.end(output)
+    }
+
+    pub(crate) fn write_module_with(&self, printer: &mut dyn DecompilePrint) -> anyhow::Result<()> {
+        let mut writer = PrintWriter::new(printer);
+        for (i, part) in self.parts().iter().enumerate() {
+            if i > 0 {
+                writer.write_str("\n\n")?;
+            }
+            writer.render(self.pretty_part(part, INDENT, &pretty::BoxAllocator))?;
+        }
+        // The output ends with a blank line.
+        writer.write_str("\n\n")?;
+        Ok(())
+    }
+}
+
+fn pretty_label<'b, D>(target: BlockIndex, allocator: &'b D) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    allocator.text(target.to_string()).annotate(Token::Label)
+}
+
+fn pretty_br<'b, D>(target: BlockIndex, allocator: &'b D) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    allocator
+        .text("br")
+        .annotate(Token::Keyword)
+        .append(allocator.space())
+        .append(pretty_label(target, allocator))
+}
+
+// An operator printed like a call, e.g. `v128.bitselect(a, b, c)`.
+fn pretty_operator_call<'b, D>(
+    name: String,
+    args: Vec<DocBuilder<'b, D, Token>>,
+    allocator: &'b D,
+) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    allocator
+        .text(name)
+        .annotate(Token::Operator)
+        .append(allocator.intersperse(args, allocator.text(", ")).parens())
+}
+
+// One comment line per instruction, e.g. `// @0x2f: local.get 0`.
+fn pretty_instructions<'b, D>(
+    instructions: &'b [(usize, String)],
+    allocator: &'b D,
+) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    allocator.intersperse(
+        instructions.iter().map(|(offset, instruction)| {
+            allocator
+                .text(format!("// @{:#x}: {}", offset, instruction))
+                .annotate(Token::Comment)
+        }),
+        allocator.hardline(),
+    )
+}
+
+// A function's name after the `func` keyword, or its bare index if it has
+// none, as in `func 3(...)`.
+fn pretty_func_header_name(func_index: FuncIdx, names: &Names) -> String {
+    match names.func(func_index) {
+        Some(name) => name.to_string(),
+        None => func_index.to_string(),
+    }
+}
+
+fn pretty_table<'b, D>(table: u32, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    allocator.text(ctx.names.table(table)).annotate(Token::Name)
+}
+
+fn pretty_tag<'b, D>(tag: u32, allocator: &'b D) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    allocator.text(format!("tag{}", tag)).annotate(Token::Name)
+}
+
+// e.g. `table0.grow(value, delta)`
+fn pretty_table_method<'b, D>(
+    table: u32,
+    method: &'static str,
+    args: Vec<DocBuilder<'b, D, Token>>,
+    ctx: Ctx<'b>,
+    allocator: &'b D,
+) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    pretty_method(pretty_table(table, ctx, allocator), method, args, allocator)
+}
+
+fn pretty_memory<'b, D>(memory: u32, ctx: Ctx<'b>, allocator: &'b D) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    allocator
+        .text(ctx.names.memory(memory))
+        .annotate(Token::Name)
+}
+
+// `receiver.method(args)`, e.g. `table0.size()`.
+fn pretty_method<'b, D>(
+    receiver: DocBuilder<'b, D, Token>,
+    method: &'static str,
+    args: Vec<DocBuilder<'b, D, Token>>,
+    allocator: &'b D,
+) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    receiver
+        .append(allocator.text("."))
+        .append(allocator.text(method).annotate(Token::Keyword))
+        .append(allocator.intersperse(args, allocator.text(", ")).parens())
+}
+
+// e.g. `x.field2` or `x.field2_s`
+fn pretty_field<'b, D>(
+    object: &'b Expression,
+    field: u32,
+    extension: Extension,
+    ctx: Ctx<'b>,
+    allocator: &'b D,
+) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    object
+        .pretty_operand(ctx, allocator)
+        .append(allocator.text("."))
+        .append(
+            allocator
+                .text(format!("field{}{}", field, extension.suffix()))
+                .annotate(Token::Name),
+        )
+}
+
+// An integer constant, or if it's an address inside a data segment, e.g.
+// `0x1040 /* data[2]+0x40 */`. `bits` is the constant's width.
+fn pretty_int_const<'b, D>(
+    value: i64,
+    bits: u32,
+    ctx: Ctx<'b>,
+    allocator: &'b D,
+) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    let address = unsigned(value, bits);
+    let Some((segment, offset)) = ctx.func.data_segment_at(address) else {
+        return allocator
+            .text(int_literal(value, bits))
+            .annotate(Token::Literal);
+    };
+    let segment = ctx.names.data_segment(segment);
+    let comment = if offset == 0 {
+        format!("/* {} */", segment)
+    } else {
+        format!("/* {}+{:#x} */", segment, offset)
+    };
+    allocator
+        .text(format!("{:#x}", address))
+        .annotate(Token::Literal)
+        .append(allocator.space())
+        .append(allocator.text(comment).annotate(Token::Comment))
+}
+
+// The address a load or store accesses, with the access's offset added to
+// the index, e.g. `memory[arg0 + 8]`. A constant address has the offset
+// added in, so that `i32.load offset=0x20 (i32.const 0x10400)` is
+// `memory[0x10420 /* .data+0x20 */]`.
+fn pretty_address<'b, D>(
+    index: &'b Expression,
+    arg: &wasm::MemArg,
+    ctx: Ctx<'b>,
+    allocator: &'b D,
+) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    let base = match index {
+        Expression::I32Const { value } => *value as u32 as u64,
+        Expression::I64Const { value } => *value as u64,
+        _ if arg.offset == 0 => return index.pretty(ctx, allocator),
+        _ => {
+            return index
+                .pretty_operand(ctx, allocator)
+                .append(allocator.space())
+                .append(allocator.text("+").annotate(Token::Operator))
+                .append(allocator.space())
+                .append(
+                    allocator
+                        .text(int_literal(arg.offset as i64, 64))
+                        .annotate(Token::Literal),
+                );
+        }
+    };
+    let address = base.wrapping_add(arg.offset);
+    // Data segments are only known for the first memory.
+    if arg.memory == 0 && ctx.func.data_segment_at(address).is_some() {
+        return pretty_int_const(address as i64, 64, ctx, allocator);
+    }
+    allocator
+        .text(hex_literal(address))
+        .annotate(Token::Literal)
+}
+
+// A constant's bits, as an unsigned number.
+fn unsigned(value: i64, bits: u32) -> u64 {
+    if bits == 32 {
+        value as u32 as u64
+    } else {
+        value as u64
+    }
+}
+
+// An integer in the base that best shows what it's for: hex for what looks
+// like bits, such as a mask (`0xff00`), a power of two (`0x10000`) or a
+// round hex number, and decimal for anything else, small numbers like
+// counts and offsets included. A negative number stays negative unless
+// it's only a bit or two, like `0x80000000`. Where it's used as a mask,
+// `Expression::pretty_mask` prints it in hex regardless.
+fn int_literal(value: i64, bits: u32) -> String {
+    if (-256..=256).contains(&value) {
+        return value.to_string();
+    }
+    let value_bits = unsigned(value, bits);
+    // One run of ones, e.g. `0xff00`.
+    let is_run = |bits: u64| {
+        let run = bits >> bits.trailing_zeros();
+        run & run.wrapping_add(1) == 0
+    };
+    let looks_like_bits = value_bits.count_ones() <= 2
+        || value > 0 && (is_run(value_bits) || value_bits.is_multiple_of(0x1000));
+    if looks_like_bits {
+        hex_literal(value_bits)
+    } else {
+        value.to_string()
+    }
+}
+
+// e.g. `0xff`, though digits that read the same in either base stay
+// decimal.
+fn hex_literal(value: u64) -> String {
+    if value < 10 {
+        value.to_string()
+    } else {
+        format!("{:#x}", value)
+    }
+}
+
+// The values a branch passes to its target's params, e.g.
+// ` with (/* b0 := */ x, /* b1 := */ 1)`, or nothing if there are none.
+fn pretty_with<'b, D>(
+    params: &'b [Expression],
+    ctx: Ctx<'b>,
+    allocator: &'b D,
+) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    if params.is_empty() {
+        return allocator.nil();
+    }
+    let params = params.iter().enumerate().map(|(i, param)| {
+        allocator
+            .text(format!("/* b{} := */", i))
+            .annotate(Token::Comment)
+            .append(allocator.space())
+            .append(param.pretty(ctx, allocator))
+    });
+    allocator
+        .space()
+        .append(allocator.text("with").annotate(Token::Keyword))
+        .append(allocator.space())
+        .append(allocator.intersperse(params, allocator.text(", ")).parens())
+}
+
+// e.g. `data[2]`
+fn pretty_segment<'b, D>(segment: Segment, allocator: &'b D) -> DocBuilder<'b, D, Token>
+where
+    D: DocAllocator<'b, Token>,
+    D::Doc: Clone,
+{
+    let (space, index) = match segment {
+        Segment::Data(index) => ("data", index),
+        Segment::Elem(index) => ("elem", index),
+    };
+    allocator
+        .text(space)
+        .append(allocator.text(index.to_string()).brackets())
+        .annotate(Token::Name)
+}
+
+// The wat syntax for a type, except that concrete types are named `typeN`
+// or by their name.
+pub(crate) fn val_type_name(ty: wasm::ValType, names: &Names) -> String {
+    match ty {
+        wasm::ValType::Ref(ref_type) => {
+            ref_type_name(ref_type.is_nullable(), ref_type.heap_type(), names)
+        }
+        _ => ty.to_string(),
+    }
+}
+
+fn ref_type_name(nullable: bool, heap_type: wasm::HeapType, names: &Names) -> String {
+    let name = match heap_type {
+        wasm::HeapType::Concrete(wasm::UnpackedIndex::Module(index)) => {
+            names.type_name(index.into())
+        }
+        wasm::HeapType::Concrete(index) => index.to_string(),
+        _ => {
+            if let Some(ref_type) = wasm::RefType::new(nullable, heap_type) {
+                return ref_type.to_string();
+            }
+            format!("{:?}", heap_type)
+        }
+    };
+    if nullable {
+        format!("(ref null {})", name)
+    } else {
+        format!("(ref {})", name)
     }
 }
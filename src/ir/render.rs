@@ -0,0 +1,391 @@
+use std::io;
+
+use pretty::{BoxAllocator, DocBuilder, Render, RenderAnnotated};
+
+use crate::ir::*;
+
+/// How printed output is highlighted.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum OutputFormat {
+    /// Plain text.
+    #[default]
+    Text,
+    /// Text with ANSI escape codes, for terminals.
+    Ansi,
+    /// An HTML fragment: a stylesheet generated from the theme followed by a
+    /// `<pre>` element with a `<span>` per token.
+    Html,
+}
+
+/// Receives printed output along with the category of each token, for
+/// embedders that highlight it themselves. Modeled on `wasmprinter::Print`:
+/// each `start_*` call is followed by the text of the token and then
+/// [`reset_color`](DecompilePrint::reset_color), and tokens are never
+/// nested, so a tokenizer that keeps a stack and one that doesn't both work.
+pub trait DecompilePrint {
+    fn write_str(&mut self, s: &str) -> io::Result<()>;
+
+    fn start_keyword(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn start_name(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn start_literal(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn start_type(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn start_comment(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Defaults to [`start_name`](DecompilePrint::start_name).
+    fn start_label(&mut self) -> io::Result<()> {
+        self.start_name()
+    }
+
+    /// Defaults to [`start_name`](DecompilePrint::start_name).
+    fn start_function(&mut self) -> io::Result<()> {
+        self.start_name()
+    }
+
+    /// Defaults to [`start_keyword`](DecompilePrint::start_keyword).
+    fn start_operator(&mut self) -> io::Result<()> {
+        self.start_keyword()
+    }
+
+    fn reset_color(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Options for printing a module or function.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub format: OutputFormat,
+    /// Only used by the ANSI and HTML formats. Defaults to [`Theme::dark`].
+    pub theme: Theme,
+    /// The column lines are wrapped at where they can be. Defaults to 80.
+    pub width: usize,
+    /// How many spaces each level of nesting is indented by. Defaults to 2.
+    pub indent: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            format: OutputFormat::default(),
+            theme: Theme::dark(),
+            width: WIDTH,
+            indent: INDENT,
+        }
+    }
+}
+
+pub(crate) const WIDTH: usize = 80;
+pub(crate) const INDENT: usize = 2;
+
+// The class of the `<pre>` element, which scopes the generated stylesheet.
+const HTML_CLASS: &str = "wasm-decompile";
+
+impl RenderOptions {
+    pub(crate) fn render(
+        &self,
+        doc: DocBuilder<'_, BoxAllocator, Token>,
+        output: &mut dyn io::Write,
+    ) -> anyhow::Result<()> {
+        self.begin(output)?;
+        self.render_part(doc, output)?;
+        writeln!(output)?;
+        self.end(output)
+    }
+
+    // Writes what comes before the rendered documents.
+    pub(crate) fn begin(&self, output: &mut dyn io::Write) -> anyhow::Result<()> {
+        if self.format == OutputFormat::Html {
+            write_stylesheet(&self.theme, output)?;
+            write!(output, "<pre class=\"{}\">", HTML_CLASS)?;
+        }
+        Ok(())
+    }
+
+    // Renders one document. A document can be rendered in several parts,
+    // between `begin` and `end`, as long as each part starts at column zero.
+    pub(crate) fn render_part(
+        &self,
+        doc: DocBuilder<'_, BoxAllocator, Token>,
+        output: &mut dyn io::Write,
+    ) -> anyhow::Result<()> {
+        match self.format {
+            OutputFormat::Text => doc.render(self.width, output)?,
+            OutputFormat::Ansi => {
+                let mut writer = AnsiWriter {
+                    upstream: &mut *output,
+                    theme: &self.theme,
+                    styles: Vec::new(),
+                };
+                doc.render_raw(self.width, &mut writer)?;
+            }
+            OutputFormat::Html => doc.render_raw(
+                self.width,
+                &mut HtmlWriter {
+                    upstream: &mut *output,
+                    spans: Vec::new(),
+                },
+            )?,
+        }
+        Ok(())
+    }
+
+    // Writes what comes after the rendered documents.
+    pub(crate) fn end(&self, output: &mut dyn io::Write) -> anyhow::Result<()> {
+        if self.format == OutputFormat::Html {
+            writeln!(output, "</pre>")?;
+        }
+        Ok(())
+    }
+}
+
+struct AnsiWriter<'a, W> {
+    upstream: W,
+    theme: &'a Theme,
+    // The styles of the enclosing annotations, innermost last.
+    styles: Vec<Style>,
+}
+
+impl<W: io::Write> AnsiWriter<'_, W> {
+    fn set_style(&mut self, style: Style) -> io::Result<()> {
+        let mut codes = vec![];
+        if style.bold {
+            codes.push("1".to_string());
+        }
+        if style.italic {
+            codes.push("3".to_string());
+        }
+        if let Some(Color(r, g, b)) = style.color {
+            codes.push(format!("38;2;{};{};{}", r, g, b));
+        }
+        if codes.is_empty() {
+            return Ok(());
+        }
+        write!(self.upstream, "\x1b[{}m", codes.join(";"))
+    }
+}
+
+impl<W: io::Write> Render for AnsiWriter<'_, W> {
+    type Error = io::Error;
+
+    fn write_str(&mut self, s: &str) -> io::Result<usize> {
+        self.upstream.write(s.as_bytes())
+    }
+
+    fn write_str_all(&mut self, s: &str) -> io::Result<()> {
+        self.upstream.write_all(s.as_bytes())
+    }
+
+    fn fail_doc(&self) -> io::Error {
+        io::Error::other("Document failed to render")
+    }
+}
+
+impl<W: io::Write> RenderAnnotated<'_, Token> for AnsiWriter<'_, W> {
+    fn push_annotation(&mut self, token: &Token) -> io::Result<()> {
+        let style = self.theme.style(*token);
+        self.styles.push(style);
+        self.set_style(style)
+    }
+
+    fn pop_annotation(&mut self) -> io::Result<()> {
+        if self.styles.pop() != Some(Style::default()) {
+            write!(self.upstream, "\x1b[0m")?;
+            if let Some(style) = self.styles.last() {
+                self.set_style(*style)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_stylesheet(theme: &Theme, output: &mut dyn io::Write) -> io::Result<()> {
+    writeln!(output, "<style>")?;
+    let mut page = vec![];
+    if let Some(background) = theme.background {
+        page.push(format!("background: {};", background));
+    }
+    if let Some(foreground) = theme.foreground {
+        page.push(format!("color: {};", foreground));
+    }
+    if !page.is_empty() {
+        writeln!(output, ".{} {{ {} }}", HTML_CLASS, page.join(" "))?;
+    }
+    for token in Token::ALL {
+        let style = theme.style(token);
+        let mut properties = vec![];
+        if let Some(color) = style.color {
+            properties.push(format!("color: {};", color));
+        }
+        if style.bold {
+            properties.push("font-weight: bold;".to_string());
+        }
+        if style.italic {
+            properties.push("font-style: italic;".to_string());
+        }
+        if !properties.is_empty() {
+            writeln!(
+                output,
+                ".{} .{} {{ {} }}",
+                HTML_CLASS,
+                token.name(),
+                properties.join(" ")
+            )?;
+        }
+    }
+    writeln!(output, "</style>")
+}
+
+struct HtmlWriter<W> {
+    upstream: W,
+    // Whether each enclosing annotation opened a span, innermost last.
+    spans: Vec<bool>,
+}
+
+impl<W: io::Write> Render for HtmlWriter<W> {
+    type Error = io::Error;
+
+    fn write_str(&mut self, s: &str) -> io::Result<usize> {
+        self.write_str_all(s)?;
+        Ok(s.len())
+    }
+
+    fn write_str_all(&mut self, s: &str) -> io::Result<()> {
+        let mut rest = s;
+        while let Some(i) = rest.find(['&', '<', '>', '"']) {
+            self.upstream.write_all(&rest.as_bytes()[..i])?;
+            let escaped = match rest.as_bytes()[i] {
+                b'&' => "&amp;",
+                b'<' => "&lt;",
+                b'>' => "&gt;",
+                _ => "&quot;",
+            };
+            self.upstream.write_all(escaped.as_bytes())?;
+            rest = &rest[i + 1..];
+        }
+        self.upstream.write_all(rest.as_bytes())
+    }
+
+    fn fail_doc(&self) -> io::Error {
+        io::Error::other("Document failed to render")
+    }
+}
+
+impl<W: io::Write> RenderAnnotated<'_, Token> for HtmlWriter<W> {
+    fn push_annotation(&mut self, token: &Token) -> io::Result<()> {
+        self.spans.push(!matches!(token, Token::Offset(_)));
+        match token {
+            Token::Offset(_) => Ok(()),
+            _ => write!(self.upstream, "<span class=\"{}\">", token.name()),
+        }
+    }
+
+    fn pop_annotation(&mut self) -> io::Result<()> {
+        match self.spans.pop() {
+            Some(true) => write!(self.upstream, "</span>"),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Forwards rendered text and annotations to a `DecompilePrint`, ending the
+// enclosing token before a nested one starts and restarting it after.
+pub(crate) struct PrintWriter<'a> {
+    printer: &'a mut dyn DecompilePrint,
+    // The enclosing annotations, innermost last.
+    tokens: Vec<Token>,
+}
+
+impl<'a> PrintWriter<'a> {
+    pub(crate) fn new(printer: &'a mut dyn DecompilePrint) -> Self {
+        PrintWriter {
+            printer,
+            tokens: Vec::new(),
+        }
+    }
+
+    pub(crate) fn render(&mut self, doc: DocBuilder<'_, BoxAllocator, Token>) -> io::Result<()> {
+        doc.render_raw(WIDTH, self)
+    }
+
+    pub(crate) fn write_str(&mut self, s: &str) -> io::Result<()> {
+        self.printer.write_str(s)
+    }
+
+    // The innermost enclosing token that's highlighted.
+    fn highlighted(&self) -> Option<Token> {
+        (self.tokens.iter().rev())
+            .find(|token| !matches!(token, Token::Offset(_)))
+            .copied()
+    }
+
+    fn start(&mut self, token: Token) -> io::Result<()> {
+        match token {
+            Token::Keyword => self.printer.start_keyword(),
+            Token::Label => self.printer.start_label(),
+            Token::Name => self.printer.start_name(),
+            Token::Function => self.printer.start_function(),
+            Token::Literal => self.printer.start_literal(),
+            Token::Type => self.printer.start_type(),
+            Token::Operator => self.printer.start_operator(),
+            Token::Comment => self.printer.start_comment(),
+            Token::Offset(_) => Ok(()),
+        }
+    }
+}
+
+impl Render for PrintWriter<'_> {
+    type Error = io::Error;
+
+    fn write_str(&mut self, s: &str) -> io::Result<usize> {
+        self.write_str_all(s)?;
+        Ok(s.len())
+    }
+
+    fn write_str_all(&mut self, s: &str) -> io::Result<()> {
+        self.printer.write_str(s)
+    }
+
+    fn fail_doc(&self) -> io::Error {
+        io::Error::other("Document failed to render")
+    }
+}
+
+impl RenderAnnotated<'_, Token> for PrintWriter<'_> {
+    // Offsets aren't highlighted, so they leave the enclosing token be.
+    fn push_annotation(&mut self, token: &Token) -> io::Result<()> {
+        let highlighted = self.highlighted();
+        self.tokens.push(*token);
+        if let Token::Offset(_) = token {
+            return Ok(());
+        }
+        if highlighted.is_some() {
+            self.printer.reset_color()?;
+        }
+        self.start(*token)
+    }
+
+    fn pop_annotation(&mut self) -> io::Result<()> {
+        if let Some(Token::Offset(_)) | None = self.tokens.pop() {
+            return Ok(());
+        }
+        self.printer.reset_color()?;
+        match self.highlighted() {
+            Some(token) => self.start(token),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::ir::print::val_type_name;
+use crate::ir::*;
+
+/// Whether the operators applied to a local treat it as a signed or an
+/// unsigned number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Signedness {
+    Signed,
+    Unsigned,
+    /// Treated as both, so neither can be assumed.
+    Mixed,
+}
+
+impl Signedness {
+    fn join(self, other: Signedness) -> Signedness {
+        if self == other {
+            self
+        } else {
+            Signedness::Mixed
+        }
+    }
+}
+
+impl BinaryExpression {
+    // The signedness the operator treats its operands as, if it matters to
+    // it, and whether it does for both or, for a shift, only the first.
+    fn signedness(&self) -> Option<(Signedness, bool)> {
+        use BinaryExpression::*;
+        use Signedness::*;
+        Some(match self {
+            I32LtS | I32GtS | I32LeS | I32GeS | I32DivS | I32RemS | I64LtS | I64GtS | I64LeS
+            | I64GeS | I64DivS | I64RemS => (Signed, true),
+            I32LtU | I32GtU | I32LeU | I32GeU | I32DivU | I32RemU | I64LtU | I64GtU | I64LeU
+            | I64GeU | I64DivU | I64RemU => (Unsigned, true),
+            I32ShrS | I64ShrS => (Signed, false),
+            I32ShrU | I64ShrU => (Unsigned, false),
+            _ => return None,
+        })
+    }
+}
+
+impl Func {
+    // Works out the signedness of each integer local from the operators
+    // applied to it directly, so that `i <_u n` can print as `i < n` with
+    // `i` and `n` declared `u32`.
+    pub(crate) fn infer_signedness(&mut self) {
+        fn expression(expr: &Expression, signedness: &mut HashMap<LocalIdx, Signedness>) {
+            if let Expression::Binary(op, lhs, rhs) = expr {
+                if let Some((sign, both)) = op.signedness() {
+                    let operands = if both { vec![lhs, rhs] } else { vec![lhs] };
+                    for operand in operands {
+                        if let Expression::GetLocal(get) = &**operand {
+                            signedness
+                                .entry(get.local_index)
+                                .and_modify(|known| *known = known.join(sign))
+                                .or_insert(sign);
+                        }
+                    }
+                }
+            }
+            for operand in expr.operands() {
+                expression(operand, signedness);
+            }
+        }
+
+        fn statements(stmts: &[Statement], signedness: &mut HashMap<LocalIdx, Signedness>) {
+            for stmt in stmts {
+                for expr in stmt.expressions() {
+                    expression(expr, signedness);
+                }
+                for body in stmt.bodies() {
+                    statements(body, signedness);
+                }
+            }
+        }
+
+        let mut signedness = HashMap::new();
+        for block in self.blocks.values() {
+            statements(&block.statements, &mut signedness);
+            for expr in block.terminator.expressions() {
+                expression(expr, &mut signedness);
+            }
+        }
+        self.signedness = signedness;
+    }
+
+    // The type a local is declared with: `u32` or `u64` for an integer only
+    // ever treated as unsigned.
+    pub(crate) fn local_type_name(&self, local_index: LocalIdx, names: &Names) -> String {
+        let ty = self.local(local_index).ty;
+        match (ty, self.signedness.get(&local_index)) {
+            (wasm::ValType::I32, Some(Signedness::Unsigned)) => "u32".to_string(),
+            (wasm::ValType::I64, Some(Signedness::Unsigned)) => "u64".to_string(),
+            (ty, _) => val_type_name(ty, names),
+        }
+    }
+
+    // Whether `op` can print without its `_s` or `_u` suffix: it's applied
+    // to a local of its signedness, and to none that's of the other or of
+    // both.
+    pub(crate) fn implies_signedness(
+        &self,
+        op: &BinaryExpression,
+        lhs: &Expression,
+        rhs: &Expression,
+    ) -> bool {
+        let Some((sign, both)) = op.signedness() else {
+            return false;
+        };
+        let operands = if both { vec![lhs, rhs] } else { vec![lhs] };
+        let mut implied = false;
+        for operand in operands {
+            let Expression::GetLocal(get) = operand else {
+                continue;
+            };
+            match self.signedness.get(&get.local_index) {
+                Some(known) if *known == sign => implied = true,
+                Some(_) => return false,
+                None => {}
+            }
+        }
+        implied
+    }
+}
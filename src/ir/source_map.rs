@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context};
+use wasmparser as wasm;
+
+/// A position in the original source or in the module, as printed before
+/// the statements it produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceLocation {
+    Line {
+        file: Arc<str>,
+        /// 1-based.
+        line: u32,
+    },
+    /// The byte range in the module of the instruction, with
+    /// [`DecodeOptions::offsets`](crate::DecodeOptions::offsets). Printed
+    /// as where it starts.
+    Offset(Range<usize>),
+    /// The instructions a statement was decoded from, with their byte
+    /// offsets and as they print in the text format, with
+    /// [`DecodeOptions::interleave`](crate::DecodeOptions::interleave).
+    Instructions(Vec<(usize, String)>),
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceLocation::Line { file, line } => write!(f, "{}:{}", file, line),
+            SourceLocation::Offset(offsets) => write!(f, "@{:#x}", offsets.start),
+            SourceLocation::Instructions(instructions) => {
+                for (i, (offset, instruction)) in instructions.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "@{:#x}: {}", offset, instruction)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for SourceLocation {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        if u.arbitrary()? {
+            let start = u.int_in_range(0..=0xffff)?;
+            let len = u.int_in_range(1..=8)?;
+            return Ok(SourceLocation::Offset(start..start + len));
+        }
+        Ok(SourceLocation::Line {
+            file: Arc::from(*u.choose(&["main.c", "lib/util.rs"])?),
+            line: u.int_in_range(1..=9999)?,
+        })
+    }
+}
+
+/// A version 3 source map for a wasm module, as produced by Emscripten's
+/// `-gsource-map` and similar toolchains.
+///
+/// Wasm source maps have a single generated line whose columns are byte
+/// offsets in the module, so a map is a sorted list of offsets and the
+/// source line each starts.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    // Byte offset in the module and the location it maps to, or `None` for
+    // offsets that are explicitly unmapped.
+    entries: Vec<(u32, Option<SourceLocation>)>,
+}
+
+impl SourceMap {
+    pub fn parse(text: &str) -> anyhow::Result<SourceMap> {
+        let json: serde_json::Value = serde_json::from_str(text)?;
+        if json["version"] != 3 {
+            bail!("unsupported source map version {}", json["version"]);
+        }
+        let source_root = json["sourceRoot"].as_str().unwrap_or("");
+        let Some(sources) = json["sources"].as_array() else {
+            bail!("source map has no `sources`");
+        };
+        let files = sources
+            .iter()
+            .map(|source| match source.as_str() {
+                Some(source) => Ok(Arc::from(format!("{}{}", source_root, source))),
+                None => Err(anyhow!("source map `sources` must be strings")),
+            })
+            .collect::<anyhow::Result<Vec<Arc<str>>>>()?;
+        let Some(mappings) = json["mappings"].as_str() else {
+            bail!("source map has no `mappings`");
+        };
+
+        // Every field is relative to the same field of the previous segment.
+        let (mut offset, mut source, mut line) = (0i64, 0i64, 0i64);
+        let mut entries = Vec::new();
+        let first_line = mappings.split(';').next().unwrap_or("");
+        for segment in first_line.split(',').filter(|segment| !segment.is_empty()) {
+            let fields = decode_vlq(segment)?;
+            offset += fields[0];
+            let location = if fields.len() >= 4 {
+                source += fields[1];
+                line += fields[2];
+                let Some(file) = usize::try_from(source).ok().and_then(|i| files.get(i)) else {
+                    bail!("source map segment `{}` has no such source", segment);
+                };
+                Some(SourceLocation::Line {
+                    file: file.clone(),
+                    line: u32::try_from(line + 1)?,
+                })
+            } else {
+                None
+            };
+            entries.push((u32::try_from(offset)?, location));
+        }
+        entries.sort_by_key(|(offset, _)| *offset);
+
+        Ok(SourceMap { entries })
+    }
+
+    /// The source map a module refers to in its `sourceMappingURL` section,
+    /// or failing that its DWARF line table, or `None` if it has neither.
+    ///
+    /// A relative URL is resolved against `dir`, the directory the module
+    /// was read from. Without it, only inline `data:` URLs are loaded. A map
+    /// that can't be loaded is skipped.
+    pub fn from_module(module: &[u8], dir: Option<&Path>) -> Option<SourceMap> {
+        let sections = DebugSections::read(module).ok()?;
+        let url_map = sections
+            .source_mapping_url
+            .and_then(|url| SourceMap::load(url, dir).ok());
+        url_map.or_else(|| SourceMap::dwarf(&sections).ok().flatten())
+    }
+
+    /// The source map at a `sourceMappingURL`: a `data:` URL, or a path or
+    /// `file://` URL, relative ones resolved against `dir`. Other URLs
+    /// aren't fetched.
+    pub fn load(url: &str, dir: Option<&Path>) -> anyhow::Result<SourceMap> {
+        if let Some(data) = url.strip_prefix("data:") {
+            let Some((media_type, data)) = data.split_once(',') else {
+                bail!("invalid data URL");
+            };
+            let bytes = if media_type.ends_with(";base64") {
+                decode_base64(data)?
+            } else {
+                percent_decode(data)
+            };
+            return SourceMap::parse(std::str::from_utf8(&bytes)?);
+        }
+        let path = match url.strip_prefix("file://") {
+            Some(path) => Path::new(path),
+            None if url.contains("://") => bail!("can't fetch the source map at `{}`", url),
+            None => Path::new(url),
+        };
+        let path = match dir {
+            _ if path.is_absolute() => path.to_path_buf(),
+            Some(dir) => dir.join(path),
+            None => bail!("no directory to find the source map `{}` in", url),
+        };
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("cannot read `{}`", path.display()))?;
+        SourceMap::parse(&text)
+    }
+
+    /// The line table in a module's embedded DWARF `.debug_line` section,
+    /// or `None` if it has none.
+    ///
+    /// DWARF addresses are offsets into the code section. Sequences at
+    /// address 0, which is where linkers put discarded functions, are left
+    /// out.
+    pub fn from_dwarf(module: &[u8]) -> anyhow::Result<Option<SourceMap>> {
+        SourceMap::dwarf(&DebugSections::read(module)?)
+    }
+
+    fn dwarf(sections: &DebugSections) -> anyhow::Result<Option<SourceMap>> {
+        let DebugSections {
+            code_start: Some(code_start),
+            dwarf: ref sections,
+            ..
+        } = *sections
+        else {
+            return Ok(None);
+        };
+        let Some(debug_line) = sections.get(".debug_line").copied() else {
+            return Ok(None);
+        };
+        let section = |name: &str| {
+            gimli::EndianSlice::new(
+                sections.get(name).copied().unwrap_or(&[]),
+                gimli::LittleEndian,
+            )
+        };
+        let debug_line = gimli::DebugLine::new(debug_line, gimli::LittleEndian);
+        let debug_str = gimli::DebugStr::from(section(".debug_str"));
+        let debug_line_str = gimli::DebugLineStr::from(section(".debug_line_str"));
+        let string = |value: gimli::AttributeValue<gimli::EndianSlice<gimli::LittleEndian>>| {
+            let bytes = match value {
+                gimli::AttributeValue::String(bytes) => bytes,
+                gimli::AttributeValue::DebugStrRef(offset) => debug_str.get_str(offset)?,
+                gimli::AttributeValue::DebugLineStrRef(offset) => debug_line_str.get_str(offset)?,
+                _ => return Err(gimli::Error::UnsupportedAttributeForm),
+            };
+            Ok(String::from_utf8_lossy(&bytes).into_owned())
+        };
+
+        let mut files: HashMap<String, Arc<str>> = HashMap::new();
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while offset < sections[".debug_line"].len() {
+            // wasm32 addresses are 4 bytes. DWARF 5 headers say so
+            // themselves.
+            let program = debug_line.program(gimli::DebugLineOffset(offset), 4, None, None)?;
+            offset += program.header().format().initial_length_size() as usize
+                + program.header().unit_length();
+            let mut rows = program.rows();
+            let mut sequence = Vec::new();
+            while let Some((header, row)) = rows.next_row()? {
+                let Ok(address) = u32::try_from(row.address() + code_start as u64) else {
+                    continue;
+                };
+                if row.end_sequence() {
+                    if sequence
+                        .first()
+                        .is_some_and(|(start, _)| *start != code_start as u32)
+                    {
+                        entries.append(&mut sequence);
+                        entries.push((address, None));
+                    }
+                    sequence.clear();
+                    continue;
+                }
+                let Some(file) = row.file(header) else {
+                    continue;
+                };
+                let mut path = string(file.path_name())?;
+                if let Some(directory) = file.directory(header) {
+                    let directory = string(directory)?;
+                    if !path.starts_with('/') && !directory.is_empty() {
+                        path = format!("{}/{}", directory.trim_end_matches('/'), path);
+                    }
+                }
+                let file = files
+                    .entry(path)
+                    .or_insert_with_key(|path| Arc::from(path.as_str()));
+                let location = row.line().map(|line| SourceLocation::Line {
+                    file: file.clone(),
+                    line: line.get() as u32,
+                });
+                sequence.push((address, location));
+            }
+        }
+        entries.sort_by_key(|(offset, _)| *offset);
+        Ok(Some(SourceMap { entries }))
+    }
+
+    /// The location of the code at a byte offset in the module: that of the
+    /// closest mapped offset at or before it.
+    pub(crate) fn lookup(&self, offset: usize) -> Option<SourceLocation> {
+        let end = self
+            .entries
+            .partition_point(|(entry_offset, _)| *entry_offset as usize <= offset);
+        self.entries[..end].last()?.1.clone()
+    }
+}
+
+// What a module says about where its code came from.
+#[derive(Default)]
+struct DebugSections<'a> {
+    // The offset of the code section's contents in the module.
+    code_start: Option<usize>,
+    // The `.debug_*` custom sections by name.
+    dwarf: HashMap<&'a str, &'a [u8]>,
+    source_mapping_url: Option<&'a str>,
+}
+
+impl<'a> DebugSections<'a> {
+    fn read(module: &'a [u8]) -> anyhow::Result<DebugSections<'a>> {
+        let mut sections = DebugSections::default();
+        for payload in wasm::Parser::new(0).parse_all(module) {
+            match payload? {
+                wasm::Payload::CodeSectionStart { range, .. } => {
+                    sections.code_start = Some(range.start)
+                }
+                wasm::Payload::CustomSection(section) => match section.name() {
+                    "sourceMappingURL" => {
+                        let mut reader =
+                            wasm::BinaryReader::new(section.data(), section.data_offset());
+                        sections.source_mapping_url = Some(reader.read_string()?);
+                    }
+                    name if name.starts_with(".debug_") => {
+                        sections.dwarf.insert(name, section.data());
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        Ok(sections)
+    }
+}
+
+fn base64_digit(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+fn decode_base64(text: &str) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut bits, mut num_bits) = (0u32, 0);
+    for c in text.trim_end_matches('=').bytes() {
+        let Some(digit) = base64_digit(c) else {
+            bail!("invalid base64");
+        };
+        bits = (bits << 6) | digit as u32;
+        num_bits += 6;
+        if num_bits >= 8 {
+            num_bits -= 8;
+            bytes.push((bits >> num_bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+// `%XX` escapes decoded; anything else is kept as is.
+fn percent_decode(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut rest = text.as_bytes();
+    while let Some((&c, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) if c == b'%' => {
+                bytes.push(byte);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(c);
+                rest = tail;
+            }
+        }
+    }
+    bytes
+}
+
+// Decodes a segment of base64 VLQ numbers, e.g. `AAgBC`.
+fn decode_vlq(segment: &str) -> anyhow::Result<Vec<i64>> {
+    let mut values = Vec::new();
+    let mut value: i64 = 0;
+    let mut shift = 0;
+    for c in segment.bytes() {
+        let Some(digit) = base64_digit(c) else {
+            bail!("invalid character in source map segment `{}`", segment);
+        };
+        let digit = digit as i64;
+        if shift > 32 {
+            bail!("source map segment `{}` overflows", segment);
+        }
+        value += (digit & 0b11111) << shift;
+        if digit & 0b100000 != 0 {
+            shift += 5;
+            continue;
+        }
+        // The lowest bit is the sign.
+        values.push(if value & 1 != 0 {
+            -(value >> 1)
+        } else {
+            value >> 1
+        });
+        value = 0;
+        shift = 0;
+    }
+    if shift != 0 {
+        bail!("source map segment `{}` is truncated", segment);
+    }
+    Ok(values)
+}
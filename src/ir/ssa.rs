@@ -0,0 +1,280 @@
+use std::collections::BTreeSet;
+
+use crate::ir::*;
+
+// The definitions of each local that may reach a point, by site.
+type Reaching = Vec<BTreeSet<usize>>;
+
+fn join(reaching: &mut Reaching, other: &Reaching) {
+    for (defs, other_defs) in reaching.iter_mut().zip(other) {
+        defs.extend(other_defs);
+    }
+}
+
+// Each read and write of a local is a site, numbered in the order the
+// function is walked, after one for each local's value on entry. A read
+// shares a web with every write that reaches it, which is SSA with each
+// block's entry joining what reaches it as a phi would, and the phis then
+// coalesced again.
+struct Webs {
+    next_site: usize,
+    // Union-find over the sites.
+    parents: Vec<usize>,
+    // The local each site reads or writes.
+    site_locals: Vec<LocalIdx>,
+    // Set once the webs are known: the local each site's web becomes.
+    renamed: Option<Vec<LocalIdx>>,
+}
+
+impl Webs {
+    fn find(&mut self, site: usize) -> usize {
+        let mut root = site;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        let mut site = site;
+        while self.parents[site] != root {
+            let parent = self.parents[site];
+            self.parents[site] = root;
+            site = parent;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        // The earlier site is the root, so a web's root is its first site.
+        self.parents[a.max(b)] = a.min(b);
+    }
+
+    fn site(&mut self, local_index: LocalIdx) -> usize {
+        let site = self.next_site;
+        self.next_site += 1;
+        if site == self.parents.len() {
+            self.parents.push(site);
+            self.site_locals.push(local_index);
+        }
+        site
+    }
+
+    fn read(&mut self, local_index: &mut LocalIdx, reaching: &Reaching) {
+        let site = self.site(*local_index);
+        if let Some(renamed) = &self.renamed {
+            *local_index = renamed[site];
+            return;
+        }
+        for def in reaching[local_index.as_usize()].clone() {
+            self.union(site, def);
+        }
+    }
+
+    fn write(&mut self, local_index: &mut LocalIdx, reaching: &mut Reaching) {
+        let site = self.site(*local_index);
+        if let Some(renamed) = &self.renamed {
+            *local_index = renamed[site];
+            return;
+        }
+        reaching[local_index.as_usize()] = BTreeSet::from([site]);
+    }
+
+    fn expression(&mut self, expr: &mut Expression, reaching: &Reaching) {
+        match expr {
+            Expression::GetLocal(get) => self.read(&mut get.local_index, reaching),
+            Expression::GetLocalN(get) => {
+                for local_index in &mut get.local_indices {
+                    self.read(local_index, reaching);
+                }
+            }
+            _ => {}
+        }
+        for operand in expr.operands_mut() {
+            self.expression(operand, reaching);
+        }
+    }
+
+    // Once the webs are known the statements are walked once more, in the
+    // same order, to rename each site.
+    fn statements(&mut self, statements: &mut [Statement], reaching: &mut Reaching) {
+        for stmt in statements {
+            match stmt {
+                Statement::LocalSet(stmt) => {
+                    self.expression(&mut stmt.value, reaching);
+                    self.write(&mut stmt.index, reaching);
+                }
+                Statement::LocalSetN(stmt) => {
+                    self.expression(&mut stmt.value, reaching);
+                    for local_index in &mut stmt.index {
+                        self.write(local_index, reaching);
+                    }
+                }
+                Statement::If(stmt) => {
+                    self.expression(&mut stmt.condition, reaching);
+                    let mut reaching_false = reaching.clone();
+                    self.statements(&mut stmt.true_statements, reaching);
+                    self.statements(&mut stmt.false_statements, &mut reaching_false);
+                    join(reaching, &reaching_false);
+                }
+                Statement::Loop(stmt) => {
+                    // What reaches the top of the loop is what reaches it
+                    // from before plus what the last iteration left, found
+                    // by going round until nothing changes. Each time round
+                    // numbers the same sites.
+                    let first_site = self.next_site;
+                    loop {
+                        self.next_site = first_site;
+                        let top = reaching.clone();
+                        let mut bottom = top.clone();
+                        match stmt.kind {
+                            LoopKind::While => {
+                                self.expression(&mut stmt.condition, &bottom);
+                                self.statements(&mut stmt.body, &mut bottom);
+                            }
+                            LoopKind::DoWhile => {
+                                self.statements(&mut stmt.body, &mut bottom);
+                                self.expression(&mut stmt.condition, &bottom);
+                            }
+                        }
+                        join(reaching, &bottom);
+                        if self.renamed.is_some() || *reaching == top {
+                            // A while loop leaves from the top and a
+                            // do-while loop from the bottom.
+                            if stmt.kind == LoopKind::DoWhile {
+                                *reaching = bottom;
+                            }
+                            break;
+                        }
+                    }
+                }
+                stmt => {
+                    for expr in stmt.expressions_mut() {
+                        self.expression(expr, reaching);
+                    }
+                }
+            }
+        }
+    }
+
+    fn block(&mut self, block: &mut Block, reaching: &mut Reaching) {
+        self.statements(&mut block.statements, reaching);
+        for expr in block.terminator.expressions_mut() {
+            self.expression(expr, reaching);
+        }
+    }
+}
+
+impl Func {
+    // Gives each web of a local its own local: a write and the reads it
+    // reaches, joined with other writes that reach the same reads. A local
+    // that's reused for unrelated values becomes one local per value,
+    // named `x_1`, `x_2` and so on after the original. A param keeps its
+    // value on entry, and its name for it.
+    pub(crate) fn split_locals(&mut self) {
+        // As in `tidy_locals`, an exception can leave a `try_table` body
+        // from the middle of a block, which the walk doesn't see.
+        if self.error.is_some()
+            || self
+                .blocks
+                .values()
+                .any(|block| matches!(block.terminator, Terminator::Try(..)))
+        {
+            return;
+        }
+        let num_locals = self.locals.len();
+        let mut webs = Webs {
+            next_site: num_locals,
+            parents: (0..num_locals).collect(),
+            site_locals: (0..num_locals).map(|i| LocalIdx::from(i as u32)).collect(),
+            renamed: None,
+        };
+
+        let order = self.reverse_post_order();
+        let predecessors = self.predecessors();
+        let mut first_sites: HashMap<BlockIndex, usize> = HashMap::new();
+        let mut reaching_out: HashMap<BlockIndex, Reaching> = HashMap::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block_index in &order {
+                let mut reaching = vec![BTreeSet::new(); num_locals];
+                if *block_index == self.entry_block {
+                    for (i, defs) in reaching.iter_mut().enumerate() {
+                        defs.insert(i);
+                    }
+                }
+                for pred in predecessors.get(block_index).into_iter().flatten() {
+                    if let Some(out) = reaching_out.get(pred) {
+                        join(&mut reaching, out);
+                    }
+                }
+                webs.next_site = *first_sites.entry(*block_index).or_insert(webs.next_site);
+                webs.block(self.blocks.get_mut(block_index).unwrap(), &mut reaching);
+                if reaching_out.get(block_index) != Some(&reaching) {
+                    reaching_out.insert(*block_index, reaching);
+                    changed = true;
+                }
+            }
+            webs.next_site = webs.parents.len();
+        }
+
+        // The webs of each local, by their first site. A local's zero on
+        // entry that nothing reads needs no local of its own.
+        let num_params = self.ty.params().len();
+        let num_sites = webs.parents.len();
+        let mut web_sizes: HashMap<usize, usize> = HashMap::new();
+        for site in 0..num_sites {
+            *web_sizes.entry(webs.find(site)).or_default() += 1;
+        }
+        let mut local_webs: Vec<Vec<usize>> = vec![vec![]; num_locals];
+        for site in 0..num_sites {
+            let root = webs.find(site);
+            let local_webs = &mut local_webs[webs.site_locals[site].as_usize()];
+            let unread_zero = site < num_locals && site >= num_params && web_sizes[&root] == 1;
+            if !unread_zero && !local_webs.contains(&root) {
+                local_webs.push(root);
+            }
+        }
+
+        let mut web_locals: HashMap<usize, LocalIdx> = HashMap::new();
+        for (i, roots) in local_webs.iter().enumerate() {
+            let local_index = LocalIdx::from(i as u32);
+            let Some((first, rest)) = roots.split_first() else {
+                continue;
+            };
+            web_locals.insert(*first, local_index);
+            if rest.is_empty() {
+                continue;
+            }
+            let Local { ty, name } = self.locals[i].clone();
+            // A param's value on entry is always its first web.
+            let mut suffix = 1;
+            if i >= num_params {
+                self.locals[i].name = format!("{}_{}", name, suffix);
+                suffix += 1;
+            }
+            for root in rest {
+                web_locals.insert(*root, LocalIdx::from(self.locals.len() as u32));
+                self.locals.push(Local {
+                    ty,
+                    name: format!("{}_{}", name, suffix),
+                });
+                suffix += 1;
+            }
+        }
+
+        let renamed = (0..num_sites)
+            .map(|site| {
+                let root = webs.find(site);
+                web_locals
+                    .get(&root)
+                    .copied()
+                    .unwrap_or(webs.site_locals[site])
+            })
+            .collect();
+        webs.renamed = Some(renamed);
+        for block_index in &order {
+            webs.next_site = first_sites[block_index];
+            let mut reaching = vec![BTreeSet::new(); num_locals];
+            webs.block(self.blocks.get_mut(block_index).unwrap(), &mut reaching);
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::ir::passes::negate;
+use crate::ir::*;
+
+/// A function's control flow as nested blocks, loops and ifs, printed in
+/// place of its blocks and the branches between them. Refers to the blocks
+/// by index, so it's built once every pass that changes them is done.
+#[derive(Debug, Clone)]
+pub(crate) enum Node {
+    /// The statements of a block.
+    Statements(BlockIndex),
+    /// `block @N { ... }`. `break @N` leaves it for the code of block N,
+    /// which follows it.
+    Block(BlockIndex, Vec<Node>),
+    /// `loop @N { ... }`, which starts with the code of block N and goes
+    /// round until something branches out of it. `continue @N` goes back to
+    /// the top and `break @N` leaves it. Both leave out the label inside
+    /// the innermost loop, as does the loop if nothing else needs it.
+    Loop(BlockIndex, Vec<Node>),
+    /// The `br_if` that ends block N, as `if (...) { ... } else { ... }`.
+    If(BlockIndex, Expression, Vec<Node>, Vec<Node>),
+    Break(BlockIndex),
+    Continue(BlockIndex),
+    /// The terminator of a block that doesn't branch to a single place: a
+    /// return, `unreachable`, throw or `br_table`.
+    Exit(BlockIndex),
+}
+
+// Where control goes after a list of nodes runs off its end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Next {
+    Block(BlockIndex),
+    Return,
+    Unknown,
+}
+
+// The dominator tree and loops of a function, from which its structure
+// follows as in Norman Ramsey's "Beyond Relooper" (ICFP 2022).
+struct Structurer<'a> {
+    func: &'a Func,
+    rpo_number: HashMap<BlockIndex, usize>,
+    dominators: DominatorTree,
+    // Blocks with a branch back to them, and the blocks in each one's loop.
+    loops: HashMap<BlockIndex, HashSet<BlockIndex>>,
+    // Blocks placed after a `block` that's broken out of to get to them:
+    // those with more than one branch forward to them, those a `br_table`
+    // branches forward to, and the blocks a loop exits to.
+    merge_nodes: HashSet<BlockIndex>,
+}
+
+impl Structurer<'_> {
+    fn is_backward(&self, from: BlockIndex, to: BlockIndex) -> bool {
+        self.rpo_number[&to] <= self.rpo_number[&from]
+    }
+
+    // The code for `block_index` and all it dominates. If it's a loop
+    // header, the blocks its loop exits to are placed after the loop.
+    fn tree(&self, block_index: BlockIndex) -> Vec<Node> {
+        let mut merge_children: Vec<BlockIndex> = self
+            .dominators
+            .children(block_index)
+            .iter()
+            .copied()
+            .filter(|child| self.merge_nodes.contains(child))
+            .collect();
+        // The last to be placed is the outermost block.
+        merge_children.sort_by_key(|child| std::cmp::Reverse(self.rpo_number[child]));
+        let Some(body) = self.loops.get(&block_index) else {
+            return self.within(&merge_children, &|| self.code(block_index));
+        };
+        let (inner, outer): (Vec<BlockIndex>, Vec<BlockIndex>) = merge_children
+            .into_iter()
+            .partition(|child| body.contains(child));
+        self.within(&outer, &|| {
+            vec![Node::Loop(
+                block_index,
+                self.within(&inner, &|| self.code(block_index)),
+            )]
+        })
+    }
+
+    // `code` nested in a `block` for each of `merge_children`, each
+    // followed by the child's own code.
+    fn within(&self, merge_children: &[BlockIndex], code: &dyn Fn() -> Vec<Node>) -> Vec<Node> {
+        let Some((child, rest)) = merge_children.split_first() else {
+            return code();
+        };
+        let mut nodes = vec![Node::Block(*child, self.within(rest, code))];
+        nodes.extend(self.tree(*child));
+        nodes
+    }
+
+    // A block's statements and where it branches to.
+    fn code(&self, block_index: BlockIndex) -> Vec<Node> {
+        let block = &self.func.blocks[&block_index];
+        let mut nodes = vec![];
+        if !block.statements.is_empty() {
+            nodes.push(Node::Statements(block_index));
+        }
+        match &block.terminator {
+            Terminator::Br(target, _) => nodes.extend(self.branch(block_index, *target)),
+            Terminator::BrIf(condition, true_target, false_target, _) => nodes.push(Node::If(
+                block_index,
+                condition.clone(),
+                self.branch(block_index, *true_target),
+                self.branch(block_index, *false_target),
+            )),
+            _ => nodes.push(Node::Exit(block_index)),
+        }
+        nodes
+    }
+
+    fn branch(&self, from: BlockIndex, to: BlockIndex) -> Vec<Node> {
+        if self.is_backward(from, to) {
+            vec![Node::Continue(to)]
+        } else if self.merge_nodes.contains(&to) {
+            vec![Node::Break(to)]
+        } else {
+            self.tree(to)
+        }
+    }
+}
+
+// Whether control never runs off the end of `nodes`.
+fn ends_in_jump(nodes: &[Node]) -> bool {
+    match nodes.last() {
+        Some(Node::Break(_) | Node::Continue(_) | Node::Exit(_) | Node::Loop(..)) => true,
+        Some(Node::If(_, _, true_nodes, false_nodes)) => {
+            ends_in_jump(true_nodes) && ends_in_jump(false_nodes)
+        }
+        _ => false,
+    }
+}
+
+// `block @X { loop @H { ... } }` is `loop @H { ... }` with each `break @X`
+// leaving the loop instead, as `break @H`. A `br_table` can't leave a loop,
+// so a block one goes to is kept.
+fn break_out_of_loops(nodes: Vec<Node>, func: &Func) -> Vec<Node> {
+    nodes
+        .into_iter()
+        .map(|node| match node {
+            Node::Block(target, nodes) => {
+                let mut nodes = break_out_of_loops(nodes, func);
+                match &mut nodes[..] {
+                    [Node::Loop(header, body)]
+                        if !body.iter().any(|node| node.exits_to(func, target)) =>
+                    {
+                        let header = *header;
+                        let mut body = std::mem::take(body);
+                        for node in &mut body {
+                            node.retarget_breaks(target, header);
+                        }
+                        Node::Loop(header, body)
+                    }
+                    _ => Node::Block(target, nodes),
+                }
+            }
+            Node::Loop(header, nodes) => Node::Loop(header, break_out_of_loops(nodes, func)),
+            Node::If(block_index, condition, true_nodes, false_nodes) => Node::If(
+                block_index,
+                condition,
+                break_out_of_loops(true_nodes, func),
+                break_out_of_loops(false_nodes, func),
+            ),
+            node => node,
+        })
+        .collect()
+}
+
+impl Node {
+    fn children_mut(&mut self) -> Vec<&mut Node> {
+        match self {
+            Node::Block(_, nodes) | Node::Loop(_, nodes) => nodes.iter_mut().collect(),
+            Node::If(_, _, true_nodes, false_nodes) => {
+                true_nodes.iter_mut().chain(false_nodes).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    fn retarget_breaks(&mut self, from: BlockIndex, to: BlockIndex) {
+        match self {
+            Node::Break(target) if *target == from => *target = to,
+            node => {
+                for child in node.children_mut() {
+                    child.retarget_breaks(from, to);
+                }
+            }
+        }
+    }
+
+    // Whether a `br_table` in or under this node goes to `target`.
+    fn exits_to(&self, func: &Func, target: BlockIndex) -> bool {
+        match self {
+            Node::Exit(block_index) => func.blocks[block_index].successors().contains(&target),
+            Node::Block(_, nodes) | Node::Loop(_, nodes) => {
+                nodes.iter().any(|node| node.exits_to(func, target))
+            }
+            Node::If(_, _, true_nodes, false_nodes) => true_nodes
+                .iter()
+                .chain(false_nodes)
+                .any(|node| node.exits_to(func, target)),
+            _ => false,
+        }
+    }
+
+    // Whether this node, in a loop whose header is `header`, needs the
+    // loop's label: a `br_table` to it does, as does a `break` or `continue`
+    // from a loop nested in it.
+    pub(crate) fn needs_loop_label(&self, func: &Func, header: BlockIndex, nested: bool) -> bool {
+        match self {
+            Node::Statements(_) => false,
+            Node::Block(_, nodes) => nodes
+                .iter()
+                .any(|node| node.needs_loop_label(func, header, nested)),
+            Node::Loop(_, nodes) => nodes
+                .iter()
+                .any(|node| node.needs_loop_label(func, header, true)),
+            Node::If(_, _, true_nodes, false_nodes) => true_nodes
+                .iter()
+                .chain(false_nodes)
+                .any(|node| node.needs_loop_label(func, header, nested)),
+            Node::Break(target) | Node::Continue(target) => *target == header && nested,
+            Node::Exit(block_index) => func.blocks[block_index].successors().contains(&header),
+        }
+    }
+
+    // Whether a `break`, `continue` or `br_table` in or under this node
+    // goes to `target`.
+    fn refers_to(&self, func: &Func, target: BlockIndex) -> bool {
+        match self {
+            Node::Statements(_) => false,
+            Node::Block(_, nodes) | Node::Loop(_, nodes) => {
+                nodes.iter().any(|node| node.refers_to(func, target))
+            }
+            Node::If(_, _, true_nodes, false_nodes) => true_nodes
+                .iter()
+                .chain(false_nodes)
+                .any(|node| node.refers_to(func, target)),
+            Node::Break(to) | Node::Continue(to) => *to == target,
+            Node::Exit(block_index) => func.blocks[block_index].successors().contains(&target),
+        }
+    }
+}
+
+impl Func {
+    // The nested form of the function's blocks, or `None` if they can't be
+    // nested: blocks with params can't be placed inline, and neither can
+    // `try_table` bodies and handlers. Irreducible control flow, with a
+    // loop that can be entered other than through its header, can't be
+    // either, though wasm's structured control flow never has any.
+    pub(crate) fn structure_control_flow(&self) -> Option<Vec<Node>> {
+        if self.error.is_some() || !self.blocks.contains_key(&self.entry_block) {
+            return None;
+        }
+        for block in self.blocks.values() {
+            let nestable = match &block.terminator {
+                Terminator::Br(_, params)
+                | Terminator::BrIf(_, _, _, params)
+                | Terminator::BrTable(_, _, _, params) => params.is_empty(),
+                Terminator::Unknown | Terminator::Try(..) => false,
+                _ => true,
+            };
+            if !nestable || !block.params.is_empty() {
+                return None;
+            }
+        }
+
+        let rpo = self.reverse_post_order();
+        let rpo_number: HashMap<BlockIndex, usize> = rpo
+            .iter()
+            .enumerate()
+            .map(|(number, block_index)| (*block_index, number))
+            .collect();
+        let predecessors = self.predecessors();
+
+        let dominators = self.dominator_tree();
+
+        let mut loops: HashMap<BlockIndex, HashSet<BlockIndex>> = HashMap::new();
+        let mut forward_edges: HashMap<BlockIndex, usize> = HashMap::new();
+        let mut merge_nodes = HashSet::new();
+        for block_index in &rpo {
+            // Finding loop bodies is quadratic in the worst case, and a
+            // function out of time is printed unstructured instead.
+            if self.past_deadline() {
+                return None;
+            }
+            let block = &self.blocks[block_index];
+            for successor in block.successors() {
+                if rpo_number[&successor] > rpo_number[block_index] {
+                    *forward_edges.entry(successor).or_default() += 1;
+                    if matches!(block.terminator, Terminator::BrTable(..)) {
+                        merge_nodes.insert(successor);
+                    }
+                } else if dominators.dominates(successor, *block_index) {
+                    // The loop is the header and every block that reaches
+                    // this branch back to it without going through it.
+                    let body = loops.entry(successor).or_default();
+                    body.insert(successor);
+                    let mut stack = vec![*block_index];
+                    while let Some(member) = stack.pop() {
+                        if body.insert(member) {
+                            stack.extend(&predecessors[&member]);
+                        }
+                    }
+                } else {
+                    return None;
+                }
+            }
+        }
+        merge_nodes.extend(
+            forward_edges
+                .into_iter()
+                .filter(|(_, count)| *count > 1)
+                .map(|(block_index, _)| block_index),
+        );
+        for (header, body) in &loops {
+            merge_nodes.extend(
+                dominators
+                    .children(*header)
+                    .iter()
+                    .filter(|child| !body.contains(child)),
+            );
+        }
+
+        let structurer = Structurer {
+            func: self,
+            rpo_number,
+            dominators,
+            loops,
+            merge_nodes,
+        };
+        let nodes = self.tidy(structurer.tree(self.entry_block), Next::Return);
+        Some(break_out_of_loops(nodes, self))
+    }
+
+    // Removes branches to where control would go anyway, and the blocks
+    // and `else`s that leaves unneeded:
+    //
+    // - `if (c) { A; break @1 } else { B }` becomes
+    //   `if (c) { A; break @1 } B`
+    // - `if (c) {} else { B }` becomes `if (!c) { B }`
+    // - a `block` nothing breaks out of is replaced by its contents
+    fn tidy(&self, nodes: Vec<Node>, next: Next) -> Vec<Node> {
+        let mut tidied = vec![];
+        let mut pending: VecDeque<Node> = nodes.into();
+        while let Some(node) = pending.pop_front() {
+            let node_next = if pending.is_empty() {
+                next
+            } else {
+                Next::Unknown
+            };
+            match node {
+                Node::Block(target, nodes) => {
+                    let nodes = self.tidy(nodes, Next::Block(target));
+                    if nodes.iter().any(|node| node.refers_to(self, target)) {
+                        tidied.push(Node::Block(target, nodes));
+                    } else {
+                        // Running off the end of the block goes to its
+                        // target, which comes next.
+                        for node in nodes.into_iter().rev() {
+                            pending.push_front(node);
+                        }
+                    }
+                }
+                Node::Loop(header, nodes) => {
+                    tidied.push(Node::Loop(header, self.tidy(nodes, Next::Block(header))));
+                }
+                Node::If(block_index, condition, true_nodes, false_nodes) => {
+                    if true_nodes.is_empty() && !false_nodes.is_empty() {
+                        pending.push_front(Node::If(
+                            block_index,
+                            negate(condition),
+                            false_nodes,
+                            vec![],
+                        ));
+                        continue;
+                    }
+                    let true_nodes = self.tidy(true_nodes, node_next);
+                    if ends_in_jump(&true_nodes) {
+                        for node in false_nodes.into_iter().rev() {
+                            pending.push_front(node);
+                        }
+                        tidied.push(Node::If(block_index, condition, true_nodes, vec![]));
+                    } else {
+                        let false_nodes = self.tidy(false_nodes, node_next);
+                        if true_nodes.is_empty() && !false_nodes.is_empty() {
+                            tidied.push(Node::If(
+                                block_index,
+                                negate(condition),
+                                false_nodes,
+                                vec![],
+                            ));
+                        } else {
+                            tidied.push(Node::If(block_index, condition, true_nodes, false_nodes));
+                        }
+                    }
+                }
+                Node::Break(target) | Node::Continue(target)
+                    if node_next == Next::Block(target) => {}
+                Node::Exit(block_index)
+                    if node_next == Next::Return
+                        && self.blocks[&block_index].terminator.is_empty_return() => {}
+                node => tidied.push(node),
+            }
+        }
+        tidied
+    }
+}
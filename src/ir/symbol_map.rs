@@ -0,0 +1,120 @@
+use anyhow::{anyhow, bail};
+
+use crate::ir::names::debug_name;
+use crate::ir::*;
+
+/// Names for a module's functions, globals and locals kept in a separate
+/// file, for builds whose name section was stripped. Lines look like
+///
+/// ```text
+/// 12:malloc          function 12, as in Emscripten's `.symbols` files
+/// 12=malloc          the same
+/// global 3=counter
+/// local 12 0=size    local 0 of function 12, which may be a param
+/// ```
+///
+/// Blank lines and lines starting with `#` are skipped. The names are
+/// treated like those of the name section, which takes precedence. Indices
+/// the module doesn't have are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolMap {
+    funcs: HashMap<FuncIdx, String>,
+    globals: HashMap<GlobalIdx, String>,
+    locals: HashMap<(FuncIdx, LocalIdx), String>,
+}
+
+fn parse_index(index: &str) -> anyhow::Result<u32> {
+    index
+        .parse()
+        .map_err(|_| anyhow!("invalid index `{}`", index))
+}
+
+impl SymbolMap {
+    pub fn parse(text: &str) -> anyhow::Result<SymbolMap> {
+        let mut map = SymbolMap::default();
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            map.parse_entry(line)
+                .map_err(|err| anyhow!("line {}: {}", line_index + 1, err))?;
+        }
+        Ok(map)
+    }
+
+    fn parse_entry(&mut self, line: &str) -> anyhow::Result<()> {
+        // Names can contain `:` and `=` too, e.g. `std::sort`, so only the
+        // first one ends the index.
+        let Some((key, name)) = line.split_once([':', '=']) else {
+            bail!("expected `index:name` or `index=name`");
+        };
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            bail!("missing name");
+        }
+        match key.split_whitespace().collect::<Vec<_>>()[..] {
+            [func] => {
+                self.funcs.insert(parse_index(func)?.into(), name);
+            }
+            ["global", global] => {
+                self.globals.insert(parse_index(global)?.into(), name);
+            }
+            ["local", func, local] => {
+                let func = parse_index(func)?.into();
+                self.locals.insert((func, parse_index(local)?.into()), name);
+            }
+            _ => bail!(
+                "expected `N`, `global N` or `local F N`, not `{}`",
+                key.trim()
+            ),
+        }
+        Ok(())
+    }
+}
+
+impl Module {
+    // Names what the name section didn't. Imported functions keep their
+    // qualified names.
+    pub(crate) fn apply_symbol_map(&mut self, map: &SymbolMap) {
+        let mut funcs: Vec<(&FuncIdx, &String)> = map.funcs.iter().collect();
+        funcs.sort();
+        for (func_index, name) in funcs {
+            if self.func(*func_index).is_none() || self.debug_func_names.contains_key(func_index) {
+                continue;
+            }
+            self.debug_func_names.insert(*func_index, name.clone());
+            self.name_entry(*func_index, EntryKind::Named(name.clone()));
+        }
+
+        let mut globals: Vec<(&GlobalIdx, &String)> = map.globals.iter().collect();
+        globals.sort();
+        for (global_index, name) in globals {
+            if global_index.as_usize() < self.globals.len() {
+                self.name_global(*global_index, name);
+                self.names.insert_global(*global_index, name);
+            }
+        }
+
+        // The locals after the params and the locals the body declares are
+        // the decoder's `temp`s, which the map can't know about.
+        for func in &mut self.funcs {
+            let mut taken: HashSet<String> =
+                func.locals.iter().map(|local| local.name.clone()).collect();
+            for (i, local) in func.locals.iter_mut().enumerate() {
+                if local.name.starts_with("temp") {
+                    break;
+                }
+                let name = map
+                    .locals
+                    .get(&(func.index, LocalIdx::from(i as u32)))
+                    .and_then(|name| debug_name(name));
+                if let Some(name) = name {
+                    if taken.insert(name.clone()) {
+                        local.name = name;
+                    }
+                }
+            }
+        }
+    }
+}
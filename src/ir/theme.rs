@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::{anyhow, bail};
+
+/// The category of a printed token, attached to the output as an annotation
+/// so that the ANSI and HTML writers can highlight it.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum Token {
+    /// `func`, `if`, `br`, `return`, ...
+    Keyword,
+    /// Block labels, `@N`.
+    Label,
+    /// Params, locals, block params and globals.
+    Name,
+    /// Called functions.
+    Function,
+    /// Constants.
+    Literal,
+    /// Value types.
+    Type,
+    /// Infix and unary operators.
+    Operator,
+    /// Comments, such as the error printed for a function that failed to
+    /// decompile.
+    Comment,
+    /// A statement or terminator, with the byte offset in the module of
+    /// the first instruction it was decoded from. It isn't highlighted, and
+    /// is for writers that map the output back to the module.
+    Offset(usize),
+}
+
+impl Token {
+    pub const ALL: [Token; 8] = [
+        Token::Keyword,
+        Token::Label,
+        Token::Name,
+        Token::Function,
+        Token::Literal,
+        Token::Type,
+        Token::Operator,
+        Token::Comment,
+    ];
+
+    /// The name used for this category in theme files and as the HTML class.
+    pub fn name(self) -> &'static str {
+        match self {
+            Token::Keyword => "keyword",
+            Token::Label => "label",
+            Token::Name => "name",
+            Token::Function => "function",
+            Token::Literal => "literal",
+            Token::Type => "type",
+            Token::Operator => "operator",
+            Token::Comment => "comment",
+            Token::Offset(_) => "offset",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Token> {
+        Token::ALL.into_iter().find(|token| token.name() == name)
+    }
+}
+
+/// A 24-bit RGB color, written as `#rrggbb`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl Color {
+    fn parse(text: &str) -> anyhow::Result<Color> {
+        let hex = text
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.is_ascii())
+            .ok_or_else(|| anyhow!("invalid color `{}`, expected #rrggbb", text))?;
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+        Ok(Color(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// How a token category is highlighted.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct Style {
+    pub color: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl Style {
+    fn color(r: u8, g: u8, b: u8) -> Style {
+        Style {
+            color: Some(Color(r, g, b)),
+            ..Style::default()
+        }
+    }
+
+    fn bold(self) -> Style {
+        Style { bold: true, ..self }
+    }
+
+    fn italic(self) -> Style {
+        Style {
+            italic: true,
+            ..self
+        }
+    }
+}
+
+/// A mapping from token category to style. Categories without a style are
+/// printed in the terminal's or page's default color.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Theme {
+    /// Page colors for the HTML writer. The ANSI writer leaves the terminal's
+    /// own colors alone.
+    pub background: Option<Color>,
+    pub foreground: Option<Color>,
+    styles: HashMap<Token, Style>,
+}
+
+impl Theme {
+    /// The names accepted by [`Theme::builtin`].
+    pub const BUILTINS: [&'static str; 3] = ["light", "dark", "solarized"];
+
+    pub fn builtin(name: &str) -> Option<Theme> {
+        match name {
+            "light" => Some(Theme::light()),
+            "dark" => Some(Theme::dark()),
+            "solarized" => Some(Theme::solarized()),
+            _ => None,
+        }
+    }
+
+    pub fn light() -> Theme {
+        Theme::default()
+            .with_colors(Color(0xff, 0xff, 0xff), Color(0x24, 0x29, 0x2e))
+            .with(Token::Keyword, Style::color(0xd7, 0x3a, 0x49).bold())
+            .with(Token::Label, Style::color(0x6f, 0x42, 0xc1))
+            .with(Token::Function, Style::color(0x00, 0x5c, 0xc5))
+            .with(Token::Literal, Style::color(0x00, 0x86, 0x72))
+            .with(Token::Type, Style::color(0xe3, 0x62, 0x09))
+            .with(Token::Comment, Style::color(0x6a, 0x73, 0x7d).italic())
+    }
+
+    pub fn dark() -> Theme {
+        Theme::default()
+            .with_colors(Color(0x1e, 0x1e, 0x1e), Color(0xd4, 0xd4, 0xd4))
+            .with(Token::Keyword, Style::color(0xc5, 0x86, 0xc0).bold())
+            .with(Token::Label, Style::color(0x4e, 0xc9, 0xb0))
+            .with(Token::Name, Style::color(0x9c, 0xdc, 0xfe))
+            .with(Token::Function, Style::color(0xdc, 0xdc, 0xaa))
+            .with(Token::Literal, Style::color(0xb5, 0xce, 0xa8))
+            .with(Token::Type, Style::color(0x56, 0x9c, 0xd6))
+            .with(Token::Comment, Style::color(0x6a, 0x99, 0x55).italic())
+    }
+
+    pub fn solarized() -> Theme {
+        Theme::default()
+            .with_colors(Color(0x00, 0x2b, 0x36), Color(0x83, 0x94, 0x96))
+            .with(Token::Keyword, Style::color(0x85, 0x99, 0x00).bold())
+            .with(Token::Label, Style::color(0x6c, 0x71, 0xc4))
+            .with(Token::Name, Style::color(0x26, 0x8b, 0xd2))
+            .with(Token::Function, Style::color(0xb5, 0x89, 0x00))
+            .with(Token::Literal, Style::color(0x2a, 0xa1, 0x98))
+            .with(Token::Type, Style::color(0xcb, 0x4b, 0x16))
+            .with(Token::Operator, Style::color(0x93, 0xa1, 0xa1))
+            .with(Token::Comment, Style::color(0x58, 0x6e, 0x75).italic())
+    }
+
+    /// Parses a theme from a file with one `category = style` entry per line,
+    /// where the style is an optional `#rrggbb` color followed by `bold`
+    /// and/or `italic`. The `background` and `foreground` entries take a
+    /// single color. Blank lines and lines starting with `//` are ignored.
+    ///
+    /// ```text
+    /// // Keywords in bold red
+    /// keyword = #cc0000 bold
+    /// comment = italic
+    /// ```
+    pub fn parse(text: &str) -> anyhow::Result<Theme> {
+        let mut theme = Theme::default();
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            theme
+                .parse_entry(line)
+                .map_err(|err| anyhow!("line {}: {}", line_index + 1, err))?;
+        }
+        Ok(theme)
+    }
+
+    fn parse_entry(&mut self, line: &str) -> anyhow::Result<()> {
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("expected `category = style`");
+        };
+        let (key, value) = (key.trim(), value.trim());
+        match key {
+            "background" => self.background = Some(Color::parse(value)?),
+            "foreground" => self.foreground = Some(Color::parse(value)?),
+            _ => {
+                let Some(token) = Token::from_name(key) else {
+                    bail!("unknown token category `{}`", key);
+                };
+                let mut style = Style::default();
+                for word in value.split_whitespace() {
+                    match word {
+                        "bold" => style.bold = true,
+                        "italic" => style.italic = true,
+                        _ => style.color = Some(Color::parse(word)?),
+                    }
+                }
+                self.styles.insert(token, style);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn with(mut self, token: Token, style: Style) -> Theme {
+        self.styles.insert(token, style);
+        self
+    }
+
+    fn with_colors(self, background: Color, foreground: Color) -> Theme {
+        Theme {
+            background: Some(background),
+            foreground: Some(foreground),
+            ..self
+        }
+    }
+
+    pub fn style(&self, token: Token) -> Style {
+        self.styles.get(&token).copied().unwrap_or_default()
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+
+use crate::ir::*;
+
+/// What built a module, from its `producers` and `target_features` custom
+/// sections.
+#[derive(Debug, Clone, Default)]
+pub struct Toolchain {
+    /// Source languages and their versions, e.g. `("Rust", "1.78.0")`.
+    pub languages: Vec<(String, String)>,
+    /// Tools that produced or transformed the module and their versions,
+    /// e.g. `("rustc", "1.78.0 (9b00956e5 2024-04-29)")`.
+    pub processed_by: Vec<(String, String)>,
+    /// Features the module uses or requires, e.g. `simd128`. Those it's
+    /// marked as not using are left out.
+    pub features: Vec<String>,
+}
+
+impl Toolchain {
+    pub fn is_empty(&self) -> bool {
+        self.languages.is_empty() && self.processed_by.is_empty() && self.features.is_empty()
+    }
+
+    pub(crate) fn read_producers(
+        &mut self,
+        reader: wasm::ProducersSectionReader,
+    ) -> anyhow::Result<()> {
+        for field in reader {
+            let field = field?;
+            let list = match field.name {
+                "language" => &mut self.languages,
+                "processed-by" => &mut self.processed_by,
+                _ => continue,
+            };
+            for value in field.values {
+                let value = value?;
+                list.push((value.name.to_string(), value.version.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    // Each entry is a prefix, `+` for used, `-` for not used or `=` for
+    // required, then the feature's name.
+    pub(crate) fn read_target_features(
+        &mut self,
+        section: &wasm::CustomSectionReader,
+    ) -> anyhow::Result<()> {
+        let mut reader = wasm::BinaryReader::new(section.data(), section.data_offset());
+        let count = reader.read_var_u32()?;
+        for _ in 0..count {
+            let prefix = reader.read_u8()?;
+            let name = reader.read_string()?;
+            if prefix != b'-' {
+                self.features.push(name.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Toolchain {
+    /// e.g. `compiled by rustc 1.78.0, features: simd128, bulk-memory`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |entries: &[(String, String)]| -> String {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(name, version)| format!("{} {}", name, version).trim().to_string())
+                .collect();
+            entries.join(", ")
+        };
+        let mut parts = vec![];
+        if !self.processed_by.is_empty() {
+            parts.push(format!("compiled by {}", list(&self.processed_by)));
+        } else if !self.languages.is_empty() {
+            parts.push(format!("written in {}", list(&self.languages)));
+        }
+        if !self.features.is_empty() {
+            parts.push(format!("features: {}", self.features.join(", ")));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl Module {
+    /// What built the module, as far as its custom sections say.
+    pub fn toolchain(&self) -> &Toolchain {
+        &self.toolchain
+    }
+}
@@ -0,0 +1,88 @@
+use crate::ir::*;
+
+/// Hooks for walking a function with [`Func::walk`]. Each does nothing by
+/// default, so a visitor only implements the ones it needs. The `pre_` hook
+/// of a part runs before the parts in it are walked, and its `post_` hook
+/// after.
+///
+/// Blocks are walked in order of their index, each statement by statement
+/// and then its terminator. A statement's expressions are walked before
+/// the statements nested in it, and an expression's operands in the order
+/// they're evaluated.
+pub trait Visitor {
+    fn pre_func(&mut self, _func: &Func) {}
+    fn post_func(&mut self, _func: &Func) {}
+    fn pre_block(&mut self, _block_index: BlockIndex, _block: &Block) {}
+    fn post_block(&mut self, _block_index: BlockIndex, _block: &Block) {}
+    fn pre_statement(&mut self, _stmt: &Statement) {}
+    fn post_statement(&mut self, _stmt: &Statement) {}
+    fn pre_terminator(&mut self, _terminator: &Terminator) {}
+    fn post_terminator(&mut self, _terminator: &Terminator) {}
+    fn pre_expression(&mut self, _expr: &Expression) {}
+    fn post_expression(&mut self, _expr: &Expression) {}
+}
+
+impl Func {
+    /// Walks the function's blocks, statements and expressions, calling
+    /// `visitor`'s hooks on each.
+    pub fn walk(&self, visitor: &mut impl Visitor) {
+        fn expression(visitor: &mut impl Visitor, expr: &Expression) {
+            visitor.pre_expression(expr);
+            for operand in expr.operands() {
+                expression(visitor, operand);
+            }
+            visitor.post_expression(expr);
+        }
+
+        fn statement(visitor: &mut impl Visitor, stmt: &Statement) {
+            visitor.pre_statement(stmt);
+            for expr in stmt.expressions() {
+                expression(visitor, expr);
+            }
+            for stmt in stmt.bodies().into_iter().flatten() {
+                statement(visitor, stmt);
+            }
+            visitor.post_statement(stmt);
+        }
+
+        visitor.pre_func(self);
+        for block_index in self.visual_block_order() {
+            let block = &self.blocks[&block_index];
+            visitor.pre_block(block_index, block);
+            for stmt in &block.statements {
+                statement(visitor, stmt);
+            }
+            visitor.pre_terminator(&block.terminator);
+            for expr in block.terminator.expressions() {
+                expression(visitor, expr);
+            }
+            visitor.post_terminator(&block.terminator);
+            visitor.post_block(block_index, block);
+        }
+        visitor.post_func(self);
+    }
+
+    /// Calls `f` with every statement, those nested in ifs and loops
+    /// included, in the order [`Func::walk`] visits them.
+    pub fn visit_statements(&self, f: impl FnMut(&Statement)) {
+        struct Statements<F>(F);
+        impl<F: FnMut(&Statement)> Visitor for Statements<F> {
+            fn pre_statement(&mut self, stmt: &Statement) {
+                (self.0)(stmt)
+            }
+        }
+        self.walk(&mut Statements(f));
+    }
+
+    /// Calls `f` with every expression, operands included, in the order
+    /// [`Func::walk`] visits them: each before its operands.
+    pub fn visit_expressions(&self, f: impl FnMut(&Expression)) {
+        struct Expressions<F>(F);
+        impl<F: FnMut(&Expression)> Visitor for Expressions<F> {
+            fn pre_expression(&mut self, expr: &Expression) {
+                (self.0)(expr)
+            }
+        }
+        self.walk(&mut Expressions(f));
+    }
+}
@@ -0,0 +1,162 @@
+use std::ops::Range;
+
+use crate::ir::print::ModulePart;
+use crate::ir::*;
+
+/// Where each function is in a module's printed output, so that a viewer
+/// can render just the lines it shows. Lines are numbered from zero and are
+/// the same in every output format.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    // The first line of each part of the output, followed by the line count.
+    // The last line of each part's range is the blank line after it.
+    part_starts: Vec<usize>,
+    // Each function and the index of its part.
+    funcs: Vec<(FuncIdx, usize)>,
+}
+
+impl LineIndex {
+    /// The number of lines in the whole output.
+    pub fn line_count(&self) -> usize {
+        *self.part_starts.last().unwrap()
+    }
+
+    /// The lines a function is printed on, not including the blank line
+    /// after it.
+    pub fn func_lines(&self, func_index: FuncIdx) -> Option<Range<usize>> {
+        let i = self
+            .funcs
+            .binary_search_by_key(&func_index, |(func_index, _)| *func_index)
+            .ok()?;
+        let part = self.funcs[i].1;
+        Some(self.part_starts[part]..self.part_starts[part + 1] - 1)
+    }
+
+    /// The function printed on a line, if any.
+    pub fn func_at_line(&self, line: usize) -> Option<FuncIdx> {
+        let part = self.part_starts.partition_point(|start| *start <= line) - 1;
+        self.funcs
+            .iter()
+            .find(|(_, func_part)| *func_part == part)
+            .map(|(func_index, _)| *func_index)
+    }
+}
+
+impl Module {
+    /// Renders every function once to count its lines. The index stays valid
+    /// for as long as the module isn't changed.
+    pub fn line_index(&self) -> anyhow::Result<LineIndex> {
+        self.line_index_with_options(&RenderOptions::default())
+    }
+
+    /// Like [`line_index`](Module::line_index), for output written with
+    /// `options`, whose width and indentation decide where lines break.
+    pub fn line_index_with_options(&self, options: &RenderOptions) -> anyhow::Result<LineIndex> {
+        let mut part_starts = vec![0];
+        let mut funcs = vec![];
+        let mut text = Vec::new();
+        for (i, part) in self.parts().iter().enumerate() {
+            text.clear();
+            options.render_part(
+                self.pretty_part(part, options.indent, &pretty::BoxAllocator),
+                &mut text,
+            )?;
+            let line_count = text.iter().filter(|c| **c == b'\n').count() + 1;
+            // Plus the blank line that follows every part.
+            part_starts.push(part_starts[i] + line_count + 1);
+            if let ModulePart::Func(func) = part {
+                funcs.push((func.index, i));
+            }
+        }
+        // Functions may be printed out of index order.
+        funcs.sort();
+        Ok(LineIndex { part_starts, funcs })
+    }
+
+    /// Writes the given lines of the module's output, each followed by a
+    /// newline. Only the functions on those lines are rendered.
+    pub fn write_lines(
+        &self,
+        index: &LineIndex,
+        lines: Range<usize>,
+        mut output: impl std::io::Write,
+        options: &RenderOptions,
+    ) -> anyhow::Result<()> {
+        options.begin(&mut output)?;
+        let mut text = Vec::new();
+        for (i, part) in self.parts().iter().enumerate() {
+            let part_lines = index.part_starts[i]..index.part_starts[i + 1];
+            if part_lines.end <= lines.start || part_lines.start >= lines.end {
+                continue;
+            }
+            text.clear();
+            options.render_part(
+                self.pretty_part(part, options.indent, &pretty::BoxAllocator),
+                &mut text,
+            )?;
+            let text = std::str::from_utf8(&text)?;
+            for (line, content) in part_lines.zip(text.split('\n').chain(Some(""))) {
+                if lines.contains(&line) {
+                    writeln!(output, "{}", content)?;
+                }
+            }
+        }
+        options.end(&mut output)
+    }
+
+    /// The defined functions whose index is in `funcs`.
+    pub fn funcs_in(&self, funcs: Range<FuncIdx>) -> Vec<FuncIdx> {
+        (self.funcs.iter())
+            .map(|func| func.index)
+            .filter(|func_index| funcs.contains(func_index))
+            .collect()
+    }
+
+    /// Writes the functions at `func_indices`, in that order and separated
+    /// by blank lines, without the rest of the module.
+    pub fn write_funcs_at(
+        &self,
+        func_indices: &[FuncIdx],
+        mut output: impl std::io::Write,
+        options: &RenderOptions,
+    ) -> anyhow::Result<()> {
+        let funcs = (func_indices.iter())
+            .map(|func_index| self.defined_func(*func_index))
+            .collect::<anyhow::Result<Vec<&Func>>>()?;
+        options.begin(&mut output)?;
+        for (i, func) in funcs.into_iter().enumerate() {
+            if i > 0 {
+                write!(output, "\n\n")?;
+            }
+            options.render_part(
+                self.pretty_func(func, options.indent, &pretty::BoxAllocator),
+                &mut output,
+            )?;
+        }
+        writeln!(output)?;
+        options.end(&mut output)
+    }
+
+    /// Writes the functions whose index is in `funcs`, separated by blank
+    /// lines, without the rest of the module.
+    pub fn write_funcs(
+        &self,
+        funcs: Range<FuncIdx>,
+        mut output: impl std::io::Write,
+        options: &RenderOptions,
+    ) -> anyhow::Result<()> {
+        options.begin(&mut output)?;
+        let in_range = self.funcs.iter().filter(|func| funcs.contains(&func.index));
+        for (i, func) in in_range.enumerate() {
+            if i > 0 {
+                write!(output, "\n\n")?;
+            }
+            options.render_part(
+                self.pretty_func(func, options.indent, &pretty::BoxAllocator),
+                &mut output,
+            )?;
+        }
+        writeln!(output)?;
+        options.end(&mut output)
+    }
+}
@@ -1,2 +1,3 @@
 mod ir;
+pub mod text;
 pub use ir::*;
@@ -1,44 +1,810 @@
-use anyhow::bail;
-use clap::Parser;
+use anyhow::{bail, Context};
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use regex::Regex;
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use std::ops::Range;
 use std::path::PathBuf;
+use std::time::Duration;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+use wasm_decompile::*;
 
-mod ir;
-pub use ir::*;
+mod config;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     input: PathBuf,
     output: Option<PathBuf>,
-    #[clap(short = 'f')]
-    func_index: Option<u32>,
-    #[clap(short = 'g')]
-    graphviz: bool,
+    /// Only decompile the function at this index, or the defined functions
+    /// in a range such as `10..20` or `10..=19`. Can be given more than
+    /// once. The other functions are only validated, so indirect calls
+    /// that depend on what they do are left unresolved.
+    #[clap(short = 'f', value_name = "INDEX", value_parser = parse_func_selection)]
+    funcs: Vec<FuncSelection>,
+    /// Only decompile the function exported as NAME, like -f with its
+    /// index.
+    #[clap(short = 'e', long, value_name = "NAME")]
+    export: Option<String>,
+    /// Only decompile the functions whose name, or a name they're exported
+    /// as, matches REGEX. Adds to the functions picked with -f and -e.
+    #[clap(long, value_name = "REGEX")]
+    filter: Option<String>,
+    /// For a component, only decompile the core module at this index,
+    /// counting from zero in the order they appear. By default every core
+    /// module is decompiled.
+    #[clap(long)]
+    component_index: Option<usize>,
+    /// Write graphviz DOT instead of decompiling: the control flow graph of
+    /// the function picked with -f, or without -f the module's call graph.
+    /// `--graphviz=domtree` writes the function's dominator tree instead.
+    #[clap(
+        short = 'g',
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "cfg"
+    )]
+    graphviz: Option<Graphviz>,
+    /// In graphviz output, show at most N statements of each block.
+    #[clap(long, value_name = "N")]
+    graphviz_max_statements: Option<usize>,
+    /// In graphviz output, cut lines longer than N characters short.
+    #[clap(long, value_name = "N")]
+    graphviz_max_line_length: Option<usize>,
+    /// Instead of printing the decompiled module, print the lines of it
+    /// that match REGEX, each with the function it's in and its line
+    /// number there. Only searches the functions picked with -f, -e or
+    /// --filter, if any. With --quick this is fast even on very large
+    /// modules.
+    #[clap(long, value_name = "REGEX")]
+    grep: Option<String>,
+    /// Instead of decompiling, list every function with its index, body
+    /// size, name, signature and exports, to find the ones to pick with -f.
+    /// Function bodies are only validated, so this is fast on any module.
+    #[clap(long)]
+    list: bool,
+    /// Instead of decompiling, list the N largest functions, largest first,
+    /// to find the ones worth reading first or the bloat worth cutting.
+    #[clap(long, value_name = "N")]
+    top: Option<usize>,
+    /// What --top ranks functions by: body size or decompiled lines.
+    /// `lines` decompiles every function to count them, so it's slower on
+    /// large modules.
+    #[clap(long, value_enum, default_value = "size", requires = "top")]
+    top_by: TopBy,
+    /// Instead of decompiling, list every mutable global with the functions
+    /// that read and write it.
+    #[clap(long)]
+    globals: bool,
+    /// Instead of decompiling, describe each exported function: its
+    /// signature, which params look like pointers and lengths, the globals
+    /// it touches and the imports it may call.
+    #[clap(long, value_enum)]
+    abi: Option<Abi>,
+    /// Instead of decompiling, dump an analysis of each function, or of the
+    /// one picked with -f. `dominators` is each function's dominator tree,
+    /// with blocks numbered as in the graphviz output.
+    #[clap(long, value_enum)]
+    dump: Option<Dump>,
+    /// Skip control flow reconstruction and print each function's blocks as
+    /// decoded. Much faster and lighter on memory for very large modules.
+    #[clap(long)]
+    quick: bool,
+    /// Don't optimize: print each function as the blocks it decoded to,
+    /// like --passes with none. The stack pointer global is still named and
+    /// indirect calls still annotated, and options that ask for a rewrite
+    /// of their own, such as --tidy-locals, still apply.
+    #[clap(long, conflicts_with_all = ["passes", "skip_passes"])]
+    no_optimize: bool,
+    /// Only run these optimization passes, separated by commas: any of
+    /// fold-constants, forward-temps, merge-blocks, jump-threading,
+    /// simplify-terminators, recover-divisions, normalize-conditions,
+    /// dead-code, name-locals, remove-unused-locals, stack-frame,
+    /// infer-signedness and structuring. They run in that order.
+    #[clap(
+        long,
+        value_name = "PASSES",
+        value_delimiter = ',',
+        value_parser = parse_pass,
+        conflicts_with = "skip_passes"
+    )]
+    passes: Option<Vec<Pass>>,
+    /// Run every optimization pass but these, separated by commas.
+    #[clap(long, value_name = "PASSES", value_delimiter = ',', value_parser = parse_pass)]
+    skip_passes: Vec<Pass>,
+    /// Print the functions picked with -f as they are after each of these
+    /// passes, separated by commas, before decompiling them. For finding
+    /// out which pass a change in the output comes from.
+    #[clap(long, value_name = "PASSES", value_delimiter = ',', value_parser = parse_pass)]
+    dump_after: Vec<Pass>,
+    /// Change which proposals a module may use, separated by commas: a
+    /// name such as `simd`, `gc` or `tail-call` (or `+simd`) accepts it,
+    /// `-simd` rejects it and `all` stands for every proposal. Applied in
+    /// order to the proposals accepted by default.
+    #[clap(
+        long,
+        value_name = "FEATURES",
+        value_delimiter = ',',
+        allow_hyphen_values = true,
+        value_parser = parse_feature
+    )]
+    features: Vec<(bool, WasmFeatures)>,
+    /// Print functions whose body is byte-identical to an earlier
+    /// function's as `func N = alias of func M`.
+    #[clap(long)]
+    dedup: bool,
+    /// Print the entry points (the start function, `_start`, `main` and
+    /// constructors) before the other functions.
+    #[clap(long)]
+    entry_first: bool,
+    /// Only print the functions reachable from the entry points.
+    #[clap(long)]
+    entry_only: bool,
+    /// Only print the functions reachable from the function exported as
+    /// NAME, callers before the functions they call. Can be given more than
+    /// once.
+    #[clap(long, value_name = "NAME", conflicts_with = "entry_only")]
+    reachable_from: Vec<String>,
+    /// Treat mutable globals and table slots that nothing reachable writes
+    /// as constants, turning indirect calls through them into direct calls.
+    #[clap(long)]
+    freeze: bool,
+    /// Annotate indirect calls whose target isn't known with the functions
+    /// of the type they expect.
+    #[clap(long)]
+    indirect_candidates: bool,
+    /// Print assignments of chains of selects at least DEPTH deep as
+    /// nested if/else statements.
+    #[clap(long, value_name = "DEPTH")]
+    flatten_selects: Option<usize>,
+    /// Hoist subexpressions nested more than DEPTH operators deep into
+    /// temps.
+    #[clap(long, value_name = "DEPTH")]
+    max_depth: Option<usize>,
+    /// Hoist the leftmost operands of an expression with more than WIDTH
+    /// that aren't just a local or a constant into temps.
+    #[clap(long, value_name = "WIDTH")]
+    max_width: Option<usize>,
+    /// Drop unused locals, merge those never live at the same time, and
+    /// name the rest by how they're used (`i`, `ptr`, `len`, `a`).
+    #[clap(long)]
+    tidy_locals: bool,
+    /// Give each unrelated value a local is reused for its own local,
+    /// named `x_1`, `x_2` and so on.
+    #[clap(long)]
+    split_locals: bool,
+    /// Demangle Rust and C++ function names.
+    #[clap(long)]
+    demangle: bool,
+    /// Also write a JSON object per function to FILE, one per line, with the
+    /// name it's printed with and where its body is in the module.
+    #[clap(long, value_name = "FILE")]
+    name_map: Option<PathBuf>,
+    /// Also write a JSON object per function to FILE, one per line, with
+    /// the lines it's printed on and the line, column and byte offset of
+    /// each of its statements and terminators.
+    #[clap(long, value_name = "FILE")]
+    line_map: Option<PathBuf>,
+    /// A symbol map naming functions, globals and locals the module
+    /// doesn't name itself, such as an Emscripten `.symbols` file.
+    #[clap(long, value_name = "FILE")]
+    names: Option<PathBuf>,
+    /// Another module this one imports functions from, so that calls to
+    /// them print with the names they have there. NAME is the module name
+    /// the imports use, and defaults to the file's name without its
+    /// extension. Can be given more than once.
+    #[clap(long, value_name = "[NAME=]FILE")]
+    link: Vec<String>,
+    /// A source map for the module, used to annotate statements with the
+    /// source lines they came from. Without one, the source map named by
+    /// the module's `sourceMappingURL` or its DWARF line table is used, if
+    /// it has either.
+    #[clap(long)]
+    source_map: Option<PathBuf>,
+    /// Annotate each statement with the byte offset of the instruction it
+    /// came from, as `// @0x1a3c`.
+    #[clap(long)]
+    offsets: bool,
+    /// Print the instructions each statement was decoded from before it,
+    /// as `// @0x2f: local.get 0`.
+    #[clap(long)]
+    interleave: bool,
+    /// How to format the output. jsonl writes one JSON object per function.
+    #[clap(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Color theme for the ansi and html formats: one of light, dark or
+    /// solarized, or the path to a theme file.
+    #[clap(long, default_value = "dark")]
+    theme: String,
+    /// The column to wrap lines at where they can be.
+    #[clap(long, value_name = "COLUMNS", default_value_t = 80)]
+    width: usize,
+    /// How many spaces to indent each level of nesting by.
+    #[clap(long, value_name = "SPACES", default_value_t = 2)]
+    indent: usize,
+    /// How many threads to decompile functions on. Defaults to one per
+    /// CPU.
+    #[clap(short = 'j', long, value_name = "N")]
+    jobs: Option<usize>,
+    /// Keep running and decompile the input again each time it changes,
+    /// for following along while editing the source it's built from.
+    #[clap(long)]
+    watch: bool,
+    /// Read default options from FILE, a TOML table of long option names
+    /// and their values such as `width = 100` or `demangle = true`.
+    /// Options on the command line override it. By default
+    /// `wasm-decompile.toml` is read from the current directory or the
+    /// nearest parent that has one.
+    #[clap(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Don't read a config file.
+    #[clap(long, conflicts_with = "config")]
+    no_config: bool,
+}
+
+impl Cli {
+    // Parses the command line, with the options in the config file, if
+    // there is one, under it.
+    fn parse_with_config() -> anyhow::Result<Cli> {
+        let args: Vec<OsString> = std::env::args_os().collect();
+        let command = Cli::command();
+        let matches = command.clone().get_matches_from(&args);
+        let path = match matches.get_one::<PathBuf>("config") {
+            Some(path) => Some(path.clone()),
+            None if matches.get_flag("no_config") => None,
+            None => config::find(),
+        };
+        let Some(path) = path else {
+            return Ok(Cli::from_arg_matches(&matches)?);
+        };
+        let config_args = config::args(&command, &matches, &path)?;
+        let args = (args.iter().take(1).cloned())
+            .chain(config_args)
+            .chain(args.iter().skip(1).cloned());
+        Ok(Cli::parse_from(args))
+    }
+
+    // Whether only some functions are to be decompiled.
+    fn picks_funcs(&self) -> bool {
+        !self.funcs.is_empty() || self.export.is_some() || self.filter.is_some()
+    }
+
+    // The functions to decompile when the others only need validating:
+    // those picked with -f or -e. Functions picked with --filter are
+    // matched against names that aren't known until the module's decoded.
+    fn only_funcs(&self) -> Option<FuncPicks> {
+        if self.list || (self.top.is_some() && self.top_by == TopBy::Size) {
+            return Some(FuncPicks::default());
+        }
+        let module_wide = self.globals
+            || self.abi.is_some()
+            || self.top.is_some()
+            || !self.reachable_from.is_empty();
+        if !self.picks_funcs() || self.filter.is_some() || module_wide {
+            return None;
+        }
+        Some(FuncPicks {
+            ranges: self.func_ranges(),
+            exports: self.export.iter().cloned().collect(),
+        })
+    }
+
+    // The functions picked with -f.
+    fn func_ranges(&self) -> Vec<Range<FuncIdx>> {
+        (self.funcs.iter())
+            .map(|selection| match selection {
+                FuncSelection::Index(func_index) => {
+                    FuncIdx::from(*func_index)..FuncIdx::from(func_index.saturating_add(1))
+                }
+                FuncSelection::Range(range) => FuncIdx::from(range.start)..FuncIdx::from(range.end),
+            })
+            .collect()
+    }
+
+    fn features(&self) -> WasmFeatures {
+        let mut features = WasmFeatures::default();
+        for (enable, feature) in &self.features {
+            features.set(*feature, *enable);
+        }
+        features
+    }
+
+    fn skipped_passes(&self) -> Vec<Pass> {
+        if self.no_optimize {
+            return Pass::ALL.to_vec();
+        }
+        match &self.passes {
+            Some(passes) => (Pass::ALL.into_iter())
+                .filter(|pass| !passes.contains(pass))
+                .collect(),
+            None => self.skip_passes.clone(),
+        }
+    }
+}
+
+// A function index given to -f, or a range of them.
+#[derive(Clone)]
+enum FuncSelection {
+    Index(u32),
+    Range(Range<u32>),
+}
+
+fn parse_func_selection(text: &str) -> Result<FuncSelection, String> {
+    let index = |text: &str| {
+        text.parse::<u32>()
+            .map_err(|_| format!("`{}` is not a function index", text))
+    };
+    if let Some((start, end)) = text.split_once("..=") {
+        let end = index(end)?;
+        return Ok(FuncSelection::Range(index(start)?..end.saturating_add(1)));
+    }
+    if let Some((start, end)) = text.split_once("..") {
+        return Ok(FuncSelection::Range(index(start)?..index(end)?));
+    }
+    Ok(FuncSelection::Index(index(text)?))
+}
+
+fn parse_pass(name: &str) -> Result<Pass, String> {
+    Pass::from_name(name).ok_or_else(|| {
+        let names: Vec<&str> = Pass::ALL.iter().map(|pass| pass.name()).collect();
+        format!("`{}` is not a pass, try one of {}", name, names.join(", "))
+    })
+}
+
+// A proposal given to --features, or all of them, and whether it's accepted.
+fn parse_feature(text: &str) -> Result<(bool, WasmFeatures), String> {
+    let (enable, name) = match text.strip_prefix('-') {
+        Some(name) => (false, name),
+        None => (true, text.strip_prefix('+').unwrap_or(text)),
+    };
+    if name == "all" {
+        return Ok((enable, WasmFeatures::all()));
+    }
+    let feature = WasmFeatures::from_name(&name.replace('-', "_").to_uppercase());
+    feature.map(|feature| (enable, feature)).ok_or_else(|| {
+        let names: Vec<String> = (WasmFeatures::all().iter_names())
+            .map(|(name, _)| name.replace('_', "-").to_lowercase())
+            .collect();
+        format!(
+            "`{}` is not a feature, try one of {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Ansi,
+    Html,
+    Jsonl,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Graphviz {
+    Cfg,
+    Domtree,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Dump {
+    Dominators,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TopBy {
+    Size,
+    Lines,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Abi {
+    Text,
+    Markdown,
+    Json,
+}
+
+fn load_theme(name: &str) -> anyhow::Result<Theme> {
+    if let Some(theme) = Theme::builtin(name) {
+        return Ok(theme);
+    }
+    let text = std::fs::read_to_string(name).with_context(|| {
+        format!(
+            "`{}` is not a built-in theme ({}) or a readable file",
+            name,
+            Theme::BUILTINS.join(", ")
+        )
+    })?;
+    Theme::parse(&text).with_context(|| format!("invalid theme file `{}`", name))
+}
+
+// Modules named by `--link`, with the name their importers use.
+fn load_links(cli: &Cli) -> anyhow::Result<Vec<(String, PathBuf, Module)>> {
+    let mut links = vec![];
+    for link in &cli.link {
+        let (name, path) = match link.split_once('=') {
+            Some((name, path)) => (name.to_string(), PathBuf::from(path)),
+            None => {
+                let path = PathBuf::from(link);
+                let Some(name) = path.file_stem() else {
+                    bail!("`{}` has no file name to use as a module name", link);
+                };
+                (name.to_string_lossy().into_owned(), path)
+            }
+        };
+        let other =
+            std::fs::read(&path).with_context(|| format!("cannot read `{}`", path.display()))?;
+        let other = Module::from_buffer_with_options(
+            &wat::parse_bytes(&other)?,
+            &DecodeOptions {
+                quick: true,
+                ..Default::default()
+            },
+        )?;
+        links.push((name, path, other));
+    }
+    Ok(links)
+}
+
+/// Compare two versions of a module, printing a unified diff of each
+/// function that changed.
+#[derive(Parser)]
+#[command(name = "wasm-decompile diff")]
+struct DiffCli {
+    old: PathBuf,
+    new: PathBuf,
+    output: Option<PathBuf>,
+}
+
+fn diff(cli: DiffCli) -> anyhow::Result<()> {
+    let read = |path: &PathBuf| -> anyhow::Result<Module> {
+        let input =
+            std::fs::read(path).with_context(|| format!("cannot read `{}`", path.display()))?;
+        Module::from_buffer_with_options(
+            &wat::parse_bytes(&input)?,
+            &DecodeOptions {
+                module_dir: path.parent().map(PathBuf::from),
+                ..Default::default()
+            },
+        )
+    };
+    let old = read(&cli.old)?;
+    let new = read(&cli.new)?;
+    let output: Box<dyn std::io::Write> = match &cli.output {
+        Some(output_path) => Box::new(std::fs::File::create(output_path)?),
+        None => Box::new(std::io::stdout()),
+    };
+    old.write_diff(&new, output)
 }
 
 fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    // Nothing is logged unless `RUST_LOG` asks for it, for example
+    // `RUST_LOG=wasm_decompile=debug` to time each function and pass.
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+        .with_writer(std::io::stderr)
+        .init();
+    // `wasm-decompile diff OLD NEW` takes its own arguments.
+    if std::env::args_os().nth(1).is_some_and(|arg| arg == "diff") {
+        return diff(DiffCli::parse_from(std::env::args_os().skip(1)));
+    }
+    let cli = Cli::parse_with_config()?;
+    if cli.watch {
+        watch(&cli)
+    } else {
+        decompile(&cli)
+    }
+}
+
+// Decompiles the input again whenever it changes, until interrupted. A
+// module that fails to decompile, such as one saved halfway through an
+// edit, is reported and waited out.
+fn watch(cli: &Cli) -> anyhow::Result<()> {
+    let modified = || {
+        std::fs::metadata(&cli.input)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    let mut decompiled = None;
+    loop {
+        let last_modified = modified();
+        if last_modified.is_some() && last_modified != decompiled {
+            decompiled = last_modified;
+            let mut stdout = std::io::stdout();
+            if cli.output.is_none() && stdout.is_terminal() {
+                // Clear the screen for the new output.
+                write!(stdout, "\x1b[2J\x1b[H")?;
+            }
+            if let Err(error) = decompile(cli) {
+                eprintln!("error: {:#}", error);
+            }
+            stdout.flush()?;
+        }
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
+
+fn decompile(cli: &Cli) -> anyhow::Result<()> {
     let input = std::fs::read(&cli.input)?;
     let input_binary = wat::parse_bytes(&input)?;
-    let module = Module::from_buffer(&input_binary)?;
+    let source_map = match &cli.source_map {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("cannot read `{}`", path.display()))?;
+            Some(
+                SourceMap::parse(&text)
+                    .with_context(|| format!("invalid source map `{}`", path.display()))?,
+            )
+        }
+        None => None,
+    };
+    let symbol_map = match &cli.names {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("cannot read `{}`", path.display()))?;
+            Some(
+                SymbolMap::parse(&text)
+                    .with_context(|| format!("invalid symbol map `{}`", path.display()))?,
+            )
+        }
+        None => None,
+    };
+    let decode_options = DecodeOptions {
+        quick: cli.quick,
+        source_map,
+        offsets: cli.offsets,
+        interleave: cli.interleave,
+        module_dir: cli.input.parent().map(PathBuf::from),
+        dedup: cli.dedup,
+        entry_first: cli.entry_first,
+        entry_only: cli.entry_only,
+        reachable_from: cli.reachable_from.clone(),
+        freeze: cli.freeze,
+        indirect_candidates: cli.indirect_candidates,
+        flatten_selects: cli.flatten_selects,
+        max_expression_depth: cli.max_depth,
+        max_expression_width: cli.max_width,
+        tidy_locals: cli.tidy_locals,
+        split_locals: cli.split_locals,
+        demangle: cli.demangle,
+        symbol_map,
+        jobs: cli.jobs,
+        only: cli.only_funcs(),
+        dump_after: cli.dump_after.clone(),
+        dump_funcs: cli.func_ranges(),
+        skip_passes: cli.skipped_passes(),
+        features: cli.features(),
+        limits: Limits::default(),
+    };
+    let links = load_links(cli)?;
+    let options = RenderOptions {
+        format: match cli.format {
+            Format::Text => OutputFormat::Text,
+            Format::Ansi => OutputFormat::Ansi,
+            Format::Html => OutputFormat::Html,
+            Format::Jsonl => OutputFormat::Text,
+        },
+        theme: load_theme(&cli.theme)?,
+        width: cli.width,
+        indent: cli.indent,
+    };
+    if cli.graphviz.is_some() && cli.format != Format::Text {
+        bail!("graphviz output can only be written as text");
+    }
+    if cli.line_map.is_some()
+        && (cli.picks_funcs()
+            || cli.graphviz.is_some()
+            || !matches!(cli.format, Format::Text | Format::Ansi))
+    {
+        bail!("--line-map needs the whole module written as text");
+    }
+    if !cli.dump_after.is_empty() && (cli.funcs.is_empty() || cli.quick) {
+        bail!("--dump-after needs the functions to dump picked with -f, and no --quick");
+    }
+
+    // A component is decompiled one core module at a time.
+    let component = is_component(&input_binary);
+    let buffers: Vec<(usize, &[u8])> = if component {
+        let modules = core_modules(&input_binary)?;
+        match cli.component_index {
+            Some(index) => match modules.get(index) {
+                Some(module) => vec![(index, *module)],
+                None => bail!(
+                    "no core module {} in a component with {}",
+                    index,
+                    modules.len()
+                ),
+            },
+            None => modules.into_iter().enumerate().collect(),
+        }
+    } else {
+        if cli.component_index.is_some() {
+            bail!("--component-index can only be used with a component");
+        }
+        vec![(0, &input_binary[..])]
+    };
+    if buffers.len() > 1 && (cli.picks_funcs() || cli.name_map.is_some() || cli.line_map.is_some())
+    {
+        bail!(
+            "the component has {} core modules, pick one with --component-index",
+            buffers.len()
+        );
+    }
 
-    let output: Box<dyn std::io::Write> = if let Some(output_path) = cli.output {
-        Box::new(std::fs::File::create(&output_path)?)
+    let mut output: Box<dyn std::io::Write> = if let Some(output_path) = &cli.output {
+        Box::new(std::fs::File::create(output_path)?)
     } else {
         Box::new(std::io::stdout())
     };
 
-    if let Some(func_index) = cli.func_index {
-        if cli.graphviz {
-            module.write_func_graphviz(func_index, output)?;
+    for (index, buffer) in &buffers {
+        let mut module = Module::from_buffer_with_options(buffer, &decode_options)?;
+        for (name, path, other) in &links {
+            module
+                .link(name, other)
+                .with_context(|| format!("cannot link `{}`", path.display()))?;
+        }
+        if component && matches!(cli.format, Format::Text | Format::Ansi) {
+            writeln!(output, "// core module {}", index)?;
+        }
+        if let Some(path) = &cli.name_map {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("cannot create `{}`", path.display()))?;
+            let mut name_map = std::io::BufWriter::new(file);
+            module.write_name_map(&mut name_map)?;
+            name_map.flush()?;
+        }
+        if let Some(path) = &cli.line_map {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("cannot create `{}`", path.display()))?;
+            let mut line_map = std::io::BufWriter::new(file);
+            module.write_line_map_with_options(&mut line_map, &options)?;
+            line_map.flush()?;
+        }
+        write_module(cli, &module, &options, &mut output)?;
+    }
+
+    Ok(())
+}
+
+fn write_module(
+    cli: &Cli,
+    module: &Module,
+    options: &RenderOptions,
+    mut output: impl std::io::Write,
+) -> anyhow::Result<()> {
+    if cli.list {
+        module.write_list(output)?;
+        return Ok(());
+    }
+
+    if let Some(n) = cli.top {
+        let ranking = match cli.top_by {
+            TopBy::Size => Ranking::Size,
+            TopBy::Lines => Ranking::Lines,
+        };
+        module.write_top(n, ranking, output)?;
+        return Ok(());
+    }
+
+    if cli.globals {
+        for report in module.global_report() {
+            writeln!(output, "{}", report)?;
+        }
+        return Ok(());
+    }
+
+    if let Some(abi) = cli.abi {
+        let format = match abi {
+            Abi::Text => AbiFormat::Text,
+            Abi::Markdown => AbiFormat::Markdown,
+            Abi::Json => AbiFormat::Json,
+        };
+        module.write_abi(output, format)?;
+        return Ok(());
+    }
+
+    let filter = match &cli.filter {
+        Some(filter) => {
+            Some(Regex::new(filter).with_context(|| format!("invalid --filter `{}`", filter))?)
+        }
+        None => None,
+    };
+    let mut func_indices = vec![];
+    for selection in &cli.funcs {
+        match selection {
+            FuncSelection::Index(func_index) => func_indices.push(FuncIdx::from(*func_index)),
+            FuncSelection::Range(range) => func_indices
+                .extend(module.funcs_in(FuncIdx::from(range.start)..FuncIdx::from(range.end))),
+        }
+    }
+    if let Some(name) = &cli.export {
+        match module.exported_func(name) {
+            Some(func_index) => func_indices.push(func_index),
+            None => bail!("no function is exported as `{}`", name),
+        }
+    }
+    if let Some(filter) = &filter {
+        func_indices.extend(module.find_funcs(|name| filter.is_match(name)));
+    }
+    func_indices.sort();
+    func_indices.dedup();
+    if cli.picks_funcs() && func_indices.is_empty() {
+        bail!("no functions match -f, -e or --filter");
+    }
+
+    for func_index in &func_indices {
+        for (pass, text) in module.pass_dumps(*func_index) {
+            writeln!(output, "// after {}", pass.name())?;
+            writeln!(output, "{}", text)?;
+        }
+    }
+
+    if let Some(Dump::Dominators) = cli.dump {
+        if cli.picks_funcs() {
+            for func_index in func_indices {
+                module.write_func_dominators(func_index, &mut output)?;
+            }
+        } else {
+            module.write_dominators(output)?;
+        }
+        return Ok(());
+    }
+
+    if let Some(pattern) = &cli.grep {
+        let pattern =
+            Regex::new(pattern).with_context(|| format!("invalid --grep `{}`", pattern))?;
+        if cli.picks_funcs() {
+            module.write_grep_in(&func_indices, |line| pattern.is_match(line), output)?;
         } else {
-            module.write_func(func_index, output)?;
+            module.write_grep(|line| pattern.is_match(line), output)?;
+        }
+        return Ok(());
+    }
+
+    let graphviz_options = GraphvizOptions {
+        max_statements: cli.graphviz_max_statements,
+        max_line_length: cli.graphviz_max_line_length,
+    };
+    if cli.picks_funcs() {
+        if cli.graphviz.is_some() && func_indices.len() > 1 {
+            bail!(
+                "graphviz output is for one function, but {} were picked",
+                func_indices.len()
+            );
+        }
+        match cli.graphviz {
+            Some(Graphviz::Cfg) => module.write_func_graphviz_with_options(
+                func_indices[0],
+                output,
+                &graphviz_options,
+            )?,
+            Some(Graphviz::Domtree) => module.write_func_dominators_graphviz_with_options(
+                func_indices[0],
+                output,
+                &graphviz_options,
+            )?,
+            None if cli.format == Format::Jsonl => {
+                for func_index in func_indices {
+                    module.write_func_jsonl(func_index, &mut output)?;
+                }
+            }
+            None => module.write_funcs_at(&func_indices, output, options)?,
         }
     } else {
-        if cli.graphviz {
-            bail!("cannot use graphviz on a whole module");
+        match cli.graphviz {
+            Some(Graphviz::Cfg) => module.write_call_graph_graphviz(output)?,
+            Some(Graphviz::Domtree) => {
+                bail!("--graphviz=domtree needs a function, pick one with -f")
+            }
+            None if cli.format == Format::Jsonl => module.write_jsonl(output)?,
+            None => module.write_with_options(output, options)?,
         }
-        module.write(output)?;
     }
 
     Ok(())
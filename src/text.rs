@@ -0,0 +1,1261 @@
+//! Parser for the textual format produced by `Module::write`.
+//!
+//! The grammar is purely syntactic. It doesn't know about wasm types or
+//! operators, it only recovers the shape of the output so that tools can
+//! consume it without scraping lines.
+//!
+//! ```text
+//! module   ::= 'module' '{' global* func* '}'
+//! global   ::= 'global' operand ':' 'mut'? type ('=' expr)?
+//! func     ::= 'func' NAME '(' bindings? ')' ('(' 'start' ')')? export* '{' binding* block* '}'
+//!            | 'func' NAME '=' 'alias' 'of' 'func' NAME
+//! export   ::= '(' 'export' STRING ')'     ; STRING is escaped as by Rust's `{:?}`
+//! bindings ::= binding (',' binding)*
+//! binding  ::= NAME ':' type
+//! type     ::= NAME | '(' (NAME | NUMBER | type)* ')'
+//! block    ::= label? stmt*            ; only the entry block is unlabeled
+//! label    ::= LABEL ('(' bindings ')')? ':'
+//! stmt     ::= 'nop' | 'unreachable' | 'unknown'
+//!            | 'return' exprs?         ; values must start on the same line
+//!            | br
+//!            | 'if' expr br br
+//!            | 'if' expr '{' stmt* '}' else?
+//!            | 'while' expr '{' stmt* '}'
+//!            | 'do' '{' stmt* '}' 'while' expr
+//!            | 'block' LABEL '{' stmt* '}'
+//!            | 'loop' LABEL? '{' stmt* '}'
+//!            | 'break' LABEL? | 'continue' LABEL?
+//!            | 'br_table' '[' expr ']' '(' (LABEL (',' LABEL)*)? 'default' LABEL ')' with?
+//!            | 'try' br catch*
+//!            | 'throw' NAME '(' exprs? ')'
+//!            | 'rethrow' expr
+//!            | expr (',' expr)* '=' expr
+//!            | expr
+//! else     ::= 'else' ('{' stmt* '}' | 'if' expr '{' stmt* '}' else?)
+//! br       ::= 'br' LABEL with?
+//! with     ::= 'with' '(' exprs ')'
+//! catch    ::= ('catch' | 'catch_ref') NAME 'br' LABEL
+//!            | ('catch_all' | 'catch_all_ref') 'br' LABEL
+//! exprs    ::= expr (',' expr)*
+//! expr     ::= operand (OP operand | '?' operand ':' operand | ('as' | 'is') type)?
+//! operand  ::= '*' operand | '!' operand | primary ('(' exprs? ')' | '[' expr ']' | '.' NAME)*
+//! primary  ::= NUMBER | NAME | '(' expr ')'
+//! ```
+//!
+//! Operands of infix operators and selects are always parenthesized by the
+//! printer when they are themselves compound, so no precedence rules are
+//! needed. `//` and `/* */` comments are ignored.
+
+use std::collections::HashSet;
+
+use anyhow::{anyhow, bail};
+
+/// A parsed `module { ... }`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub globals: Vec<Global>,
+    pub funcs: Vec<Func>,
+}
+
+/// A parsed global declaration. Imported globals have no `init`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    /// e.g. `globals[0]` or `__stack_pointer`
+    pub name: Expression,
+    pub mutable: bool,
+    pub ty: String,
+    pub init: Option<Expression>,
+}
+
+/// A parsed function definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Func {
+    pub name: String,
+    pub params: Vec<Binding>,
+    /// Whether this is the module's start function.
+    pub start: bool,
+    /// The names the function is exported under.
+    pub exports: Vec<String>,
+    pub locals: Vec<Binding>,
+    pub blocks: Vec<Block>,
+    /// For `func 42 = alias of func 17`, the function this duplicates. An
+    /// alias has no params, locals or blocks of its own.
+    pub alias_of: Option<String>,
+}
+
+/// A `name: type` pair, used for params, locals and block params.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub name: String,
+    pub ty: String,
+}
+
+/// A basic block. `label` is `None` only for the entry block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub label: Option<u32>,
+    pub params: Vec<Binding>,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Nop,
+    Unreachable,
+    Unknown,
+    Return(Vec<Expression>),
+    Br(Branch),
+    BrIf {
+        condition: Expression,
+        if_true: Branch,
+        if_false: Branch,
+    },
+    BrTable {
+        index: Expression,
+        targets: Vec<u32>,
+        default: u32,
+        args: Vec<Expression>,
+    },
+    /// `try br @N` followed by the handlers, one clause per line.
+    Try {
+        body: Branch,
+        catches: Vec<Catch>,
+    },
+    Throw {
+        tag: String,
+        args: Vec<Expression>,
+    },
+    Rethrow(Expression),
+    If {
+        condition: Expression,
+        true_statements: Vec<Statement>,
+        false_statements: Vec<Statement>,
+    },
+    /// `while (c) { ... }`, or `do { ... } while (c)` if `do_while`.
+    While {
+        condition: Expression,
+        body: Vec<Statement>,
+        do_while: bool,
+    },
+    /// `block @N { ... }`, which `break @N` leaves.
+    Block {
+        label: u32,
+        body: Vec<Statement>,
+    },
+    /// `loop @N { ... }`, which `continue @N` goes back to the top of and
+    /// `break @N` leaves. The label is left out if only the loop's own
+    /// `break`s and `continue`s go to it.
+    Loop {
+        label: Option<u32>,
+        body: Vec<Statement>,
+    },
+    /// `break @N`, or `break` for the innermost loop.
+    Break(Option<u32>),
+    /// `continue @N`, or `continue` for the innermost loop.
+    Continue(Option<u32>),
+    Assign {
+        targets: Vec<Expression>,
+        value: Expression,
+    },
+    Expression(Expression),
+}
+
+/// A `br @N with (...)` edge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch {
+    pub target: u32,
+    pub args: Vec<Expression>,
+}
+
+/// A `catch tag0 br @N` clause. `kind` is the keyword, one of `catch`,
+/// `catch_ref`, `catch_all` and `catch_all_ref`; the `_all` forms have no
+/// tag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catch {
+    pub kind: String,
+    pub tag: Option<String>,
+    pub target: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    /// Numbers are kept as written so float formatting round-trips.
+    Number(String),
+    Name(String),
+    Call {
+        callee: Box<Expression>,
+        args: Vec<Expression>,
+    },
+    Index {
+        base: Box<Expression>,
+        index: Box<Expression>,
+    },
+    Deref(Box<Expression>),
+    /// `!value`
+    Not(Box<Expression>),
+    Binary {
+        op: String,
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Select {
+        condition: Box<Expression>,
+        on_true: Box<Expression>,
+        on_false: Box<Expression>,
+    },
+    /// `value as type` or `value is type`.
+    Cast {
+        keyword: String,
+        value: Box<Expression>,
+        ty: String,
+    },
+    /// `base.name` where `base` isn't a name. Fields of names are lexed as
+    /// part of the name, e.g. `arg0.field1`.
+    Field {
+        base: Box<Expression>,
+        name: String,
+    },
+}
+
+/// Parse the output of `Module::write`.
+pub fn parse(text: &str) -> anyhow::Result<Module> {
+    let tokens = lex(text)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let module = parser.module()?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        bail!("{}: trailing input {:?}", token.line, token.kind);
+    }
+    Ok(module)
+}
+
+// Names the printer emits without declaring them.
+const BUILTIN_NAMES: &[&str] = &[
+    "memory.size",
+    "bottom",
+    "null",
+    "__stack_pointer",
+    "__memory_base",
+    "__table_base",
+];
+
+impl Module {
+    /// Check invariants that any printed module should satisfy: block labels
+    /// are unique, every branch targets a label defined in the same
+    /// function, and every name read or assigned is a param, local or param
+    /// of the enclosing block, a function of the module, an imported
+    /// function such as `env.log`, or a `$name` from the name section.
+    ///
+    /// Callees and the bases of index expressions (`memory[...]`,
+    /// `globals[...]`) are not checked.
+    pub fn check(&self) -> anyhow::Result<()> {
+        let funcs: HashSet<&str> = self.funcs.iter().map(|func| func.name.as_str()).collect();
+        for func in &self.funcs {
+            func.check(&funcs)?;
+        }
+        Ok(())
+    }
+}
+
+impl Func {
+    fn check(&self, funcs: &HashSet<&str>) -> anyhow::Result<()> {
+        let mut labels = HashSet::new();
+        for block in &self.blocks {
+            if let Some(label) = block.label {
+                if !labels.insert(label) {
+                    bail!("func {}: duplicate label @{}", self.name, label);
+                }
+            }
+        }
+
+        for block in &self.blocks {
+            let mut scope: HashSet<&str> = BUILTIN_NAMES.iter().copied().collect();
+            scope.extend(funcs);
+            for binding in self.params.iter().chain(&self.locals).chain(&block.params) {
+                scope.insert(&binding.name);
+            }
+            let checker = Checker {
+                func: &self.name,
+                labels: &labels,
+                scope: &scope,
+                enclosing: Vec::new(),
+            };
+            for statement in &block.statements {
+                checker.statement(statement)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Checker<'a> {
+    func: &'a str,
+    labels: &'a HashSet<u32>,
+    scope: &'a HashSet<&'a str>,
+    // The labels of the `block`s and `loop`s the statement is in, innermost
+    // last, and whether each is a loop.
+    enclosing: Vec<(Option<u32>, bool)>,
+}
+
+impl Checker<'_> {
+    fn nested(&self, label: Option<u32>, is_loop: bool) -> Self {
+        let mut enclosing = self.enclosing.clone();
+        enclosing.push((label, is_loop));
+        Checker { enclosing, ..*self }
+    }
+
+    fn statements(&self, statements: &[Statement]) -> anyhow::Result<()> {
+        for statement in statements {
+            self.statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&self, statement: &Statement) -> anyhow::Result<()> {
+        match statement {
+            Statement::Nop | Statement::Unreachable | Statement::Unknown => Ok(()),
+            Statement::Return(values) => self.expressions(values),
+            Statement::Br(branch) => self.branch(branch),
+            Statement::BrIf {
+                condition,
+                if_true,
+                if_false,
+            } => {
+                self.expression(condition)?;
+                self.branch(if_true)?;
+                self.branch(if_false)
+            }
+            Statement::BrTable {
+                index,
+                targets,
+                default,
+                args,
+            } => {
+                self.expression(index)?;
+                for target in targets.iter().chain(Some(default)) {
+                    self.label(*target)?;
+                }
+                self.expressions(args)
+            }
+            Statement::Try { body, catches } => {
+                self.branch(body)?;
+                for catch in catches {
+                    self.label(catch.target)?;
+                }
+                Ok(())
+            }
+            Statement::Throw { args, .. } => self.expressions(args),
+            Statement::Rethrow(exception) => self.expression(exception),
+            Statement::If {
+                condition,
+                true_statements,
+                false_statements,
+            } => {
+                self.expression(condition)?;
+                self.statements(true_statements)?;
+                self.statements(false_statements)
+            }
+            Statement::While {
+                condition, body, ..
+            } => {
+                self.expression(condition)?;
+                self.statements(body)
+            }
+            Statement::Block { label, body } => self.nested(Some(*label), false).statements(body),
+            Statement::Loop { label, body } => self.nested(*label, true).statements(body),
+            Statement::Break(label) => self.jump("break", *label),
+            Statement::Continue(label) => self.jump("continue", *label),
+            Statement::Assign { targets, value } => {
+                self.expressions(targets)?;
+                self.expression(value)
+            }
+            Statement::Expression(expression) => self.expression(expression),
+        }
+    }
+
+    fn branch(&self, branch: &Branch) -> anyhow::Result<()> {
+        self.label(branch.target)?;
+        self.expressions(&branch.args)
+    }
+
+    // `break` leaves an enclosing `block` or `loop` and `continue` goes back
+    // to the top of an enclosing `loop`. Without a label they're for the
+    // innermost loop.
+    fn jump(&self, keyword: &str, label: Option<u32>) -> anyhow::Result<()> {
+        let found = match label {
+            Some(label) => self
+                .enclosing
+                .iter()
+                .any(|(x, is_loop)| *x == Some(label) && (*is_loop || keyword == "break")),
+            None => self.enclosing.iter().any(|(_, is_loop)| *is_loop),
+        };
+        if !found {
+            match label {
+                Some(label) => bail!(
+                    "func {}: {} to unenclosing label @{}",
+                    self.func,
+                    keyword,
+                    label
+                ),
+                None => bail!("func {}: {} outside a loop", self.func, keyword),
+            }
+        }
+        Ok(())
+    }
+
+    // A `br_table` in a nested body can go to any enclosing label.
+    fn label(&self, label: u32) -> anyhow::Result<()> {
+        if !self.labels.contains(&label) && !self.enclosing.iter().any(|(x, _)| *x == Some(label)) {
+            bail!("func {}: branch to undefined label @{}", self.func, label);
+        }
+        Ok(())
+    }
+
+    fn expressions(&self, expressions: &[Expression]) -> anyhow::Result<()> {
+        for expression in expressions {
+            self.expression(expression)?;
+        }
+        Ok(())
+    }
+
+    fn expression(&self, expression: &Expression) -> anyhow::Result<()> {
+        match expression {
+            Expression::Number(_) => Ok(()),
+            Expression::Name(name) => {
+                // `funcN` is a reference to a function, and `$name` a
+                // global named by the name section.
+                let is_func_ref = name
+                    .strip_prefix("func")
+                    .is_some_and(|index| index.parse::<u32>().is_ok());
+                let is_debug_name = name.starts_with('$');
+                // `x.field1` is a field of `x`, and any other dotted name an
+                // imported function such as `env.log`.
+                let base = name.split('.').next().unwrap_or(name);
+                let is_import = name
+                    .rsplit_once('.')
+                    .is_some_and(|(_, last)| !last.starts_with("field"));
+                if !is_func_ref
+                    && !is_debug_name
+                    && !is_import
+                    && !self.scope.contains(name.as_str())
+                    && !self.scope.contains(base)
+                {
+                    bail!("func {}: use of undeclared name `{}`", self.func, name);
+                }
+                Ok(())
+            }
+            Expression::Call { callee, args } => {
+                if !matches!(**callee, Expression::Name(_)) {
+                    self.expression(callee)?;
+                }
+                self.expressions(args)
+            }
+            Expression::Index { base, index } => {
+                if !matches!(**base, Expression::Name(_)) {
+                    self.expression(base)?;
+                }
+                self.expression(index)
+            }
+            Expression::Deref(value)
+            | Expression::Not(value)
+            | Expression::Cast { value, .. }
+            | Expression::Field { base: value, .. } => self.expression(value),
+            Expression::Binary { lhs, rhs, .. } => {
+                self.expression(lhs)?;
+                self.expression(rhs)
+            }
+            Expression::Select {
+                condition,
+                on_true,
+                on_false,
+            } => {
+                self.expression(condition)?;
+                self.expression(on_true)?;
+                self.expression(on_false)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Name(String),
+    Number(String),
+    Label(u32),
+    Op(String),
+    Punct(char),
+    String(String),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '.'
+}
+
+fn is_op_char(c: char) -> bool {
+    "=!<>+-*/%&|^".contains(c)
+}
+
+fn lex(text: &str) -> anyhow::Result<Vec<Token>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    let take_while = |mut i: usize, f: &dyn Fn(char) -> bool| {
+        while i < chars.len() && f(chars[i]) {
+            i += 1;
+        }
+        i
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let start = i;
+
+        if c == '\n' {
+            line += 1;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        // Comments
+        if c == '/' && next == Some('/') {
+            i = take_while(i, &|c| c != '\n');
+            continue;
+        }
+        if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            if i >= chars.len() {
+                bail!("{}: unterminated comment", line);
+            }
+            i += 2;
+            continue;
+        }
+
+        let kind = if c.is_ascii_digit()
+            || (c == '-' && next.is_some_and(|n| n.is_ascii_digit() || n == 'i' || n == 'N'))
+        {
+            i = take_while(i + 1, &|c| c.is_ascii_alphanumeric() || c == '.');
+            TokenKind::Number(chars[start..i].iter().collect())
+        } else if is_name_start(c) {
+            i = take_while(i, &is_name_char);
+            // Demangled names are paths like `core::fmt::write`.
+            while chars.get(i) == Some(&':')
+                && chars.get(i + 1) == Some(&':')
+                && chars.get(i + 2).is_some_and(|c| is_name_char(*c))
+            {
+                i = take_while(i + 2, &is_name_char);
+            }
+            let name: String = chars[start..i].iter().collect();
+            if name == "inf" || name == "NaN" {
+                TokenKind::Number(name)
+            } else {
+                TokenKind::Name(name)
+            }
+        } else if c == '@' {
+            i = take_while(i + 1, &|c| c.is_ascii_digit());
+            let digits: String = chars[start + 1..i].iter().collect();
+            match digits.parse() {
+                Ok(label) => TokenKind::Label(label),
+                Err(_) => bail!("{}: malformed block label", line),
+            }
+        } else if c == '#' {
+            i = take_while(i + 1, &is_name_char);
+            TokenKind::Op(chars[start..i].iter().collect())
+        } else if c == '!' && next != Some('=') {
+            // Only `!=` continues a `!`, so `!-1` is `!` then `-1`.
+            i += 1;
+            TokenKind::Op("!".to_string())
+        } else if is_op_char(c) {
+            i = take_while(i, &is_op_char);
+            // Signedness suffixes, e.g. `<_s` or `>>_u`
+            if chars.get(i) == Some(&'_')
+                && matches!(chars.get(i + 1), Some('s') | Some('u'))
+                && !chars.get(i + 2).copied().is_some_and(is_name_char)
+            {
+                i += 2;
+            }
+            TokenKind::Op(chars[start..i].iter().collect())
+        } else if c == '"' {
+            let (string, end) =
+                lex_string(&chars, i + 1).map_err(|err| anyhow!("{}: {}", line, err))?;
+            i = end;
+            TokenKind::String(string)
+        } else if "(){}[],:?.".contains(c) {
+            i += 1;
+            TokenKind::Punct(c)
+        } else {
+            bail!("{}: unexpected character {:?}", line, c);
+        };
+        tokens.push(Token { kind, line });
+    }
+
+    Ok(tokens)
+}
+
+// The string starting after the opening quote at `start`, unescaped, and
+// the index after its closing quote.
+fn lex_string(chars: &[char], start: usize) -> anyhow::Result<(String, usize)> {
+    let mut string = String::new();
+    let mut i = start;
+    loop {
+        let Some(&c) = chars.get(i) else {
+            bail!("unterminated string");
+        };
+        i += 1;
+        match c {
+            '"' => return Ok((string, i)),
+            '\n' => bail!("unterminated string"),
+            '\\' => {
+                let Some(&escape) = chars.get(i) else {
+                    bail!("unterminated string");
+                };
+                i += 1;
+                string.push(match escape {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    '0' => '\0',
+                    '\\' | '"' | '\'' => escape,
+                    'u' if chars.get(i) == Some(&'{') => {
+                        let Some(len) = chars[i..].iter().position(|c| *c == '}') else {
+                            bail!("unterminated unicode escape");
+                        };
+                        let hex: String = chars[i + 1..i + len].iter().collect();
+                        i += len + 1;
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| anyhow!("invalid unicode escape `{}`", hex))?
+                    }
+                    _ => bail!("unknown escape `\\{}`", escape),
+                });
+            }
+            c => string.push(c),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|x| &x.kind)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&TokenKind> {
+        self.tokens.get(self.pos + offset).map(|x| &x.kind)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map(|x| x.line)
+            .unwrap_or(1)
+    }
+
+    // Whether the next token is on the same line as the previous one.
+    fn continues_line(&self) -> bool {
+        match (self.pos.checked_sub(1), self.tokens.get(self.pos)) {
+            (Some(prev), Some(token)) => self.tokens[prev].line == token.line,
+            _ => false,
+        }
+    }
+
+    fn next(&mut self) -> anyhow::Result<TokenKind> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.kind.clone())
+            }
+            None => bail!("{}: unexpected end of input", self.line()),
+        }
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek() == Some(&TokenKind::Punct(c))
+    }
+
+    fn is_name(&self, name: &str) -> bool {
+        matches!(self.peek(), Some(TokenKind::Name(x)) if x == name)
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        if self.is_punct(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, c: char) -> anyhow::Result<()> {
+        if !self.eat_punct(c) {
+            bail!("{}: expected `{}`, found {:?}", self.line(), c, self.peek());
+        }
+        Ok(())
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_name(keyword) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> anyhow::Result<()> {
+        if !self.is_name(keyword) {
+            bail!(
+                "{}: expected `{}`, found {:?}",
+                self.line(),
+                keyword,
+                self.peek()
+            );
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn name(&mut self) -> anyhow::Result<String> {
+        match self.next()? {
+            TokenKind::Name(name) => Ok(name),
+            TokenKind::Number(number) => Ok(number),
+            other => bail!("{}: expected a name, found {:?}", self.line(), other),
+        }
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        match self.next()? {
+            TokenKind::String(string) => Ok(string),
+            other => bail!("{}: expected a string, found {:?}", self.line(), other),
+        }
+    }
+
+    fn label(&mut self) -> anyhow::Result<u32> {
+        match self.next()? {
+            TokenKind::Label(label) => Ok(label),
+            other => bail!("{}: expected a block label, found {:?}", self.line(), other),
+        }
+    }
+
+    fn optional_label(&mut self) -> anyhow::Result<Option<u32>> {
+        if matches!(self.peek(), Some(TokenKind::Label(_))) {
+            return Ok(Some(self.label()?));
+        }
+        Ok(None)
+    }
+
+    fn module(&mut self) -> anyhow::Result<Module> {
+        self.expect_keyword("module")?;
+        self.expect_punct('{')?;
+        let mut globals = Vec::new();
+        while self.is_name("global") {
+            globals.push(self.global()?);
+        }
+        let mut funcs = Vec::new();
+        while !self.eat_punct('}') {
+            funcs.push(self.func()?);
+        }
+        Ok(Module { globals, funcs })
+    }
+
+    fn global(&mut self) -> anyhow::Result<Global> {
+        self.expect_keyword("global")?;
+        let name = self.operand()?;
+        self.expect_punct(':')?;
+        let mutable = self.is_name("mut");
+        if mutable {
+            self.pos += 1;
+        }
+        let ty = self.ty()?;
+        let init = if self.peek_is_assign() {
+            self.pos += 1;
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        Ok(Global {
+            name,
+            mutable,
+            ty,
+            init,
+        })
+    }
+
+    fn func(&mut self) -> anyhow::Result<Func> {
+        self.expect_keyword("func")?;
+        let name = self.name()?;
+        if self.peek_is_assign() {
+            self.pos += 1;
+            self.expect_keyword("alias")?;
+            self.expect_keyword("of")?;
+            self.expect_keyword("func")?;
+            return Ok(Func {
+                name,
+                params: Vec::new(),
+                start: false,
+                exports: Vec::new(),
+                locals: Vec::new(),
+                blocks: Vec::new(),
+                alias_of: Some(self.name()?),
+            });
+        }
+        self.expect_punct('(')?;
+        let params = self.bindings(')')?;
+        let start = self.is_punct('(') && self.peek_at(1) == Some(&TokenKind::Name("start".into()));
+        if start {
+            self.pos += 2;
+            self.expect_punct(')')?;
+        }
+        let mut exports = Vec::new();
+        while self.eat_punct('(') {
+            self.expect_keyword("export")?;
+            exports.push(self.string()?);
+            self.expect_punct(')')?;
+        }
+        self.expect_punct('{')?;
+
+        let mut locals = Vec::new();
+        while matches!(self.peek(), Some(TokenKind::Name(_)))
+            && self.peek_at(1) == Some(&TokenKind::Punct(':'))
+        {
+            locals.push(self.binding()?);
+        }
+
+        let mut blocks = Vec::new();
+        while !self.eat_punct('}') {
+            blocks.push(self.block(blocks.is_empty())?);
+        }
+
+        Ok(Func {
+            name,
+            params,
+            start,
+            exports,
+            locals,
+            blocks,
+            alias_of: None,
+        })
+    }
+
+    // Parses a comma separated list of bindings, consuming `close`.
+    fn bindings(&mut self, close: char) -> anyhow::Result<Vec<Binding>> {
+        let mut bindings = Vec::new();
+        if self.eat_punct(close) {
+            return Ok(bindings);
+        }
+        loop {
+            bindings.push(self.binding()?);
+            if self.eat_punct(close) {
+                return Ok(bindings);
+            }
+            self.expect_punct(',')?;
+        }
+    }
+
+    fn binding(&mut self) -> anyhow::Result<Binding> {
+        let name = self.name()?;
+        self.expect_punct(':')?;
+        let ty = self.ty()?;
+        Ok(Binding { name, ty })
+    }
+
+    fn ty(&mut self) -> anyhow::Result<String> {
+        if !self.eat_punct('(') {
+            return self.name();
+        }
+        let mut parts = Vec::new();
+        while !self.eat_punct(')') {
+            if self.is_punct('(') {
+                parts.push(self.ty()?);
+            } else {
+                parts.push(self.name()?);
+            }
+        }
+        Ok(format!("({})", parts.join(" ")))
+    }
+
+    fn block(&mut self, is_entry: bool) -> anyhow::Result<Block> {
+        let mut label = None;
+        let mut params = Vec::new();
+        if matches!(self.peek(), Some(TokenKind::Label(_))) {
+            label = Some(self.label()?);
+            if self.eat_punct('(') {
+                params = self.bindings(')')?;
+            }
+            self.expect_punct(':')?;
+        } else if !is_entry {
+            bail!("{}: expected a block label", self.line());
+        }
+
+        let mut statements = Vec::new();
+        while !self.is_punct('}') && !matches!(self.peek(), Some(TokenKind::Label(_))) {
+            statements.push(self.statement()?);
+        }
+
+        Ok(Block {
+            label,
+            params,
+            statements,
+        })
+    }
+
+    fn statements(&mut self) -> anyhow::Result<Vec<Statement>> {
+        self.expect_punct('{')?;
+        let mut statements = Vec::new();
+        while !self.eat_punct('}') {
+            statements.push(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> anyhow::Result<Statement> {
+        if let Some(TokenKind::Name(keyword)) = self.peek() {
+            match keyword.as_str() {
+                "nop" => {
+                    self.pos += 1;
+                    return Ok(Statement::Nop);
+                }
+                "unreachable" => {
+                    self.pos += 1;
+                    return Ok(Statement::Unreachable);
+                }
+                "unknown" => {
+                    self.pos += 1;
+                    return Ok(Statement::Unknown);
+                }
+                "return" => {
+                    let line = self.line();
+                    self.pos += 1;
+                    let values = if self.pos < self.tokens.len()
+                        && self.line() == line
+                        && self.starts_expression()
+                    {
+                        self.expressions()?
+                    } else {
+                        Vec::new()
+                    };
+                    return Ok(Statement::Return(values));
+                }
+                "br" => return Ok(Statement::Br(self.branch()?)),
+                "br_table" => return self.br_table(),
+                "try" => {
+                    self.pos += 1;
+                    let body = self.branch()?;
+                    let mut catches = Vec::new();
+                    while let Some(kind) = self.catch_kind() {
+                        self.pos += 1;
+                        let tag = if kind.starts_with("catch_all") {
+                            None
+                        } else {
+                            Some(self.name()?)
+                        };
+                        self.expect_keyword("br")?;
+                        let target = self.label()?;
+                        catches.push(Catch { kind, tag, target });
+                    }
+                    return Ok(Statement::Try { body, catches });
+                }
+                "throw" => {
+                    self.pos += 1;
+                    let tag = self.name()?;
+                    self.expect_punct('(')?;
+                    let args = if self.eat_punct(')') {
+                        Vec::new()
+                    } else {
+                        let args = self.expressions()?;
+                        self.expect_punct(')')?;
+                        args
+                    };
+                    return Ok(Statement::Throw { tag, args });
+                }
+                "rethrow" => {
+                    self.pos += 1;
+                    return Ok(Statement::Rethrow(self.expression()?));
+                }
+                "if" => {
+                    self.pos += 1;
+                    let condition = self.expression()?;
+                    if self.is_punct('{') {
+                        let true_statements = self.statements()?;
+                        let false_statements = if !self.eat_keyword("else") {
+                            Vec::new()
+                        } else if self.is_name("if") {
+                            vec![self.statement()?]
+                        } else {
+                            self.statements()?
+                        };
+                        return Ok(Statement::If {
+                            condition,
+                            true_statements,
+                            false_statements,
+                        });
+                    }
+                    let if_true = self.branch()?;
+                    let if_false = self.branch()?;
+                    return Ok(Statement::BrIf {
+                        condition,
+                        if_true,
+                        if_false,
+                    });
+                }
+                "while" => {
+                    self.pos += 1;
+                    let condition = self.expression()?;
+                    let body = self.statements()?;
+                    return Ok(Statement::While {
+                        condition,
+                        body,
+                        do_while: false,
+                    });
+                }
+                "do" => {
+                    self.pos += 1;
+                    let body = self.statements()?;
+                    self.expect_keyword("while")?;
+                    let condition = self.expression()?;
+                    return Ok(Statement::While {
+                        condition,
+                        body,
+                        do_while: true,
+                    });
+                }
+                "block" => {
+                    self.pos += 1;
+                    let label = self.label()?;
+                    let body = self.statements()?;
+                    return Ok(Statement::Block { label, body });
+                }
+                "loop" => {
+                    self.pos += 1;
+                    let label = self.optional_label()?;
+                    let body = self.statements()?;
+                    return Ok(Statement::Loop { label, body });
+                }
+                "break" => {
+                    self.pos += 1;
+                    return Ok(Statement::Break(self.optional_label()?));
+                }
+                "continue" => {
+                    self.pos += 1;
+                    return Ok(Statement::Continue(self.optional_label()?));
+                }
+                _ => {}
+            }
+        }
+
+        let first = self.expression()?;
+        if !self.is_punct(',') && !self.peek_is_assign() {
+            return Ok(Statement::Expression(first));
+        }
+
+        let mut targets = vec![first];
+        while self.eat_punct(',') {
+            targets.push(self.expression()?);
+        }
+        if !self.peek_is_assign() {
+            bail!("{}: expected `=`, found {:?}", self.line(), self.peek());
+        }
+        self.pos += 1;
+        let value = self.expression()?;
+        Ok(Statement::Assign { targets, value })
+    }
+
+    fn peek_is_assign(&self) -> bool {
+        matches!(self.peek(), Some(TokenKind::Op(op)) if op == "=")
+    }
+
+    fn branch(&mut self) -> anyhow::Result<Branch> {
+        self.expect_keyword("br")?;
+        let target = self.label()?;
+        let args = self.with_args()?;
+        Ok(Branch { target, args })
+    }
+
+    fn catch_kind(&self) -> Option<String> {
+        match self.peek() {
+            Some(TokenKind::Name(name))
+                if matches!(
+                    name.as_str(),
+                    "catch" | "catch_ref" | "catch_all" | "catch_all_ref"
+                ) =>
+            {
+                Some(name.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn br_table(&mut self) -> anyhow::Result<Statement> {
+        self.expect_keyword("br_table")?;
+        self.expect_punct('[')?;
+        let index = self.expression()?;
+        self.expect_punct(']')?;
+        self.expect_punct('(')?;
+        let mut targets = Vec::new();
+        while !self.is_name("default") {
+            targets.push(self.label()?);
+            if !self.is_name("default") {
+                self.expect_punct(',')?;
+            }
+        }
+        self.expect_keyword("default")?;
+        let default = self.label()?;
+        self.expect_punct(')')?;
+        let args = self.with_args()?;
+        Ok(Statement::BrTable {
+            index,
+            targets,
+            default,
+            args,
+        })
+    }
+
+    fn with_args(&mut self) -> anyhow::Result<Vec<Expression>> {
+        if !self.is_name("with") {
+            return Ok(Vec::new());
+        }
+        self.pos += 1;
+        self.expect_punct('(')?;
+        let args = self.expressions()?;
+        self.expect_punct(')')?;
+        Ok(args)
+    }
+
+    fn starts_expression(&self) -> bool {
+        match self.peek() {
+            Some(TokenKind::Name(_)) | Some(TokenKind::Number(_)) => true,
+            Some(TokenKind::Punct('(')) => true,
+            Some(TokenKind::Op(op)) => op == "*",
+            _ => false,
+        }
+    }
+
+    fn expressions(&mut self) -> anyhow::Result<Vec<Expression>> {
+        let mut exprs = vec![self.expression()?];
+        while self.eat_punct(',') {
+            exprs.push(self.expression()?);
+        }
+        Ok(exprs)
+    }
+
+    fn expression(&mut self) -> anyhow::Result<Expression> {
+        let lhs = self.operand()?;
+        match self.peek() {
+            Some(TokenKind::Name(keyword))
+                if (keyword == "as" || keyword == "is") && self.continues_line() =>
+            {
+                let keyword = keyword.clone();
+                self.pos += 1;
+                let ty = self.ty()?;
+                Ok(Expression::Cast {
+                    keyword,
+                    value: Box::new(lhs),
+                    ty,
+                })
+            }
+            // Statements are separated by newlines, so an operator on the
+            // next line starts a new statement, e.g. a store `*(x) = y`.
+            Some(TokenKind::Op(op)) if op != "=" && self.continues_line() => {
+                let op = op.clone();
+                self.pos += 1;
+                let rhs = self.operand()?;
+                Ok(Expression::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                })
+            }
+            Some(TokenKind::Punct('?')) => {
+                self.pos += 1;
+                let on_true = self.operand()?;
+                self.expect_punct(':')?;
+                let on_false = self.operand()?;
+                Ok(Expression::Select {
+                    condition: Box::new(lhs),
+                    on_true: Box::new(on_true),
+                    on_false: Box::new(on_false),
+                })
+            }
+            _ => Ok(lhs),
+        }
+    }
+
+    fn operand(&mut self) -> anyhow::Result<Expression> {
+        if matches!(self.peek(), Some(TokenKind::Op(op)) if op == "*") {
+            self.pos += 1;
+            return Ok(Expression::Deref(Box::new(self.operand()?)));
+        }
+        if matches!(self.peek(), Some(TokenKind::Op(op)) if op == "!") {
+            self.pos += 1;
+            return Ok(Expression::Not(Box::new(self.operand()?)));
+        }
+
+        let mut expr = match self.next()? {
+            TokenKind::Number(number) => Expression::Number(number),
+            TokenKind::Name(name) => Expression::Name(name),
+            TokenKind::Punct('(') => {
+                let inner = self.expression()?;
+                self.expect_punct(')')?;
+                inner
+            }
+            other => bail!("{}: expected an expression, found {:?}", self.line(), other),
+        };
+
+        loop {
+            if self.eat_punct('(') {
+                let args = if self.eat_punct(')') {
+                    Vec::new()
+                } else {
+                    let args = self.expressions()?;
+                    self.expect_punct(')')?;
+                    args
+                };
+                expr = Expression::Call {
+                    callee: Box::new(expr),
+                    args,
+                };
+            } else if self.eat_punct('.') {
+                let name = self.name()?;
+                expr = Expression::Field {
+                    base: Box::new(expr),
+                    name,
+                };
+            } else if self.eat_punct('[') {
+                let index = self.expression()?;
+                self.expect_punct(']')?;
+                expr = Expression::Index {
+                    base: Box::new(expr),
+                    index: Box::new(index),
+                };
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Property tests on the printed output of randomly generated functions.
+//!
+//! The generator produces valid wasm with arbitrarily nested control flow, so
+//! that every pass gets exercised on shapes the snapshot tests don't cover.
+
+use std::fmt::Write;
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+const NUM_LOCALS: u32 = 4;
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Const(i32),
+    Get(u32),
+    Tee(u32, Box<Expr>),
+    Eqz(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Select(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+// Branch depths are arbitrary and reduced modulo the number of enclosing
+// frames when printed, so that they always target a valid frame.
+#[derive(Debug, Clone)]
+enum Stmt {
+    Set(u32, Expr),
+    Drop(Expr),
+    Br(u32),
+    BrIf(u32, Expr),
+    BrTable(Vec<u32>, u32, Expr),
+    Return,
+    Unreachable,
+    Block(Vec<Stmt>),
+    Loop(Vec<Stmt>),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+}
+
+fn expr() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        any::<i32>().prop_map(Expr::Const),
+        (0..NUM_LOCALS).prop_map(Expr::Get),
+    ];
+    leaf.prop_recursive(4, 16, 3, |inner| {
+        prop_oneof![
+            (0..NUM_LOCALS, inner.clone()).prop_map(|(l, e)| Expr::Tee(l, Box::new(e))),
+            inner.clone().prop_map(|e| Expr::Eqz(Box::new(e))),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| Expr::Add(Box::new(a), Box::new(b))),
+            (inner.clone(), inner.clone(), inner).prop_map(|(c, a, b)| Expr::Select(
+                Box::new(c),
+                Box::new(a),
+                Box::new(b)
+            )),
+        ]
+    })
+}
+
+fn stmt() -> impl Strategy<Value = Stmt> {
+    let leaf = prop_oneof![
+        (0..NUM_LOCALS, expr()).prop_map(|(l, e)| Stmt::Set(l, e)),
+        expr().prop_map(Stmt::Drop),
+        any::<u32>().prop_map(Stmt::Br),
+        (any::<u32>(), expr()).prop_map(|(d, e)| Stmt::BrIf(d, e)),
+        (vec(any::<u32>(), 0..4), any::<u32>(), expr())
+            .prop_map(|(ds, d, e)| Stmt::BrTable(ds, d, e)),
+        Just(Stmt::Return),
+        Just(Stmt::Unreachable),
+    ];
+    leaf.prop_recursive(5, 48, 4, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..4).prop_map(Stmt::Block),
+            vec(inner.clone(), 0..4).prop_map(Stmt::Loop),
+            (expr(), vec(inner.clone(), 0..4), vec(inner, 0..4))
+                .prop_map(|(c, t, f)| Stmt::If(c, t, f)),
+        ]
+    })
+}
+
+fn write_expr(out: &mut String, expr: &Expr) {
+    match expr {
+        Expr::Const(value) => write!(out, "(i32.const {})", value).unwrap(),
+        Expr::Get(local) => write!(out, "(local.get {})", local).unwrap(),
+        Expr::Tee(local, value) => {
+            write!(out, "(local.tee {} ", local).unwrap();
+            write_expr(out, value);
+            out.push(')');
+        }
+        Expr::Eqz(value) => {
+            out.push_str("(i32.eqz ");
+            write_expr(out, value);
+            out.push(')');
+        }
+        Expr::Add(lhs, rhs) => {
+            out.push_str("(i32.add ");
+            write_expr(out, lhs);
+            write_expr(out, rhs);
+            out.push(')');
+        }
+        Expr::Select(condition, on_true, on_false) => {
+            out.push_str("(select ");
+            write_expr(out, on_true);
+            write_expr(out, on_false);
+            write_expr(out, condition);
+            out.push(')');
+        }
+    }
+}
+
+// `frames` is the number of enclosing frames, including the function.
+fn write_stmts(out: &mut String, stmts: &[Stmt], frames: u32) {
+    for stmt in stmts {
+        write_stmt(out, stmt, frames);
+        out.push('\n');
+    }
+}
+
+fn write_stmt(out: &mut String, stmt: &Stmt, frames: u32) {
+    match stmt {
+        Stmt::Set(local, value) => {
+            write!(out, "(local.set {} ", local).unwrap();
+            write_expr(out, value);
+            out.push(')');
+        }
+        Stmt::Drop(value) => {
+            out.push_str("(drop ");
+            write_expr(out, value);
+            out.push(')');
+        }
+        Stmt::Br(depth) => write!(out, "(br {})", depth % frames).unwrap(),
+        Stmt::BrIf(depth, condition) => {
+            write!(out, "(br_if {} ", depth % frames).unwrap();
+            write_expr(out, condition);
+            out.push(')');
+        }
+        Stmt::BrTable(depths, default, index) => {
+            out.push_str("(br_table");
+            for depth in depths.iter().chain(Some(default)) {
+                write!(out, " {}", depth % frames).unwrap();
+            }
+            out.push(' ');
+            write_expr(out, index);
+            out.push(')');
+        }
+        Stmt::Return => out.push_str("(return)"),
+        Stmt::Unreachable => out.push_str("(unreachable)"),
+        Stmt::Block(body) => {
+            out.push_str("(block\n");
+            write_stmts(out, body, frames + 1);
+            out.push(')');
+        }
+        Stmt::Loop(body) => {
+            out.push_str("(loop\n");
+            write_stmts(out, body, frames + 1);
+            out.push(')');
+        }
+        Stmt::If(condition, true_body, false_body) => {
+            out.push_str("(if ");
+            write_expr(out, condition);
+            out.push_str("\n(then\n");
+            write_stmts(out, true_body, frames + 1);
+            out.push_str(")\n(else\n");
+            write_stmts(out, false_body, frames + 1);
+            out.push_str("))");
+        }
+    }
+}
+
+fn module_text(body: &[Stmt]) -> String {
+    let mut out = String::new();
+    out.push_str("(module (func (param i32 i32) (local i32 i32)\n");
+    write_stmts(&mut out, body, 1);
+    out.push_str("))\n");
+    out
+}
+
+fn decompile_module(text: &str) -> wasm_decompile::Module {
+    let binary = wat::parse_str(text).unwrap();
+    let module = wasm_decompile::Module::from_buffer(&binary).unwrap();
+    let errors: Vec<_> = module.errors().collect();
+    assert!(errors.is_empty(), "{:?}", errors);
+    module
+}
+
+fn decompile(text: &str) -> String {
+    decompile_module(text).render_to_string().unwrap()
+}
+
+proptest! {
+    // Every `@N` referenced by a branch is defined exactly once, and every
+    // name used is declared.
+    #[test]
+    fn output_is_well_formed(body in vec(stmt(), 0..6)) {
+        let input = module_text(&body);
+        let output = decompile(&input);
+        let module = wasm_decompile::text::parse(&output)
+            .map_err(|err| TestCaseError::fail(format!("{}\n{}", err, output)))?;
+        module
+            .check()
+            .map_err(|err| TestCaseError::fail(format!("{}\n{}", err, output)))?;
+    }
+}
+
+proptest! {
+    // Successors and predecessors agree, and after dead code elimination
+    // every block is reachable, so RPO visits all of them starting at the
+    // entry block.
+    #[test]
+    fn graph_is_consistent(body in vec(stmt(), 0..6)) {
+        let module = decompile_module(&module_text(&body));
+        let func = module.func(0.into()).unwrap();
+        let predecessors = func.predecessors();
+
+        let mut edges = 0;
+        for block_index in func.block_indices() {
+            let successors = func.block(block_index).unwrap().successors();
+            prop_assert_eq!(&successors, &func.successors(block_index));
+            for successor in successors {
+                prop_assert!(func.block(successor).is_some());
+                prop_assert!(predecessors[&successor].contains(&block_index));
+                edges += 1;
+            }
+        }
+        prop_assert_eq!(edges, predecessors.values().map(Vec::len).sum::<usize>());
+
+        let rpo = func.reverse_post_order();
+        prop_assert_eq!(rpo.first(), Some(&func.entry_block()));
+        let mut sorted_rpo = rpo.clone();
+        sorted_rpo.sort();
+        prop_assert_eq!(sorted_rpo, func.block_indices());
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+proptest! {
+    // The printers cope with any structurally valid IR, including shapes
+    // the decoder never produces.
+    #[test]
+    fn arbitrary_funcs_print(data in vec(any::<u8>(), 0..4096)) {
+        let mut u = arbitrary::Unstructured::new(&data);
+        let func: wasm_decompile::ArbitraryFunc = arbitrary::Arbitrary::arbitrary(&mut u)
+            .map_err(|err| TestCaseError::reject(err.to_string()))?;
+        let mut output = Vec::new();
+        func.write(&mut output).unwrap();
+        prop_assert!(String::from_utf8(output).unwrap().starts_with("func"));
+        let mut output = Vec::new();
+        func.write_graphviz(&mut output).unwrap();
+        prop_assert!(String::from_utf8(output).unwrap().starts_with("digraph"));
+    }
+}
@@ -1,9 +1,19 @@
 use std::ffi::OsStr;
+use std::io::Write;
+
+// Compares `actual` with the snapshot at `path`, or overwrites the snapshot
+// with it when `UPDATE_SNAPSHOTS` is set.
+fn assert_snapshot(path: &str, actual: &str) {
+    if std::env::var("UPDATE_SNAPSHOTS").is_ok() {
+        std::fs::write(path, actual).unwrap();
+    } else {
+        let expected = std::fs::read_to_string(path).unwrap();
+        assert_eq!(actual, expected, "{}", path);
+    }
+}
 
 #[test]
 fn test_snapshot() {
-    let update_snapshots = std::env::var("UPDATE_SNAPSHOTS").is_ok();
-
     let test_files = std::fs::read_dir("tests/snapshots").unwrap();
     for file in test_files {
         let file = file.unwrap();
@@ -20,12 +30,1843 @@ fn test_snapshot() {
             let output_string = String::from_utf8(output).unwrap();
 
             let expected_path = test_path.with_extension("snapshot");
-            if update_snapshots {
-                std::fs::write(expected_path, output_string).unwrap();
-            } else {
-                let expected = std::fs::read(&expected_path).unwrap();
-                assert_eq!(output_string, String::from_utf8(expected).unwrap());
+            assert_snapshot(expected_path.to_str().unwrap(), &output_string);
+        }
+    }
+}
+
+#[test]
+fn test_snapshot_reparse() {
+    let test_files = std::fs::read_dir("tests/snapshots").unwrap();
+    for file in test_files {
+        let test_path = file.unwrap().path();
+        if test_path.extension() != Some(OsStr::new("snapshot")) {
+            continue;
+        }
+
+        let text = std::fs::read_to_string(&test_path).unwrap();
+        let result = wasm_decompile::text::parse(&text).and_then(|module| module.check());
+        if let Err(err) = result {
+            panic!("{}: {}", test_path.display(), err);
+        }
+    }
+}
+
+fn strip_ansi(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| *c == 'm');
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn strip_html(text: &str) -> String {
+    let body = text.split_once("<pre class=\"wasm-decompile\">").unwrap().1;
+    let body = body.strip_suffix("\n</pre>\n").unwrap();
+    let mut result = String::new();
+    let mut in_tag = false;
+    for c in body.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => result.push(c),
+            _ => {}
+        }
+    }
+    result
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        + "\n"
+}
+
+// Highlighting only adds markup around the plain text output.
+#[test]
+fn test_snapshot_highlighted() {
+    let test_files = std::fs::read_dir("tests/snapshots").unwrap();
+    for file in test_files {
+        let test_path = file.unwrap().path();
+        if test_path.extension() != Some(OsStr::new("wat")) {
+            continue;
+        }
+
+        let input_binary = wat::parse_file(&test_path).unwrap();
+        let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+        let expected = std::fs::read_to_string(test_path.with_extension("snapshot")).unwrap();
+
+        for (format, strip) in [
+            (
+                wasm_decompile::OutputFormat::Ansi,
+                strip_ansi as fn(&str) -> String,
+            ),
+            (wasm_decompile::OutputFormat::Html, strip_html),
+        ] {
+            for theme in wasm_decompile::Theme::BUILTINS {
+                let options = wasm_decompile::RenderOptions {
+                    format,
+                    theme: wasm_decompile::Theme::builtin(theme).unwrap(),
+                    ..Default::default()
+                };
+                let mut output = Vec::new();
+                module.write_with_options(&mut output, &options).unwrap();
+                let output = String::from_utf8(output).unwrap();
+                assert_eq!(strip(&output), expected, "{}", test_path.display());
             }
         }
     }
 }
+
+// Collects the text a `DecompilePrint` is given, checking that each token
+// is ended before the next starts.
+#[derive(Default)]
+struct TokenPrinter {
+    text: String,
+    in_token: bool,
+    tokens: usize,
+}
+
+impl TokenPrinter {
+    fn start(&mut self) -> std::io::Result<()> {
+        assert!(!self.in_token, "nested token");
+        self.in_token = true;
+        self.tokens += 1;
+        Ok(())
+    }
+}
+
+impl wasm_decompile::DecompilePrint for TokenPrinter {
+    fn write_str(&mut self, s: &str) -> std::io::Result<()> {
+        self.text.push_str(s);
+        Ok(())
+    }
+
+    fn start_keyword(&mut self) -> std::io::Result<()> {
+        self.start()
+    }
+
+    fn start_name(&mut self) -> std::io::Result<()> {
+        self.start()
+    }
+
+    fn start_literal(&mut self) -> std::io::Result<()> {
+        self.start()
+    }
+
+    fn start_type(&mut self) -> std::io::Result<()> {
+        self.start()
+    }
+
+    fn start_comment(&mut self) -> std::io::Result<()> {
+        self.start()
+    }
+
+    fn reset_color(&mut self) -> std::io::Result<()> {
+        assert!(self.in_token, "reset without a token");
+        self.in_token = false;
+        Ok(())
+    }
+}
+
+// A `DecompilePrint` is given the plain text output, split into tokens.
+#[test]
+fn test_snapshot_print_tokens() {
+    let test_files = std::fs::read_dir("tests/snapshots").unwrap();
+    for file in test_files {
+        let test_path = file.unwrap().path();
+        if test_path.extension() != Some(OsStr::new("wat")) {
+            continue;
+        }
+
+        let input_binary = wat::parse_file(&test_path).unwrap();
+        let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+        let expected = std::fs::read_to_string(test_path.with_extension("snapshot")).unwrap();
+
+        let mut printer = TokenPrinter::default();
+        module.write_with(&mut printer).unwrap();
+        assert!(!printer.in_token, "{}", test_path.display());
+        assert!(printer.tokens > 0, "{}", test_path.display());
+        assert_eq!(printer.text, expected, "{}", test_path.display());
+    }
+}
+
+// Each line is a JSON object holding the same text as `write_func`.
+#[test]
+fn test_snapshot_jsonl() {
+    let test_files = std::fs::read_dir("tests/snapshots").unwrap();
+    for file in test_files {
+        let test_path = file.unwrap().path();
+        if test_path.extension() != Some(OsStr::new("wat")) {
+            continue;
+        }
+
+        let input_binary = wat::parse_file(&test_path).unwrap();
+        let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+        let mut output = Vec::new();
+        module.write_jsonl(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        for line in output.lines() {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            let index = value["index"].as_u64().unwrap() as u32;
+            // Unnamed functions are `func 3(...)` and referred to as `func3`.
+            let name = value["name"].as_str().unwrap();
+            let header = match name.strip_prefix("func") {
+                Some(number) if number == index.to_string() => format!("func {}(", index),
+                _ => format!("func {}(", name),
+            };
+            assert!(value["text"].as_str().unwrap().contains(&header));
+
+            let mut expected = Vec::new();
+            module.write_func(index.into(), &mut expected).unwrap();
+            assert_eq!(
+                value["text"].as_str().unwrap(),
+                String::from_utf8(expected).unwrap(),
+                "{}",
+                test_path.display()
+            );
+            assert!(value["metrics"]["blocks"].is_u64());
+        }
+    }
+}
+
+// Quick mode prints the blocks as decoded, which is still valid output and
+// highlights the same way as the full output.
+#[test]
+fn test_snapshot_quick() {
+    let test_files = std::fs::read_dir("tests/snapshots").unwrap();
+    for file in test_files {
+        let test_path = file.unwrap().path();
+        if test_path.extension() != Some(OsStr::new("wat")) {
+            continue;
+        }
+
+        let input_binary = wat::parse_file(&test_path).unwrap();
+        let module = wasm_decompile::Module::from_buffer_with_options(
+            &input_binary,
+            &wasm_decompile::DecodeOptions {
+                quick: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        module.write(&mut output).unwrap();
+        let text = String::from_utf8(output).unwrap();
+
+        let result = wasm_decompile::text::parse(&text).and_then(|module| module.check());
+        if let Err(err) = result {
+            panic!("{}: {}", test_path.display(), err);
+        }
+
+        let options = wasm_decompile::RenderOptions {
+            format: wasm_decompile::OutputFormat::Html,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        module.write_with_options(&mut output, &options).unwrap();
+        let html = String::from_utf8(output).unwrap();
+        assert_eq!(strip_html(&html), text, "{}", test_path.display());
+    }
+}
+
+#[test]
+fn test_snapshot_source_map() {
+    let input_binary = wat::parse_file("tests/snapshots/source-map.wat").unwrap();
+    let source_map = std::fs::read_to_string("tests/snapshots/source-map.wasm.map").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            source_map: Some(wasm_decompile::SourceMap::parse(&source_map).unwrap()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(module.source_mapping_url(), Some("source-map.wasm.map"));
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/source-map.mapped.snapshot", &output_string);
+}
+
+// The map the module's `sourceMappingURL` names is found next to it.
+#[test]
+fn test_source_mapping_url() {
+    let input_binary = wat::parse_file("tests/snapshots/source-map.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            module_dir: Some("tests/snapshots".into()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let expected = std::fs::read_to_string("tests/snapshots/source-map.mapped.snapshot").unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+    // A truncated URL is ignored, since custom sections aren't validated.
+    let input_binary =
+        wat::parse_str(r#"(module (@custom "sourceMappingURL" "\05ab") (func))"#).unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+    let expected =
+        wasm_decompile::Module::from_buffer(&wat::parse_str("(module (func))").unwrap()).unwrap();
+    assert_eq!(
+        module.render_to_string().unwrap(),
+        expected.render_to_string().unwrap()
+    );
+}
+
+#[test]
+fn test_snapshot_dedup() {
+    let input_binary = wat::parse_file("tests/snapshots/duplicates.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            dedup: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(module.duplicate_of(2.into()), Some(0.into()));
+    assert_eq!(module.duplicate_of(3.into()), None);
+    assert_eq!(module.duplicate_of(4.into()), Some(0.into()));
+    assert_eq!(module.duplicate_of(5.into()), None);
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/duplicates.dedup.snapshot", &output_string);
+}
+
+// Windows of the output hold the same lines as the whole output.
+#[test]
+fn test_snapshot_lines() {
+    let test_files = std::fs::read_dir("tests/snapshots").unwrap();
+    for file in test_files {
+        let test_path = file.unwrap().path();
+        if test_path.extension() != Some(OsStr::new("wat")) {
+            continue;
+        }
+
+        let input_binary = wat::parse_file(&test_path).unwrap();
+        let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+        let expected = std::fs::read_to_string(test_path.with_extension("snapshot")).unwrap();
+        let expected_lines: Vec<&str> = expected.split_inclusive('\n').collect();
+
+        let index = module.line_index().unwrap();
+        assert_eq!(index.line_count(), expected_lines.len());
+
+        for start in (0..expected_lines.len()).step_by(5) {
+            let lines = start..(start + 7).min(expected_lines.len());
+            let mut output = Vec::new();
+            module
+                .write_lines(&index, lines.clone(), &mut output, &Default::default())
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                expected_lines[lines.clone()].concat(),
+                "{}: lines {:?}",
+                test_path.display(),
+                lines
+            );
+        }
+
+        let mut all_funcs = Vec::new();
+        module
+            .write_funcs(
+                0.into()..u32::MAX.into(),
+                &mut all_funcs,
+                &Default::default(),
+            )
+            .unwrap();
+        let mut funcs_text = String::new();
+        for line in 0..index.line_count() {
+            let Some(func_index) = index.func_at_line(line) else {
+                continue;
+            };
+            let func_lines = index.func_lines(func_index).unwrap();
+            if line == func_lines.start {
+                if !funcs_text.is_empty() {
+                    funcs_text.push('\n');
+                }
+                funcs_text.push_str(&expected_lines[func_lines].concat());
+            }
+        }
+        assert_eq!(String::from_utf8(all_funcs).unwrap(), funcs_text);
+    }
+}
+
+#[test]
+fn test_snapshot_entry_only() {
+    let input_binary = wat::parse_file("tests/snapshots/entry-points.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            entry_first: true,
+            entry_only: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let entry_points: Vec<u32> = module
+        .entry_points()
+        .iter()
+        .map(|entry_point| entry_point.func_index.as_u32())
+        .collect();
+    assert_eq!(entry_points, [5, 4, 3, 2]);
+    // func0 isn't called and isn't in the table.
+    let mut reachable: Vec<u32> = module
+        .reachable_from_entry_points()
+        .iter()
+        .map(|func_index| func_index.as_u32())
+        .collect();
+    reachable.sort();
+    assert_eq!(reachable, [1, 2, 3, 4, 5]);
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+    wasm_decompile::text::parse(&output_string).unwrap();
+
+    assert_snapshot(
+        "tests/snapshots/entry-points.entry-only.snapshot",
+        &output_string,
+    );
+}
+
+#[test]
+fn test_snapshot_reachable_from() {
+    let input_binary = wat::parse_file("tests/snapshots/entry-points.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            reachable_from: vec!["_start".to_string()],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    // `_start` calls `__wasm_call_ctors` and then `main`, which calls
+    // `double` through the table.
+    let order: Vec<u32> = module
+        .call_graph()
+        .topological_order([module.exported_func("_start").unwrap()])
+        .iter()
+        .map(|func_index| func_index.as_u32())
+        .collect();
+    assert_eq!(order, [3, 4, 2, 1]);
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot(
+        "tests/snapshots/entry-points.reachable.snapshot",
+        &output_string,
+    );
+
+    let missing = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            reachable_from: vec!["nope".to_string()],
+            ..Default::default()
+        },
+    );
+    assert!(missing.is_err());
+}
+
+#[test]
+fn test_snapshot_offsets() {
+    let input_binary = wat::parse_file("tests/snapshots/temps.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            offsets: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/temps.offsets.snapshot", &output_string);
+}
+
+#[test]
+fn test_snapshot_interleave() {
+    let input_binary = wat::parse_file("tests/snapshots/temps.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            interleave: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/temps.interleave.snapshot", &output_string);
+}
+
+#[test]
+fn test_snapshot_width_and_indent() {
+    let input_binary = wat::parse_file("tests/snapshots/structure.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+
+    let options = wasm_decompile::RenderOptions {
+        width: 40,
+        indent: 4,
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    module.write_with_options(&mut output, &options).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/structure.narrow.snapshot", &output_string);
+}
+
+#[test]
+fn test_snapshot_skip_passes() {
+    for (name, skip_passes, suffix) in [
+        (
+            "structure",
+            wasm_decompile::Pass::ALL.to_vec(),
+            "unoptimized",
+        ),
+        (
+            "structure",
+            vec![wasm_decompile::Pass::Structuring],
+            "unstructured",
+        ),
+        (
+            "stack-frame",
+            wasm_decompile::Pass::ALL.to_vec(),
+            "unoptimized",
+        ),
+    ] {
+        let input_binary = wat::parse_file(format!("tests/snapshots/{}.wat", name)).unwrap();
+        let module = wasm_decompile::Module::from_buffer_with_options(
+            &input_binary,
+            &wasm_decompile::DecodeOptions {
+                skip_passes,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let mut output = Vec::new();
+        module.write(&mut output).unwrap();
+        let output_string = String::from_utf8(output).unwrap();
+
+        assert_snapshot(
+            &format!("tests/snapshots/{}.{}.snapshot", name, suffix),
+            &output_string,
+        );
+    }
+}
+
+#[test]
+fn test_snapshot_list() {
+    let input_binary = wat::parse_file("tests/snapshots/abi.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            only: Some(wasm_decompile::FuncPicks::default()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    module.write_list(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/abi.list", &output_string);
+}
+
+#[test]
+fn test_functions() {
+    let input_binary = wat::parse_file("tests/snapshots/abi.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+
+    let functions: Vec<_> = module.functions().collect();
+    let indices: Vec<u32> = functions.iter().map(|info| info.index().as_u32()).collect();
+    assert_eq!(indices, [0, 1, 2, 3, 4, 5]);
+
+    let log = &functions[0];
+    assert!(log.is_import());
+    assert_eq!(log.name(), "env.log");
+    assert_eq!(log.size(), None);
+    assert!(log.func().is_none());
+    assert_eq!(log.exports().collect::<Vec<_>>(), ["log"]);
+
+    let sum = &functions[2];
+    assert!(!sum.is_import());
+    assert_eq!(sum.name(), "sum");
+    assert_eq!(sum.ty().params().len(), 2);
+    assert_eq!(sum.size(), Some(43));
+    assert_eq!(sum.exports().collect::<Vec<_>>(), ["sum"]);
+    let func = sum.func().unwrap();
+    assert_eq!(func.index(), sum.index());
+    assert!(func.error().is_none());
+}
+
+#[test]
+fn test_snapshot_top() {
+    let input_binary = wat::parse_file("tests/snapshots/structure.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+
+    let mut output = Vec::new();
+    module
+        .write_top(4, wasm_decompile::Ranking::Lines, &mut output)
+        .unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/structure.top", &output_string);
+}
+
+#[test]
+fn test_snapshot_flatten_selects() {
+    let input_binary = wat::parse_file("tests/snapshots/selects.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            flatten_selects: Some(2),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/selects.flat.snapshot", &output_string);
+}
+
+#[test]
+fn test_snapshot_indirect_candidates() {
+    let input_binary = wat::parse_file("tests/snapshots/indirect-calls.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            indirect_candidates: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot(
+        "tests/snapshots/indirect-calls.candidates.snapshot",
+        &output_string,
+    );
+}
+
+#[test]
+fn test_snapshot_max_expression_depth() {
+    let input_binary = wat::parse_file("tests/snapshots/deep-expressions.wat").unwrap();
+    for (max_expression_depth, max_expression_width, suffix) in
+        [(Some(3), None, "shallow"), (None, Some(1), "thin")]
+    {
+        let module = wasm_decompile::Module::from_buffer_with_options(
+            &input_binary,
+            &wasm_decompile::DecodeOptions {
+                max_expression_depth,
+                max_expression_width,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let mut output = Vec::new();
+        module.write(&mut output).unwrap();
+        let output_string = String::from_utf8(output).unwrap();
+
+        let expected_path = format!("tests/snapshots/deep-expressions.{}.snapshot", suffix);
+        assert_snapshot(&expected_path, &output_string);
+    }
+}
+
+#[test]
+fn test_snapshot_tidy_locals() {
+    let input_binary = wat::parse_file("tests/snapshots/locals.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            tidy_locals: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+    wasm_decompile::text::parse(&output_string)
+        .and_then(|module| module.check())
+        .unwrap();
+
+    assert_snapshot("tests/snapshots/locals.tidy.snapshot", &output_string);
+}
+
+#[test]
+fn test_snapshot_split_locals() {
+    let input_binary = wat::parse_file("tests/snapshots/split-locals.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            split_locals: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+    wasm_decompile::text::parse(&output_string)
+        .and_then(|module| module.check())
+        .unwrap();
+
+    assert_snapshot(
+        "tests/snapshots/split-locals.split.snapshot",
+        &output_string,
+    );
+}
+
+#[test]
+fn test_snapshot_demangle() {
+    let input_binary = wat::parse_file("tests/snapshots/demangle.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            demangle: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+    wasm_decompile::text::parse(&output_string)
+        .and_then(|module| module.check())
+        .unwrap();
+
+    assert_snapshot(
+        "tests/snapshots/demangle.demangled.snapshot",
+        &output_string,
+    );
+}
+
+#[test]
+fn test_snapshot_symbol_map() {
+    let input_binary = wat::parse_file("tests/snapshots/symbols.wat").unwrap();
+    let symbol_map = std::fs::read_to_string("tests/snapshots/symbols.symbols").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            symbol_map: Some(wasm_decompile::SymbolMap::parse(&symbol_map).unwrap()),
+            demangle: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+    wasm_decompile::text::parse(&output_string)
+        .and_then(|module| module.check())
+        .unwrap();
+
+    assert_snapshot("tests/snapshots/symbols.named.snapshot", &output_string);
+}
+
+#[test]
+fn test_snapshot_freeze() {
+    let input_binary = wat::parse_file("tests/snapshots/freeze.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            freeze: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let frozen: Vec<String> = module.frozen().iter().map(ToString::to_string).collect();
+    assert_eq!(
+        frozen,
+        [
+            "globals[0] = 1 (never written)",
+            "globals[1] = 3 (only written by unreachable func3)",
+            "table0[0] = func1 (never written)",
+            "table0[1] = func2 (never written)",
+        ]
+    );
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+    wasm_decompile::text::parse(&output_string).unwrap();
+
+    assert_snapshot("tests/snapshots/freeze.frozen.snapshot", &output_string);
+}
+
+#[test]
+fn test_snapshot_link() {
+    let input_binary = wat::parse_file("tests/snapshots/link-main.wat").unwrap();
+    let mut module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+    let math_binary = wat::parse_file("tests/snapshots/link-math.wat").unwrap();
+    let math = wasm_decompile::Module::from_buffer(&math_binary).unwrap();
+    assert_eq!(module.link("math", &math).unwrap(), 2);
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+    wasm_decompile::text::parse(&output_string).unwrap();
+
+    assert_snapshot("tests/snapshots/link-main.linked.snapshot", &output_string);
+
+    // The signatures of linked functions must match.
+    let other_binary = wat::parse_str(
+        r#"(module (func (export "add") (param i64 i64) (result i64) (local.get 0)))"#,
+    )
+    .unwrap();
+    let other = wasm_decompile::Module::from_buffer(&other_binary).unwrap();
+    assert!(module.link("math", &other).is_err());
+}
+
+// Not a `.snapshot`, since it isn't decompiled output.
+#[test]
+fn test_snapshot_abi() {
+    let input_binary = wat::parse_file("tests/snapshots/abi.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+
+    let mut output = Vec::new();
+    for format in [
+        wasm_decompile::AbiFormat::Text,
+        wasm_decompile::AbiFormat::Markdown,
+        wasm_decompile::AbiFormat::Json,
+    ] {
+        writeln!(output, "{:?}:", format).unwrap();
+        module.write_abi(&mut output, format).unwrap();
+        writeln!(output).unwrap();
+    }
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/abi.abi", &output_string);
+}
+
+#[test]
+fn test_snapshot_dominators() {
+    let input_binary = wat::parse_file("tests/snapshots/structure.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+
+    let mut output = Vec::new();
+    module.write_dominators(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/structure.dominators", &output_string);
+
+    // Each block's immediate dominator lists it as a child, and the entry
+    // block dominates them all.
+    for func_index in 0..5 {
+        let func = module.func(func_index.into()).unwrap();
+        let dominators = func.dominator_tree();
+        let root = dominators.root();
+        assert_eq!(dominators.immediate_dominator(root), None);
+        for block_index in func.block_indices() {
+            assert!(dominators.dominates(root, block_index));
+            if let Some(idom) = dominators.immediate_dominator(block_index) {
+                assert!(dominators.children(idom).contains(&block_index));
+                assert!(!dominators.dominates(block_index, idom));
+            }
+        }
+    }
+}
+
+// Blocks as decoded, so that the loops and branches are still there to
+// draw.
+#[test]
+fn test_snapshot_graphviz() {
+    let elided = wasm_decompile::GraphvizOptions {
+        max_statements: Some(3),
+        max_line_length: Some(30),
+    };
+    for (name, func_index, graphviz_options, snapshot) in [
+        ("structure", 4, Default::default(), "structure.cfg.dot"),
+        ("terminators", 3, Default::default(), "terminators.cfg.dot"),
+        ("text-syntax", 1, Default::default(), "text-syntax.cfg.dot"),
+        ("strings", 1, Default::default(), "strings.cfg.dot"),
+        ("strings", 1, elided, "strings.elided.cfg.dot"),
+    ] {
+        let input_binary = wat::parse_file(format!("tests/snapshots/{}.wat", name)).unwrap();
+        let options = wasm_decompile::DecodeOptions {
+            quick: true,
+            ..Default::default()
+        };
+        let module =
+            wasm_decompile::Module::from_buffer_with_options(&input_binary, &options).unwrap();
+
+        let mut output = Vec::new();
+        module
+            .write_func_graphviz_with_options(func_index.into(), &mut output, &graphviz_options)
+            .unwrap();
+        let output_string = String::from_utf8(output).unwrap();
+
+        assert_snapshot(&format!("tests/snapshots/{}", snapshot), &output_string);
+    }
+}
+
+#[test]
+fn test_snapshot_dominators_graphviz() {
+    let input_binary = wat::parse_file("tests/snapshots/structure.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+
+    let mut output = Vec::new();
+    module
+        .write_func_dominators_graphviz(1.into(), &mut output)
+        .unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/structure.domtree.dot", &output_string);
+}
+
+#[test]
+fn test_snapshot_name_map() {
+    let input_binary = wat::parse_file("tests/snapshots/symbols.wat").unwrap();
+    let symbol_map = std::fs::read_to_string("tests/snapshots/symbols.symbols").unwrap();
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            symbol_map: Some(wasm_decompile::SymbolMap::parse(&symbol_map).unwrap()),
+            demangle: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut output = Vec::new();
+    module.write_name_map(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+    for line in output_string.lines() {
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        if let Some(range) = value["range"].as_array() {
+            let start = range[0].as_u64().unwrap() as usize;
+            let end = range[1].as_u64().unwrap() as usize;
+            assert!(start < end && end <= input_binary.len());
+        }
+    }
+
+    assert_snapshot("tests/snapshots/symbols.names.jsonl", &output_string);
+}
+
+#[test]
+fn test_snapshot_diff() {
+    let old = wat::parse_file("tests/snapshots/diff-old.wat").unwrap();
+    let old = wasm_decompile::Module::from_buffer(&old).unwrap();
+    let new = wat::parse_file("tests/snapshots/diff-new.wat").unwrap();
+    let new = wasm_decompile::Module::from_buffer(&new).unwrap();
+
+    let mut output = Vec::new();
+    old.write_diff(&new, &mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    // A module is no different from itself.
+    let mut output = Vec::new();
+    old.write_diff(&old, &mut output).unwrap();
+    assert!(output.is_empty());
+
+    assert_snapshot("tests/snapshots/diff-old.diff", &output_string);
+}
+
+#[test]
+fn test_snapshot_line_map() {
+    let input_binary = wat::parse_file("tests/snapshots/temps.wat").unwrap();
+    let decode = |offsets| {
+        let module = wasm_decompile::Module::from_buffer_with_options(
+            &input_binary,
+            &wasm_decompile::DecodeOptions {
+                offsets,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut text = Vec::new();
+        module.write(&mut text).unwrap();
+        let mut line_map = Vec::new();
+        module.write_line_map(&mut line_map).unwrap();
+        (
+            String::from_utf8(text).unwrap(),
+            String::from_utf8(line_map).unwrap(),
+        )
+    };
+
+    // The line map doesn't need the offsets printed, and lines up with
+    // the output either way.
+    for offsets in [false, true] {
+        let (text, output_string) = decode(offsets);
+        let lines: Vec<&str> = text.lines().collect();
+        for line in output_string.lines() {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            let func_lines = value["lines"].as_array().unwrap();
+            let start = func_lines[0].as_u64().unwrap() as usize;
+            assert!(lines[start].starts_with("func "));
+            let range = value["range"].as_array().unwrap();
+            let range = range[0].as_u64().unwrap()..range[1].as_u64().unwrap();
+            for statement in value["statements"].as_array().unwrap() {
+                let line = statement["line"].as_u64().unwrap() as usize;
+                let column = statement["column"].as_u64().unwrap() as usize;
+                let offset = statement["offset"].as_u64().unwrap();
+                assert!(!lines[line][column..].starts_with(' '));
+                assert!(range.contains(&offset));
+                // A statement printed with its offset has the same one. A
+                // terminator has none printed, but can follow the offset
+                // of statements that were folded into it.
+                if let Some(comment) = lines[line - 1].trim().strip_prefix("// @0x") {
+                    assert!(offsets);
+                    assert!(u64::from_str_radix(comment, 16).unwrap() <= offset);
+                }
+            }
+        }
+    }
+    let (_, output_string) = decode(false);
+
+    assert_snapshot("tests/snapshots/temps.line-map.jsonl", &output_string);
+}
+
+#[test]
+fn test_component() {
+    let component = wat::parse_str(
+        r#"(component
+            (core module (func (export "one") (result i32) (i32.const 1)))
+            (component
+                (core module (func (export "id") (param i32) (result i32) (local.get 0)))))"#,
+    )
+    .unwrap();
+    assert!(wasm_decompile::is_component(&component));
+    assert_eq!(wasm_decompile::core_modules(&component).unwrap().len(), 2);
+    assert!(wasm_decompile::Module::from_buffer(&component).is_err());
+
+    let modules = wasm_decompile::Module::from_component(&component, &Default::default()).unwrap();
+    let outputs: Vec<String> = modules
+        .iter()
+        .map(|module| {
+            let mut output = Vec::new();
+            module.write(&mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        })
+        .collect();
+    assert_eq!(outputs.len(), 2);
+    assert!(outputs[0].contains("return 1"));
+    assert!(outputs[1].contains("return arg0"));
+}
+
+#[test]
+fn test_limits() {
+    let decode = |path, limits| {
+        let input_binary = wat::parse_file(path).unwrap();
+        let module = wasm_decompile::Module::from_buffer_with_options(
+            &input_binary,
+            &wasm_decompile::DecodeOptions {
+                limits,
+                ..Default::default()
+            },
+        )?;
+        let mut output = Vec::new();
+        module.write(&mut output).unwrap();
+        let output_string = String::from_utf8(output).unwrap();
+        wasm_decompile::text::parse(&output_string).unwrap();
+        anyhow::Ok(output_string)
+    };
+
+    assert!(decode(
+        "tests/snapshots/entry-points.wat",
+        wasm_decompile::Limits {
+            max_module_bytes: Some(16),
+            ..Default::default()
+        }
+    )
+    .is_err());
+
+    let output = decode(
+        "tests/snapshots/entry-points.wat",
+        wasm_decompile::Limits {
+            max_funcs: Some(2),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(output.contains("func double("));
+    assert!(!output.contains("func main("));
+    assert!(output.contains("// 4 more functions not decompiled, over the limit of 2"));
+
+    let output = decode(
+        "tests/snapshots/if-simple.wat",
+        wasm_decompile::Limits {
+            func_time_budget: Some(std::time::Duration::ZERO),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(output.contains("// ran out of its 0ms budget"));
+
+    // The passes after merging blocks stop at the deadline too, leaving
+    // the functions unstructured.
+    let output = decode(
+        "tests/snapshots/structure.wat",
+        wasm_decompile::Limits {
+            func_time_budget: Some(std::time::Duration::ZERO),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(output.contains("  br @1\n"));
+    assert!(!output.contains("loop {"));
+    assert_eq!(
+        output.matches("// ran out of its 0ms budget").count(),
+        output.matches("\nfunc ").count()
+    );
+}
+
+#[test]
+fn test_snapshot_call_graph_graphviz() {
+    let input_binary = wat::parse_file("tests/snapshots/call-graph.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+
+    let mut output = Vec::new();
+    module.write_call_graph_graphviz(&mut output).unwrap();
+    let output_string = String::from_utf8(output).unwrap();
+
+    assert_snapshot("tests/snapshots/call-graph.dot", &output_string);
+}
+
+#[test]
+fn test_write_func_by_name() {
+    let input_binary = wat::parse_str(
+        r#"(module
+            (func $helper (param i32) (result i32)
+                (i32.mul (local.get 0) (i32.const 3)))
+            (func (export "run") (export "alias") (param i32) (result i32)
+                (call $helper (local.get 0))))"#,
+    )
+    .unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+    let run = wasm_decompile::FuncIdx::from(1);
+    assert_eq!(module.exported_func("run"), Some(run));
+    assert_eq!(module.exported_func("alias"), Some(run));
+    assert_eq!(module.exported_func("helper"), None);
+
+    let mut expected = Vec::new();
+    module.write_func(run, &mut expected).unwrap();
+    let mut output = Vec::new();
+    module.write_func_by_name("alias", &mut output).unwrap();
+    assert_eq!(output, expected);
+
+    // Names from the name section are found too.
+    let helper = wasm_decompile::FuncIdx::from(0);
+    assert_eq!(module.func_index_of("run"), Some(run));
+    assert_eq!(module.func_index_of("helper"), Some(helper));
+    let mut expected = Vec::new();
+    module.write_func(helper, &mut expected).unwrap();
+    let mut output = Vec::new();
+    module.write_func_by_name("helper", &mut output).unwrap();
+    assert_eq!(output, expected);
+
+    assert_eq!(module.func_index_of("missing"), None);
+    let error = module
+        .write_func_by_name("missing", Vec::new())
+        .unwrap_err();
+    assert_eq!(error.to_string(), "no function is named `missing`");
+}
+
+#[test]
+fn test_write_funcs_at() {
+    let input_binary = wat::parse_str(
+        r#"(module
+            (import "env" "log" (func $log (param i32)))
+            (func $add_one (param i32) (result i32)
+                (i32.add (local.get 0) (i32.const 1)))
+            (func $add_two (export "plus2") (param i32) (result i32)
+                (i32.add (local.get 0) (i32.const 2)))
+            (func $log_it (param i32)
+                (call $log (local.get 0))))"#,
+    )
+    .unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+    let [log, add_one, add_two, log_it] = [0, 1, 2, 3].map(wasm_decompile::FuncIdx::from);
+
+    assert_eq!(module.funcs_in(log..log_it), [add_one, add_two]);
+    assert_eq!(
+        module.find_funcs(|name| name.starts_with("add")),
+        [add_one, add_two]
+    );
+    assert_eq!(module.find_funcs(|name| name == "plus2"), [add_two]);
+
+    let mut output = Vec::new();
+    module
+        .write_funcs_at(&[log_it, add_one], &mut output, &Default::default())
+        .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let headers: Vec<&str> = output
+        .lines()
+        .filter(|line| line.starts_with("func"))
+        .collect();
+    assert_eq!(
+        headers,
+        ["func log_it(arg0: i32) {", "func add_one(arg0: i32) {"]
+    );
+
+    assert!(module
+        .write_funcs_at(&[log], Vec::new(), &Default::default())
+        .is_err());
+}
+
+#[test]
+fn test_write_grep() {
+    let input_binary = wat::parse_str(
+        r#"(module
+            (import "wasi" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+            (global $count (mut i32) (i32.const 0))
+            (func $print (param i32 i32) (result i32)
+                (global.set $count (i32.add (global.get $count) (i32.const 1)))
+                (call $fd_write (i32.const 1) (local.get 0) (local.get 1) (i32.const 0)))
+            (func $count (result i32)
+                (global.get $count)))"#,
+    )
+    .unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+
+    let mut output = Vec::new();
+    module
+        .write_grep(|line| line.contains("fd_write"), &mut output)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "print:3:   return wasi.fd_write(1, arg0, arg1, 0)\n"
+    );
+
+    let mut output = Vec::new();
+    let count = wasm_decompile::FuncIdx::from(2);
+    module
+        .write_grep_in(&[count], |line| line.contains("$count"), &mut output)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "count:2:   return $count\n"
+    );
+}
+
+#[test]
+fn test_call_graph() {
+    let input_binary = wat::parse_str(
+        r#"(module
+            (import "env" "log" (func $log (param i32)))
+            (type $unary (func (param i32) (result i32)))
+            (table 2 funcref)
+            (elem (i32.const 0) $double $log)
+            (func $main (export "main") (param i32) (result i32)
+                (call $log (local.get 0))
+                (call_indirect (type $unary) (local.get 0) (i32.const 0)))
+            (func $double (param i32) (result i32)
+                (i32.add (local.get 0) (local.get 0)))
+            (func $even (param i32) (result i32)
+                (if (result i32) (i32.eqz (local.get 0))
+                    (then (i32.const 1))
+                    (else (call $odd (i32.sub (local.get 0) (i32.const 1))))))
+            (func $odd (param i32) (result i32)
+                (if (result i32) (i32.eqz (local.get 0))
+                    (then (i32.const 0))
+                    (else (call $even (i32.sub (local.get 0) (i32.const 1)))))))"#,
+    )
+    .unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+    let call_graph = module.call_graph();
+    let [log, main, double, even, odd] = [0, 1, 2, 3, 4].map(wasm_decompile::FuncIdx::from);
+
+    let call = |func_index, kind| wasm_decompile::Call { func_index, kind };
+    // `log` is in the table too, but has the wrong type to be called there.
+    assert_eq!(
+        call_graph.callees(main),
+        [
+            call(log, wasm_decompile::CallKind::Direct),
+            call(double, wasm_decompile::CallKind::Indirect)
+        ]
+    );
+    assert_eq!(
+        call_graph.callers(double),
+        [call(main, wasm_decompile::CallKind::Indirect)]
+    );
+    assert!(call_graph.callees(log).is_empty());
+
+    let reachable = call_graph.reachable_from([main]);
+    assert_eq!(reachable.len(), 3);
+    assert!(!reachable.contains(&even));
+    assert!(call_graph.reaches(even, odd));
+    assert!(call_graph.is_recursive(odd));
+    assert!(!call_graph.is_recursive(main));
+}
+
+#[test]
+fn test_pass_dumps() {
+    use wasm_decompile::Pass;
+
+    let input_binary = wat::parse_file("tests/snapshots/division.wat").unwrap();
+    let [first, second] = [0, 1].map(wasm_decompile::FuncIdx::from);
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            dump_after: vec![Pass::ForwardTemps, Pass::RecoverDivisions],
+            dump_funcs: vec![first..second],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // Temps are forwarded before and after blocks are merged.
+    let dumps = module.pass_dumps(first);
+    let passes: Vec<Pass> = dumps.iter().map(|(pass, _)| *pass).collect();
+    assert_eq!(
+        passes,
+        [
+            Pass::ForwardTemps,
+            Pass::ForwardTemps,
+            Pass::RecoverDivisions
+        ]
+    );
+    assert!(!dumps[1].1.contains("/_u 10"));
+    assert!(dumps[2].1.contains("/_u 10"));
+    assert!(module.pass_dumps(second).is_empty());
+}
+
+#[test]
+fn test_jobs_match_serial_output() {
+    let input_binary = wat::parse_file("tests/snapshots/ublock-publicsuffixlist.wat").unwrap();
+    let write = |jobs| {
+        let module = wasm_decompile::Module::from_buffer_with_options(
+            &input_binary,
+            &wasm_decompile::DecodeOptions {
+                jobs: Some(jobs),
+                // The passes after optimizing run in parallel too.
+                flatten_selects: Some(2),
+                max_expression_depth: Some(4),
+                tidy_locals: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        module.write(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+    assert_eq!(write(1), write(4));
+}
+
+#[test]
+fn test_only_funcs() {
+    let input_binary = wat::parse_str(
+        r#"(module
+            (func $add_one (param i32) (result i32)
+                (i32.add (local.get 0) (i32.const 1)))
+            (func $add_two (export "plus2") (param i32) (result i32)
+                (i32.add (local.get 0) (i32.const 2)))
+            (func $add_three (param i32) (result i32)
+                (i32.add (local.get 0) (i32.const 3))))"#,
+    )
+    .unwrap();
+    let [add_one, add_two, add_three] = [0, 1, 2].map(wasm_decompile::FuncIdx::from);
+    let module = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            only: Some(wasm_decompile::FuncPicks {
+                ranges: vec![add_one..add_two],
+                exports: vec!["plus2".to_string()],
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(module.errors().count(), 0);
+
+    let mut output = Vec::new();
+    module
+        .write_funcs_at(
+            &[add_one, add_two, add_three],
+            &mut output,
+            &Default::default(),
+        )
+        .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("arg0 + 1"));
+    assert!(output.contains("arg0 + 2"));
+    assert!(!output.contains("arg0 + 3"));
+    assert!(output.contains("// func 2 was not decompiled"));
+
+    // Invalid code is still found in functions that aren't decompiled.
+    let invalid = wat::parse_str(
+        r#"(module
+            (func $ok)
+            (func $bad (result i32) (i64.const 0)))"#,
+    )
+    .unwrap();
+    let decoded = wasm_decompile::Module::from_buffer_with_options(
+        &invalid,
+        &wasm_decompile::DecodeOptions {
+            only: Some(wasm_decompile::FuncPicks {
+                ranges: vec![add_one..add_two],
+                exports: vec![],
+            }),
+            ..Default::default()
+        },
+    );
+    assert!(decoded.is_err());
+}
+
+#[test]
+fn test_render_to_string() {
+    let input_binary = wat::parse_file("tests/snapshots/abi.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+
+    let mut output = Vec::new();
+    module.write(&mut output).unwrap();
+    assert_eq!(module.render_to_string().unwrap().as_bytes(), output);
+
+    let func_index = module.exported_func("sum").unwrap();
+    let mut output = Vec::new();
+    module.write_func(func_index, &mut output).unwrap();
+    let text = module.write_func_to_string(func_index).unwrap();
+    assert_eq!(text.as_bytes(), output);
+    assert_eq!(module.func(func_index).unwrap().to_string(), text);
+    assert!(module
+        .write_func_to_string(wasm_decompile::FuncIdx::from(1000))
+        .is_err());
+
+    // The string variants take the same options as the writers.
+    let options = wasm_decompile::RenderOptions {
+        format: wasm_decompile::OutputFormat::Html,
+        width: 40,
+        indent: 4,
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    module.write_with_options(&mut output, &options).unwrap();
+    let text = module.render_to_string_with_options(&options).unwrap();
+    assert_eq!(text.as_bytes(), output);
+    assert!(text.contains("<pre class=\"wasm-decompile\">"));
+    let mut output = Vec::new();
+    module
+        .write_func_with_options(func_index, &mut output, &options)
+        .unwrap();
+    let text = module
+        .write_func_to_string_with_options(func_index, &options)
+        .unwrap();
+    assert_eq!(text.as_bytes(), output);
+}
+
+#[test]
+fn test_lazy_module() {
+    let input_binary = wat::parse_file("tests/snapshots/abi.wat").unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+    let lazy = wasm_decompile::LazyModule::from_buffer(input_binary.clone()).unwrap();
+
+    let func_index = lazy.module().exported_func("sum").unwrap();
+    assert!(!lazy.is_decompiled(func_index));
+    let mut expected = Vec::new();
+    module.write_func(func_index, &mut expected).unwrap();
+    let mut output = Vec::new();
+    lazy.write_func(func_index, &mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        String::from_utf8(expected).unwrap()
+    );
+
+    // Each function is only decompiled once.
+    assert!(lazy.is_decompiled(func_index));
+    let func = lazy.func(func_index).unwrap();
+    assert!(std::ptr::eq(func, lazy.func(func_index).unwrap()));
+    assert!(func.error().is_none());
+    assert!(lazy.func(wasm_decompile::FuncIdx::from(1000)).is_err());
+
+    // Invalid code is found up front, not when its function is asked for.
+    let invalid = wat::parse_str(
+        r#"(module
+            (func $ok)
+            (func $bad (result i32) (i64.const 0)))"#,
+    )
+    .unwrap();
+    assert!(wasm_decompile::LazyModule::from_buffer(invalid).is_err());
+}
+
+#[test]
+fn test_disabled_features() {
+    let input_binary = wat::parse_file("tests/snapshots/simd.wat").unwrap();
+    assert!(wasm_decompile::Module::from_buffer(&input_binary).is_ok());
+
+    let mut features = wasm_decompile::WasmFeatures::default();
+    features.remove(wasm_decompile::WasmFeatures::SIMD);
+    let error = wasm_decompile::Module::from_buffer_with_options(
+        &input_binary,
+        &wasm_decompile::DecodeOptions {
+            features,
+            ..Default::default()
+        },
+    )
+    .err()
+    .unwrap();
+    assert!(error.to_string().contains("SIMD support is not enabled"));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_config_file() {
+    let run = |args: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_wasm-decompile"))
+            .current_dir("tests/snapshots/config")
+            .args(args)
+            .output()
+            .unwrap();
+        (output.status.success(), output.stdout, output.stderr)
+    };
+    let input_binary = wat::parse_file("tests/snapshots/temps.wat").unwrap();
+    let library = |offsets: bool| {
+        let module = wasm_decompile::Module::from_buffer_with_options(
+            &input_binary,
+            &wasm_decompile::DecodeOptions {
+                offsets,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        module.write(&mut output).unwrap();
+        output
+    };
+
+    // The config file in the current directory is read by default.
+    let (success, output, _) = run(&["../temps.wat"]);
+    assert!(success);
+    let output_string = String::from_utf8(output).unwrap();
+    assert_snapshot("tests/snapshots/temps.config.snapshot", &output_string);
+
+    // Options on the command line override the config file's.
+    let (success, output, _) = run(&["../temps.wat", "--indent=2"]);
+    assert!(success);
+    assert_eq!(output, library(true));
+
+    let (success, output, _) = run(&["../temps.wat", "--no-config"]);
+    assert!(success);
+    assert_eq!(output, library(false));
+
+    let (success, _, error) = run(&["../temps.wat", "--config=unknown-key.toml"]);
+    assert!(!success);
+    let error = String::from_utf8(error).unwrap();
+    assert!(error.contains("`colour` in `unknown-key.toml` is not an option"));
+}
+
+#[test]
+fn test_ir_api() {
+    use wasm_decompile::{Expression, FuncIdx, GlobalIdx, LocalIdx, Statement};
+
+    let input_binary = wat::parse_str(
+        r#"(module
+            (global $total (mut i32) (i32.const 0))
+            (func $square (param i32) (result i32)
+                (i32.mul (local.get 0) (local.get 0)))
+            (func (export "run") (param i32)
+                (global.set $total (call $square (local.get 0)))))"#,
+    )
+    .unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+    let run = module.func(module.exported_func("run").unwrap()).unwrap();
+    assert!(run.error().is_none());
+    assert_eq!(
+        (run.ty().params().iter())
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        ["i32"]
+    );
+    assert_eq!(run.num_locals(), 1);
+    assert_eq!(run.local_name(LocalIdx::from(0)), Some("arg0"));
+
+    fn calls(expr: &Expression, found: &mut Vec<FuncIdx>) {
+        if let Expression::Call(call) = expr {
+            found.push(call.func_index());
+        }
+        for operand in expr.operands() {
+            calls(operand, found);
+        }
+    }
+    let mut found = vec![];
+    let mut globals_set = vec![];
+    for block_index in run.block_indices() {
+        let block = run.block(block_index).unwrap();
+        for stmt in block.statements() {
+            if let Statement::GlobalSet(set) = stmt {
+                globals_set.push(set.index());
+            }
+            for expr in stmt.expressions() {
+                calls(expr, &mut found);
+            }
+        }
+        for expr in block.terminator().expressions() {
+            calls(expr, &mut found);
+        }
+    }
+    assert_eq!(found, [FuncIdx::from(0)]);
+    assert_eq!(globals_set, [GlobalIdx::from(0)]);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_tracing() {
+    let run = |rust_log: Option<&str>| {
+        let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_wasm-decompile"));
+        command
+            .arg("tests/snapshots/temps.wat")
+            .env_remove("RUST_LOG");
+        if let Some(rust_log) = rust_log {
+            command.env("RUST_LOG", rust_log);
+        }
+        let output = command.output().unwrap();
+        assert!(output.status.success());
+        strip_ansi(&String::from_utf8(output.stderr).unwrap())
+    };
+
+    // Nothing is logged unless asked for.
+    assert_eq!(run(None), "");
+
+    // Each section, function and pass gets a span, opened and closed.
+    let log = run(Some("wasm_decompile=debug"));
+    for span in [
+        "module{bytes=168}",
+        "section{name=\"code\"}",
+        "func{index=3}",
+        "optimize{func=3}",
+        "pass{name=\"forward-temps\"}",
+    ] {
+        assert!(
+            log.contains(&format!("{}: wasm_decompile", span)),
+            "{}",
+            span
+        );
+    }
+    assert!(log.contains("close time.busy="));
+    assert!(!log.contains("validator_stack"));
+
+    // The decoder's state after each operator is only logged at trace level.
+    assert!(run(Some("wasm_decompile=trace")).contains("validator_stack"));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_watch() {
+    let dir = std::env::temp_dir().join(format!("wasm-decompile-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input_path = dir.join("input.wat");
+    let output_path = dir.join("output.dcmp");
+    std::fs::copy("tests/snapshots/temps.wat", &input_path).unwrap();
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_wasm-decompile"))
+        .args(["--watch", "--no-config"])
+        .arg(&input_path)
+        .arg(&output_path)
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let wait_for = |path: &str| {
+        let input_binary = wat::parse_file(path).unwrap();
+        let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+        let expected = module.render_to_string().unwrap();
+        let start = std::time::Instant::now();
+        while std::fs::read_to_string(&output_path).ok().as_ref() != Some(&expected) {
+            assert!(
+                start.elapsed().as_secs() < 10,
+                "{} was not decompiled",
+                path
+            );
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+    };
+
+    wait_for("tests/snapshots/temps.wat");
+    // Invalid input is reported without stopping, and the next change is
+    // picked up.
+    std::fs::write(&input_path, "(module (func (i32.add)))").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+    std::fs::copy("tests/snapshots/effects.wat", &input_path).unwrap();
+    wait_for("tests/snapshots/effects.wat");
+
+    child.kill().unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("error: "));
+}
+
+#[test]
+fn test_library_dependencies() {
+    // What only the binary needs stays behind the `cli` feature.
+    let output = std::process::Command::new(env!("CARGO"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["tree", "--package=wasm-decompile", "--no-default-features"])
+        .args(["--edges=normal", "--prefix=none", "--format={p}"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let tree = String::from_utf8(output.stdout).unwrap();
+    let packages: Vec<_> = tree
+        .lines()
+        .filter_map(|line| line.split(' ').next())
+        .collect();
+    assert!(packages.contains(&"wasmparser"));
+    for package in ["clap", "regex", "toml", "tracing-subscriber", "wat"] {
+        assert!(!packages.contains(&package), "{}", package);
+    }
+}
+
+#[test]
+fn test_func_indices() {
+    use wasm_decompile::{DefFuncIdx, FuncIdx};
+
+    assert_eq!(FuncIdx::from(1).to_defined(2), None);
+    assert_eq!(FuncIdx::from(2).to_defined(2), Some(DefFuncIdx::from(0)));
+    assert_eq!(DefFuncIdx::from(0).to_func(2), Some(FuncIdx::from(2)));
+    assert_eq!(DefFuncIdx::from(u32::MAX).to_func(1), None);
+    let func_index = FuncIdx::from(u32::MAX);
+    assert_eq!(
+        func_index.to_defined(0).unwrap().to_func(0),
+        Some(func_index)
+    );
+
+    // Defined functions are numbered after the imported ones everywhere.
+    let input_binary = wat::parse_str(
+        r#"(module
+            (import "env" "f" (func $f))
+            (import "env" "g" (func $g))
+            (func $h (export "h") call $g))"#,
+    )
+    .unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+    let func_index = module.exported_func("h").unwrap();
+    assert_eq!(func_index, FuncIdx::from(2));
+    assert_eq!(module.func(func_index).unwrap().index(), func_index);
+    assert!(module.func(FuncIdx::from(1)).is_none());
+    assert!(module.func(FuncIdx::from(3)).is_none());
+    let call_graph = module.call_graph();
+    let callees = call_graph.callees(func_index);
+    assert_eq!(callees.len(), 1);
+    assert_eq!(callees[0].func_index, FuncIdx::from(1));
+}
+
+#[test]
+fn test_ir_offsets() {
+    use wasm_decompile::{SourceLocation, Statement};
+
+    let input_binary = wat::parse_str(
+        r#"(module
+            (global $total (mut i32) (i32.const 0))
+            (func $square (param i32) (result i32)
+                (i32.mul (local.get 0) (local.get 0)))
+            (func (export "run") (param i32) (result i32)
+                (global.set $total (call $square (local.get 0)))
+                (drop (br_if 0 (i32.const 7) (local.get 0)))
+                (i32.const 1)))"#,
+    )
+    .unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+    let run = module.func(module.exported_func("run").unwrap()).unwrap();
+
+    // Each statement is preceded by the range of the instructions it came
+    // from, even without `offsets`, and each terminator has one unless it
+    // was made up.
+    let mut statements = vec![];
+    let mut terminators = vec![];
+    for block_index in run.block_indices() {
+        let block = run.block(block_index).unwrap();
+        let mut offsets = None;
+        for stmt in block.statements() {
+            match stmt {
+                Statement::Location(SourceLocation::Offset(range)) => offsets = Some(range),
+                Statement::Location(_) => {}
+                _ => statements.push(&input_binary[offsets.unwrap().clone()]),
+            }
+        }
+        if let Some(range) = block.terminator_offsets() {
+            assert!(!range.is_empty());
+            terminators.push(&input_binary[range]);
+        }
+    }
+    // From the first instruction of their operands to the last of their
+    // own: global.set and drop; br_if and the function's end.
+    let set_total: &[u8] = &[0x20, 0, 0x10, 0, 0x24, 0];
+    let br_if: &[u8] = &[0x41, 7, 0x20, 0, 0x0d, 0];
+    let drop: &[u8] = &[0x41, 7, 0x20, 0, 0x0d, 0, 0x1a];
+    assert_eq!(statements, [set_total, drop]);
+    terminators.sort();
+    assert_eq!(terminators, [&[0x41, 1, 0x0b], br_if]);
+}
+
+#[test]
+fn test_visitor() {
+    use wasm_decompile::{Block, BlockIndex, Expression, Visitor};
+
+    // How deep expressions nest, and which blocks were entered.
+    #[derive(Default)]
+    struct Depth {
+        depth: usize,
+        max_depth: usize,
+        blocks: Vec<BlockIndex>,
+    }
+    impl Visitor for Depth {
+        fn pre_block(&mut self, block_index: BlockIndex, _block: &Block) {
+            self.blocks.push(block_index);
+        }
+        fn pre_expression(&mut self, _expr: &Expression) {
+            self.depth += 1;
+            self.max_depth = self.max_depth.max(self.depth);
+        }
+        fn post_expression(&mut self, _expr: &Expression) {
+            self.depth -= 1;
+        }
+    }
+
+    let input_binary = wat::parse_str(
+        r#"(module
+            (func (export "f") (param i32) (result i32)
+                (i32.mul (i32.add (local.get 0) (i32.const 1)) (local.get 0))))"#,
+    )
+    .unwrap();
+    let module = wasm_decompile::Module::from_buffer(&input_binary).unwrap();
+    let func = module.func(module.exported_func("f").unwrap()).unwrap();
+
+    let mut depth = Depth::default();
+    func.walk(&mut depth);
+    assert_eq!(depth.depth, 0);
+    assert_eq!(depth.max_depth, 3);
+    assert_eq!(depth.blocks, func.block_indices());
+
+    let mut num_exprs = 0;
+    func.visit_expressions(|_| num_exprs += 1);
+    assert_eq!(num_exprs, 5);
+}
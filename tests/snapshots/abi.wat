@@ -0,0 +1,29 @@
+(module
+  (import "env" "log" (func $log (param i32 i32)))
+  (import "env" "abort" (func $abort))
+  (memory (export "memory") 1)
+  (global $sp (mut i32) (i32.const 65536))
+  (global $calls (mut i32) (i32.const 0))
+  ;; Sums `len` bytes starting at `ptr`.
+  (func $sum (export "sum") (param $ptr i32) (param $len i32) (result i32)
+    (local $total i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.eqz (local.get $len)))
+        (local.set $total
+          (i32.add (local.get $total) (i32.load8_u (local.get $ptr))))
+        (local.set $ptr (i32.add (local.get $ptr) (i32.const 1)))
+        (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+        (br $next)))
+    (local.get $total))
+  (func $report (param i32 i32)
+    (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+    (call $log (local.get 0) (local.get 1)))
+  (func (export "print") (param $msg i32) (param $len i32) (param $level i32)
+    (if (i32.gt_u (local.get $level) (i32.load offset=4 (local.get $msg)))
+      (then (call $abort)))
+    (call $report (local.get $msg) (local.get $len)))
+  (func (export "scale") (param f64 i64) (result f64)
+    (f64.mul (local.get 0) (f64.convert_i64_s (local.get 1))))
+  (export "log" (func $log))
+)
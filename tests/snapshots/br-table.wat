@@ -0,0 +1,16 @@
+(module
+  (import "env" "check" (func $check (result i32)))
+  ;; The `br_table` passes a value to whichever target it picks, and the
+  ;; index is computed after it.
+  (func (param i32) (result i32)
+    (block $outer (result i32)
+      (block $inner (result i32)
+        (br_table $inner $outer $outer (call $check) (local.get 0)))
+      (i32.add (i32.const 1))))
+  ;; The index has a side effect the value mustn't move past.
+  (func (result i32)
+    (block $outer (result i32)
+      (block $inner (result i32)
+        (br_table $inner $outer (i32.const 5) (call $check)))
+      (drop)
+      (i32.const 6))))
@@ -0,0 +1,15 @@
+(module
+  (import "env" "log" (func $log (param i32)))
+  (type $unop (func (param i32) (result i32)))
+  (table 3 funcref)
+  (elem (i32.const 0) $double $square $log)
+  (global $handler (mut funcref) (ref.null func))
+  (func $main (export "main") (param i32) (result i32)
+    (call $log (local.get 0))
+    (global.set $handler (ref.func $square))
+    (call_indirect (type $unop) (local.get 0) (local.get 0)))
+  (func $double (param i32) (result i32)
+    (i32.add (local.get 0) (local.get 0)))
+  (func $square (param i32) (result i32)
+    (call $log (local.get 0))
+    (i32.mul (local.get 0) (local.get 0))))
@@ -0,0 +1,21 @@
+(module
+  (memory 1)
+  ;; An inverted integer comparison is flipped, as a value too.
+  (func (param $a i32) (param $b i32) (result i32)
+    (i32.eqz (i32.lt_s (local.get $a) (local.get $b))))
+  ;; `eqz(eqz(x))` is `x` as a condition, but not as a value.
+  (func (param $x i32) (result i32)
+    (if (i32.eqz (i32.eqz (local.get $x)))
+      (then (i32.store (i32.const 0) (local.get $x))))
+    (i32.eqz (i32.eqz (local.get $x))))
+  ;; Both sides negated: `!(a || b)`.
+  (func (param $a i32) (param $b i32)
+    (block $skip
+      (br_if $skip (local.get $a))
+      (br_if $skip (local.get $b))
+      (i32.store (i32.const 0) (i32.const 1))))
+  ;; A select's condition is a condition.
+  (func (param $a i32) (param $b i32) (result i32)
+    (select (local.get $a) (local.get $b)
+      (i32.eqz (i32.ne (local.get $a) (i32.const 3)))))
+)
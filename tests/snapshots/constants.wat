@@ -0,0 +1,35 @@
+(module
+  (import "env" "f" (func $f (param i32)))
+  (memory 1)
+  ;; Arithmetic on constants, but not a division that would trap.
+  (func (param $x i32) (result i32)
+    (i32.store (i32.const 0) (i32.div_u (i32.const 1) (i32.const 0)))
+    (i32.mul (i32.add (i32.const 1) (i32.const 2)) (local.get $x)))
+  ;; A local that's only ever one constant, then folded into another.
+  (func (param $p i32)
+    (local $n i32) (local $m i32)
+    (local.set $n (i32.const 10))
+    (local.set $m (i32.shl (local.get $n) (i32.const 2)))
+    (loop $loop
+      (i32.store (local.get $p) (local.get $m))
+      (local.set $p (i32.add (local.get $p) (i32.const 4)))
+      (br_if $loop (i32.lt_u (local.get $p) (local.get $n)))))
+  ;; Read before it's assigned each time round, so it's still zero the
+  ;; first time.
+  (func
+    (local $n i32)
+    (loop $loop
+      (call $f (local.get $n))
+      (local.set $n (i32.const 5))
+      (br $loop)))
+  ;; Branches on constants, as unoptimized builds leave for `if (1)` and
+  ;; `while (0)`.
+  (func (param $x i32)
+    (block $skip
+      (br_if $skip (i32.eqz (i32.const 1)))
+      (call $f (local.get $x)))
+    (block $done
+      (loop $loop
+        (br_if $done (i32.const 1))
+        (call $f (i32.const 0))
+        (br $loop)))))
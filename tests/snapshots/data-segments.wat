@@ -0,0 +1,19 @@
+(module
+  (import "env" "__memory_base" (global $base i32))
+  (memory 1)
+  (data (i32.const 0) "\00\00\00\00")
+  (data (i32.const 1024) "hello\00")
+  (data (i32.const 0x1000) "\01\02\03\04\05\06\07\08")
+  ;; Placed relative to an imported base, so its address isn't known.
+  (data (global.get $base) "pic")
+  (data "passive")
+  ;; Named in the name section, as LLVM names its segments.
+  (data $.rodata (i32.const 0x2000) "\00\00\00\00\2a\00\00\00")
+  (func (param i32) (result i32)
+    (i32.store (i32.const 0x1004) (i32.load8_u (i32.const 1026)))
+    ;; One past the end of a segment isn't inside it.
+    (i32.store (i32.const 0x1008) (i32.const 1024))
+    ;; The offset is part of the address.
+    (i32.store offset=4 (i32.const 0x1000) (i32.load offset=4 (i32.const 0x2000)))
+    (i32.add (local.get 0) (i32.const 2)))
+)
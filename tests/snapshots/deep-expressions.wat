@@ -0,0 +1,41 @@
+(module
+  (import "env" "f" (func $f (param i32) (result i32)))
+  (memory 1)
+  ;; Loads inlined into address arithmetic.
+  (func (param i32 i32) (result i32)
+    (i32.load offset=4
+      (i32.add
+        (local.get 0)
+        (i32.shl
+          (i32.load offset=8
+            (i32.add
+              (local.get 1)
+              (i32.mul (i32.load (local.get 0)) (i32.const 12))))
+          (i32.const 2)))))
+  ;; A store whose address is evaluated before its value, which calls.
+  (func (param i32 i32)
+    (i32.store
+      (i32.add (local.get 0) (i32.mul (i32.add (local.get 1) (i32.const 1)) (i32.const 8)))
+      (call $f
+        (i32.add (local.get 0) (i32.mul (i32.sub (local.get 1) (i32.const 1)) (i32.const 8))))))
+  ;; A call comes first, so only what has no side effects and reads no
+  ;; globals can be hoisted before it.
+  (global $g (mut i32) (i32.const 0))
+  (func (param i32 i32) (result i32)
+    (i32.add
+      (call $f (local.get 0))
+      (i32.add
+        (i32.load (i32.load (i32.add (local.get 1) (i32.const 4))))
+        (i32.mul (i32.add (global.get $g) (i32.const 1)) (local.get 0)))))
+  ;; A deep condition.
+  (func (param i32 i32)
+    (if (i32.lt_u (i32.add (local.get 0) (i32.mul (i32.add (local.get 1) (i32.const 1)) (i32.const 4))) (i32.const 100))
+      (then (drop (call $f (local.get 0))))))
+  ;; A wide call, whose arguments are each computed.
+  (func (param i32 i32) (result i32)
+    (call $sum3
+      (i32.add (local.get 0) (i32.const 1))
+      (call $f (local.get 1))
+      (i32.load (local.get 0))))
+  (func $sum3 (param i32 i32 i32) (result i32)
+    (i32.add (i32.add (local.get 0) (local.get 1)) (local.get 2))))
@@ -0,0 +1,17 @@
+(module
+  ;; Rust, with the hash that's left out when demangled.
+  (func $_ZN4core3fmt5write17h0123456789abcdefE (param i32) (result i32)
+    (call $_Z3addii (local.get 0) (i32.const 1)))
+  ;; The same path with a different hash, so it's printed by index.
+  (func $_ZN4core3fmt5write17hfedcba9876543210E (param i32) (result i32)
+    (local.get 0))
+  ;; Rust's v0 mangling.
+  (func $_RNvCs15kBYyAo9fc_7mycrate7example
+    (drop (call $_ZN4core3fmt5write17h0123456789abcdefE (i32.const 2))))
+  ;; C++, named by its export.
+  (func $_Z3addii (export "_Z3addii") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1)))
+  (func $_ZNSt6vectorIiSaIiEE9push_backERKi (param i32 i32))
+  (func $helper
+    (call $_ZNSt6vectorIiSaIiEE9push_backERKi (i32.const 0) (i32.const 4)))
+)
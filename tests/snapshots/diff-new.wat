@@ -0,0 +1,9 @@
+(module
+  (func $added (param i32) (result i32)
+    (i32.sub (local.get 0) (i32.const 1)))
+  (func $helper (param i32) (result i32)
+    (i32.mul (local.get 0) (i32.const 5)))
+  (func (export "run") (param i32) (result i32)
+    (call $helper (local.get 0)))
+  (func (param i32) (result i32)
+    (i32.add (local.get 0) (i32.const 1))))
@@ -0,0 +1,10 @@
+(module
+  (func $helper (param i32) (result i32)
+    (i32.mul (local.get 0) (i32.const 3)))
+  (func (export "run") (param i32) (result i32)
+    (call $helper (local.get 0)))
+  (func $removed (result i32)
+    (i32.const 7))
+  ;; Unnamed, and at a different index in the new module.
+  (func (param i32) (result i32)
+    (i32.add (local.get 0) (i32.const 1))))
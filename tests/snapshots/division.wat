@@ -0,0 +1,67 @@
+(module
+  ;; x / 10
+  (func (param i32) (result i32)
+    (i32.wrap_i64
+      (i64.shr_u
+        (i64.mul (i64.extend_i32_u (local.get 0)) (i64.const 3435973837))
+        (i64.const 35))))
+  ;; x % 10
+  (func (param i32) (result i32)
+    (i32.sub
+      (local.get 0)
+      (i32.mul
+        (i32.wrap_i64
+          (i64.shr_u
+            (i64.mul (i64.extend_i32_u (local.get 0)) (i64.const 3435973837))
+            (i64.const 35)))
+        (i32.const 10))))
+  ;; x / 14, with the factor of two shifted out first.
+  (func (param i32) (result i32)
+    (i32.wrap_i64
+      (i64.shr_u
+        (i64.mul
+          (i64.extend_i32_u (i32.shr_u (local.get 0) (i32.const 1)))
+          (i64.const 2454267027))
+        (i64.const 34))))
+  ;; Signed x / 10, which reads the product twice.
+  (func (param i32) (result i32)
+    (local i64)
+    (i32.add
+      (i32.wrap_i64
+        (i64.shr_u
+          (local.tee 1 (i64.mul (i64.extend_i32_s (local.get 0)) (i64.const 1717986919)))
+          (i64.const 63)))
+      (i32.wrap_i64 (i64.shr_s (local.get 1) (i64.const 34)))))
+  ;; Signed x % 10, as x + (x / 10) * -10.
+  (func (param i32) (result i32)
+    (local i64)
+    (i32.add
+      (i32.mul
+        (i32.add
+          (i32.wrap_i64
+            (i64.shr_u
+              (local.tee 1 (i64.mul (i64.extend_i32_s (local.get 0)) (i64.const 1717986919)))
+              (i64.const 63)))
+          (i32.wrap_i64 (i64.shr_s (local.get 1) (i64.const 34))))
+        (i32.const -10))
+      (local.get 0)))
+;; Signed x % 10 with x read first, so that it's spilled to a temp when
+  ;; the product is assigned.
+  (func (param i32) (result i32)
+    (local i64)
+    (i32.sub
+      (local.get 0)
+      (i32.mul
+        (i32.add
+          (i32.wrap_i64
+            (i64.shr_u
+              (local.tee 1 (i64.mul (i64.extend_i32_s (local.get 0)) (i64.const 1717986919)))
+              (i64.const 63)))
+          (i32.wrap_i64 (i64.shr_s (local.get 1) (i64.const 34))))
+        (i32.const 10))))
+    ;; Not a magic number for any divisor.
+  (func (param i32) (result i32)
+    (i32.wrap_i64
+      (i64.shr_u
+        (i64.mul (i64.extend_i32_u (local.get 0)) (i64.const 12345))
+        (i64.const 35)))))
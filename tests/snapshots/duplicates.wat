@@ -0,0 +1,15 @@
+(module
+  (func (param i32) (result i32)
+    (i32.add (local.get 0) (i32.const 1)))
+  (func (param i32) (result i32)
+    (i32.add (local.get 0) (i32.const 2)))
+  (func (param i32) (result i32)
+    (i32.add (local.get 0) (i32.const 1)))
+  (func (param i64) (result i64)
+    (local.get 0))
+  (func (param i32) (result i32)
+    (i32.add (local.get 0) (i32.const 1)))
+  ;; Same body bytes as func 3, different type.
+  (func (param i32) (result i32)
+    (local.get 0))
+)
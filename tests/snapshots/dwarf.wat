@@ -0,0 +1,22 @@
+(module
+  (memory 1)
+  (func (param i32) (result i32)
+    (local i32)
+    (local.set 1 (i32.mul (local.get 0) (i32.const 2)))
+    (i32.store (local.get 0) (local.get 1))
+    (call 1 (local.get 1))
+    (local.get 1))
+  (func (param i32)
+    (drop (local.get 0)))
+  ;; A DWARF 4 line table for src/main.c lines 3-5 and src/util.c line 10,
+  ;; plus a sequence at address 0 for a function the linker dropped.
+  (@custom ".debug_line" (after code)
+    "\6c\00\00\00\04\00\2c\00\00\00\01\01\01\fb\0e\0d"
+    "\00\01\01\01\01\00\00\00\01\00\00\01"
+    "src\00\00"
+    "main.c\00\01\00\00"
+    "util.c\00\01\00\00\00"
+    "\00\05\02\00\00\00\00\03\e2\00\01\02\04\00\01\01"
+    "\00\05\02\05\00\00\00\03\02\01\02\07\03\01\01\02\07\03\01\01\02\07\00\01\01"
+    "\00\05\02\1c\00\00\00\04\02\03\09\01\02\04\00\01\01")
+)
@@ -0,0 +1,37 @@
+(module
+  (import "env" "f" (func $f (param i32) (result i32)))
+  (global $g (mut i32) (i32.const 0))
+  (memory 1)
+  ;; Two loads can be evaluated in either order, so both temps are read
+  ;; where they're used.
+  (func (param $p i32) (param $q i32) (param $c i32) (result i32)
+    (i32.load (local.get $p))
+    (i32.load (local.get $q))
+    (block (br_if 0 (local.get $c)))
+    (i32.sub))
+  ;; The call may write the memory the load reads, but both are folded
+  ;; since the load is still evaluated first.
+  (func (param $p i32) (param $q i32) (param $c i32)
+    (i32.load (local.get $q))
+    (call $f (local.get $p))
+    (block (br_if 0 (local.get $c)))
+    (i32.store))
+  ;; Likewise for a global read before a call.
+  (func (param $p i32) (param $c i32) (result i32)
+    (global.get $g)
+    (call $f (local.get $p))
+    (block (br_if 0 (local.get $c)))
+    (i32.add))
+  ;; A division may trap, and so can't move past a store.
+  (func (param $p i32) (param $q i32) (param $c i32)
+    (i32.div_u (local.get $p) (local.get $q))
+    (block (br_if 0 (local.get $c)))
+    (i32.store (i32.const 0) (i32.const 1))
+    (global.set $g))
+  ;; A temp read by a later statement, not the next one, stays.
+  (func (param $p i32) (param $c i32) (result i32)
+    (i32.load (local.get $p))
+    (block (br_if 0 (local.get $c)))
+    (global.set $g (i32.const 1))
+    (global.set $g (i32.const 2))
+    (return)))
@@ -0,0 +1,21 @@
+(module
+  (type $callback (func (param i32) (result i32)))
+  (table 2 funcref)
+  (elem (i32.const 0) $double)
+  (global $ready (mut i32) (i32.const 0))
+  (func $unused (param i32) (result i32)
+    (i32.mul (local.get 0) (local.get 0)))
+  (func $double (param i32) (result i32)
+    (i32.add (local.get 0) (local.get 0)))
+  (func $main (result i32)
+    (call_indirect (type $callback) (i32.const 21) (i32.const 0)))
+  (func (export "_start")
+    (call $__wasm_call_ctors)
+    (drop (call $main)))
+  ;; Only reachable through its debug name.
+  (func $__wasm_call_ctors
+    (global.set $ready (i32.const 1)))
+  (func $init
+    (nop))
+  (start $init)
+)
@@ -0,0 +1,31 @@
+(module
+  (tag $error (param i32))
+  (tag $empty)
+  (import "env" "may_throw" (func $may_throw (param i32)))
+  (func (param i32)
+    (throw $error (local.get 0)))
+  (func (param i32) (result i32)
+    (block $caught (result i32)
+      (try_table (catch $error $caught)
+        (call $may_throw (local.get 0)))
+      (i32.const 0)))
+  (func (param i32) (result i32)
+    (block $other
+      (block $caught (result i32)
+        (try_table (result i32) (catch $error $caught) (catch_all $other)
+          (call $may_throw (local.get 0))
+          (i32.const 1)))
+      (return (i32.add (i32.const 100))))
+    (i32.const -1))
+  (func (param i32)
+    (block $handler (result exnref)
+      (try_table (catch_all_ref $handler)
+        (call $may_throw (local.get 0))
+        (throw $empty))
+      (return))
+    (throw_ref))
+  (func (param i32) (result i32 exnref)
+    (try_table (catch_ref $error 0)
+      (call $may_throw (local.get 0)))
+    (unreachable))
+)
@@ -0,0 +1,30 @@
+(module
+  (type $binop (func (param i32 i32) (result i32)))
+  (import "env" "log" (func $log (param i32)))
+  (table $ops 3 funcref)
+  (table $callbacks (export "callbacks") 1 funcref)
+  (elem (table $ops) (i32.const 0) func $add $sub)
+  (elem (table $callbacks) (i32.const 0) func $add)
+  ;; Never written.
+  (global $verbose (mut i32) (i32.const 1))
+  ;; Only written by a function nothing calls.
+  (global $scale (mut i32) (i32.const 3))
+  ;; Written by an export.
+  (global $count (mut i32) (i32.const 0))
+  ;; The host may write it.
+  (global $limit (export "limit") (mut i32) (i32.const 100))
+  (func $add (type $binop)
+    (i32.add (local.get 0) (local.get 1)))
+  (func $sub (type $binop)
+    (i32.sub (local.get 0) (local.get 1)))
+  (func $unused
+    (global.set $scale (i32.const 4)))
+  (func (export "run") (param i32) (result i32)
+    (global.set $count (i32.add (global.get $count) (i32.const 1)))
+    (if (global.get $verbose)
+      (then (call $log (global.get $limit))))
+    (i32.add
+      (call_indirect $ops (type $binop)
+        (local.get 0) (global.get $scale) (i32.const 1))
+      (call_indirect $callbacks (type $binop)
+        (local.get 0) (i32.const 2) (i32.const 0)))))
@@ -0,0 +1,30 @@
+(module
+  (type $unop (func (param i32) (result i32)))
+  (type $pair (func (param i32) (result i32 i32)))
+  (type $sink (func (param i32)))
+  (func $double (type $unop)
+    (i32.add (local.get 0) (local.get 0)))
+  (elem declare func $double)
+  (func (param (ref $unop) i32) (result i32)
+    (call_ref $unop (local.get 1) (local.get 0)))
+  (func (param (ref null $unop)) (result i32)
+    (block $null (result i32)
+      (i32.const -1)
+      (br_on_null $null (local.get 0))
+      (call_ref $unop)))
+  (func (param (ref null $unop)) (result i32)
+    (i32.const 3)
+    (block $some (result (ref $unop))
+      (br_on_non_null $some (local.get 0))
+      (return (i32.const 0)))
+    (call_ref $unop))
+  (func (param (ref null $pair) (ref $sink))
+    (call_ref $sink
+      (i32.add (call_ref $pair (i32.const 1) (ref.as_non_null (local.get 0))))
+      (local.get 1)))
+  ;; A non-nullable local has no default value, so it has to be set before
+  ;; it's read.
+  (func (result i32)
+    (local $f (ref $unop))
+    (local.set $f (ref.func $double))
+    (call_ref $unop (i32.const 21) (local.get $f))))
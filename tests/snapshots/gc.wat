@@ -0,0 +1,41 @@
+(module
+  (type $point (struct (field $x (mut i32)) (field $y (mut i32))))
+  (type $bytes (array (mut i8)))
+  (type $refs (array (mut anyref)))
+  (data $hello "hello")
+  (func (param i32 i32) (result (ref $point))
+    (struct.new $point (local.get 0) (local.get 1)))
+  (func (param (ref null $point)) (result i32)
+    (i32.add
+      (struct.get $point $x (local.get 0))
+      (struct.get $point $y (local.get 0))))
+  (func (param (ref $point) i32)
+    (struct.set $point $y (local.get 0) (local.get 1))
+    (struct.set $point $x (struct.new_default $point) (i32.const 0)))
+  (func (param i32) (result (ref $bytes))
+    (local (ref null $bytes))
+    (local.set 1 (array.new $bytes (i32.const 0) (local.get 0)))
+    (array.set $bytes (local.get 1) (i32.const 0) (i32.const 255))
+    (array.fill $bytes (local.get 1) (i32.const 1) (i32.const 7) (i32.const 2))
+    (array.init_data $bytes $hello (local.get 1) (i32.const 0) (i32.const 0) (i32.const 5))
+    (ref.as_non_null (local.get 1)))
+  (func (param (ref $bytes) (ref $bytes)) (result i32)
+    (array.copy $bytes $bytes
+      (local.get 0) (i32.const 0) (local.get 1) (i32.const 0) (array.len (local.get 1)))
+    (i32.add
+      (array.get_s $bytes (local.get 0) (i32.const 0))
+      (array.get_u $bytes (local.get 1) (i32.const 1))))
+  (func (result (ref $refs))
+    (array.new_fixed $refs 3
+      (ref.i31 (i32.const 1)) (ref.null none) (array.new_default $bytes (i32.const 4))))
+  (func (result (ref $bytes))
+    (array.new_data $bytes $hello (i32.const 1) (i32.const 3)))
+  (func (param anyref) (result i32)
+    (if (result i32) (ref.test (ref $point) (local.get 0))
+      (then
+        (struct.get $point $x (ref.cast (ref $point) (local.get 0))))
+      (else
+        (i31.get_s (ref.cast (ref i31) (local.get 0))))))
+  (func (param eqref eqref) (result i32)
+    (ref.eq (local.get 0) (local.get 1)))
+)
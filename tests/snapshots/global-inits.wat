@@ -0,0 +1,14 @@
+(module
+  (import "env" "__memory_base" (global $memory_base i32))
+  (import "env" "flags" (global i64))
+  ;; Extended constant expressions are folded where they can be.
+  (global $folded i32 (i32.mul (i32.const 4) (i32.const 256)))
+  (global $end i32 (i32.add (global.get $memory_base) (i32.sub (i32.const 80) (i32.const 16))))
+  (global $mask i64 (i64.sub (global.get 1) (i64.const 1)))
+  (global $handler (mut funcref) (ref.func $handle))
+  (global $none (mut externref) (ref.null extern))
+  (global $splat v128 (v128.const i32x4 1 2 3 4))
+  (func $handle (result i32)
+    (i32.add (global.get $end) (global.get $folded)))
+  (elem declare func $handle)
+)
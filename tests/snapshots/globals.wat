@@ -0,0 +1,33 @@
+(module
+  (import "env" "flag" (global $flag (mut i32)))
+  (memory 1)
+  (global $sp (mut i32) (i32.const 1024))
+  (global $const i32 (i32.const 7))
+  (global $counter (mut i64) (i64.const 0))
+  (global $unused (mut f32) (f32.const 0))
+  ;; Shadow stack frame: decrement on entry, increment before returning.
+  (func (param i32) (result i32)
+    (local i32)
+    (local.set 1 (i32.sub (global.get $sp) (i32.const 16)))
+    (global.set $sp (local.get 1))
+    (i32.store (local.get 1) (local.get 0))
+    (if (local.get 0)
+      (then
+        (i32.store (local.get 1) (global.get $const))))
+    (global.set $sp (i32.add (local.get 1) (i32.const 16)))
+    (local.get 1))
+  ;; Save and restore.
+  (func
+    (local i32)
+    (local.set 0 (global.get $flag))
+    (global.set $flag (i32.const 1))
+    (call 2)
+    (global.set $flag (local.get 0)))
+  ;; Plain counter, never restored.
+  (func
+    (global.set $counter (i64.add (global.get $counter) (i64.const 1)))
+    (drop (global.get $flag)))
+  ;; Named, so the report lists it by name.
+  (func $reset
+    (global.set $counter (i64.const 0)))
+)
@@ -0,0 +1,14 @@
+(module
+  (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+  (import "./app_bg.js" "__wbg_log_0b7d" (func (param i32)))
+  ;; The same import twice, so the second is printed by index.
+  (import "env" "abort" (func))
+  (import "env" "abort" (func))
+  (table 1 funcref)
+  (func (param i32) (result i32)
+    (call 1 (local.get 0))
+    (table.set (i32.const 0) (ref.func 2))
+    (if (i32.eqz (local.get 0)) (then (call 3)))
+    (call 0 (i32.const 1) (local.get 0) (i32.const 1) (i32.const 8)))
+  (elem declare func 2)
+)
@@ -0,0 +1,26 @@
+(module
+  (type $unop (func (param i32) (result i32)))
+  (type $binop (func (param i32 i32) (result i32)))
+  (table $ops 4 funcref)
+  (table $handlers 2 funcref)
+  (elem (table $ops) (i32.const 0) func $neg $not $add)
+  (elem (table $handlers) (i32.const 0) func $neg)
+  (func $neg (type $unop)
+    (i32.sub (i32.const 0) (local.get 0)))
+  (func $not (type $unop)
+    (i32.eqz (local.get 0)))
+  (func $add (type $binop)
+    (i32.add (local.get 0) (local.get 1)))
+  ;; A constant index into a table nothing writes.
+  (func (param i32) (result i32)
+    (call_indirect $ops (type $unop) (local.get 0) (i32.const 1)))
+  ;; The slot holds a function of another type, so the call traps.
+  (func (param i32) (result i32)
+    (call_indirect $ops (type $unop) (local.get 0) (i32.const 2)))
+  ;; Any function of the type may be called.
+  (func (param i32 i32) (result i32)
+    (call_indirect $ops (type $unop) (local.get 0) (local.get 1)))
+  ;; A table that's written, so its slots can change.
+  (func (param i32) (result i32)
+    (table.set $handlers (i32.const 1) (ref.func $not))
+    (call_indirect $handlers (type $unop) (local.get 0) (i32.const 0))))
@@ -0,0 +1,29 @@
+(module
+  (memory 1)
+  ;; Masks print in hex, shift amounts and counts in decimal.
+  (func (param $x i32) (result i32)
+    (i32.or
+      (i32.and (i32.shr_u (local.get $x) (i32.const 8)) (i32.const 255))
+      (i32.and (local.get $x) (i32.const 0xff00))))
+  (func (param $x i32) (result i32)
+    (i32.xor (local.get $x) (i32.const -16)))
+  (func (param $x i64) (result i64)
+    (i64.and (local.get $x) (i64.const 0x7fffffffffffffff)))
+  ;; Powers of two and round hex numbers look like bits, anything else
+  ;; doesn't.
+  (func (param $x i32) (result i32)
+    (i32.add
+      (i32.mul (local.get $x) (i32.const 1000))
+      (i32.mul (local.get $x) (i32.const 0x10000))))
+  (func (param $x i32) (result i32)
+    (i32.add
+      (i32.add (local.get $x) (i32.const 0x12000))
+      (i32.add (local.get $x) (i32.const -65536))))
+  (func (param $x i64) (result i64)
+    (i64.add
+      (i64.mul (local.get $x) (i64.const 1000000))
+      (i64.mul (local.get $x) (i64.const -1000))))
+  ;; Constant addresses print in hex.
+  (func
+    (i32.store (i32.const 1024) (i32.load (i32.const 8))))
+)
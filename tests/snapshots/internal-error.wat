@@ -0,0 +1,19 @@
+(module
+  (memory 1 1 shared)
+  (func $pair (result i32 i32)
+    i32.const 1
+    i32.const 2
+  )
+  ;; Atomics aren't supported yet.
+  (func $sum (result i32)
+    i32.const 0
+    i32.atomic.load
+    i32.const 1
+    i32.add
+  )
+  (func $after (param i32) (result i32)
+    local.get 0
+    i32.const 1
+    i32.add
+  )
+)
@@ -0,0 +1,10 @@
+(module
+  (import "math" "add" (func $add (param i32 i32) (result i32)))
+  (import "math" "negate" (func $negate (param i32) (result i32)))
+  ;; Not exported by math, so left unresolved.
+  (import "math" "abs" (func $abs (param i32) (result i32)))
+  (import "env" "log" (func $log (param i32)))
+  (func (param i32) (result i32)
+    (call $log (local.get 0))
+    (call $add (call $negate (local.get 0)) (call $abs (local.get 0))))
+)
@@ -0,0 +1,6 @@
+(module
+  (func $add_i32 (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1)))
+  (func (export "negate") (param i32) (result i32)
+    (i32.sub (i32.const 0) (local.get 0)))
+)
@@ -0,0 +1,16 @@
+(module
+  (memory 1)
+  ;; Sums a length-prefixed array of i32s: `$p` is an address, `$n` the
+  ;; bound the counter `$i` runs up to, and `$sum` just a number.
+  (func (param $list i32) (result i32)
+    (local $p i32) (local $n i32) (local $i i32) (local $sum i32)
+    (local.set $n (i32.load (local.get $list)))
+    (local.set $p (i32.add (local.get $list) (i32.const 4)))
+    (block $done
+      (loop $loop
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (local.set $sum (i32.add (local.get $sum) (i32.load (local.get $p))))
+        (local.set $p (i32.add (local.get $p) (i32.const 4)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $loop)))
+    (local.get $sum)))
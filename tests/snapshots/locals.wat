@@ -0,0 +1,34 @@
+(module
+  (import "env" "f" (func $f (param i32) (result i32)))
+  (memory 1)
+  ;; Sums `len` bytes from `p`. Local 5 is never used, and locals 3 and 4
+  ;; are never live at the same time.
+  (func (param $p i32) (param $len i32) (result i32)
+    (local $i i32) (local $x i32) (local $y i32) (local $unused i32)
+    (local $sum i32) (local $q i32)
+    (local.set $q (local.get $p))
+    (block $done
+      (loop $loop
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (local.set $x (i32.load8_u (i32.add (local.get $q) (local.get $i))))
+        (local.set $sum (i32.add (local.get $sum) (call $f (local.get $x))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $loop)))
+    (local.set $y (i32.mul (local.get $sum) (i32.const 3)))
+    (call $f (local.get $y)))
+  ;; A local read before it's written holds zero, so it can't share with
+  ;; one that's already been written.
+  (func (param i32) (result i32)
+    (local i32 i32 f64)
+    (local.set 1 (call $f (local.get 0)))
+    (drop (call $f (local.get 1)))
+    (local.set 3 (f64.const 1))
+    (i32.add (local.get 2) (i32.trunc_f64_s (local.get 3))))
+  ;; Local 2 is a copy of local 1, which isn't used after, so they share
+  ;; and the copy goes away.
+  (func (param i32) (result i32)
+    (local i32 i32)
+    (local.set 1 (call $f (local.get 0)))
+    (local.set 2 (local.get 1))
+    (drop (call $f (local.get 2)))
+    (call $f (local.get 2))))
@@ -0,0 +1,42 @@
+(module
+  (memory 1)
+  ;; A counted loop tested at the top.
+  (func (param $n i32) (result i32)
+    (local $i i32) (local $sum i32)
+    (block $done
+      (loop $loop
+        (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+        (local.set $sum (i32.add (local.get $sum) (local.get $i)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $loop)))
+    (local.get $sum))
+  ;; A loop tested at the bottom, as compilers usually emit them.
+  (func (param $p i32) (param $n i32)
+    (loop $loop
+      (i32.store8 (local.get $p) (i32.const 0))
+      (local.set $p (i32.add (local.get $p) (i32.const 1)))
+      (br_if $loop (local.tee $n (i32.sub (local.get $n) (i32.const 1))))))
+  ;; Nested loops, the inner one with an if in its body.
+  (func (param $w i32) (param $h i32)
+    (local $x i32) (local $y i32)
+    (block $rows_done
+      (loop $rows
+        (br_if $rows_done (i32.ge_u (local.get $y) (local.get $h)))
+        (local.set $x (i32.const 0))
+        (loop $cols
+          (if (i32.and (local.get $x) (i32.const 1))
+            (then (i32.store (local.get $x) (local.get $y)))
+            (else (i32.store (local.get $y) (local.get $x))))
+          (local.set $x (i32.add (local.get $x) (i32.const 1)))
+          (br_if $cols (i32.lt_u (local.get $x) (local.get $w))))
+        (local.set $y (i32.add (local.get $y) (i32.const 1)))
+        (br $rows))))
+  ;; A float comparison can't be flipped to negate it.
+  (func (param $x f64) (result f64)
+    (block $done
+      (loop $loop
+        (br_if $done (f64.lt (local.get $x) (f64.const 1)))
+        (local.set $x (f64.mul (local.get $x) (f64.const 0.5)))
+        (br $loop)))
+    (local.get $x))
+)
@@ -0,0 +1,18 @@
+(module
+  (import "env" "heap" (memory 1))
+  (memory $scratch 1)
+  (memory $wide i64 1)
+  ;; Copies a word from the imported memory to the second one.
+  (func (param i32)
+    (i32.store $scratch (local.get 0) (i32.load (local.get 0))))
+  (func (result i32)
+    (drop (memory.grow $scratch (i32.const 1)))
+    (i32.add (memory.size) (memory.size $scratch)))
+  (func (param i64) (result i64)
+    (i64.store $wide (local.get 0) (memory.grow $wide (i64.const 2)))
+    (i64.load8_u $wide (local.get 0)))
+  ;; Offsets are added to the index in any memory.
+  (func (param i32) (result i32)
+    (i32.store $scratch offset=16 (i32.const 32) (i32.const 1))
+    (i32.load $scratch offset=8 (local.get 0)))
+)
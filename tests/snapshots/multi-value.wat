@@ -0,0 +1,22 @@
+(module
+  (func $pair (param i32) (result i32 i64)
+    (local.get 0) (i64.const 7))
+  (func (param i32) (result i64)
+    (local i64)
+    (block (result i32 i64)
+      (local.get 0)
+      (i64.const 1)
+      (br_if 0 (local.get 0))
+      (drop) (drop)
+      (call $pair (i32.const 3)))
+    (local.set 1)
+    (drop)
+    (local.get 1))
+  (func (param i32) (result i32 i64)
+    (if (result i32 i64) (local.get 0)
+      (then (call $pair (i32.const 1)))
+      (else (i32.const 2) (i64.const 3))))
+  (func (result i32)
+    (call $pair (i32.const 5))
+    (drop))
+)
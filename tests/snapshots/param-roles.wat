@@ -0,0 +1,28 @@
+(module
+  (import "env" "write" (func $write (param i32 i32)))
+  (memory 1)
+  ;; `$n` bounds the loop over `$p`, so it's its length.
+  (func (param $p i32) (param $n i32) (result i32)
+    (local $i i32) (local $sum i32)
+    (block $done
+      (loop $loop
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (local.set $sum
+          (i32.add (local.get $sum) (i32.load8_u (i32.add (local.get $p) (local.get $i)))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $loop)))
+    (local.get $sum))
+  ;; `$n` is passed along with `$p`, so it's its length too.
+  (func (param $p i32) (param $n i32)
+    (i32.store8 (local.get $p) (i32.const 0))
+    (call $write (local.get $p) (local.get $n)))
+  ;; The integer after the pointer is a value stored through it, not a
+  ;; length.
+  (func (param $p i32) (param $x i32) (result i32)
+    (local $old i32)
+    (local.set $old (i32.load (local.get $p)))
+    (i32.store (local.get $p) (local.get $x))
+    (local.get $old))
+  ;; Nor is a divisor.
+  (func (param $p i32) (param $d i32) (result i32)
+    (i32.div_u (i32.load (local.get $p)) (local.get $d))))
@@ -0,0 +1,23 @@
+(module
+  (table $funcs 2 funcref)
+  (table $externs 1 externref)
+  (elem declare func 0)
+  (func (result funcref)
+    (ref.func 0))
+  (func (param externref) (result i32)
+    (ref.is_null (local.get 0)))
+  (func (param i32) (result externref)
+    (local externref)
+    (local.set 1 (ref.null extern))
+    (select (result externref)
+      (local.get 1) (table.get $externs (i32.const 0)) (local.get 0)))
+  (func (param i32 funcref)
+    (table.set $funcs (local.get 0) (local.get 1))
+    (table.fill $funcs (i32.const 0) (ref.null func) (table.size $funcs)))
+  (func (param externref) (result i32)
+    (table.grow $externs (local.get 0) (i32.const 4)))
+  ;; The operands' types are subtypes of the select's result type.
+  (func (param i32) (result funcref)
+    (select (result funcref) (ref.func 0) (ref.null func) (local.get 0))
+    (block (br_if 0 (local.get 0))))
+)
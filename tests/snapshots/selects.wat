@@ -0,0 +1,41 @@
+(module
+  (import "env" "f" (func $f (result i32)))
+  (global $g (mut i32) (i32.const 0))
+  ;; Branchless code picking one of five values.
+  (func (param i32) (result i32)
+    (local i32)
+    (local.set 1
+      (select
+        (i32.const 10)
+        (select
+          (i32.const 20)
+          (select
+            (i32.const 30)
+            (select (i32.const 40) (i32.const 50) (i32.eq (local.get 0) (i32.const 4)))
+            (i32.eq (local.get 0) (i32.const 3)))
+          (i32.eq (local.get 0) (i32.const 2)))
+        (i32.eq (local.get 0) (i32.const 1))))
+    (local.get 1))
+  ;; Selects on both sides, and under an if.
+  (func (param i32 i32)
+    (if (local.get 1)
+      (then
+        (global.set $g
+          (select
+            (select (local.get 0) (i32.const 1) (local.get 1))
+            (select (i32.const 2) (i32.const 3) (local.get 0))
+            (call $f))))))
+  ;; Too shallow to flatten.
+  (func (param i32) (result i32)
+    (local i32)
+    (local.set 1 (select (i32.const 1) (i32.const 2) (local.get 0)))
+    (local.get 1))
+  ;; The call has to happen whichever value is picked.
+  (func (param i32) (result i32)
+    (local i32)
+    (local.set 1
+      (select
+        (call $f)
+        (select (i32.const 1) (i32.const 2) (local.get 0))
+        (local.get 0)))
+    (local.get 1)))
@@ -0,0 +1,35 @@
+(module
+  (memory 1)
+  ;; `if (a && b)`, as compilers emit it: skip the body if either is false.
+  (func (param $a i32) (param $b i32)
+    (block $skip
+      (br_if $skip (i32.eqz (local.get $a)))
+      (br_if $skip (i32.eqz (local.get $b)))
+      (i32.store (i32.const 0) (i32.const 1))))
+  ;; `if (a || b)`: either one jumps into the body.
+  (func (param $a i32) (param $b i32)
+    (block $skip
+      (block $body
+        (br_if $body (local.get $a))
+        (br_if $skip (i32.eqz (local.get $b))))
+      (i32.store (i32.const 0) (i32.const 2))))
+  ;; A chain of three, the last one a call that only happens if the others
+  ;; pass.
+  (func $check (param i32) (result i32) (local.get 0))
+  (func (param $p i32) (param $n i32) (result i32)
+    (block $fail
+      (br_if $fail (i32.eqz (local.get $p)))
+      (br_if $fail (i32.le_s (local.get $n) (i32.const 0)))
+      (br_if $fail (i32.eqz (call $check (local.get $n))))
+      (return (i32.const 1)))
+    (i32.const 0))
+  ;; `while (i < n && p[i])`.
+  (func (param $p i32) (param $n i32) (result i32)
+    (local $i i32)
+    (block $done
+      (loop $loop
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (br_if $done (i32.eqz (i32.load8_u (i32.add (local.get $p) (local.get $i)))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $loop)))
+    (local.get $i)))
@@ -0,0 +1,19 @@
+(module
+  ;; Only ever compared as unsigned, so declared `u32` and compared with a
+  ;; plain `<`.
+  (func (param $i i32) (param $n i32) (result i32)
+    (i32.lt_u (local.get $i) (local.get $n)))
+  ;; Signed division and shift of the same local: plain operators, `i32`.
+  (func (param $x i32) (result i32)
+    (i32.add
+      (i32.div_s (local.get $x) (i32.const 3))
+      (i32.shr_s (local.get $x) (i32.const 31))))
+  ;; Treated as both, so each operator keeps its suffix.
+  (func (param $x i32) (param $y i32) (result i32)
+    (i32.add
+      (i32.lt_s (local.get $x) (local.get $y))
+      (i32.lt_u (local.get $x) (i32.const 10))))
+  ;; Nothing but a constant to go on, so the suffix stays.
+  (func (param $p i32) (result i64)
+    (i64.shr_u (i64.const -1) (i64.extend_i32_u (local.get $p))))
+)
@@ -0,0 +1,28 @@
+(module
+  (memory 1)
+  (func (param i32 v128) (result v128)
+    (i32x4.add
+      (v128.load (local.get 0))
+      (i32x4.splat (i32.const 3))))
+  (func (param v128 v128) (result i32)
+    (i32.add
+      (i32x4.extract_lane 2 (local.get 0))
+      (i8x16.extract_lane_s 15 (local.get 1))))
+  (func (param v128 f32) (result v128)
+    (f32x4.replace_lane 1 (local.get 0) (local.get 1)))
+  (func (param v128 v128) (result v128)
+    (i8x16.shuffle 0 17 2 19 4 21 6 23 8 25 10 27 12 29 14 31
+      (local.get 0) (local.get 1)))
+  (func (param v128 v128 v128) (result v128)
+    (v128.bitselect (local.get 0) (local.get 1) (local.get 2)))
+  (func (result v128)
+    (v128.const i32x4 1 2 3 0xffffffff))
+  (func (param v128) (result i32)
+    (i32x4.all_true (i16x8.extend_low_i8x16_s (local.get 0))))
+  (func (param i32 v128)
+    (v128.store (local.get 0) (i64x2.shl (local.get 1) (i32.const 1)))
+    (v128.store16_lane 3 (local.get 0) (local.get 1)))
+  (func (param i32 v128) (result v128)
+    (v128.load32_lane 2 (local.get 0)
+      (v128.load8x8_u (local.get 0))))
+)
@@ -0,0 +1,12 @@
+(module
+  (memory 1)
+  (func (param i32) (result i32)
+    (local i32)
+    (local.set 1 (i32.mul (local.get 0) (i32.const 2)))
+    (i32.store (local.get 0) (local.get 1))
+    (call 1 (local.get 1))
+    (local.get 1))
+  (func (param i32)
+    (drop (local.get 0)))
+  (@custom "sourceMappingURL" (after code) "\13source-map.wasm.map")
+)
@@ -0,0 +1,31 @@
+(module
+  (memory 1)
+  ;; One local used for a length and then, unrelated, for a pointer.
+  (func (param $s i32) (result i32)
+    (local $x i32)
+    (local.set $x (i32.load (local.get $s)))
+    (i32.store (i32.const 0) (local.get $x))
+    (local.set $x (i32.load (i32.add (local.get $s) (i32.const 4))))
+    (i32.load (local.get $x)))
+  ;; Values that meet after an if/else stay one local, and a param that's
+  ;; overwritten keeps its name for the value it's called with.
+  (func (param $a i32) (result i32)
+    (local $x i32)
+    (if (local.get $a)
+      (then (local.set $x (i32.const 1)))
+      (else (local.set $x (i32.const 2))))
+    (i32.store (i32.const 0) (local.get $x))
+    (local.set $a (i32.const 7))
+    (i32.add (local.get $a) (local.get $x)))
+  ;; A loop counter is one value all the way round, and the local reused
+  ;; after the loop is another.
+  (func (param $n i32) (result i32)
+    (local $i i32)
+    (block $done
+      (loop $loop
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (i32.store (local.get $i) (i32.const 0))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $loop)))
+    (local.set $i (i32.load (i32.const 8)))
+    (i32.mul (local.get $i) (local.get $i))))
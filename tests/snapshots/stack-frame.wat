@@ -0,0 +1,27 @@
+(module
+  (import "env" "g" (func $g (param i32)))
+  (memory 1)
+  ;; No name to go by, but used like a stack pointer.
+  (global (mut i32) (i32.const 65536))
+  ;; A frame of 32 bytes: two slots written and read back, one through a
+  ;; load offset, and the address of one passed to a call.
+  (func (param i32) (result i32)
+    (local i32)
+    (local.set 1 (i32.sub (global.get 0) (i32.const 32)))
+    (global.set 0 (local.get 1))
+    (i32.store offset=8 (local.get 1) (local.get 0))
+    (i64.store (i32.add (local.get 1) (i32.const 16)) (i64.const 5))
+    (call $g (i32.add (local.get 1) (i32.const 8)))
+    (i32.add
+      (i32.load (i32.add (local.get 1) (i32.const 8)))
+      (i32.wrap_i64 (i64.load offset=16 (local.get 1))))
+    (global.set 0 (i32.add (local.get 1) (i32.const 32))))
+  ;; The caller's frame is out of reach.
+  (func (result i32)
+    (local i32)
+    (local.set 0 (i32.sub (global.get 0) (i32.const 16)))
+    (global.set 0 (local.get 0))
+    (i32.store offset=4 (local.get 0) (i32.const 1))
+    (call $g (local.get 0))
+    (global.set 0 (i32.add (local.get 0) (i32.const 16)))
+    (i32.load (i32.add (local.get 0) (i32.const 16)))))
@@ -0,0 +1,15 @@
+(module
+  (import "env" "log" (func $log (param i32 i32)))
+  (memory 1)
+  (data (i32.const 1024) "error: %s\n\00ok\00\01\02\00")
+  (data (i32.const 2048) "not terminated")
+  (data (i32.const 4096) "*/ \00")
+  (func (param $p i32)
+    ;; A format string and a string that's the tail of another one.
+    (call $log (i32.const 1024) (i32.const 1031))
+    (call $log (i32.const 1035) (local.get $p))
+    ;; Stored as a pointer.
+    (i32.store (local.get $p) (i32.const 1035))
+    ;; Not text, and not NUL-terminated.
+    (call $log (i32.const 1038) (i32.const 2048))
+    (call $log (i32.const 4096) (i32.const 0))))
@@ -0,0 +1,52 @@
+(module
+  (memory 1)
+  ;; Early returns, with the rest of the function after each.
+  (func (param $a i32) (param $b i32) (result i32)
+    (if (i32.eqz (local.get $a))
+      (then (return (i32.const -1))))
+    (if (i32.eqz (local.get $b))
+      (then (return (i32.const -2))))
+    (i32.div_u (local.get $a) (local.get $b)))
+  ;; A search loop with two ways out, one of which skips the code after it.
+  (func (param $p i32) (param $n i32) (result i32)
+    (block $not_found
+      (block $found
+        (loop $loop
+          (br_if $not_found (i32.eqz (local.get $n)))
+          (br_if $found (i32.eqz (i32.load8_u (local.get $p))))
+          (local.set $p (i32.add (local.get $p) (i32.const 1)))
+          (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+          (br $loop)))
+      (i32.store (i32.const 0) (local.get $p)))
+    (local.get $n))
+  ;; A switch whose cases fall through to the next.
+  (func (param $x i32)
+    (block $end
+      (block $two
+        (block $one
+          (block $zero
+            (br_table $zero $one $two $end (local.get $x)))
+          (i32.store (i32.const 0) (i32.const 10)))
+        (i32.store (i32.const 4) (i32.const 11)))
+      (i32.store (i32.const 8) (i32.const 12))))
+  ;; A loop left from its middle that skips to the next iteration early.
+  (func (param $n i32)
+    (local $i i32)
+    (loop $loop
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br_if $loop (i32.and (local.get $i) (i32.const 1)))
+      (i32.store (local.get $i) (local.get $n))
+      (br_if $loop (i32.lt_u (local.get $i) (local.get $n)))))
+  ;; Nested loops, where the inner one leaves both at once.
+  (func (param $n i32) (result i32)
+    (local $i i32) (local $j i32)
+    (block $done
+      (loop $outer
+        (local.set $j (i32.const 0))
+        (loop $inner
+          (br_if $done (i32.eq (i32.mul (local.get $i) (local.get $j)) (local.get $n)))
+          (local.set $j (i32.add (local.get $j) (i32.const 1)))
+          (br_if $inner (i32.lt_u (local.get $j) (local.get $n))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $outer)))
+    (local.get $i)))
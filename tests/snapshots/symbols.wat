@@ -0,0 +1,14 @@
+(module
+  (import "env" "abort" (func))
+  (global (mut i32) (i32.const 1024))
+  (global (mut i32) (i32.const 0))
+  (memory 1)
+  (func (param i32 i32) (result i32)
+    (local i32)
+    (local.set 2 (i32.load (local.get 0)))
+    (global.set 1 (i32.add (global.get 1) (i32.const 1)))
+    (i32.add (local.get 2) (local.get 1)))
+  (func (export "run") (result i32)
+    (if (i32.eqz (global.get 0)) (then (call 0)))
+    (call 1 (global.get 0) (i32.const 4)))
+)
@@ -0,0 +1,27 @@
+(module
+  (import "env" "f" (func $f (param i32) (result i32)))
+  (import "env" "g" (func $g))
+  (memory 1)
+  ;; A value on the stack when a loop starts is spilled to a temp, which
+  ;; the return reads straight after.
+  (func (param $p i32) (result i32)
+    (i32.load (local.get $p))
+    (loop))
+  ;; Spilled before a `br_if` whose targets turn out to be the same, and
+  ;; read by the store that follows once the blocks are merged.
+  (func (param $p i32) (param $c i32)
+    (call $f (local.get $p))
+    (block (br_if 0 (local.get $c)))
+    (i32.store (i32.const 0) (i32.const 1))
+    (drop))
+  (func (param $p i32) (param $c i32)
+    (i32.const 8)
+    (call $f (local.get $p))
+    (block (br_if 0 (local.get $c)))
+    (i32.store))
+  ;; A value spilled before a call can't move past it.
+  (func (param $p i32) (result i32)
+    (i32.load (local.get $p))
+    (call $g)
+    (i32.const 1)
+    (i32.add)))
@@ -0,0 +1,24 @@
+(module
+  (import "env" "check" (func $check (result i32)))
+  (global $g (mut i32) (i32.const 0))
+  ;; Both ways out of the `br_if` lead to the same place.
+  (func (param i32)
+    (block $done
+      (br_if $done (local.get 0)))
+    (block $done
+      (br_if $done (call $check)))
+    (global.set $g (i32.const 1)))
+  ;; Every target of the `br_table` is the same.
+  (func (param i32) (result i32)
+    (block $out
+      (br_table $out $out $out (local.get 0)))
+    (global.set $g (i32.const 2))
+    (i32.const 7))
+  ;; Branches to the function's end return directly.
+  (func (param i32) (result i32)
+    (if (local.get 0)
+      (then (return (call $check))))
+    (block $b (result i32)
+      (br_if $b (i32.const 1) (local.get 0))
+      (drop)
+      (i32.const 2))))
@@ -0,0 +1,38 @@
+(module
+    (type $t (func (param i32) (result i32)))
+    (table 1 funcref)
+    (memory 1)
+    (global $g (mut i32) (i32.const 0))
+    (func $f (export "f") (export "say \"hi\"\n") (param i32) (result i32)
+      local.get 0
+    )
+    (func (param i32 f32) (result i32)
+      (local f32)
+      block
+        block
+          local.get 0
+          br_table 0 1 0
+        end
+        i32.const 8
+        global.get $g
+        i32.const 3
+        i32.mul
+        i32.store
+      end
+      local.get 1
+      f32.const -1.5
+      f32.min
+      local.set 2
+      i32.const 4
+      local.get 0
+      call_indirect (type $t)
+      drop
+      i32.const 1
+      i32.const 2
+      local.get 0
+      select
+      call $f
+      global.set $g
+      global.get $g
+    )
+)
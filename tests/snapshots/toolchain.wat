@@ -0,0 +1,10 @@
+(module
+  (@producers
+    (language "Rust" "1.78.0")
+    (processed-by "rustc" "1.78.0 (9b00956e5 2024-04-29)")
+    (processed-by "wasm-bindgen" "0.2.92"))
+  ;; Two used features and one marked as not used.
+  (@custom "target_features" "\03+\07simd128+\0bbulk-memory-\0fmutable-globals")
+  (func (export "answer") (result i32)
+    (i32.const 42))
+)
@@ -0,0 +1,16 @@
+(module
+  (import "env" "__memory_base" (global i32))
+  (import "env" "__table_base" (global i32))
+  (memory 1)
+  ;; No name to go by, but used like a stack pointer.
+  (global (mut i32) (i32.const 65536))
+  (global $count (mut i32) (i32.const 0))
+  (func (param i32) (result i32)
+    (local i32)
+    (local.set 1 (i32.sub (global.get 2) (i32.const 32)))
+    (global.set 2 (local.get 1))
+    (i32.store (local.get 1) (i32.add (global.get 0) (local.get 0)))
+    (global.set $count (i32.add (global.get $count) (i32.const 1)))
+    (global.set 2 (i32.add (local.get 1) (i32.const 32)))
+    (i32.add (global.get 1) (i32.load (local.get 1))))
+)